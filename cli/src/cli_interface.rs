@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{Args, Parser, Subcommand};
 
 #[allow(clippy::upper_case_acronyms)]
@@ -56,6 +58,15 @@ pub enum Commands {
         /// optional - The path to run the command in, default will be where the command is run.
         #[clap(long, short)]
         path: Option<String>,
+
+        /// optional - Run the project packed in this bundle instead of the path
+        #[arg(long)]
+        bundle: Option<String>,
+
+        /// optional - Age identity file to unpack the bundle with, defaults to the
+        /// RINDEXER_BUNDLE_IDENTITY environment variable
+        #[arg(long)]
+        identity: Option<String>,
     },
 
     /// Add elements such as contracts to the rindexer.yaml file.
@@ -117,6 +128,253 @@ pub enum Commands {
         #[clap(long, short)]
         path: Option<String>,
     },
+    /// Work with the streams configured on your contracts
+    ///
+    /// Example:
+    /// `rindexer streams replay --from-block <FROM_BLOCK> --to-block <TO_BLOCK>`
+    #[clap(name = "streams")]
+    Streams {
+        #[clap(subcommand)]
+        subcommand: StreamsSubcommands,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    #[cfg(feature = "csv")]
+    /// Imports events exported from another indexer into the postgres event tables
+    ///
+    /// The csv headers are matched to the event table columns, indexing then resumes after the
+    /// highest imported block. Parquet exports need converting to csv first.
+    ///
+    /// Example:
+    /// `rindexer import --contract-name <CONTRACT_NAME> --event-name <EVENT_NAME> --network
+    /// <NETWORK> --file <FILE>`
+    #[clap(name = "import")]
+    Import {
+        /// The contract the events belong to
+        #[arg(long)]
+        contract_name: String,
+
+        /// The event to import
+        #[arg(long)]
+        event_name: String,
+
+        /// The network the events were emitted on
+        #[arg(long)]
+        network: String,
+
+        /// The csv file to import
+        #[arg(long)]
+        file: String,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    /// Export or import a snapshot of the indexer postgres data
+    ///
+    /// Snapshots hold the event tables and the last synced blocks so a new environment can
+    /// start from them instead of resyncing. Needs `pg_dump` and `pg_restore` installed.
+    ///
+    /// Example:
+    /// `rindexer snapshot export --file <FILE>` or
+    /// `rindexer snapshot import --file <FILE>`
+    #[clap(name = "snapshot")]
+    Snapshot {
+        #[clap(subcommand)]
+        subcommand: SnapshotSubcommands,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    /// Manage the postgres indexes and relationships defined in the manifest
+    ///
+    /// Example:
+    /// `rindexer indexes apply`
+    #[clap(name = "indexes")]
+    Indexes {
+        #[clap(subcommand)]
+        subcommand: IndexesSubcommands,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    /// Benchmarks getLogs per provider, decoding and postgres inserts for the manifest
+    ///
+    /// Prints a report with recommended manifest settings, postgres inserts go into a scratch
+    /// table which is dropped afterwards.
+    ///
+    /// Example:
+    /// `rindexer bench --blocks 1000 --network <NETWORK>`
+    #[clap(name = "bench")]
+    Bench {
+        /// optional - How many of the latest blocks to fetch logs for, defaults to 1000
+        #[arg(long, default_value_t = 1000)]
+        blocks: u64,
+
+        /// optional - Only benchmark this network
+        #[arg(long)]
+        network: Option<String>,
+
+        /// optional - How many rows to insert with each insert strategy, defaults to 10000
+        #[arg(long, default_value_t = 10000)]
+        rows: usize,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    /// Runs the events quarantined after their handler kept failing through the handlers again
+    ///
+    /// Events are quarantined once `runtime.handler_max_attempts` is reached, replay them after
+    /// fixing the handler. Events which fail again stay quarantined.
+    ///
+    /// Example:
+    /// `rindexer replay-failed --contract-name <CONTRACT_NAME>`
+    #[clap(name = "replay-failed")]
+    ReplayFailed {
+        /// optional - Only replay this contract
+        #[arg(long)]
+        contract_name: Option<String>,
+
+        /// optional - Only replay this event
+        #[arg(long)]
+        event_name: Option<String>,
+
+        /// optional - Only replay this network
+        #[arg(long)]
+        network: Option<String>,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    /// Decodes the logs of a transaction with the manifest ABIs without writing anything
+    ///
+    /// Prints the table and columns each log would be written to, or why it would be skipped,
+    /// to debug manifest and ABI mismatches.
+    ///
+    /// Example:
+    /// `rindexer decode --tx <TX_HASH> --network <NETWORK>`
+    #[clap(name = "decode")]
+    Decode {
+        /// The transaction hash to decode the logs of
+        #[arg(long)]
+        tx: String,
+
+        /// The network the transaction is on
+        #[arg(long)]
+        network: String,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    /// Decodes the raw logs stored with `store_raw_logs` again with the current ABI
+    ///
+    /// Rewrites the decoded columns of the event in place after fixing an ABI mistake instead of
+    /// syncing the chain again. Apply any migration plan for the fixed ABI first.
+    ///
+    /// Example:
+    /// `rindexer redecode --contract <CONTRACT_NAME> --event <EVENT_NAME>`
+    #[clap(name = "redecode")]
+    Redecode {
+        /// The contract the event belongs to
+        #[arg(long)]
+        contract: String,
+
+        /// The event to decode again
+        #[arg(long)]
+        event: String,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    /// Checks the postgres tables for block ranges with missing rows and schedules their repair
+    ///
+    /// Logs of each event up to its last synced block are matched against the rows by their
+    /// transaction hash and log index, ranges with logs that have no row are scheduled in
+    /// rindexer_internal.repair_ranges. Each run carries on from where the last one got to.
+    /// `--repair` hands only the missing logs to the handlers, streams get just those events.
+    /// Rust projects verify with `storage.postgres.verify` as their handlers live in the project.
+    ///
+    /// Example:
+    /// `rindexer verify --network <NETWORK>` or `rindexer verify --repair`
+    #[clap(name = "verify")]
+    Verify {
+        /// optional - Only verify this contract
+        #[arg(long)]
+        contract_name: Option<String>,
+
+        /// optional - Only verify this event
+        #[arg(long)]
+        event_name: Option<String>,
+
+        /// optional - Only verify this network
+        #[arg(long)]
+        network: Option<String>,
+
+        /// optional - How many blocks each comparison against the chain covers, defaults to 10000
+        #[arg(long, default_value_t = 10000)]
+        window: u64,
+
+        /// optional - How many windows are compared at once, defaults to 4
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// optional - Verify from the start block again instead of from where the last verify got
+        /// to
+        #[arg(long)]
+        full: bool,
+
+        /// optional - Index the scheduled ranges again once verifying is done
+        #[arg(long)]
+        repair: bool,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    /// Tears the project down from postgres and optionally its streams
+    ///
+    /// Drops the indexes, relationships, views and schemas the project created and clears its
+    /// rows in rindexer_internal. Kafka and SNS topics are never touched.
+    ///
+    /// Example:
+    /// `rindexer down --dry-run` or `rindexer down --delete-streams`
+    #[clap(name = "down")]
+    Down {
+        /// optional - Also delete the RabbitMQ exchanges rindexer declared for the streams
+        #[arg(long)]
+        delete_streams: bool,
+
+        /// optional - Print what would be removed without changing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+
+    /// Packs the project files the manifest needs into one encrypted bundle or unpacks one
+    ///
+    /// Example:
+    /// `rindexer bundle pack --file prod.bundle --recipient age1...` or
+    /// `rindexer bundle unpack --file prod.bundle --out ./project`
+    #[clap(name = "bundle")]
+    Bundle {
+        #[clap(subcommand)]
+        subcommand: BundleSubcommands,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -145,8 +403,14 @@ pub enum StartSubcommands {
     /// Starts an indexer based on the rindexer.yaml file.
     ///
     /// Example:
-    /// `rindexer start indexer`
-    Indexer,
+    /// `rindexer start indexer` or `rindexer start indexer --apply-migrations`
+    Indexer {
+        #[clap(
+            long,
+            help = "Apply the generated migration plan if the database schema has drifted from the ABIs"
+        )]
+        apply_migrations: bool,
+    },
 
     /// Starts the GraphQL server based on the rindexer.yaml file.
     ///
@@ -168,6 +432,12 @@ pub enum StartSubcommands {
     All {
         #[clap(short, long, help = "Specify the port number for all services")]
         port: Option<String>,
+
+        #[clap(
+            long,
+            help = "Apply the generated migration plan if the database schema has drifted from the ABIs"
+        )]
+        apply_migrations: bool,
     },
 }
 
@@ -214,6 +484,47 @@ pub enum CodegenSubcommands {
         #[clap(long, help = "The graphql endpoint - defaults to localhost:3001")]
         endpoint: Option<String>,
     },
+
+    /// Generates fixture rows for integration tests from the logs of the latest blocks
+    ///
+    /// Every event table gets a JSON file with its column types and the rows rindexer would
+    /// write, load them into a temp schema with `rindexer::load_fixtures` in your tests.
+    ///
+    /// Example:
+    /// `rindexer codegen fixtures --network ethereum --blocks 500`
+    Fixtures {
+        #[clap(long, help = "Only record logs on this network")]
+        network: Option<String>,
+
+        #[clap(long, default_value = "1000", help = "How many of the latest blocks to record")]
+        blocks: u64,
+
+        #[clap(long, default_value = "100", help = "The most rows written per event table")]
+        max_rows: usize,
+
+        #[clap(
+            long,
+            default_value = "fixtures",
+            help = "The directory the fixtures are written to"
+        )]
+        output: PathBuf,
+    },
+
+    /// Generates dbt sources and staging models for every event table
+    ///
+    /// Columns are typed and documented from the ABI, copy the output into the models folder of
+    /// your dbt project.
+    ///
+    /// Example:
+    /// `rindexer codegen dbt --output analytics/models/rindexer`
+    Dbt {
+        #[clap(
+            long,
+            default_value = "dbt",
+            help = "The directory the sources and staging models are written to"
+        )]
+        output: PathBuf,
+    },
 }
 
 #[derive(Args, Debug)]
@@ -289,3 +600,113 @@ pub enum PhantomSubcommands {
         network: String,
     },
 }
+
+#[derive(Subcommand, Debug)]
+pub enum StreamsSubcommands {
+    /// Re-publishes indexed rows from postgres through the configured streams
+    ///
+    /// Useful to backfill a stream added after the data was indexed.
+    ///
+    /// Example:
+    /// `rindexer streams replay --from-block <FROM_BLOCK> --to-block <TO_BLOCK>`
+    #[clap(name = "replay")]
+    Replay {
+        /// The first block to replay
+        #[arg(long)]
+        from_block: u64,
+
+        /// The last block to replay
+        #[arg(long)]
+        to_block: u64,
+
+        /// optional - Only replay this contract
+        #[arg(long)]
+        contract_name: Option<String>,
+
+        /// optional - Only replay this network
+        #[arg(long)]
+        network: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotSubcommands {
+    /// Dumps the indexer tables into a compressed snapshot file
+    ///
+    /// Example:
+    /// `rindexer snapshot export --file <FILE>`
+    #[clap(name = "export")]
+    Export {
+        /// The snapshot file to write
+        #[arg(long)]
+        file: String,
+    },
+    /// Restores the indexer tables from a snapshot file, replacing any existing data
+    ///
+    /// Example:
+    /// `rindexer snapshot import --file <FILE>`
+    #[clap(name = "import")]
+    Import {
+        /// The snapshot file to restore
+        #[arg(long)]
+        file: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IndexesSubcommands {
+    /// Creates the indexes and relationships which have `apply: manual`
+    ///
+    /// Example:
+    /// `rindexer indexes apply`
+    #[clap(name = "apply")]
+    Apply,
+    /// Shows the build state of the managed indexes and the progress of running builds
+    ///
+    /// Example:
+    /// `rindexer indexes status`
+    #[clap(name = "status")]
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BundleSubcommands {
+    /// Encrypts the project into a bundle for the given age recipients or KMS key
+    ///
+    /// Example:
+    /// `rindexer bundle pack --file <FILE> --recipient <AGE_PUBLIC_KEY>`
+    #[clap(name = "pack")]
+    Pack {
+        /// The bundle file to write
+        #[arg(long)]
+        file: String,
+
+        /// Age public key which can unpack the bundle, can be given more than once
+        #[arg(long)]
+        recipient: Vec<String>,
+
+        /// AWS KMS key id or arn to encrypt the bundle with instead of age
+        #[cfg(feature = "kms")]
+        #[arg(long, conflicts_with = "recipient")]
+        kms_key_id: Option<String>,
+    },
+    /// Decrypts a bundle into a directory
+    ///
+    /// Example:
+    /// `rindexer bundle unpack --file <FILE> --out <DIR> --identity <AGE_IDENTITY_FILE>`
+    #[clap(name = "unpack")]
+    Unpack {
+        /// The bundle file to read
+        #[arg(long)]
+        file: String,
+
+        /// Directory to write the project to
+        #[arg(long)]
+        out: String,
+
+        /// optional - Age identity file, defaults to the RINDEXER_BUNDLE_IDENTITY environment
+        /// variable
+        #[arg(long)]
+        identity: Option<String>,
+    },
+}
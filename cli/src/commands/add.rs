@@ -1,3 +1,5 @@
+#![allow(clippy::unnecessary_unwrap)]
+
 use std::{borrow::Cow, fs, path::PathBuf, time::Duration};
 
 use ethers::{
@@ -169,6 +171,7 @@ pub async fn handle_add_contract_command(
 
         manifest.contracts.push(Contract {
             name: contract_name.into_owned(),
+            addresses: None,
             details: vec![ContractDetails::new_with_address(
                 network.to_string(),
                 ValueOrArray::<Address>::Value(address),
@@ -178,12 +181,32 @@ pub async fn handle_add_contract_command(
             )],
             abi: abi_path_relative,
             include_events: None,
+            exclude_events: None,
             index_event_in_order: None,
             dependency_events: None,
+            depends_on: None,
             reorg_safe_distance: None,
             generate_csv: None,
             streams: None,
             chat: None,
+            transforms: None,
+            price_enrichment: None,
+            anonymous_events: None,
+            overloaded_events: None,
+            rename_overloaded_events: false,
+            jsonb_storage: None,
+            event_networks: None,
+            event_conditions: None,
+            computed_columns: None,
+            checkpoint: None,
+            template: None,
+            priority: None,
+            lifecycle: None,
+            handler_concurrency: None,
+            enums: None,
+            typed_addresses: None,
+            table_settings: None,
+            store_raw_logs: None,
         });
 
         write_manifest(&manifest, &rindexer_yaml_path).map_err(|e| {
@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use rindexer::{
+    manifest::yaml::{read_manifest, YAML_CONFIG_NAME},
+    run_bench, BenchOptions,
+};
+
+use crate::console::{print_error_message, print_success_message};
+
+pub async fn handle_bench_command(
+    project_path: PathBuf,
+    blocks: u64,
+    network: &Option<String>,
+    rows: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    let options = BenchOptions { blocks, network: network.clone(), rows };
+    let report = run_bench(&project_path, &manifest, &options).await.map_err(|e| {
+        print_error_message(&format!("Could not run benchmark: {}", e));
+        e
+    })?;
+
+    print_success_message(&format!("\n\n{}\n", report));
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use rindexer::{pack_bundle, unpack_bundle, BundleEncryption};
+
+use crate::{
+    cli_interface::BundleSubcommands,
+    console::{print_error_message, print_success_message},
+};
+
+pub async fn handle_bundle_command(
+    project_path: PathBuf,
+    subcommand: &BundleSubcommands,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match subcommand {
+        BundleSubcommands::Pack {
+            file,
+            recipient,
+            #[cfg(feature = "kms")]
+            kms_key_id,
+        } => {
+            let encryption = BundleEncryption::Age { recipients: recipient.clone() };
+            #[cfg(feature = "kms")]
+            let encryption = match kms_key_id {
+                Some(key_id) => BundleEncryption::Kms { key_id: key_id.clone() },
+                None => encryption,
+            };
+            let file = project_path.join(file);
+            let files = pack_bundle(&project_path, &encryption, &file).await.map_err(|e| {
+                print_error_message(&format!("Could not pack bundle: {}", e));
+                e
+            })?;
+
+            print_success_message(&format!(
+                "\n\nSuccessfully packed {} into {}\n\n",
+                files.join(", "),
+                file.display()
+            ));
+        }
+        BundleSubcommands::Unpack { file, out, identity } => {
+            let file = project_path.join(file);
+            let out = project_path.join(out);
+            let identity = identity.as_ref().map(|identity| project_path.join(identity));
+            let files = unpack_bundle(&file, identity.as_deref(), &out).await.map_err(|e| {
+                print_error_message(&format!("Could not unpack bundle: {}", e));
+                e
+            })?;
+
+            print_success_message(&format!(
+                "\n\nSuccessfully unpacked {} into {}\n\n",
+                files.join(", "),
+                out.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
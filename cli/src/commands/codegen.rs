@@ -1,12 +1,13 @@
 use std::path::PathBuf;
 
 use rindexer::{
-    format_all_files_for_project, generate_graphql_queries,
+    format_all_files_for_project, generate_dbt_models, generate_fixtures, generate_graphql_queries,
     generator::build::{generate_rindexer_handlers, generate_rindexer_typings},
     manifest::{
         core::ProjectType,
         yaml::{read_manifest, YAML_CONFIG_NAME},
     },
+    GenerateFixturesOptions,
 };
 
 use crate::{
@@ -39,6 +40,47 @@ pub async fn handle_codegen_command(
         print_error_message(&format!("Could not read the rindexer.yaml file: {}", e));
         e
     })?;
+
+    if let CodegenSubcommands::Fixtures { network, blocks, max_rows, output } = subcommand {
+        let options = GenerateFixturesOptions {
+            network: network.clone(),
+            blocks: *blocks,
+            max_rows_per_table: *max_rows,
+            output: project_path.join(output),
+        };
+        let generated =
+            generate_fixtures(&project_path, &manifest, &options).await.map_err(|e| {
+                print_error_message(&format!("Failed to generate fixtures: {}", e));
+                e
+            })?;
+
+        let rows: usize = generated.tables.iter().map(|(_, rows)| rows).sum();
+        print_success_message(&format!(
+            "Generated {} fixture rows for {} event tables in {}.",
+            rows,
+            generated.tables.len(),
+            options.output.display()
+        ));
+
+        return Ok(());
+    }
+
+    if let CodegenSubcommands::Dbt { output } = subcommand {
+        let output = project_path.join(output);
+        let generated = generate_dbt_models(&project_path, &manifest, &output).map_err(|e| {
+            print_error_message(&format!("Failed to generate dbt models: {}", e));
+            e
+        })?;
+
+        print_success_message(&format!(
+            "Generated {} dbt staging models in {}.",
+            generated.models.len(),
+            output.display()
+        ));
+
+        return Ok(());
+    }
+
     if manifest.project_type == ProjectType::NoCode {
         let error = "This command is not supported for no-code projects, please migrate to a project to use this.";
         print_error_message(error);
@@ -65,7 +107,9 @@ pub async fn handle_codegen_command(
             format_all_files_for_project(project_path);
             print_success_message("Generated rindexer indexer handlers.");
         }
-        CodegenSubcommands::GraphQL { endpoint: _endpoint } => {
+        CodegenSubcommands::GraphQL { .. } |
+        CodegenSubcommands::Fixtures { .. } |
+        CodegenSubcommands::Dbt { .. } => {
             unreachable!("This should not be reachable");
         }
     }
@@ -0,0 +1,33 @@
+use std::{path::PathBuf, str::FromStr};
+
+use ethers::types::H256;
+use rindexer::{
+    decode_transaction,
+    manifest::yaml::{read_manifest, YAML_CONFIG_NAME},
+    DecodeOptions,
+};
+
+use crate::console::{print_error_message, print_success_message};
+
+pub async fn handle_decode_command(
+    project_path: PathBuf,
+    tx: &str,
+    network: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).inspect_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+    })?;
+
+    let tx_hash = H256::from_str(tx).inspect_err(|e| {
+        print_error_message(&format!("Invalid transaction hash {}: {}", tx, e));
+    })?;
+
+    let options = DecodeOptions { tx_hash, network: network.to_string() };
+    let report = decode_transaction(&project_path, &manifest, &options).await.inspect_err(|e| {
+        print_error_message(&format!("Could not decode transaction: {}", e));
+    })?;
+
+    print_success_message(&format!("\n\n{}\n", report));
+
+    Ok(())
+}
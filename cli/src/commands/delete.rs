@@ -42,11 +42,15 @@ pub async fn handle_delete_command(
         );
 
         if postgres_delete == "yes" {
-            let postgres_client = PostgresClient::new().await.map_err(|e| {
+            let postgres_client = PostgresClient::from_storage(&manifest.storage, &manifest.name).await.map_err(|e| {
                 print_error_message(&format!("Could not connect to Postgres, make sure your connection string is mapping in the .env correctly: trace: {}", e));
                 e
             })?;
-            let sql = drop_tables_for_indexer_sql(&project_path, &manifest.to_indexer());
+            let sql = drop_tables_for_indexer_sql(
+                &project_path,
+                &manifest.to_indexer(),
+                manifest.storage.postgres_layout(),
+            );
 
             postgres_client.batch_execute(sql.as_str()).await.map_err(|e| {
                 print_error_message(&format!("Could not delete tables from Postgres make sure your connection string is mapping in the .env correctly: trace: {}", e));
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use rindexer::{
+    manifest::yaml::{read_manifest, YAML_CONFIG_NAME},
+    plan_teardown, run_teardown, TeardownOptions,
+};
+
+use crate::console::{
+    print_error_message, print_success_message, print_warn_message, prompt_for_input_list,
+};
+
+pub async fn handle_down_command(
+    project_path: PathBuf,
+    delete_streams: bool,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    let plan = plan_teardown(&project_path, &manifest, &TeardownOptions { delete_streams })
+        .await
+        .map_err(|e| {
+        print_error_message(&format!("Could not plan the teardown: {}", e));
+        e
+    })?;
+
+    if plan.is_empty() {
+        print_success_message("Nothing to tear down.");
+        return Ok(());
+    }
+
+    if dry_run {
+        print_success_message(&format!("\nrindexer down would run:\n\n{}", plan));
+        return Ok(());
+    }
+
+    print_warn_message(&format!(
+        "This will tear down the project at {}:\n\n{}",
+        project_path.display(),
+        plan
+    ));
+    print_warn_message(
+        "This operation can not be reverted. Make sure you know what you are doing.",
+    );
+
+    let confirm = prompt_for_input_list(
+        "Are you sure you wish to tear down the project (it can not be reverted)?",
+        &["yes".to_string(), "no".to_string()],
+        None,
+    );
+    if confirm != "yes" {
+        return Ok(());
+    }
+
+    run_teardown(&manifest, &plan).await.map_err(|e| {
+        print_error_message(&format!("Could not tear down the project: {}", e));
+        e
+    })?;
+
+    print_success_message("\n\nSuccessfully tore down the project.\n\n");
+
+    Ok(())
+}
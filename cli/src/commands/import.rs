@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use rindexer::{
+    import_events,
+    manifest::yaml::{read_manifest, YAML_CONFIG_NAME},
+    ImportEventsOptions,
+};
+
+use crate::console::{print_error_message, print_success_message};
+
+pub async fn handle_import_command(
+    project_path: PathBuf,
+    contract_name: &str,
+    event_name: &str,
+    network: &str,
+    file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    let options = ImportEventsOptions {
+        contract_name: contract_name.to_string(),
+        event_name: event_name.to_string(),
+        network: network.to_string(),
+        file: project_path.join(file),
+    };
+
+    let imported = import_events(&project_path, &manifest, &options).await.map_err(|e| {
+        print_error_message(&format!("Could not import events: {}", e));
+        e
+    })?;
+
+    let resume_message = match imported.last_synced_block {
+        Some(block) => format!(" - indexing will resume after block {}", block),
+        None => "".to_string(),
+    };
+    print_success_message(&format!(
+        "\n\nSuccessfully imported {} rows into {}{}\n\n",
+        imported.rows, imported.table_name, resume_message
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use rindexer::{
+    managed_indexes,
+    manifest::yaml::{read_manifest, YAML_CONFIG_NAME},
+    PostgresClient,
+};
+
+use crate::{
+    cli_interface::IndexesSubcommands,
+    console::{print_error_message, print_success_message},
+};
+
+pub async fn handle_indexes_command(
+    project_path: PathBuf,
+    subcommand: &IndexesSubcommands,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    if !manifest.storage.postgres_enabled() {
+        let error = "Postgres storage is not enabled - indexes are only managed for postgres";
+        print_error_message(error);
+        return Err(error.into());
+    }
+
+    match subcommand {
+        IndexesSubcommands::Apply => {
+            let (relationships, indexes) = manifest
+                .storage
+                .apply_manual_relationships_and_indexes(
+                    &project_path,
+                    &manifest.name,
+                    &manifest.contracts,
+                )
+                .await
+                .map_err(|e| {
+                    print_error_message(&format!("Could not apply indexes: {}", e));
+                    e
+                })?;
+
+            print_success_message(&format!(
+                "\n\nSuccessfully applied {} relationships and {} indexes\n\n",
+                relationships, indexes
+            ));
+        }
+        IndexesSubcommands::Status => {
+            let client = PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+            let indexes = managed_indexes(&client, &manifest.name).await.map_err(|e| {
+                print_error_message(&format!("Could not read the managed indexes: {}", e));
+                e
+            })?;
+
+            if indexes.is_empty() {
+                print_success_message("No indexes have been built yet");
+            }
+            for index in indexes {
+                let validity = match index.valid {
+                    Some(true) => "valid",
+                    Some(false) => "INVALID",
+                    None => "missing",
+                };
+                println!(
+                    "{} ({}) on {} - {} - {} - attempts: {}",
+                    index.index_name,
+                    index.columns,
+                    index.table_name,
+                    index.state,
+                    validity,
+                    index.attempts
+                );
+                if let Some(progress) = index.progress {
+                    println!("    progress: {}", progress);
+                }
+                if let Some(error) = index.error {
+                    println!("    error: {}", error);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,8 +1,20 @@
 pub mod add;
+pub mod bench;
+pub mod bundle;
 pub mod codegen;
+pub mod decode;
 pub mod delete;
+pub mod down;
+#[cfg(feature = "csv")]
+pub mod import;
+pub mod indexes;
 pub mod new;
 pub mod phantom;
+pub mod redecode;
+pub mod replay_failed;
+pub mod snapshot;
 pub mod start;
+pub mod streams;
+pub mod verify;
 
 const BACKUP_ETHERSCAN_API_KEY: &str = "DHBPB1EJ84JMSWP7C86387NK7IIRRQJVV1";
@@ -133,6 +133,7 @@ pub fn handle_new_command(
         name: project_name,
         description: project_description,
         repository,
+        project_id: None,
         project_type,
         networks: vec![Network {
             name: "ethereum".to_string(),
@@ -141,9 +142,27 @@ pub fn handle_new_command(
             compute_units_per_second: None,
             max_block_range: None,
             disable_logs_bloom_checks: None,
+            rpcs: None,
+            retry: None,
+            compute_unit_costs: None,
+            circuit_breaker: None,
+            merge_get_logs: None,
+            max_addresses_per_get_logs: None,
+            block_poll_interval_ms: None,
+            block_processing_delay: None,
+            live_indexing_strategy: None,
+            log_fetch_strategy: None,
+            ws: None,
+            proxy: None,
+            rpc_debug: None,
+            catch_up: None,
+            block_cache: None,
+            schedule: None,
+            head_consensus: None,
         }],
         contracts: vec![Contract {
             name: "RocketPoolETH".to_string(),
+            addresses: None,
             details: vec![ContractDetails::new_with_address(
                 "ethereum".to_string(),
                 ValueOrArray::<Address>::Value(
@@ -157,12 +176,32 @@ pub fn handle_new_command(
             )],
             abi: abi_example_path.display().to_string(),
             include_events: Some(vec!["Transfer".to_string(), "Approval".to_string()]),
+            exclude_events: None,
             index_event_in_order: None,
             dependency_events: None,
+            depends_on: None,
             reorg_safe_distance: None,
             generate_csv: None,
             streams: None,
             chat: None,
+            transforms: None,
+            price_enrichment: None,
+            anonymous_events: None,
+            overloaded_events: None,
+            rename_overloaded_events: false,
+            jsonb_storage: None,
+            event_networks: None,
+            event_conditions: None,
+            computed_columns: None,
+            checkpoint: None,
+            template: None,
+            priority: None,
+            lifecycle: None,
+            handler_concurrency: None,
+            enums: None,
+            typed_addresses: None,
+            table_settings: None,
+            store_raw_logs: None,
         }],
         phantom: None,
         global: None,
@@ -174,6 +213,29 @@ pub fn handle_new_command(
                     relationships: None,
                     indexes: None,
                     disable_create_tables: None,
+                    indexing_stats: None,
+                    default_indexes: None,
+                    unlogged_historic_sync: None,
+                    insert_strategy: None,
+                    layout: None,
+                    primary_key: None,
+                    compact_bytea: None,
+                    projections: None,
+                    rollups: None,
+                    balance_changes: None,
+                    connection_string: None,
+                    connection_string_env: None,
+                    session: None,
+                    write_retry: None,
+                    confirm_destructive_ddl: None,
+                    cdc: None,
+                    persist_handler_cache: None,
+                    hashed_topic_preimages: None,
+                    reconciliations: None,
+                    metrics_port: None,
+                    metrics_host: None,
+                    heartbeats: None,
+                    verify: None,
                 })
             } else {
                 None
@@ -189,6 +251,12 @@ pub fn handle_new_command(
             },
         },
         graphql: None,
+        push: None,
+        wallets: None,
+        gas_prices: None,
+        block_anomalies: None,
+        runtime: None,
+        webhooks: None,
     };
 
     // Write the rindexer.yaml file
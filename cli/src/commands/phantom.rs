@@ -1,3 +1,5 @@
+#![allow(clippy::unnecessary_unwrap)]
+
 use std::{
     env,
     error::Error,
@@ -548,6 +550,23 @@ async fn handle_phantom_deploy(
                             Some(U64::from(2_000))
                         },
                         disable_logs_bloom_checks: None,
+                        rpcs: None,
+                        retry: None,
+                        compute_unit_costs: None,
+                        circuit_breaker: None,
+                        merge_get_logs: None,
+                        max_addresses_per_get_logs: None,
+                        block_poll_interval_ms: None,
+                        block_processing_delay: None,
+                        live_indexing_strategy: None,
+                        log_fetch_strategy: None,
+                        ws: None,
+                        proxy: None,
+                        rpc_debug: None,
+                        catch_up: None,
+                        block_cache: None,
+                        schedule: None,
+                        head_consensus: None,
                     });
                 }
 
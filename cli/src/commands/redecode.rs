@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use rindexer::{
+    manifest::yaml::{read_manifest, YAML_CONFIG_NAME},
+    redecode_event, RedecodeOptions,
+};
+
+use crate::console::{print_error_message, print_success_message, print_warn_message};
+
+pub async fn handle_redecode_command(
+    project_path: PathBuf,
+    options: RedecodeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    let report = redecode_event(&project_path, &manifest, &options).await.map_err(|e| {
+        print_error_message(&format!("Could not decode the raw logs again: {}", e));
+        e
+    })?;
+
+    if report.undecodable > 0 {
+        print_warn_message(&format!(
+            "{} raw logs do not fit the current ABI and were left as they were",
+            report.undecodable
+        ));
+    }
+
+    print_success_message(&format!(
+        "\n\nRewrote the decoded columns of {} {}::{} rows\n\n",
+        report.rewritten, options.contract_name, options.event_name
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::{path::PathBuf, process::Command};
+
+use rindexer::{
+    manifest::{
+        core::ProjectType,
+        yaml::{read_manifest, YAML_CONFIG_NAME},
+    },
+    no_code_registry, replay_failed_events, ReplayFailedEventsOptions,
+};
+
+use crate::console::{print_error_message, print_success_message, print_warn_message};
+
+pub async fn handle_replay_failed_command(
+    project_path: PathBuf,
+    options: ReplayFailedEventsOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    // rust project handlers live in the project binary, it replays them with `--replay-failed`
+    if manifest.project_type == ProjectType::Rust {
+        let mut args = vec!["--".to_string(), "--replay-failed".to_string()];
+        if let Some(contract_name) = &options.contract_name {
+            args.push(format!("--contract-name={}", contract_name));
+        }
+        if let Some(event_name) = &options.event_name {
+            args.push(format!("--event-name={}", event_name));
+        }
+        if let Some(network) = &options.network {
+            args.push(format!("--network={}", network));
+        }
+
+        let status = Command::new("cargo")
+            .arg("run")
+            .arg("--manifest-path")
+            .arg(project_path.join("Cargo.toml"))
+            .args(args)
+            .current_dir(&project_path)
+            .status()?;
+        if !status.success() {
+            let error = format!("Replaying the failed events failed with status: {}", status);
+            print_error_message(&error);
+            return Err(error.into());
+        }
+        return Ok(());
+    }
+
+    let registry = no_code_registry(&project_path, &manifest).await.map_err(|e| {
+        print_error_message(&format!("Could not register the no-code handlers: {}", e));
+        e
+    })?;
+
+    let report = replay_failed_events(&manifest, &registry, &options).await.map_err(|e| {
+        print_error_message(&format!("Could not replay failed events: {}", e));
+        e
+    })?;
+
+    if report.skipped > 0 {
+        print_warn_message(&format!(
+            "{} failed events belong to events no longer in the manifest and were left quarantined",
+            report.skipped
+        ));
+    }
+
+    if report.still_failing > 0 {
+        let error = format!(
+            "Replayed {} failed events, {} still fail and stay quarantined",
+            report.replayed, report.still_failing
+        );
+        print_error_message(&error);
+        return Err(error.into());
+    }
+
+    print_success_message(&format!(
+        "\n\nSuccessfully replayed {} failed events\n\n",
+        report.replayed
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use rindexer::{
+    export_snapshot, import_snapshot,
+    manifest::yaml::{read_manifest, YAML_CONFIG_NAME},
+};
+
+use crate::{
+    cli_interface::SnapshotSubcommands,
+    console::{print_error_message, print_success_message},
+};
+
+pub async fn handle_snapshot_command(
+    project_path: PathBuf,
+    subcommand: &SnapshotSubcommands,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    match subcommand {
+        SnapshotSubcommands::Export { file } => {
+            let file = project_path.join(file);
+            export_snapshot(&manifest, &file).await.map_err(|e| {
+                print_error_message(&format!("Could not export snapshot: {}", e));
+                e
+            })?;
+
+            print_success_message(&format!(
+                "\n\nSuccessfully exported snapshot to {}\n\n",
+                file.display()
+            ));
+        }
+        SnapshotSubcommands::Import { file } => {
+            let file = project_path.join(file);
+            import_snapshot(&manifest, &file).await.map_err(|e| {
+                print_error_message(&format!("Could not import snapshot: {}", e));
+                e
+            })?;
+
+            print_success_message(&format!(
+                "\n\nSuccessfully imported snapshot from {}\n\n",
+                file.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
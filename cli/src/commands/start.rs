@@ -1,8 +1,9 @@
-use std::{env, path::PathBuf, process::Command, thread, time::Duration};
+use std::{path::PathBuf, process::Command, thread, time::Duration};
 
 use rindexer::{
     manifest::{
         core::ProjectType,
+        storage::Storage,
         yaml::{read_manifest, YAML_CONFIG_NAME},
     },
     rindexer_error, rindexer_info, setup_info_logger, start_rindexer_no_code,
@@ -39,7 +40,11 @@ fn check_postgres_connection(conn_str: &str, max_retries: u32) -> Result<(), Str
     Err("Postgres did not become available within the given retries.".into())
 }
 
-fn check_docker_compose_status(project_path: &PathBuf, max_retries: u32) -> Result<(), String> {
+fn check_docker_compose_status(
+    project_path: &PathBuf,
+    storage: &Storage,
+    max_retries: u32,
+) -> Result<(), String> {
     let mut retries = 0;
 
     while retries < max_retries {
@@ -58,14 +63,14 @@ fn check_docker_compose_status(project_path: &PathBuf, max_retries: u32) -> Resu
             if !output.contains("Exit") && output.contains("Up") {
                 rindexer_info!("All containers are up and running.");
 
-                return if let Ok(conn_str) = env::var("DATABASE_URL") {
+                return if let Ok(conn_str) = storage.postgres_connection_string() {
                     check_postgres_connection(&conn_str, max_retries).map_err(|e| {
                         let error = format!("Failed to connect to PostgresSQL: {}", e);
                         rindexer_error!(error);
                         error
                     })
                 } else {
-                    let error = "DATABASE_URL not set.".to_string();
+                    let error = format!("{} not set.", storage.postgres_connection_string_env());
                     rindexer_error!(error);
                     Err(error)
                 }
@@ -83,7 +88,7 @@ fn check_docker_compose_status(project_path: &PathBuf, max_retries: u32) -> Resu
     Err("Docker containers did not start successfully within the given retries.".into())
 }
 
-fn start_docker_compose(project_path: &PathBuf) -> Result<(), String> {
+fn start_docker_compose(project_path: &PathBuf, storage: &Storage) -> Result<(), String> {
     if !project_path.exists() {
         return Err(format!("Project path does not exist: {:?}", project_path));
     }
@@ -108,7 +113,7 @@ fn start_docker_compose(project_path: &PathBuf) -> Result<(), String> {
 
     rindexer_info!("Docker starting up the postgres container..");
 
-    check_docker_compose_status(project_path, 200)
+    check_docker_compose_status(project_path, storage, 200)
 }
 
 pub async fn start(
@@ -125,17 +130,19 @@ pub async fn start(
     })?;
 
     if manifest.storage.postgres_enabled() {
-        let client = PostgresClient::new().await;
+        let client = PostgresClient::from_storage(&manifest.storage, &manifest.name).await;
         if client.is_err() {
             // find if docker-compose.yml is present in parent
             let docker_compose_path = project_path.join("docker-compose.yml");
             if !docker_compose_path.exists() {
-                return Err(
-                    "The DATABASE_URL mapped is not running please make sure it is correct".into()
-                );
+                return Err(format!(
+                    "The {} mapped is not running please make sure it is correct",
+                    manifest.storage.postgres_connection_string_env()
+                )
+                .into());
             }
 
-            match start_docker_compose(&project_path) {
+            match start_docker_compose(&project_path, &manifest.storage) {
                 Ok(_) => {
                     rindexer_info!("Docker postgres containers started up successfully");
                 }
@@ -151,21 +158,35 @@ pub async fn start(
     match manifest.project_type {
         ProjectType::Rust => {
             let project_cargo_manifest_path = project_path.join("Cargo.toml");
+            let mut args: Vec<String> = vec!["--".to_string()];
+            match command {
+                StartSubcommands::Indexer { apply_migrations } => {
+                    args.push("--indexer".to_string());
+                    if *apply_migrations {
+                        args.push("--apply-migrations".to_string());
+                    }
+                }
+                StartSubcommands::Graphql { port } => {
+                    args.push("--graphql".to_string());
+                    if let Some(port) = port {
+                        args.push(format!("--port={}", port));
+                    }
+                }
+                StartSubcommands::All { port, apply_migrations } => {
+                    if let Some(port) = port {
+                        args.push(format!("--port={}", port));
+                    }
+                    if *apply_migrations {
+                        args.push("--apply-migrations".to_string());
+                    }
+                }
+            }
+
             let status = Command::new("cargo")
                 .arg("run")
                 .arg("--manifest-path")
                 .arg(project_cargo_manifest_path)
-                .arg(match command {
-                    StartSubcommands::Indexer => "-- --indexer".to_string(),
-                    StartSubcommands::Graphql { port } => match port {
-                        Some(port) => format!("-- --graphql --port={}", port),
-                        None => "-- --graphql".to_string(),
-                    },
-                    StartSubcommands::All { port } => match port {
-                        Some(port) => format!("-- --port={}", port),
-                        None => "".to_string(),
-                    },
-                })
+                .args(args)
                 .status()
                 .expect("Failed to execute cargo run.");
 
@@ -174,10 +195,13 @@ pub async fn start(
             }
         }
         ProjectType::NoCode => match command {
-            StartSubcommands::Indexer => {
+            StartSubcommands::Indexer { apply_migrations } => {
                 let details = StartNoCodeDetails {
                     manifest_path: &project_path.join(YAML_CONFIG_NAME),
-                    indexing_details: IndexerNoCodeDetails { enabled: true },
+                    indexing_details: IndexerNoCodeDetails {
+                        enabled: true,
+                        apply_migrations: *apply_migrations,
+                    },
                     graphql_details: GraphqlOverrideSettings {
                         enabled: false,
                         override_port: None,
@@ -192,7 +216,10 @@ pub async fn start(
             StartSubcommands::Graphql { port } => {
                 let details = StartNoCodeDetails {
                     manifest_path: &project_path.join(YAML_CONFIG_NAME),
-                    indexing_details: IndexerNoCodeDetails { enabled: false },
+                    indexing_details: IndexerNoCodeDetails {
+                        enabled: false,
+                        apply_migrations: false,
+                    },
                     graphql_details: GraphqlOverrideSettings {
                         enabled: true,
                         override_port: port.as_ref().and_then(|port| port.parse().ok()),
@@ -204,10 +231,13 @@ pub async fn start(
                     e
                 })?;
             }
-            StartSubcommands::All { port } => {
+            StartSubcommands::All { port, apply_migrations } => {
                 let details = StartNoCodeDetails {
                     manifest_path: &project_path.join(YAML_CONFIG_NAME),
-                    indexing_details: IndexerNoCodeDetails { enabled: true },
+                    indexing_details: IndexerNoCodeDetails {
+                        enabled: true,
+                        apply_migrations: *apply_migrations,
+                    },
                     graphql_details: GraphqlOverrideSettings {
                         enabled: true,
                         override_port: port.as_ref().and_then(|port| port.parse().ok()),
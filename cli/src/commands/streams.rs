@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use rindexer::{
+    manifest::yaml::{read_manifest, YAML_CONFIG_NAME},
+    replay_streams, ReplayStreamsOptions,
+};
+
+use crate::{
+    cli_interface::StreamsSubcommands,
+    console::{print_error_message, print_success_message},
+};
+
+pub async fn handle_streams_command(
+    project_path: PathBuf,
+    subcommand: &StreamsSubcommands,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    match subcommand {
+        StreamsSubcommands::Replay { from_block, to_block, contract_name, network } => {
+            if from_block > to_block {
+                let error = "--from-block must be less than or equal to --to-block";
+                print_error_message(error);
+                return Err(error.into());
+            }
+
+            let options = ReplayStreamsOptions {
+                from_block: *from_block,
+                to_block: *to_block,
+                contract_name: contract_name.clone(),
+                network: network.clone(),
+            };
+
+            let streamed =
+                replay_streams(&project_path, &manifest, &options).await.map_err(|e| {
+                    print_error_message(&format!("Could not replay streams: {}", e));
+                    e
+                })?;
+
+            print_success_message(&format!(
+                "\n\nSuccessfully replayed {} events to streams for blocks {} - {}\n\n",
+                streamed, from_block, to_block
+            ));
+        }
+    }
+
+    Ok(())
+}
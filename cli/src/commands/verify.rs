@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use rindexer::{
+    manifest::{
+        core::ProjectType,
+        yaml::{read_manifest, YAML_CONFIG_NAME},
+    },
+    no_code_registry, verify_indexed_events, VerifyOptions,
+};
+
+use crate::console::{print_error_message, print_success_message, print_warn_message};
+
+pub async fn handle_verify_command(
+    project_path: PathBuf,
+    options: VerifyOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    if manifest.project_type != ProjectType::NoCode {
+        let error = "Rust project handlers live in your project, set `storage.postgres.verify` to verify while indexing or call `rindexer::verify_indexed_events` with the registry from `register_all_handlers`";
+        print_error_message(error);
+        return Err(error.into());
+    }
+
+    let registry = no_code_registry(&project_path, &manifest).await.map_err(|e| {
+        print_error_message(&format!("Could not register the no-code handlers: {}", e));
+        e
+    })?;
+
+    let report = verify_indexed_events(&manifest, &registry, &options).await.map_err(|e| {
+        print_error_message(&format!("Could not verify the indexed events: {}", e));
+        e
+    })?;
+
+    for unverifiable in &report.unverifiable {
+        print_warn_message(&format!("Skipped {}", unverifiable));
+    }
+
+    for gap in &report.gaps {
+        print_warn_message(&format!(
+            "{}::{} on {} - {} rows missing in blocks {} to {}",
+            gap.contract_name,
+            gap.event_name,
+            gap.network,
+            gap.missing,
+            gap.from_block,
+            gap.to_block
+        ));
+    }
+
+    for (gap, error) in &report.repair_failed {
+        print_error_message(&format!(
+            "Could not repair {}::{} on {} blocks {} to {}: {}",
+            gap.contract_name, gap.event_name, gap.network, gap.from_block, gap.to_block, error
+        ));
+    }
+    if !report.repair_failed.is_empty() {
+        return Err(
+            format!("{} repairs failed and stay scheduled", report.repair_failed.len()).into()
+        );
+    }
+
+    if !report.repaired.is_empty() {
+        print_success_message(&format!(
+            "\n\nVerified {} events and repaired {} ranges\n\n",
+            report.verified,
+            report.repaired.len()
+        ));
+    } else if report.gaps.is_empty() {
+        print_success_message(&format!(
+            "\n\nVerified {} events - no missing rows found\n\n",
+            report.verified
+        ));
+    } else {
+        print_warn_message(&format!(
+            "Found {} ranges with missing rows and scheduled their repair, run `rindexer verify --repair` to index them again",
+            report.gaps.len()
+        ));
+    }
+
+    Ok(())
+}
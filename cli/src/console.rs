@@ -1,4 +1,8 @@
-use std::{io, io::Write, str::FromStr};
+use std::{
+    io,
+    io::{IsTerminal, Write},
+    str::FromStr,
+};
 
 use colored::Colorize;
 use regex::Regex;
@@ -118,3 +122,20 @@ pub fn prompt_for_input(
         },
     }
 }
+
+/// Asks on the terminal before running a destructive statement, anything not attached to a
+/// terminal can not confirm so it is refused
+pub fn confirm_destructive_ddl(reason: &str, sql: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+
+    println!("rindexer wants to run destructive DDL to {}:\n\n{}\n", reason, sql.trim());
+    print!("{}", "Type 'yes' to run it: ".yellow());
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok() && answer.trim() == "yes"
+}
@@ -12,17 +12,30 @@ mod commands;
 mod console;
 mod rindexer_yaml;
 
-use std::{path::PathBuf, str::FromStr, sync::Once};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Once,
+};
 
 use clap::Parser;
-use rindexer::{load_env_from_path, manifest::core::ProjectType};
+use rindexer::{
+    build_runtime, load_env_from_path,
+    manifest::{core::ProjectType, runtime::RuntimeSettings, yaml::YAML_CONFIG_NAME},
+    set_destructive_ddl_confirmer, unpack_bundle_to_temp_dir, RedecodeOptions,
+    ReplayFailedEventsOptions, VerifyOptions,
+};
 
 use crate::{
     cli_interface::{AddSubcommands, Commands, NewSubcommands, CLI},
     commands::{
-        add::handle_add_contract_command, codegen::handle_codegen_command,
-        delete::handle_delete_command, new::handle_new_command, phantom::handle_phantom_commands,
-        start::start,
+        add::handle_add_contract_command, bench::handle_bench_command,
+        bundle::handle_bundle_command, codegen::handle_codegen_command,
+        decode::handle_decode_command, delete::handle_delete_command, down::handle_down_command,
+        indexes::handle_indexes_command, new::handle_new_command, phantom::handle_phantom_commands,
+        redecode::handle_redecode_command, replay_failed::handle_replay_failed_command,
+        snapshot::handle_snapshot_command, start::start, streams::handle_streams_command,
+        verify::handle_verify_command,
     },
     console::print_error_message,
 };
@@ -70,12 +83,42 @@ fn resolve_path(override_path: &Option<String>) -> Result<PathBuf, String> {
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     env::set_var("RUST_BACKTRACE", "full");
     set_panic_hook();
-    let cli = CLI::parse();
+    set_destructive_ddl_confirmer(Box::new(console::confirm_destructive_ddl));
+    let mut cli = CLI::parse();
+
+    // a bundle is unpacked into a temporary directory which is removed once the process exits
+    let _bundle_dir = match &mut cli.command {
+        Commands::Start { path, bundle: Some(bundle), identity, .. } => {
+            let dir = RuntimeSettings::default()
+                .build()?
+                .block_on(unpack_bundle_to_temp_dir(
+                    Path::new(bundle),
+                    identity.as_deref().map(Path::new),
+                ))
+                .inspect_err(|e| print_error_message(&format!("Could not unpack bundle: {}", e)))?;
+            *path = Some(dir.path().display().to_string());
+            Some(dir)
+        }
+        _ => None,
+    };
 
+    // only starting uses the manifest runtime settings, every other command is short lived
+    let runtime = match &cli.command {
+        Commands::Start { path, .. } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            build_runtime(&resolved_path.join(YAML_CONFIG_NAME))?
+        }
+        _ => RuntimeSettings::default().build()?,
+    };
+
+    runtime.block_on(run(cli))
+}
+
+async fn run(cli: CLI) -> Result<(), Box<dyn std::error::Error>> {
     match &cli.command {
         Commands::New { subcommand, path } => {
             let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
@@ -101,7 +144,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             load_env_from_path(&resolved_path);
             handle_codegen_command(resolved_path, subcommand).await
         }
-        Commands::Start { subcommand, path } => {
+        Commands::Start { subcommand, path, .. } => {
             let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
             load_env_from_path(&resolved_path);
             start(resolved_path, subcommand).await
@@ -116,5 +159,101 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             load_env_from_path(&resolved_path);
             handle_phantom_commands(resolved_path, subcommand).await
         }
+        Commands::Streams { subcommand, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_streams_command(resolved_path, subcommand).await
+        }
+        #[cfg(feature = "csv")]
+        Commands::Import { contract_name, event_name, network, file, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            commands::import::handle_import_command(
+                resolved_path,
+                contract_name,
+                event_name,
+                network,
+                file,
+            )
+            .await
+        }
+        Commands::Snapshot { subcommand, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_snapshot_command(resolved_path, subcommand).await
+        }
+        Commands::Indexes { subcommand, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_indexes_command(resolved_path, subcommand).await
+        }
+        Commands::Bench { blocks, network, rows, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_bench_command(resolved_path, *blocks, network, *rows).await
+        }
+        Commands::ReplayFailed { contract_name, event_name, network, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_replay_failed_command(
+                resolved_path,
+                ReplayFailedEventsOptions {
+                    contract_name: contract_name.clone(),
+                    event_name: event_name.clone(),
+                    network: network.clone(),
+                },
+            )
+            .await
+        }
+        Commands::Decode { tx, network, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_decode_command(resolved_path, tx, network).await
+        }
+        Commands::Redecode { contract, event, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_redecode_command(
+                resolved_path,
+                RedecodeOptions { contract_name: contract.clone(), event_name: event.clone() },
+            )
+            .await
+        }
+        Commands::Verify {
+            contract_name,
+            event_name,
+            network,
+            window,
+            concurrency,
+            full,
+            repair,
+            path,
+        } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_verify_command(
+                resolved_path,
+                VerifyOptions {
+                    contract_name: contract_name.clone(),
+                    event_name: event_name.clone(),
+                    network: network.clone(),
+                    window_blocks: *window,
+                    concurrency: *concurrency,
+                    full: *full,
+                    repair: *repair,
+                },
+            )
+            .await
+        }
+        Commands::Down { delete_streams, dry_run, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_down_command(resolved_path, *delete_streams, *dry_run).await
+        }
+        Commands::Bundle { subcommand, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_path(&resolved_path);
+            handle_bundle_command(resolved_path, subcommand).await
+        }
     }
 }
@@ -12,6 +12,14 @@ fn main() {
     if target_dir.exists() {
         fs::remove_dir_all(&target_dir).expect("Failed to remove old resources directory");
     }
+
+    println!("cargo:rerun-if-changed=resources");
+
+    // the resources are the graphql server binaries, builds without the server leave them out
+    if env::var_os("CARGO_FEATURE_GRAPHQL").is_none() {
+        return;
+    }
+
     fs::create_dir_all(&target_dir).expect("Failed to create resources directory");
     for entry in fs::read_dir(resources_dir).expect("Failed to read resources directory") {
         let entry = entry.expect("Failed to read directory entry");
@@ -21,6 +29,4 @@ fn main() {
                 .expect("Failed to copy file");
         }
     }
-
-    println!("cargo:rerun-if-changed=resources");
 }
@@ -9,7 +9,7 @@ use crate::{
         sql_type_wrapper::{solidity_type_to_ethereum_sql_type_wrapper, EthereumSqlTypeWrapper},
     },
     event::contract_setup::IndexingContractSetup,
-    helpers::{camel_to_snake, get_full_path},
+    helpers::camel_to_snake,
     manifest::contract::Contract,
 };
 
@@ -62,6 +62,21 @@ impl GenerateAbiNamePropertiesResult {
 }
 
 impl ABIInput {
+    /// Indexed strings, bytes, arrays and tuples are only logged as the keccak hash of their
+    /// value so the topic can not be decoded back into the declared type
+    pub fn is_hashed_topic(&self) -> bool {
+        self.indexed.unwrap_or_default() &&
+            (self.type_ == "string" ||
+                self.type_ == "bytes" ||
+                self.type_.ends_with(']') ||
+                self.type_.starts_with("tuple"))
+    }
+
+    /// Name of the column a hashed topic is stored in, so it is never mistaken for the value
+    pub fn hashed_topic_name(&self) -> String {
+        format!("{}_hash", self.name)
+    }
+
     pub fn format_param_type(&self) -> Result<String, ParamTypeError> {
         match self.type_.as_str() {
             "tuple" => {
@@ -86,6 +101,34 @@ impl ABIInput {
         inputs
             .iter()
             .flat_map(|input| {
+                if input.is_hashed_topic() {
+                    // the generated bindings keep the ABI name for the H256 field
+                    if properties_type == &GenerateAbiPropertiesType::Object {
+                        let value = format!(
+                            "{}{}.0",
+                            prefix.map_or_else(|| "".to_string(), |p| format!("{}.", p)),
+                            camel_to_snake(&input.name),
+                        );
+                        return vec![GenerateAbiNamePropertiesResult::new(
+                            value,
+                            &input.name,
+                            "bytes32",
+                        )];
+                    }
+
+                    let stored = ABIInput {
+                        name: input.hashed_topic_name(),
+                        type_: "bytes32".to_string(),
+                        components: None,
+                        ..input.clone()
+                    };
+                    return ABIInput::generate_abi_name_properties(
+                        &[stored],
+                        properties_type,
+                        prefix,
+                    );
+                }
+
                 if let Some(components) = &input.components {
                     ABIInput::generate_abi_name_properties(
                         components,
@@ -152,6 +195,10 @@ pub struct ABIItem {
 
     #[serde(rename = "type", default)]
     pub type_: String,
+
+    /// Name in the ABI when the event is overloaded and `name` has been made unique
+    #[serde(skip)]
+    pub abi_name: Option<String>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -164,6 +211,9 @@ pub enum ReadAbiError {
 
     #[error("Could not read ABI JSON: {0}")]
     CouldNotReadAbiJson(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
 }
 
 impl ABIItem {
@@ -176,6 +226,53 @@ impl ABIItem {
         Ok(formatted_inputs.join(","))
     }
 
+    /// Full event signature as it is hashed into topic0 e.g. `Swap(address,uint256)`
+    pub fn full_event_signature(&self) -> Result<String, ParamTypeError> {
+        Ok(format!(
+            "{}({})",
+            self.abi_name.as_ref().unwrap_or(&self.name),
+            self.format_event_signature()?
+        ))
+    }
+
+    /// Overloaded events share a name so the first declared keeps its name and every other
+    /// overload gets its signature hash appended, unless the manifest names the overload
+    fn apply_overloaded_event_names(
+        abi_items: &mut [ABIItem],
+        contract: &Contract,
+    ) -> Result<(), ParamTypeError> {
+        let mut seen_names: Vec<String> = vec![];
+        for index in 0..abi_items.len() {
+            let item = &abi_items[index];
+            if item.type_ != "event" {
+                continue;
+            }
+
+            let overloads =
+                abi_items.iter().filter(|i| i.type_ == "event" && i.name == item.name).count();
+            if overloads < 2 {
+                continue;
+            }
+
+            let signature = item.full_event_signature()?;
+            let overload_name = contract.overloaded_event_name(&signature).map(|n| n.to_string());
+            let is_first = !seen_names.contains(&item.name);
+            seen_names.push(item.name.clone());
+
+            let name = match overload_name {
+                Some(name) => name,
+                None if is_first => continue,
+                None => format!("{}_{}", item.name, &hex::encode(keccak256(&signature))[..8]),
+            };
+
+            let item = &mut abi_items[index];
+            item.abi_name = Some(item.name.clone());
+            item.name = name;
+        }
+
+        Ok(())
+    }
+
     pub fn extract_event_names_and_signatures_from_abi(
         abi_json: Vec<ABIItem>,
     ) -> Result<Vec<EventInfo>, ParamTypeError> {
@@ -193,19 +290,38 @@ impl ABIItem {
         project_path: &Path,
         contract: &Contract,
     ) -> Result<Vec<ABIItem>, ReadAbiError> {
-        let full_path = get_full_path(project_path, &contract.abi)
+        let abi_str = contract
+            .read_abi(project_path)
             .map_err(|_| ReadAbiError::AbiPathDoesNotExist(contract.abi.clone()))?;
-        let abi_str = fs::read_to_string(full_path)?;
-        let abi_items: Vec<ABIItem> = serde_json::from_str(&abi_str)?;
+        let mut abi_items: Vec<ABIItem> = serde_json::from_str(&abi_str)?;
+        if contract.rename_overloaded_events {
+            ABIItem::apply_overloaded_event_names(&mut abi_items, contract)?;
+        }
 
         let filtered_abi_items = match &contract.include_events {
             Some(events) => abi_items
                 .into_iter()
-                .filter(|item| item.type_ != "event" || events.contains(&item.name))
+                .filter(|item| {
+                    item.type_ != "event" ||
+                        events.contains(&item.name) ||
+                        item.abi_name.as_ref().is_some_and(|name| events.contains(name))
+                })
                 .collect(),
             None => abi_items,
         };
 
+        let filtered_abi_items = match &contract.exclude_events {
+            Some(events) => filtered_abi_items
+                .into_iter()
+                .filter(|item| {
+                    item.type_ != "event" ||
+                        !(events.contains(&item.name) ||
+                            item.abi_name.as_ref().is_some_and(|name| events.contains(name)))
+                })
+                .collect(),
+            None => filtered_abi_items,
+        };
+
         Ok(filtered_abi_items)
     }
 
@@ -244,6 +360,7 @@ impl ABIItem {
 pub struct EventInfo {
     pub name: String,
     pub inputs: Vec<ABIInput>,
+    abi_name: String,
     signature: String,
     struct_result: String,
     struct_data: String,
@@ -259,16 +376,28 @@ impl EventInfo {
     pub fn new(item: ABIItem, signature: String) -> Self {
         let struct_result = format!("{}Result", item.name);
         let struct_data = format!("{}Data", item.name);
-        EventInfo { name: item.name, inputs: item.inputs, signature, struct_result, struct_data }
+        EventInfo {
+            abi_name: item.abi_name.unwrap_or_else(|| item.name.clone()),
+            name: item.name,
+            inputs: item.inputs,
+            signature,
+            struct_result,
+            struct_data,
+        }
+    }
+
+    /// The canonical signature topic0 is hashed from, e.g. `Transfer(address,address,uint256)`
+    pub fn event_signature(&self) -> String {
+        format!("{}({})", self.abi_name, self.signature)
     }
 
     pub fn topic_id(&self) -> H256 {
-        let event_signature = format!("{}({})", self.name, self.signature);
+        let event_signature = format!("{}({})", self.abi_name, self.signature);
         H256::from_slice(&keccak256(event_signature))
     }
 
     pub fn topic_id_as_hex_string(&self) -> String {
-        let event_signature = format!("{}({})", self.name, self.signature);
+        let event_signature = format!("{}({})", self.abi_name, self.signature);
         Map::collect(keccak256(event_signature).iter().map(|byte| format!("{:02x}", byte)))
     }
 
@@ -404,3 +533,41 @@ pub fn get_abi_item_with_db_map(
         ))),
     }
 }
+
+/// Columns rindexer adds to every event table and the solidity type of what they hold
+const INJECTED_EVENT_PARAMETERS: [(&str, &str); 7] = [
+    ("contract_address", "address"),
+    ("tx_hash", "bytes32"),
+    ("block_number", "uint64"),
+    ("block_hash", "bytes32"),
+    ("network", "string"),
+    ("tx_index", "uint64"),
+    ("log_index", "uint256"),
+];
+
+/// Like `get_abi_item_with_db_map` but also resolves injected parameters such as `network` or
+/// `contract_address`, event inputs take precedence
+pub fn get_abi_item_or_injected_with_db_map(
+    abi_items: &[ABIItem],
+    event_name: &str,
+    name: &str,
+) -> Result<GetAbiItemWithDbMap, GetAbiItemWithDbMapError> {
+    let result =
+        get_abi_item_with_db_map(abi_items, event_name, &name.split('.').collect::<Vec<&str>>());
+    if result.is_ok() {
+        return result;
+    }
+
+    match INJECTED_EVENT_PARAMETERS.iter().find(|(injected, _)| *injected == name) {
+        Some((injected, type_)) => Ok(GetAbiItemWithDbMap {
+            abi_item: ABIInput {
+                indexed: None,
+                name: injected.to_string(),
+                type_: type_.to_string(),
+                components: None,
+            },
+            db_column_name: injected.to_string(),
+        }),
+        None => result,
+    }
+}
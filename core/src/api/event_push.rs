@@ -0,0 +1,1027 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use ethers::{
+    abi::{Abi, Event},
+    types::{H256, U256, U64},
+};
+use futures::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::{
+    io::AsyncWriteExt,
+    net::TcpStream,
+    sync::broadcast::{self, error::RecvError},
+};
+use tokio_tungstenite::{
+    tungstenite::{handshake::derive_accept_key, protocol::Role, Message},
+    WebSocketStream,
+};
+use tracing::{debug, error, info};
+
+use crate::{
+    abi::{ABIInput, ABIItem, ParamTypeError, ReadAbiError},
+    api::http::{read_request, serve, write_response, HttpRequest},
+    database::postgres::sql_type_wrapper::{
+        map_ethereum_wrapper_to_json, map_log_params_to_ethereum_wrapper,
+    },
+    event::{
+        callback_registry::{EventCallbackRegistry, EventCallbackRegistryInformation, EventResult},
+        WhereExpression, WhereExpressionError,
+    },
+    indexer::parse_log,
+    manifest::{core::Manifest, push::PushSettings},
+    streams::EventRowsReader,
+    PostgresClient,
+};
+
+const SSE_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Rows read from postgres at a time when a client connects with `from_block` or resumes
+const BACKFILL_PAGE_SIZE: usize = 1_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum StartEventPushServerError {
+    #[error("Could not bind event push server to port {0}: {1}")]
+    CouldNotBind(u16, std::io::Error),
+
+    #[error("Could not read ABI items: {0}")]
+    CouldNotReadAbiItems(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
+
+    #[error("Could not read ABI {0}")]
+    CouldNotReadAbi(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PushFilterError {
+    #[error("from_block {0} is not a block number")]
+    InvalidFromBlock(String),
+
+    #[error("where is invalid: {0}")]
+    InvalidWhere(#[from] WhereExpressionError),
+}
+
+/// A decoded event as it is written by the indexer
+#[derive(Debug, Clone, Serialize)]
+pub struct PushedEvent {
+    pub contract_name: String,
+    pub event_name: String,
+    pub network: String,
+    #[serde(skip)]
+    pub block_number: U64,
+    #[serde(skip)]
+    pub log_index: U256,
+    pub data: Value,
+}
+
+/// Fans events out from the write pipeline to every connected client
+pub struct EventPushHub {
+    sender: broadcast::Sender<Arc<PushedEvent>>,
+}
+
+impl EventPushHub {
+    pub fn new(buffer: usize) -> Self {
+        let (sender, _) = broadcast::channel(buffer.max(1));
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: PushedEvent) {
+        // no one listening is not an error
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<PushedEvent>> {
+        self.sender.subscribe()
+    }
+}
+
+/// The query string a client connects with, `network`, `from_block`, `where` and `token` are
+/// reserved and any other key has to equal the event field at that dotted path
+#[derive(Debug, Default)]
+pub struct PushFilter {
+    pub network: Option<String>,
+    pub from_block: Option<u64>,
+    condition: Option<WhereExpression>,
+    equals: Vec<(Vec<String>, String)>,
+}
+
+fn json_path_text(value: &Value, path: &[String]) -> Option<String> {
+    match path.iter().try_fold(value, |value, key| value.get(key))? {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+impl PushFilter {
+    pub fn parse(query: &str) -> Result<Self, PushFilterError> {
+        let mut filter = PushFilter::default();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "network" => filter.network = Some(value.into_owned()),
+                "from_block" => {
+                    filter.from_block = Some(
+                        value
+                            .parse()
+                            .map_err(|_| PushFilterError::InvalidFromBlock(value.into_owned()))?,
+                    )
+                }
+                "where" => filter.condition = Some(WhereExpression::parse(&value)?),
+                "token" => {}
+                _ => filter
+                    .equals
+                    .push((key.split('.').map(str::to_string).collect(), value.into_owned())),
+            }
+        }
+
+        Ok(filter)
+    }
+
+    pub fn matches(&self, network: &str, data: &Value) -> bool {
+        if self.network.as_ref().is_some_and(|n| n != network) {
+            return false;
+        }
+
+        let input_value = |path: &[String]| json_path_text(data, path);
+        let equals = self.equals.iter().all(|(path, expected)| {
+            input_value(path).is_some_and(|value| {
+                // addresses and hashes are checksummed differently depending on the writer
+                if value.starts_with("0x") {
+                    value.eq_ignore_ascii_case(expected)
+                } else {
+                    &value == expected
+                }
+            })
+        });
+
+        match &self.condition {
+            Some(condition) => equals && condition.matches(&input_value),
+            None => equals,
+        }
+    }
+}
+
+/// Decodes the logs given to rust project handlers
+pub(crate) struct PushDecoder {
+    topic_id: H256,
+    abi_event: Event,
+    inputs: Vec<ABIInput>,
+    jsonb_inputs: Vec<String>,
+}
+
+impl PushDecoder {
+    /// The json of every log the handler was given in the shape no-code projects push
+    pub(crate) fn pushed_events(
+        &self,
+        contract_name: &str,
+        event_name: &str,
+        results: &[EventResult],
+    ) -> Vec<PushedEvent> {
+        results
+            .iter()
+            .filter(|result| !result.tx_information.removed)
+            .filter_map(|result| {
+                let log = parse_log(&self.abi_event, &result.log)?;
+                let wrappers = map_log_params_to_ethereum_wrapper(
+                    &self.inputs,
+                    &log.params,
+                    &self.jsonb_inputs,
+                )
+                .ok()?;
+                Some(PushedEvent {
+                    contract_name: contract_name.to_string(),
+                    event_name: event_name.to_string(),
+                    network: result.tx_information.network.clone(),
+                    block_number: result.tx_information.block_number,
+                    log_index: result.tx_information.log_index,
+                    data: map_ethereum_wrapper_to_json(
+                        &self.inputs,
+                        &wrappers,
+                        &result.tx_information,
+                        false,
+                    ),
+                })
+            })
+            .collect()
+    }
+}
+
+struct PushEvent {
+    reader: EventRowsReader,
+    networks: Vec<String>,
+    decoder: Arc<PushDecoder>,
+}
+
+type PushEvents = HashMap<(String, String), PushEvent>;
+
+fn push_events(
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<PushEvents, StartEventPushServerError> {
+    let mut events = HashMap::new();
+    for contract in &manifest.contracts {
+        let mut contract = contract.clone();
+        let is_filter = contract.identify_and_modify_filter();
+        let abi_items = ABIItem::get_abi_items(project_path, &contract, is_filter)?;
+        let event_infos = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
+        let abi: Abi = contract
+            .read_abi(project_path)
+            .ok()
+            .and_then(|abi| serde_json::from_str(&abi).ok())
+            .ok_or_else(|| StartEventPushServerError::CouldNotReadAbi(contract.abi.clone()))?;
+
+        let mut networks: Vec<String> =
+            contract.details.iter().map(|detail| detail.network.clone()).collect();
+        networks.sort();
+        networks.dedup();
+
+        for event_info in &event_infos {
+            let topic_id = event_info.topic_id();
+            let Some(mut abi_event) = abi.events().find(|e| e.signature() == topic_id).cloned()
+            else {
+                continue;
+            };
+            if let Some(layout) = contract.anonymous_event_layout(&event_info.name) {
+                abi_event.anonymous = true;
+                for input in abi_event.inputs.iter_mut() {
+                    input.indexed = layout.topics.contains(&input.name);
+                }
+            }
+
+            events.insert(
+                (contract.name.clone(), event_info.name.clone()),
+                PushEvent {
+                    reader: EventRowsReader::new(manifest, &contract, event_info),
+                    networks: networks.clone(),
+                    decoder: Arc::new(PushDecoder {
+                        topic_id,
+                        abi_event,
+                        inputs: event_info.inputs.clone(),
+                        jsonb_inputs: contract.jsonb_inputs(&event_info.name, &event_info.inputs),
+                    }),
+                },
+            );
+        }
+    }
+
+    Ok(events)
+}
+
+/// The last position sent on every network, it is the sse event id so a client that reconnects
+/// with `Last-Event-ID` resumes right after it
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PushCursor(BTreeMap<String, (U64, U256)>);
+
+impl PushCursor {
+    /// Parses `{network}:{block}:{log_index}` pairs separated by commas
+    pub fn parse(id: &str) -> Option<Self> {
+        id.split(',')
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let mut parts = part.rsplitn(3, ':');
+                let log_index = U256::from_dec_str(parts.next()?).ok()?;
+                let block_number = U64::from_dec_str(parts.next()?).ok()?;
+                Some((parts.next()?.to_string(), (block_number, log_index)))
+            })
+            .collect::<Option<BTreeMap<_, _>>>()
+            .map(PushCursor)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Moves past the position returning false if it was already passed
+    fn advance(&mut self, network: &str, position: (U64, U256)) -> bool {
+        match self.0.get_mut(network) {
+            Some(last) if position <= *last => false,
+            Some(last) => {
+                *last = position;
+                true
+            }
+            None => {
+                self.0.insert(network.to_string(), position);
+                true
+            }
+        }
+    }
+}
+
+impl fmt::Display for PushCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let positions: Vec<String> = self
+            .0
+            .iter()
+            .map(|(network, (block_number, log_index))| {
+                format!("{}:{}:{}", network, block_number, log_index)
+            })
+            .collect();
+        write!(f, "{}", positions.join(","))
+    }
+}
+
+/// What one connection asked for and how far it has got
+struct Subscription<'a> {
+    contract_name: String,
+    event_name: String,
+    push_event: &'a PushEvent,
+    filter: PushFilter,
+    cursor: PushCursor,
+}
+
+impl<'a> Subscription<'a> {
+    /// Works out the contract, event and filter from `/{transport}/{contract}/{event}?{filters}`
+    fn route(
+        request: &HttpRequest,
+        transport: &str,
+        events: &'a PushEvents,
+        cursor: PushCursor,
+    ) -> Result<Self, (&'static str, String)> {
+        let segments: Vec<&str> = request.path().trim_matches('/').split('/').collect();
+        let (contract_name, event_name) = match segments.as_slice() {
+            [prefix, contract_name, event_name] if *prefix == transport => {
+                (contract_name.to_string(), event_name.to_string())
+            }
+            _ => {
+                return Err((
+                    "404 Not Found",
+                    format!("Connect to /{}/{{contract}}/{{event}}", transport),
+                ))
+            }
+        };
+        let Some(push_event) = events.get(&(contract_name.clone(), event_name.clone())) else {
+            return Err((
+                "404 Not Found",
+                format!("{}::{} is not indexed", contract_name, event_name),
+            ));
+        };
+
+        let filter =
+            PushFilter::parse(request.query()).map_err(|e| ("400 Bad Request", e.to_string()))?;
+
+        Ok(Self { contract_name, event_name, push_event, filter, cursor })
+    }
+
+    /// Whether the event is for this subscription and not sent yet, the cursor moves past it either
+    /// way so filtered events are not read again on resume
+    fn accepts(&mut self, event: &PushedEvent) -> bool {
+        event.contract_name == self.contract_name &&
+            event.event_name == self.event_name &&
+            self.cursor.advance(&event.network, (event.block_number, event.log_index)) &&
+            self.filter.matches(&event.network, &event.data)
+    }
+
+    /// Events already written from `from_block`, networks the cursor has seen resume right after
+    /// it, `None` when the client only wants new events
+    fn backfill<'p>(
+        &self,
+        postgres: Option<&'p PostgresClient>,
+    ) -> Result<Option<Backfill<'p>>, String> {
+        if self.filter.from_block.is_none() && self.cursor.is_empty() {
+            return Ok(None);
+        }
+        let Some(postgres) = postgres else {
+            return Err("from_block and resuming need postgres storage enabled".to_string());
+        };
+
+        let networks = self
+            .push_event
+            .networks
+            .iter()
+            .filter(|network| self.filter.network.as_ref().is_none_or(|n| n == *network))
+            .filter_map(|network| match self.cursor.0.get(network) {
+                Some(position) => Some((network.clone(), position.0.as_u64(), Some(*position))),
+                None => {
+                    self.filter.from_block.map(|from_block| (network.clone(), from_block, None))
+                }
+            })
+            .collect();
+
+        Ok(Some(Backfill { postgres, networks, page_size: BACKFILL_PAGE_SIZE }))
+    }
+}
+
+/// Network, from block and the last position read on it
+type BackfillNetwork = (String, u64, Option<(U64, U256)>);
+
+/// Reads the backfill of a subscription one page at a time so a client asking for all of history
+/// does not hold every row in memory
+struct Backfill<'p> {
+    postgres: &'p PostgresClient,
+    networks: VecDeque<BackfillNetwork>,
+    page_size: usize,
+}
+
+impl Backfill<'_> {
+    /// The next events to send, `None` once every network has been read up to the head
+    async fn next_page(
+        &mut self,
+        subscription: &mut Subscription<'_>,
+    ) -> Result<Option<Vec<PushedEvent>>, String> {
+        while let Some((network, from_block, after)) = self.networks.front_mut() {
+            let rows = subscription
+                .push_event
+                .reader
+                .read_page(self.postgres, network, *from_block, *after, self.page_size)
+                .await
+                .map_err(|e| {
+                    error!(
+                        "{}::{} - Error reading backfill: {}",
+                        subscription.contract_name, subscription.event_name, e
+                    );
+                    "Could not read backfill".to_string()
+                })?;
+            if rows.is_empty() {
+                self.networks.pop_front();
+                continue;
+            }
+
+            let network = network.clone();
+            *after = rows
+                .last()
+                .map(|(tx_information, _)| (tx_information.block_number, tx_information.log_index));
+            let mut events = Vec::with_capacity(rows.len());
+            for (tx_information, data) in rows {
+                let event = PushedEvent {
+                    contract_name: subscription.contract_name.clone(),
+                    event_name: subscription.event_name.clone(),
+                    network: network.clone(),
+                    block_number: tx_information.block_number,
+                    log_index: tx_information.log_index,
+                    data,
+                };
+                if subscription.accepts(&event) {
+                    events.push(event);
+                }
+            }
+
+            return Ok(Some(events));
+        }
+
+        Ok(None)
+    }
+}
+
+struct PushServer {
+    events: PushEvents,
+    postgres: Option<Arc<PostgresClient>>,
+    hub: Arc<EventPushHub>,
+    token: String,
+    allowed_origins: Vec<String>,
+}
+
+impl PushServer {
+    /// Browsers can not set headers on websockets or event sources so the token can also be sent
+    /// as the `token` query param
+    fn authorize(&self, request: &HttpRequest) -> Result<(), (&'static str, String)> {
+        if let Some(origin) = request.header("origin") {
+            if !self.allowed_origins.iter().any(|allowed| allowed == origin) {
+                return Err(("403 Forbidden", format!("Origin {} is not allowed", origin)));
+            }
+        }
+
+        let token = request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string)
+            .or_else(|| {
+                url::form_urlencoded::parse(request.query().as_bytes())
+                    .find(|(key, _)| key == "token")
+                    .map(|(_, value)| value.into_owned())
+            });
+        match token {
+            Some(token) if token_matches(&self.token, &token) => Ok(()),
+            _ => Err(("401 Unauthorized", "A valid token is required".to_string())),
+        }
+    }
+}
+
+fn token_matches(expected: &str, token: &str) -> bool {
+    expected.len() == token.len() &&
+        expected.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn event_message(event: &PushedEvent) -> Message {
+    Message::Text(json!(event).to_string())
+}
+
+fn error_message(message: String) -> Message {
+    Message::Text(json!({ "error": message }).to_string())
+}
+
+async fn push_to_websocket(
+    mut stream: TcpStream,
+    request: HttpRequest,
+    mut subscription: Subscription<'_>,
+    server: &PushServer,
+) {
+    let Some(key) = request
+        .header("upgrade")
+        .filter(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+        .and(request.header("sec-websocket-key"))
+    else {
+        write_response(
+            &mut stream,
+            "400 Bad Request",
+            "text/plain",
+            "Expected a websocket upgrade",
+        )
+        .await;
+        return;
+    };
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    );
+    if stream.write_all(handshake.as_bytes()).await.is_err() {
+        return;
+    }
+    let socket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+
+    // subscribe before the backfill so nothing written while it runs is missed
+    let mut receiver = server.hub.subscribe();
+    let (mut sink, mut source) = socket.split();
+
+    match subscription.backfill(server.postgres.as_deref()) {
+        Ok(Some(mut backfill)) => loop {
+            match backfill.next_page(&mut subscription).await {
+                Ok(Some(events)) => {
+                    for event in events {
+                        if sink.send(event_message(&event)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(message) => {
+                    let _ = sink.send(error_message(message)).await;
+                    let _ = sink.close().await;
+                    return;
+                }
+            }
+        },
+        Ok(None) => {}
+        Err(message) => {
+            let _ = sink.send(error_message(message)).await;
+            let _ = sink.close().await;
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            message = source.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    if subscription.accepts(&event) && sink.send(event_message(&event)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    let _ = sink
+                        .send(error_message(format!(
+                            "Fell {} events behind, reconnect with from_block to catch up",
+                            skipped
+                        )))
+                        .await;
+                    let _ = sink.close().await;
+                    break;
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+fn sse_event(subscription: &Subscription, event: &PushedEvent) -> String {
+    format!("id: {}\ndata: {}\n\n", subscription.cursor, json!(event))
+}
+
+fn sse_error(message: String) -> String {
+    format!("event: error\ndata: {}\n\n", json!({ "error": message }))
+}
+
+async fn push_to_sse(
+    mut stream: TcpStream,
+    request: HttpRequest,
+    mut subscription: Subscription<'_>,
+    server: &PushServer,
+) {
+    // subscribe before the backfill so nothing written while it runs is missed
+    let mut receiver = server.hub.subscribe();
+    let allow_origin = request.header("origin").map_or_else(String::new, |origin| {
+        format!("Access-Control-Allow-Origin: {}\r\nVary: Origin\r\n", origin)
+    });
+    let headers = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n{}\r\n",
+        allow_origin
+    );
+    if stream.write_all(headers.as_bytes()).await.is_err() {
+        return;
+    }
+
+    match subscription.backfill(server.postgres.as_deref()) {
+        Ok(Some(mut backfill)) => loop {
+            match backfill.next_page(&mut subscription).await {
+                Ok(Some(events)) => {
+                    for event in events {
+                        if stream
+                            .write_all(sse_event(&subscription, &event).as_bytes())
+                            .await
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(message) => {
+                    let _ = stream.write_all(sse_error(message).as_bytes()).await;
+                    return;
+                }
+            }
+        },
+        Ok(None) => {}
+        Err(message) => {
+            let _ = stream.write_all(sse_error(message).as_bytes()).await;
+            return;
+        }
+    }
+
+    // comments keep proxies from timing the stream out and tell us when the client has gone
+    let mut keep_alive = tokio::time::interval(SSE_KEEP_ALIVE);
+    loop {
+        let message = tokio::select! {
+            _ = keep_alive.tick() => ": keep-alive\n\n".to_string(),
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    if !subscription.accepts(&event) {
+                        continue;
+                    }
+                    sse_event(&subscription, &event)
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    let _ = stream
+                        .write_all(
+                            sse_error(format!(
+                                "Fell {} events behind, reconnect to resume",
+                                skipped
+                            ))
+                            .as_bytes(),
+                        )
+                        .await;
+                    break;
+                }
+                Err(RecvError::Closed) => break,
+            }
+        };
+        if stream.write_all(message.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn push_to_connection(mut stream: TcpStream, server: Arc<PushServer>) {
+    let Some(request) = read_request(&mut stream, 0).await else {
+        return;
+    };
+    if let Err((status, message)) = server.authorize(&request) {
+        debug!("Event push connection refused: {}", message);
+        write_response(&mut stream, status, "text/plain", message).await;
+        return;
+    }
+
+    let is_sse = request.path().starts_with("/sse/");
+    let cursor = match is_sse {
+        true => request.header("last-event-id").and_then(PushCursor::parse).unwrap_or_default(),
+        false => PushCursor::default(),
+    };
+    let transport = if is_sse { "sse" } else { "ws" };
+    let subscription = match Subscription::route(&request, transport, &server.events, cursor) {
+        Ok(subscription) => subscription,
+        Err((status, message)) => {
+            write_response(&mut stream, status, "text/plain", message).await;
+            return;
+        }
+    };
+
+    if is_sse {
+        push_to_sse(stream, request, subscription, &server).await;
+    } else {
+        push_to_websocket(stream, request, subscription, &server).await;
+    }
+}
+
+async fn serve_event_push(
+    events: PushEvents,
+    settings: &PushSettings,
+    postgres: Option<Arc<PostgresClient>>,
+    hub: Arc<EventPushHub>,
+) -> Result<(), StartEventPushServerError> {
+    let server = Arc::new(PushServer {
+        events,
+        postgres,
+        hub,
+        token: settings.token.clone(),
+        allowed_origins: settings.allowed_origins.clone(),
+    });
+
+    serve("Event push server", &settings.host, settings.port, move |stream| {
+        push_to_connection(stream, Arc::clone(&server))
+    })
+    .await
+    .map_err(|e| StartEventPushServerError::CouldNotBind(settings.port, e))?;
+
+    info!(
+        "Event push running on: ws://{0}:{1}/ws/{{contract}}/{{event}} and http://{0}:{1}/sse/{{contract}}/{{event}}",
+        settings.host, settings.port
+    );
+
+    Ok(())
+}
+
+/// Serves `/ws/{contract}/{event}` and `/sse/{contract}/{event}` streaming every decoded event
+/// published to the hub as json
+pub async fn start_event_push_server(
+    project_path: &Path,
+    manifest: &Manifest,
+    settings: &PushSettings,
+    postgres: Option<Arc<PostgresClient>>,
+    hub: Arc<EventPushHub>,
+) -> Result<(), StartEventPushServerError> {
+    serve_event_push(push_events(project_path, manifest)?, settings, postgres, hub).await
+}
+
+/// The decoder of every event in the manifest keyed by contract and event name, only used for
+/// registry events whose topic id matches
+pub(crate) fn registry_event_decoders(
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<HashMap<(String, String), Arc<PushDecoder>>, StartEventPushServerError> {
+    Ok(push_events(project_path, manifest)?
+        .into_iter()
+        .map(|(key, push_event)| (key, push_event.decoder))
+        .collect())
+}
+
+impl PushDecoder {
+    pub(crate) fn decodes(&self, event: &EventCallbackRegistryInformation) -> bool {
+        self.topic_id == event.topic_id
+    }
+}
+
+/// Starts the push server for a rust project, every event is pushed once its handler has
+/// succeeded
+pub async fn start_registry_event_push(
+    project_path: &Path,
+    manifest: &Manifest,
+    settings: &PushSettings,
+    postgres: Option<Arc<PostgresClient>>,
+    registry: &mut EventCallbackRegistry,
+) -> Result<(), StartEventPushServerError> {
+    let events = push_events(project_path, manifest)?;
+    let hub = Arc::new(EventPushHub::new(settings.buffer));
+
+    for event in registry.events.iter_mut() {
+        let Some(decoder) = events
+            .get(&(event.contract.name.clone(), event.event_name.clone()))
+            .map(|push_event| Arc::clone(&push_event.decoder))
+            .filter(|decoder| decoder.decodes(event))
+        else {
+            debug!("{} is not pushed as its ABI event was not found", event.info_log_name());
+            continue;
+        };
+
+        let callback = Arc::clone(&event.callback);
+        let hub = Arc::clone(&hub);
+        let contract_name = event.contract.name.clone();
+        let event_name = event.event_name.clone();
+        event.callback = Arc::new(move |results| {
+            let pushed_events = decoder.pushed_events(&contract_name, &event_name, &results);
+            let callback = Arc::clone(&callback);
+            let hub = Arc::clone(&hub);
+            Box::pin(async move {
+                callback(results).await?;
+                for pushed_event in pushed_events {
+                    hub.publish(pushed_event);
+                }
+                Ok(())
+            })
+        });
+    }
+
+    serve_event_push(events, settings, postgres, hub).await
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_filter_matches() {
+        let data = json!({
+            "from": "0xAbC0000000000000000000000000000000000001",
+            "value": "1000",
+            "transaction_information": { "block_number": 12 }
+        });
+
+        let filter =
+            PushFilter::parse("network=ethereum&from=0xabc0000000000000000000000000000000000001")
+                .unwrap();
+        assert!(filter.matches("ethereum", &data));
+        assert!(!filter.matches("base", &data));
+
+        let filter = PushFilter::parse("transaction_information.block_number=13").unwrap();
+        assert!(!filter.matches("ethereum", &data));
+
+        let filter = PushFilter::parse("from_block=10&where=value%20%3E%20999").unwrap();
+        assert_eq!(filter.from_block, Some(10));
+        assert!(filter.matches("ethereum", &data));
+
+        assert!(PushFilter::parse("from_block=latest").is_err());
+    }
+
+    #[test]
+    fn test_push_cursor() {
+        let mut cursor = PushCursor::parse("base:20:1,ethereum:10:3").unwrap();
+        assert_eq!(cursor.to_string(), "base:20:1,ethereum:10:3");
+
+        assert!(!cursor.advance("ethereum", (U64::from(10), U256::from(3))));
+        assert!(cursor.advance("ethereum", (U64::from(10), U256::from(4))));
+        assert!(cursor.advance("polygon", (U64::from(1), U256::zero())));
+        assert_eq!(cursor.to_string(), "base:20:1,ethereum:10:4,polygon:1:0");
+
+        assert_eq!(PushCursor::parse(""), Some(PushCursor::default()));
+        assert_eq!(PushCursor::parse("ethereum:ten:1"), None);
+    }
+
+    fn push_server(events: PushEvents) -> PushServer {
+        PushServer {
+            events,
+            postgres: None,
+            hub: Arc::new(EventPushHub::new(1)),
+            token: "secret".to_string(),
+            allowed_origins: vec!["https://app.example".to_string()],
+        }
+    }
+
+    fn request(target: &str, headers: &[(&str, &str)]) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            target: target.to_string(),
+            headers: headers.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+            body: vec![],
+        }
+    }
+
+    #[test]
+    fn test_authorize() {
+        let server = push_server(HashMap::new());
+
+        assert!(server
+            .authorize(&request("/ws/Erc20/Transfer", &[("Authorization", "Bearer secret")]))
+            .is_ok());
+        assert!(server.authorize(&request("/sse/Erc20/Transfer?token=secret", &[])).is_ok());
+        assert!(server
+            .authorize(&request(
+                "/sse/Erc20/Transfer?token=secret",
+                &[("Origin", "https://app.example")]
+            ))
+            .is_ok());
+
+        assert_eq!(
+            server.authorize(&request("/ws/Erc20/Transfer", &[])).unwrap_err().0,
+            "401 Unauthorized"
+        );
+        assert_eq!(
+            server.authorize(&request("/ws/Erc20/Transfer?token=secrets", &[])).unwrap_err().0,
+            "401 Unauthorized"
+        );
+        assert_eq!(
+            server
+                .authorize(&request(
+                    "/ws/Erc20/Transfer?token=secret",
+                    &[("Origin", "https://evil.example")]
+                ))
+                .unwrap_err()
+                .0,
+            "403 Forbidden"
+        );
+        assert!(PushFilter::parse("token=secret").unwrap().matches("ethereum", &json!({})));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database in DATABASE_URL"]
+    async fn test_backfill_reads_pages() {
+        let postgres = PostgresClient::new().await.expect("DATABASE_URL has to point to postgres");
+        let manifest: Manifest = serde_yaml::from_str(
+            r#"
+name: PushPaging
+project_type: no-code
+networks: []
+storage:
+  postgres:
+    enabled: true
+contracts:
+  - name: Counter
+    abi: ./abis/Counter.json
+    details:
+      - network: ethereum
+        address: "0x0000000000000000000000000000000000000001"
+"#,
+        )
+        .unwrap();
+        let item: ABIItem = serde_json::from_value(json!({
+            "type": "event",
+            "name": "Ticked",
+            "inputs": [{ "name": "count", "type": "uint256", "indexed": false }]
+        }))
+        .unwrap();
+        let event_info = crate::abi::EventInfo::new(item, "Ticked(uint256)".to_string());
+        let reader = EventRowsReader::new(&manifest, &manifest.contracts[0], &event_info);
+        let table_name = reader.table_name("ethereum");
+        let schema = table_name.split('.').next().unwrap();
+        postgres
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}; CREATE TABLE {1} (count NUMERIC, contract_address TEXT, tx_hash TEXT, block_number NUMERIC, block_hash TEXT, network TEXT, tx_index NUMERIC, log_index TEXT);
+                 INSERT INTO {1} VALUES
+                    (1, '0x01', '0x01', 10, '0x01', 'ethereum', 0, '0'),
+                    (2, '0x01', '0x01', 10, '0x01', 'ethereum', 0, '2'),
+                    (3, '0x01', '0x01', 11, '0x01', 'ethereum', 0, '10'),
+                    (4, '0x01', '0x01', 12, '0x01', 'ethereum', 0, '1'),
+                    (5, '0x01', '0x01', 12, '0x01', 'ethereum', 0, '9')",
+                schema, table_name
+            ))
+            .await
+            .unwrap();
+
+        let mut events = HashMap::new();
+        events.insert(
+            ("Counter".to_string(), "Ticked".to_string()),
+            PushEvent {
+                reader,
+                networks: vec!["ethereum".to_string()],
+                decoder: Arc::new(PushDecoder {
+                    topic_id: H256::zero(),
+                    abi_event: Event {
+                        name: "Ticked".to_string(),
+                        inputs: vec![],
+                        anonymous: false,
+                    },
+                    inputs: vec![],
+                    jsonb_inputs: vec![],
+                }),
+            },
+        );
+        let counts = |pages: &[Vec<PushedEvent>]| -> Vec<Vec<String>> {
+            pages
+                .iter()
+                .map(|page| page.iter().map(|event| event.data["count"].to_string()).collect())
+                .collect()
+        };
+
+        let mut subscription = Subscription::route(
+            &request("/ws/Counter/Ticked?from_block=11", &[]),
+            "ws",
+            &events,
+            PushCursor::default(),
+        )
+        .unwrap();
+        let mut backfill = subscription.backfill(Some(&postgres)).unwrap().unwrap();
+        backfill.page_size = 1;
+        let mut pages = vec![];
+        while let Some(page) = backfill.next_page(&mut subscription).await.unwrap() {
+            pages.push(page);
+        }
+        assert_eq!(counts(&pages), vec![vec!["\"3\""], vec!["\"4\""], vec!["\"5\""]]);
+
+        // resuming reads only what comes after the cursor
+        let mut subscription = Subscription::route(
+            &request("/sse/Counter/Ticked", &[]),
+            "sse",
+            &events,
+            PushCursor::parse("ethereum:10:2").unwrap(),
+        )
+        .unwrap();
+        let mut backfill = subscription.backfill(Some(&postgres)).unwrap().unwrap();
+        backfill.page_size = 2;
+        let mut pages = vec![];
+        while let Some(page) = backfill.next_page(&mut subscription).await.unwrap() {
+            pages.push(page);
+        }
+        assert_eq!(counts(&pages), vec![vec!["\"3\"", "\"4\""], vec!["\"5\""]]);
+        assert_eq!(subscription.cursor.to_string(), "ethereum:12:9");
+
+        postgres.batch_execute(&format!("DROP SCHEMA {} CASCADE", schema)).await.unwrap();
+    }
+}
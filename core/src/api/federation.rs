@@ -0,0 +1,592 @@
+use graphql_parser::query::{
+    parse_query, Definition, Document, Field, OperationDefinition, Query, Selection, SelectionSet,
+    Type, Value as GraphqlValue, VariableDefinition,
+};
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use tokio::sync::OnceCell;
+
+use crate::api::{http::HttpRequest, persisted_queries::forward};
+
+/// Full type information of the schema so it can be printed back as SDL
+const INTROSPECTION_QUERY: &str = r#"
+query FederationIntrospection {
+  __schema {
+    types {
+      kind
+      name
+      fields(includeDeprecated: true) {
+        name
+        args { name type { ...TypeRef } defaultValue }
+        type { ...TypeRef }
+      }
+      inputFields { name type { ...TypeRef } defaultValue }
+      interfaces { name }
+      enumValues(includeDeprecated: true) { name }
+      possibleTypes { name }
+    }
+  }
+}
+
+fragment TypeRef on __Type {
+  kind
+  name
+  ofType { kind name ofType { kind name ofType { kind name ofType { kind name ofType { kind name } } } } }
+}
+"#;
+
+const BUILT_IN_SCALARS: [&str; 5] = ["String", "Int", "Float", "Boolean", "ID"];
+
+const ROOT_TYPES: [&str; 3] = ["Query", "Mutation", "Subscription"];
+
+/// Every table type implements `Node` so entities are keyed by the global `nodeId`
+const ENTITY_KEY: &str = "nodeId";
+
+const ENTITY_ALIAS_PREFIX: &str = "_entity";
+
+fn type_ref(type_: &Value) -> String {
+    let of_type = || type_ref(&type_["ofType"]);
+    match type_["kind"].as_str() {
+        Some("NON_NULL") => format!("{}!", of_type()),
+        Some("LIST") => format!("[{}]", of_type()),
+        _ => type_["name"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+fn field_sdl(field: &Value) -> String {
+    let mut sdl = format!("  {}", field["name"].as_str().unwrap_or_default());
+    let args: Vec<String> = field["args"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|arg| {
+            let mut arg_sdl =
+                format!("{}: {}", arg["name"].as_str().unwrap_or_default(), type_ref(&arg["type"]));
+            if let Some(default) = arg["defaultValue"].as_str() {
+                arg_sdl.push_str(&format!(" = {}", default));
+            }
+            arg_sdl
+        })
+        .collect();
+    if !args.is_empty() {
+        sdl.push_str(&format!("({})", args.join(", ")));
+    }
+    sdl.push_str(&format!(": {}", type_ref(&field["type"])));
+    if let Some(default) = field["defaultValue"].as_str() {
+        sdl.push_str(&format!(" = {}", default));
+    }
+    sdl
+}
+
+fn names(values: &Value) -> Vec<&str> {
+    values.as_array().into_iter().flatten().filter_map(|value| value["name"].as_str()).collect()
+}
+
+fn block(fields: &Value) -> String {
+    let lines: Vec<String> = fields.as_array().into_iter().flatten().map(field_sdl).collect();
+    format!("{{\n{}\n}}", lines.join("\n"))
+}
+
+/// Prints the introspected schema as the subgraph SDL, table types get an `@key` on `nodeId`
+fn subgraph_sdl(schema: &Value) -> String {
+    let mut definitions = vec![];
+    for type_ in schema["types"].as_array().into_iter().flatten() {
+        let name = type_["name"].as_str().unwrap_or_default();
+        if name.starts_with("__") || BUILT_IN_SCALARS.contains(&name) {
+            continue;
+        }
+
+        let definition = match type_["kind"].as_str().unwrap_or_default() {
+            "SCALAR" => format!("scalar {}", name),
+            "OBJECT" | "INTERFACE" => {
+                let interfaces = names(&type_["interfaces"]);
+                let mut head = format!(
+                    "{} {}",
+                    if type_["kind"] == "OBJECT" { "type" } else { "interface" },
+                    name
+                );
+                if !interfaces.is_empty() {
+                    head.push_str(&format!(" implements {}", interfaces.join(" & ")));
+                }
+                if interfaces.contains(&"Node") && !ROOT_TYPES.contains(&name) {
+                    head.push_str(&format!(" @key(fields: \"{}\")", ENTITY_KEY));
+                }
+                format!("{} {}", head, block(&type_["fields"]))
+            }
+            "UNION" => format!("union {} = {}", name, names(&type_["possibleTypes"]).join(" | ")),
+            "ENUM" => {
+                format!("enum {} {{\n  {}\n}}", name, names(&type_["enumValues"]).join("\n  "))
+            }
+            "INPUT_OBJECT" => format!("input {} {}", name, block(&type_["inputFields"])),
+            _ => continue,
+        };
+        definitions.push(definition);
+    }
+
+    format!("{}\n", definitions.join("\n\n"))
+}
+
+fn graphql_value_to_json(value: &GraphqlValue<'static, String>, variables: &Value) -> Value {
+    match value {
+        GraphqlValue::Variable(name) => variables.get(name).cloned().unwrap_or(Value::Null),
+        GraphqlValue::Int(number) => number.as_i64().map_or(Value::Null, Value::from),
+        GraphqlValue::Float(number) => json!(number),
+        GraphqlValue::String(string) | GraphqlValue::Enum(string) => json!(string),
+        GraphqlValue::Boolean(boolean) => json!(boolean),
+        GraphqlValue::Null => Value::Null,
+        GraphqlValue::List(values) => {
+            values.iter().map(|value| graphql_value_to_json(value, variables)).collect()
+        }
+        GraphqlValue::Object(values) => values
+            .iter()
+            .map(|(name, value)| (name.clone(), graphql_value_to_json(value, variables)))
+            .collect::<Map<_, _>>()
+            .into(),
+    }
+}
+
+fn response_key(field: &Field<'static, String>) -> String {
+    field.alias.clone().unwrap_or_else(|| field.name.clone())
+}
+
+/// Root fields of a subgraph operation the graphql server does not know about
+#[derive(Debug, PartialEq)]
+enum FederatedOperation {
+    /// `_service { sdl }`, the response keys of the `sdl` and `__typename` fields selected
+    Service { response_key: String, selection: Vec<(String, String)> },
+    /// `_entities` rewritten to a `node` lookup per representation
+    Entities { response_key: String, typenames: Vec<Option<String>>, body: Value },
+}
+
+fn selected_operation<'d>(
+    document: &'d mut Document<'static, String>,
+    operation_name: Option<&str>,
+) -> Option<&'d mut OperationDefinition<'static, String>> {
+    let mut operations =
+        document.definitions.iter_mut().filter_map(|definition| match definition {
+            Definition::Operation(operation) => Some(operation),
+            Definition::Fragment(_) => None,
+        });
+    match operation_name {
+        Some(operation_name) => operations.find(|operation| match operation {
+            OperationDefinition::Query(query) => query.name.as_deref() == Some(operation_name),
+            _ => false,
+        }),
+        None => operations.next(),
+    }
+}
+
+/// Finds `_service` or `_entities` in the operation, `Ok(None)` for anything the graphql server
+/// answers itself
+fn federated_operation(operation: &Value) -> Result<Option<FederatedOperation>, String> {
+    let Some(query) = operation.get("query").and_then(Value::as_str) else {
+        return Ok(None);
+    };
+    if !query.contains("_service") && !query.contains("_entities") {
+        return Ok(None);
+    }
+
+    let mut document = parse_query::<String>(query).map_err(|e| e.to_string())?.into_static();
+    let operation_name = operation.get("operationName").and_then(Value::as_str);
+    let variables = operation.get("variables").cloned().unwrap_or_else(|| json!({}));
+    let Some(definition) = selected_operation(&mut document, operation_name) else {
+        return Ok(None);
+    };
+    let query = match definition {
+        OperationDefinition::SelectionSet(selection_set) => {
+            *definition = OperationDefinition::Query(Query {
+                position: selection_set.span.0,
+                name: None,
+                variable_definitions: vec![],
+                directives: vec![],
+                selection_set: selection_set.clone(),
+            });
+            match definition {
+                OperationDefinition::Query(query) => query,
+                _ => return Ok(None),
+            }
+        }
+        OperationDefinition::Query(query) => query,
+        _ => return Ok(None),
+    };
+
+    let fields: Vec<&Field<'static, String>> = query
+        .selection_set
+        .items
+        .iter()
+        .filter_map(|selection| match selection {
+            Selection::Field(field) if field.name != "__typename" => Some(field),
+            _ => None,
+        })
+        .collect();
+    let federated = match fields.as_slice() {
+        [field] if field.name == "_service" || field.name == "_entities" => (*field).clone(),
+        fields
+            if fields.iter().any(|field| field.name == "_service" || field.name == "_entities") =>
+        {
+            return Err("_service and _entities have to be the only field of the query".to_string())
+        }
+        _ => return Ok(None),
+    };
+
+    if federated.name == "_service" {
+        let selection = federated
+            .selection_set
+            .items
+            .iter()
+            .filter_map(|selection| match selection {
+                Selection::Field(field) => Some((response_key(field), field.name.clone())),
+                _ => None,
+            })
+            .collect();
+        return Ok(Some(FederatedOperation::Service {
+            response_key: response_key(&federated),
+            selection,
+        }));
+    }
+
+    let representations = federated
+        .arguments
+        .iter()
+        .find(|(name, _)| name == "representations")
+        .map(|(_, value)| graphql_value_to_json(value, &variables))
+        .ok_or_else(|| "_entities needs its representations".to_string())?;
+    let representations = representations.as_array().cloned().unwrap_or_default();
+
+    // every representation becomes an aliased `node` lookup with the selection of `_entities`
+    let mut entity_selection = federated.selection_set.clone();
+    entity_selection.items.insert(
+        0,
+        Selection::Field(Field {
+            position: federated.position,
+            alias: None,
+            name: "__typename".to_string(),
+            arguments: vec![],
+            directives: vec![],
+            selection_set: SelectionSet { span: federated.selection_set.span, items: vec![] },
+        }),
+    );
+    let mut upstream_variables = match &variables {
+        Value::Object(variables) => variables.clone(),
+        _ => Map::new(),
+    };
+    if let Some((_, GraphqlValue::Variable(name))) =
+        federated.arguments.iter().find(|(name, _)| name == "representations")
+    {
+        upstream_variables.remove(name);
+        query.variable_definitions.retain(|definition| &definition.name != name);
+    }
+
+    let mut typenames = vec![];
+    let mut lookups = vec![];
+    for (index, representation) in representations.iter().enumerate() {
+        let Some(node_id) = representation.get(ENTITY_KEY).and_then(Value::as_str) else {
+            typenames.push(None);
+            continue;
+        };
+        typenames.push(representation["__typename"].as_str().map(str::to_string));
+
+        let alias = format!("{}{}", ENTITY_ALIAS_PREFIX, index);
+        upstream_variables.insert(alias.clone(), json!(node_id));
+        query.variable_definitions.push(VariableDefinition {
+            position: federated.position,
+            name: alias.clone(),
+            var_type: Type::NonNullType(Box::new(Type::NamedType("ID".to_string()))),
+            default_value: None,
+        });
+        lookups.push(Selection::Field(Field {
+            position: federated.position,
+            alias: Some(alias.clone()),
+            name: "node".to_string(),
+            arguments: vec![(ENTITY_KEY.to_string(), GraphqlValue::Variable(alias))],
+            directives: vec![],
+            selection_set: entity_selection.clone(),
+        }));
+    }
+    if lookups.is_empty() {
+        // nothing to look up still has to be a valid query
+        lookups.push(Selection::Field(Field {
+            position: federated.position,
+            alias: None,
+            name: "__typename".to_string(),
+            arguments: vec![],
+            directives: vec![],
+            selection_set: SelectionSet { span: federated.selection_set.span, items: vec![] },
+        }));
+    }
+    query.selection_set.items = lookups;
+
+    let mut body = json!({ "query": document.to_string(), "variables": upstream_variables });
+    if let Some(operation_name) = operation_name {
+        body["operationName"] = json!(operation_name);
+    }
+    Ok(Some(FederatedOperation::Entities {
+        response_key: response_key(&federated),
+        typenames,
+        body,
+    }))
+}
+
+/// Lines the `node` lookups up with the representations, a node of another type is no entity
+fn entities_response(
+    response_key: &str,
+    typenames: &[Option<String>],
+    mut response: Value,
+) -> Value {
+    let data = response.get("data").cloned().unwrap_or(Value::Null);
+    let entities: Vec<Value> = typenames
+        .iter()
+        .enumerate()
+        .map(|(index, typename)| {
+            let entity = data
+                .get(format!("{}{}", ENTITY_ALIAS_PREFIX, index))
+                .cloned()
+                .unwrap_or(Value::Null);
+            match (typename, entity.get("__typename").and_then(Value::as_str)) {
+                (Some(typename), Some(entity_typename)) if typename == entity_typename => entity,
+                _ => Value::Null,
+            }
+        })
+        .collect();
+
+    let mut body = json!({ "data": { response_key: entities } });
+    if let Some(errors) = response.get_mut("errors") {
+        body["errors"] = errors.take();
+    }
+    body
+}
+
+fn graphql_error(message: &str) -> Value {
+    json!({ "errors": [{ "message": message }] })
+}
+
+/// Serves the graphql api as an Apollo Federation subgraph, `_service` is answered with the
+/// schema of the graphql server and `_entities` is looked up through its `node` field
+#[derive(Default)]
+pub struct Federation {
+    // introspected the first time a router asks as the graphql server starts after the gateway
+    sdl: OnceCell<String>,
+}
+
+impl Federation {
+    async fn sdl(&self, client: &Client, upstream: &str) -> Result<&String, String> {
+        self.sdl
+            .get_or_try_init(|| async {
+                let response: Value = client
+                    .post(format!("{}/graphql", upstream))
+                    .json(&json!({ "query": INTROSPECTION_QUERY }))
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .json()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                match response.pointer("/data/__schema") {
+                    Some(schema) => Ok(subgraph_sdl(schema)),
+                    None => Err(format!("Could not introspect the graphql server: {}", response)),
+                }
+            })
+            .await
+    }
+
+    /// The response for a subgraph operation, `None` when the body goes on to the graphql server
+    /// as it is
+    pub async fn respond(
+        &self,
+        client: &Client,
+        upstream: &str,
+        request: &HttpRequest,
+        body: &[u8],
+    ) -> Option<Value> {
+        // batches are passed on, routers send subgraph operations one at a time
+        let operation: Value = serde_json::from_slice(body).ok()?;
+        let federated = match federated_operation(&operation) {
+            Ok(federated) => federated?,
+            Err(e) => return Some(graphql_error(&e)),
+        };
+
+        match federated {
+            FederatedOperation::Service { response_key, selection } => {
+                let sdl = match self.sdl(client, upstream).await {
+                    Ok(sdl) => sdl,
+                    Err(e) => return Some(graphql_error(&e)),
+                };
+                let service: Map<String, Value> = selection
+                    .into_iter()
+                    .map(|(key, field)| {
+                        let value = if field == "sdl" { json!(sdl) } else { json!("_Service") };
+                        (key, value)
+                    })
+                    .collect();
+                Some(json!({ "data": { response_key: service } }))
+            }
+            FederatedOperation::Entities { response_key, typenames, body } => {
+                let response = forward(client, upstream, request, body.to_string().into_bytes())
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|(_, _, response)| {
+                        serde_json::from_slice::<Value>(&response).map_err(|e| e.to_string())
+                    });
+                match response {
+                    Ok(response) => Some(entities_response(&response_key, &typenames, response)),
+                    Err(e) => Some(graphql_error(&e)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prints_table_types_with_a_key() {
+        let schema = json!({
+            "types": [
+                { "kind": "SCALAR", "name": "String" },
+                { "kind": "SCALAR", "name": "BigFloat" },
+                { "kind": "OBJECT", "name": "__Type", "fields": [] },
+                {
+                    "kind": "OBJECT",
+                    "name": "Query",
+                    "interfaces": [{ "name": "Node" }],
+                    "fields": [{
+                        "name": "node",
+                        "args": [{ "name": "nodeId", "type": { "kind": "NON_NULL", "name": null, "ofType": { "kind": "SCALAR", "name": "ID" } }, "defaultValue": null }],
+                        "type": { "kind": "INTERFACE", "name": "Node" }
+                    }]
+                },
+                {
+                    "kind": "OBJECT",
+                    "name": "Transfer",
+                    "interfaces": [{ "name": "Node" }],
+                    "fields": [
+                        { "name": "nodeId", "args": [], "type": { "kind": "NON_NULL", "name": null, "ofType": { "kind": "SCALAR", "name": "ID" } } },
+                        { "name": "value", "args": [], "type": { "kind": "SCALAR", "name": "BigFloat" } }
+                    ]
+                },
+                { "kind": "ENUM", "name": "TransfersOrderBy", "enumValues": [{ "name": "NATURAL" }, { "name": "VALUE_ASC" }] }
+            ]
+        });
+
+        assert_eq!(
+            subgraph_sdl(&schema),
+            "scalar BigFloat\n\n\
+             type Query implements Node {\n  node(nodeId: ID!): Node\n}\n\n\
+             type Transfer implements Node @key(fields: \"nodeId\") {\n  nodeId: ID!\n  value: BigFloat\n}\n\n\
+             enum TransfersOrderBy {\n  NATURAL\n  VALUE_ASC\n}\n"
+        );
+    }
+
+    #[test]
+    fn finds_the_service_sdl_query() {
+        let operation =
+            json!({ "query": "query SubgraphIntrospectQuery { _service { schema: sdl } }" });
+        assert_eq!(
+            federated_operation(&operation).unwrap(),
+            Some(FederatedOperation::Service {
+                response_key: "_service".to_string(),
+                selection: vec![("schema".to_string(), "sdl".to_string())],
+            })
+        );
+
+        assert_eq!(
+            federated_operation(&json!({ "query": "{ allTransfers { nodes { value } } }" }))
+                .unwrap(),
+            None
+        );
+        assert!(federated_operation(
+            &json!({ "query": "{ _service { sdl } allTransfers { totalCount } }" })
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn looks_entities_up_by_node_id() {
+        let operation = json!({
+            "query": "query($representations: [_Any!]!) { _entities(representations: $representations) { ... on Transfer { value } } }",
+            "variables": {
+                "representations": [
+                    { "__typename": "Transfer", "nodeId": "WyJ0cmFuc2ZlcnMiLDFd" },
+                    { "__typename": "Transfer" }
+                ]
+            }
+        });
+
+        let Some(FederatedOperation::Entities { response_key, typenames, body }) =
+            federated_operation(&operation).unwrap()
+        else {
+            panic!("expected an entities lookup");
+        };
+        assert_eq!(response_key, "_entities");
+        assert_eq!(typenames, vec![Some("Transfer".to_string()), None]);
+        assert_eq!(body["variables"], json!({ "_entity0": "WyJ0cmFuc2ZlcnMiLDFd" }));
+        let upstream_query = body["query"].as_str().unwrap();
+        assert!(upstream_query.contains("query($_entity0: ID!)"), "{}", upstream_query);
+        assert!(upstream_query.contains("_entity0: node(nodeId: $_entity0)"), "{}", upstream_query);
+        assert!(upstream_query.contains("... on Transfer"), "{}", upstream_query);
+        assert!(!upstream_query.contains("representations"), "{}", upstream_query);
+
+        let response = json!({
+            "data": { "_entity0": { "__typename": "Transfer", "value": "5" } }
+        });
+        assert_eq!(
+            entities_response(&response_key, &typenames, response),
+            json!({ "data": { "_entities": [{ "__typename": "Transfer", "value": "5" }, null] } })
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_subgraph_operations_through_the_graphql_server() {
+        let upstream = format!("{}/federation", mockito::server_url());
+        let _introspection = mockito::mock("POST", "/federation/graphql")
+            .match_body(mockito::Matcher::Regex("FederationIntrospection".to_string()))
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({ "data": { "__schema": { "types": [
+                    { "kind": "OBJECT", "name": "Transfer", "interfaces": [{ "name": "Node" }], "fields": [
+                        { "name": "nodeId", "args": [], "type": { "kind": "NON_NULL", "name": null, "ofType": { "kind": "SCALAR", "name": "ID" } } }
+                    ] }
+                ] } } })
+                .to_string(),
+            )
+            .create();
+        let _entities = mockito::mock("POST", "/federation/graphql")
+            .match_body(mockito::Matcher::Regex("_entity0: node".to_string()))
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({ "data": { "_entity0": { "__typename": "Transfer", "nodeId": "a" } } })
+                    .to_string(),
+            )
+            .create();
+        let request = |body: Value| HttpRequest {
+            method: "POST".to_string(),
+            target: "/graphql".to_string(),
+            headers: vec![],
+            body: body.to_string().into_bytes(),
+        };
+        let federation = Federation::default();
+        let client = Client::new();
+
+        let service = request(json!({ "query": "{ _service { sdl } }" }));
+        assert_eq!(
+            federation.respond(&client, &upstream, &service, &service.body).await,
+            Some(json!({ "data": { "_service": {
+                "sdl": "type Transfer implements Node @key(fields: \"nodeId\") {\n  nodeId: ID!\n}\n"
+            } } }))
+        );
+
+        let entities = request(json!({
+            "query": "query($r: [_Any!]!) { _entities(representations: $r) { ... on Transfer { nodeId } } }",
+            "variables": { "r": [{ "__typename": "Transfer", "nodeId": "a" }] }
+        }));
+        assert_eq!(
+            federation.respond(&client, &upstream, &entities, &entities.body).await,
+            Some(json!({ "data": { "_entities": [{ "__typename": "Transfer", "nodeId": "a" }] } }))
+        );
+
+        let other = request(json!({ "query": "{ allTransfers { totalCount } }" }));
+        assert_eq!(federation.respond(&client, &upstream, &other, &other.body).await, None);
+    }
+}
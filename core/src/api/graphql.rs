@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     env,
     path::{Path, PathBuf},
     process::{Child, Command, Stdio},
@@ -15,19 +16,27 @@ use tokio::sync::{oneshot, oneshot::Sender};
 use tracing::{error, info};
 
 use crate::{
+    api::{
+        federation::Federation,
+        persisted_queries::{
+            load_persisted_queries, persisted_query_hash, start_graphql_gateway, PersistedQueries,
+            PersistedQueriesError,
+        },
+    },
     database::postgres::{
-        client::connection_string, generate::generate_indexer_contract_schema_name,
+        generate::{
+            contract_networks, generate_indexer_contract_schema_name, generate_network_schema_name,
+        },
+        projection::generate_projection_schema_name,
     },
     helpers::{kill_process_on_port, set_thread_no_logging},
     indexer::Indexer,
-    manifest::graphql::GraphQLSettings,
+    manifest::{
+        graphql::GraphQLSettings,
+        storage::{PostgresLayout, Storage},
+    },
 };
 
-pub struct GraphqlOverrideSettings {
-    pub enabled: bool,
-    pub override_port: Option<u16>,
-}
-
 fn get_graphql_exe() -> Result<PathBuf, ()> {
     let postgraphile_filename = match env::consts::OS {
         "windows" => "rindexer-graphql-win.exe",
@@ -95,29 +104,51 @@ pub enum StartGraphqlServerError {
 
     #[error("Could not start up GraphQL server {0}")]
     GraphQLServerStartupError(String),
+
+    #[error("{0}")]
+    PersistedQueriesError(#[from] PersistedQueriesError),
 }
 
 pub async fn start_graphql_server(
+    project_path: &Path,
     indexer: &Indexer,
     settings: &GraphQLSettings,
+    storage: &Storage,
 ) -> Result<GraphQLServer, StartGraphqlServerError> {
     info!("Starting GraphQL server");
 
-    let schemas: Vec<String> = indexer
-        .contracts
-        .iter()
-        .map(move |contract| {
-            generate_indexer_contract_schema_name(
+    let layout = storage.postgres_layout();
+    let mut schemas: Vec<String> = vec![];
+    for contract in &indexer.contracts {
+        let contract_schemas = match layout {
+            PostgresLayout::Shared => vec![generate_indexer_contract_schema_name(
                 &indexer.name,
                 &contract.before_modify_name_if_filter_readonly(),
-            )
-        })
-        .collect();
+            )],
+            PostgresLayout::SchemaPerNetwork => {
+                contract_networks(contract).into_iter().map(generate_network_schema_name).collect()
+            }
+        };
+        for schema in contract_schemas {
+            if !schemas.contains(&schema) {
+                schemas.push(schema);
+            }
+        }
+    }
+    if !storage.postgres_projections().is_empty() || !storage.postgres_rollups().is_empty() {
+        schemas.push(generate_projection_schema_name(&indexer.name));
+    }
 
-    let connection_string = connection_string()?;
+    let connection_string = storage.postgres_connection_string()?;
     let port = settings.port;
-    let graphql_endpoint = format!("http://localhost:{}/graphql", &port);
-    let graphql_playground = format!("http://localhost:{}/playground", &port);
+    let endpoint_host = match &settings.persisted_queries {
+        Some(persisted_queries) if persisted_queries.host != "0.0.0.0" => {
+            persisted_queries.host.as_str()
+        }
+        _ => "localhost",
+    };
+    let graphql_endpoint = format!("http://{}:{}/graphql", endpoint_host, &port);
+    let graphql_playground = format!("http://{}:{}/playground", endpoint_host, &port);
 
     let rindexer_graphql_exe = get_graphql_exe().map_err(|_| {
         StartGraphqlServerError::GraphQLServerStartupError(
@@ -125,9 +156,60 @@ pub async fn start_graphql_server(
         )
     })?;
 
+    let mut flags = ServerFlags {
+        filter_only_on_indexed_columns: settings.filter_only_on_indexed_columns,
+        disable_advanced_filters: settings.disable_advanced_filters,
+        host: None,
+    };
+    let persisted = match &settings.persisted_queries {
+        Some(persisted_queries) => {
+            let mut queries = load_persisted_queries(project_path, persisted_queries)?;
+            // the health check has to get through the allow list
+            queries
+                .insert(persisted_query_hash(HEALTH_CHECK_QUERY), HEALTH_CHECK_QUERY.to_string());
+            info!(
+                "GraphQL loaded {} persisted queries{}",
+                queries.len(),
+                if persisted_queries.allow_list_only { " - allow list only" } else { "" }
+            );
+            Some((
+                PersistedQueries::new(queries, persisted_queries.allow_list_only),
+                persisted_queries.host.as_str(),
+            ))
+        }
+        // without persisted queries the gateway only runs for federation and listens where the
+        // graphql server would, operations pass through it as they are
+        None if settings.federation => {
+            Some((PersistedQueries::new(BTreeMap::new(), false), "0.0.0.0"))
+        }
+        None => None,
+    };
+    let federation = if settings.federation {
+        info!("GraphQL serving the API as a federated subgraph");
+        Some(Federation::default())
+    } else {
+        None
+    };
+
     // kill any existing process on the port
     kill_process_on_port(port).map_err(StartGraphqlServerError::GraphQLServerStartupError)?;
 
+    // with persisted queries or federation the graphql server listens on a local port behind
+    // the gateway
+    let server_port = match persisted {
+        Some((persisted, host)) => {
+            let server_port = free_local_port()
+                .map_err(|e| StartGraphqlServerError::GraphQLServerStartupError(e.to_string()))?;
+            start_graphql_gateway(host, port, server_port, persisted, federation)
+                .await
+                .map_err(|e| StartGraphqlServerError::GraphQLServerStartupError(e.to_string()))?;
+            // nothing may reach the graphql server around the allow list
+            flags.host = Some("127.0.0.1");
+            server_port
+        }
+        None => port,
+    };
+
     let (tx, rx) = oneshot::channel();
     let tx_arc = Arc::new(Mutex::new(Some(tx)));
 
@@ -136,9 +218,8 @@ pub async fn start_graphql_server(
         rindexer_graphql_exe,
         connection_string,
         schemas.join(","),
-        Arc::new(port),
-        settings.filter_only_on_indexed_columns,
-        settings.disable_advanced_filters,
+        Arc::new(server_port),
+        flags,
     );
 
     setup_ctrlc_handler(Arc::new(Mutex::new(None::<Child>)));
@@ -156,6 +237,19 @@ pub async fn start_graphql_server(
     Ok(GraphQLServer { pid })
 }
 
+struct ServerFlags {
+    filter_only_on_indexed_columns: bool,
+    disable_advanced_filters: bool,
+    /// Interface the graphql server listens on, all of them when not set
+    host: Option<&'static str>,
+}
+
+fn free_local_port() -> std::io::Result<u16> {
+    Ok(std::net::TcpListener::bind(("127.0.0.1", 0))?.local_addr()?.port())
+}
+
+const HEALTH_CHECK_QUERY: &str = "query MyQuery { nodeId }";
+
 static MANUAL_STOP: AtomicBool = AtomicBool::new(false);
 
 fn spawn_start_server(
@@ -164,8 +258,7 @@ fn spawn_start_server(
     connection_string: String,
     schemas: String,
     port: Arc<u16>,
-    filter_only_on_indexed_columns: bool,
-    disable_advanced_filters: bool,
+    flags: ServerFlags,
 ) {
     tokio::spawn(async move {
         loop {
@@ -173,15 +266,8 @@ fn spawn_start_server(
                 break;
             }
 
-            match start_server(
-                &rindexer_graphql_exe,
-                &connection_string,
-                &schemas,
-                &port,
-                filter_only_on_indexed_columns,
-                disable_advanced_filters,
-            )
-            .await
+            match start_server(&rindexer_graphql_exe, &connection_string, &schemas, &port, &flags)
+                .await
             {
                 Ok(child) => {
                     let pid = child.id();
@@ -254,10 +340,10 @@ async fn start_server(
     connection_string: &str,
     schemas: &str,
     port: &u16,
-    filter_only_on_indexed_columns: bool,
-    disable_advanced_filters: bool,
+    flags: &ServerFlags,
 ) -> Result<Child, String> {
-    Command::new(rindexer_graphql_exe)
+    let mut command = Command::new(rindexer_graphql_exe);
+    command
         .arg(connection_string)
         .arg(schemas)
         .arg(port.to_string())
@@ -265,12 +351,12 @@ async fn start_server(
         .arg("1000")
         // graphql_timeout
         .arg("10000")
-        .arg(filter_only_on_indexed_columns.to_string())
-        .arg(disable_advanced_filters.to_string())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-        .map_err(|e| e.to_string())
+        .arg(flags.filter_only_on_indexed_columns.to_string())
+        .arg(flags.disable_advanced_filters.to_string());
+    if let Some(host) = flags.host {
+        command.arg(host);
+    }
+    command.stdout(Stdio::null()).stderr(Stdio::null()).spawn().map_err(|e| e.to_string())
 }
 
 fn setup_ctrlc_handler(child_arc: Arc<Mutex<Option<Child>>>) {
@@ -296,7 +382,7 @@ async fn perform_health_check(
 ) -> Result<(), StartGraphqlServerError> {
     let client = Client::new();
     let health_check_query = json!({
-        "query": "query MyQuery { nodeId }"
+        "query": HEALTH_CHECK_QUERY
     });
     let mut health_check_attempts = 0;
     while health_check_attempts < 40 {
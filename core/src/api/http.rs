@@ -0,0 +1,183 @@
+use std::{future::Future, io, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+    time::timeout,
+};
+use tracing::error;
+
+const MAX_REQUEST_HEAD_BYTES: usize = 16 * 1024;
+
+/// A client has this long to send its whole request before the connection is dropped
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct HttpRequest {
+    pub method: String,
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The target without its query string
+    pub fn path(&self) -> &str {
+        self.target.split_once('?').map_or(self.target.as_str(), |(path, _)| path)
+    }
+
+    /// The query string without the leading `?`, empty when there is none
+    pub fn query(&self) -> &str {
+        self.target.split_once('?').map_or("", |(_, query)| query)
+    }
+}
+
+async fn read_request_inner(stream: &mut TcpStream, max_body_bytes: usize) -> Option<HttpRequest> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 8192];
+    let head_end = loop {
+        if let Some(position) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+        if request.len() > MAX_REQUEST_HEAD_BYTES {
+            return None;
+        }
+        let read = stream.read(&mut buffer).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    };
+
+    let head = String::from_utf8(request[..head_end].to_vec()).ok()?;
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+    let content_length =
+        match headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("content-length")) {
+            Some((_, value)) => value.parse::<usize>().ok()?,
+            None => 0,
+        };
+    if content_length > max_body_bytes {
+        return None;
+    }
+
+    let mut body = request.split_off(head_end);
+    while body.len() < content_length {
+        let read = stream.read(&mut buffer).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        body.extend_from_slice(&buffer[..read]);
+    }
+    body.truncate(content_length);
+
+    Some(HttpRequest { method, target, headers, body })
+}
+
+/// Reads the request line, headers and the `Content-Length` body, `None` when the request is
+/// malformed, bigger than allowed or not sent in time
+pub async fn read_request(stream: &mut TcpStream, max_body_bytes: usize) -> Option<HttpRequest> {
+    timeout(REQUEST_READ_TIMEOUT, read_request_inner(stream, max_body_bytes)).await.ok().flatten()
+}
+
+pub async fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: impl AsRef<[u8]>,
+) {
+    let body = body.as_ref();
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    if stream.write_all(head.as_bytes()).await.is_ok() {
+        let _ = stream.write_all(body).await;
+    }
+}
+
+/// Binds to the host and port and hands every connection to its own task, the returned handle
+/// runs for as long as the server does
+pub async fn serve<F, Fut>(
+    name: &'static str,
+    host: &str,
+    port: u16,
+    handle_connection: F,
+) -> io::Result<JoinHandle<()>>
+where
+    F: Fn(TcpStream) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind((host, port)).await?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_connection(stream));
+                }
+                Err(e) => error!("{} could not accept connection: {}", name, e),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reads_request_with_body() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut client = TcpStream::connect(address).await.unwrap();
+            client
+                .write_all(
+                    b"POST /webhooks/ethereum?a=1 HTTP/1.1\r\nX-Signature: abc\r\nContent-Length: 2\r\n\r\n{}",
+                )
+                .await
+                .unwrap();
+            // keeps the connection open while the server reads
+            let _ = client.read(&mut [0u8; 1]).await;
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let request = read_request(&mut stream, 1024).await.unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path(), "/webhooks/ethereum");
+        assert_eq!(request.header("x-signature"), Some("abc"));
+        assert_eq!(request.body, b"{}");
+    }
+
+    #[tokio::test]
+    async fn test_refuses_bodies_over_the_limit() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut client = TcpStream::connect(address).await.unwrap();
+            client.write_all(b"POST / HTTP/1.1\r\nContent-Length: 2048\r\n\r\n").await.unwrap();
+            let _ = client.read(&mut [0u8; 1]).await;
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        assert!(read_request(&mut stream, 1024).await.is_none());
+    }
+}
@@ -0,0 +1,68 @@
+use tokio::net::TcpStream;
+use tracing::info;
+
+use crate::{
+    api::http::{read_request, serve, write_response},
+    database::postgres::metrics::render_postgres_metrics,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum StartMetricsServerError {
+    #[error("Could not bind metrics server to port {0}: {1}")]
+    CouldNotBind(u16, std::io::Error),
+}
+
+async fn serve_metrics(mut stream: TcpStream) {
+    let Some(request) = read_request(&mut stream, 0).await else {
+        write_response(&mut stream, "400 Bad Request", "text/plain", "").await;
+        return;
+    };
+
+    if request.method == "GET" && request.path() == "/metrics" {
+        let body = render_postgres_metrics();
+        write_response(&mut stream, "200 OK", "text/plain; version=0.0.4", body).await;
+    } else {
+        write_response(&mut stream, "404 Not Found", "text/plain", "").await;
+    }
+}
+
+/// Serves the postgres write path metrics on `/metrics` for prometheus to scrape
+pub async fn start_metrics_server(host: &str, port: u16) -> Result<(), StartMetricsServerError> {
+    serve("Metrics server", host, port, serve_metrics)
+        .await
+        .map_err(|e| StartMetricsServerError::CouldNotBind(port, e))?;
+
+    info!("Metrics running on: http://{}:{}/metrics", host, port);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    async fn get(port: u16, path: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_serves_metrics_only_on_metrics_path() {
+        let port =
+            std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap().local_addr().unwrap().port();
+        start_metrics_server("127.0.0.1", port).await.unwrap();
+
+        let metrics = get(port, "/metrics").await;
+        assert!(metrics.starts_with("HTTP/1.1 200 OK"));
+        assert!(metrics.contains("text/plain; version=0.0.4"));
+        assert!(get(port, "/other").await.starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
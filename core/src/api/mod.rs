@@ -1,6 +1,29 @@
+mod event_push;
+// only served behind the graphql server
+#[cfg_attr(not(feature = "graphql"), allow(dead_code))]
+mod federation;
 mod generate_operations;
 mod generate_schema;
+#[cfg(feature = "graphql")]
 mod graphql;
+pub(crate) mod http;
+mod metrics;
+// only the hash is used without the graphql server
+#[cfg_attr(not(feature = "graphql"), allow(dead_code))]
+mod persisted_queries;
 
+pub(crate) use event_push::{registry_event_decoders, PushDecoder};
+pub use event_push::{
+    start_event_push_server, start_registry_event_push, EventPushHub, PushedEvent,
+    StartEventPushServerError,
+};
 pub use generate_schema::generate_graphql_queries;
-pub use graphql::{start_graphql_server, GraphqlOverrideSettings, StartGraphqlServerError};
+#[cfg(feature = "graphql")]
+pub use graphql::{start_graphql_server, StartGraphqlServerError};
+pub use metrics::{start_metrics_server, StartMetricsServerError};
+pub use persisted_queries::persisted_query_hash;
+
+pub struct GraphqlOverrideSettings {
+    pub enabled: bool,
+    pub override_port: Option<u16>,
+}
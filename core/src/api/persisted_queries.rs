@@ -0,0 +1,415 @@
+use std::{collections::BTreeMap, fs, io, path::Path, sync::Arc};
+
+use reqwest::Client;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::{net::TcpStream, task::JoinHandle};
+use tracing::error;
+
+use crate::{
+    api::{
+        federation::Federation,
+        http::{read_request, serve, write_response, HttpRequest},
+    },
+    manifest::graphql::PersistedQueriesSettings,
+};
+
+/// Operations bigger than this are refused before they reach the graphql server
+const MAX_OPERATION_BYTES: usize = 1024 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PersistedQueriesError {
+    #[error("Could not read persisted queries: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Could not parse persisted queries json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Persisted query hash {0} does not match the sha256 of its query")]
+    HashMismatch(String),
+
+    #[error("Allow list only is enabled but no persisted queries were found in {0}")]
+    NoPersistedQueries(String),
+}
+
+/// The hex sha256 of the query text which clients send as `extensions.persistedQuery.sha256Hash`
+pub fn persisted_query_hash(query: &str) -> String {
+    hex::encode(Sha256::digest(query.as_bytes()))
+}
+
+fn read_graphql_files(
+    folder: &Path,
+    queries: &mut BTreeMap<String, String>,
+) -> Result<(), PersistedQueriesError> {
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            read_graphql_files(&path, queries)?;
+        } else if path.extension().is_some_and(|extension| extension == "graphql") {
+            let query = fs::read_to_string(&path)?;
+            queries.insert(persisted_query_hash(&query), query);
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads the persisted queries keyed by their hash
+pub fn load_persisted_queries(
+    project_path: &Path,
+    settings: &PersistedQueriesSettings,
+) -> Result<BTreeMap<String, String>, PersistedQueriesError> {
+    let path = project_path.join(&settings.path);
+    let mut queries = BTreeMap::new();
+
+    if path.is_dir() {
+        read_graphql_files(&path, &mut queries)?;
+    } else {
+        let persisted: BTreeMap<String, String> =
+            serde_json::from_str(&fs::read_to_string(&path)?)?;
+        for (hash, query) in persisted {
+            if !hash.eq_ignore_ascii_case(&persisted_query_hash(&query)) {
+                return Err(PersistedQueriesError::HashMismatch(hash));
+            }
+            queries.insert(hash.to_lowercase(), query);
+        }
+    }
+
+    if settings.allow_list_only && queries.is_empty() {
+        return Err(PersistedQueriesError::NoPersistedQueries(settings.path.clone()));
+    }
+
+    Ok(queries)
+}
+
+#[derive(Debug, PartialEq)]
+enum PersistedQueryRejection {
+    NotFound,
+    HashMismatch,
+    NotAllowed,
+    InvalidOperation(String),
+}
+
+impl PersistedQueryRejection {
+    fn to_graphql_error(&self) -> Value {
+        let (code, message) = match self {
+            PersistedQueryRejection::NotFound => {
+                ("PERSISTED_QUERY_NOT_FOUND", "PersistedQueryNotFound".to_string())
+            }
+            PersistedQueryRejection::HashMismatch => {
+                ("PERSISTED_QUERY_HASH_MISMATCH", "provided sha does not match query".to_string())
+            }
+            PersistedQueryRejection::NotAllowed => (
+                "PERSISTED_QUERY_NOT_ALLOWED",
+                "Only persisted queries can be executed".to_string(),
+            ),
+            PersistedQueryRejection::InvalidOperation(e) => ("BAD_REQUEST", e.clone()),
+        };
+        json!({ "errors": [{ "message": message, "extensions": { "code": code } }] })
+    }
+}
+
+/// Sits in front of the graphql server which only ever gets full queries, operations sent as
+/// `extensions.persistedQuery.sha256Hash` get their query filled in and with the allow list
+/// anything which is not persisted is refused
+pub struct PersistedQueries {
+    queries: BTreeMap<String, String>,
+    allow_list_only: bool,
+}
+
+impl PersistedQueries {
+    pub fn new(queries: BTreeMap<String, String>, allow_list_only: bool) -> Self {
+        Self { queries, allow_list_only }
+    }
+
+    fn resolve_operation(&self, operation: &mut Value) -> Result<(), PersistedQueryRejection> {
+        let hash = operation
+            .pointer("/extensions/persistedQuery/sha256Hash")
+            .and_then(Value::as_str)
+            .map(str::to_lowercase);
+        let query = operation.get("query").and_then(Value::as_str);
+
+        let hash = match (hash, query) {
+            (Some(hash), None) => {
+                let query = self.queries.get(&hash).ok_or(PersistedQueryRejection::NotFound)?;
+                operation["query"] = Value::String(query.clone());
+                return Ok(());
+            }
+            (Some(hash), Some(query)) => {
+                if persisted_query_hash(query) != hash {
+                    return Err(PersistedQueryRejection::HashMismatch);
+                }
+                hash
+            }
+            (None, Some(query)) => persisted_query_hash(query),
+            (None, None) => {
+                return Err(PersistedQueryRejection::InvalidOperation(
+                    "Operations need a query or a persisted query hash".to_string(),
+                ))
+            }
+        };
+
+        if self.allow_list_only && !self.queries.contains_key(&hash) {
+            return Err(PersistedQueryRejection::NotAllowed);
+        }
+        Ok(())
+    }
+
+    /// The body to send on to the graphql server, a batch is resolved operation by operation
+    fn resolve_body(&self, body: &[u8]) -> Result<Vec<u8>, PersistedQueryRejection> {
+        let mut body: Value = serde_json::from_slice(body)
+            .map_err(|e| PersistedQueryRejection::InvalidOperation(e.to_string()))?;
+        match &mut body {
+            Value::Array(operations) => {
+                for operation in operations {
+                    self.resolve_operation(operation)?;
+                }
+            }
+            operation => self.resolve_operation(operation)?,
+        }
+        Ok(body.to_string().into_bytes())
+    }
+}
+
+pub(super) async fn forward(
+    client: &Client,
+    upstream: &str,
+    request: &HttpRequest,
+    body: Vec<u8>,
+) -> Result<(String, String, Vec<u8>), reqwest::Error> {
+    let method =
+        reqwest::Method::from_bytes(request.method.as_bytes()).unwrap_or(reqwest::Method::GET);
+    let mut upstream_request =
+        client.request(method, format!("{}{}", upstream, request.target)).body(body);
+    for (name, value) in &request.headers {
+        if !["host", "content-length", "connection"]
+            .iter()
+            .any(|skipped| name.eq_ignore_ascii_case(skipped))
+        {
+            upstream_request = upstream_request.header(name, value);
+        }
+    }
+
+    let response = upstream_request.send().await?;
+    let status = response.status();
+    let status = format!("{} {}", status.as_u16(), status.canonical_reason().unwrap_or_default());
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("text/plain")
+        .to_string();
+    Ok((status, content_type, response.bytes().await?.to_vec()))
+}
+
+#[derive(Debug, PartialEq)]
+enum GatewayResponse {
+    Status(&'static str),
+    Rejected(PersistedQueryRejection),
+}
+
+/// The body to forward for the request, only `/graphql` and the playground are passed on and
+/// the playground is closed with the allow list as it could only run introspection queries
+fn route(persisted: &PersistedQueries, request: &HttpRequest) -> Result<Vec<u8>, GatewayResponse> {
+    match request.path() {
+        "/graphql" if request.method == "POST" => {
+            persisted.resolve_body(&request.body).map_err(GatewayResponse::Rejected)
+        }
+        "/graphql" if persisted.allow_list_only => {
+            Err(GatewayResponse::Status("405 Method Not Allowed"))
+        }
+        "/graphql" | "/playground" if !persisted.allow_list_only => Ok(request.body.clone()),
+        _ => Err(GatewayResponse::Status("404 Not Found")),
+    }
+}
+
+async fn serve_gateway(
+    mut stream: TcpStream,
+    persisted: Arc<PersistedQueries>,
+    federation: Option<Arc<Federation>>,
+    client: Client,
+    upstream: Arc<String>,
+) {
+    let Some(request) = read_request(&mut stream, MAX_OPERATION_BYTES).await else {
+        write_response(&mut stream, "400 Bad Request", "text/plain", "").await;
+        return;
+    };
+
+    let body = match route(&persisted, &request) {
+        Ok(body) => body,
+        Err(GatewayResponse::Status(status)) => {
+            write_response(&mut stream, status, "text/plain", "").await;
+            return;
+        }
+        Err(GatewayResponse::Rejected(rejection)) => {
+            write_response(
+                &mut stream,
+                "200 OK",
+                "application/json",
+                rejection.to_graphql_error().to_string(),
+            )
+            .await;
+            return;
+        }
+    };
+
+    // subgraph operations are answered here as the graphql server does not know them
+    if let Some(federation) = federation.filter(|_| request.method == "POST") {
+        if let Some(response) = federation.respond(&client, &upstream, &request, &body).await {
+            write_response(&mut stream, "200 OK", "application/json", response.to_string()).await;
+            return;
+        }
+    }
+
+    match forward(&client, &upstream, &request, body).await {
+        Ok((status, content_type, body)) => {
+            write_response(&mut stream, &status, &content_type, body).await
+        }
+        Err(e) => {
+            error!("GraphQL persisted queries could not reach the graphql server: {}", e);
+            write_response(&mut stream, "502 Bad Gateway", "text/plain", "").await;
+        }
+    }
+}
+
+/// Serves the graphql api on the host and port, every request goes on to the graphql server on
+/// the local upstream port once its persisted query is resolved. With federation the subgraph
+/// operations are answered by the gateway.
+pub async fn start_graphql_gateway(
+    host: &str,
+    port: u16,
+    upstream_port: u16,
+    persisted: PersistedQueries,
+    federation: Option<Federation>,
+) -> io::Result<JoinHandle<()>> {
+    let persisted = Arc::new(persisted);
+    let federation = federation.map(Arc::new);
+    let client = Client::new();
+    let upstream = Arc::new(format!("http://127.0.0.1:{}", upstream_port));
+    // stands in for the graphql server on its public port
+    serve("GraphQL gateway", host, port, move |stream| {
+        serve_gateway(
+            stream,
+            Arc::clone(&persisted),
+            federation.clone(),
+            client.clone(),
+            Arc::clone(&upstream),
+        )
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_and_verifies_json_persisted_queries() {
+        let dir = tempfile::tempdir().unwrap();
+        let query = "query MyQuery { nodeId }";
+        assert_eq!(
+            persisted_query_hash(query),
+            "3cd0da47f491df863cd4eebb5c28bbca38e5baa0f06f8dfc7179b9f54bf21839"
+        );
+
+        let settings = PersistedQueriesSettings {
+            path: "queries.json".to_string(),
+            allow_list_only: true,
+            host: "127.0.0.1".to_string(),
+        };
+        fs::write(
+            dir.path().join("queries.json"),
+            serde_json::json!({ persisted_query_hash(query): query }).to_string(),
+        )
+        .unwrap();
+        let queries = load_persisted_queries(dir.path(), &settings).unwrap();
+        assert_eq!(queries.get(&persisted_query_hash(query)).map(String::as_str), Some(query));
+
+        fs::write(dir.path().join("queries.json"), serde_json::json!({ "abc": query }).to_string())
+            .unwrap();
+        assert!(matches!(
+            load_persisted_queries(dir.path(), &settings),
+            Err(PersistedQueriesError::HashMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn resolves_operations_sent_by_hash() {
+        let query = "query MyQuery { nodeId }";
+        let hash = persisted_query_hash(query);
+        let persisted =
+            PersistedQueries::new(BTreeMap::from([(hash.clone(), query.to_string())]), false);
+
+        let body =
+            json!({ "extensions": { "persistedQuery": { "version": 1, "sha256Hash": hash } } });
+        let resolved: Value =
+            serde_json::from_slice(&persisted.resolve_body(body.to_string().as_bytes()).unwrap())
+                .unwrap();
+        assert_eq!(resolved["query"], query);
+
+        let unknown = json!({ "extensions": { "persistedQuery": { "sha256Hash": "abc" } } });
+        assert_eq!(
+            persisted.resolve_body(unknown.to_string().as_bytes()),
+            Err(PersistedQueryRejection::NotFound)
+        );
+
+        let mismatch = json!({ "query": "{ other }", "extensions": { "persistedQuery": { "sha256Hash": hash } } });
+        assert_eq!(
+            persisted.resolve_body(mismatch.to_string().as_bytes()),
+            Err(PersistedQueryRejection::HashMismatch)
+        );
+
+        // anything goes without the allow list
+        let other = json!({ "query": "{ other }" });
+        assert!(persisted.resolve_body(other.to_string().as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn allow_list_refuses_queries_which_are_not_persisted() {
+        let query = "query MyQuery { nodeId }";
+        let persisted = PersistedQueries::new(
+            BTreeMap::from([(persisted_query_hash(query), query.to_string())]),
+            true,
+        );
+
+        assert!(persisted.resolve_body(json!({ "query": query }).to_string().as_bytes()).is_ok());
+        let batch = json!([{ "query": query }, { "query": "{ other }" }]);
+        assert_eq!(
+            persisted.resolve_body(batch.to_string().as_bytes()),
+            Err(PersistedQueryRejection::NotAllowed)
+        );
+    }
+
+    #[test]
+    fn only_graphql_requests_are_forwarded_with_the_allow_list() {
+        let query = "query MyQuery { nodeId }";
+        let persisted = PersistedQueries::new(
+            BTreeMap::from([(persisted_query_hash(query), query.to_string())]),
+            true,
+        );
+        let request = |method: &str, target: &str, body: Value| HttpRequest {
+            method: method.to_string(),
+            target: target.to_string(),
+            headers: vec![],
+            body: body.to_string().into_bytes(),
+        };
+
+        assert!(
+            route(&persisted, &request("POST", "/graphql?x=1", json!({ "query": query }))).is_ok()
+        );
+        assert_eq!(
+            route(&persisted, &request("POST", "/graphql", json!({ "query": "{ other }" }))),
+            Err(GatewayResponse::Rejected(PersistedQueryRejection::NotAllowed))
+        );
+        assert_eq!(
+            route(&persisted, &request("GET", "/graphql?query={other}", Value::Null)),
+            Err(GatewayResponse::Status("405 Method Not Allowed"))
+        );
+        for target in ["/graphql/", "/GRAPHQL", "/playground", "/graphql/stream"] {
+            assert_eq!(
+                route(&persisted, &request("POST", target, json!({ "query": "{ other }" }))),
+                Err(GatewayResponse::Status("404 Not Found"))
+            );
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use ethers::{
+    abi::{Abi, Event, RawLog},
+    types::{Address, Filter, Log, ValueOrArray, H256, U256, U64},
+};
+use url::Url;
+
+use crate::{
+    database::postgres::{
+        client::{BulkInsertPostgresError, PostgresClient, PostgresConnectionError, PostgresError},
+        internal_schema::{internal_schema, internal_table},
+    },
+    helpers::camel_to_snake,
+    manifest::{core::Manifest, network::Network, storage::InsertStrategy},
+    provider::{create_load_balanced_client, RetryClientError, RpcClientOptions},
+    EthereumSqlTypeWrapper,
+};
+
+/// getLogs block ranges tried on every provider on top of the configured `max_block_range`
+const BENCH_BLOCK_RANGES: [u64; 3] = [100, 1_000, 10_000];
+
+const BENCH_INSERT_BATCH_SIZE: usize = 1_000;
+
+const BENCH_INSERT_COLUMNS: [&str; 7] =
+    ["contract_address", "value", "tx_hash", "block_number", "block_hash", "network", "log_index"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum BenchError {
+    #[error("Network {0} not found in the manifest")]
+    NetworkNotFound(String),
+
+    #[error("Could not read ABI {0}: {1}")]
+    CouldNotReadAbi(String, String),
+
+    #[error("{0}")]
+    RetryClientError(#[from] RetryClientError),
+
+    #[error("{0}")]
+    PostgresConnectionError(#[from] PostgresConnectionError),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("{0}")]
+    BulkInsertError(#[from] BulkInsertPostgresError),
+}
+
+pub struct BenchOptions {
+    /// How many blocks back from the head every provider is asked for
+    pub blocks: u64,
+    pub network: Option<String>,
+    /// Rows written with each insert strategy
+    pub rows: usize,
+}
+
+pub struct ProviderBench {
+    pub network: String,
+    /// Only the host so API keys in the url are not printed
+    pub host: String,
+    pub block_range: u64,
+    pub blocks: u64,
+    pub requests: usize,
+    pub logs: usize,
+    pub elapsed: Duration,
+    pub error: Option<String>,
+}
+
+impl ProviderBench {
+    pub fn blocks_per_second(&self) -> f64 {
+        per_second(self.blocks as usize, self.elapsed)
+    }
+}
+
+pub struct DecodeBench {
+    pub logs: usize,
+    pub decoded: usize,
+    pub elapsed: Duration,
+}
+
+pub struct InsertBench {
+    pub strategy: InsertStrategy,
+    pub rows: usize,
+    pub elapsed: Duration,
+}
+
+pub struct BenchReport {
+    pub providers: Vec<ProviderBench>,
+    pub decode: DecodeBench,
+    pub inserts: Vec<InsertBench>,
+}
+
+fn per_second(count: usize, elapsed: Duration) -> f64 {
+    count as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+impl BenchReport {
+    /// Manifest settings the measurements point to, only where there was something to compare
+    pub fn recommendations(&self) -> Vec<String> {
+        let mut recommendations = vec![];
+
+        let mut networks: Vec<&String> =
+            self.providers.iter().map(|provider| &provider.network).collect();
+        networks.dedup();
+        for network in networks {
+            let fastest = self
+                .providers
+                .iter()
+                .filter(|provider| &provider.network == network && provider.error.is_none())
+                .max_by(|a, b| a.blocks_per_second().total_cmp(&b.blocks_per_second()));
+            if let Some(fastest) = fastest {
+                recommendations.push(format!(
+                    "networks.{}.max_block_range: {} (fastest on {})",
+                    network, fastest.block_range, fastest.host
+                ));
+            }
+        }
+
+        let fastest_insert = self
+            .inserts
+            .iter()
+            .max_by(|a, b| per_second(a.rows, a.elapsed).total_cmp(&per_second(b.rows, b.elapsed)));
+        if let Some(fastest_insert) = fastest_insert {
+            recommendations.push(format!(
+                "storage.postgres.insert_strategy: {}",
+                insert_strategy_name(fastest_insert.strategy)
+            ));
+        }
+
+        recommendations
+    }
+}
+
+fn insert_strategy_name(strategy: InsertStrategy) -> &'static str {
+    match strategy {
+        InsertStrategy::Auto => "auto",
+        InsertStrategy::Copy => "copy",
+        InsertStrategy::Unnest => "unnest",
+        InsertStrategy::Values => "values",
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "getLogs")?;
+        for provider in &self.providers {
+            match &provider.error {
+                Some(error) => writeln!(
+                    f,
+                    "  {} {} range {}: failed - {}",
+                    provider.network, provider.host, provider.block_range, error
+                )?,
+                None => writeln!(
+                    f,
+                    "  {} {} range {}: {:.0} blocks/s, {:.0} logs/s ({} requests, {} logs in {:.2?})",
+                    provider.network,
+                    provider.host,
+                    provider.block_range,
+                    provider.blocks_per_second(),
+                    per_second(provider.logs, provider.elapsed),
+                    provider.requests,
+                    provider.logs,
+                    provider.elapsed
+                )?,
+            }
+        }
+
+        writeln!(f, "decode")?;
+        writeln!(
+            f,
+            "  {:.0} logs/s ({} of {} logs decoded in {:.2?})",
+            per_second(self.decode.logs, self.decode.elapsed),
+            self.decode.decoded,
+            self.decode.logs,
+            self.decode.elapsed
+        )?;
+
+        if !self.inserts.is_empty() {
+            writeln!(f, "postgres inserts")?;
+            for insert in &self.inserts {
+                writeln!(
+                    f,
+                    "  {}: {:.0} rows/s ({} rows in {:.2?})",
+                    insert_strategy_name(insert.strategy),
+                    per_second(insert.rows, insert.elapsed),
+                    insert.rows,
+                    insert.elapsed
+                )?;
+            }
+        }
+
+        writeln!(f, "recommended settings")?;
+        for recommendation in self.recommendations() {
+            writeln!(f, "  {}", recommendation)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Events of every contract ABI keyed by topic0
+fn manifest_events(
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<HashMap<H256, Vec<Event>>, BenchError> {
+    let mut events: HashMap<H256, Vec<Event>> = HashMap::new();
+    for contract in &manifest.contracts {
+        let abi: Abi = contract
+            .read_abi(project_path)
+            .map_err(|e| e.to_string())
+            .and_then(|abi| serde_json::from_str(&abi).map_err(|e| e.to_string()))
+            .map_err(|e| BenchError::CouldNotReadAbi(contract.abi.clone(), e))?;
+        for event in abi.events() {
+            events.entry(event.signature()).or_default().push(event.clone());
+        }
+    }
+
+    Ok(events)
+}
+
+/// The logs the manifest contracts index on the network, `None` when it has no contracts
+fn network_filter(manifest: &Manifest, network: &str, topics: Vec<H256>) -> Option<Filter> {
+    let details: Vec<_> = manifest
+        .contracts
+        .iter()
+        .flat_map(|contract| &contract.details)
+        .filter(|details| details.network == network)
+        .collect();
+    if details.is_empty() {
+        return None;
+    }
+
+    let mut filter = Filter::new().topic0(topics);
+    // contracts matched by event filters can be any address
+    if details.iter().all(|details| details.address().is_some()) {
+        let addresses: Vec<Address> = details
+            .iter()
+            .filter_map(|details| details.address())
+            .flat_map(|address| match address {
+                ValueOrArray::Value(address) => vec![*address],
+                ValueOrArray::Array(addresses) => addresses.clone(),
+            })
+            .collect();
+        filter = filter.address(addresses);
+    }
+
+    Some(filter)
+}
+
+async fn bench_provider(
+    manifest: &Manifest,
+    network: &Network,
+    filter: &Filter,
+    blocks: u64,
+) -> Result<(Vec<ProviderBench>, Vec<Log>), BenchError> {
+    let mut block_ranges: Vec<u64> = BENCH_BLOCK_RANGES.to_vec();
+    block_ranges.extend(network.max_block_range.map(|range| range.as_u64()));
+    block_ranges.retain(|range| *range <= blocks.max(BENCH_BLOCK_RANGES[0]));
+    block_ranges.sort();
+    block_ranges.dedup();
+
+    let mut results = vec![];
+    let mut sample_logs = vec![];
+    for endpoint in network.rpc_endpoints() {
+        let host = Url::parse(&endpoint.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| "unknown".to_string());
+        let client = create_load_balanced_client(
+            &[endpoint],
+            &RpcClientOptions::from_network(network),
+            manifest.get_custom_headers(),
+        )?;
+
+        let latest = match client.get_block_number().await {
+            Ok(latest) => latest.as_u64(),
+            Err(e) => {
+                results.push(ProviderBench {
+                    network: network.name.clone(),
+                    host,
+                    block_range: 0,
+                    blocks: 0,
+                    requests: 0,
+                    logs: 0,
+                    elapsed: Duration::ZERO,
+                    error: Some(e.to_string()),
+                });
+                continue;
+            }
+        };
+        let from_block = latest.saturating_sub(blocks);
+
+        for block_range in &block_ranges {
+            let mut result = ProviderBench {
+                network: network.name.clone(),
+                host: host.clone(),
+                block_range: *block_range,
+                blocks: latest - from_block + 1,
+                requests: 0,
+                logs: 0,
+                elapsed: Duration::ZERO,
+                error: None,
+            };
+            let mut logs = vec![];
+            let started = Instant::now();
+            let mut current = from_block;
+            while current <= latest {
+                let to_block = latest.min(current + block_range - 1);
+                let chunk = filter.clone().from_block(U64::from(current)).to_block(to_block);
+                result.requests += 1;
+                match client.get_filter_logs(&chunk).await {
+                    Ok(chunk_logs) => logs.extend(chunk_logs),
+                    Err(e) => {
+                        result.error = Some(e.to_string());
+                        break;
+                    }
+                }
+                current = to_block + 1;
+            }
+            result.elapsed = started.elapsed();
+            result.logs = logs.len();
+
+            if result.error.is_none() && sample_logs.is_empty() {
+                sample_logs = logs;
+            }
+            results.push(result);
+        }
+    }
+
+    Ok((results, sample_logs))
+}
+
+fn bench_decode(events: &HashMap<H256, Vec<Event>>, logs: &[Log]) -> DecodeBench {
+    let started = Instant::now();
+    let decoded = logs
+        .iter()
+        .filter(|log| {
+            let Some(events) = log.topics.first().and_then(|topic0| events.get(topic0)) else {
+                return false;
+            };
+            events.iter().any(|event| {
+                event
+                    .parse_log(RawLog { topics: log.topics.clone(), data: log.data.to_vec() })
+                    .is_ok()
+            })
+        })
+        .count();
+
+    DecodeBench { logs: logs.len(), decoded, elapsed: started.elapsed() }
+}
+
+fn bench_insert_rows(rows: usize) -> Vec<Vec<EthereumSqlTypeWrapper>> {
+    (0..rows)
+        .map(|row| {
+            vec![
+                EthereumSqlTypeWrapper::Address(Address::from_low_u64_be(row as u64)),
+                EthereumSqlTypeWrapper::U256(U256::from(row) * U256::exp10(18)),
+                EthereumSqlTypeWrapper::H256(H256::from_low_u64_be(row as u64)),
+                EthereumSqlTypeWrapper::U64(U64::from(row)),
+                EthereumSqlTypeWrapper::H256(H256::from_low_u64_be(row as u64 / 100)),
+                EthereumSqlTypeWrapper::String("bench".to_string()),
+                EthereumSqlTypeWrapper::U256(U256::from(row % 100)),
+            ]
+        })
+        .collect()
+}
+
+/// Writes the same rows with each insert strategy into a scratch table shaped like an event table
+async fn bench_inserts(manifest: &Manifest, rows: usize) -> Result<Vec<InsertBench>, BenchError> {
+    let postgres = PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+    let table_name = internal_table(&format!("{}_insert_bench", camel_to_snake(&manifest.name)));
+    let columns: Vec<String> = BENCH_INSERT_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let data = bench_insert_rows(rows);
+
+    let mut results = vec![];
+    for strategy in [InsertStrategy::Copy, InsertStrategy::Unnest, InsertStrategy::Values] {
+        postgres
+            .batch_execute(&format!(
+                r#"
+                    CREATE SCHEMA IF NOT EXISTS {schema};
+                    DROP TABLE IF EXISTS {table_name};
+                    CREATE TABLE {table_name} (
+                        rindexer_id SERIAL PRIMARY KEY NOT NULL,
+                        contract_address CHAR(66) NOT NULL,
+                        value NUMERIC NOT NULL,
+                        tx_hash CHAR(66) NOT NULL,
+                        block_number NUMERIC NOT NULL,
+                        block_hash CHAR(66) NOT NULL,
+                        network VARCHAR(50) NOT NULL,
+                        log_index VARCHAR(78) NOT NULL
+                    );
+                "#,
+                schema = internal_schema()
+            ))
+            .await?;
+
+        let started = Instant::now();
+        for batch in data.chunks(BENCH_INSERT_BATCH_SIZE) {
+            postgres.insert_bulk(&table_name, &columns, batch, strategy, false).await?;
+        }
+        results.push(InsertBench { strategy, rows, elapsed: started.elapsed() });
+    }
+
+    postgres.batch_execute(&format!("DROP TABLE IF EXISTS {};", table_name)).await?;
+
+    Ok(results)
+}
+
+/// Measures getLogs on every provider over the latest blocks, decoding of the logs returned and
+/// postgres insert speed for each insert strategy
+pub async fn run_bench(
+    project_path: &Path,
+    manifest: &Manifest,
+    options: &BenchOptions,
+) -> Result<BenchReport, BenchError> {
+    if let Some(network) = &options.network {
+        if !manifest.networks.iter().any(|n| &n.name == network) {
+            return Err(BenchError::NetworkNotFound(network.clone()));
+        }
+    }
+
+    let events = manifest_events(project_path, manifest)?;
+    let topics: Vec<H256> = events.keys().copied().collect();
+
+    let mut providers = vec![];
+    let mut logs = vec![];
+    for network in &manifest.networks {
+        if options.network.as_ref().is_some_and(|name| name != &network.name) {
+            continue;
+        }
+        let Some(filter) = network_filter(manifest, &network.name, topics.clone()) else {
+            continue;
+        };
+
+        let (results, sample_logs) =
+            bench_provider(manifest, network, &filter, options.blocks).await?;
+        providers.extend(results);
+        logs.extend(sample_logs);
+    }
+
+    let decode = bench_decode(&events, &logs);
+
+    let inserts = if manifest.storage.postgres_enabled() {
+        bench_inserts(manifest, options.rows).await?
+    } else {
+        vec![]
+    };
+
+    Ok(BenchReport { providers, decode, inserts })
+}
@@ -0,0 +1,481 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
+
+use ethers::{
+    abi::{Abi, RawLog},
+    types::Bytes,
+};
+use futures::FutureExt;
+use tokio::{runtime::Runtime, sync::broadcast, task::JoinHandle};
+use tracing::error;
+
+use crate::{
+    event::{
+        callback_registry::{
+            Decoder, EventCallbackRegistry, EventCallbackRegistryInformation, EventCallbackType,
+            EventResult,
+        },
+        contract_setup::{ContractInformation, CreateContractInformationError},
+    },
+    generate_random_id,
+    manifest::{
+        contract::{Contract, ContractDetails},
+        core::{Manifest, ProjectType},
+        network::Network,
+        storage::Storage,
+        yaml::{validate_manifest, ValidateManifestError},
+    },
+    provider::{CreateNetworkProvider, RetryClientError},
+    start::{start_rindexer_with_manifest, IndexingDetails},
+    GraphqlOverrideSettings,
+};
+
+/// Slow subscribers miss the oldest batches once this many are waiting
+const SUBSCRIBER_CAPACITY: usize = 1_024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum IndexerBuilderError {
+    #[error("Network {0} for contract {1} was not added to the builder")]
+    NetworkNotFound(String, String),
+
+    #[error("Could not parse the ABI of contract {0}: {1}")]
+    InvalidAbi(String, serde_json::Error),
+
+    #[error("Handler registered for {0}::{1} which is not an event in the contract ABI")]
+    HandlerEventNotFound(String, String),
+
+    #[error("Could not write the ABI of contract {0}: {1}")]
+    CouldNotWriteAbi(String, std::io::Error),
+
+    #[error("The indexer is not valid: {0}")]
+    InvalidManifest(#[from] ValidateManifestError),
+
+    #[error("Could not create the runtime: {0}")]
+    CouldNotCreateRuntime(std::io::Error),
+
+    #[error("{0}")]
+    RetryClientError(#[from] RetryClientError),
+
+    #[error("{0}")]
+    CreateContractInformationError(#[from] CreateContractInformationError),
+
+    #[error("The indexer is already running")]
+    AlreadyRunning,
+}
+
+/// A batch of logs for one event, sent to every subscriber before the handler runs
+#[derive(Debug, Clone)]
+pub struct IndexedEvents {
+    pub contract_name: String,
+    pub event_name: String,
+    pub results: Arc<Vec<EventResult>>,
+}
+
+/// The ABI decoded log of an event result from an indexer built with `IndexerBuilder`
+pub fn decoded_log(result: &EventResult) -> Option<&ethers::abi::Log> {
+    result.decoded_data.downcast_ref::<Option<ethers::abi::Log>>()?.as_ref()
+}
+
+fn abi_decoder(abi: Arc<Abi>) -> Decoder {
+    Arc::new(move |topics, data: Bytes| {
+        let log = topics.first().and_then(|topic_id| {
+            abi.events().find(|event| event.signature() == *topic_id).and_then(|event| {
+                event.parse_log(RawLog { topics: topics.clone(), data: data.to_vec() }).ok()
+            })
+        });
+        Arc::new(log)
+    })
+}
+
+/// Runs an indexer from Rust values instead of a rindexer.yaml
+///
+/// ```ignore
+/// let mut indexer = IndexerBuilder::new("my_indexer")
+///     .network(Network::new("ethereum", 1, "https://..."))
+///     .contract("USDC", USDC_ABI, vec![ContractDetails::new_with_address(...)])
+///     .on_event("USDC", "Transfer", handler)
+///     .build()?;
+/// indexer.start()?;
+/// ```
+pub struct IndexerBuilder {
+    name: String,
+    project_path: Option<PathBuf>,
+    networks: Vec<Network>,
+    contracts: Vec<(Contract, String)>,
+    storage: Storage,
+    handlers: HashMap<(String, String), EventCallbackType>,
+}
+
+impl IndexerBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            project_path: None,
+            networks: vec![],
+            contracts: vec![],
+            storage: Storage::default(),
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Where ABIs and any csv files are written, defaults to a folder named after the indexer in
+    /// the temp directory
+    pub fn project_path(mut self, project_path: PathBuf) -> Self {
+        self.project_path = Some(project_path);
+        self
+    }
+
+    pub fn network(mut self, network: Network) -> Self {
+        self.networks.push(network);
+        self
+    }
+
+    /// `abi` is the ABI json, every event in it is indexed
+    pub fn contract(mut self, name: &str, abi: &str, details: Vec<ContractDetails>) -> Self {
+        let contract = Contract {
+            name: name.to_string(),
+            details,
+            addresses: None,
+            abi: format!("./abis/{}.abi.json", name),
+            include_events: None,
+            exclude_events: None,
+            index_event_in_order: None,
+            dependency_events: None,
+            depends_on: None,
+            reorg_safe_distance: None,
+            generate_csv: None,
+            streams: None,
+            chat: None,
+            transforms: None,
+            price_enrichment: None,
+            anonymous_events: None,
+            overloaded_events: None,
+            rename_overloaded_events: false,
+            jsonb_storage: None,
+            event_networks: None,
+            event_conditions: None,
+            computed_columns: None,
+            checkpoint: None,
+            template: None,
+            priority: None,
+            lifecycle: None,
+            handler_concurrency: None,
+            enums: None,
+            typed_addresses: None,
+            table_settings: None,
+            store_raw_logs: None,
+        };
+        self.contracts.push((contract, abi.to_string()));
+        self
+    }
+
+    pub fn storage(mut self, storage: Storage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Events without a handler are still indexed and sent to subscribers
+    pub fn on_event(
+        mut self,
+        contract_name: &str,
+        event_name: &str,
+        handler: EventCallbackType,
+    ) -> Self {
+        self.handlers.insert((contract_name.to_string(), event_name.to_string()), handler);
+        self
+    }
+
+    pub fn build(self) -> Result<IndexerHandle, IndexerBuilderError> {
+        let project_path = self
+            .project_path
+            .unwrap_or_else(|| std::env::temp_dir().join(format!("rindexer-{}", self.name)));
+
+        let mut abis = HashMap::new();
+        for (contract, abi) in &self.contracts {
+            for details in &contract.details {
+                if !self.networks.iter().any(|network| network.name == details.network) {
+                    return Err(IndexerBuilderError::NetworkNotFound(
+                        details.network.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+            }
+
+            let parsed: Abi = serde_json::from_str(abi)
+                .map_err(|e| IndexerBuilderError::InvalidAbi(contract.name.clone(), e))?;
+
+            // everything reading ABIs from disk works unchanged, same as contract templates
+            let abi_path = project_path.join("abis").join(format!("{}.abi.json", contract.name));
+            fs::create_dir_all(project_path.join("abis"))
+                .and_then(|_| fs::write(&abi_path, abi))
+                .map_err(|e| IndexerBuilderError::CouldNotWriteAbi(contract.name.clone(), e))?;
+
+            abis.insert(contract.name.clone(), Arc::new(parsed));
+        }
+
+        for (contract_name, event_name) in self.handlers.keys() {
+            let has_event = abis
+                .get(contract_name)
+                .is_some_and(|abi| abi.events().any(|event| event.name == *event_name));
+            if !has_event {
+                return Err(IndexerBuilderError::HandlerEventNotFound(
+                    contract_name.clone(),
+                    event_name.clone(),
+                ));
+            }
+        }
+
+        let manifest = Manifest {
+            name: self.name,
+            description: None,
+            repository: None,
+            project_id: None,
+            project_type: ProjectType::Rust,
+            networks: self.networks,
+            storage: self.storage,
+            contracts: self.contracts.into_iter().map(|(contract, _)| contract).collect(),
+            phantom: None,
+            global: None,
+            graphql: None,
+            push: None,
+            wallets: None,
+            gas_prices: None,
+            block_anomalies: None,
+            runtime: None,
+            webhooks: None,
+        };
+
+        // the same checks a rindexer.yaml goes through, the ABIs are on disk by now
+        validate_manifest(&project_path, &manifest)?;
+
+        let (events, _) = broadcast::channel(SUBSCRIBER_CAPACITY);
+
+        Ok(IndexerHandle {
+            project_path,
+            manifest: Arc::new(manifest),
+            abis,
+            handlers: self.handlers,
+            events,
+            runtime: None,
+            task: None,
+        })
+    }
+}
+
+/// A built indexer, it runs on its own runtime so stopping it also stops every task it spawned
+pub struct IndexerHandle {
+    project_path: PathBuf,
+    manifest: Arc<Manifest>,
+    abis: HashMap<String, Arc<Abi>>,
+    handlers: HashMap<(String, String), EventCallbackType>,
+    events: broadcast::Sender<IndexedEvents>,
+    runtime: Option<Runtime>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl IndexerHandle {
+    fn callback(&self, contract_name: &str, event_name: &str) -> EventCallbackType {
+        let handler = self.handlers.get(&(contract_name.to_string(), event_name.to_string()));
+        let handler = handler.map(Arc::clone);
+        let events = self.events.clone();
+        let contract_name = contract_name.to_string();
+        let event_name = event_name.to_string();
+
+        Arc::new(move |results: Vec<EventResult>| {
+            if events.receiver_count() > 0 {
+                let _ = events.send(IndexedEvents {
+                    contract_name: contract_name.clone(),
+                    event_name: event_name.clone(),
+                    results: Arc::new(results.clone()),
+                });
+            }
+
+            match &handler {
+                Some(handler) => handler(results),
+                None => async { Ok(()) }.boxed(),
+            }
+        })
+    }
+
+    fn registry(&self) -> Result<EventCallbackRegistry, IndexerBuilderError> {
+        let network_providers = CreateNetworkProvider::create(&self.manifest)?;
+
+        let mut registry = EventCallbackRegistry::new();
+        for contract in &self.manifest.contracts {
+            let abi = Arc::clone(&self.abis[&contract.name]);
+            let contract_information = ContractInformation::create(
+                contract,
+                &network_providers,
+                abi_decoder(Arc::clone(&abi)),
+            )?;
+
+            for event in abi.events() {
+                registry.register_event(EventCallbackRegistryInformation {
+                    id: generate_random_id(10),
+                    indexer_name: self.manifest.name.clone(),
+                    topic_id: event.signature(),
+                    event_name: event.name.clone(),
+                    index_event_in_order: false,
+                    contract: contract_information.clone(),
+                    callback: self.callback(&contract.name, &event.name),
+                });
+            }
+        }
+
+        Ok(registry)
+    }
+
+    /// Starts indexing in the background, historic sync first then live indexing
+    pub fn start(&mut self) -> Result<(), IndexerBuilderError> {
+        if self.is_running() {
+            return Err(IndexerBuilderError::AlreadyRunning);
+        }
+
+        let registry = self.registry()?;
+        let runtime = self
+            .manifest
+            .runtime_settings()
+            .build()
+            .map_err(IndexerBuilderError::CouldNotCreateRuntime)?;
+
+        let project_path = self.project_path.clone();
+        let manifest = Arc::clone(&self.manifest);
+        self.task = Some(runtime.spawn(async move {
+            let result = start_rindexer_with_manifest(
+                &project_path,
+                manifest,
+                Some(IndexingDetails { registry, apply_migrations: false }),
+                GraphqlOverrideSettings { enabled: false, override_port: None },
+            )
+            .await;
+            if let Err(e) = result {
+                error!("Indexer stopped: {}", e);
+            }
+        }));
+        self.runtime = Some(runtime);
+
+        Ok(())
+    }
+
+    /// Stops indexing and every task it spawned, it can be started again afterwards
+    pub fn stop(&mut self) {
+        self.task = None;
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(|task| !task.is_finished())
+    }
+
+    /// Every batch of logs indexed from now on, across all contracts and events
+    pub fn subscribe(&self) -> broadcast::Receiver<IndexedEvents> {
+        self.events.subscribe()
+    }
+}
+
+impl Drop for IndexerHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use ethers::types::{Address, ValueOrArray};
+
+    use super::*;
+    use crate::manifest::network::LiveIndexingStrategy;
+
+    const TRANSFER_ABI: &str = r#"[{"type":"event","name":"Transfer","anonymous":false,"inputs":[
+        {"name":"from","type":"address","indexed":true},
+        {"name":"to","type":"address","indexed":true},
+        {"name":"value","type":"uint256","indexed":false}]}]"#;
+
+    fn builder(project_path: &std::path::Path) -> IndexerBuilder {
+        IndexerBuilder::new("builder_test")
+            .project_path(project_path.to_path_buf())
+            .network(Network::new("ethereum", 1, "http://localhost:8545"))
+            .contract(
+                "Token",
+                TRANSFER_ABI,
+                vec![ContractDetails::new_with_address(
+                    "ethereum".to_string(),
+                    ValueOrArray::Value(Address::zero()),
+                    None,
+                    None,
+                    None,
+                )],
+            )
+    }
+
+    #[test]
+    fn test_build_rejects_invalid_indexers() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let result = IndexerBuilder::new("builder_test")
+            .project_path(dir.path().to_path_buf())
+            .contract(
+                "Token",
+                TRANSFER_ABI,
+                vec![ContractDetails::new_with_address(
+                    "base".to_string(),
+                    ValueOrArray::Value(Address::zero()),
+                    None,
+                    None,
+                    None,
+                )],
+            )
+            .build();
+        assert!(matches!(result, Err(IndexerBuilderError::NetworkNotFound(..))));
+
+        let result = builder(dir.path())
+            .on_event("Token", "Approval", Arc::new(|_| async { Ok(()) }.boxed()))
+            .build();
+        assert!(matches!(result, Err(IndexerBuilderError::HandlerEventNotFound(..))));
+
+        let result = builder(dir.path()).contract("Broken", "not json", vec![]).build();
+        assert!(matches!(result, Err(IndexerBuilderError::InvalidAbi(..))));
+
+        // the manifest validation runs as it would for a rindexer.yaml
+        let mut network = Network::new("base", 8453, "http://localhost:8545");
+        network.live_indexing_strategy = Some(LiveIndexingStrategy::Subscribe);
+        let result = builder(dir.path()).network(network).build();
+        assert!(matches!(result, Err(IndexerBuilderError::InvalidManifest(..))));
+
+        assert!(builder(dir.path()).build().is_ok());
+        assert!(dir.path().join("abis").join("Token.abi.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_callback_sends_to_subscribers_and_runs_the_handler() {
+        let dir = tempfile::tempdir().unwrap();
+        let handled = Arc::new(AtomicBool::new(false));
+        let handler_handled = Arc::clone(&handled);
+        let indexer = builder(dir.path())
+            .on_event(
+                "Token",
+                "Transfer",
+                Arc::new(move |_| {
+                    handler_handled.store(true, Ordering::SeqCst);
+                    async { Ok(()) }.boxed()
+                }),
+            )
+            .build()
+            .unwrap();
+
+        let mut events = indexer.subscribe();
+        (indexer.callback("Token", "Transfer"))(vec![]).await.unwrap();
+
+        let indexed = events.recv().await.unwrap();
+        assert_eq!(indexed.contract_name, "Token");
+        assert_eq!(indexed.event_name, "Transfer");
+        assert!(handled.load(Ordering::SeqCst));
+
+        // events without a handler still reach subscribers
+        (indexer.callback("Token", "Approval"))(vec![]).await.unwrap();
+        assert_eq!(events.recv().await.unwrap().event_name, "Approval");
+    }
+}
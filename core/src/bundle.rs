@@ -0,0 +1,614 @@
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use tracing::info;
+
+use crate::{
+    database::postgres::user_migrations::USER_MIGRATIONS_FOLDER,
+    helpers::get_full_path,
+    manifest::yaml::{read_manifest, ReadManifestError, YAML_CONFIG_NAME},
+};
+
+const BUNDLE_MAGIC: &str = "rindexer-bundle/1";
+
+/// Age identities used to unpack a bundle when no identity file is given
+pub const BUNDLE_IDENTITY_ENV: &str = "RINDEXER_BUNDLE_IDENTITY";
+
+#[derive(thiserror::Error, Debug)]
+pub enum BundleError {
+    #[error("Could not read the manifest: {0}")]
+    ReadManifest(#[from] ReadManifestError),
+
+    #[error("{0}: {1}")]
+    Io(String, io::Error),
+
+    #[error("{0} is outside the project so can not be bundled")]
+    FileOutsideProject(String),
+
+    #[error("Invalid age recipient {0}: {1}")]
+    InvalidRecipient(String, &'static str),
+
+    #[error("No age identities given, pass an identity file or set {}", BUNDLE_IDENTITY_ENV)]
+    NoIdentities,
+
+    #[error("Invalid age identity: {0}")]
+    InvalidIdentity(&'static str),
+
+    #[error("Pack needs at least one age recipient or a KMS key")]
+    NoEncryption,
+
+    #[error("Could not encrypt the bundle: {0}")]
+    Encrypt(String),
+
+    #[error("Could not decrypt the bundle: {0}")]
+    Decrypt(String),
+
+    #[error("Not a rindexer bundle or it was written by a newer version")]
+    InvalidBundle,
+
+    #[error("KMS error: {0}")]
+    Kms(String),
+
+    #[error("KMS bundles need rindexer built with the `kms` feature")]
+    KmsNotEnabled,
+
+    #[error("Bundles need rindexer built with the `bundle` feature")]
+    BundleNotEnabled,
+}
+
+fn io_error(context: impl Into<String>) -> impl FnOnce(io::Error) -> BundleError {
+    let context = context.into();
+    move |e| BundleError::Io(context, e)
+}
+
+/// How `pack_bundle` encrypts the bundle
+#[derive(Debug, Clone)]
+pub enum BundleEncryption {
+    /// Age public keys, any of their identities can unpack the bundle
+    Age { recipients: Vec<String> },
+    /// Envelope encryption with a data key generated by the AWS KMS key, unpacking needs
+    /// `kms:Decrypt` on it
+    Kms { key_id: String },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "encryption", rename_all = "snake_case")]
+enum BundleHeader {
+    Age,
+    Kms { key_id: String, encrypted_data_key: String },
+}
+
+/// Files of the project keyed by their path relative to it, contents are hex encoded
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BundleContents {
+    files: BTreeMap<String, String>,
+}
+
+/// Only plain relative paths are bundled so unpacking can never write outside its directory
+fn relative_bundle_path(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    let plain = path.components().all(|component| matches!(component, Component::Normal(_)));
+    (plain && path.components().next().is_some()).then(|| path.to_path_buf())
+}
+
+/// Adds `path` relative to the project, anything outside of it is refused
+fn add_bundle_file(
+    project_path: &Path,
+    files: &mut Vec<String>,
+    path: &Path,
+    name: &str,
+) -> Result<(), BundleError> {
+    let full_path = path.canonicalize().map_err(io_error(format!("Could not read {}", name)))?;
+    let relative = full_path
+        .strip_prefix(project_path)
+        .ok()
+        .and_then(|relative| relative.to_str())
+        .filter(|relative| relative_bundle_path(relative).is_some())
+        .ok_or_else(|| BundleError::FileOutsideProject(name.to_string()))?
+        .to_string();
+    if !files.contains(&relative) {
+        files.push(relative);
+    }
+    Ok(())
+}
+
+/// Adds every file in `folder` and its subfolders with the extension
+fn add_bundle_folder(
+    project_path: &Path,
+    files: &mut Vec<String>,
+    folder: &Path,
+    extension: &str,
+) -> Result<(), BundleError> {
+    let entries = fs::read_dir(folder)
+        .map_err(io_error(format!("Could not read {}", folder.display())))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io_error(format!("Could not read {}", folder.display())))?;
+    let mut paths: Vec<PathBuf> = entries.into_iter().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            add_bundle_folder(project_path, files, &path, extension)?;
+        } else if path.extension().is_some_and(|path_extension| path_extension == extension) {
+            add_bundle_file(project_path, files, &path, &path.display().to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// The manifest and every file it references, the `.env` and the user migrations
+fn bundle_files(project_path: &Path) -> Result<Vec<String>, BundleError> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME))?;
+    let project_path = project_path.canonicalize().map_err(io_error("Could not read project"))?;
+
+    let mut files = vec![YAML_CONFIG_NAME.to_string()];
+    if project_path.join(".env").exists() {
+        files.push(".env".to_string());
+    }
+    for contract in &manifest.contracts {
+        // template ABIs ship with rindexer
+        if !contract.abi.is_empty() {
+            let abi = get_full_path(&project_path, &contract.abi)
+                .map_err(io_error(format!("Could not read ABI {}", contract.abi)))?;
+            add_bundle_file(&project_path, &mut files, &abi, &contract.abi)?;
+        }
+
+        for address_file in
+            contract.details.iter().filter_map(|details| details.address_file.as_ref())
+        {
+            add_bundle_file(
+                &project_path,
+                &mut files,
+                &project_path.join(address_file),
+                address_file,
+            )?;
+        }
+    }
+
+    if let Some(persisted_queries) =
+        manifest.graphql.as_ref().and_then(|graphql| graphql.persisted_queries.as_ref())
+    {
+        let path = project_path.join(&persisted_queries.path);
+        if path.is_dir() {
+            add_bundle_folder(&project_path, &mut files, &path, "graphql")?;
+        } else {
+            add_bundle_file(&project_path, &mut files, &path, &persisted_queries.path)?;
+        }
+    }
+
+    let migrations = project_path.join(USER_MIGRATIONS_FOLDER);
+    if migrations.is_dir() {
+        add_bundle_folder(&project_path, &mut files, &migrations, "sql")?;
+    }
+
+    Ok(files)
+}
+
+/// The age encryption behind bundles, only built with the `bundle` feature
+#[cfg(feature = "bundle")]
+mod crypto {
+    use std::{
+        io::{Read, Write},
+        iter,
+        str::FromStr,
+    };
+
+    use age::secrecy::SecretString;
+
+    use super::BundleError;
+
+    fn encrypt(encryptor: age::Encryptor, plaintext: &[u8]) -> Result<Vec<u8>, BundleError> {
+        let mut encrypted = vec![];
+        let mut writer = encryptor
+            .wrap_output(&mut encrypted)
+            .map_err(|e| BundleError::Encrypt(e.to_string()))?;
+        writer.write_all(plaintext).map_err(|e| BundleError::Encrypt(e.to_string()))?;
+        writer.finish().map_err(|e| BundleError::Encrypt(e.to_string()))?;
+        Ok(encrypted)
+    }
+
+    fn decrypt(mut reader: impl Read) -> Result<Vec<u8>, BundleError> {
+        let mut decrypted = vec![];
+        reader.read_to_end(&mut decrypted).map_err(|e| BundleError::Decrypt(e.to_string()))?;
+        Ok(decrypted)
+    }
+
+    fn decrypt_error(e: age::DecryptError) -> BundleError {
+        BundleError::Decrypt(e.to_string())
+    }
+
+    pub fn encrypt_to_recipients(
+        recipients: &[String],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, BundleError> {
+        let recipients = recipients
+            .iter()
+            .map(|recipient| {
+                age::x25519::Recipient::from_str(recipient)
+                    .map_err(|e| BundleError::InvalidRecipient(recipient.clone(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if recipients.is_empty() {
+            return Err(BundleError::NoEncryption);
+        }
+        let encryptor = age::Encryptor::with_recipients(
+            recipients.iter().map(|recipient| recipient as &dyn age::Recipient),
+        )
+        .map_err(|e| BundleError::Encrypt(e.to_string()))?;
+        encrypt(encryptor, plaintext)
+    }
+
+    pub fn encrypt_with_passphrase(
+        passphrase: String,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, BundleError> {
+        encrypt(age::Encryptor::with_user_passphrase(SecretString::from(passphrase)), plaintext)
+    }
+
+    /// `identities` is the text of an age identity file
+    pub fn decrypt_with_identities(
+        encrypted: &[u8],
+        identities: &str,
+    ) -> Result<Vec<u8>, BundleError> {
+        let identities = identities
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| age::x25519::Identity::from_str(line).map_err(BundleError::InvalidIdentity))
+            .collect::<Result<Vec<_>, _>>()?;
+        if identities.is_empty() {
+            return Err(BundleError::NoIdentities);
+        }
+
+        let decryptor = age::Decryptor::new(encrypted).map_err(decrypt_error)?;
+        if decryptor.is_scrypt() {
+            return Err(BundleError::InvalidBundle);
+        }
+        decrypt(
+            decryptor
+                .decrypt(identities.iter().map(|identity| identity as &dyn age::Identity))
+                .map_err(decrypt_error)?,
+        )
+    }
+
+    pub fn decrypt_with_passphrase(
+        encrypted: &[u8],
+        passphrase: String,
+    ) -> Result<Vec<u8>, BundleError> {
+        let decryptor = age::Decryptor::new(encrypted).map_err(decrypt_error)?;
+        if !decryptor.is_scrypt() {
+            return Err(BundleError::InvalidBundle);
+        }
+        let identity = age::scrypt::Identity::new(SecretString::from(passphrase));
+        decrypt(
+            decryptor
+                .decrypt(iter::once(&identity as &dyn age::Identity))
+                .map_err(decrypt_error)?,
+        )
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use age::secrecy::ExposeSecret;
+
+        use super::*;
+
+        #[test]
+        fn test_age_round_trip() {
+            let identity = age::x25519::Identity::generate();
+            let encrypted =
+                encrypt_to_recipients(&[identity.to_public().to_string()], b"manifest").unwrap();
+
+            assert_eq!(
+                decrypt_with_identities(&encrypted, identity.to_string().expose_secret()).unwrap(),
+                b"manifest".to_vec()
+            );
+            assert!(matches!(
+                decrypt_with_passphrase(&encrypted, "passphrase".to_string()),
+                Err(BundleError::InvalidBundle)
+            ));
+        }
+    }
+}
+
+#[cfg(not(feature = "bundle"))]
+mod crypto {
+    use super::BundleError;
+
+    pub fn encrypt_to_recipients(_: &[String], _: &[u8]) -> Result<Vec<u8>, BundleError> {
+        Err(BundleError::BundleNotEnabled)
+    }
+
+    pub fn encrypt_with_passphrase(_: String, _: &[u8]) -> Result<Vec<u8>, BundleError> {
+        Err(BundleError::BundleNotEnabled)
+    }
+
+    pub fn decrypt_with_identities(_: &[u8], _: &str) -> Result<Vec<u8>, BundleError> {
+        Err(BundleError::BundleNotEnabled)
+    }
+
+    pub fn decrypt_with_passphrase(_: &[u8], _: String) -> Result<Vec<u8>, BundleError> {
+        Err(BundleError::BundleNotEnabled)
+    }
+}
+
+#[cfg(feature = "kms")]
+async fn kms_client() -> aws_sdk_kms::Client {
+    let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+    aws_sdk_kms::Client::new(&config)
+}
+
+/// Plaintext and encrypted data key, the plaintext is used as the age passphrase
+#[cfg(feature = "kms")]
+async fn kms_generate_data_key(key_id: &str) -> Result<(String, Vec<u8>), BundleError> {
+    let output = kms_client()
+        .await
+        .generate_data_key()
+        .key_id(key_id)
+        .key_spec(aws_sdk_kms::types::DataKeySpec::Aes256)
+        .send()
+        .await
+        .map_err(|e| BundleError::Kms(e.to_string()))?;
+
+    match (output.plaintext, output.ciphertext_blob) {
+        (Some(plaintext), Some(encrypted)) => {
+            Ok((hex::encode(plaintext.into_inner()), encrypted.into_inner()))
+        }
+        _ => Err(BundleError::Kms("no data key returned".to_string())),
+    }
+}
+
+#[cfg(not(feature = "kms"))]
+async fn kms_generate_data_key(_key_id: &str) -> Result<(String, Vec<u8>), BundleError> {
+    Err(BundleError::KmsNotEnabled)
+}
+
+#[cfg(feature = "kms")]
+async fn kms_decrypt_data_key(key_id: &str, encrypted: Vec<u8>) -> Result<String, BundleError> {
+    let output = kms_client()
+        .await
+        .decrypt()
+        .key_id(key_id)
+        .ciphertext_blob(aws_sdk_kms::primitives::Blob::new(encrypted))
+        .send()
+        .await
+        .map_err(|e| BundleError::Kms(e.to_string()))?;
+
+    output
+        .plaintext
+        .map(|plaintext| hex::encode(plaintext.into_inner()))
+        .ok_or_else(|| BundleError::Kms("no data key returned".to_string()))
+}
+
+#[cfg(not(feature = "kms"))]
+async fn kms_decrypt_data_key(_key_id: &str, _encrypted: Vec<u8>) -> Result<String, BundleError> {
+    Err(BundleError::KmsNotEnabled)
+}
+
+fn split_bundle(bundle: &[u8]) -> Option<(BundleHeader, &[u8])> {
+    let mut parts = bundle.splitn(3, |byte| *byte == b'\n');
+    if parts.next()? != BUNDLE_MAGIC.as_bytes() {
+        return None;
+    }
+    let header = serde_json::from_slice(parts.next()?).ok()?;
+    Some((header, parts.next()?))
+}
+
+/// Encrypts the manifest, the files it references, the project `.env` and the user migrations
+/// into one file which `unpack_bundle` or `rindexer start --bundle` restore, returns the bundled
+/// paths
+pub async fn pack_bundle(
+    project_path: &Path,
+    encryption: &BundleEncryption,
+    file: &Path,
+) -> Result<Vec<String>, BundleError> {
+    let files = bundle_files(project_path)?;
+    let mut contents = BundleContents::default();
+    for path in &files {
+        let bytes = fs::read(project_path.join(path))
+            .map_err(io_error(format!("Could not read {}", path)))?;
+        contents.files.insert(path.clone(), hex::encode(bytes));
+    }
+    let plaintext =
+        serde_json::to_vec(&contents).map_err(|e| BundleError::Encrypt(e.to_string()))?;
+
+    let (header, encrypted) = match encryption {
+        BundleEncryption::Age { recipients } => {
+            if recipients.is_empty() {
+                return Err(BundleError::NoEncryption);
+            }
+            (BundleHeader::Age, crypto::encrypt_to_recipients(recipients, &plaintext)?)
+        }
+        BundleEncryption::Kms { key_id } => {
+            let (passphrase, encrypted_data_key) = kms_generate_data_key(key_id).await?;
+            (
+                BundleHeader::Kms {
+                    key_id: key_id.clone(),
+                    encrypted_data_key: hex::encode(encrypted_data_key),
+                },
+                crypto::encrypt_with_passphrase(passphrase, &plaintext)?,
+            )
+        }
+    };
+
+    let mut bundle = format!(
+        "{}\n{}\n",
+        BUNDLE_MAGIC,
+        serde_json::to_string(&header).map_err(|e| BundleError::Encrypt(e.to_string()))?
+    )
+    .into_bytes();
+    bundle.extend(encrypted);
+    fs::write(file, bundle).map_err(io_error(format!("Could not write {}", file.display())))?;
+
+    Ok(files)
+}
+
+/// Decrypts a bundle into `out_dir`, age bundles are decrypted with the identities in
+/// `identity_file` or the `RINDEXER_BUNDLE_IDENTITY` environment variable
+pub async fn unpack_bundle(
+    file: &Path,
+    identity_file: Option<&Path>,
+    out_dir: &Path,
+) -> Result<Vec<String>, BundleError> {
+    let bundle = fs::read(file).map_err(io_error(format!("Could not read {}", file.display())))?;
+    let (header, encrypted) = split_bundle(&bundle).ok_or(BundleError::InvalidBundle)?;
+
+    let decrypted = match header {
+        BundleHeader::Age => {
+            let identities = match identity_file {
+                Some(identity_file) => fs::read_to_string(identity_file).map_err(io_error(
+                    format!("Could not read identity file {}", identity_file.display()),
+                ))?,
+                None => env::var(BUNDLE_IDENTITY_ENV).map_err(|_| BundleError::NoIdentities)?,
+            };
+            crypto::decrypt_with_identities(encrypted, &identities)?
+        }
+        BundleHeader::Kms { key_id, encrypted_data_key } => {
+            let encrypted_data_key =
+                hex::decode(encrypted_data_key).map_err(|_| BundleError::InvalidBundle)?;
+            let passphrase = kms_decrypt_data_key(&key_id, encrypted_data_key).await?;
+            crypto::decrypt_with_passphrase(encrypted, passphrase)?
+        }
+    };
+
+    let contents: BundleContents =
+        serde_json::from_slice(&decrypted).map_err(|_| BundleError::InvalidBundle)?;
+
+    let mut files = vec![];
+    for (path, bytes) in contents.files {
+        let relative = relative_bundle_path(&path).ok_or(BundleError::InvalidBundle)?;
+        let bytes = hex::decode(bytes).map_err(|_| BundleError::InvalidBundle)?;
+        let target = out_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(io_error(format!("Could not create {}", parent.display())))?;
+        }
+        fs::write(&target, bytes)
+            .map_err(io_error(format!("Could not write {}", target.display())))?;
+        files.push(path);
+    }
+
+    info!("Unpacked {} files from bundle {}", files.len(), file.display());
+    Ok(files)
+}
+
+/// Unpacks a bundle into a private temporary directory which is removed when it is dropped, so
+/// the decrypted secrets only live on disk while the indexer runs
+pub async fn unpack_bundle_to_temp_dir(
+    file: &Path,
+    identity_file: Option<&Path>,
+) -> Result<TempDir, BundleError> {
+    let dir = tempfile::Builder::new()
+        .prefix("rindexer-bundle")
+        .tempdir()
+        .map_err(io_error("Could not create a temporary directory"))?;
+    unpack_bundle(file, identity_file, dir.path()).await?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_bundle_path_rejects_escapes() {
+        assert!(relative_bundle_path("abis/ERC20.json").is_some());
+        assert!(relative_bundle_path(".env").is_some());
+        assert!(relative_bundle_path("../secrets.json").is_none());
+        assert!(relative_bundle_path("/etc/passwd").is_none());
+        assert!(relative_bundle_path("").is_none());
+    }
+
+    #[test]
+    fn test_split_bundle() {
+        let mut bundle = format!("{}\n{}\n", BUNDLE_MAGIC, r#"{"encryption":"age"}"#).into_bytes();
+        bundle.extend(b"encrypted");
+        let (header, encrypted) = split_bundle(&bundle).unwrap();
+        assert!(matches!(header, BundleHeader::Age));
+        assert_eq!(encrypted, b"encrypted");
+
+        assert!(split_bundle(b"rindexer-bundle/2\n{}\n").is_none());
+    }
+
+    #[cfg(feature = "bundle")]
+    #[tokio::test]
+    async fn test_bundle_round_trips_every_referenced_file() {
+        use age::secrecy::ExposeSecret;
+
+        let project = tempfile::tempdir().unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = project.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+        write(
+            YAML_CONFIG_NAME,
+            r#"
+name: Vaults
+project_type: no-code
+networks:
+  - name: ethereum
+    chain_id: 1
+    rpc: http://127.0.0.1:8545
+storage:
+  postgres:
+    enabled: true
+graphql:
+  persisted_queries:
+    path: queries
+contracts:
+  - name: Vault
+    abi: ./abis/Vault.json
+    details:
+      - network: ethereum
+        address_file: addresses.txt
+"#,
+        );
+        write("abis/Vault.json", "[]");
+        write("addresses.txt", "0x0000000000000000000000000000000000000001\n");
+        write("queries/vaults/all.graphql", "query AllVaults { nodeId }");
+        write("migrations/001_views.sql", "SELECT 1;");
+        write("migrations/README.md", "not a migration");
+        write(".env", "DATABASE_URL=postgres://localhost");
+
+        let identity = age::x25519::Identity::generate();
+        let bundle = project.path().join("project.bundle");
+        let packed = pack_bundle(
+            project.path(),
+            &BundleEncryption::Age { recipients: vec![identity.to_public().to_string()] },
+            &bundle,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            packed,
+            vec![
+                YAML_CONFIG_NAME,
+                ".env",
+                "abis/Vault.json",
+                "addresses.txt",
+                "queries/vaults/all.graphql",
+                "migrations/001_views.sql"
+            ]
+        );
+
+        let identity_file = project.path().join("identity.txt");
+        fs::write(&identity_file, identity.to_string().expose_secret()).unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let mut unpacked = unpack_bundle(&bundle, Some(&identity_file), out.path()).await.unwrap();
+        unpacked.sort();
+        let mut expected = packed.clone();
+        expected.sort();
+        assert_eq!(unpacked, expected);
+        for path in &packed {
+            assert_eq!(
+                fs::read(out.path().join(path)).unwrap(),
+                fs::read(project.path().join(path)).unwrap()
+            );
+        }
+    }
+}
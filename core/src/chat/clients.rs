@@ -312,4 +312,59 @@ impl ChatClients {
             unreachable!("Event data should be an array");
         }
     }
+
+    /// Sends a plain message to every configured chat, for notices which are not about an event
+    pub async fn send_notification(&self, message: &str) -> Result<usize, ChatError> {
+        let mut tasks: SendMessage = Vec::new();
+
+        for instance in self.telegram.iter().flatten() {
+            let client = Arc::clone(&instance.client);
+            let chat_id = ChatId(instance.config.chat_id);
+            let message = escape_telegram_markdown(message);
+            tasks.push(task::spawn(async move {
+                client.send_message(chat_id, &message).await?;
+                Ok(())
+            }));
+        }
+        for instance in self.discord.iter().flatten() {
+            let client = Arc::clone(&instance.client);
+            let channel_id = ChannelId::new(instance.config.channel_id);
+            let message = message.to_string();
+            tasks.push(task::spawn(async move {
+                client.send_message(channel_id, &message).await?;
+                Ok(())
+            }));
+        }
+        for instance in self.slack.iter().flatten() {
+            let client = Arc::clone(&instance.client);
+            let channel = instance.config.channel.clone();
+            let message = message.to_string();
+            tasks.push(task::spawn(async move {
+                client.send_message(&channel, &message).await?;
+                Ok(())
+            }));
+        }
+
+        let mut messages_sent = 0;
+        for result in join_all(tasks).await {
+            match result {
+                Ok(Ok(_)) => messages_sent += 1,
+                Ok(Err(e)) => return Err(e),
+                Err(e) => return Err(ChatError::JoinError(e)),
+            }
+        }
+
+        Ok(messages_sent)
+    }
+}
+
+/// Telegram messages are sent as MarkdownV2 which rejects unescaped reserved characters
+fn escape_telegram_markdown(message: &str) -> String {
+    message
+        .chars()
+        .flat_map(|c| {
+            let escape = "_*[]()~`>#+-=|{}.!\\".contains(c);
+            escape.then_some('\\').into_iter().chain(std::iter::once(c))
+        })
+        .collect()
 }
@@ -5,3 +5,4 @@ mod discord;
 mod slack;
 mod telegram;
 mod template;
+pub(crate) use template::Template;
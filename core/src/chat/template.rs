@@ -28,6 +28,19 @@ impl Template {
         template
     }
 
+    /// A template of only a single placeholder resolves to the raw json value so numbers, objects
+    /// and arrays keep their type
+    pub fn parse_template_value(&self, event_data: &Value) -> Value {
+        if let Some(path) = self.value.trim().strip_prefix("{{").and_then(|p| p.strip_suffix("}}"))
+        {
+            if !path.contains("{{") && !path.contains('(') {
+                return self.get_nested_raw_value(event_data, path.trim()).unwrap_or(Value::Null);
+            }
+        }
+
+        Value::String(self.parse_template_inline(event_data))
+    }
+
     fn extract_placeholders(&self, template: &str) -> Vec<String> {
         let mut placeholders = Vec::new();
         let mut start = 0;
@@ -44,7 +57,7 @@ impl Template {
         placeholders
     }
 
-    fn get_nested_value(&self, data: &Value, path: &str) -> Option<String> {
+    fn get_nested_raw_value(&self, data: &Value, path: &str) -> Option<Value> {
         let keys: Vec<&str> = path.split('.').collect();
         let mut current = data;
         for key in keys {
@@ -54,7 +67,11 @@ impl Template {
                 return None;
             }
         }
-        Some(current.to_string().replace('"', ""))
+        Some(current.clone())
+    }
+
+    fn get_nested_value(&self, data: &Value, path: &str) -> Option<String> {
+        self.get_nested_raw_value(data, path).map(|value| value.to_string().replace('"', ""))
     }
 
     fn evaluate_function(&self, function_call: &str, event_data: &Value) -> Option<String> {
@@ -0,0 +1,579 @@
+#[cfg(feature = "csv")]
+use std::{collections::HashMap, path::PathBuf};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use async_trait::async_trait;
+use ethers::types::U64;
+use tracing::{error, info};
+
+use crate::{
+    database::postgres::{
+        client::{BulkInsertPostgresError, PostgresClient, PostgresError, TextStatement},
+        generate::{
+            generate_event_table_full_name_for_network, generate_tables_for_indexer_sql,
+            GenerateTablesForIndexerSqlError,
+        },
+        indexes::build_indexes,
+        write_retry::WriteError,
+    },
+    indexer::{get_last_synced_block_number_postgres, update_last_synced_block_number_postgres},
+    manifest::{
+        core::Manifest,
+        storage::{InsertStrategy, PostgresLayout, RelationshipsAndIndexersError, Storage},
+    },
+    EthereumSqlTypeWrapper,
+};
+#[cfg(feature = "csv")]
+use crate::{
+    indexer::{
+        get_last_synced_block_number_file, update_last_synced_block_number_for_file,
+        UpdateLastSyncedBlockNumberFile,
+    },
+    AsyncCsvAppender,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum StorageBackendError {
+    #[error("{0}")]
+    Backend(#[from] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("{0}")]
+    Postgres(#[from] PostgresError),
+
+    #[error("{0}")]
+    BulkInsert(#[from] BulkInsertPostgresError),
+
+    #[error("Error creating tables for indexer: {0}")]
+    GeneratingTables(#[from] GenerateTablesForIndexerSqlError),
+
+    #[error("{0}")]
+    RelationshipsAndIndexes(#[from] RelationshipsAndIndexersError),
+
+    #[cfg(feature = "csv")]
+    #[error("Could not write csv: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("File IO error: {0}")]
+    FileIo(#[from] std::io::Error),
+
+    #[cfg(feature = "csv")]
+    #[error("{0}")]
+    LastSyncedFile(#[from] UpdateLastSyncedBlockNumberFile),
+
+    #[error("A {0} storage backend is registered while {0} storage is enabled in the manifest, every event would be written twice")]
+    AlreadyEnabledInManifest(String),
+}
+
+impl StorageBackendError {
+    /// Postgres gave up retrying the write, see `write_retry`
+    pub fn is_retries_exhausted(&self) -> bool {
+        match self {
+            StorageBackendError::Postgres(e) => e.is_retries_exhausted(),
+            StorageBackendError::BulkInsert(e) => e.is_retries_exhausted(),
+            _ => false,
+        }
+    }
+}
+
+/// Identifies where an event on a network has been indexed up to
+#[derive(Debug, Clone, Copy)]
+pub struct CheckpointKey<'a> {
+    pub indexer_name: &'a str,
+    pub contract_name: &'a str,
+    pub event_name: &'a str,
+    pub network: &'a str,
+}
+
+/// The decoded logs for one event on one network, `rows` holds the typed values in
+/// `column_names` order and `text_rows` the same logs formatted as strings (the CSV row). Rust
+/// projects only build the rows of the storage they enable so the other one is empty
+pub struct StorageBatch<'a> {
+    pub key: CheckpointKey<'a>,
+    pub column_names: &'a [String],
+    pub rows: &'a [Vec<EthereumSqlTypeWrapper>],
+    pub text_rows: &'a [Vec<String>],
+}
+
+/// Where indexed events are stored, postgres and csv are built in and anything else can be
+/// plugged in with `register_storage_backend`
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn create_schema(
+        &self,
+        _project_path: &Path,
+        _manifest: &Manifest,
+    ) -> Result<(), StorageBackendError> {
+        Ok(())
+    }
+
+    async fn write_batch(&self, batch: &StorageBatch<'_>) -> Result<(), StorageBackendError>;
+
+    async fn read_checkpoint(
+        &self,
+        key: &CheckpointKey<'_>,
+    ) -> Result<Option<U64>, StorageBackendError>;
+
+    async fn write_checkpoint(
+        &self,
+        key: &CheckpointKey<'_>,
+        block: U64,
+    ) -> Result<(), StorageBackendError>;
+
+    async fn apply_indexes(
+        &self,
+        _project_path: &Path,
+        _manifest: &Manifest,
+    ) -> Result<(), StorageBackendError> {
+        Ok(())
+    }
+}
+
+fn storage_backends() -> &'static Mutex<Vec<Arc<dyn StorageBackend>>> {
+    static STORAGE_BACKENDS: OnceLock<Mutex<Vec<Arc<dyn StorageBackend>>>> = OnceLock::new();
+    STORAGE_BACKENDS.get_or_init(Default::default)
+}
+
+/// Writes every event and checkpoint to the backend alongside the storage in the manifest,
+/// register it before starting rindexer
+pub fn register_storage_backend(backend: Arc<dyn StorageBackend>) {
+    storage_backends().lock().unwrap().push(backend);
+}
+
+pub fn registered_storage_backends() -> Vec<Arc<dyn StorageBackend>> {
+    storage_backends().lock().unwrap().clone()
+}
+
+/// Writes the batch to every registered backend, the generated Rust handlers call this next to
+/// their own postgres and csv writes
+pub async fn write_registered_storage_backends(
+    batch: &StorageBatch<'_>,
+) -> Result<(), StorageBackendError> {
+    if batch.rows.is_empty() && batch.text_rows.is_empty() {
+        return Ok(());
+    }
+
+    for backend in registered_storage_backends() {
+        if let Err(e) = backend.write_batch(batch).await {
+            error!(
+                "{}::{} - Error writing to {}: {}",
+                batch.key.contract_name,
+                batch.key.event_name,
+                backend.name(),
+                e
+            );
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// The built in backends already write everything when they are enabled in the manifest
+fn check_not_enabled_in_manifest(
+    backends: &[Arc<dyn StorageBackend>],
+    storage: &Storage,
+) -> Result<(), StorageBackendError> {
+    for backend in backends {
+        let enabled = match backend.name() {
+            "postgres" => storage.postgres_enabled(),
+            "csv" => storage.csv_enabled(),
+            _ => false,
+        };
+        if enabled {
+            return Err(StorageBackendError::AlreadyEnabledInManifest(backend.name().to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `create_schema` and `apply_indexes` on every registered backend
+pub async fn setup_registered_storage_backends(
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<(), StorageBackendError> {
+    let backends = registered_storage_backends();
+    check_not_enabled_in_manifest(&backends, &manifest.storage)?;
+
+    for backend in backends {
+        info!("Setting up {} storage", backend.name());
+        backend.create_schema(project_path, manifest).await?;
+        backend.apply_indexes(project_path, manifest).await?;
+    }
+
+    Ok(())
+}
+
+pub struct PostgresStorageBackend {
+    client: Arc<PostgresClient>,
+    layout: PostgresLayout,
+    insert_strategy: InsertStrategy,
+    skip_conflicts: bool,
+}
+
+impl PostgresStorageBackend {
+    pub fn new(client: Arc<PostgresClient>, storage: &Storage) -> Self {
+        Self {
+            client,
+            layout: storage.postgres_layout(),
+            insert_strategy: storage.postgres_insert_strategy(),
+            skip_conflicts: storage.postgres_natural_primary_key(),
+        }
+    }
+
+    /// Writes the batch and runs `row_statements` (one list per row, projections and rollups)
+    /// in the same transaction
+    pub async fn write_batch_with_statements(
+        &self,
+        batch: &StorageBatch<'_>,
+        row_statements: &[Vec<TextStatement>],
+    ) -> Result<(), StorageBackendError> {
+        if batch.rows.is_empty() {
+            return Ok(());
+        }
+
+        let table_name = generate_event_table_full_name_for_network(
+            self.layout,
+            batch.key.indexer_name,
+            batch.key.contract_name,
+            batch.key.event_name,
+            batch.key.network,
+        );
+        if row_statements.iter().all(|statements| statements.is_empty()) {
+            self.client
+                .insert_bulk(
+                    &table_name,
+                    batch.column_names,
+                    batch.rows,
+                    self.insert_strategy,
+                    self.skip_conflicts,
+                )
+                .await?;
+        } else {
+            self.client
+                .insert_bulk_with_statements(
+                    &table_name,
+                    batch.column_names,
+                    batch.rows,
+                    self.skip_conflicts,
+                    row_statements,
+                    &[],
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StorageBackend for PostgresStorageBackend {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn create_schema(
+        &self,
+        project_path: &Path,
+        manifest: &Manifest,
+    ) -> Result<(), StorageBackendError> {
+        let sql = generate_tables_for_indexer_sql(
+            project_path,
+            &manifest.to_indexer(),
+            &manifest.storage,
+        )?;
+        self.client.execute_ddl(sql.as_str(), "create the event tables").await?;
+
+        Ok(())
+    }
+
+    async fn write_batch(&self, batch: &StorageBatch<'_>) -> Result<(), StorageBackendError> {
+        self.write_batch_with_statements(batch, &[]).await
+    }
+
+    async fn read_checkpoint(
+        &self,
+        key: &CheckpointKey<'_>,
+    ) -> Result<Option<U64>, StorageBackendError> {
+        Ok(get_last_synced_block_number_postgres(
+            &self.client,
+            key.indexer_name,
+            key.contract_name,
+            key.event_name,
+            key.network,
+        )
+        .await?)
+    }
+
+    async fn write_checkpoint(
+        &self,
+        key: &CheckpointKey<'_>,
+        block: U64,
+    ) -> Result<(), StorageBackendError> {
+        update_last_synced_block_number_postgres(
+            &self.client,
+            key.indexer_name,
+            key.contract_name,
+            key.event_name,
+            key.network,
+            block,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn apply_indexes(
+        &self,
+        project_path: &Path,
+        manifest: &Manifest,
+    ) -> Result<(), StorageBackendError> {
+        let (_, indexes) = manifest
+            .storage
+            .create_relationships_and_indexes(project_path, &manifest.name, &manifest.contracts)
+            .await?;
+
+        build_indexes(&self.client, &manifest.name, &indexes).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "csv")]
+/// One csv file per event in `{path}/{contract}/` with the last synced blocks next to them
+pub struct CsvStorageBackend {
+    path: PathBuf,
+    appenders: Mutex<HashMap<String, Arc<AsyncCsvAppender>>>,
+}
+
+#[cfg(feature = "csv")]
+impl CsvStorageBackend {
+    /// `path` is the full path of the csv folder
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, appenders: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn event_file_path(&self, contract_name: &str, event_name: &str) -> PathBuf {
+        self.path
+            .join(contract_name)
+            .join(format!("{}-{}.csv", contract_name, event_name).to_lowercase())
+    }
+
+    /// Creates the event file with its header if it does not exist yet
+    pub async fn create_event_file(
+        &self,
+        contract_name: &str,
+        event_name: &str,
+        headers: Vec<String>,
+    ) -> Result<(), StorageBackendError> {
+        tokio::fs::create_dir_all(self.path.join(contract_name).join("last-synced-blocks")).await?;
+
+        let file_path = self.event_file_path(contract_name, event_name);
+        if !file_path.exists() {
+            self.appender(&file_path).append_header(headers).await?;
+        }
+
+        Ok(())
+    }
+
+    fn appender(&self, file_path: &Path) -> Arc<AsyncCsvAppender> {
+        let file_path = file_path.display().to_string();
+        let mut appenders = self.appenders.lock().unwrap();
+        Arc::clone(
+            appenders
+                .entry(file_path.clone())
+                .or_insert_with(|| Arc::new(AsyncCsvAppender::new(&file_path))),
+        )
+    }
+}
+
+#[cfg(feature = "csv")]
+#[async_trait]
+impl StorageBackend for CsvStorageBackend {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    async fn write_batch(&self, batch: &StorageBatch<'_>) -> Result<(), StorageBackendError> {
+        if batch.text_rows.is_empty() {
+            return Ok(());
+        }
+
+        let file_path = self.event_file_path(batch.key.contract_name, batch.key.event_name);
+        self.appender(&file_path).append_bulk(batch.text_rows.to_vec()).await?;
+
+        Ok(())
+    }
+
+    async fn read_checkpoint(
+        &self,
+        key: &CheckpointKey<'_>,
+    ) -> Result<Option<U64>, StorageBackendError> {
+        let result = get_last_synced_block_number_file(
+            &self.path,
+            key.contract_name,
+            key.network,
+            key.event_name,
+        )
+        .await?;
+
+        Ok(result.filter(|block| !block.is_zero()))
+    }
+
+    async fn write_checkpoint(
+        &self,
+        key: &CheckpointKey<'_>,
+        block: U64,
+    ) -> Result<(), StorageBackendError> {
+        update_last_synced_block_number_for_file(
+            &self.path,
+            key.contract_name,
+            key.network,
+            key.event_name,
+            block,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NamedBackend(&'static str);
+
+    #[async_trait]
+    impl StorageBackend for NamedBackend {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn write_batch(&self, _batch: &StorageBatch<'_>) -> Result<(), StorageBackendError> {
+            Ok(())
+        }
+
+        async fn read_checkpoint(
+            &self,
+            _key: &CheckpointKey<'_>,
+        ) -> Result<Option<U64>, StorageBackendError> {
+            Ok(None)
+        }
+
+        async fn write_checkpoint(
+            &self,
+            _key: &CheckpointKey<'_>,
+            _block: U64,
+        ) -> Result<(), StorageBackendError> {
+            Ok(())
+        }
+    }
+
+    const KEY: CheckpointKey<'static> = CheckpointKey {
+        indexer_name: "BackendTest",
+        contract_name: "Token",
+        event_name: "Transfer",
+        network: "ethereum",
+    };
+
+    #[test]
+    fn test_rejects_backends_the_manifest_already_enables() {
+        let storage: Storage = serde_yaml::from_str("csv:\n  enabled: true\n").unwrap();
+
+        let backends: Vec<Arc<dyn StorageBackend>> = vec![Arc::new(NamedBackend("clickhouse"))];
+        assert!(check_not_enabled_in_manifest(&backends, &storage).is_ok());
+
+        let backends: Vec<Arc<dyn StorageBackend>> = vec![Arc::new(NamedBackend("csv"))];
+        assert!(matches!(
+            check_not_enabled_in_manifest(&backends, &storage),
+            Err(StorageBackendError::AlreadyEnabledInManifest(name)) if name == "csv"
+        ));
+
+        let backends: Vec<Arc<dyn StorageBackend>> = vec![Arc::new(NamedBackend("postgres"))];
+        assert!(check_not_enabled_in_manifest(&backends, &storage).is_ok());
+    }
+
+    #[cfg(feature = "csv")]
+    #[tokio::test]
+    async fn test_csv_backend_writes_batches_and_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = CsvStorageBackend::new(dir.path().to_path_buf());
+        backend
+            .create_event_file("Token", "Transfer", vec!["from".to_string(), "to".to_string()])
+            .await
+            .unwrap();
+
+        let column_names = vec!["from".to_string(), "to".to_string()];
+        let text_rows = vec![vec!["0xa".to_string(), "0xb".to_string()]];
+        backend
+            .write_batch(&StorageBatch {
+                key: KEY,
+                column_names: &column_names,
+                rows: &[],
+                text_rows: &text_rows,
+            })
+            .await
+            .unwrap();
+
+        let written =
+            std::fs::read_to_string(backend.event_file_path("Token", "Transfer")).unwrap();
+        assert_eq!(written, "from,to\n0xa,0xb\n");
+
+        assert_eq!(backend.read_checkpoint(&KEY).await.unwrap(), None);
+        backend.write_checkpoint(&KEY, U64::from(42)).await.unwrap();
+        assert_eq!(backend.read_checkpoint(&KEY).await.unwrap(), Some(U64::from(42)));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database in DATABASE_URL"]
+    async fn test_postgres_backend_writes_rows_with_their_statements() {
+        let client = PostgresClient::new().await.expect("DATABASE_URL has to point to postgres");
+        let table_name = generate_event_table_full_name_for_network(
+            PostgresLayout::Shared,
+            KEY.indexer_name,
+            KEY.contract_name,
+            KEY.event_name,
+            KEY.network,
+        );
+        let (schema, _) = table_name.split_once('.').unwrap();
+        client
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {schema} CASCADE; CREATE SCHEMA {schema};
+                CREATE TABLE {table_name} (network TEXT);
+                CREATE TABLE {schema}.statements (network TEXT);"
+            ))
+            .await
+            .unwrap();
+
+        let backend = PostgresStorageBackend::new(Arc::new(client), &Storage::default());
+        let column_names = vec!["network".to_string()];
+        let rows = vec![vec![EthereumSqlTypeWrapper::String("ethereum".to_string())]];
+        let batch =
+            StorageBatch { key: KEY, column_names: &column_names, rows: &rows, text_rows: &[] };
+        let statements = vec![vec![(
+            format!("INSERT INTO {schema}.statements (network) VALUES ($1)"),
+            vec![Some("ethereum".to_string())],
+        )]];
+        backend.write_batch_with_statements(&batch, &statements).await.unwrap();
+        backend.write_batch(&batch).await.unwrap();
+
+        let count = |table: String| {
+            let client = Arc::clone(&backend.client);
+            async move {
+                let rows =
+                    client.query(&format!("SELECT count(*) FROM {table}"), &[]).await.unwrap();
+                rows[0].get::<_, i64>(0)
+            }
+        };
+        assert_eq!(count(table_name.clone()).await, 2);
+        assert_eq!(count(format!("{schema}.statements")).await, 1);
+
+        backend.client.batch_execute(&format!("DROP SCHEMA {schema} CASCADE")).await.unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use ethers::abi::LogParam;
+
+use crate::{
+    abi::ABIInput,
+    database::postgres::{
+        client::TextStatement,
+        projection::{
+            generate_projection_schema_name, projection_table_name, EventExpressionParams,
+        },
+    },
+    event::callback_registry::TxInformation,
+    manifest::balance_change::BalanceChangeSource,
+    types::code::Code,
+};
+
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+/// Balance changes live next to the projections in the projections schema
+pub fn balance_changes_table_name(indexer_name: &str) -> String {
+    projection_table_name(indexer_name, "balance_changes")
+}
+
+pub fn generate_balance_changes_table_sql(indexer_name: &str) -> Code {
+    let table_name = balance_changes_table_name(indexer_name);
+    Code::new(format!(
+        r#"
+        CREATE SCHEMA IF NOT EXISTS {schema_name};
+        CREATE TABLE IF NOT EXISTS {table_name} (
+            "network" TEXT NOT NULL,
+            "block_number" NUMERIC NOT NULL,
+            "block_hash" TEXT NOT NULL,
+            "tx_hash" TEXT NOT NULL,
+            "log_index" NUMERIC NOT NULL,
+            "operation_index" SMALLINT NOT NULL,
+            "account" TEXT NOT NULL,
+            "currency" TEXT NOT NULL,
+            "decimals" SMALLINT,
+            "delta" NUMERIC NOT NULL,
+            PRIMARY KEY ("network", "tx_hash", "log_index", "operation_index")
+        );
+        CREATE INDEX IF NOT EXISTS balance_changes_account_idx ON {table_name} ("account", "currency");
+    "#,
+        schema_name = generate_projection_schema_name(indexer_name),
+        table_name = table_name,
+    ))
+}
+
+/// The inserts recording the debit and credit of a single event, both take the same TEXT
+/// parameters. Changes to the zero address are left out as they are mints and burns
+#[derive(Debug, Clone)]
+pub struct BalanceChangeStatements {
+    statements: Vec<String>,
+    retract_sql: String,
+    params: EventExpressionParams,
+}
+
+impl BalanceChangeStatements {
+    pub fn new(indexer_name: &str, source: &BalanceChangeSource, inputs: &[ABIInput]) -> Self {
+        let mut params = EventExpressionParams::new();
+        let block_hash = params.expression_sql(inputs, "$block_hash", "TEXT");
+        let tx_hash = params.expression_sql(inputs, "$tx_hash", "TEXT");
+        let log_index = params.expression_sql(inputs, "$log_index", "NUMERIC");
+        let amount = params.expression_sql(inputs, &source.amount, "NUMERIC");
+        let currency = params.expression_sql(inputs, source.currency(), "TEXT");
+        let decimals = source.decimals.map_or("NULL".to_string(), |decimals| decimals.to_string());
+
+        let operations = [(0, &source.from, format!("-{}", amount)), (1, &source.to, amount)];
+        let statements = operations
+            .into_iter()
+            .filter_map(|(operation_index, account, delta)| {
+                let account = params.expression_sql(inputs, account.as_ref()?, "TEXT");
+                Some(format!(
+                    "INSERT INTO {} (\"network\", \"block_number\", \"block_hash\", \"tx_hash\", \"log_index\", \"operation_index\", \"account\", \"currency\", \"decimals\", \"delta\") \
+                    SELECT * FROM (SELECT $1::TEXT, $2::NUMERIC, {}, {}, {}, {}::SMALLINT, {} AS account, {}, {}::SMALLINT, {}) AS change \
+                    WHERE change.account <> '{}' ON CONFLICT DO NOTHING",
+                    balance_changes_table_name(indexer_name),
+                    block_hash,
+                    tx_hash,
+                    log_index,
+                    operation_index,
+                    account,
+                    currency,
+                    decimals,
+                    delta,
+                    ZERO_ADDRESS
+                ))
+            })
+            .collect();
+
+        let retract_sql = format!(
+            "DELETE FROM {} WHERE \"network\" = $1 AND \"tx_hash\" = $2 AND \"log_index\" = $3::NUMERIC",
+            balance_changes_table_name(indexer_name)
+        );
+
+        Self { statements, retract_sql, params }
+    }
+
+    pub fn for_event(
+        &self,
+        inputs: &[ABIInput],
+        log_params: &[LogParam],
+        tx_information: &TxInformation,
+    ) -> Vec<(String, Vec<Option<String>>)> {
+        let values = self.params.values(inputs, log_params, tx_information);
+        self.statements.iter().map(|sql| (sql.clone(), values.clone())).collect()
+    }
+
+    /// Deletes the debit and credit of an event a reorg removed
+    pub fn for_removed_event(&self, tx_information: &TxInformation) -> TextStatement {
+        (
+            self.retract_sql.clone(),
+            vec![
+                Some(tx_information.network.clone()),
+                Some(format!("{:?}", tx_information.transaction_hash)),
+                Some(tx_information.log_index.to_string()),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str, type_: &str) -> ABIInput {
+        ABIInput {
+            indexed: None,
+            name: name.to_string(),
+            type_: type_.to_string(),
+            components: None,
+        }
+    }
+
+    #[test]
+    fn test_debit_and_credit_share_params() {
+        let inputs =
+            vec![input("from", "address"), input("to", "address"), input("value", "uint256")];
+        let source = BalanceChangeSource {
+            contract_name: "USDC".to_string(),
+            event_name: "Transfer".to_string(),
+            from: Some("$from".to_string()),
+            to: Some("$to".to_string()),
+            amount: "$value".to_string(),
+            currency: Some("'USDC'".to_string()),
+            decimals: Some(6),
+        };
+
+        let statements = BalanceChangeStatements::new("indexer", &source, &inputs);
+        assert_eq!(statements.statements.len(), 2);
+        assert!(statements.statements[0].contains("0::SMALLINT, ($7::TEXT)::TEXT AS account"));
+        assert!(statements.statements[0].contains("-($6::NUMERIC)::NUMERIC"));
+        assert!(statements.statements[1].contains("1::SMALLINT, ($8::TEXT)::TEXT AS account"));
+        assert_eq!(statements.params.len(), 8);
+    }
+}
@@ -0,0 +1,77 @@
+use ethers::types::U64;
+
+use crate::{
+    database::postgres::client::{PostgresClient, PostgresError},
+    types::code::Code,
+};
+
+/// Written for `uncle` and `reorged` rows, `missed_slot` rows also set `missed_slots`
+pub const BLOCK_ANOMALY_COLUMNS: [&str; 4] = ["network", "block_number", "kind", "hash"];
+
+/// `uncle` rows hold the uncle hash against the block which included it, `missed_slot` rows hold
+/// the block after the gap and how many slots before it had no block and `reorged` rows hold the
+/// hash of a block a reorg replaced
+pub fn generate_block_anomalies_tables_sql(table_name: &str, last_synced_table_name: &str) -> Code {
+    let schema_name = table_name.split('.').next().unwrap_or(table_name);
+
+    Code::new(format!(
+        "CREATE SCHEMA IF NOT EXISTS {schema_name};
+        CREATE TABLE IF NOT EXISTS {table_name} (
+            network VARCHAR(50) NOT NULL,
+            block_number NUMERIC NOT NULL,
+            kind VARCHAR(20) NOT NULL,
+            hash CHAR(66) NOT NULL,
+            missed_slots NUMERIC,
+            PRIMARY KEY (network, block_number, kind, hash)
+        );
+        CREATE TABLE IF NOT EXISTS {last_synced_table_name} (
+            network VARCHAR(50) PRIMARY KEY NOT NULL,
+            block_number NUMERIC NOT NULL
+        );"
+    ))
+}
+
+pub async fn block_anomalies_last_synced(
+    client: &PostgresClient,
+    last_synced_table_name: &str,
+    network: &str,
+) -> Result<Option<U64>, PostgresError> {
+    let row = client
+        .query_one_or_none(
+            &format!(
+                "SELECT block_number::TEXT FROM {} WHERE network = $1",
+                last_synced_table_name
+            ),
+            &[&network],
+        )
+        .await?;
+
+    Ok(row.and_then(|row| row.get::<_, String>(0).parse::<u64>().ok()).map(U64::from))
+}
+
+/// Takes the network and block number as TEXT params
+pub fn update_block_anomalies_last_synced_sql(last_synced_table_name: &str) -> String {
+    format!(
+        "INSERT INTO {} (network, block_number) VALUES ($1, $2::NUMERIC) \
+        ON CONFLICT (network) DO UPDATE SET block_number = EXCLUDED.block_number",
+        last_synced_table_name
+    )
+}
+
+/// Takes the network, block number, block hash and missed slots as TEXT params
+pub fn insert_missed_slots_sql(table_name: &str) -> String {
+    format!(
+        "INSERT INTO {} (network, block_number, kind, hash, missed_slots) \
+        VALUES ($1, $2::NUMERIC, 'missed_slot', $3, $4::NUMERIC) ON CONFLICT DO NOTHING",
+        table_name
+    )
+}
+
+/// Takes the network and the first replaced block as TEXT params, what was recorded about the
+/// replaced blocks goes while the record of their hashes being replaced stays
+pub fn delete_replaced_block_anomalies_sql(table_name: &str) -> String {
+    format!(
+        "DELETE FROM {} WHERE network = $1 AND block_number >= $2::NUMERIC AND kind <> 'reorged'",
+        table_name
+    )
+}
@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use crate::{
+    abi::ABIItem,
+    database::postgres::generate::{
+        generate_event_table_full_names, GenerateTablesForIndexerSqlError,
+    },
+    indexer::Indexer,
+    manifest::storage::{PostgresCdc, Storage},
+};
+
+fn generate_publication_sql(publication: &str, table_names: &[String]) -> String {
+    let mut sql = format!(
+        "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_publication WHERE pubname = '{publication}') THEN CREATE PUBLICATION {publication}; END IF; END $$;"
+    );
+
+    for table_name in table_names {
+        let Some((schema_name, table)) = table_name.split_once('.') else {
+            continue;
+        };
+        // adding a table twice errors so only the ones not in it yet are added
+        sql.push_str(&format!(
+            "DO $$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_publication_tables WHERE pubname = '{publication}' AND schemaname = '{schema_name}' AND tablename = '{table}') THEN ALTER PUBLICATION {publication} ADD TABLE {table_name}; END IF; END $$;"
+        ));
+    }
+
+    sql
+}
+
+/// Replica identity and publication membership of the given tables
+pub fn generate_cdc_sql(cdc: &PostgresCdc, table_names: &[String]) -> String {
+    let mut sql = String::new();
+
+    if let Some(replica_identity) = cdc.replica_identity {
+        for table_name in table_names {
+            sql.push_str(&format!(
+                "ALTER TABLE {} REPLICA IDENTITY {};",
+                table_name,
+                replica_identity.as_sql()
+            ));
+        }
+    }
+
+    if let Some(publication) = &cdc.publication {
+        sql.push_str(&generate_publication_sql(publication, table_names));
+    }
+
+    sql
+}
+
+/// CDC settings for every event table of the indexer, empty when `cdc` is not set
+pub fn generate_cdc_sql_for_indexer(
+    project_path: &Path,
+    indexer: &Indexer,
+    storage: &Storage,
+) -> Result<String, GenerateTablesForIndexerSqlError> {
+    let Some(cdc) = storage.postgres_cdc() else {
+        return Ok(String::new());
+    };
+
+    let mut table_names = vec![];
+    for contract in &indexer.contracts {
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+        let abi_items = ABIItem::read_abi_items(project_path, contract)?;
+        for event_info in ABIItem::extract_event_names_and_signatures_from_abi(abi_items)? {
+            table_names.extend(generate_event_table_full_names(
+                storage.postgres_layout(),
+                &indexer.name,
+                contract,
+                &contract_name,
+                &event_info.name,
+            ));
+        }
+    }
+
+    Ok(generate_cdc_sql(cdc, &table_names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::storage::ReplicaIdentity;
+
+    #[test]
+    fn test_generate_cdc_sql() {
+        let cdc = PostgresCdc {
+            replica_identity: Some(ReplicaIdentity::Full),
+            publication: Some("rindexer_cdc".to_string()),
+        };
+        let sql = generate_cdc_sql(&cdc, &["my_indexer_erc20.transfer".to_string()]);
+
+        assert!(sql.starts_with("ALTER TABLE my_indexer_erc20.transfer REPLICA IDENTITY FULL;"));
+        assert!(sql.contains("CREATE PUBLICATION rindexer_cdc;"));
+        assert!(sql.contains("schemaname = 'my_indexer_erc20' AND tablename = 'transfer'"));
+        assert!(sql.contains("ALTER PUBLICATION rindexer_cdc ADD TABLE my_indexer_erc20.transfer;"));
+    }
+
+    #[test]
+    fn test_generate_cdc_sql_empty_without_settings() {
+        assert!(generate_cdc_sql(&PostgresCdc::default(), &["a.b".to_string()]).is_empty());
+    }
+}
@@ -1,32 +1,101 @@
-use std::{env, time::Duration};
+use std::{collections::HashMap, env, future::Future, time::Duration};
 
-use bb8::{Pool, RunError};
+use async_trait::async_trait;
+use bb8::{CustomizeConnection, Pool, PooledConnection, RunError};
 use bb8_postgres::PostgresConnectionManager;
 use bytes::Buf;
 use dotenv::dotenv;
 use futures::pin_mut;
 use native_tls::TlsConnector;
 use postgres_native_tls::MakeTlsConnector;
-use tokio::{task, time::timeout};
+use tokio::{
+    runtime::Handle,
+    sync::Mutex,
+    task,
+    time::{timeout, Instant},
+};
 use tokio_postgres::{
     binary_copy::BinaryCopyInWriter,
     config::SslMode,
     types::{ToSql, Type as PgType},
-    Config, CopyInSink, Error as PgError, Row, Statement, ToStatement,
-    Transaction as PgTransaction,
+    Client, Config, CopyInSink, Error as PgError, Row, Statement, ToStatement, Transaction,
 };
 use tracing::{debug, error};
 
-use crate::database::postgres::{
-    generate::generate_event_table_columns_names_sql, sql_type_wrapper::EthereumSqlTypeWrapper,
+use crate::{
+    database::postgres::{
+        ddl_audit::{confirm_destructive_ddl, insert_ddl_audit_sql, is_destructive_ddl},
+        generate::generate_event_table_columns_names_sql,
+        insert_strategy::{select_insert_path, InsertPath, MAX_QUERY_PARAMETERS},
+        metrics::postgres_write_metrics,
+        sql_type_wrapper::EthereumSqlTypeWrapper,
+        write_retry::{WriteError, WriteRetryQueue},
+    },
+    event::callback_registry::TxInformation,
+    manifest::storage::{InsertStrategy, PostgresSessionSettings, Storage},
 };
 
+const ON_CONFLICT_DO_NOTHING: &str = " ON CONFLICT DO NOTHING";
+
+/// The env var the connection string is read from unless the manifest names another
+pub const DEFAULT_CONNECTION_STRING_ENV: &str = "DATABASE_URL";
+
 pub fn connection_string() -> Result<String, env::VarError> {
+    connection_string_from_env(DEFAULT_CONNECTION_STRING_ENV)
+}
+
+pub fn connection_string_from_env(name: &str) -> Result<String, env::VarError> {
     dotenv().ok();
-    let connection = env::var("DATABASE_URL")?;
+    let connection = env::var(name)?;
     Ok(connection)
 }
 
+pub(crate) fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quotes every schema of a comma separated search path so a name can not inject SQL
+fn quote_search_path(search_path: &str) -> String {
+    search_path
+        .split(',')
+        .map(|schema| format!("\"{}\"", schema.trim().trim_matches('"').replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The SET statements for the session settings, `None` when there is nothing to set
+fn session_settings_sql(settings: &PostgresSessionSettings) -> Option<String> {
+    let mut statements = vec![];
+    if let Some(application_name) = &settings.application_name {
+        statements.push(format!("SET application_name = {};", quote_literal(application_name)));
+    }
+    if let Some(statement_timeout) = &settings.statement_timeout {
+        statements.push(format!("SET statement_timeout = {};", quote_literal(statement_timeout)));
+    }
+    if let Some(search_path) = &settings.search_path {
+        statements.push(format!("SET search_path = {};", quote_search_path(search_path)));
+    }
+
+    if statements.is_empty() {
+        None
+    } else {
+        Some(statements.join(" "))
+    }
+}
+
+/// Applies the session settings to every connection the pool opens
+#[derive(Debug)]
+struct SessionSettingsCustomizer {
+    sql: String,
+}
+
+#[async_trait]
+impl CustomizeConnection<Client, PgError> for SessionSettingsCustomizer {
+    async fn on_acquire(&self, connection: &mut Client) -> Result<(), PgError> {
+        connection.batch_execute(&self.sql).await
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum PostgresConnectionError {
     #[error("The database connection string is wrong please check your environment: {0}")]
@@ -46,6 +115,9 @@ pub enum PostgresConnectionError {
 
     #[error("Could not create tls connector")]
     CouldNotCreateTlsConnector,
+
+    #[error("Could not apply the postgres session settings: {0}")]
+    InvalidSessionSettings(tokio_postgres::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -55,10 +127,180 @@ pub enum PostgresError {
 
     #[error("Connection pool error: {0}")]
     ConnectionPoolError(#[from] RunError<tokio_postgres::Error>),
+
+    #[error("Gave up after {0} attempts: {1}")]
+    WriteRetriesExhausted(u32, Box<PostgresError>),
+
+    #[error("Destructive DDL to {0} was not confirmed")]
+    DestructiveDdlNotConfirmed(String),
 }
 
+type OwnedConnection = PooledConnection<'static, PostgresConnectionManager<MakeTlsConnector>>;
+
+/// A transaction on a pooled connection it owns so it can be held across tasks, it is rolled
+/// back when dropped without being committed.
+///
+/// It replaces the public `transaction: tokio_postgres::Transaction<'static>` field, which only
+/// lived as long as the pool by transmuting its lifetime. Statements go through the methods
+/// here instead and it finishes with `commit` or `rollback`.
 pub struct PostgresTransaction {
-    pub transaction: PgTransaction<'static>,
+    // only taken once the transaction is committed, rolled back or dropped
+    conn: Option<OwnedConnection>,
+}
+
+impl PostgresTransaction {
+    async fn begin(conn: OwnedConnection) -> Result<Self, PostgresError> {
+        conn.batch_execute("BEGIN").await?;
+        Ok(Self { conn: Some(conn) })
+    }
+
+    fn client(&self) -> &Client {
+        self.conn.as_ref().expect("transaction connection is only taken when it finishes")
+    }
+
+    fn finish(&mut self) -> OwnedConnection {
+        self.conn.take().expect("transaction connection is only taken when it finishes")
+    }
+
+    pub async fn batch_execute(&self, sql: &str) -> Result<(), PostgresError> {
+        Ok(self.client().batch_execute(sql).await?)
+    }
+
+    pub async fn copy_in<T, U>(&self, statement: &T) -> Result<CopyInSink<U>, PostgresError>
+    where
+        T: ?Sized + ToStatement,
+        U: Buf + 'static + Send,
+    {
+        Ok(self.client().copy_in(statement).await?)
+    }
+
+    /// Deletes the rows of logs a reorg removed from the event table, returns the rows deleted
+    pub async fn delete_removed_logs(
+        &self,
+        table_name: &str,
+        removed: &[&TxInformation],
+        compact_bytea: bool,
+    ) -> Result<u64, PostgresError> {
+        let rows: Vec<_> = removed
+            .iter()
+            .map(|tx_information| removed_log_row(tx_information, compact_bytea))
+            .collect();
+        self.execute_for_rows(&removed_logs_delete_sql(table_name), &rows).await
+    }
+
+    pub async fn execute<T>(
+        &self,
+        query: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, PostgresError>
+    where
+        T: ?Sized + ToStatement,
+    {
+        Ok(self.client().execute(query, params).await?)
+    }
+
+    pub async fn query<T>(
+        &self,
+        query: &T,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, PostgresError>
+    where
+        T: ?Sized + ToStatement,
+    {
+        Ok(self.client().query(query, params).await?)
+    }
+
+    pub async fn prepare(
+        &self,
+        query: &str,
+        parameter_types: &[PgType],
+    ) -> Result<Statement, PostgresError> {
+        Ok(self.client().prepare_typed(query, parameter_types).await?)
+    }
+
+    /// Runs the statement once for every row of params, returns the rows affected
+    pub async fn execute_for_rows(
+        &self,
+        statement: &str,
+        rows: &[Vec<EthereumSqlTypeWrapper>],
+    ) -> Result<u64, PostgresError> {
+        let statement = self.client().prepare(statement).await?;
+        let mut affected = 0;
+        for row in rows {
+            let params: Vec<&(dyn ToSql + Sync)> =
+                row.iter().map(|param| param as &(dyn ToSql + Sync)).collect();
+            affected += self.client().execute(&statement, &params).await?;
+        }
+        Ok(affected)
+    }
+
+    /// Runs statements which take their params as text, returns the rows affected
+    pub async fn execute_text_statements(
+        &self,
+        statements: &[TextStatement],
+    ) -> Result<u64, PostgresError> {
+        let mut affected = 0;
+        for (sql, params) in statements {
+            let statement = self.prepare(sql, &vec![PgType::TEXT; params.len()]).await?;
+            let params: Vec<&(dyn ToSql + Sync)> =
+                params.iter().map(|param| param as &(dyn ToSql + Sync)).collect();
+            affected += self.client().execute(&statement, &params).await?;
+        }
+        Ok(affected)
+    }
+
+    pub async fn commit(mut self) -> Result<(), PostgresError> {
+        let conn = self.finish();
+        let start = Instant::now();
+        conn.batch_execute("COMMIT").await?;
+        postgres_write_metrics().observe_commit(start.elapsed());
+        Ok(())
+    }
+
+    pub async fn rollback(mut self) -> Result<(), PostgresError> {
+        self.finish().batch_execute("ROLLBACK").await?;
+        Ok(())
+    }
+}
+
+impl Drop for PostgresTransaction {
+    fn drop(&mut self) {
+        // the connection only goes back to the pool once the rollback ran
+        let Some(conn) = self.conn.take() else {
+            return;
+        };
+        match Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    if let Err(e) = conn.batch_execute("ROLLBACK").await {
+                        error!("Could not roll back a dropped transaction: {}", e);
+                    }
+                });
+            }
+            Err(_) => {
+                // the client does not need the runtime to send the rollback, once the runtime
+                // driving the connection is gone it fails and the closed connection is discarded
+                // by the pool
+                if let Err(e) = futures::executor::block_on(conn.batch_execute("ROLLBACK")) {
+                    error!("Could not roll back a dropped transaction: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// A session advisory lock held on its own pooled connection, it has to be released before it
+/// is dropped or the connection goes back to the pool still holding it
+pub struct AdvisoryLock<'a> {
+    conn: PooledConnection<'a, PostgresConnectionManager<MakeTlsConnector>>,
+    key: String,
+}
+
+impl AdvisoryLock<'_> {
+    pub async fn release(self) -> Result<(), PostgresError> {
+        self.conn.execute("SELECT pg_advisory_unlock(hashtext($1))", &[&self.key]).await?;
+        Ok(())
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -72,12 +314,147 @@ pub enum BulkInsertPostgresError {
 
 pub struct PostgresClient {
     pool: Pool<PostgresConnectionManager<MakeTlsConnector>>,
+    // table name -> column name and type, needed to cast UNNEST arrays
+    column_types: Mutex<HashMap<String, HashMap<String, String>>>,
+    write_retry: WriteRetryQueue,
+    /// Recorded against every DDL statement in the audit table
+    indexer_name: Option<String>,
+    confirm_destructive_ddl: bool,
+    /// DDL and COPY run without it, building an index or copying a batch takes longer than a
+    /// query should
+    statement_timeout: Option<String>,
+}
+
+/// SQL with its params as text, cast by the statement itself
+pub type TextStatement = (String, Vec<Option<String>>);
+
+/// Deletes the row of a log a reorg removed, params are built by `removed_log_row`
+pub fn removed_logs_delete_sql(table_name: &str) -> String {
+    format!("DELETE FROM {} WHERE network = $1 AND tx_hash = $2 AND log_index = $3", table_name)
+}
+
+pub fn removed_log_row(
+    tx_information: &TxInformation,
+    compact_bytea: bool,
+) -> Vec<EthereumSqlTypeWrapper> {
+    let row = vec![
+        EthereumSqlTypeWrapper::String(tx_information.network.clone()),
+        EthereumSqlTypeWrapper::H256(tx_information.transaction_hash),
+        EthereumSqlTypeWrapper::U256(tx_information.log_index),
+    ];
+    if compact_bytea {
+        row.into_iter().map(EthereumSqlTypeWrapper::into_compact_bytea).collect()
+    } else {
+        row
+    }
+}
+
+/// Statements with text params, prepared once per transaction and reused for every row
+async fn execute_text_statements(
+    transaction: &Transaction<'_>,
+    prepared: &mut HashMap<String, Statement>,
+    statements: &[TextStatement],
+) -> Result<(), PostgresError> {
+    for (sql, params) in statements {
+        let statement = match prepared.get(sql) {
+            Some(statement) => statement.clone(),
+            None => {
+                let statement = transaction
+                    .prepare_typed(sql, &vec![PgType::TEXT; params.len()])
+                    .await
+                    .map_err(PostgresError::PgError)?;
+                prepared.insert(sql.clone(), statement.clone());
+                statement
+            }
+        };
+        let params: Vec<&(dyn ToSql + Sync)> =
+            params.iter().map(|param| param as &(dyn ToSql + Sync)).collect();
+        transaction.execute(&statement, &params).await.map_err(PostgresError::PgError)?;
+    }
+    Ok(())
+}
+
+fn bulk_insert_values_query<'a>(
+    table_name: &str,
+    column_names: &[String],
+    bulk_data: &'a [Vec<EthereumSqlTypeWrapper>],
+    skip_conflicts: bool,
+) -> (String, Vec<&'a (dyn ToSql + Sync + 'a)>) {
+    let total_columns = column_names.len();
+
+    let mut query = format!(
+        "INSERT INTO {} ({}) VALUES ",
+        table_name,
+        generate_event_table_columns_names_sql(column_names),
+    );
+    let mut params: Vec<&'a (dyn ToSql + Sync + 'a)> = Vec::new();
+
+    for (i, row) in bulk_data.iter().enumerate() {
+        if i > 0 {
+            query.push(',');
+        }
+        let mut placeholders = vec![];
+        for j in 0..total_columns {
+            placeholders.push(format!("${}", i * total_columns + j + 1));
+        }
+        query.push_str(&format!("({})", placeholders.join(",")));
+
+        for param in row {
+            params.push(param as &'a (dyn ToSql + Sync + 'a));
+        }
+    }
+    if skip_conflicts {
+        query.push_str(ON_CONFLICT_DO_NOTHING);
+    }
+
+    (query, params)
 }
 
 impl PostgresClient {
+    /// Connects with the connection string in DATABASE_URL
     pub async fn new() -> Result<Self, PostgresConnectionError> {
-        async fn _new(disable_ssl: bool) -> Result<PostgresClient, PostgresConnectionError> {
-            let connection_str = connection_string()?;
+        Self::new_with_connection_string(&connection_string()?).await
+    }
+
+    /// Connects with the connection string in the given env var
+    pub async fn new_from_env(name: &str) -> Result<Self, PostgresConnectionError> {
+        Self::new_with_connection_string(&connection_string_from_env(name)?).await
+    }
+
+    /// Connects with the connection string and session settings the manifest storage is
+    /// configured with
+    pub async fn from_storage(
+        storage: &Storage,
+        indexer_name: &str,
+    ) -> Result<Self, PostgresConnectionError> {
+        let mut client = Self::connect(
+            &storage.postgres_connection_string()?,
+            session_settings_sql(&storage.postgres_session_settings(indexer_name)),
+        )
+        .await?;
+        client.write_retry = WriteRetryQueue::new(storage.postgres_write_retry());
+        client.indexer_name = Some(indexer_name.to_string());
+        client.confirm_destructive_ddl = storage.postgres_confirm_destructive_ddl();
+        client.statement_timeout =
+            storage.postgres_session_settings(indexer_name).statement_timeout;
+        Ok(client)
+    }
+
+    pub async fn new_with_connection_string(
+        connection_str: &str,
+    ) -> Result<Self, PostgresConnectionError> {
+        Self::connect(connection_str, None).await
+    }
+
+    async fn connect(
+        connection_str: &str,
+        session_sql: Option<String>,
+    ) -> Result<Self, PostgresConnectionError> {
+        async fn _new(
+            connection_str: &str,
+            session_sql: Option<String>,
+            disable_ssl: bool,
+        ) -> Result<PostgresClient, PostgresConnectionError> {
             let mut config: Config = connection_str
                 .parse()
                 .map_err(|_| PostgresConnectionError::CouldNotParseConnectionString)?;
@@ -103,7 +480,7 @@ impl PostgresClient {
                             config.get_ssl_mode() != SslMode::Disable &&
                             !connection_str.contains("sslmode=require")
                         {
-                            return Box::pin(_new(true)).await;
+                            return Box::pin(_new(connection_str, session_sql, true)).await;
                         }
                         error!("Error connecting to database: {}", e);
                         return Err(PostgresConnectionError::CanNotConnectToDatabase);
@@ -117,6 +494,13 @@ impl PostgresClient {
             // Spawn the connection future to ensure the connection is established
             let connection_handle = task::spawn(connection);
 
+            if let Some(sql) = &session_sql {
+                client
+                    .batch_execute(sql)
+                    .await
+                    .map_err(PostgresConnectionError::InvalidSessionSettings)?;
+            }
+
             // Perform a simple query to check the connection
             match client.query_one("SELECT 1", &[]).await {
                 Ok(_) => {}
@@ -133,19 +517,167 @@ impl PostgresClient {
 
             let manager = PostgresConnectionManager::new(config, tls_connector);
 
-            let pool = Pool::builder().build(manager).await?;
+            let mut builder = Pool::builder();
+            if let Some(sql) = session_sql {
+                builder =
+                    builder.connection_customizer(Box::new(SessionSettingsCustomizer { sql }));
+            }
+            let pool = builder.build(manager).await?;
 
-            Ok(PostgresClient { pool })
+            Ok(PostgresClient {
+                pool,
+                column_types: Mutex::new(HashMap::new()),
+                write_retry: WriteRetryQueue::new(Default::default()),
+                indexer_name: None,
+                confirm_destructive_ddl: false,
+                statement_timeout: None,
+            })
         }
 
-        _new(false).await
+        _new(connection_str, session_sql, false).await
+    }
+
+    async fn connection(
+        &self,
+    ) -> Result<PooledConnection<'_, PostgresConnectionManager<MakeTlsConnector>>, PostgresError>
+    {
+        let start = Instant::now();
+        let connection = self.pool.get().await?;
+        postgres_write_metrics().observe_pool_wait(start.elapsed());
+        Ok(connection)
+    }
+
+    async fn owned_connection(&self) -> Result<OwnedConnection, PostgresError> {
+        let start = Instant::now();
+        let connection = self.pool.get_owned().await?;
+        postgres_write_metrics().observe_pool_wait(start.elapsed());
+        Ok(connection)
     }
 
     pub async fn batch_execute(&self, sql: &str) -> Result<(), PostgresError> {
-        let conn = self.pool.get().await?;
+        let conn = self.connection().await?;
         conn.batch_execute(sql).await.map_err(PostgresError::PgError)
     }
 
+    /// Runs the statements with the `statement_timeout` lifted, they run on their own so a
+    /// `CONCURRENTLY` statement is not wrapped in a transaction
+    async fn batch_execute_without_timeout(&self, sql: &str) -> Result<(), PostgresError> {
+        let conn = self.connection().await?;
+        let Some(statement_timeout) = &self.statement_timeout else {
+            return conn.batch_execute(sql).await.map_err(PostgresError::PgError);
+        };
+
+        conn.batch_execute("SET statement_timeout = 0").await?;
+        let result = conn.batch_execute(sql).await;
+        // the connection goes back to the pool so it gets the timeout back either way
+        conn.batch_execute(&format!(
+            "SET statement_timeout = {}",
+            quote_literal(statement_timeout)
+        ))
+        .await?;
+        result.map_err(PostgresError::PgError)
+    }
+
+    /// Lifts the `statement_timeout` for the rest of the transaction
+    async fn lift_statement_timeout(
+        &self,
+        transaction: &tokio_postgres::Transaction<'_>,
+    ) -> Result<(), PgError> {
+        if self.statement_timeout.is_some() {
+            transaction.batch_execute("SET LOCAL statement_timeout = 0").await?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the advisory lock keyed by `key`, the connection holding it stays idle so it
+    /// does not block `CONCURRENTLY` statements run on other connections
+    pub async fn advisory_lock(&self, key: &str) -> Result<AdvisoryLock<'_>, PostgresError> {
+        let conn = self.connection().await?;
+        conn.execute("SELECT pg_advisory_lock(hashtext($1))", &[&key]).await?;
+        Ok(AdvisoryLock { conn, key: key.to_string() })
+    }
+
+    /// Runs generated DDL and records it in the ddl_audit internal table with why it ran, the table
+    /// is created by `setup_postgres`. Destructive statements need confirming when
+    /// `confirm_destructive_ddl` is set
+    pub async fn execute_ddl(&self, sql: &str, reason: &str) -> Result<(), PostgresError> {
+        let destructive = is_destructive_ddl(sql);
+        if destructive && self.confirm_destructive_ddl {
+            // the confirmer can wait on the terminal so it must not block the runtime
+            let (owned_reason, owned_sql) = (reason.to_string(), sql.to_string());
+            let confirmed = tokio::task::spawn_blocking(move || {
+                confirm_destructive_ddl(&owned_reason, &owned_sql)
+            })
+            .await
+            .unwrap_or(false);
+            if !confirmed {
+                return Err(PostgresError::DestructiveDdlNotConfirmed(reason.to_string()));
+            }
+        }
+
+        self.execute_confirmed_ddl(sql, reason).await
+    }
+
+    /// Same as `execute_ddl` for callers which already confirmed with the user
+    pub async fn execute_confirmed_ddl(
+        &self,
+        sql: &str,
+        reason: &str,
+    ) -> Result<(), PostgresError> {
+        self.batch_execute_without_timeout(sql).await?;
+
+        // the statement already ran so a failed audit write should not fail the caller
+        let audit = async {
+            let conn = self.connection().await?;
+            conn.execute(
+                &insert_ddl_audit_sql(),
+                &[&self.indexer_name, &reason, &sql.trim(), &is_destructive_ddl(sql)],
+            )
+            .await?;
+            Ok::<(), PostgresError>(())
+        };
+        if let Err(e) = audit.await {
+            error!("Could not record DDL to {} in the audit table: {}", reason, e);
+        }
+
+        Ok(())
+    }
+
+    /// Runs the statements in a single transaction so they either all apply or none do
+    pub async fn batch_execute_in_transaction(&self, sql: &str) -> Result<(), PostgresError> {
+        let transaction = self.transaction().await?;
+        transaction.batch_execute(sql).await?;
+        transaction.commit().await
+    }
+
+    /// Runs every statement with its params in a single transaction so they either all apply or
+    /// none do, returns the rows affected
+    pub async fn execute_in_transaction(
+        &self,
+        statements: &[(&str, &[&(dyn ToSql + Sync)])],
+    ) -> Result<u64, PostgresError> {
+        let transaction = self.transaction().await?;
+        let mut affected = 0;
+        for (statement, params) in statements {
+            affected += transaction.execute(*statement, params).await?;
+        }
+        transaction.commit().await?;
+        Ok(affected)
+    }
+
+    /// Runs the statement once for every row of params in a single transaction, returns the rows
+    /// affected
+    pub async fn execute_for_rows(
+        &self,
+        statement: &str,
+        rows: &[Vec<EthereumSqlTypeWrapper>],
+    ) -> Result<u64, PostgresError> {
+        let transaction = self.transaction().await?;
+        let affected = transaction.execute_for_rows(statement, rows).await?;
+        transaction.commit().await?;
+        Ok(affected)
+    }
+
     pub async fn execute<T>(
         &self,
         query: &T,
@@ -154,7 +686,7 @@ impl PostgresClient {
     where
         T: ?Sized + ToStatement,
     {
-        let conn = self.pool.get().await?;
+        let conn = self.connection().await?;
         conn.execute(query, params).await.map_err(PostgresError::PgError)
     }
 
@@ -163,18 +695,22 @@ impl PostgresClient {
         query: &str,
         parameter_types: &[PgType],
     ) -> Result<Statement, PostgresError> {
-        let conn = self.pool.get().await?;
+        let conn = self.connection().await?;
         conn.prepare_typed(query, parameter_types).await.map_err(PostgresError::PgError)
     }
 
+    /// Begins a transaction on a connection checked out of the pool for as long as it lives
     pub async fn transaction(&self) -> Result<PostgresTransaction, PostgresError> {
-        let mut conn = self.pool.get().await?;
-        let transaction = conn.transaction().await.map_err(PostgresError::PgError)?;
+        PostgresTransaction::begin(self.owned_connection().await?).await
+    }
 
-        // Wrap the transaction in a static lifetime
-        let boxed_transaction: Box<PgTransaction<'static>> =
-            unsafe { std::mem::transmute(Box::new(transaction)) };
-        Ok(PostgresTransaction { transaction: *boxed_transaction })
+    /// A transaction the `statement_timeout` does not apply to, for COPYs of whole files
+    pub async fn transaction_without_timeout(&self) -> Result<PostgresTransaction, PostgresError> {
+        let transaction = self.transaction().await?;
+        if self.statement_timeout.is_some() {
+            transaction.batch_execute("SET LOCAL statement_timeout = 0").await?;
+        }
+        Ok(transaction)
     }
 
     pub async fn query<T>(
@@ -185,7 +721,7 @@ impl PostgresClient {
     where
         T: ?Sized + ToStatement,
     {
-        let conn = self.pool.get().await?;
+        let conn = self.connection().await?;
         let rows = conn.query(query, params).await.map_err(PostgresError::PgError)?;
         Ok(rows)
     }
@@ -198,7 +734,7 @@ impl PostgresClient {
     where
         T: ?Sized + ToStatement,
     {
-        let conn = self.pool.get().await?;
+        let conn = self.connection().await?;
         let row = conn.query_one(query, params).await.map_err(PostgresError::PgError)?;
         Ok(row)
     }
@@ -211,11 +747,23 @@ impl PostgresClient {
     where
         T: ?Sized + ToStatement,
     {
-        let conn = self.pool.get().await?;
+        let conn = self.connection().await?;
         let row = conn.query_opt(query, params).await.map_err(PostgresError::PgError)?;
         Ok(row)
     }
 
+    /// Runs a write with the manifest `write_retry` settings, the write has to be safe to run
+    /// again after a transient failure
+    pub async fn with_write_retry<R, E, F, Fut>(&self, table_name: &str, write: F) -> Result<R, E>
+    where
+        E: WriteError,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<R, E>>,
+    {
+        self.write_retry.run(table_name, write).await
+    }
+
+    /// Runs every insert in one transaction, retried as a whole on transient failures
     pub async fn batch_insert<T>(
         &self,
         query: &T,
@@ -224,30 +772,37 @@ impl PostgresClient {
     where
         T: ?Sized + ToStatement,
     {
-        let mut conn = self.pool.get().await?;
-        let transaction = conn.transaction().await.map_err(PostgresError::PgError)?;
+        self.write_retry
+            .run("batch insert", || async {
+                let transaction = self.transaction().await?;
 
-        for params in params_list {
-            let params_refs: Vec<&(dyn ToSql + Sync)> =
-                params.iter().map(|param| param.as_ref() as &(dyn ToSql + Sync)).collect();
-            transaction.execute(query, &params_refs).await.map_err(PostgresError::PgError)?;
-        }
+                for params in &params_list {
+                    let params_refs: Vec<&(dyn ToSql + Sync)> =
+                        params.iter().map(|param| param.as_ref() as &(dyn ToSql + Sync)).collect();
+                    transaction.execute(query, &params_refs).await?;
+                }
 
-        transaction.commit().await.map_err(PostgresError::PgError)?;
-        Ok(())
+                transaction.commit().await
+            })
+            .await
     }
 
-    pub async fn copy_in<T, U>(&self, statement: &T) -> Result<CopyInSink<U>, PostgresError>
-    where
-        T: ?Sized + ToStatement,
-        U: Buf + 'static + Send,
-    {
-        let conn = self.pool.get().await?;
-
-        conn.copy_in(statement).await.map_err(PostgresError::PgError)
+    /// COPYs the rows in, retried on transient failures as a failed COPY writes nothing
+    pub async fn bulk_insert_via_copy(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        column_types: &[PgType],
+        data: &[Vec<EthereumSqlTypeWrapper>],
+    ) -> Result<(), BulkInsertPostgresError> {
+        self.write_retry
+            .run(table_name, || {
+                self.bulk_insert_via_copy_once(table_name, column_names, column_types, data)
+            })
+            .await
     }
 
-    pub async fn bulk_insert_via_copy(
+    async fn bulk_insert_via_copy_once(
         &self,
         table_name: &str,
         column_names: &[String],
@@ -269,7 +824,10 @@ impl PostgresClient {
 
         //debug!("Prepared data: {:?}", prepared_data);
 
-        let sink = self.copy_in(&stmt).await?;
+        let mut conn = self.connection().await?;
+        let transaction = conn.transaction().await?;
+        self.lift_statement_timeout(&transaction).await?;
+        let sink = transaction.copy_in(&stmt).await?;
 
         let writer = BinaryCopyInWriter::new(sink, column_types);
         pin_mut!(writer);
@@ -279,40 +837,385 @@ impl PostgresClient {
         }
 
         writer.finish().await?;
+        transaction.commit().await?;
 
         Ok(())
     }
 
-    pub async fn bulk_insert<'a>(
+    pub async fn bulk_insert(
         &self,
         table_name: &str,
         column_names: &[String],
-        bulk_data: &'a [Vec<EthereumSqlTypeWrapper>],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
     ) -> Result<u64, PostgresError> {
-        let total_columns = column_names.len();
+        self.write_retry
+            .run(table_name, || self.bulk_insert_values(table_name, column_names, bulk_data, false))
+            .await
+    }
+
+    async fn bulk_insert_values(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
+        skip_conflicts: bool,
+    ) -> Result<u64, PostgresError> {
+        let (query, params) =
+            bulk_insert_values_query(table_name, column_names, bulk_data, skip_conflicts);
+        self.execute(&query, &params).await
+    }
+
+    /// Inserts the batch with multi VALUES and then runs the statements, all in one transaction
+    /// so anything derived from the rows can never drift from them. Statement parameters are
+    /// bound as TEXT.
+    /// Inserts the rows with the statements in one transaction, `row_statements` line up with
+    /// `bulk_data` and only run for rows which were inserted so a row skipped as a conflict does
+    /// not apply its projections twice, `statements` always run
+    pub async fn insert_bulk_with_statements(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
+        skip_conflicts: bool,
+        row_statements: &[Vec<TextStatement>],
+        statements: &[TextStatement],
+    ) -> Result<(), PostgresError> {
+        self.write_retry
+            .run(table_name, || {
+                self.insert_bulk_with_statements_once(
+                    table_name,
+                    column_names,
+                    bulk_data,
+                    skip_conflicts,
+                    row_statements,
+                    statements,
+                )
+            })
+            .await
+    }
+
+    async fn insert_bulk_with_statements_once(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
+        skip_conflicts: bool,
+        row_statements: &[Vec<TextStatement>],
+        statements: &[TextStatement],
+    ) -> Result<(), PostgresError> {
+        let mut conn = self.connection().await?;
+        let transaction = conn.transaction().await.map_err(PostgresError::PgError)?;
+        let mut prepared: HashMap<String, Statement> = HashMap::new();
+
+        let mut inserted = 0;
+        if skip_conflicts && row_statements.iter().any(|statements| !statements.is_empty()) {
+            // rows go in one at a time so only the statements of rows which were inserted run
+            for (index, row) in bulk_data.iter().enumerate() {
+                let (query, params) = bulk_insert_values_query(
+                    table_name,
+                    column_names,
+                    std::slice::from_ref(row),
+                    true,
+                );
+                let row_inserted =
+                    transaction.execute(&query, &params).await.map_err(PostgresError::PgError)?;
+                inserted += row_inserted;
+                if row_inserted > 0 {
+                    if let Some(statements) = row_statements.get(index) {
+                        execute_text_statements(&transaction, &mut prepared, statements).await?;
+                    }
+                }
+            }
+        } else {
+            let rows_per_insert = (MAX_QUERY_PARAMETERS / column_names.len().max(1)).max(1);
+            for rows in bulk_data.chunks(rows_per_insert) {
+                let (query, params) =
+                    bulk_insert_values_query(table_name, column_names, rows, skip_conflicts);
+                inserted +=
+                    transaction.execute(&query, &params).await.map_err(PostgresError::PgError)?;
+            }
+            for statements in row_statements {
+                execute_text_statements(&transaction, &mut prepared, statements).await?;
+            }
+        }
+        execute_text_statements(&transaction, &mut prepared, statements).await?;
+
+        let start = Instant::now();
+        transaction.commit().await.map_err(PostgresError::PgError)?;
+        postgres_write_metrics().observe_commit(start.elapsed());
+
+        InsertPath::Values.record(bulk_data.len());
+        postgres_write_metrics().observe_batch(bulk_data.len());
+        if skip_conflicts {
+            postgres_write_metrics().observe_conflicts(bulk_data.len(), inserted);
+        }
+        Ok(())
+    }
 
-        let mut query = format!(
-            "INSERT INTO {} ({}) VALUES ",
+    async fn table_column_types(
+        &self,
+        table_name: &str,
+    ) -> Result<HashMap<String, String>, PostgresError> {
+        if let Some(column_types) = self.column_types.lock().await.get(table_name) {
+            return Ok(column_types.clone());
+        }
+
+        let rows = self
+            .query(
+                "SELECT a.attname::TEXT AS column_name, format_type(a.atttypid, a.atttypmod) AS data_type \
+                FROM pg_attribute a \
+                WHERE a.attrelid = $1::TEXT::regclass AND a.attnum > 0 AND NOT a.attisdropped",
+                &[&table_name],
+            )
+            .await?;
+        let column_types: HashMap<String, String> = rows
+            .iter()
+            .map(|row| (row.get::<_, String>("column_name"), row.get::<_, String>("data_type")))
+            .collect();
+
+        self.column_types.lock().await.insert(table_name.to_string(), column_types.clone());
+
+        Ok(column_types)
+    }
+
+    pub async fn bulk_insert_via_unnest(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        column_db_types: &[String],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
+        skip_conflicts: bool,
+    ) -> Result<u64, PostgresError> {
+        let unnest_params = column_db_types
+            .iter()
+            .enumerate()
+            .map(|(i, db_type)| format!("${}::{}[]", i + 1, db_type))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = format!(
+            "INSERT INTO {} ({}) SELECT * FROM UNNEST({}){}",
             table_name,
             generate_event_table_columns_names_sql(column_names),
+            unnest_params,
+            if skip_conflicts { ON_CONFLICT_DO_NOTHING } else { "" }
         );
-        let mut params: Vec<&'a (dyn ToSql + Sync + 'a)> = Vec::new();
 
-        for (i, row) in bulk_data.iter().enumerate() {
-            if i > 0 {
-                query.push(',');
-            }
-            let mut placeholders = vec![];
-            for j in 0..total_columns {
-                placeholders.push(format!("${}", i * total_columns + j + 1));
+        let columns: Vec<Vec<&EthereumSqlTypeWrapper>> = (0..column_names.len())
+            .map(|i| bulk_data.iter().filter_map(|row| row.get(i)).collect())
+            .collect();
+        let params: Vec<&(dyn ToSql + Sync)> =
+            columns.iter().map(|column| column as &(dyn ToSql + Sync)).collect();
+
+        self.execute(&query, &params).await
+    }
+
+    /// COPY can not skip conflicting rows so it is copied into a temp table first and then
+    /// inserted from there, returns how many rows were inserted
+    pub async fn bulk_insert_via_staged_copy(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        column_types: &[PgType],
+        data: &[Vec<EthereumSqlTypeWrapper>],
+    ) -> Result<u64, BulkInsertPostgresError> {
+        let columns_sql = generate_event_table_columns_names_sql(column_names);
+        let mut conn = self.connection().await?;
+        let transaction = conn.transaction().await?;
+        self.lift_statement_timeout(&transaction).await?;
+
+        transaction
+            .batch_execute(&format!(
+                "CREATE TEMP TABLE rindexer_staged_insert (LIKE {} INCLUDING DEFAULTS) ON COMMIT DROP;",
+                table_name
+            ))
+            .await?;
+
+        let sink = transaction
+            .copy_in(&format!(
+                "COPY rindexer_staged_insert ({}) FROM STDIN WITH (FORMAT binary)",
+                columns_sql
+            ))
+            .await?;
+        let writer = BinaryCopyInWriter::new(sink, column_types);
+        pin_mut!(writer);
+        for row in data.iter() {
+            let row: Vec<&(dyn ToSql + Sync)> =
+                row.iter().map(|param| param as &(dyn ToSql + Sync)).collect();
+            writer.as_mut().write(&row).await?;
+        }
+        writer.finish().await?;
+
+        let inserted = transaction
+            .execute(
+                &format!(
+                    "INSERT INTO {} ({}) SELECT {} FROM rindexer_staged_insert{}",
+                    table_name, columns_sql, columns_sql, ON_CONFLICT_DO_NOTHING
+                ),
+                &[],
+            )
+            .await?;
+        let start = Instant::now();
+        transaction.commit().await?;
+        postgres_write_metrics().observe_commit(start.elapsed());
+
+        Ok(inserted)
+    }
+
+    /// Inserts the batch picking COPY, UNNEST or multi VALUES based on the strategy and the
+    /// batch size, `skip_conflicts` drops rows which already exist for tables with a natural
+    /// primary key. Transient failures are retried with the manifest `write_retry` settings.
+    pub async fn insert_bulk(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
+        strategy: InsertStrategy,
+        skip_conflicts: bool,
+    ) -> Result<(), BulkInsertPostgresError> {
+        self.write_retry
+            .run(table_name, || {
+                self.insert_bulk_once(table_name, column_names, bulk_data, strategy, skip_conflicts)
+            })
+            .await
+    }
+
+    async fn insert_bulk_once(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
+        strategy: InsertStrategy,
+        skip_conflicts: bool,
+    ) -> Result<(), BulkInsertPostgresError> {
+        let rows = bulk_data.len();
+        if rows == 0 {
+            return Ok(());
+        }
+
+        let mut path = select_insert_path(strategy, rows, column_names.len(), true);
+        let mut column_db_types = vec![];
+        if path == InsertPath::Unnest {
+            let column_types = self.table_column_types(table_name).await?;
+            column_db_types = column_names
+                .iter()
+                .filter_map(|name| column_types.get(name).cloned())
+                .collect::<Vec<_>>();
+            let supports_unnest = column_db_types.len() == column_names.len() &&
+                !column_db_types.iter().any(|db_type| db_type.ends_with("[]"));
+            if !supports_unnest {
+                path = select_insert_path(strategy, rows, column_names.len(), false);
             }
-            query.push_str(&format!("({})", placeholders.join(",")));
+        }
+
+        debug!("Inserting {} rows into {} via {:?}", rows, table_name, path);
 
-            for param in row {
-                params.push(param as &'a (dyn ToSql + Sync + 'a));
+        let inserted = match path {
+            InsertPath::Copy => {
+                let column_types: Vec<PgType> =
+                    bulk_data[0].iter().map(|param| param.to_type()).collect();
+                if skip_conflicts {
+                    self.bulk_insert_via_staged_copy(
+                        table_name,
+                        column_names,
+                        &column_types,
+                        bulk_data,
+                    )
+                    .await?
+                } else {
+                    self.bulk_insert_via_copy_once(
+                        table_name,
+                        column_names,
+                        &column_types,
+                        bulk_data,
+                    )
+                    .await?;
+                    rows as u64
+                }
             }
+            InsertPath::Unnest => {
+                self.bulk_insert_via_unnest(
+                    table_name,
+                    column_names,
+                    &column_db_types,
+                    bulk_data,
+                    skip_conflicts,
+                )
+                .await?
+            }
+            InsertPath::Values => {
+                self.bulk_insert_values(table_name, column_names, bulk_data, skip_conflicts).await?
+            }
+        };
+
+        path.record(rows);
+        postgres_write_metrics().observe_batch(rows);
+        if skip_conflicts {
+            postgres_write_metrics().observe_conflicts(rows, inserted);
         }
 
-        self.execute(&query, &params).await
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database in DATABASE_URL"]
+    async fn dropping_a_transaction_rolls_it_back() {
+        let client = PostgresClient::new().await.expect("DATABASE_URL has to point to postgres");
+        let lock = "SELECT pg_try_advisory_xact_lock(hashtext('rindexer_dropped_transaction'))";
+
+        let transaction = client.transaction().await.unwrap();
+        let locked: bool = transaction.query(lock, &[]).await.unwrap()[0].get(0);
+        assert!(locked);
+        drop(transaction);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // the lock is only released for another session once the dropped transaction was rolled
+        // back
+        let other = PostgresClient::new().await.unwrap();
+        let transaction = other.transaction().await.unwrap();
+        let locked: bool = transaction.query(lock, &[]).await.unwrap()[0].get(0);
+        assert!(locked);
+        transaction.commit().await.unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a postgres database in DATABASE_URL"]
+    fn dropping_a_transaction_outside_a_runtime_rolls_it_back() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let client =
+            runtime.block_on(PostgresClient::new()).expect("DATABASE_URL has to point to postgres");
+        let lock =
+            "SELECT pg_try_advisory_xact_lock(hashtext('rindexer_dropped_sync_transaction'))";
+
+        let transaction = runtime.block_on(client.transaction()).unwrap();
+        let locked: bool = runtime.block_on(transaction.query(lock, &[])).unwrap()[0].get(0);
+        assert!(locked);
+        std::thread::spawn(move || drop(transaction)).join().unwrap();
+
+        let other = runtime.block_on(PostgresClient::new()).unwrap();
+        runtime.block_on(async {
+            let transaction = other.transaction().await.unwrap();
+            let locked: bool = transaction.query(lock, &[]).await.unwrap()[0].get(0);
+            assert!(locked);
+            transaction.commit().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn generates_session_settings_sql() {
+        assert_eq!(session_settings_sql(&PostgresSessionSettings::default()), None);
+        assert_eq!(
+            session_settings_sql(&PostgresSessionSettings {
+                application_name: Some("rindexer_o'brien".to_string()),
+                statement_timeout: Some("30s".to_string()),
+                search_path: Some("my_indexer_projections, public".to_string()),
+            })
+            .unwrap(),
+            "SET application_name = 'rindexer_o''brien'; SET statement_timeout = '30s'; SET search_path = \"my_indexer_projections\", \"public\";"
+        );
     }
 }
@@ -0,0 +1,267 @@
+use std::{path::Path, time::Duration};
+
+use ethers::types::{Address, Chain, ValueOrArray};
+use futures::{stream, StreamExt};
+use reqwest::Client;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        internal_schema::internal_table,
+    },
+    helpers::{format_address, public_read_env_value},
+    manifest::{core::Manifest, storage::Storage},
+};
+
+/// Explorer lookups are one request per address so large address lists stop being looked up
+/// after this many
+const MAX_EXPLORER_LOOKUPS: usize = 50;
+
+/// Explorers rate limit per key so only this many lookups run at once
+const EXPLORER_LOOKUP_CONCURRENCY: usize = 4;
+
+const EXPLORER_LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(thiserror::Error, Debug)]
+pub enum ContractMetadataError {
+    #[error("Could not read ABI {0}: {1}")]
+    CouldNotReadAbi(String, std::io::Error),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+}
+
+pub fn contracts_table_name() -> String {
+    internal_table("contracts")
+}
+
+pub fn generate_contracts_table_sql() -> String {
+    format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {table} (
+            indexer_name TEXT NOT NULL,
+            contract_name TEXT NOT NULL,
+            network TEXT NOT NULL,
+            address CHAR(42),
+            abi_hash CHAR(64) NOT NULL,
+            start_block NUMERIC,
+            end_block NUMERIC,
+            explorer_name TEXT,
+            compiler_version TEXT,
+            recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+        CREATE INDEX IF NOT EXISTS contracts_indexer_name_idx ON {table} (indexer_name);
+    "#,
+        table = contracts_table_name()
+    )
+}
+
+/// Name and compiler version of a contract verified on the chain explorer
+struct VerifiedSource {
+    name: String,
+    compiler_version: String,
+}
+
+async fn fetch_verified_source(
+    client: &Client,
+    chain_id: u64,
+    address: Address,
+    api_key: &str,
+) -> Option<VerifiedSource> {
+    let (api_url, _) = Chain::try_from(chain_id).ok()?.etherscan_urls()?;
+    let response = client
+        .get(api_url)
+        .query(&[
+            ("module", "contract"),
+            ("action", "getsourcecode"),
+            ("address", &format_address(&address)),
+            ("apikey", api_key),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    let response: Value = match response {
+        Ok(response) => response.json().await.ok()?,
+        Err(e) => {
+            debug!("Could not look up {} on the explorer: {}", format_address(&address), e);
+            return None;
+        }
+    };
+
+    // unverified contracts come back with an empty name
+    let source = response.get("result")?.get(0)?;
+    let name = source.get("ContractName")?.as_str().filter(|name| !name.is_empty())?;
+
+    Some(VerifiedSource {
+        name: name.to_string(),
+        compiler_version: source.get("CompilerVersion")?.as_str()?.to_string(),
+    })
+}
+
+fn sql_text(value: Option<&str>) -> String {
+    value.map_or_else(|| "NULL".to_string(), |value| format!("'{}'", value.replace('\'', "''")))
+}
+
+/// An address to look up on the explorer of its network
+struct ExplorerLookup {
+    network: String,
+    chain_id: u64,
+    address: Address,
+}
+
+/// Fills in the explorer name and compiler version of the recorded rows, it runs in the
+/// background as explorers can be slow and nothing waits on it
+async fn record_verified_sources(
+    storage: Storage,
+    indexer_name: String,
+    api_key: String,
+    lookups: Vec<ExplorerLookup>,
+) {
+    let http = match Client::builder().timeout(EXPLORER_LOOKUP_TIMEOUT).build() {
+        Ok(http) => http,
+        Err(e) => {
+            warn!("Could not create the explorer client: {}", e);
+            return;
+        }
+    };
+    let sources: Vec<(ExplorerLookup, VerifiedSource)> = stream::iter(lookups)
+        .map(|lookup| {
+            let http = &http;
+            let api_key = &api_key;
+            async move {
+                fetch_verified_source(http, lookup.chain_id, lookup.address, api_key)
+                    .await
+                    .map(|source| (lookup, source))
+            }
+        })
+        .buffer_unordered(EXPLORER_LOOKUP_CONCURRENCY)
+        .filter_map(|source| async move { source })
+        .collect()
+        .await;
+    if sources.is_empty() {
+        return;
+    }
+
+    let client = match PostgresClient::from_storage(&storage, &indexer_name).await {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Could not record the explorer verified sources: {}", e);
+            return;
+        }
+    };
+    let sql = format!(
+        "UPDATE {} SET explorer_name = $1, compiler_version = $2 \
+        WHERE indexer_name = $3 AND network = $4 AND address = $5",
+        contracts_table_name()
+    );
+    for (lookup, source) in sources {
+        let address = format_address(&lookup.address);
+        if let Err(e) = client
+            .execute(
+                &sql,
+                &[&source.name, &source.compiler_version, &indexer_name, &lookup.network, &address],
+            )
+            .await
+        {
+            warn!("Could not record the explorer verified source of {}: {}", address, e);
+        }
+    }
+}
+
+/// Rewrites the rows for the indexer with every contract address it indexes and the ABI it
+/// decodes them with, the explorer verified source is filled in the background when a
+/// `etherscan_api_key` is set
+pub async fn record_contract_metadata(
+    client: &PostgresClient,
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<(), ContractMetadataError> {
+    let api_key = manifest
+        .global
+        .as_ref()
+        .and_then(|global| global.etherscan_api_key.as_ref())
+        .map(|key| public_read_env_value(key).unwrap_or_else(|_| key.to_string()));
+    let mut lookups = vec![];
+
+    let mut sql = format!(
+        "DELETE FROM {} WHERE indexer_name = {};",
+        contracts_table_name(),
+        sql_text(Some(&manifest.name))
+    );
+    for contract in &manifest.contracts {
+        let abi = contract
+            .read_abi(project_path)
+            .map_err(|e| ContractMetadataError::CouldNotReadAbi(contract.abi.clone(), e))?;
+        let abi_hash = hex::encode(Sha256::digest(&abi));
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+
+        for details in &contract.details {
+            let chain_id = manifest
+                .networks
+                .iter()
+                .find(|network| network.name == details.network)
+                .map(|network| network.chain_id);
+            // filter contracts index any address so they get a single row without one
+            let addresses: Vec<Option<Address>> = match details.address() {
+                Some(ValueOrArray::Value(address)) => vec![Some(*address)],
+                Some(ValueOrArray::Array(addresses)) => {
+                    addresses.iter().copied().map(Some).collect()
+                }
+                None => vec![None],
+            };
+
+            for address in addresses {
+                if let (Some(address), Some(chain_id), Some(_)) = (address, chain_id, &api_key) {
+                    if lookups.len() < MAX_EXPLORER_LOOKUPS {
+                        lookups.push(ExplorerLookup {
+                            network: details.network.clone(),
+                            chain_id,
+                            address,
+                        });
+                    }
+                }
+
+                sql.push_str(&format!(
+                    "INSERT INTO {} (indexer_name, contract_name, network, address, abi_hash, start_block, end_block) VALUES ({}, {}, {}, {}, '{}', {}, {});",
+                    contracts_table_name(),
+                    sql_text(Some(&manifest.name)),
+                    sql_text(Some(&contract_name)),
+                    sql_text(Some(&details.network)),
+                    sql_text(address.as_ref().map(format_address).as_deref()),
+                    abi_hash,
+                    details.start_block.map_or_else(|| "NULL".to_string(), |block| block.to_string()),
+                    details.end_block.map_or_else(|| "NULL".to_string(), |block| block.to_string()),
+                ));
+            }
+        }
+    }
+
+    client.batch_execute_in_transaction(&sql).await?;
+    info!("Recorded the indexed contracts of {} to {}", manifest.name, contracts_table_name());
+
+    if let (Some(api_key), false) = (api_key, lookups.is_empty()) {
+        tokio::spawn(record_verified_sources(
+            manifest.storage.clone(),
+            manifest.name.clone(),
+            api_key,
+            lookups,
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sql_text() {
+        assert_eq!(sql_text(None), "NULL");
+        assert_eq!(sql_text(Some("Vault")), "'Vault'");
+        assert_eq!(sql_text(Some("o'hare")), "'o''hare'");
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::OnceLock;
+
+use crate::database::postgres::internal_schema::{internal_schema, internal_table};
+
+pub fn ddl_audit_table_name() -> String {
+    internal_table("ddl_audit")
+}
+
+pub fn create_ddl_audit_table_sql() -> String {
+    format!(
+        r#"
+        CREATE SCHEMA IF NOT EXISTS {};
+        CREATE TABLE IF NOT EXISTS {} (
+            id BIGSERIAL PRIMARY KEY,
+            indexer_name TEXT,
+            reason TEXT NOT NULL,
+            statement TEXT NOT NULL,
+            destructive BOOLEAN NOT NULL,
+            executed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    "#,
+        internal_schema(),
+        ddl_audit_table_name()
+    )
+}
+
+/// Records the statement unless the same one was already recorded for the indexer, most DDL
+/// runs on every start and finds everything in place. Destructive statements are always recorded
+pub fn insert_ddl_audit_sql() -> String {
+    format!(
+        "INSERT INTO {table} (indexer_name, reason, statement, destructive) \
+         SELECT $1, $2, $3, $4 \
+         WHERE $4 OR NOT EXISTS ( \
+            SELECT 1 FROM {table} WHERE indexer_name IS NOT DISTINCT FROM $1 AND statement = $3 \
+         )",
+        table = ddl_audit_table_name()
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// A quoted identifier, never a keyword
+    Identifier,
+    Symbol(char),
+}
+
+/// Splits the SQL into statements of keyword and symbol tokens, string literals and comments are
+/// dropped so their text can not look like a statement. Dollar quoted bodies are tokenized as
+/// the statements they hold
+fn tokenize_statements(sql: &str) -> Vec<Vec<Token>> {
+    let mut statements = vec![];
+    let mut tokens = vec![];
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                while let Some(c) = chars.next() {
+                    if c == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            '"' => {
+                while let Some(c) = chars.next() {
+                    if c == '"' {
+                        if chars.peek() == Some(&'"') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                tokens.push(Token::Identifier);
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            ';' => statements.push(std::mem::take(&mut tokens)),
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = c.to_uppercase().to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '$') {
+                        break;
+                    }
+                    word.extend(c.to_uppercase());
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+            c if c.is_whitespace() => {}
+            c => tokens.push(Token::Symbol(c)),
+        }
+    }
+    statements.push(tokens);
+
+    statements
+}
+
+fn is_word(token: Option<&Token>, word: &str) -> bool {
+    matches!(token, Some(Token::Word(w)) if w == word)
+}
+
+/// An `ALTER TABLE` action starting at the `DROP` which removes a column, `COLUMN` is optional
+fn drops_column(tokens: &[Token]) -> bool {
+    if is_word(tokens.get(1), "COLUMN") {
+        return true;
+    }
+    !["CONSTRAINT", "DEFAULT", "NOT", "EXPRESSION", "IDENTITY"]
+        .iter()
+        .any(|keyword| is_word(tokens.get(1), keyword))
+}
+
+/// Statements which lose data, constraints, indexes and views are dropped and recreated on every
+/// start so they do not count. Any other `DROP ... CASCADE` counts as it takes whatever depends on
+/// the object with it
+pub fn is_destructive_ddl(sql: &str) -> bool {
+    tokenize_statements(sql).iter().any(|tokens| {
+        let mut in_alter_table = false;
+        tokens.iter().enumerate().any(|(i, token)| {
+            let next = tokens.get(i + 1);
+            match token {
+                Token::Word(word) if word == "TRUNCATE" => true,
+                Token::Word(word) if word == "ALTER" && is_word(next, "TABLE") => {
+                    in_alter_table = true;
+                    false
+                }
+                Token::Word(word) if word == "DROP" => {
+                    is_word(next, "TABLE") ||
+                        is_word(next, "SCHEMA") ||
+                        if in_alter_table {
+                            drops_column(&tokens[i..])
+                        } else {
+                            tokens[i..].iter().any(|token| is_word(Some(token), "CASCADE"))
+                        }
+                }
+                _ => false,
+            }
+        })
+    })
+}
+
+pub type DestructiveDdlConfirmer = Box<dyn Fn(&str, &str) -> bool + Send + Sync>;
+
+fn destructive_ddl_confirmer() -> &'static OnceLock<DestructiveDdlConfirmer> {
+    static CONFIRMER: OnceLock<DestructiveDdlConfirmer> = OnceLock::new();
+    &CONFIRMER
+}
+
+/// Sets how destructive DDL is confirmed when `confirm_destructive_ddl` is on, it is called with
+/// the reason and the statement. The CLI asks on the terminal, without one it is refused
+pub fn set_destructive_ddl_confirmer(confirmer: DestructiveDdlConfirmer) {
+    if destructive_ddl_confirmer().set(confirmer).is_err() {
+        tracing::warn!("A destructive DDL confirmer is already set, keeping the first one");
+    }
+}
+
+pub fn confirm_destructive_ddl(reason: &str, sql: &str) -> bool {
+    destructive_ddl_confirmer().get().is_some_and(|confirmer| confirmer(reason, sql))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_destructive_ddl() {
+        assert!(is_destructive_ddl("DROP SCHEMA IF EXISTS indexer_erc20 CASCADE;"));
+        assert!(is_destructive_ddl("drop schema indexer_erc20;"));
+        assert!(is_destructive_ddl("DROP TYPE IF EXISTS indexer_erc20.status CASCADE;"));
+        assert!(!is_destructive_ddl("DROP TYPE IF EXISTS indexer_erc20.status;"));
+        assert!(!is_destructive_ddl("ALTER TABLE t DROP CONSTRAINT t_fkey CASCADE;"));
+        assert!(is_destructive_ddl("ALTER TABLE t\n    DROP   COLUMN \"value\";"));
+        assert!(is_destructive_ddl("ALTER TABLE t ADD COLUMN a INT, DROP \"value\";"));
+        assert!(is_destructive_ddl("truncate rindexer_internal.x;"));
+        assert!(!is_destructive_ddl("DROP INDEX CONCURRENTLY IF EXISTS idx_transfer_from;"));
+        assert!(!is_destructive_ddl("CREATE TABLE IF NOT EXISTS t (a INT);"));
+        assert!(!is_destructive_ddl("ALTER TABLE t ALTER COLUMN a DROP NOT NULL;"));
+        assert!(!is_destructive_ddl("ALTER TABLE t DROP CONSTRAINT t_pkey;"));
+        assert!(!is_destructive_ddl(
+            "COMMENT ON TABLE t IS 'DROP TABLE t; truncate t'; -- DROP SCHEMA s\nCREATE TABLE \"drop table\" (a INT);"
+        ));
+        assert!(is_destructive_ddl(
+            "DO $$ BEGIN IF EXISTS (SELECT 1) THEN DROP TABLE t; END IF; END $$;"
+        ));
+    }
+}
@@ -0,0 +1,58 @@
+use ethers::types::U64;
+
+use crate::{
+    database::postgres::client::{PostgresClient, PostgresError},
+    types::code::Code,
+};
+
+/// The priority fee percentiles asked for in `eth_feeHistory`
+pub const GAS_PRICE_REWARD_PERCENTILES: [f64; 3] = [25.0, 50.0, 75.0];
+
+pub const GAS_PRICE_COLUMNS: [&str; 7] = [
+    "network",
+    "block_number",
+    "base_fee_per_gas",
+    "gas_used_ratio",
+    "priority_fee_p25",
+    "priority_fee_p50",
+    "priority_fee_p75",
+];
+
+/// The table is shared across indexers as the gas context of a block does not depend on them, the
+/// basefee is NULL on chains without EIP-1559
+pub fn generate_gas_price_table_sql(table_name: &str) -> Code {
+    let schema_name = table_name.split('.').next().unwrap_or(table_name);
+
+    Code::new(format!(
+        "CREATE SCHEMA IF NOT EXISTS {schema_name};
+        CREATE TABLE IF NOT EXISTS {table_name} (
+            network VARCHAR(50) NOT NULL,
+            block_number NUMERIC NOT NULL,
+            base_fee_per_gas NUMERIC,
+            gas_used_ratio NUMERIC NOT NULL,
+            priority_fee_p25 NUMERIC,
+            priority_fee_p50 NUMERIC,
+            priority_fee_p75 NUMERIC,
+            PRIMARY KEY (network, block_number)
+        );
+        ALTER TABLE {table_name} ALTER COLUMN base_fee_per_gas DROP NOT NULL;"
+    ))
+}
+
+pub async fn gas_price_last_sampled(
+    client: &PostgresClient,
+    table_name: &str,
+    network: &str,
+) -> Result<Option<U64>, PostgresError> {
+    let row = client
+        .query_one_or_none(
+            &format!("SELECT MAX(block_number)::TEXT FROM {} WHERE network = $1", table_name),
+            &[&network],
+        )
+        .await?;
+
+    Ok(row
+        .and_then(|row| row.get::<_, Option<String>>(0))
+        .and_then(|block| block.parse::<u64>().ok())
+        .map(U64::from))
+}
@@ -1,12 +1,31 @@
-use std::path::Path;
+use std::{path::Path, slice};
 
 use tracing::{error, info};
 
 use crate::{
     abi::{ABIInput, ABIItem, EventInfo, GenerateAbiPropertiesType, ParamTypeError, ReadAbiError},
+    database::postgres::{
+        contract_metadata::generate_contracts_table_sql,
+        heartbeat::generate_heartbeats_table_sql,
+        internal_schema::{internal_schema, internal_table},
+        projection::generate_projection_schema_name,
+        topic_preimages::{generate_topic_preimages_table_sql, topic_preimages_table_name},
+        user_migrations::generate_applied_migrations_table_sql,
+        wallet_activity::generate_wallet_activity_schema_name,
+    },
+    handler_cache::handler_cache_table_name,
     helpers::camel_to_snake,
     indexer::Indexer,
-    manifest::contract::Contract,
+    manifest::{
+        computed_column::{computed_columns_for_event, ComputedColumn},
+        contract::Contract,
+        price_enrichment::{price_enrichments_for_event, EventPriceEnrichment},
+        solidity_enum::{enums_for_event, EventInputEnum},
+        storage::{PostgresLayout, Storage},
+        table_settings::{table_settings_for_event, TableStorageSettings},
+        template::template_columns_for_event,
+        transform::{transforms_for_event, EventInputTransform, ValueTransformType},
+    },
     types::code::Code,
 };
 
@@ -17,17 +36,121 @@ fn generate_columns(inputs: &[ABIInput], property_type: &GenerateAbiPropertiesTy
         .collect()
 }
 
-pub fn generate_columns_with_data_types(inputs: &[ABIInput]) -> Vec<String> {
-    generate_columns(inputs, &GenerateAbiPropertiesType::PostgresWithDataTypes)
+/// Column name and db type for each input, any transformed inputs take the transform type and
+/// any JSONB inputs are kept as a single column
+pub fn generate_column_definitions(
+    inputs: &[ABIInput],
+    transforms: &[&EventInputTransform],
+    jsonb_inputs: &[String],
+    compact_bytea: bool,
+) -> Vec<(String, String)> {
+    inputs
+        .iter()
+        .flat_map(|input| {
+            if jsonb_inputs.contains(&input.name) {
+                return vec![(camel_to_snake(&input.name), "JSONB".to_string())];
+            }
+
+            if let Some(transform) = transforms.iter().find(|t| t.input_name == input.name) {
+                return vec![(
+                    camel_to_snake(&input.name),
+                    transform.db_type(&input.type_).to_string(),
+                )];
+            }
+
+            ABIInput::generate_abi_name_properties(
+                slice::from_ref(input),
+                &GenerateAbiPropertiesType::PostgresColumnsNamesOnly,
+                None,
+            )
+            .into_iter()
+            .map(|property| {
+                let db_type = if compact_bytea && property.abi_type == "address" {
+                    "BYTEA".to_string()
+                } else {
+                    solidity_type_to_db_type(&property.abi_type)
+                };
+                (property.value, db_type)
+            })
+            .collect()
+        })
+        .collect()
+}
+
+/// Prices are stored as NUMERIC so precision of the oracle is kept
+pub fn generate_price_enrichment_column_definitions(
+    price_enrichments: &[&EventPriceEnrichment],
+) -> Vec<(String, String)> {
+    price_enrichments
+        .iter()
+        .map(|enrichment| (enrichment.column_name.clone(), "NUMERIC".to_string()))
+        .collect()
+}
+
+pub fn generate_template_column_definitions(template_columns: &[&str]) -> Vec<(String, String)> {
+    template_columns.iter().map(|column| (column.to_string(), "NUMERIC".to_string())).collect()
 }
 
-fn generate_columns_names_only(inputs: &[ABIInput]) -> Vec<String> {
-    generate_columns(inputs, &GenerateAbiPropertiesType::PostgresColumnsNamesOnly)
+/// Computed columns are generated by postgres so are never part of the insert columns
+pub fn generate_computed_column_definitions(
+    computed_columns: &[&ComputedColumn],
+) -> Vec<(String, String)> {
+    computed_columns
+        .iter()
+        .map(|column| {
+            (
+                column.name.clone(),
+                format!("{} GENERATED ALWAYS AS ({}) STORED", column.type_, column.expression),
+            )
+        })
+        .collect()
 }
 
-pub fn generate_column_names_only_with_base_properties(inputs: &[ABIInput]) -> Vec<String> {
+/// Topics and data of the log as emitted for contracts which store raw logs
+pub fn generate_raw_log_column_definitions(contract: &Contract) -> Vec<(String, String)> {
+    if !contract.stores_raw_logs() {
+        return vec![];
+    }
+
+    vec![
+        ("raw_topics".to_string(), "BYTEA[]".to_string()),
+        ("raw_data".to_string(), "BYTEA".to_string()),
+    ]
+}
+
+/// Enum label columns are generated by postgres from the raw uint8 column
+pub fn generate_enum_column_definitions(enums: &[&EventInputEnum]) -> Vec<(String, String)> {
+    enums
+        .iter()
+        .map(|input_enum| {
+            (
+                input_enum.label_column_name(),
+                format!("TEXT GENERATED ALWAYS AS ({}) STORED", input_enum.label_expression()),
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn generate_columns_names_only(
+    inputs: &[ABIInput],
+    jsonb_inputs: &[String],
+) -> Vec<String> {
+    if jsonb_inputs.is_empty() {
+        return generate_columns(inputs, &GenerateAbiPropertiesType::PostgresColumnsNamesOnly);
+    }
+
+    generate_column_definitions(inputs, &[], jsonb_inputs, false)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect()
+}
+
+pub fn generate_column_names_only_with_base_properties(
+    inputs: &[ABIInput],
+    jsonb_inputs: &[String],
+) -> Vec<String> {
     let mut column_names: Vec<String> = vec!["contract_address".to_string()];
-    column_names.extend(generate_columns_names_only(inputs));
+    column_names.extend(generate_columns_names_only(inputs, jsonb_inputs));
     column_names.extend(vec![
         "tx_hash".to_string(),
         "block_number".to_string(),
@@ -39,54 +162,487 @@ pub fn generate_column_names_only_with_base_properties(inputs: &[ABIInput]) -> V
     column_names
 }
 
+/// Each table the event is written to with the smart comment name to expose it as on graphql
+fn event_tables_with_graphql_names(
+    event_info: &EventInfo,
+    contract: &Contract,
+    indexer_name: &str,
+    apply_full_name_comment_for_events: &[String],
+    layout: PostgresLayout,
+) -> Vec<(String, Option<String>)> {
+    let contract_name = contract.before_modify_name_if_filter_readonly();
+    match layout {
+        PostgresLayout::Shared => vec![(
+            generate_event_table_full_name(indexer_name, &contract_name, &event_info.name),
+            apply_full_name_comment_for_events
+                .contains(&event_info.name)
+                .then(|| format!("{}{}", contract.name, event_info.name)),
+        )],
+        // every network has the same table names so they always need naming apart
+        PostgresLayout::SchemaPerNetwork => contract_networks(contract)
+            .into_iter()
+            .map(|network| {
+                (
+                    generate_network_event_table_full_name(
+                        network,
+                        &contract_name,
+                        &event_info.name,
+                    ),
+                    Some(format!(
+                        "{}_{}{}",
+                        generate_network_schema_name(network),
+                        contract.name,
+                        event_info.name
+                    )),
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Escaped for an `E'...'` string literal
+fn comment_literal(comment: &str) -> String {
+    format!("E'{}'", comment.replace('\\', "\\\\").replace('\'', "''").replace('\n', "\\n"))
+}
+
+fn transform_description(transform: &EventInputTransform) -> &'static str {
+    match transform.transform {
+        ValueTransformType::ScaleDecimals => "scaled down by its decimals",
+        ValueTransformType::Lowercase => "lowercased",
+        ValueTransformType::Hash => "stored as its hash",
+        ValueTransformType::Redact => "redacted",
+    }
+}
+
+/// Describes every column the ABI inputs of the event are written to, with its solidity type and
+/// whether it is indexed
+fn event_input_column_comments(
+    contract: &Contract,
+    event_info: &EventInfo,
+) -> Vec<(String, String)> {
+    let transforms = transforms_for_event(&contract.transforms, &event_info.name);
+    let jsonb_inputs = contract.jsonb_inputs(&event_info.name, &event_info.inputs);
+
+    event_info
+        .inputs
+        .iter()
+        .flat_map(|input| {
+            let indexed = if input.indexed.unwrap_or_default() { " indexed" } else { "" };
+            let column = camel_to_snake(&input.name);
+
+            if jsonb_inputs.contains(&input.name) {
+                return vec![(column, format!("{}{} {} as JSON", input.type_, indexed, input.name))];
+            }
+            if let Some(transform) = transforms.iter().find(|t| t.input_name == input.name) {
+                return vec![(
+                    column,
+                    format!(
+                        "{}{} {} {}",
+                        input.type_,
+                        indexed,
+                        input.name,
+                        transform_description(transform)
+                    ),
+                )];
+            }
+
+            ABIInput::generate_abi_name_properties(
+                slice::from_ref(input),
+                &GenerateAbiPropertiesType::PostgresColumnsNamesOnly,
+                None,
+            )
+            .into_iter()
+            .map(|property| {
+                let comment = if property.abi_name == input.name {
+                    format!("{}{} {}", property.abi_type, indexed, input.name)
+                } else {
+                    format!(
+                        "{} {} of {}{} {}",
+                        property.abi_type, property.abi_name, input.type_, indexed, input.name
+                    )
+                };
+                (property.value, comment)
+            })
+            .collect()
+        })
+        .collect()
+}
+
+/// What the event table holds, with the readable signature and topic0 of the event
+pub(crate) fn event_table_description(contract: &Contract, event_info: &EventInfo) -> String {
+    let readable_signature = format!(
+        "{}({})",
+        event_info.name,
+        event_info
+            .inputs
+            .iter()
+            .map(|input| {
+                let indexed = if input.indexed.unwrap_or_default() { " indexed" } else { "" };
+                format!("{}{} {}", input.type_, indexed, input.name)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    format!(
+        "{} {} events - {} - topic0 0x{}",
+        contract.name,
+        event_info.name,
+        readable_signature,
+        event_info.topic_id_as_hex_string()
+    )
+}
+
+/// Describes the base columns and the columns the ABI inputs of the event are written to
+pub(crate) fn event_column_descriptions(
+    contract: &Contract,
+    event_info: &EventInfo,
+) -> Vec<(String, String)> {
+    let base_columns = [
+        ("contract_address", "Address of the contract which emitted the event"),
+        ("tx_hash", "Hash of the transaction the event was emitted in"),
+        ("block_number", "Block the event was emitted in"),
+        ("block_hash", "Hash of the block the event was emitted in"),
+        ("network", "Network the event was emitted on"),
+        ("tx_index", "Position of the transaction in its block"),
+        ("log_index", "Position of the log in its block"),
+    ]
+    .into_iter()
+    .map(|(column, comment)| (column.to_string(), comment.to_string()));
+    let raw_log_columns =
+        generate_raw_log_column_definitions(contract).into_iter().map(|(column, _)| {
+            let comment = if column == "raw_topics" {
+                "Topics of the log as emitted, decoded again by rindexer redecode"
+            } else {
+                "Data of the log as emitted, decoded again by rindexer redecode"
+            };
+            (column, comment.to_string())
+        });
+
+    base_columns
+        .chain(event_input_column_comments(contract, event_info))
+        .chain(raw_log_columns)
+        .collect()
+}
+
+/// Documents the table and its columns from the ABI so the schema explains itself in any SQL
+/// tool, a GraphQL smart comment has to stay on the first line of the table comment
+fn generate_event_comments_sql(
+    table_name: &str,
+    graphql_name: Option<&str>,
+    contract: &Contract,
+    event_info: &EventInfo,
+) -> String {
+    let description = event_table_description(contract, event_info);
+    let table_comment = match graphql_name {
+        Some(graphql_name) => format!("@name {}\n{}", graphql_name, description),
+        None => description,
+    };
+
+    let mut sql =
+        format!("COMMENT ON TABLE {} IS {};", table_name, comment_literal(&table_comment));
+    for (column, comment) in event_column_descriptions(contract, event_info) {
+        sql.push_str(&format!(
+            "\nCOMMENT ON COLUMN {}.\"{}\" IS {};",
+            table_name,
+            column,
+            comment_literal(&comment)
+        ));
+    }
+    sql
+}
+
+/// The event specific columns of an event table, the base columns every table has are left out
+fn event_table_column_definitions(
+    contract: &Contract,
+    event_info: &EventInfo,
+    compact_bytea: bool,
+) -> Vec<(String, String)> {
+    let mut column_definitions = generate_column_definitions(
+        &event_info.inputs,
+        &transforms_for_event(&contract.transforms, &event_info.name),
+        &contract.jsonb_inputs(&event_info.name, &event_info.inputs),
+        compact_bytea,
+    );
+    column_definitions.extend(generate_price_enrichment_column_definitions(
+        &price_enrichments_for_event(&contract.price_enrichment, &event_info.name),
+    ));
+    column_definitions.extend(generate_template_column_definitions(template_columns_for_event(
+        &contract.template,
+        &event_info.name,
+    )));
+    column_definitions.extend(generate_computed_column_definitions(&computed_columns_for_event(
+        &contract.computed_columns,
+        &event_info.name,
+    )));
+    column_definitions.extend(generate_enum_column_definitions(&enums_for_event(
+        &contract.enums,
+        &event_info.name,
+    )));
+    column_definitions.extend(generate_raw_log_column_definitions(contract));
+    column_definitions
+}
+
+/// Every column of an event table
+pub fn event_table_column_names(
+    contract: &Contract,
+    event_info: &EventInfo,
+    storage: &Storage,
+) -> Vec<String> {
+    let mut column_names: Vec<String> =
+        event_table_column_definitions(contract, event_info, storage.postgres_compact_bytea())
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+    column_names.extend(
+        [
+            "contract_address",
+            "tx_hash",
+            "block_number",
+            "block_hash",
+            "network",
+            "tx_index",
+            "log_index",
+        ]
+        .map(String::from),
+    );
+    if !storage.postgres_natural_primary_key() {
+        column_names.push("rindexer_id".to_string());
+    }
+    column_names
+}
+
 fn generate_event_table_sql_with_comments(
     abi_inputs: &[EventInfo],
-    contract_name: &str,
-    schema_name: &str,
+    contract: &Contract,
+    indexer_name: &str,
     apply_full_name_comment_for_events: Vec<String>,
+    storage: &Storage,
 ) -> String {
+    let default_indexes = storage.postgres_default_indexes();
+    let unlogged = storage.postgres_unlogged_historic_sync();
+    let natural_primary_key = storage.postgres_natural_primary_key();
+    let compact_bytea = storage.postgres_compact_bytea();
+    let hash_db_type = if compact_bytea { "BYTEA" } else { "CHAR(66)" };
+
     abi_inputs
         .iter()
-        .map(|event_info| {
-            let table_name = format!("{}.{}", schema_name, camel_to_snake(&event_info.name));
-            info!("Creating table if not exists: {}", table_name);
-            let event_columns = if event_info.inputs.is_empty() {
+        .flat_map(|event_info| {
+            let column_definitions =
+                event_table_column_definitions(contract, event_info, compact_bytea);
+            let table_settings =
+                table_settings_for_event(&contract.table_settings, &event_info.name);
+            let hex_view_columns = if compact_bytea {
+                generate_hex_view_columns(&column_definitions, natural_primary_key)
+            } else {
+                vec![]
+            };
+            let event_columns = if column_definitions.is_empty() {
                 "".to_string()
             } else {
-                generate_columns_with_data_types(&event_info.inputs).join(", ") + ","
+                column_definitions
+                    .iter()
+                    .map(|(name, db_type)| format!("\"{}\" {}", name, db_type))
+                    .collect::<Vec<_>>()
+                    .join(", ") +
+                    ","
             };
 
-            let create_table_sql = format!(
-                "CREATE TABLE IF NOT EXISTS {} (\
-                rindexer_id SERIAL PRIMARY KEY NOT NULL, \
-                contract_address CHAR(66) NOT NULL, \
-                {} \
-                tx_hash CHAR(66) NOT NULL, \
-                block_number NUMERIC NOT NULL, \
-                block_hash CHAR(66) NOT NULL, \
-                network VARCHAR(50) NOT NULL, \
-                tx_index NUMERIC NOT NULL, \
-                log_index VARCHAR(78) NOT NULL\
-            );",
-                table_name, event_columns
-            );
+            event_tables_with_graphql_names(
+                event_info,
+                contract,
+                indexer_name,
+                &apply_full_name_comment_for_events,
+                storage.postgres_layout(),
+            )
+            .into_iter()
+            .map(move |(table_name, graphql_name)| {
+                info!("Creating table if not exists: {}", table_name);
+                let create_table_sql = format!(
+                    "CREATE {}TABLE IF NOT EXISTS {} (\
+                    {}\
+                    contract_address {hash_db_type} NOT NULL, \
+                    {} \
+                    tx_hash {hash_db_type} NOT NULL, \
+                    block_number NUMERIC NOT NULL, \
+                    block_hash {hash_db_type} NOT NULL, \
+                    network VARCHAR(50) NOT NULL, \
+                    tx_index NUMERIC NOT NULL, \
+                    log_index VARCHAR(78) NOT NULL\
+                    {}\
+                );",
+                    if unlogged { "UNLOGGED " } else { "" },
+                    table_name,
+                    if natural_primary_key {
+                        ""
+                    } else {
+                        "rindexer_id SERIAL PRIMARY KEY NOT NULL, "
+                    },
+                    event_columns,
+                    if natural_primary_key {
+                        ", PRIMARY KEY (network, tx_hash, log_index)"
+                    } else {
+                        ""
+                    },
+                    hash_db_type = hash_db_type,
+                );
 
-            if !apply_full_name_comment_for_events.contains(&event_info.name) {
-                return create_table_sql;
-            }
+                let create_table_sql =
+                    match generate_hashed_topic_rename_sql(&table_name, &event_info.inputs) {
+                        Some(rename_sql) => format!("{}\n{}", create_table_sql, rename_sql),
+                        None => create_table_sql,
+                    };
 
-            // smart comments needed to avoid clashing of order by graphql names
-            let table_comment = format!(
-                "COMMENT ON TABLE {} IS E'@name {}{}';",
-                table_name, contract_name, event_info.name
-            );
+                let create_table_sql = if hex_view_columns.is_empty() {
+                    create_table_sql
+                } else {
+                    format!(
+                        "{}\n{}",
+                        create_table_sql,
+                        generate_hex_view_sql(&table_name, &hex_view_columns)
+                    )
+                };
+
+                // unlogged tables get their default indexes once historic sync is complete
+                let create_table_sql = if default_indexes && !unlogged {
+                    format!("{}\n{}", create_table_sql, generate_default_indexes_sql(&table_name))
+                } else {
+                    create_table_sql
+                };
+
+                let create_table_sql = match table_settings {
+                    Some(settings) => format!(
+                        "{}\n{}",
+                        create_table_sql,
+                        generate_table_storage_settings_sql(&table_name, settings)
+                    ),
+                    None => create_table_sql,
+                };
 
-            format!("{}\n{}", create_table_sql, table_comment)
+                // smart comments needed to avoid clashing of order by graphql names
+                let comments_sql = generate_event_comments_sql(
+                    &table_name,
+                    graphql_name.as_deref(),
+                    contract,
+                    event_info,
+                );
+
+                format!("{}\n{}", create_table_sql, comments_sql)
+            })
+            .collect::<Vec<_>>()
         })
         .collect::<Vec<_>>()
         .join("\n")
 }
 
+fn generate_hex_view_columns(
+    column_definitions: &[(String, String)],
+    natural_primary_key: bool,
+) -> Vec<(String, String)> {
+    let mut columns = vec![];
+    if !natural_primary_key {
+        columns.push(("rindexer_id".to_string(), "SERIAL".to_string()));
+    }
+    columns.push(("contract_address".to_string(), "BYTEA".to_string()));
+    columns.extend(column_definitions.iter().cloned());
+    columns.extend(
+        [
+            ("tx_hash", "BYTEA"),
+            ("block_number", "NUMERIC"),
+            ("block_hash", "BYTEA"),
+            ("network", "VARCHAR(50)"),
+            ("tx_index", "NUMERIC"),
+            ("log_index", "VARCHAR(78)"),
+        ]
+        .iter()
+        .map(|(name, db_type)| (name.to_string(), db_type.to_string())),
+    );
+    columns
+}
+
+/// Compact tables keep addresses and hashes as raw bytes, the `_hex` view exposes every BYTEA
+/// column as 0x prefixed hex so they can still be read and compared by eye
+pub fn generate_hex_view_sql(table_name: &str, columns: &[(String, String)]) -> String {
+    let select_columns = columns
+        .iter()
+        .map(|(name, db_type)| {
+            if db_type == "BYTEA" {
+                format!("'0x' || encode(\"{name}\", 'hex') AS \"{name}\"", name = name)
+            } else {
+                format!("\"{}\"", name)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "CREATE OR REPLACE VIEW {table_name}_hex AS SELECT {} FROM {table_name};",
+        select_columns,
+        table_name = table_name
+    )
+}
+
+/// Tables created before indexed strings, bytes and arrays were stored as `{name}_hash` columns
+/// have the hash under the input name, those columns are renamed and converted to BYTEA on the
+/// next start so existing data is kept, the compact `_hex` view is created again after it
+fn generate_hashed_topic_rename_sql(table_name: &str, inputs: &[ABIInput]) -> Option<String> {
+    let (schema, table) = table_name.split_once('.')?;
+    let renames = inputs
+        .iter()
+        .filter(|input| input.is_hashed_topic() && !input.type_.starts_with("tuple"))
+        .map(|input| {
+            let column = camel_to_snake(&input.name);
+            let hashed_column = camel_to_snake(&input.hashed_topic_name());
+            format!(
+                "IF EXISTS (SELECT 1 FROM information_schema.columns WHERE table_schema = '{schema}' AND table_name = '{table}' AND column_name = '{column}') AND \
+                NOT EXISTS (SELECT 1 FROM information_schema.columns WHERE table_schema = '{schema}' AND table_name = '{table}' AND column_name = '{hashed_column}') THEN \
+                DROP VIEW IF EXISTS {table_name}_hex; \
+                ALTER TABLE {table_name} RENAME COLUMN \"{column}\" TO \"{hashed_column}\"; \
+                ALTER TABLE {table_name} ALTER COLUMN \"{hashed_column}\" TYPE BYTEA USING \
+                CASE WHEN \"{hashed_column}\"::TEXT ~ '^0x[0-9a-fA-F]{{64}}$' THEN decode(substring(\"{hashed_column}\"::TEXT from 3), 'hex') \
+                WHEN \"{hashed_column}\"::TEXT ~ '^\\\\x[0-9a-f]{{64}}$' THEN \"{hashed_column}\"::TEXT::BYTEA END; \
+                END IF;",
+                schema = schema,
+                table = table,
+                table_name = table_name,
+                column = column,
+                hashed_column = hashed_column,
+            )
+        })
+        .collect::<Vec<_>>();
+    if renames.is_empty() {
+        return None;
+    }
+
+    Some(format!("DO $$ BEGIN {} END $$;", renames.join(" ")))
+}
+
+/// Applied with ALTER on every start so existing tables pick up changed settings, a new
+/// fillfactor only applies to pages written from then on. The `cluster_on` index is built
+/// through the managed indexes by `apply_cluster_indexes`
+pub fn generate_table_storage_settings_sql(
+    table_name: &str,
+    settings: &TableStorageSettings,
+) -> String {
+    let parameters = settings.storage_parameters();
+    if parameters.is_empty() {
+        return String::new();
+    }
+
+    format!("ALTER TABLE {} SET ({});", table_name, parameters.join(", "))
+}
+
+/// Rows are inserted in roughly block order so a BRIN index keeps block range queries fast while
+/// staying tiny
+pub fn generate_default_indexes_sql(table_name: &str) -> String {
+    let index_prefix = format!("idx_{}", table_name.rsplit('.').next().unwrap_or(table_name));
+    format!(
+        "CREATE INDEX IF NOT EXISTS {index_prefix}_block_number_brin ON {table_name} USING BRIN (block_number);\n\
+        CREATE INDEX IF NOT EXISTS {index_prefix}_contract_address ON {table_name} (contract_address);",
+        index_prefix = index_prefix,
+        table_name = table_name,
+    )
+}
+
 fn generate_internal_event_table_sql(
     abi_inputs: &[EventInfo],
     schema_name: &str,
@@ -94,7 +650,8 @@ fn generate_internal_event_table_sql(
 ) -> String {
     abi_inputs.iter().map(|event_info| {
         let table_name = format!(
-            "rindexer_internal.{}_{}",
+            "{}.{}_{}",
+            internal_schema(),
             schema_name,
             camel_to_snake(&event_info.name)
         );
@@ -116,6 +673,70 @@ fn generate_internal_event_table_sql(
     }).collect::<Vec<_>>().join("\n")
 }
 
+fn event_signatures_table_name() -> String {
+    internal_table("event_signatures")
+}
+
+pub(crate) fn create_event_signatures_table_sql() -> String {
+    format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {table} (
+            indexer_name TEXT NOT NULL,
+            topic0 CHAR(66) NOT NULL,
+            contract_name TEXT NOT NULL,
+            event_name TEXT NOT NULL,
+            event_signature TEXT NOT NULL,
+            network TEXT NOT NULL,
+            table_name TEXT NOT NULL,
+            PRIMARY KEY (indexer_name, contract_name, event_name, network)
+        );
+        CREATE INDEX IF NOT EXISTS event_signatures_topic0_idx ON {table} (topic0);
+    "#,
+        table = event_signatures_table_name()
+    )
+}
+
+/// Maps every indexed topic0 to the table it is written to, the rows for the indexer are
+/// rewritten on each setup so it always matches the manifest
+fn generate_event_signatures_sql(
+    indexer_name: &str,
+    contracts: &[(&Contract, String, Vec<EventInfo>)],
+    layout: PostgresLayout,
+) -> String {
+    let mut sql = format!(
+        "{}DELETE FROM {} WHERE indexer_name = '{}';",
+        create_event_signatures_table_sql(),
+        event_signatures_table_name(),
+        indexer_name.replace('\'', "''")
+    );
+
+    for (contract, contract_name, event_names) in contracts {
+        for event_info in event_names {
+            for network in contract_networks(contract) {
+                sql.push_str(&format!(
+                    "INSERT INTO {} (indexer_name, topic0, contract_name, event_name, event_signature, network, table_name) VALUES ('{}', '0x{}', '{}', '{}', '{}', '{}', '{}');",
+                    event_signatures_table_name(),
+                    indexer_name.replace('\'', "''"),
+                    event_info.topic_id_as_hex_string(),
+                    contract_name.replace('\'', "''"),
+                    event_info.name,
+                    event_info.event_signature(),
+                    network.replace('\'', "''"),
+                    generate_event_table_full_name_for_network(
+                        layout,
+                        indexer_name,
+                        contract_name,
+                        &event_info.name,
+                        network
+                    )
+                ));
+            }
+        }
+    }
+
+    sql
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum GenerateTablesForIndexerSqlError {
     #[error("{0}")]
@@ -159,16 +780,26 @@ fn find_clashing_event_names(
 pub fn generate_tables_for_indexer_sql(
     project_path: &Path,
     indexer: &Indexer,
+    storage: &Storage,
 ) -> Result<Code, GenerateTablesForIndexerSqlError> {
-    let mut sql = "CREATE SCHEMA IF NOT EXISTS rindexer_internal;".to_string();
+    let mut sql = format!("CREATE SCHEMA IF NOT EXISTS {};", internal_schema());
+    let mut event_signatures = vec![];
 
     for contract in &indexer.contracts {
         let contract_name = contract.before_modify_name_if_filter_readonly();
         let abi_items = ABIItem::read_abi_items(project_path, contract)?;
         let event_names = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
         let schema_name = generate_indexer_contract_schema_name(&indexer.name, &contract_name);
-        sql.push_str(format!("CREATE SCHEMA IF NOT EXISTS {};", schema_name).as_str());
-        info!("Creating schema if not exists: {}", schema_name);
+        let event_schema_names = match storage.postgres_layout() {
+            PostgresLayout::Shared => vec![schema_name.clone()],
+            PostgresLayout::SchemaPerNetwork => {
+                contract_networks(contract).into_iter().map(generate_network_schema_name).collect()
+            }
+        };
+        for event_schema_name in event_schema_names {
+            sql.push_str(format!("CREATE SCHEMA IF NOT EXISTS {};", event_schema_name).as_str());
+            info!("Creating schema if not exists: {}", event_schema_name);
+        }
 
         let networks: Vec<&str> = contract.details.iter().map(|d| d.network.as_str()).collect();
 
@@ -177,30 +808,62 @@ pub fn generate_tables_for_indexer_sql(
 
         sql.push_str(&generate_event_table_sql_with_comments(
             &event_names,
-            &contract.name,
-            &schema_name,
+            contract,
+            &indexer.name,
             event_matching_name_on_other,
+            storage,
         ));
         sql.push_str(&generate_internal_event_table_sql(&event_names, &schema_name, networks));
+        event_signatures.push((contract, contract_name.to_string(), event_names));
     }
 
+    sql.push_str(&generate_event_signatures_sql(
+        &indexer.name,
+        &event_signatures,
+        storage.postgres_layout(),
+    ));
+
     sql.push_str(&format!(
         r#"
-        CREATE TABLE IF NOT EXISTS rindexer_internal.{indexer_name}_last_known_relationship_dropping_sql (
+        CREATE TABLE IF NOT EXISTS {schema}.{indexer_name}_last_known_relationship_dropping_sql (
             key INT PRIMARY KEY,
             value TEXT NOT NULL
         );
     "#,
+        schema = internal_schema(),
         indexer_name = camel_to_snake(&indexer.name)
     ));
 
+    sql.push_str(&generate_applied_migrations_table_sql(&indexer.name));
+    sql.push_str(&generate_contracts_table_sql());
+
+    if storage.postgres_hashed_topic_preimages() {
+        sql.push_str(generate_topic_preimages_table_sql(&indexer.name).as_str());
+    }
+
+    if storage.postgres_heartbeats() {
+        sql.push_str(generate_heartbeats_table_sql(&indexer.name).as_str());
+    }
+
     sql.push_str(&format!(
         r#"
-        CREATE TABLE IF NOT EXISTS rindexer_internal.{indexer_name}_last_known_indexes_dropping_sql (
+        CREATE TABLE IF NOT EXISTS {schema}.{indexer_name}_last_known_relationship_views_dropping_sql (
             key INT PRIMARY KEY,
             value TEXT NOT NULL
         );
     "#,
+        schema = internal_schema(),
+        indexer_name = camel_to_snake(&indexer.name)
+    ));
+
+    sql.push_str(&format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {schema}.{indexer_name}_last_known_indexes_dropping_sql (
+            key INT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+    "#,
+        schema = internal_schema(),
         indexer_name = camel_to_snake(&indexer.name)
     ));
 
@@ -216,6 +879,73 @@ pub fn generate_event_table_full_name(
     format!("{}.{}", schema_name, camel_to_snake(event_name))
 }
 
+pub fn generate_network_schema_name(network: &str) -> String {
+    camel_to_snake(network).replace('-', "_")
+}
+
+pub fn generate_network_event_table_full_name(
+    network: &str,
+    contract_name: &str,
+    event_name: &str,
+) -> String {
+    format!(
+        "{}.{}_{}",
+        generate_network_schema_name(network),
+        camel_to_snake(contract_name),
+        camel_to_snake(event_name)
+    )
+}
+
+/// The table an event on a network is written to for the layout
+pub fn generate_event_table_full_name_for_network(
+    layout: PostgresLayout,
+    indexer_name: &str,
+    contract_name: &str,
+    event_name: &str,
+    network: &str,
+) -> String {
+    match layout {
+        PostgresLayout::Shared => {
+            generate_event_table_full_name(indexer_name, contract_name, event_name)
+        }
+        PostgresLayout::SchemaPerNetwork => {
+            generate_network_event_table_full_name(network, contract_name, event_name)
+        }
+    }
+}
+
+/// Every table an event is written to, with the schema per network layout this is one table for
+/// each network the contract is indexed on
+pub fn generate_event_table_full_names(
+    layout: PostgresLayout,
+    indexer_name: &str,
+    contract: &Contract,
+    contract_name: &str,
+    event_name: &str,
+) -> Vec<String> {
+    match layout {
+        PostgresLayout::Shared => {
+            vec![generate_event_table_full_name(indexer_name, contract_name, event_name)]
+        }
+        PostgresLayout::SchemaPerNetwork => contract_networks(contract)
+            .into_iter()
+            .map(|network| {
+                generate_network_event_table_full_name(network, contract_name, event_name)
+            })
+            .collect(),
+    }
+}
+
+pub fn contract_networks(contract: &Contract) -> Vec<&str> {
+    let mut networks: Vec<&str> = vec![];
+    for details in &contract.details {
+        if !networks.contains(&details.network.as_str()) {
+            networks.push(&details.network);
+        }
+    }
+    networks
+}
+
 pub fn generate_event_table_columns_names_sql(column_names: &[String]) -> String {
     column_names.iter().map(|name| format!("\"{}\"", name)).collect::<Vec<String>>().join(", ")
 }
@@ -224,25 +954,109 @@ pub fn generate_indexer_contract_schema_name(indexer_name: &str, contract_name:
     format!("{}_{}", camel_to_snake(indexer_name), camel_to_snake(contract_name))
 }
 
-pub fn drop_tables_for_indexer_sql(project_path: &Path, indexer: &Indexer) -> Code {
+/// Internal tables holding the last synced block of every event of the indexer, without the schema
+pub fn indexer_checkpoint_table_names(project_path: &Path, indexer: &Indexer) -> Vec<String> {
+    indexer
+        .contracts
+        .iter()
+        .flat_map(|contract| {
+            let schema_name = generate_indexer_contract_schema_name(
+                &indexer.name,
+                &contract.before_modify_name_if_filter_readonly(),
+            );
+            ABIItem::read_abi_items(project_path, contract)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |abi_item| format!("{}_{}", schema_name, camel_to_snake(&abi_item.name)))
+        })
+        .collect()
+}
+
+pub fn drop_tables_for_indexer_sql(
+    project_path: &Path,
+    indexer: &Indexer,
+    layout: PostgresLayout,
+) -> Code {
     let mut sql = format!(
-        "DROP TABLE IF EXISTS rindexer_internal.{}_last_known_indexes_dropping_sql CASCADE;",
+        "DROP TABLE IF EXISTS {}.{}_last_known_indexes_dropping_sql CASCADE;",
+        internal_schema(),
         camel_to_snake(&indexer.name)
     );
-    sql.push_str(format!("DROP TABLE IF EXISTS rindexer_internal.{}_last_known_relationship_dropping_sql CASCADE;", camel_to_snake(&indexer.name)).as_str());
+    sql.push_str(
+        format!(
+            "DROP TABLE IF EXISTS {}.{}_last_known_relationship_dropping_sql CASCADE;",
+            internal_schema(),
+            camel_to_snake(&indexer.name)
+        )
+        .as_str(),
+    );
+    sql.push_str(
+        format!(
+            "DROP TABLE IF EXISTS {}.{}_last_known_relationship_views_dropping_sql CASCADE;",
+            internal_schema(),
+            camel_to_snake(&indexer.name)
+        )
+        .as_str(),
+    );
+    sql.push_str(
+        format!(
+            "DROP TABLE IF EXISTS {}.{}_applied_migrations CASCADE;",
+            internal_schema(),
+            camel_to_snake(&indexer.name)
+        )
+        .as_str(),
+    );
+    sql.push_str(
+        format!("DROP TABLE IF EXISTS {} CASCADE;", handler_cache_table_name(&indexer.name))
+            .as_str(),
+    );
+    sql.push_str(
+        format!("DROP TABLE IF EXISTS {} CASCADE;", topic_preimages_table_name(&indexer.name))
+            .as_str(),
+    );
+    sql.push_str(
+        format!(
+            "DROP SCHEMA IF EXISTS {} CASCADE;",
+            generate_projection_schema_name(&indexer.name)
+        )
+        .as_str(),
+    );
+    sql.push_str(
+        format!(
+            "DROP SCHEMA IF EXISTS {} CASCADE;",
+            generate_wallet_activity_schema_name(&indexer.name)
+        )
+        .as_str(),
+    );
 
     for contract in &indexer.contracts {
         let contract_name = contract.before_modify_name_if_filter_readonly();
         let schema_name = generate_indexer_contract_schema_name(&indexer.name, &contract_name);
-        sql.push_str(format!("DROP SCHEMA IF EXISTS {} CASCADE;", schema_name).as_str());
+        if layout == PostgresLayout::Shared {
+            sql.push_str(format!("DROP SCHEMA IF EXISTS {} CASCADE;", schema_name).as_str());
+        }
 
-        // drop last synced blocks for contracts
         let abi_items = ABIItem::read_abi_items(project_path, contract);
         if let Ok(abi_items) = abi_items {
             for abi_item in abi_items.iter() {
+                // network schemas are shared with other indexers so only drop our tables
+                if layout == PostgresLayout::SchemaPerNetwork {
+                    for network in contract_networks(contract) {
+                        sql.push_str(&format!(
+                            "DROP TABLE IF EXISTS {} CASCADE;",
+                            generate_network_event_table_full_name(
+                                network,
+                                &contract_name,
+                                &abi_item.name
+                            )
+                        ));
+                    }
+                }
+
+                // drop last synced blocks for contracts
                 let table_name = format!("{}_{}", schema_name, camel_to_snake(&abi_item.name));
                 sql.push_str(
-                    format!("DROP TABLE IF EXISTS rindexer_internal.{} CASCADE;", table_name)
+                    format!("DROP TABLE IF EXISTS {} CASCADE;", internal_table(&table_name))
                         .as_str(),
                 );
             }
@@ -0,0 +1,113 @@
+use ethers::types::U64;
+
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        internal_schema::internal_table,
+    },
+    helpers::camel_to_snake,
+    types::code::Code,
+};
+
+pub fn heartbeats_table_name(indexer_name: &str) -> String {
+    internal_table(&format!("{}_heartbeats", camel_to_snake(indexer_name)))
+}
+
+/// Block ranges an event was fetched for without finding any logs, so a range with no rows in
+/// the event table can be told apart from one which is not indexed yet
+pub fn generate_heartbeats_table_sql(indexer_name: &str) -> Code {
+    Code::new(format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {} (
+            contract_name TEXT NOT NULL,
+            event_name TEXT NOT NULL,
+            network TEXT NOT NULL,
+            from_block NUMERIC NOT NULL,
+            to_block NUMERIC NOT NULL,
+            recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (contract_name, event_name, network, from_block)
+        );
+    "#,
+        heartbeats_table_name(indexer_name)
+    ))
+}
+
+/// Extends the range ending right before `from_block` so consecutive empty ranges stay one row
+fn record_heartbeat_sql(indexer_name: &str) -> String {
+    format!(
+        "WITH extended AS (\
+            UPDATE {table} SET to_block = $5::NUMERIC, recorded_at = NOW() \
+            WHERE contract_name = $1 AND event_name = $2 AND network = $3 AND to_block = $4::NUMERIC - 1 \
+            RETURNING 1\
+        ) \
+        INSERT INTO {table} (contract_name, event_name, network, from_block, to_block) \
+        SELECT $1, $2, $3, $4::NUMERIC, $5::NUMERIC WHERE NOT EXISTS (SELECT 1 FROM extended) \
+        ON CONFLICT (contract_name, event_name, network, from_block) \
+        DO UPDATE SET to_block = GREATEST({table}.to_block, EXCLUDED.to_block), recorded_at = NOW()",
+        table = heartbeats_table_name(indexer_name)
+    )
+}
+
+/// Cuts the empty ranges of an event back to before the block a reorg replaced, the blocks from
+/// it on are fetched again. Both take the contract, event, network and the replaced block
+pub fn retract_heartbeats_sql(indexer_name: &str) -> [String; 2] {
+    let table = heartbeats_table_name(indexer_name);
+    let event = "contract_name = $1 AND event_name = $2 AND network = $3";
+    [
+        format!("DELETE FROM {table} WHERE {event} AND from_block >= $4::NUMERIC"),
+        format!(
+            "UPDATE {table} SET to_block = $4::NUMERIC - 1 WHERE {event} AND to_block >= $4::NUMERIC"
+        ),
+    ]
+}
+
+pub async fn record_heartbeat(
+    client: &PostgresClient,
+    indexer_name: &str,
+    contract_name: &str,
+    event_name: &str,
+    network: &str,
+    from_block: U64,
+    to_block: U64,
+) -> Result<(), PostgresError> {
+    client
+        .execute(
+            &record_heartbeat_sql(indexer_name),
+            &[
+                &contract_name,
+                &event_name,
+                &network,
+                &from_block.to_string(),
+                &to_block.to_string(),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_heartbeat_sql_extends_adjacent_range() {
+        let sql = record_heartbeat_sql("MyIndexer");
+
+        assert!(heartbeats_table_name("MyIndexer").ends_with(".my_indexer_heartbeats"));
+        assert!(
+            sql.contains(&format!("UPDATE {} SET to_block", heartbeats_table_name("MyIndexer")))
+        );
+        assert!(sql.contains("to_block = $4::NUMERIC - 1"));
+        assert!(sql.contains("WHERE NOT EXISTS (SELECT 1 FROM extended)"));
+    }
+
+    #[test]
+    fn test_retract_heartbeats_sql_cuts_ranges_at_replaced_block() {
+        let [delete, update] = retract_heartbeats_sql("MyIndexer");
+
+        assert!(delete.ends_with("AND from_block >= $4::NUMERIC"));
+        assert!(update.contains("SET to_block = $4::NUMERIC - 1"));
+        assert!(update.ends_with("AND to_block >= $4::NUMERIC"));
+    }
+}
@@ -0,0 +1,401 @@
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use ethers::types::{Address, ValueOrArray, U64};
+use futures::{pin_mut, SinkExt};
+use tracing::info;
+
+use crate::{
+    abi::{ABIItem, ParamTypeError, ReadAbiError},
+    database::postgres::{
+        client::PostgresError,
+        generate::{
+            generate_column_definitions, generate_column_names_only_with_base_properties,
+            generate_event_table_columns_names_sql, generate_event_table_full_name_for_network,
+        },
+        internal_schema::internal_schema,
+        setup::{setup_postgres, SetupPostgresError},
+    },
+    helpers::{camel_to_snake, format_address, set_address_format},
+    manifest::{
+        contract::Contract, core::Manifest, price_enrichment::price_enrichments_for_event,
+        template::template_columns_for_event, transform::transforms_for_event,
+    },
+    EthereumSqlTypeWrapper,
+};
+
+/// Rows are sent to postgres in chunks of this many rows
+const IMPORT_CHUNK_ROWS: u64 = 10_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ImportEventsError {
+    #[error("Postgres storage is not enabled - events are imported into the postgres tables")]
+    PostgresNotEnabled,
+
+    #[error("Could not setup postgres: {0}")]
+    SetupPostgresError(#[from] SetupPostgresError),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("Contract {0} not found in the manifest")]
+    ContractNotFound(String),
+
+    #[error("Contract {0} is not indexed on network {1}")]
+    NetworkNotFound(String, String),
+
+    #[error("Event {1} not found in the ABI for contract {0}")]
+    EventNotFound(String, String),
+
+    #[error("Could not read ABI items: {0}")]
+    ReadAbiError(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
+
+    #[error("Only csv files can be imported, {0} files need converting to csv first")]
+    UnsupportedFileFormat(String),
+
+    #[error("Parquet files can not be imported yet, convert {0} to csv first")]
+    ParquetNotSupported(String),
+
+    #[error("Could not read csv: {0}")]
+    CsvError(#[from] csv::Error),
+
+    #[error("Column {0} in the file does not exist on the event table")]
+    UnknownColumn(String),
+
+    #[error("Column {0} is missing from the file and can not be derived")]
+    MissingColumn(String),
+
+    #[error("Invalid block number {0} on row {1}")]
+    InvalidBlockNumber(String, u64),
+}
+
+pub struct ImportEventsOptions {
+    pub contract_name: String,
+    pub event_name: String,
+    pub network: String,
+    pub file: PathBuf,
+}
+
+pub struct ImportedEvents {
+    pub table_name: String,
+    pub rows: u64,
+    /// The highest block imported which indexing resumes after
+    pub last_synced_block: Option<U64>,
+}
+
+/// Where each table column comes from
+#[derive(Debug, Clone, PartialEq)]
+enum ImportColumnSource {
+    File(usize),
+    Value(String),
+}
+
+/// Matches the file headers (in snake or camel case) to the insert columns, `network` and a
+/// single address `contract_address` can be left out of the file as they are known
+fn import_column_mapping(
+    headers: &[String],
+    insert_columns: &[String],
+    optional_columns: &[String],
+    derived_values: &[(&str, Option<String>)],
+) -> Result<Vec<(String, ImportColumnSource)>, ImportEventsError> {
+    let header_columns: Vec<String> =
+        headers.iter().map(|header| camel_to_snake(header.trim())).collect();
+    if let Some(unknown) = header_columns
+        .iter()
+        .find(|column| !insert_columns.contains(column) && !optional_columns.contains(column))
+    {
+        return Err(ImportEventsError::UnknownColumn(unknown.clone()));
+    }
+
+    let mut mapping = vec![];
+    for column in insert_columns.iter().chain(optional_columns) {
+        if let Some(index) = header_columns.iter().position(|header| header == column) {
+            mapping.push((column.clone(), ImportColumnSource::File(index)));
+            continue;
+        }
+        if optional_columns.contains(column) {
+            continue;
+        }
+        match derived_values.iter().find(|(name, _)| name == column) {
+            Some((_, Some(value))) => {
+                mapping.push((column.clone(), ImportColumnSource::Value(value.clone())))
+            }
+            _ => return Err(ImportEventsError::MissingColumn(column.clone())),
+        }
+    }
+
+    Ok(mapping)
+}
+
+/// Writes addresses in the file how the indexer writes them so imported rows match indexed ones,
+/// address arrays come in the postgres `{a,b}` text form
+fn normalize_address_value(value: &str) -> String {
+    let normalize = |value: &str| match value.trim().parse::<Address>() {
+        Ok(address) => format_address(&address),
+        Err(_) => value.to_string(),
+    };
+
+    match value.strip_prefix('{').and_then(|values| values.strip_suffix('}')) {
+        Some(values) if !values.is_empty() => {
+            format!("{{{}}}", values.split(',').map(normalize).collect::<Vec<_>>().join(","))
+        }
+        Some(_) => value.to_string(),
+        None => normalize(value),
+    }
+}
+
+fn take_chunk(writer: &mut csv::Writer<Vec<u8>>) -> Result<Vec<u8>, csv::Error> {
+    let writer = std::mem::replace(writer, csv::Writer::from_writer(vec![]));
+    writer.into_inner().map_err(|e| csv::Error::from(e.into_error()))
+}
+
+fn find_contract<'a>(manifest: &'a Manifest, contract_name: &str) -> Option<&'a Contract> {
+    manifest.contracts.iter().find(|contract| contract.name == contract_name)
+}
+
+/// Loads previously exported events from a csv file into the event table with COPY and moves
+/// the last synced block to the highest imported block so indexing carries on after it. Parquet
+/// exports are not read, they need converting to csv first.
+pub async fn import_events(
+    project_path: &Path,
+    manifest: &Manifest,
+    options: &ImportEventsOptions,
+) -> Result<ImportedEvents, ImportEventsError> {
+    if !manifest.storage.postgres_enabled() {
+        return Err(ImportEventsError::PostgresNotEnabled);
+    }
+
+    let extension =
+        options.file.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    if extension == "parquet" {
+        return Err(ImportEventsError::ParquetNotSupported(options.file.display().to_string()));
+    }
+    if extension != "csv" {
+        return Err(ImportEventsError::UnsupportedFileFormat(extension));
+    }
+
+    // imported rows have to be written like the indexer writes them
+    set_address_format(
+        manifest.global.as_ref().and_then(|global| global.address_format).unwrap_or_default(),
+    );
+
+    let mut contract = find_contract(manifest, &options.contract_name)
+        .ok_or_else(|| ImportEventsError::ContractNotFound(options.contract_name.clone()))?
+        .clone();
+    let details =
+        contract.details.iter().find(|detail| detail.network == options.network).ok_or_else(
+            || ImportEventsError::NetworkNotFound(contract.name.clone(), options.network.clone()),
+        )?;
+    let contract_address = match details.address() {
+        Some(ValueOrArray::Value(address)) => Some(*address),
+        Some(ValueOrArray::Array(addresses)) if addresses.len() == 1 => Some(addresses[0]),
+        _ => None,
+    };
+
+    let is_filter = contract.identify_and_modify_filter();
+    let abi_items = ABIItem::get_abi_items(project_path, &contract, is_filter)?;
+    let event_info = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?
+        .into_iter()
+        .find(|event| event.name == options.event_name)
+        .ok_or_else(|| {
+            ImportEventsError::EventNotFound(contract.name.clone(), options.event_name.clone())
+        })?;
+
+    let compact_bytea = manifest.storage.postgres_compact_bytea();
+    let jsonb_inputs = contract.jsonb_inputs(&event_info.name, &event_info.inputs);
+    let insert_columns =
+        generate_column_names_only_with_base_properties(&event_info.inputs, &jsonb_inputs);
+    let optional_columns: Vec<String> =
+        price_enrichments_for_event(&contract.price_enrichment, &event_info.name)
+            .iter()
+            .map(|enrichment| enrichment.column_name.clone())
+            .chain(
+                template_columns_for_event(&contract.template, &event_info.name)
+                    .iter()
+                    .map(|column| column.to_string()),
+            )
+            .collect();
+    let column_definitions = generate_column_definitions(
+        &event_info.inputs,
+        &transforms_for_event(&contract.transforms, &event_info.name),
+        &jsonb_inputs,
+        compact_bytea,
+    );
+    let mut bytea_columns: Vec<String> = column_definitions
+        .iter()
+        .filter(|(_, db_type)| db_type == "BYTEA")
+        .map(|(name, _)| name.clone())
+        .collect();
+    let mut address_columns: Vec<String> = column_definitions
+        .iter()
+        .filter(|(_, db_type)| db_type.starts_with("CHAR(42)"))
+        .map(|(name, _)| name.clone())
+        .collect();
+    if compact_bytea {
+        bytea_columns.extend(
+            ["contract_address", "tx_hash", "block_hash"].iter().map(|column| column.to_string()),
+        );
+    } else {
+        address_columns.push("contract_address".to_string());
+    }
+
+    let mut reader = csv::Reader::from_path(&options.file)?;
+    let headers: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+    let derived_values = [
+        ("network", Some(options.network.clone())),
+        ("contract_address", contract_address.as_ref().map(format_address)),
+    ];
+    let mapping =
+        import_column_mapping(&headers, &insert_columns, &optional_columns, &derived_values)?;
+    let block_number_index = mapping.iter().position(|(column, _)| column == "block_number");
+
+    // tables have to exist to import into
+    let client = setup_postgres(project_path, manifest, false).await?;
+    let contract_name = contract.before_modify_name_if_filter_readonly().to_string();
+    let table_name = generate_event_table_full_name_for_network(
+        manifest.storage.postgres_layout(),
+        &manifest.name,
+        &contract_name,
+        &event_info.name,
+        &options.network,
+    );
+    let column_names: Vec<String> = mapping.iter().map(|(column, _)| column.clone()).collect();
+    let statement = format!(
+        "COPY {} ({}) FROM STDIN WITH (FORMAT csv)",
+        table_name,
+        generate_event_table_columns_names_sql(&column_names)
+    );
+
+    info!("Importing {} into {}", options.file.display(), table_name);
+
+    let transaction = client.transaction_without_timeout().await?;
+    let sink = transaction.copy_in::<_, Bytes>(&statement).await?;
+    pin_mut!(sink);
+
+    let mut last_synced_block: Option<U64> = None;
+    let mut row_number: u64 = 0;
+    let mut chunk_start: u64 = 0;
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for record in reader.records() {
+        let record = record?;
+        row_number += 1;
+
+        let values: Vec<String> = mapping
+            .iter()
+            .map(|(column, source)| {
+                let value = match source {
+                    ImportColumnSource::File(index) => record.get(*index).unwrap_or_default(),
+                    ImportColumnSource::Value(value) => value.as_str(),
+                };
+                match value.strip_prefix("0x") {
+                    Some(hex) if bytea_columns.contains(column) => format!("\\x{}", hex),
+                    _ if address_columns.contains(column) => normalize_address_value(value),
+                    _ => value.to_string(),
+                }
+            })
+            .collect();
+
+        if let Some(index) = block_number_index {
+            let block_number = U64::from_dec_str(values[index].trim()).map_err(|_| {
+                ImportEventsError::InvalidBlockNumber(values[index].clone(), row_number)
+            })?;
+            last_synced_block = last_synced_block.max(Some(block_number));
+        }
+
+        writer.write_record(&values)?;
+        if row_number >= chunk_start + IMPORT_CHUNK_ROWS {
+            chunk_start = row_number;
+            let chunk = take_chunk(&mut writer)?;
+            sink.send(Bytes::from(chunk)).await.map_err(PostgresError::PgError)?;
+        }
+    }
+    let chunk = take_chunk(&mut writer)?;
+    if !chunk.is_empty() {
+        sink.send(Bytes::from(chunk)).await.map_err(PostgresError::PgError)?;
+    }
+    let rows = sink.finish().await.map_err(PostgresError::PgError)?;
+    transaction.commit().await?;
+
+    if let Some(block_number) = last_synced_block {
+        client
+            .execute(
+                &format!(
+                    "UPDATE {}.{}_{}_{} SET last_synced_block = $1 WHERE network = $2 AND $1 > last_synced_block",
+                    internal_schema(),
+                    camel_to_snake(&manifest.name),
+                    camel_to_snake(&contract_name),
+                    camel_to_snake(&event_info.name)
+                ),
+                &[&EthereumSqlTypeWrapper::U64(block_number), &options.network],
+            )
+            .await?;
+    }
+
+    Ok(ImportedEvents { table_name, rows, last_synced_block })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn maps_file_headers_to_columns() {
+        let insert_columns =
+            strings(&["contract_address", "from", "value", "block_number", "network"]);
+        let derived = [("network", Some("ethereum".to_string())), ("contract_address", None)];
+
+        let mapping = import_column_mapping(
+            &strings(&["blockNumber", "value", "from", "contract_address"]),
+            &insert_columns,
+            &strings(&["usd_price"]),
+            &derived,
+        )
+        .unwrap();
+        assert_eq!(
+            mapping,
+            vec![
+                ("contract_address".to_string(), ImportColumnSource::File(3)),
+                ("from".to_string(), ImportColumnSource::File(2)),
+                ("value".to_string(), ImportColumnSource::File(1)),
+                ("block_number".to_string(), ImportColumnSource::File(0)),
+                ("network".to_string(), ImportColumnSource::Value("ethereum".to_string())),
+            ]
+        );
+
+        assert!(matches!(
+            import_column_mapping(
+                &strings(&["from", "value", "block_number"]),
+                &insert_columns,
+                &[],
+                &derived
+            ),
+            Err(ImportEventsError::MissingColumn(column)) if column == "contract_address"
+        ));
+        assert!(matches!(
+            import_column_mapping(&strings(&["memo"]), &insert_columns, &[], &derived),
+            Err(ImportEventsError::UnknownColumn(column)) if column == "memo"
+        ));
+    }
+
+    #[test]
+    fn normalizes_addresses_to_the_address_format() {
+        // addresses are lowercase unless the manifest turns on checksums
+        let lowercase = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed";
+        let checksum = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+        assert_eq!(normalize_address_value(checksum), lowercase);
+        assert_eq!(
+            normalize_address_value(&format!("{{{},{}}}", checksum, lowercase)),
+            format!("{{{},{}}}", lowercase, lowercase)
+        );
+        assert_eq!(normalize_address_value("{}"), "{}");
+        assert_eq!(normalize_address_value("not an address"), "not an address");
+    }
+}
@@ -1,13 +1,28 @@
 use std::{path::Path, sync::Arc};
 
 use futures::future::join_all;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
-    abi::{get_abi_item_with_db_map, ABIItem, GetAbiItemWithDbMapError, ReadAbiError},
-    database::postgres::client::{PostgresClient, PostgresConnectionError, PostgresError},
+    abi::{get_abi_item_or_injected_with_db_map, ABIItem, GetAbiItemWithDbMapError, ReadAbiError},
+    database::postgres::{
+        client::{PostgresClient, PostgresConnectionError, PostgresError},
+        generate::generate_event_table_full_names,
+        internal_schema::internal_schema,
+        managed_index::{
+            clustered_indexes, drop_invalid_index, generate_managed_indexes_table_sql, identifier,
+            index_validity, record_index_state, remove_index_state, run_index_build,
+            ManagedIndexState,
+        },
+        projection::projection_table_name,
+    },
     helpers::camel_to_snake,
-    manifest::{contract::Contract, storage::PostgresIndexes},
+    manifest::{
+        contract::Contract,
+        core::Manifest,
+        storage::{ApplyPolicy, PostgresIndexes, PostgresLayout, Storage},
+        table_settings::table_settings_for_event,
+    },
     types::code::Code,
 };
 
@@ -15,6 +30,8 @@ use crate::{
 pub struct PostgresIndexResult {
     db_table_name: String,
     db_table_columns: Vec<String>,
+    apply: ApplyPolicy,
+    cluster: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -27,22 +44,41 @@ pub enum ApplyPostgresIndexesError {
 }
 
 impl PostgresIndexResult {
+    /// The index `cluster_on` marks the table for `CLUSTER` on, named apart from the configured
+    /// indexes so a stale one can be told apart when the columns change
+    pub fn cluster(db_table_name: &str, columns: &[String]) -> Self {
+        PostgresIndexResult {
+            db_table_name: db_table_name.to_string(),
+            db_table_columns: columns.to_vec(),
+            apply: ApplyPolicy::Immediately,
+            cluster: true,
+        }
+    }
+
+    pub fn with_apply_policy(
+        indexes: &[PostgresIndexResult],
+        apply: ApplyPolicy,
+    ) -> Vec<PostgresIndexResult> {
+        indexes.iter().filter(|index| index.apply == apply).cloned().collect()
+    }
+
     pub fn apply_index_sql(&self) -> Code {
-        info!(
-            "Applying index after historic resync complete: table - {} constraint - {}",
-            self.db_table_name,
-            self.index_name()
-        );
+        info!("Applying index: table - {} constraint - {}", self.db_table_name, self.index_name());
 
         // CONCURRENTLY is used to avoid locking the table for writes
         Code::new(format!(
             r#"
-                CREATE INDEX CONCURRENTLY {index_name}
+                CREATE INDEX CONCURRENTLY IF NOT EXISTS {index_name}
                 ON {db_table_name} ({db_table_columns});
             "#,
             index_name = self.index_name(),
             db_table_name = self.db_table_name,
-            db_table_columns = self.db_table_columns.join(", "),
+            db_table_columns = self
+                .db_table_columns
+                .iter()
+                .map(|column| format!("\"{}\"", column))
+                .collect::<Vec<_>>()
+                .join(", "),
         ))
     }
 
@@ -67,34 +103,128 @@ impl PostgresIndexResult {
 
     pub fn index_name(&self) -> String {
         format!(
-            "idx_{db_table_name}_{db_table_columns}",
-            db_table_name = self.db_table_name.split('.').last().unwrap_or_else(|| panic!(
-                "Failed to split and then get schema for table: {}",
-                self.db_table_name
-            )),
+            "idx_{db_table_name}_{cluster}{db_table_columns}",
+            db_table_name = self.table(),
+            cluster = if self.cluster { "cluster_" } else { "" },
             db_table_columns = self.db_table_columns.join("_"),
         )
     }
 
+    fn table(&self) -> &str {
+        self.db_table_name.split('.').next_back().unwrap_or_else(|| {
+            panic!("Failed to split and then get schema for table: {}", self.db_table_name)
+        })
+    }
+
+    fn schema(&self) -> &str {
+        self.db_table_name.split('.').next().unwrap_or_default()
+    }
+
+    /// Builds the index unless a valid one exists, rebuilding it when a failed build left it
+    /// invalid. Builds on the same table are serialized across indexers with an advisory lock
+    /// and the state of every build is recorded in the managed indexes table
+    pub async fn build(
+        &self,
+        client: &PostgresClient,
+        indexer_name: &str,
+    ) -> Result<(), PostgresError> {
+        let lock = client.advisory_lock(&format!("rindexer_index:{}", self.db_table_name)).await?;
+        let result = self.build_locked(client, indexer_name).await;
+        lock.release().await?;
+        result
+    }
+
+    async fn record_state(
+        &self,
+        client: &PostgresClient,
+        indexer_name: &str,
+        state: ManagedIndexState,
+        error: Option<&str>,
+    ) -> Result<(), PostgresError> {
+        record_index_state(
+            client,
+            indexer_name,
+            &self.db_table_name,
+            &self.index_name(),
+            &self.db_table_columns.join(", "),
+            state,
+            error,
+        )
+        .await
+    }
+
+    async fn build_locked(
+        &self,
+        client: &PostgresClient,
+        indexer_name: &str,
+    ) -> Result<(), PostgresError> {
+        let index_name = self.index_name();
+        match index_validity(client, self.schema(), &index_name).await? {
+            Some(true) => {
+                return self.record_state(client, indexer_name, ManagedIndexState::Valid, None).await
+            }
+            Some(false) => drop_invalid_index(client, self.schema(), &index_name).await?,
+            None => {}
+        }
+
+        self.record_state(client, indexer_name, ManagedIndexState::Building, None).await?;
+        let sql = self.apply_index_sql();
+        if let Err(e) =
+            run_index_build(client, &self.db_table_name, &index_name, sql.as_str()).await
+        {
+            let error = e.to_string();
+            self.record_state(client, indexer_name, ManagedIndexState::Failed, Some(&error))
+                .await?;
+            return Err(e);
+        }
+
+        if index_validity(client, self.schema(), &index_name).await? == Some(true) {
+            self.record_state(client, indexer_name, ManagedIndexState::Valid, None).await
+        } else {
+            warn!(
+                "Index {} is not valid after building it, it is rebuilt on the next apply",
+                index_name
+            );
+            self.record_state(
+                client,
+                indexer_name,
+                ManagedIndexState::Failed,
+                Some("index is not valid after the build"),
+            )
+            .await
+        }
+    }
+
     pub async fn apply_indexes(
+        storage: &Storage,
+        manifest_name: &str,
         indexes: Vec<PostgresIndexResult>,
     ) -> Result<(), ApplyPostgresIndexesError> {
         if indexes.is_empty() {
             return Ok(());
         }
 
-        let client = PostgresClient::new().await?;
-
-        // do a loop due to deadlocks on concurrent execution
-        for postgres_index in indexes {
-            let sql = postgres_index.apply_index_sql();
-            client.execute(sql.as_str(), &[]).await?;
-        }
+        let client = PostgresClient::from_storage(storage, manifest_name).await?;
+        build_indexes(&client, manifest_name, &indexes).await?;
 
         Ok(())
     }
 }
 
+/// Builds the indexes one after the other as concurrent builds deadlock each other
+pub async fn build_indexes(
+    client: &PostgresClient,
+    indexer_name: &str,
+    indexes: &[PostgresIndexResult],
+) -> Result<(), PostgresError> {
+    client.batch_execute(generate_managed_indexes_table_sql(indexer_name).as_str()).await?;
+    for postgres_index in indexes {
+        postgres_index.build(client, indexer_name).await?;
+    }
+
+    Ok(())
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum GetLastKnownIndexesDroppingSqlError {
     #[error("Could not read last known indexes: {0}")]
@@ -112,8 +242,9 @@ async fn get_last_known_indexes_dropping_sql(
         .query_one_or_none(
             &format!(
                 r#"
-                    SELECT value FROM rindexer_internal.{}_last_known_indexes_dropping_sql WHERE key = 1
+                    SELECT value FROM {}.{}_last_known_indexes_dropping_sql WHERE key = 1
                 "#,
+                internal_schema(),
                 camel_to_snake(manifest_name)
             ),
             &[],
@@ -144,8 +275,11 @@ pub enum DropLastKnownIndexesError {
     CouldNotDropIndexes(PostgresError),
 }
 
-pub async fn drop_last_known_indexes(manifest_name: &str) -> Result<(), DropLastKnownIndexesError> {
-    let client = Arc::new(PostgresClient::new().await?);
+pub async fn drop_last_known_indexes(
+    storage: &Storage,
+    manifest_name: &str,
+) -> Result<(), DropLastKnownIndexesError> {
+    let client = Arc::new(PostgresClient::from_storage(storage, manifest_name).await?);
 
     // people can edit the indexes, so we have to drop old stuff
     // we save all drops in the database, so we can drop them all at once
@@ -157,7 +291,7 @@ pub async fn drop_last_known_indexes(manifest_name: &str) -> Result<(), DropLast
         let client = Arc::clone(&client);
         async move {
             client
-                .execute(sql.as_str(), &[])
+                .execute_ddl(sql.as_str(), "drop the last known indexes")
                 .await
                 .map_err(DropLastKnownIndexesError::CouldNotDropIndexes)
         }
@@ -193,14 +327,34 @@ pub enum PrepareIndexesError {
 }
 
 pub async fn prepare_indexes(
+    storage: &Storage,
     project_path: &Path,
     manifest_name: &str,
     postgres_indexes: &PostgresIndexes,
     contracts: &[Contract],
+    layout: PostgresLayout,
 ) -> Result<Vec<PostgresIndexResult>, PrepareIndexesError> {
     let mut index_results: Vec<PostgresIndexResult> = vec![];
     let mut dropping_sql: Vec<Code> = vec![];
-    let client = Arc::new(PostgresClient::new().await?);
+    let client = Arc::new(PostgresClient::from_storage(storage, manifest_name).await?);
+
+    let default_apply = postgres_indexes.apply.unwrap_or_default();
+    let mut push_index =
+        |db_table_names: &[String], db_table_columns: Vec<String>, apply: ApplyPolicy| {
+            for db_table_name in db_table_names {
+                let index_result = PostgresIndexResult {
+                    db_table_name: db_table_name.clone(),
+                    db_table_columns: db_table_columns.clone(),
+                    apply,
+                    cluster: false,
+                };
+                // manual indexes are only ever built by `rindexer indexes apply` so they stay
+                if apply != ApplyPolicy::Manual {
+                    dropping_sql.push(index_result.drop_index_sql());
+                }
+                index_results.push(index_result);
+            }
+        };
 
     // global first
     if let Some(global_injected_parameters) = &postgres_indexes.global_injected_parameters {
@@ -208,20 +362,20 @@ pub async fn prepare_indexes(
             let abi_items = ABIItem::read_abi_items(project_path, contract)?;
 
             for abi_item in abi_items {
-                let db_table_name = format!(
-                    "{}_{}.{}",
-                    camel_to_snake(manifest_name),
-                    camel_to_snake(&contract.name),
-                    camel_to_snake(&abi_item.name)
+                let db_table_names = generate_event_table_full_names(
+                    layout,
+                    manifest_name,
+                    contract,
+                    &contract.name,
+                    &abi_item.name,
                 );
 
                 for global_parameter_column_name in global_injected_parameters {
-                    let index_result = PostgresIndexResult {
-                        db_table_name: db_table_name.clone(),
-                        db_table_columns: vec![global_parameter_column_name.clone()],
-                    };
-                    dropping_sql.push(index_result.drop_index_sql());
-                    index_results.push(index_result);
+                    push_index(
+                        &db_table_names,
+                        vec![global_parameter_column_name.clone()],
+                        default_apply,
+                    );
                 }
             }
         }
@@ -243,60 +397,59 @@ pub async fn prepare_indexes(
 
                     if let Some(injected_parameters) = &contract_event_indexes.injected_parameters {
                         for abi_item in &abi_items {
-                            let db_table_name = format!(
-                                "{}_{}.{}",
-                                camel_to_snake(manifest_name),
-                                camel_to_snake(&contract.name),
-                                camel_to_snake(&abi_item.name)
+                            let db_table_names = generate_event_table_full_names(
+                                layout,
+                                manifest_name,
+                                contract,
+                                &contract.name,
+                                &abi_item.name,
                             );
 
                             for injected_parameter in injected_parameters {
-                                let index_result = PostgresIndexResult {
-                                    db_table_name: db_table_name.clone(),
-                                    db_table_columns: vec![injected_parameter.clone()],
-                                };
-                                dropping_sql.push(index_result.drop_index_sql());
-                                index_results.push(index_result);
+                                push_index(
+                                    &db_table_names,
+                                    vec![injected_parameter.clone()],
+                                    default_apply,
+                                );
                             }
                         }
                     }
 
                     for event_indexes in &contract_event_indexes.events {
-                        let db_table_name = format!(
-                            "{}_{}.{}",
-                            camel_to_snake(manifest_name),
-                            camel_to_snake(&contract.name),
-                            camel_to_snake(&event_indexes.name)
+                        let db_table_names = generate_event_table_full_names(
+                            layout,
+                            manifest_name,
+                            contract,
+                            &contract.name,
+                            &event_indexes.name,
                         );
 
                         if let Some(injected_parameters) = &event_indexes.injected_parameters {
                             for injected_parameter in injected_parameters {
-                                let index_result = PostgresIndexResult {
-                                    db_table_name: db_table_name.clone(),
-                                    db_table_columns: vec![injected_parameter.clone()],
-                                };
-                                dropping_sql.push(index_result.drop_index_sql());
-                                index_results.push(index_result);
+                                push_index(
+                                    &db_table_names,
+                                    vec![injected_parameter.clone()],
+                                    default_apply,
+                                );
                             }
                         }
 
                         for index in &event_indexes.indexes {
                             let mut db_table_columns = vec![];
                             for parameter in &index.event_input_names {
-                                let abi_parameter = get_abi_item_with_db_map(
+                                let abi_parameter = get_abi_item_or_injected_with_db_map(
                                     &abi_items,
                                     &event_indexes.name,
-                                    &parameter.split('.').collect::<Vec<&str>>(),
+                                    parameter,
                                 )?;
                                 db_table_columns.push(abi_parameter.db_column_name);
                             }
 
-                            let index_result = PostgresIndexResult {
-                                db_table_name: db_table_name.clone(),
+                            push_index(
+                                &db_table_names,
                                 db_table_columns,
-                            };
-                            dropping_sql.push(index_result.drop_index_sql());
-                            index_results.push(index_result);
+                                index.apply.unwrap_or(default_apply),
+                            );
                         }
                     }
                 }
@@ -311,9 +464,10 @@ pub async fn prepare_indexes(
     client
         .execute(
             &format!(r#"
-                INSERT INTO rindexer_internal.{manifest_name}_last_known_indexes_dropping_sql (key, value) VALUES (1, $1)
+                INSERT INTO {schema}.{manifest_name}_last_known_indexes_dropping_sql (key, value) VALUES (1, $1)
                 ON CONFLICT (key) DO UPDATE SET value = $1;
             "#,
+                     schema = internal_schema(),
                      manifest_name = camel_to_snake(manifest_name)
             ),
             &[&indexes_dropping_sql_json],
@@ -322,3 +476,91 @@ pub async fn prepare_indexes(
 
     Ok(index_results)
 }
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApplyClusterIndexesError {
+    #[error("{0}")]
+    ReadAbiError(#[from] ReadAbiError),
+
+    #[error("Could not apply cluster indexes: {0}")]
+    PostgresError(#[from] PostgresError),
+}
+
+/// Whether the index is one `cluster_on` created, including the unsuffixed name older versions
+/// gave it
+fn is_cluster_index(table_name: &str, index_name: &str) -> bool {
+    let table = table_name.rsplit('.').next().unwrap_or(table_name);
+    index_name == identifier(&format!("idx_{}_cluster", table)) ||
+        index_name.starts_with(identifier(&format!("idx_{}_cluster_", table)))
+}
+
+/// Builds the indexes `cluster_on` asks for through the managed indexes and marks the tables for
+/// `CLUSTER` on them. A cluster index of columns `cluster_on` no longer lists is dropped, an index
+/// the table was marked on by hand is left alone
+pub async fn apply_cluster_indexes(
+    client: &PostgresClient,
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<(), ApplyClusterIndexesError> {
+    let layout = manifest.storage.postgres_layout();
+    let mut tables: Vec<(String, Option<&[String]>)> = vec![];
+    for contract in &manifest.contracts {
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+        for abi_item in ABIItem::read_abi_items(project_path, contract)? {
+            let cluster_on = table_settings_for_event(&contract.table_settings, &abi_item.name)
+                .and_then(|settings| settings.cluster_on.as_deref());
+            for table_name in generate_event_table_full_names(
+                layout,
+                &manifest.name,
+                contract,
+                &contract_name,
+                &abi_item.name,
+            ) {
+                tables.push((table_name, cluster_on));
+            }
+        }
+    }
+    for projection in manifest.storage.postgres_projections() {
+        let cluster_on =
+            projection.table_settings.as_ref().and_then(|settings| settings.cluster_on.as_deref());
+        tables.push((projection_table_name(&manifest.name, &projection.name), cluster_on));
+    }
+
+    client.batch_execute(generate_managed_indexes_table_sql(&manifest.name).as_str()).await?;
+    let clustered = clustered_indexes(client).await?;
+    for (table_name, cluster_on) in tables {
+        let index = cluster_on.map(|columns| PostgresIndexResult::cluster(&table_name, columns));
+        let index_name = index.as_ref().map(|index| index.index_name());
+        let current = clustered.get(&table_name);
+
+        if let Some(current) = current {
+            if index_name.as_deref().map(identifier) != Some(current.as_str()) &&
+                is_cluster_index(&table_name, current)
+            {
+                info!("cluster_on of {} changed, dropping cluster index {}", table_name, current);
+                let schema = table_name.split('.').next().unwrap_or_default();
+                client
+                    .execute_ddl(
+                        &format!("DROP INDEX CONCURRENTLY IF EXISTS {}.{};", schema, current),
+                        "drop a stale cluster index",
+                    )
+                    .await?;
+                remove_index_state(client, &manifest.name, &table_name, current).await?;
+            }
+        }
+
+        if let (Some(index), Some(index_name)) = (index, index_name) {
+            index.build(client, &manifest.name).await?;
+            if current.map(String::as_str) != Some(identifier(&index_name)) {
+                client
+                    .execute_ddl(
+                        &format!("ALTER TABLE {} CLUSTER ON {};", table_name, index_name),
+                        "mark the table for cluster",
+                    )
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
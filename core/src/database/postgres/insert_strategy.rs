@@ -0,0 +1,132 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::info;
+
+use crate::manifest::storage::InsertStrategy;
+
+/// Batches up to this size are cheapest as a single multi VALUES insert
+pub const VALUES_MAX_ROWS: usize = 10;
+
+/// Batches up to this size use UNNEST, anything bigger goes the COPY route
+pub const UNNEST_MAX_ROWS: usize = 100;
+
+/// Postgres protocol limit on bind parameters in a single statement
+pub const MAX_QUERY_PARAMETERS: usize = 65535;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InsertPath {
+    Copy,
+    Unnest,
+    Values,
+}
+
+struct InsertPathCounters {
+    batches: AtomicU64,
+    rows: AtomicU64,
+}
+
+impl InsertPathCounters {
+    const fn new() -> Self {
+        Self { batches: AtomicU64::new(0), rows: AtomicU64::new(0) }
+    }
+}
+
+static COPY_COUNTERS: InsertPathCounters = InsertPathCounters::new();
+static UNNEST_COUNTERS: InsertPathCounters = InsertPathCounters::new();
+static VALUES_COUNTERS: InsertPathCounters = InsertPathCounters::new();
+
+impl InsertPath {
+    const ALL: [InsertPath; 3] = [InsertPath::Copy, InsertPath::Unnest, InsertPath::Values];
+
+    fn counters(&self) -> &'static InsertPathCounters {
+        match self {
+            InsertPath::Copy => &COPY_COUNTERS,
+            InsertPath::Unnest => &UNNEST_COUNTERS,
+            InsertPath::Values => &VALUES_COUNTERS,
+        }
+    }
+
+    pub fn record(&self, rows: usize) {
+        let counters = self.counters();
+        counters.batches.fetch_add(1, Ordering::Relaxed);
+        counters.rows.fetch_add(rows as u64, Ordering::Relaxed);
+    }
+}
+
+/// Returns the (batches, rows) written through each insert path since startup
+pub fn insert_path_metrics() -> Vec<(InsertPath, u64, u64)> {
+    InsertPath::ALL
+        .iter()
+        .map(|path| {
+            let counters = path.counters();
+            (*path, counters.batches.load(Ordering::Relaxed), counters.rows.load(Ordering::Relaxed))
+        })
+        .collect()
+}
+
+pub fn log_insert_path_metrics() {
+    let metrics = insert_path_metrics();
+    if metrics.iter().all(|(_, batches, _)| *batches == 0) {
+        return;
+    }
+
+    let metrics = metrics
+        .iter()
+        .map(|(path, batches, rows)| format!("{:?}: {} batches / {} rows", path, batches, rows))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    info!("Postgres insert paths used - {}", metrics);
+}
+
+/// UNNEST flattens multidimensional arrays so tables with array columns can not use it
+pub fn select_insert_path(
+    strategy: InsertStrategy,
+    rows: usize,
+    columns: usize,
+    supports_unnest: bool,
+) -> InsertPath {
+    let fits_in_values = rows * columns <= MAX_QUERY_PARAMETERS;
+
+    match strategy {
+        InsertStrategy::Copy => InsertPath::Copy,
+        InsertStrategy::Unnest if supports_unnest => InsertPath::Unnest,
+        InsertStrategy::Values if fits_in_values => InsertPath::Values,
+        InsertStrategy::Unnest | InsertStrategy::Values => {
+            if fits_in_values && rows <= UNNEST_MAX_ROWS {
+                InsertPath::Values
+            } else {
+                InsertPath::Copy
+            }
+        }
+        InsertStrategy::Auto => {
+            if rows <= VALUES_MAX_ROWS && fits_in_values {
+                InsertPath::Values
+            } else if rows <= UNNEST_MAX_ROWS && supports_unnest {
+                InsertPath::Unnest
+            } else if rows <= UNNEST_MAX_ROWS && fits_in_values {
+                InsertPath::Values
+            } else {
+                InsertPath::Copy
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_insert_path() {
+        assert_eq!(select_insert_path(InsertStrategy::Auto, 5, 10, true), InsertPath::Values);
+        assert_eq!(select_insert_path(InsertStrategy::Auto, 50, 10, true), InsertPath::Unnest);
+        assert_eq!(select_insert_path(InsertStrategy::Auto, 50, 10, false), InsertPath::Values);
+        assert_eq!(select_insert_path(InsertStrategy::Auto, 500, 10, true), InsertPath::Copy);
+        assert_eq!(select_insert_path(InsertStrategy::Copy, 1, 10, true), InsertPath::Copy);
+        assert_eq!(select_insert_path(InsertStrategy::Unnest, 500, 10, true), InsertPath::Unnest);
+        assert_eq!(select_insert_path(InsertStrategy::Unnest, 500, 10, false), InsertPath::Copy);
+        assert_eq!(select_insert_path(InsertStrategy::Values, 500, 10, true), InsertPath::Values);
+        assert_eq!(select_insert_path(InsertStrategy::Values, 10_000, 10, true), InsertPath::Copy);
+    }
+}
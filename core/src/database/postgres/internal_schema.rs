@@ -0,0 +1,194 @@
+use std::sync::OnceLock;
+
+use tracing::warn;
+
+use crate::helpers::camel_to_snake;
+
+/// Schema of the internal bookkeeping tables for projects without a `project_id`
+pub const DEFAULT_INTERNAL_SCHEMA: &str = "rindexer_internal";
+
+static INTERNAL_SCHEMA: OnceLock<String> = OnceLock::new();
+
+/// Tables in the internal schema holding rows of every indexer, keyed by `indexer_name`
+pub const SHARED_INTERNAL_TABLES: [&str; 7] = [
+    "indexing_stats",
+    "contract_lifecycle",
+    "event_signatures",
+    "failed_events",
+    "repair_ranges",
+    "verify_progress",
+    "contracts",
+];
+
+pub fn internal_schema_for_project(project_id: Option<&str>) -> String {
+    match project_id {
+        Some(project_id) => format!("{}_{}", DEFAULT_INTERNAL_SCHEMA, project_id),
+        None => DEFAULT_INTERNAL_SCHEMA.to_string(),
+    }
+}
+
+/// Namespaces the internal tables of this process by the project id of its manifest, the first
+/// manifest read wins as the tables of one process never move schema while it runs
+pub fn set_internal_schema_project(project_id: Option<&str>) {
+    let schema = internal_schema_for_project(project_id);
+    let current = INTERNAL_SCHEMA.get_or_init(|| schema.clone());
+    if *current != schema {
+        warn!(
+            "Internal tables already use {} - ignoring the project id of a second manifest",
+            current
+        );
+    }
+}
+
+pub fn internal_schema() -> &'static str {
+    INTERNAL_SCHEMA.get().map_or(DEFAULT_INTERNAL_SCHEMA, |schema| schema.as_str())
+}
+
+/// Schema qualified name of an internal table
+pub fn internal_table(table: &str) -> String {
+    format!("{}.{}", internal_schema(), table)
+}
+
+/// Tables in the internal schema owned by one indexer, named `{indexer}_{table}`
+pub const INDEXER_INTERNAL_TABLES: [&str; 9] = [
+    "last_known_indexes_dropping_sql",
+    "last_known_relationship_dropping_sql",
+    "last_known_relationship_views_dropping_sql",
+    "applied_migrations",
+    "handler_cache",
+    "heartbeats",
+    "managed_indexes",
+    "topic_preimages",
+    "insert_bench",
+];
+
+/// Tables in the internal schema shared with every indexer on the chain, copied over as a whole
+const COPIED_INTERNAL_TABLES: [&str; 4] =
+    ["deployment_blocks", "gas_prices", "block_anomalies", "block_anomalies_last_synced"];
+
+/// Copies a table from the shared schema, `LIKE` keeps the serial defaults pointing at the old
+/// schema so those get sequences of their own carrying on from the copied rows
+fn copy_internal_table_sql(schema: &str, table: &str, rows: Option<&str>) -> String {
+    let old = DEFAULT_INTERNAL_SCHEMA;
+    let (filter, delete) = match rows {
+        Some(filter) => {
+            (format!(" WHERE {}", filter), format!("DELETE FROM {old}.{table} WHERE {filter};"))
+        }
+        None => (String::new(), String::new()),
+    };
+
+    format!(
+        r#"
+                IF to_regclass('{old}.{table}') IS NOT NULL THEN
+                    CREATE TABLE {schema}.{table} (LIKE {old}.{table} INCLUDING ALL);
+                    INSERT INTO {schema}.{table} SELECT * FROM {old}.{table}{filter};
+                    FOR serial_column IN
+                        SELECT attname FROM pg_attribute
+                        WHERE attrelid = '{old}.{table}'::regclass AND attnum > 0 AND NOT attisdropped AND attidentity = ''
+                        AND pg_get_serial_sequence('{old}.{table}', attname) IS NOT NULL
+                    LOOP
+                        sequence_name := '{table}_' || serial_column.attname || '_seq';
+                        EXECUTE format('CREATE SEQUENCE {schema}.%I OWNED BY {schema}.{table}.%I', sequence_name, serial_column.attname);
+                        EXECUTE format('ALTER TABLE {schema}.{table} ALTER COLUMN %I SET DEFAULT nextval(%L)', serial_column.attname, '{schema}.' || sequence_name);
+                        EXECUTE format('SELECT setval(%L, COALESCE((SELECT MAX(%I) FROM {schema}.{table}), 0) + 1, false)', '{schema}.' || sequence_name, serial_column.attname);
+                    END LOOP;
+                    {delete}
+                END IF;"#
+    )
+}
+
+/// Moves the internal tables of an indexer out of the shared `rindexer_internal` schema the first
+/// time its project gets a `project_id`, nothing runs once the namespaced schema exists.
+/// `checkpoint_tables` are the last synced block tables of every event of the indexer.
+pub fn generate_internal_schema_migration_sql(
+    indexer_name: &str,
+    schema: &str,
+    checkpoint_tables: &[String],
+) -> String {
+    if schema == DEFAULT_INTERNAL_SCHEMA {
+        return String::new();
+    }
+
+    // tables are prefixed with the snake case name, the shared rows keep the name as it is
+    let snake_name = camel_to_snake(indexer_name);
+    let owned_tables = INDEXER_INTERNAL_TABLES
+        .iter()
+        .map(|table| format!("{}_{}", snake_name, table))
+        .chain(checkpoint_tables.iter().cloned())
+        .map(|table| format!("'{}'", table.replace('\'', "''")))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let indexer_filter = format!("indexer_name = '{}'", indexer_name.replace('\'', "''"));
+    // the audit trail is kept on teardown so it is not one of the shared tables
+    let shared_tables = SHARED_INTERNAL_TABLES
+        .iter()
+        .chain(["ddl_audit"].iter())
+        .map(|table| copy_internal_table_sql(schema, table, Some(&indexer_filter)))
+        .chain(
+            COPIED_INTERNAL_TABLES.iter().map(|table| copy_internal_table_sql(schema, table, None)),
+        )
+        .collect::<String>();
+
+    format!(
+        r#"
+        DO $$
+        DECLARE
+            owned_table TEXT;
+            serial_column record;
+            sequence_name TEXT;
+        BEGIN
+            IF to_regnamespace('{schema}') IS NULL AND to_regnamespace('{old}') IS NOT NULL THEN
+                CREATE SCHEMA {schema};
+                FOREACH owned_table IN ARRAY ARRAY[{owned_tables}]::TEXT[] LOOP
+                    IF to_regclass(format('{old}.%I', owned_table)) IS NOT NULL THEN
+                        EXECUTE format('ALTER TABLE {old}.%I SET SCHEMA {schema}', owned_table);
+                    END IF;
+                END LOOP;
+                {shared_tables}
+            END IF;
+        END $$;
+        "#,
+        old = DEFAULT_INTERNAL_SCHEMA,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internal_schema_for_project() {
+        assert_eq!(internal_schema_for_project(None), "rindexer_internal");
+        assert_eq!(internal_schema_for_project(Some("dex")), "rindexer_internal_dex");
+    }
+
+    #[test]
+    fn test_internal_schema_migration_sql() {
+        assert!(generate_internal_schema_migration_sql("my_indexer", "rindexer_internal", &[])
+            .is_empty());
+
+        let sql = generate_internal_schema_migration_sql(
+            "MyIndexer",
+            "rindexer_internal_dex",
+            &["my_indexer_erc_20_transfer".to_string()],
+        );
+        // only the tables of the indexer move, not another indexer sharing the prefix
+        assert!(!sql.contains("LIKE 'my"));
+        assert!(sql.contains("'my_indexer_heartbeats'"));
+        assert!(sql.contains("'my_indexer_erc_20_transfer'"));
+        assert!(sql.contains("ALTER TABLE rindexer_internal.%I SET SCHEMA rindexer_internal_dex"));
+        assert!(sql.contains(
+            "INSERT INTO rindexer_internal_dex.failed_events SELECT * FROM rindexer_internal.failed_events WHERE indexer_name = 'MyIndexer';"
+        ));
+        assert!(sql.contains(
+            "INSERT INTO rindexer_internal_dex.ddl_audit SELECT * FROM rindexer_internal.ddl_audit WHERE indexer_name = 'MyIndexer';"
+        ));
+        assert!(sql.contains(
+            "CREATE SEQUENCE rindexer_internal_dex.%I OWNED BY rindexer_internal_dex.failed_events.%I"
+        ));
+        assert!(sql.contains(
+            "INSERT INTO rindexer_internal_dex.gas_prices SELECT * FROM rindexer_internal.gas_prices;"
+        ));
+        assert!(!sql.contains("DELETE FROM rindexer_internal.gas_prices"));
+    }
+}
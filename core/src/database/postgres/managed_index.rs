@@ -0,0 +1,322 @@
+use std::{collections::HashMap, time::Duration};
+
+use tokio::time::interval;
+use tracing::{info, warn};
+
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        internal_schema::internal_table,
+    },
+    helpers::camel_to_snake,
+    types::code::Code,
+};
+
+/// How often a running index build logs its progress
+const INDEX_PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Postgres truncates identifiers to this many bytes
+const MAX_IDENTIFIER_LENGTH: usize = 63;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManagedIndexState {
+    Building,
+    Valid,
+    Failed,
+}
+
+impl ManagedIndexState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ManagedIndexState::Building => "building",
+            ManagedIndexState::Valid => "valid",
+            ManagedIndexState::Failed => "failed",
+        }
+    }
+}
+
+pub fn managed_indexes_table_name(indexer_name: &str) -> String {
+    internal_table(&format!("{}_managed_indexes", camel_to_snake(indexer_name)))
+}
+
+pub fn generate_managed_indexes_table_sql(indexer_name: &str) -> Code {
+    Code::new(format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {} (
+            table_name TEXT NOT NULL,
+            index_name TEXT NOT NULL,
+            columns TEXT NOT NULL,
+            state TEXT NOT NULL,
+            attempts INT NOT NULL DEFAULT 0,
+            error TEXT,
+            started_at TIMESTAMPTZ,
+            finished_at TIMESTAMPTZ,
+            PRIMARY KEY (table_name, index_name)
+        );
+    "#,
+        managed_indexes_table_name(indexer_name)
+    ))
+}
+
+/// An index rindexer manages with its recorded build state and, while it is being built by any
+/// connection, the progress postgres reports for it
+#[derive(Debug)]
+pub struct ManagedIndex {
+    pub table_name: String,
+    pub index_name: String,
+    pub columns: String,
+    pub state: String,
+    pub attempts: i32,
+    pub error: Option<String>,
+    pub finished_at: Option<String>,
+    /// `None` when the index does not exist, `Some(false)` when a failed build left it invalid
+    pub valid: Option<bool>,
+    pub progress: Option<String>,
+}
+
+pub(crate) fn identifier(name: &str) -> &str {
+    match name.char_indices().nth(MAX_IDENTIFIER_LENGTH) {
+        Some((end, _)) => &name[..end],
+        None => name,
+    }
+}
+
+/// Whether the index exists and postgres can use it, `CREATE INDEX CONCURRENTLY` leaves an
+/// invalid index behind when it fails
+pub async fn index_validity(
+    client: &PostgresClient,
+    schema: &str,
+    index_name: &str,
+) -> Result<Option<bool>, PostgresError> {
+    let row = client
+        .query_one_or_none(
+            "SELECT i.indisvalid FROM pg_index i \
+            JOIN pg_class c ON c.oid = i.indexrelid \
+            JOIN pg_namespace n ON n.oid = c.relnamespace \
+            WHERE n.nspname = $1 AND c.relname = $2",
+            &[&schema, &identifier(index_name)],
+        )
+        .await?;
+
+    Ok(row.map(|row| row.get(0)))
+}
+
+fn format_index_progress(
+    phase: &str,
+    blocks_done: i64,
+    blocks_total: i64,
+    tuples_done: i64,
+    tuples_total: i64,
+) -> String {
+    let (done, total, unit) = if blocks_total > 0 {
+        (blocks_done, blocks_total, "blocks")
+    } else {
+        (tuples_done, tuples_total, "tuples")
+    };
+    if total > 0 {
+        format!(
+            "{} - {} {}/{} ({:.1}%)",
+            phase,
+            unit,
+            done,
+            total,
+            done as f64 * 100.0 / total as f64
+        )
+    } else {
+        phase.to_string()
+    }
+}
+
+const INDEX_PROGRESS_SQL: &str =
+    "SELECT phase, blocks_done, blocks_total, tuples_done, tuples_total \
+    FROM pg_stat_progress_create_index WHERE relid = to_regclass($1)";
+
+async fn index_progress(client: &PostgresClient, table_name: &str) -> Option<String> {
+    let row = client.query_one_or_none(INDEX_PROGRESS_SQL, &[&table_name]).await.ok()??;
+    Some(format_index_progress(row.get(0), row.get(1), row.get(2), row.get(3), row.get(4)))
+}
+
+pub async fn record_index_state(
+    client: &PostgresClient,
+    indexer_name: &str,
+    table_name: &str,
+    index_name: &str,
+    columns: &str,
+    state: ManagedIndexState,
+    error: Option<&str>,
+) -> Result<(), PostgresError> {
+    let sql = if state == ManagedIndexState::Building {
+        format!(
+            "INSERT INTO {table} (table_name, index_name, columns, state, attempts, error, started_at) \
+            VALUES ($1, $2, $3, $4, 1, $5, NOW()) \
+            ON CONFLICT (table_name, index_name) DO UPDATE SET columns = EXCLUDED.columns, \
+            state = EXCLUDED.state, attempts = {table}.attempts + 1, error = NULL, \
+            started_at = NOW(), finished_at = NULL",
+            table = managed_indexes_table_name(indexer_name)
+        )
+    } else {
+        format!(
+            "INSERT INTO {table} (table_name, index_name, columns, state, error, finished_at) \
+            VALUES ($1, $2, $3, $4, $5, NOW()) \
+            ON CONFLICT (table_name, index_name) DO UPDATE SET columns = EXCLUDED.columns, \
+            state = EXCLUDED.state, error = EXCLUDED.error, finished_at = NOW()",
+            table = managed_indexes_table_name(indexer_name)
+        )
+    };
+
+    client.execute(&sql, &[&table_name, &index_name, &columns, &state.as_str(), &error]).await?;
+    Ok(())
+}
+
+/// Runs the `CREATE INDEX CONCURRENTLY` statement, logging what `pg_stat_progress_create_index`
+/// reports for the table while it runs
+pub async fn run_index_build(
+    client: &PostgresClient,
+    table_name: &str,
+    index_name: &str,
+    sql: &str,
+) -> Result<(), PostgresError> {
+    let build = client.execute_ddl(sql, "apply an index");
+    tokio::pin!(build);
+
+    let mut progress_interval = interval(INDEX_PROGRESS_INTERVAL);
+    // the first tick completes straight away
+    progress_interval.tick().await;
+    loop {
+        tokio::select! {
+            result = &mut build => return result,
+            _ = progress_interval.tick() => {
+                if let Some(progress) = index_progress(client, table_name).await {
+                    info!("Building index {} on {}: {}", index_name, table_name, progress);
+                }
+            }
+        }
+    }
+}
+
+/// Drops an index a failed concurrent build left invalid so it can be built again
+pub async fn drop_invalid_index(
+    client: &PostgresClient,
+    schema: &str,
+    index_name: &str,
+) -> Result<(), PostgresError> {
+    warn!("Index {}.{} is invalid from a failed build, rebuilding it", schema, index_name);
+    client
+        .execute_ddl(
+            &format!("DROP INDEX CONCURRENTLY IF EXISTS {}.{};", schema, index_name),
+            "drop an invalid index",
+        )
+        .await
+}
+
+/// Forgets the recorded state of an index which was dropped on purpose
+pub async fn remove_index_state(
+    client: &PostgresClient,
+    indexer_name: &str,
+    table_name: &str,
+    index_name: &str,
+) -> Result<(), PostgresError> {
+    client
+        .execute(
+            &format!(
+                "DELETE FROM {} WHERE table_name = $1 AND index_name = $2",
+                managed_indexes_table_name(indexer_name)
+            ),
+            &[&table_name, &index_name],
+        )
+        .await?;
+    Ok(())
+}
+
+/// The index each table is marked for `CLUSTER` on keyed by the schema qualified table name
+pub async fn clustered_indexes(
+    client: &PostgresClient,
+) -> Result<HashMap<String, String>, PostgresError> {
+    let rows = client
+        .query(
+            "SELECT n.nspname || '.' || t.relname, c.relname FROM pg_index i \
+            JOIN pg_class c ON c.oid = i.indexrelid \
+            JOIN pg_class t ON t.oid = i.indrelid \
+            JOIN pg_namespace n ON n.oid = t.relnamespace \
+            WHERE i.indisclustered",
+            &[],
+        )
+        .await?;
+
+    Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// The recorded state of every index the indexer manages
+pub async fn managed_indexes(
+    client: &PostgresClient,
+    indexer_name: &str,
+) -> Result<Vec<ManagedIndex>, PostgresError> {
+    client.batch_execute(generate_managed_indexes_table_sql(indexer_name).as_str()).await?;
+
+    let rows = client
+        .query(
+            &format!(
+                "SELECT m.table_name, m.index_name, m.columns, m.state, m.attempts, m.error, \
+                m.finished_at::TEXT, \
+                (SELECT i.indisvalid FROM pg_index i \
+                    JOIN pg_class c ON c.oid = i.indexrelid \
+                    JOIN pg_namespace n ON n.oid = c.relnamespace \
+                    WHERE n.nspname = split_part(m.table_name, '.', 1) \
+                    AND c.relname = left(m.index_name, {}::INT)) \
+                FROM {} m ORDER BY m.table_name, m.index_name",
+                MAX_IDENTIFIER_LENGTH,
+                managed_indexes_table_name(indexer_name)
+            ),
+            &[],
+        )
+        .await?;
+
+    let mut indexes = vec![];
+    for row in rows {
+        let table_name: String = row.get(0);
+        let state: String = row.get(3);
+        let progress = if state == ManagedIndexState::Building.as_str() {
+            index_progress(client, &table_name).await
+        } else {
+            None
+        };
+        indexes.push(ManagedIndex {
+            table_name,
+            index_name: row.get(1),
+            columns: row.get(2),
+            state,
+            attempts: row.get(4),
+            error: row.get(5),
+            finished_at: row.get(6),
+            valid: row.get(7),
+            progress,
+        });
+    }
+
+    Ok(indexes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_index_progress() {
+        assert_eq!(
+            format_index_progress("building index: scanning table", 25, 100, 0, 0),
+            "building index: scanning table - blocks 25/100 (25.0%)"
+        );
+        assert_eq!(
+            format_index_progress("building index: loading tuples in tree", 0, 0, 1, 4),
+            "building index: loading tuples in tree - tuples 1/4 (25.0%)"
+        );
+        assert_eq!(format_index_progress("initializing", 0, 0, 0, 0), "initializing");
+    }
+
+    #[test]
+    fn test_identifier_is_truncated_like_postgres() {
+        let long = "a".repeat(70);
+        assert_eq!(identifier(&long).len(), 63);
+        assert_eq!(identifier("idx_transfer_from"), "idx_transfer_from");
+    }
+}
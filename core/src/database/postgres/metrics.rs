@@ -0,0 +1,230 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
+
+use crate::database::postgres::{
+    insert_strategy::{insert_path_metrics, InsertPath},
+    write_retry::write_retry_metrics,
+};
+
+const ROW_BUCKETS: &[f64] = &[1.0, 10.0, 100.0, 1_000.0, 10_000.0, 100_000.0];
+const SECONDS_BUCKETS: &[f64] =
+    &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Cumulative prometheus histogram, each bucket counts every value up to its bound
+struct Histogram {
+    bounds: &'static [f64],
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            if value <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+            Some((f64::from_bits(sum) + value).to_bits())
+        });
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, f64::from_bits(self.sum.load(Ordering::Relaxed)));
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+/// What the postgres write path has done since startup, recorded by `PostgresClient`
+pub struct PostgresWriteMetrics {
+    batch_rows: Histogram,
+    commit_seconds: Histogram,
+    pool_wait_seconds: Histogram,
+    conflicting_rows: AtomicU64,
+    undecodable_logs: AtomicU64,
+}
+
+impl PostgresWriteMetrics {
+    fn new() -> Self {
+        Self {
+            batch_rows: Histogram::new(ROW_BUCKETS),
+            commit_seconds: Histogram::new(SECONDS_BUCKETS),
+            pool_wait_seconds: Histogram::new(SECONDS_BUCKETS),
+            conflicting_rows: AtomicU64::new(0),
+            undecodable_logs: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe_batch(&self, rows: usize) {
+        self.batch_rows.observe(rows as f64);
+    }
+
+    /// Rows of a batch which were skipped because they already existed
+    pub fn observe_conflicts(&self, rows: usize, inserted: u64) {
+        self.conflicting_rows.fetch_add((rows as u64).saturating_sub(inserted), Ordering::Relaxed);
+    }
+
+    /// Logs left out of a batch because their params did not fit the ABI
+    pub fn observe_undecodable(&self, logs: usize) {
+        self.undecodable_logs.fetch_add(logs as u64, Ordering::Relaxed);
+    }
+
+    pub fn observe_commit(&self, elapsed: Duration) {
+        self.commit_seconds.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_pool_wait(&self, elapsed: Duration) {
+        self.pool_wait_seconds.observe(elapsed.as_secs_f64());
+    }
+}
+
+pub fn postgres_write_metrics() -> &'static PostgresWriteMetrics {
+    static METRICS: OnceLock<PostgresWriteMetrics> = OnceLock::new();
+    METRICS.get_or_init(PostgresWriteMetrics::new)
+}
+
+fn insert_path_label(path: InsertPath) -> &'static str {
+    match path {
+        InsertPath::Copy => "copy",
+        InsertPath::Unnest => "unnest",
+        InsertPath::Values => "values",
+    }
+}
+
+fn render_counter(out: &mut String, name: &str, help: &str, values: &[(Option<String>, u64)]) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for (labels, value) in values {
+        match labels {
+            Some(labels) => {
+                let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+            }
+            None => {
+                let _ = writeln!(out, "{} {}", name, value);
+            }
+        }
+    }
+}
+
+/// The postgres write path metrics in the prometheus text exposition format
+pub fn render_postgres_metrics() -> String {
+    let metrics = postgres_write_metrics();
+    let mut out = String::new();
+
+    metrics.batch_rows.render(
+        &mut out,
+        "rindexer_postgres_batch_rows",
+        "Rows in each batch written to postgres",
+    );
+
+    let insert_paths = insert_path_metrics();
+    render_counter(
+        &mut out,
+        "rindexer_postgres_insert_batches_total",
+        "Batches written through each insert path",
+        &insert_paths
+            .iter()
+            .map(|(path, batches, _)| {
+                (Some(format!("path=\"{}\"", insert_path_label(*path))), *batches)
+            })
+            .collect::<Vec<_>>(),
+    );
+    render_counter(
+        &mut out,
+        "rindexer_postgres_insert_rows_total",
+        "Rows written through each insert path",
+        &insert_paths
+            .iter()
+            .map(|(path, _, rows)| (Some(format!("path=\"{}\"", insert_path_label(*path))), *rows))
+            .collect::<Vec<_>>(),
+    );
+    render_counter(
+        &mut out,
+        "rindexer_postgres_conflicting_rows_total",
+        "Rows skipped because they were already written",
+        &[(None, metrics.conflicting_rows.load(Ordering::Relaxed))],
+    );
+    render_counter(
+        &mut out,
+        "rindexer_undecodable_logs_total",
+        "Logs skipped because their params did not fit the ABI",
+        &[(None, metrics.undecodable_logs.load(Ordering::Relaxed))],
+    );
+
+    let (retried, recovered, exhausted, rejected) = write_retry_metrics();
+    render_counter(
+        &mut out,
+        "rindexer_postgres_write_retries_total",
+        "Failed writes by what happened to them after retrying",
+        &[
+            (Some("outcome=\"retried\"".to_string()), retried),
+            (Some("outcome=\"recovered\"".to_string()), recovered),
+            (Some("outcome=\"exhausted\"".to_string()), exhausted),
+            (Some("outcome=\"rejected\"".to_string()), rejected),
+        ],
+    );
+
+    metrics.commit_seconds.render(
+        &mut out,
+        "rindexer_postgres_commit_seconds",
+        "Time taken to commit write transactions",
+    );
+    metrics.pool_wait_seconds.render(
+        &mut out,
+        "rindexer_postgres_pool_wait_seconds",
+        "Time spent waiting for a connection from the pool",
+    );
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_render() {
+        let histogram = Histogram::new(&[1.0, 10.0]);
+        histogram.observe(1.0);
+        histogram.observe(5.0);
+        histogram.observe(50.0);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "rows", "Rows");
+        assert!(out.contains("rows_bucket{le=\"1\"} 1\n"));
+        assert!(out.contains("rows_bucket{le=\"10\"} 2\n"));
+        assert!(out.contains("rows_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("rows_sum 56\n"));
+        assert!(out.contains("rows_count 3\n"));
+    }
+}
@@ -1,6 +1,30 @@
+pub mod balance_change;
+pub mod block_anomaly;
+pub mod cdc;
 pub mod client;
+pub mod contract_metadata;
+pub mod ddl_audit;
+pub mod gas_price;
 pub mod generate;
+pub mod heartbeat;
+#[cfg(feature = "csv")]
+pub mod import;
 pub mod indexes;
+pub mod insert_strategy;
+pub mod internal_schema;
+pub mod managed_index;
+pub mod metrics;
+pub mod projection;
+pub mod reconciliation;
 pub mod relationship;
+pub mod rollup;
+pub mod schema_drift;
 pub mod setup;
+pub mod snapshot;
 pub mod sql_type_wrapper;
+pub mod table_query;
+pub mod topic_preimages;
+pub mod user_migrations;
+pub mod views;
+pub mod wallet_activity;
+pub mod write_retry;
@@ -0,0 +1,466 @@
+use ethers::{abi::LogParam, types::U64};
+use regex::Regex;
+
+use crate::{
+    abi::ABIInput,
+    database::postgres::{client::TextStatement, generate::generate_table_storage_settings_sql},
+    event::callback_registry::TxInformation,
+    helpers::{camel_to_snake, format_address},
+    indexer::log_param_text,
+    manifest::projection::{Projection, ProjectionColumn, ProjectionUpsert},
+    types::code::Code,
+};
+
+pub fn generate_projection_schema_name(indexer_name: &str) -> String {
+    format!("{}_projections", camel_to_snake(indexer_name))
+}
+
+pub fn projection_table_name(indexer_name: &str, name: &str) -> String {
+    format!("{}.{}", generate_projection_schema_name(indexer_name), camel_to_snake(name))
+}
+
+fn column_definition(column: &ProjectionColumn, not_null: bool) -> String {
+    let mut definition = format!("\"{}\" {}", column.name, column.type_);
+    if not_null {
+        definition.push_str(" NOT NULL");
+    }
+    if let Some(default) = &column.default {
+        definition.push_str(&format!(" DEFAULT {}", default));
+    }
+    definition
+}
+
+fn projection_history_table_name(indexer_name: &str, name: &str) -> String {
+    format!("{}_history", projection_table_name(indexer_name, name))
+}
+
+/// Puts the rows of the projection changed at or after a block back to their version before it,
+/// rows without one are deleted. Every statement takes the network and the block
+fn generate_projection_rollback_sql(indexer_name: &str, projection: &Projection) -> Vec<String> {
+    let table_name = projection_table_name(indexer_name, &projection.name);
+    let history_table_name = projection_history_table_name(indexer_name, &projection.name);
+    let key_columns: Vec<String> = std::iter::once("\"network\"".to_string())
+        .chain(projection.keys.iter().map(|key| format!("\"{}\"", key.name)))
+        .collect();
+    let restored_columns: String = projection
+        .columns
+        .iter()
+        .map(|column| format!("\"{0}\" = h.\"{0}\", ", column.name))
+        .collect();
+    let changed = "t.\"network\" = $1 AND t.\"last_updated_block\" >= $2::NUMERIC";
+
+    vec![
+        format!(
+            "DELETE FROM {} WHERE \"network\" = $1 AND \"last_updated_block\" >= $2::NUMERIC",
+            history_table_name
+        ),
+        format!(
+            "UPDATE {table_name} AS t SET {restored_columns}\"last_updated_block\" = h.\"last_updated_block\" \
+            FROM (SELECT DISTINCT ON ({keys}) * FROM {history_table_name} WHERE \"network\" = $1 ORDER BY {keys}, \"last_updated_block\" DESC) AS h \
+            WHERE {changed} AND {key_matches}",
+            keys = key_columns.join(", "),
+            key_matches = key_columns
+                .iter()
+                .map(|key| format!("t.{0} = h.{0}", key))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+        ),
+        format!("DELETE FROM {} AS t WHERE {}", table_name, changed),
+    ]
+}
+
+/// Every update to the projection is copied to a history table by trigger, the last version in a
+/// block wins, `{name}_as_of` picks the latest version of each key at or before a block
+fn generate_projection_history_sql(indexer_name: &str, projection: &Projection) -> String {
+    let schema_name = generate_projection_schema_name(indexer_name);
+    let name = camel_to_snake(&projection.name);
+    let table_name = projection_table_name(indexer_name, &projection.name);
+    let history_table_name = projection_history_table_name(indexer_name, &projection.name);
+
+    let key_columns: Vec<String> = std::iter::once("\"network\"".to_string())
+        .chain(projection.keys.iter().map(|key| format!("\"{}\"", key.name)))
+        .collect();
+    let on_conflict = if projection.columns.is_empty() {
+        "DO NOTHING".to_string()
+    } else {
+        format!(
+            "DO UPDATE SET {}",
+            projection
+                .columns
+                .iter()
+                .map(|column| format!("\"{0}\" = EXCLUDED.\"{0}\"", column.name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+
+    format!(
+        r#"
+            CREATE TABLE IF NOT EXISTS {history_table_name} (
+                LIKE {table_name} INCLUDING DEFAULTS,
+                PRIMARY KEY ({key_columns}, "last_updated_block")
+            );
+            CREATE OR REPLACE FUNCTION {schema_name}.{name}_record_history() RETURNS TRIGGER AS $$
+            BEGIN
+                IF NEW."last_updated_block" IS NOT NULL THEN
+                    INSERT INTO {history_table_name} SELECT NEW.*
+                    ON CONFLICT ({key_columns}, "last_updated_block") {on_conflict};
+                END IF;
+                RETURN NEW;
+            END;
+            $$ LANGUAGE plpgsql;
+            DROP TRIGGER IF EXISTS {name}_record_history ON {table_name};
+            CREATE TRIGGER {name}_record_history AFTER INSERT OR UPDATE ON {table_name}
+            FOR EACH ROW EXECUTE FUNCTION {schema_name}.{name}_record_history();
+            CREATE OR REPLACE FUNCTION {schema_name}.{name}_as_of(block_number NUMERIC)
+            RETURNS SETOF {table_name} AS $$
+                SELECT DISTINCT ON ({key_columns}) * FROM {history_table_name}
+                WHERE "last_updated_block" <= block_number
+                ORDER BY {key_columns}, "last_updated_block" DESC
+            $$ LANGUAGE sql STABLE;
+        "#,
+        key_columns = key_columns.join(", "),
+    )
+}
+
+pub fn generate_projection_tables_sql(indexer_name: &str, projections: &[Projection]) -> Code {
+    let mut sql =
+        format!("CREATE SCHEMA IF NOT EXISTS {};", generate_projection_schema_name(indexer_name));
+    for projection in projections {
+        let mut columns = vec!["\"network\" TEXT NOT NULL".to_string()];
+        columns.extend(projection.keys.iter().map(|key| column_definition(key, true)));
+        columns.extend(projection.columns.iter().map(|column| column_definition(column, false)));
+        columns.push("\"last_updated_block\" NUMERIC".to_string());
+
+        let primary_key: Vec<String> = std::iter::once("\"network\"".to_string())
+            .chain(projection.keys.iter().map(|key| format!("\"{}\"", key.name)))
+            .collect();
+
+        sql.push_str(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ({}, PRIMARY KEY ({}));",
+            projection_table_name(indexer_name, &projection.name),
+            columns.join(", "),
+            primary_key.join(", ")
+        ));
+
+        if let Some(table_settings) = &projection.table_settings {
+            sql.push_str(&generate_table_storage_settings_sql(
+                &projection_table_name(indexer_name, &projection.name),
+                table_settings,
+            ));
+        }
+
+        if projection.history.unwrap_or(false) {
+            sql.push_str(&generate_projection_history_sql(indexer_name, projection));
+        }
+    }
+
+    Code::new(sql)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ProjectionParam {
+    Input(Vec<String>),
+    Network,
+    ContractAddress,
+    TxHash,
+    BlockNumber,
+    BlockHash,
+    TxIndex,
+    LogIndex,
+}
+
+fn find_input<'a>(inputs: &'a [ABIInput], path: &[&str]) -> Option<&'a ABIInput> {
+    let (first, rest) = path.split_first()?;
+    let input = inputs.iter().find(|input| input.name == *first)?;
+    if rest.is_empty() {
+        return Some(input);
+    }
+    find_input(input.components.as_deref()?, rest)
+}
+
+fn param_sql_type(abi_type: &str) -> &'static str {
+    if abi_type.ends_with(']') {
+        "TEXT"
+    } else if abi_type.starts_with("uint") || abi_type.starts_with("int") {
+        "NUMERIC"
+    } else if abi_type == "bool" {
+        "BOOLEAN"
+    } else {
+        "TEXT"
+    }
+}
+
+fn resolve_param(inputs: &[ABIInput], name: &str) -> Option<(ProjectionParam, &'static str)> {
+    let path: Vec<&str> = name.split('.').collect();
+    if let Some(input) = find_input(inputs, &path) {
+        return Some((
+            ProjectionParam::Input(path.iter().map(|p| p.to_string()).collect()),
+            param_sql_type(&input.type_),
+        ));
+    }
+
+    match name {
+        "network" => Some((ProjectionParam::Network, "TEXT")),
+        "contract_address" => Some((ProjectionParam::ContractAddress, "TEXT")),
+        "tx_hash" => Some((ProjectionParam::TxHash, "TEXT")),
+        "block_number" => Some((ProjectionParam::BlockNumber, "NUMERIC")),
+        "block_hash" => Some((ProjectionParam::BlockHash, "TEXT")),
+        "tx_index" => Some((ProjectionParam::TxIndex, "NUMERIC")),
+        "log_index" => Some((ProjectionParam::LogIndex, "NUMERIC")),
+        _ => None,
+    }
+}
+
+/// `$name` references in upsert expressions which do not match an event input or transaction field
+pub fn unknown_expression_params<'a>(
+    inputs: &[ABIInput],
+    expressions: impl Iterator<Item = &'a String>,
+) -> Vec<String> {
+    let regex = param_regex();
+    expressions
+        .flat_map(|expression| regex.captures_iter(expression).map(|c| c[1].to_string()))
+        .filter(|name| resolve_param(inputs, name).is_none())
+        .collect()
+}
+
+fn param_regex() -> Regex {
+    Regex::new(r"\$([A-Za-z_][A-Za-z0-9_]*(?:\.[A-Za-z_][A-Za-z0-9_]*)*)")
+        .expect("projection param regex is valid")
+}
+
+/// Turns `$name` references in SQL expressions into TEXT parameters and gives their values for
+/// an event
+#[derive(Debug, Clone)]
+pub struct EventExpressionParams {
+    params: Vec<ProjectionParam>,
+}
+
+impl EventExpressionParams {
+    /// $1 is always the network and $2 the block number
+    pub fn new() -> Self {
+        Self { params: vec![ProjectionParam::Network, ProjectionParam::BlockNumber] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn expression_sql(&mut self, inputs: &[ABIInput], expression: &str, type_: &str) -> String {
+        let sql = param_regex().replace_all(expression, |captures: &regex::Captures| {
+            let Some((param, sql_type)) = resolve_param(inputs, &captures[1]) else {
+                return captures[0].to_string();
+            };
+            let index = match self.params.iter().position(|p| *p == param) {
+                Some(index) => index,
+                None => {
+                    self.params.push(param);
+                    self.params.len() - 1
+                }
+            };
+            format!("${}::{}", index + 1, sql_type)
+        });
+        format!("({})::{}", sql, type_)
+    }
+
+    pub fn values(
+        &self,
+        inputs: &[ABIInput],
+        log_params: &[LogParam],
+        tx_information: &TxInformation,
+    ) -> Vec<Option<String>> {
+        self.params
+            .iter()
+            .map(|param| match param {
+                ProjectionParam::Input(path) => log_param_text(inputs, log_params, path),
+                ProjectionParam::Network => Some(tx_information.network.clone()),
+                ProjectionParam::ContractAddress => Some(format_address(&tx_information.address)),
+                ProjectionParam::TxHash => Some(format!("{:?}", tx_information.transaction_hash)),
+                ProjectionParam::BlockNumber => Some(tx_information.block_number.to_string()),
+                ProjectionParam::BlockHash => Some(format!("{:?}", tx_information.block_hash)),
+                ProjectionParam::TxIndex => Some(tx_information.transaction_index.to_string()),
+                ProjectionParam::LogIndex => Some(tx_information.log_index.to_string()),
+            })
+            .collect()
+    }
+}
+
+/// The insert and update statements an upsert rule runs for every event, both take the same TEXT
+/// parameters
+#[derive(Debug, Clone)]
+pub struct ProjectionStatements {
+    pub name: String,
+    insert_sql: String,
+    update_sql: String,
+    /// Only projections keeping a history can be rolled back to before a reorg
+    rollback_sql: Option<Vec<String>>,
+    params: EventExpressionParams,
+}
+
+impl ProjectionStatements {
+    pub fn new(
+        indexer_name: &str,
+        projection: &Projection,
+        upsert: &ProjectionUpsert,
+        inputs: &[ABIInput],
+    ) -> Self {
+        let mut params = EventExpressionParams::new();
+
+        let key_columns: Vec<(String, String)> = projection
+            .keys
+            .iter()
+            .map(|key| {
+                let expression = upsert.keys.get(&key.name).map_or("NULL", String::as_str);
+                (format!("\"{}\"", key.name), params.expression_sql(inputs, expression, &key.type_))
+            })
+            .collect();
+        let set_columns: Vec<String> = projection
+            .columns
+            .iter()
+            .filter_map(|column| {
+                let expression = upsert.set.get(&column.name)?;
+                Some(format!(
+                    "\"{}\" = {}",
+                    column.name,
+                    params.expression_sql(inputs, expression, &column.type_)
+                ))
+            })
+            .collect();
+
+        let table_name = projection_table_name(indexer_name, &projection.name);
+        let insert_sql = format!(
+            "INSERT INTO {} (\"network\", {}) VALUES ($1, {}) ON CONFLICT DO NOTHING",
+            table_name,
+            key_columns.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(", "),
+            key_columns.iter().map(|(_, value)| value.as_str()).collect::<Vec<_>>().join(", ")
+        );
+        let update_sql = format!(
+            "UPDATE {} SET {}\"last_updated_block\" = $2::NUMERIC WHERE \"network\" = $1 AND {}",
+            table_name,
+            set_columns.iter().map(|set| format!("{}, ", set)).collect::<String>(),
+            key_columns
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, value))
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        );
+
+        let rollback_sql = projection
+            .history
+            .unwrap_or(false)
+            .then(|| generate_projection_rollback_sql(indexer_name, projection));
+
+        Self { name: projection.name.clone(), insert_sql, update_sql, rollback_sql, params }
+    }
+
+    /// The statements with their parameters for a single event
+    pub fn for_event(
+        &self,
+        inputs: &[ABIInput],
+        log_params: &[LogParam],
+        tx_information: &TxInformation,
+    ) -> [(String, Vec<Option<String>>); 2] {
+        let values = self.params.values(inputs, log_params, tx_information);
+
+        [(self.insert_sql.clone(), values.clone()), (self.update_sql.clone(), values)]
+    }
+
+    /// The statements rolling the projection on the network back to before a block a reorg
+    /// replaced, `None` without a history to roll back to
+    pub fn for_reorg(&self, network: &str, fork_block: U64) -> Option<Vec<TextStatement>> {
+        let params = vec![Some(network.to_string()), Some(fork_block.to_string())];
+        Some(self.rollback_sql.as_ref()?.iter().map(|sql| (sql.clone(), params.clone())).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn column(name: &str, type_: &str, default: Option<&str>) -> ProjectionColumn {
+        ProjectionColumn {
+            name: name.to_string(),
+            type_: type_.to_string(),
+            default: default.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn compiles_upsert_expressions() {
+        let projection = Projection {
+            name: "Balances".to_string(),
+            keys: vec![column("holder", "TEXT", None)],
+            columns: vec![column("balance", "NUMERIC", Some("0"))],
+            upserts: vec![],
+            history: None,
+            table_settings: None,
+        };
+        let upsert = ProjectionUpsert {
+            contract_name: "Token".to_string(),
+            event_name: "Transfer".to_string(),
+            keys: BTreeMap::from([("holder".to_string(), "$to".to_string())]),
+            set: BTreeMap::from([("balance".to_string(), "balance + $value".to_string())]),
+        };
+        let inputs = vec![
+            ABIInput {
+                indexed: Some(true),
+                name: "to".to_string(),
+                type_: "address".to_string(),
+                components: None,
+            },
+            ABIInput {
+                indexed: Some(false),
+                name: "value".to_string(),
+                type_: "uint256".to_string(),
+                components: None,
+            },
+        ];
+
+        let statements = ProjectionStatements::new("MyIndexer", &projection, &upsert, &inputs);
+        assert_eq!(
+            statements.insert_sql,
+            "INSERT INTO my_indexer_projections.balances (\"network\", \"holder\") VALUES ($1, ($3::TEXT)::TEXT) ON CONFLICT DO NOTHING"
+        );
+        assert_eq!(
+            statements.update_sql,
+            "UPDATE my_indexer_projections.balances SET \"balance\" = (balance + $4::NUMERIC)::NUMERIC, \"last_updated_block\" = $2::NUMERIC WHERE \"network\" = $1 AND \"holder\" = ($3::TEXT)::TEXT"
+        );
+        assert!(unknown_expression_params(
+            &inputs,
+            upsert.keys.values().chain(upsert.set.values())
+        )
+        .is_empty());
+        assert!(statements.for_reorg("ethereum", U64::from(100)).is_none());
+    }
+
+    #[test]
+    fn rolls_back_projections_with_history() {
+        let projection = Projection {
+            name: "Balances".to_string(),
+            keys: vec![column("holder", "TEXT", None)],
+            columns: vec![column("balance", "NUMERIC", Some("0"))],
+            upserts: vec![],
+            history: Some(true),
+            table_settings: None,
+        };
+
+        let rollback = generate_projection_rollback_sql("MyIndexer", &projection);
+        assert_eq!(
+            rollback[0],
+            "DELETE FROM my_indexer_projections.balances_history WHERE \"network\" = $1 AND \"last_updated_block\" >= $2::NUMERIC"
+        );
+        assert!(rollback[1].starts_with(
+            "UPDATE my_indexer_projections.balances AS t SET \"balance\" = h.\"balance\", \"last_updated_block\" = h.\"last_updated_block\""
+        ));
+        assert!(rollback[1].contains("SELECT DISTINCT ON (\"network\", \"holder\") * FROM my_indexer_projections.balances_history"));
+        assert!(
+            rollback[1].ends_with("t.\"network\" = h.\"network\" AND t.\"holder\" = h.\"holder\"")
+        );
+        assert_eq!(
+            rollback[2],
+            "DELETE FROM my_indexer_projections.balances AS t WHERE t.\"network\" = $1 AND t.\"last_updated_block\" >= $2::NUMERIC"
+        );
+    }
+}
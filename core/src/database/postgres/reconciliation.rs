@@ -0,0 +1,556 @@
+use std::{path::Path, str::FromStr, sync::Arc};
+
+use rust_decimal::Decimal;
+use tokio::time::Instant;
+use tokio_postgres::types::ToSql;
+use tracing::{error, info};
+
+use crate::{
+    abi::{get_abi_item_or_injected_with_db_map, ABIItem, ReadAbiError},
+    chat::ChatClients,
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        generate::{contract_networks, generate_event_table_full_names},
+        internal_schema::internal_schema,
+        projection::projection_table_name,
+    },
+    helpers::camel_to_snake,
+    manifest::{
+        contract::Contract,
+        core::Manifest,
+        reconciliation::{Reconciliation, ReconciliationTerm},
+        storage::PostgresLayout,
+    },
+};
+
+/// Every this many checks the event tables are summed in full again, so logs removed from a
+/// reorg after they were summed are picked up
+const FULL_CHECK_EVERY: u32 = 12;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ReconciliationError {
+    #[error("every {0} is not a number with a s, m, h, d or w suffix")]
+    InvalidEvery(String),
+
+    #[error("tolerance {0} is not a number")]
+    InvalidTolerance(String),
+
+    #[error("left and right both need at least one term")]
+    MissingTerms,
+
+    #[error("a term needs a contract_name and event_name or a projection")]
+    InvalidTerm,
+
+    #[error("contract {0} not found")]
+    ContractNotFound(String),
+
+    #[error("Could not read the ABI of contract {0}: {1}")]
+    ReadAbi(String, ReadAbiError),
+
+    #[error("event {1} not found in the ABI of contract {0}")]
+    EventNotFound(String, String),
+
+    #[error("event {0} has no input {1}")]
+    EventInputNotFound(String, String),
+
+    #[error("input {1} of event {0} is a {2} so can not be summed")]
+    EventInputNotNumeric(String, String, String),
+
+    #[error("projection {0} not found")]
+    ProjectionNotFound(String),
+
+    #[error("projection {0} has no column {1}")]
+    ProjectionColumnNotFound(String, String),
+}
+
+/// What a term adds to its side of the check
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReconciliationPart {
+    /// Projections are updated in place so they are summed in full on every check
+    Projection { sql: String, negate: bool },
+    /// Event tables only grow so a check only sums the rows of one network synced since the
+    /// previous check, up to the block the checkpoint says is complete
+    Event { sql: String, checkpoint_sql: String, network: String, negate: bool },
+}
+
+/// The statements one reconciliation runs, `compare_sql` takes the value of every part in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconciliationSql {
+    pub left: Vec<ReconciliationPart>,
+    pub right: Vec<ReconciliationPart>,
+    pub compare_sql: String,
+}
+
+fn sum_sql(column: Option<&str>) -> String {
+    match column {
+        Some(column) => format!("COALESCE(SUM(\"{}\"::NUMERIC), 0)", column),
+        None => "COUNT(*)".to_string(),
+    }
+}
+
+/// The event table column summed, it has to be a numeric input or injected parameter
+fn event_sum_column(
+    contract: &Contract,
+    abi_items: &[ABIItem],
+    event_name: &str,
+    sum: &str,
+) -> Result<String, ReconciliationError> {
+    let not_found =
+        || ReconciliationError::EventInputNotFound(event_name.to_string(), sum.to_string());
+    let event =
+        abi_items.iter().find(|item| item.name == event_name && item.type_ == "event").ok_or_else(
+            || ReconciliationError::EventNotFound(contract.name.clone(), event_name.to_string()),
+        )?;
+    let parameter = get_abi_item_or_injected_with_db_map(abi_items, event_name, sum)
+        .map_err(|_| not_found())?;
+
+    let input_name = sum.split('.').next().unwrap_or(sum);
+    let stored_as_jsonb =
+        contract.jsonb_inputs(event_name, &event.inputs).iter().any(|name| name == input_name);
+    let type_ = &parameter.abi_item.type_;
+    if stored_as_jsonb ||
+        parameter.abi_item.is_hashed_topic() ||
+        !(type_.starts_with("uint") || type_.starts_with("int")) ||
+        type_.ends_with(']')
+    {
+        return Err(ReconciliationError::EventInputNotNumeric(
+            event_name.to_string(),
+            sum.to_string(),
+            if stored_as_jsonb { "jsonb".to_string() } else { type_.clone() },
+        ));
+    }
+
+    Ok(parameter.db_column_name)
+}
+
+fn term_parts(
+    manifest: &Manifest,
+    project_path: &Path,
+    term: &ReconciliationTerm,
+) -> Result<Vec<ReconciliationPart>, ReconciliationError> {
+    let negate = term.negate.unwrap_or_default();
+    match (&term.projection, &term.contract_name, &term.event_name) {
+        (Some(projection_name), None, None) => {
+            let projection = manifest
+                .storage
+                .postgres_projections()
+                .iter()
+                .find(|projection| projection.name == *projection_name)
+                .ok_or_else(|| ReconciliationError::ProjectionNotFound(projection_name.clone()))?;
+            if let Some(sum) = &term.sum {
+                if !projection.keys.iter().chain(&projection.columns).any(|c| c.name == *sum) {
+                    return Err(ReconciliationError::ProjectionColumnNotFound(
+                        projection_name.clone(),
+                        sum.clone(),
+                    ));
+                }
+            }
+
+            Ok(vec![ReconciliationPart::Projection {
+                sql: format!(
+                    "SELECT {}::TEXT FROM {}",
+                    sum_sql(term.sum.as_deref()),
+                    projection_table_name(&manifest.name, projection_name)
+                ),
+                negate,
+            }])
+        }
+        (None, Some(contract_name), Some(event_name)) => {
+            let contract = manifest
+                .contracts
+                .iter()
+                .find(|contract| contract.name == *contract_name)
+                .ok_or_else(|| ReconciliationError::ContractNotFound(contract_name.clone()))?;
+            let abi_items = ABIItem::read_abi_items(project_path, contract)
+                .map_err(|e| ReconciliationError::ReadAbi(contract_name.clone(), e))?;
+            if !abi_items.iter().any(|item| item.name == *event_name && item.type_ == "event") {
+                return Err(ReconciliationError::EventNotFound(
+                    contract_name.clone(),
+                    event_name.clone(),
+                ));
+            }
+            let column = term
+                .sum
+                .as_deref()
+                .map(|sum| event_sum_column(contract, &abi_items, event_name, sum))
+                .transpose()?;
+
+            let contract_table_name = contract.before_modify_name_if_filter_readonly();
+            let layout = manifest.storage.postgres_layout();
+            let tables = generate_event_table_full_names(
+                layout,
+                &manifest.name,
+                contract,
+                &contract_table_name,
+                event_name,
+            );
+            let networks = contract_networks(contract);
+            let tables = match layout {
+                PostgresLayout::Shared => {
+                    networks.iter().map(|network| (tables[0].clone(), *network)).collect()
+                }
+                PostgresLayout::SchemaPerNetwork => {
+                    tables.into_iter().zip(networks).collect::<Vec<_>>()
+                }
+            };
+            let checkpoint_sql = format!(
+                "SELECT last_synced_block::TEXT FROM {}.{}_{}_{} WHERE network = $1",
+                internal_schema(),
+                camel_to_snake(&manifest.name),
+                camel_to_snake(&contract_table_name),
+                camel_to_snake(event_name)
+            );
+
+            Ok(tables
+                .into_iter()
+                .map(|(table, network)| ReconciliationPart::Event {
+                    sql: format!(
+                        "SELECT ($1::TEXT::NUMERIC + {})::TEXT FROM {} WHERE network = $2 \
+                        AND block_number > $3::TEXT::NUMERIC AND block_number <= $4::TEXT::NUMERIC",
+                        sum_sql(column.as_deref()),
+                        table
+                    ),
+                    checkpoint_sql: checkpoint_sql.clone(),
+                    network: network.to_string(),
+                    negate,
+                })
+                .collect())
+        }
+        _ => Err(ReconciliationError::InvalidTerm),
+    }
+}
+
+fn side_parts(
+    manifest: &Manifest,
+    project_path: &Path,
+    terms: &[ReconciliationTerm],
+) -> Result<Vec<ReconciliationPart>, ReconciliationError> {
+    let mut parts = vec![];
+    for term in terms {
+        parts.extend(term_parts(manifest, project_path, term)?);
+    }
+
+    Ok(parts)
+}
+
+/// Adds up the values of the parts passed as parameters from `first_parameter` on
+fn side_sql(parts: &[ReconciliationPart], first_parameter: usize) -> String {
+    let mut sql = "0".to_string();
+    for (i, part) in parts.iter().enumerate() {
+        let negate = match part {
+            ReconciliationPart::Projection { negate, .. } |
+            ReconciliationPart::Event { negate, .. } => *negate,
+        };
+        sql.push_str(&format!(
+            " {} ${}::TEXT::NUMERIC",
+            if negate { "-" } else { "+" },
+            first_parameter + i
+        ));
+    }
+
+    sql
+}
+
+/// The parts of both sides and the statement which selects both sides as text and whether they
+/// are within the tolerance of each other
+pub fn generate_reconciliation_sql(
+    manifest: &Manifest,
+    project_path: &Path,
+    reconciliation: &Reconciliation,
+) -> Result<ReconciliationSql, ReconciliationError> {
+    if reconciliation.left.is_empty() || reconciliation.right.is_empty() {
+        return Err(ReconciliationError::MissingTerms);
+    }
+    let tolerance = Decimal::from_str(reconciliation.tolerance()).map_err(|_| {
+        ReconciliationError::InvalidTolerance(reconciliation.tolerance().to_string())
+    })?;
+
+    let left = side_parts(manifest, project_path, &reconciliation.left)?;
+    let right = side_parts(manifest, project_path, &reconciliation.right)?;
+    let compare_sql = format!(
+        "SELECT left_value::TEXT, right_value::TEXT, ABS(left_value - right_value) <= {}::NUMERIC \
+        FROM (SELECT ({}) AS left_value, ({}) AS right_value) AS sides",
+        tolerance,
+        side_sql(&left, 1),
+        side_sql(&right, left.len() + 1)
+    );
+
+    Ok(ReconciliationSql { left, right, compare_sql })
+}
+
+/// The running sum of an event part and the block it is summed up to, projection parts leave it
+/// untouched
+#[derive(Clone)]
+struct EventPartState {
+    summed_to: u64,
+    total: String,
+}
+
+impl Default for EventPartState {
+    fn default() -> Self {
+        EventPartState { summed_to: 0, total: "0".to_string() }
+    }
+}
+
+struct ReconciliationCheck {
+    name: String,
+    sql: ReconciliationSql,
+    chat_clients: Option<ChatClients>,
+    part_states: Vec<EventPartState>,
+    checks: u32,
+}
+
+impl ReconciliationCheck {
+    async fn notify(&self, message: &str) {
+        if let Some(chat_clients) = &self.chat_clients {
+            if let Err(e) = chat_clients.send_notification(message).await {
+                error!("Reconciliation {} could not send a chat message: {}", self.name, e);
+            }
+        }
+    }
+
+    /// The value of every part in order, event parts only sum what was synced since last time
+    async fn part_values(
+        &mut self,
+        postgres: &PostgresClient,
+    ) -> Result<Vec<String>, PostgresError> {
+        if self.checks.is_multiple_of(FULL_CHECK_EVERY) {
+            self.part_states.iter_mut().for_each(|state| *state = EventPartState::default());
+        }
+        self.checks = self.checks.wrapping_add(1);
+
+        let parts = self.sql.left.iter().chain(&self.sql.right);
+        let mut values = vec![];
+        for (part, state) in parts.zip(self.part_states.iter_mut()) {
+            match part {
+                ReconciliationPart::Projection { sql, .. } => {
+                    values.push(postgres.query_one(sql, &[]).await?.get(0));
+                }
+                ReconciliationPart::Event { sql, checkpoint_sql, network, .. } => {
+                    let synced_to = postgres
+                        .query_one_or_none(checkpoint_sql, &[network])
+                        .await?
+                        .and_then(|row| row.get::<_, String>(0).parse::<u64>().ok())
+                        .unwrap_or_default();
+                    // the checkpoint moves back when a resync drops the data
+                    if synced_to < state.summed_to {
+                        *state = EventPartState::default();
+                    }
+                    if synced_to > state.summed_to {
+                        let row = postgres
+                            .query_one(
+                                sql,
+                                &[
+                                    &state.total,
+                                    network,
+                                    &state.summed_to.to_string(),
+                                    &synced_to.to_string(),
+                                ],
+                            )
+                            .await?;
+                        state.total = row.get(0);
+                        state.summed_to = synced_to;
+                    }
+                    values.push(state.total.clone());
+                }
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Only changes are reported so a violation is not sent again on every check
+    async fn run(&mut self, postgres: &PostgresClient, violated: &mut bool) {
+        let result = match self.part_values(postgres).await {
+            Ok(values) => {
+                let params: Vec<&(dyn ToSql + Sync)> =
+                    values.iter().map(|value| value as &(dyn ToSql + Sync)).collect();
+                postgres.query_one(&self.sql.compare_sql, &params).await
+            }
+            Err(e) => Err(e),
+        };
+        let row = match result {
+            Ok(row) => row,
+            Err(e) => {
+                // sums which may have been half updated are summed again in full
+                self.checks = 0;
+                error!("Reconciliation {} could not run: {}", self.name, e);
+                return;
+            }
+        };
+        let left: String = row.get(0);
+        let right: String = row.get(1);
+        let reconciled: bool = row.get(2);
+
+        if !reconciled && !*violated {
+            let message = format!(
+                "Reconciliation {} failed: left is {} but right is {}",
+                self.name, left, right
+            );
+            error!("{}", message);
+            self.notify(&message).await;
+        } else if reconciled && *violated {
+            let message =
+                format!("Reconciliation {} passes again: both sides are {}", self.name, left);
+            info!("{}", message);
+            self.notify(&message).await;
+        }
+        *violated = !reconciled;
+    }
+}
+
+/// Starts a task per reconciliation which checks it on its interval
+pub async fn start_reconciliation_checks(
+    manifest: &Manifest,
+    project_path: &Path,
+    postgres: Arc<PostgresClient>,
+) -> Result<(), ReconciliationError> {
+    for reconciliation in manifest.storage.postgres_reconciliations() {
+        let every = reconciliation
+            .every()
+            .ok_or_else(|| ReconciliationError::InvalidEvery(reconciliation.every.clone()))?;
+        let sql = generate_reconciliation_sql(manifest, project_path, reconciliation)?;
+        let mut check = ReconciliationCheck {
+            name: reconciliation.name.clone(),
+            part_states: vec![EventPartState::default(); sql.left.len() + sql.right.len()],
+            sql,
+            chat_clients: match &reconciliation.chat {
+                Some(chat) => Some(ChatClients::new(chat.clone()).await),
+                None => None,
+            },
+            checks: 0,
+        };
+
+        info!("Checking reconciliation {} every {}", check.name, reconciliation.every);
+        let postgres = Arc::clone(&postgres);
+        tokio::spawn(async move {
+            // the first check waits so the tables have had a chance to sync
+            let mut interval = tokio::time::interval_at(Instant::now() + every, every);
+            let mut violated = false;
+            loop {
+                interval.tick().await;
+                check.run(&postgres, &mut violated).await;
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(project_path: &Path) -> Manifest {
+        std::fs::create_dir_all(project_path.join("abis")).unwrap();
+        std::fs::write(
+            project_path.join("abis/Vault.json"),
+            r#"[{"type":"event","name":"Deposit","anonymous":false,"inputs":[
+                {"name":"owner","type":"address","indexed":true},
+                {"name":"memo","type":"string","indexed":true},
+                {"name":"assets","type":"uint256","indexed":false}]}]"#,
+        )
+        .unwrap();
+
+        serde_yaml::from_str(
+            r#"
+name: Vaults
+project_type: no-code
+networks: []
+storage:
+  postgres:
+    enabled: true
+    projections:
+      - name: tvl
+        keys:
+          - name: vault
+            type: TEXT
+        columns:
+          - name: total
+            type: NUMERIC
+        upserts: []
+contracts:
+  - name: Vault
+    abi: ./abis/Vault.json
+    details:
+      - network: ethereum
+        address: "0x0000000000000000000000000000000000000001"
+      - network: base
+        address: "0x0000000000000000000000000000000000000001"
+"#,
+        )
+        .unwrap()
+    }
+
+    fn reconciliation(sum: &str) -> Reconciliation {
+        serde_yaml::from_str(&format!(
+            r#"
+name: tvl
+tolerance: "1"
+left:
+  - contract_name: Vault
+    event_name: Deposit
+    sum: {}
+right:
+  - projection: tvl
+    sum: total
+    negate: true
+"#,
+            sum
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_generate_reconciliation_sql() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = manifest(dir.path());
+
+        let sql =
+            generate_reconciliation_sql(&manifest, dir.path(), &reconciliation("assets")).unwrap();
+        assert_eq!(sql.left.len(), 2);
+        assert_eq!(
+            sql.left[1],
+            ReconciliationPart::Event {
+                sql: "SELECT ($1::TEXT::NUMERIC + COALESCE(SUM(\"assets\"::NUMERIC), 0))::TEXT \
+                    FROM vaults_vault.deposit WHERE network = $2 \
+                    AND block_number > $3::TEXT::NUMERIC AND block_number <= $4::TEXT::NUMERIC"
+                    .to_string(),
+                checkpoint_sql: format!(
+                    "SELECT last_synced_block::TEXT FROM {}.vaults_vault_deposit WHERE network = $1",
+                    internal_schema()
+                ),
+                network: "base".to_string(),
+                negate: false,
+            }
+        );
+        assert_eq!(
+            sql.right,
+            vec![ReconciliationPart::Projection {
+                sql:
+                    "SELECT COALESCE(SUM(\"total\"::NUMERIC), 0)::TEXT FROM vaults_projections.tvl"
+                        .to_string(),
+                negate: true,
+            }]
+        );
+        assert_eq!(
+            sql.compare_sql,
+            "SELECT left_value::TEXT, right_value::TEXT, ABS(left_value - right_value) <= 1::NUMERIC \
+            FROM (SELECT (0 + $1::TEXT::NUMERIC + $2::TEXT::NUMERIC) AS left_value, \
+            (0 - $3::TEXT::NUMERIC) AS right_value) AS sides"
+        );
+
+        assert!(matches!(
+            generate_reconciliation_sql(&manifest, dir.path(), &reconciliation("shares")),
+            Err(ReconciliationError::EventInputNotFound(..))
+        ));
+        assert!(matches!(
+            generate_reconciliation_sql(&manifest, dir.path(), &reconciliation("owner")),
+            Err(ReconciliationError::EventInputNotNumeric(..))
+        ));
+        assert!(matches!(
+            generate_reconciliation_sql(&manifest, dir.path(), &reconciliation("memo")),
+            Err(ReconciliationError::EventInputNotNumeric(..))
+        ));
+    }
+}
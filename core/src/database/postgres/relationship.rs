@@ -1,13 +1,31 @@
+#![allow(clippy::double_ended_iterator_last)]
+
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
 use crate::{
-    abi::{get_abi_item_with_db_map, ABIInput, ABIItem, GetAbiItemWithDbMapError, ReadAbiError},
-    database::postgres::client::{PostgresClient, PostgresConnectionError, PostgresError},
+    abi::{
+        get_abi_item_or_injected_with_db_map, ABIInput, ABIItem, GetAbiItemWithDbMapError,
+        ReadAbiError,
+    },
+    database::postgres::{
+        client::{PostgresClient, PostgresConnectionError, PostgresError},
+        generate::{
+            contract_networks, generate_event_table_full_name,
+            generate_network_event_table_full_name,
+        },
+        internal_schema::internal_schema,
+    },
     helpers::camel_to_snake,
-    manifest::{contract::Contract, storage::ForeignKeys},
+    manifest::{
+        contract::Contract,
+        storage::{
+            ApplyPolicy, ForeignKeyOnDelete, ForeignKeyValidation, ForeignKeys, PostgresLayout,
+            Storage,
+        },
+    },
     types::code::Code,
 };
 
@@ -44,11 +62,11 @@ pub struct LinkTo {
 
     pub event: String,
 
-    pub abi_input: ABIInput,
+    pub abi_inputs: Vec<ABIInput>,
 
     pub db_table_name: String,
 
-    pub db_table_column: String,
+    pub db_table_columns: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -57,13 +75,28 @@ pub struct Relationship {
 
     pub event: String,
 
-    pub abi_input: ABIInput,
+    pub abi_inputs: Vec<ABIInput>,
 
     pub db_table_name: String,
 
-    pub db_table_column: String,
+    pub db_table_columns: Vec<String>,
 
     pub linked_to: LinkTo,
+
+    /// The view pre-joining the linked table, lives in the same schema as `db_table_name`
+    pub view_name: String,
+
+    #[serde(default)]
+    pub apply: ApplyPolicy,
+
+    #[serde(default)]
+    pub on_delete: ForeignKeyOnDelete,
+
+    #[serde(default)]
+    pub deferrable: bool,
+
+    #[serde(default)]
+    pub validation: ForeignKeyValidation,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -85,21 +118,57 @@ impl Relationship {
         false
     }
 
+    pub fn with_apply_policy(
+        relationships: &[Relationship],
+        apply: ApplyPolicy,
+    ) -> Vec<Relationship> {
+        relationships.iter().filter(|relationship| relationship.apply == apply).cloned().collect()
+    }
+
+    // constraints have no IF NOT EXISTS, so only add it if it's not already applied
     fn apply_foreign_key_construct_sql(&self) -> Code {
         Code::new(format!(
             r#"
-                ALTER TABLE {db_table_name}
-                ADD CONSTRAINT {foreign_key_construct_name}
-                FOREIGN KEY ({db_table_column}) REFERENCES {linked_db_table_name}({linked_db_table_column});
+            DO $$
+            BEGIN
+                IF NOT EXISTS (
+                    SELECT 1
+                    FROM pg_constraint
+                    WHERE conname = '{foreign_key_construct_name}'
+                    AND conrelid = '{db_table_name}'::regclass
+                ) THEN
+                    ALTER TABLE {db_table_name}
+                    ADD CONSTRAINT {foreign_key_construct_name}
+                    FOREIGN KEY ({db_table_column}) REFERENCES {linked_db_table_name}({linked_db_table_column})
+                    ON DELETE {on_delete}{deferrable}{not_valid};
+                END IF;
+            END $$;
             "#,
             foreign_key_construct_name = self.foreign_key_construct_name(),
             db_table_name = self.db_table_name,
-            db_table_column = self.db_table_column,
+            db_table_column = self.db_table_columns.join(", "),
             linked_db_table_name = self.linked_to.db_table_name,
-            linked_db_table_column = self.linked_to.db_table_column
+            linked_db_table_column = self.linked_to.db_table_columns.join(", "),
+            on_delete = self.on_delete.as_sql(),
+            deferrable = if self.deferrable { " DEFERRABLE INITIALLY DEFERRED" } else { "" },
+            not_valid =
+                if self.validation == ForeignKeyValidation::Immediate { "" } else { " NOT VALID" },
         ))
     }
 
+    /// Checks the rows which existed before a `NOT VALID` constraint was added
+    fn validate_foreign_key_construct_sql(&self) -> Option<Code> {
+        if self.validation != ForeignKeyValidation::NotValidThenValidate {
+            return None;
+        }
+
+        Some(Code::new(format!(
+            "ALTER TABLE {} VALIDATE CONSTRAINT {};",
+            self.db_table_name,
+            self.foreign_key_construct_name()
+        )))
+    }
+
     fn drop_foreign_key_construct_sql(&self) -> Code {
         Code::new(format!(
             r#"
@@ -117,9 +186,9 @@ impl Relationship {
             linked_db_table_name =
                 self.linked_to.db_table_name.split('.').last().unwrap_or_else(|| panic!(
                     "Failed to split and then get schema for table: {}",
-                    self.linked_to.db_table_column
+                    self.linked_to.db_table_name
                 )),
-            linked_db_table_column = self.linked_to.db_table_column
+            linked_db_table_column = self.linked_to.db_table_columns.join("_")
         )
     }
 
@@ -144,7 +213,7 @@ impl Relationship {
         "#,
             unique_construct_name = self.unique_construct_name(),
             linked_db_table_name = self.linked_to.db_table_name,
-            linked_db_table_column = self.linked_to.db_table_column
+            linked_db_table_column = self.linked_to.db_table_columns.join(", ")
         ))
     }
 
@@ -165,9 +234,9 @@ impl Relationship {
             linked_db_table_name =
                 self.linked_to.db_table_name.split('.').last().unwrap_or_else(|| panic!(
                     "Failed to split and then get schema for table: {}",
-                    self.linked_to.db_table_column
+                    self.linked_to.db_table_name
                 )),
-            linked_db_table_column = self.linked_to.db_table_column
+            linked_db_table_column = self.linked_to.db_table_columns.join("_")
         )
     }
 
@@ -175,12 +244,12 @@ impl Relationship {
         // CONCURRENTLY is used to avoid locking the table for writes
         Code::new(format!(
             r#"
-                CREATE INDEX CONCURRENTLY {index_name}
+                CREATE INDEX CONCURRENTLY IF NOT EXISTS {index_name}
                 ON {db_table_name} ({db_table_column});
             "#,
             index_name = self.index_name(),
             db_table_name = self.db_table_name,
-            db_table_column = self.db_table_column,
+            db_table_column = self.db_table_columns.join(", "),
         ))
     }
 
@@ -191,7 +260,7 @@ impl Relationship {
             // get schema else drop won't work
             self.db_table_name.split('.').next().unwrap_or_else(|| panic!(
                 "Failed to split and then get schema for table: {}",
-                self.db_table_column
+                self.db_table_name
             )),
             self.index_name(),
         ))
@@ -240,34 +309,47 @@ impl Relationship {
             "idx_{db_table_name}_{db_table_column}",
             db_table_name = self.db_table_name.split('.').last().unwrap_or_else(|| panic!(
                 "Failed to split and then get schema for table: {}",
-                self.db_table_column
+                self.db_table_name
             )),
-            db_table_column = self.db_table_column,
+            db_table_column = self.db_table_columns.join("_"),
         )
     }
 
     pub async fn apply(&self, client: &PostgresClient) -> Result<(), PostgresError> {
         // apply on its own as it's in a DO block
-        client.execute(self.apply_unique_construct_sql().as_str(), &[]).await?;
+        client
+            .execute_ddl(self.apply_unique_construct_sql().as_str(), "apply a relationship")
+            .await?;
         info!(
-            "Applied unique constraint key for relationship after historic resync complete: table - {} constraint - {}",
+            "Applied unique constraint key for relationship: table - {} constraint - {}",
             self.linked_to.db_table_name,
             self.unique_construct_name()
         );
 
-        client.execute(self.apply_foreign_key_construct_sql().as_str(), &[]).await?;
+        client
+            .execute_ddl(self.apply_foreign_key_construct_sql().as_str(), "apply a relationship")
+            .await?;
 
         info!(
-            "Applied foreign key for relationship after historic resync complete: table - {} constraint - {}",
+            "Applied foreign key for relationship: table - {} constraint - {}",
             self.db_table_name,
             self.foreign_key_construct_name()
         );
 
+        if let Some(validate_sql) = self.validate_foreign_key_construct_sql() {
+            client.execute_ddl(validate_sql.as_str(), "validate a relationship").await?;
+            info!(
+                "Validated foreign key for relationship: table - {} constraint - {}",
+                self.db_table_name,
+                self.foreign_key_construct_name()
+            );
+        }
+
         // CONCURRENTLY is used to avoid locking the table for writes
-        client.execute(&self.apply_index_sql().to_string(), &[]).await?;
+        client.execute_ddl(&self.apply_index_sql().to_string(), "index a relationship").await?;
 
         info!(
-            "Applied index for relationship after historic resync complete: table - {} index - {}",
+            "Applied index for relationship: table - {} index - {}",
             self.db_table_name,
             self.index_name()
         );
@@ -275,12 +357,16 @@ impl Relationship {
         Ok(())
     }
 
-    pub async fn apply_all(relationships: &Vec<Relationship>) -> Result<(), ApplyAllRelationships> {
+    pub async fn apply_all(
+        storage: &Storage,
+        manifest_name: &str,
+        relationships: &Vec<Relationship>,
+    ) -> Result<(), ApplyAllRelationships> {
         if relationships.is_empty() {
             return Ok(());
         }
 
-        let client = PostgresClient::new().await?;
+        let client = PostgresClient::from_storage(storage, manifest_name).await?;
 
         for relationship in relationships {
             relationship.apply(&client).await?;
@@ -307,8 +393,9 @@ async fn get_last_known_relationships_dropping_sql(
         .query_one_or_none(
             &format!(
                 r#"
-                    SELECT value FROM rindexer_internal.{}_last_known_relationship_dropping_sql WHERE key = 1
+                    SELECT value FROM {}.{}_last_known_relationship_dropping_sql WHERE key = 1
                 "#,
+                internal_schema(),
                 camel_to_snake(manifest_name)
             ),
             &[],
@@ -337,9 +424,10 @@ pub enum DropLastKnownRelationshipsError {
 }
 
 pub async fn drop_last_known_relationships(
+    storage: &Storage,
     manifest_name: &str,
 ) -> Result<(), DropLastKnownRelationshipsError> {
-    let client = PostgresClient::new().await?;
+    let client = PostgresClient::from_storage(storage, manifest_name).await?;
 
     // people can edit the relationships, so we have to drop old stuff
     // we save all drops in the database, so we can drop them all at once
@@ -347,20 +435,21 @@ pub async fn drop_last_known_relationships(
     let last_known_relationships_dropping_sql =
         get_last_known_relationships_dropping_sql(&client, manifest_name).await?;
     for drop_sql in last_known_relationships_dropping_sql {
-        client.batch_execute(drop_sql.as_str()).await?;
+        client.execute_ddl(drop_sql.as_str(), "drop the last known relationships").await?;
     }
 
     Ok(())
 }
 
-pub async fn create_relationships(
+/// Resolves the manifest relationships to the tables and columns they link
+pub fn build_relationships(
     project_path: &Path,
     manifest_name: &str,
     contracts: &[Contract],
     foreign_keys: &[ForeignKeys],
+    layout: PostgresLayout,
 ) -> Result<Vec<Relationship>, CreateRelationshipError> {
     let mut relationships = vec![];
-    let mut dropping_sql: Vec<Code> = vec![];
     for foreign_key in foreign_keys {
         let contract = contracts.iter().find(|c| c.name == foreign_key.contract_name);
 
@@ -375,13 +464,17 @@ pub async fn create_relationships(
                 let abi_items = ABIItem::read_abi_items(project_path, contract)?;
 
                 for linked_key in &foreign_key.foreign_keys {
-                    let parameter_mapping =
-                        foreign_key.event_input_name.split('.').collect::<Vec<&str>>();
-                    let abi_parameter = get_abi_item_with_db_map(
-                        &abi_items,
-                        &foreign_key.event_name,
-                        &parameter_mapping,
-                    )?;
+                    let abi_parameters = foreign_key
+                        .input_names()
+                        .iter()
+                        .map(|name| {
+                            get_abi_item_or_injected_with_db_map(
+                                &abi_items,
+                                &foreign_key.event_name,
+                                name,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
 
                     let linked_key_contract = contracts
                         .iter()
@@ -395,72 +488,161 @@ pub async fn create_relationships(
 
                     let linked_abi_items =
                         ABIItem::read_abi_items(project_path, linked_key_contract)?;
-                    let linked_parameter_mapping =
-                        linked_key.event_input_name.split('.').collect::<Vec<&str>>();
-                    let linked_abi_parameter = get_abi_item_with_db_map(
-                        &linked_abi_items,
-                        &linked_key.event_name,
-                        &linked_parameter_mapping,
-                    )?;
-
-                    if abi_parameter.abi_item.type_ != linked_abi_parameter.abi_item.type_ {
+                    let linked_abi_parameters = linked_key
+                        .input_names()
+                        .iter()
+                        .map(|name| {
+                            get_abi_item_or_injected_with_db_map(
+                                &linked_abi_items,
+                                &linked_key.event_name,
+                                name,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    if abi_parameters.len() != linked_abi_parameters.len() {
                         return Err(CreateRelationshipError::TypeMismatch(format!(
-                            "Type mismatch between {}.{} ({}) and {}.{} ({})",
+                            "{}.{} links {} inputs to {} inputs on {}.{}",
                             &foreign_key.contract_name,
-                            &foreign_key.event_input_name,
-                            &abi_parameter.abi_item.type_,
+                            &foreign_key.event_name,
+                            abi_parameters.len(),
+                            linked_abi_parameters.len(),
                             &linked_key.contract_name,
-                            &linked_key.event_input_name,
-                            &linked_abi_parameter.abi_item.type_
+                            &linked_key.event_name
                         )));
                     }
+                    for (abi_parameter, linked_abi_parameter) in
+                        abi_parameters.iter().zip(&linked_abi_parameters)
+                    {
+                        if abi_parameter.abi_item.type_ != linked_abi_parameter.abi_item.type_ {
+                            return Err(CreateRelationshipError::TypeMismatch(format!(
+                                "Type mismatch between {}.{} ({}) and {}.{} ({})",
+                                &foreign_key.contract_name,
+                                &abi_parameter.abi_item.name,
+                                &abi_parameter.abi_item.type_,
+                                &linked_key.contract_name,
+                                &linked_abi_parameter.abi_item.name,
+                                &linked_abi_parameter.abi_item.type_
+                            )));
+                        }
+                    }
 
-                    let relationship = Relationship {
-                        contract_name: foreign_key.contract_name.clone(),
-                        event: foreign_key.event_name.clone(),
-                        db_table_column: camel_to_snake(&abi_parameter.db_column_name),
-                        db_table_name: format!(
-                            "{}_{}.{}",
-                            camel_to_snake(manifest_name),
-                            camel_to_snake(&contract.name),
-                            camel_to_snake(&foreign_key.event_name)
-                        ),
-                        abi_input: abi_parameter.abi_item,
-                        linked_to: LinkTo {
-                            contract_name: linked_key.contract_name.clone(),
-                            event: linked_key.event_name.clone(),
-                            db_table_column: camel_to_snake(&linked_abi_parameter.db_column_name),
-                            db_table_name: format!(
-                                "{}_{}.{}",
-                                camel_to_snake(manifest_name),
-                                camel_to_snake(&linked_key_contract.name),
-                                camel_to_snake(&linked_key.event_name)
+                    // with the schema per network layout tables only link within the same network
+                    let table_pairs: Vec<(String, String)> = match layout {
+                        PostgresLayout::Shared => vec![(
+                            generate_event_table_full_name(
+                                manifest_name,
+                                &contract.name,
+                                &foreign_key.event_name,
+                            ),
+                            generate_event_table_full_name(
+                                manifest_name,
+                                &linked_key_contract.name,
+                                &linked_key.event_name,
                             ),
-                            abi_input: linked_abi_parameter.abi_item,
-                        },
+                        )],
+                        PostgresLayout::SchemaPerNetwork => {
+                            let linked_networks = contract_networks(linked_key_contract);
+                            contract_networks(contract)
+                                .into_iter()
+                                .filter(|network| linked_networks.contains(network))
+                                .map(|network| {
+                                    (
+                                        generate_network_event_table_full_name(
+                                            network,
+                                            &contract.name,
+                                            &foreign_key.event_name,
+                                        ),
+                                        generate_network_event_table_full_name(
+                                            network,
+                                            &linked_key_contract.name,
+                                            &linked_key.event_name,
+                                        ),
+                                    )
+                                })
+                                .collect()
+                        }
                     };
 
-                    let sql = relationship.drop_sql().await?;
-                    dropping_sql.extend(sql);
-                    relationships.push(relationship);
+                    for (db_table_name, linked_db_table_name) in table_pairs {
+                        let view_name = linked_key.view_name.clone().unwrap_or_else(|| {
+                            format!(
+                                "{}_with_{}",
+                                camel_to_snake(&foreign_key.event_name),
+                                camel_to_snake(&linked_key.event_name)
+                            )
+                        });
+                        relationships.push(Relationship {
+                            contract_name: foreign_key.contract_name.clone(),
+                            event: foreign_key.event_name.clone(),
+                            db_table_columns: abi_parameters
+                                .iter()
+                                .map(|parameter| camel_to_snake(&parameter.db_column_name))
+                                .collect(),
+                            db_table_name,
+                            abi_inputs: abi_parameters
+                                .iter()
+                                .map(|parameter| parameter.abi_item.clone())
+                                .collect(),
+                            linked_to: LinkTo {
+                                contract_name: linked_key.contract_name.clone(),
+                                event: linked_key.event_name.clone(),
+                                db_table_columns: linked_abi_parameters
+                                    .iter()
+                                    .map(|parameter| camel_to_snake(&parameter.db_column_name))
+                                    .collect(),
+                                db_table_name: linked_db_table_name,
+                                abi_inputs: linked_abi_parameters
+                                    .iter()
+                                    .map(|parameter| parameter.abi_item.clone())
+                                    .collect(),
+                            },
+                            view_name,
+                            apply: linked_key.apply.unwrap_or_default(),
+                            on_delete: linked_key.on_delete.unwrap_or_default(),
+                            deferrable: linked_key.deferrable.unwrap_or_default(),
+                            validation: linked_key.validation.unwrap_or_default(),
+                        });
+                    }
                 }
             }
         }
     }
 
+    Ok(relationships)
+}
+
+pub async fn create_relationships(
+    storage: &Storage,
+    project_path: &Path,
+    manifest_name: &str,
+    contracts: &[Contract],
+    foreign_keys: &[ForeignKeys],
+    layout: PostgresLayout,
+) -> Result<Vec<Relationship>, CreateRelationshipError> {
+    let relationships =
+        build_relationships(project_path, manifest_name, contracts, foreign_keys, layout)?;
+
+    let mut dropping_sql: Vec<Code> = vec![];
+    // manual relationships are only ever applied by `rindexer indexes apply` so they stay
+    for relationship in relationships.iter().filter(|r| r.apply != ApplyPolicy::Manual) {
+        dropping_sql.extend(relationship.drop_sql().await?);
+    }
+
     let relationships_dropping_sql_json = serde_json::to_string(
         &dropping_sql.iter().map(|code| code.as_str()).collect::<Vec<&str>>(),
     )?;
 
     // save relationships in postgres
-    let client = PostgresClient::new().await?;
+    let client = PostgresClient::from_storage(storage, manifest_name).await?;
 
     client
         .execute(
             &format!(r#"
-                INSERT INTO rindexer_internal.{manifest_name}_last_known_relationship_dropping_sql (key, value) VALUES (1, $1)
+                INSERT INTO {schema}.{manifest_name}_last_known_relationship_dropping_sql (key, value) VALUES (1, $1)
                 ON CONFLICT (key) DO UPDATE SET value = $1;
             "#,
+                     schema = internal_schema(),
                      manifest_name = camel_to_snake(manifest_name)
             ),
             &[&relationships_dropping_sql_json],
@@ -0,0 +1,177 @@
+use ethers::abi::LogParam;
+
+use crate::{
+    abi::ABIInput,
+    database::postgres::{
+        client::TextStatement,
+        projection::{projection_table_name, EventExpressionParams},
+    },
+    event::callback_registry::TxInformation,
+    manifest::rollup::{Rollup, RollupFunction},
+    types::code::Code,
+};
+
+/// Rollups live next to the projections in the projections schema
+pub fn generate_rollup_tables_sql(indexer_name: &str, rollups: &[Rollup]) -> Code {
+    let mut sql = String::new();
+    for rollup in rollups {
+        let mut columns = vec![
+            "\"network\" TEXT NOT NULL".to_string(),
+            "\"bucket_start\" TIMESTAMPTZ NOT NULL".to_string(),
+        ];
+        columns.extend(
+            rollup
+                .group_by
+                .iter()
+                .map(|group| format!("\"{}\" {} NOT NULL", group.column.name, group.column.type_)),
+        );
+        columns.extend(
+            rollup
+                .aggregates
+                .iter()
+                .map(|aggregate| format!("\"{}\" NUMERIC NOT NULL", aggregate.name)),
+        );
+
+        let primary_key: Vec<String> = ["network", "bucket_start"]
+            .into_iter()
+            .chain(rollup.group_by.iter().map(|group| group.column.name.as_str()))
+            .map(|column| format!("\"{}\"", column))
+            .collect();
+
+        sql.push_str(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ({}, PRIMARY KEY ({}));",
+            projection_table_name(indexer_name, &rollup.name),
+            columns.join(", "),
+            primary_key.join(", ")
+        ));
+    }
+
+    Code::new(sql)
+}
+
+/// The upsert adding a single event into its bucket, the bucket start is the last parameter
+#[derive(Debug, Clone)]
+pub struct RollupStatement {
+    pub name: String,
+    sql: String,
+    /// Takes a removed event back out of its bucket, only sums and counts can be
+    retract_sql: Option<String>,
+    /// Min and max aggregates which keep the value of a removed event
+    pub irreversible: bool,
+    params: EventExpressionParams,
+    interval_seconds: u64,
+}
+
+impl RollupStatement {
+    pub fn new(indexer_name: &str, rollup: &Rollup, inputs: &[ABIInput]) -> Self {
+        let mut params = EventExpressionParams::new();
+
+        let mut columns = vec!["\"network\"".to_string()];
+        let mut values = vec!["$1".to_string()];
+        let mut conflict_columns = vec!["\"network\"".to_string(), "\"bucket_start\"".to_string()];
+        let mut group_conditions = vec![];
+        for group in &rollup.group_by {
+            let column = format!("\"{}\"", group.column.name);
+            let value = params.expression_sql(inputs, &group.value, &group.column.type_);
+            group_conditions.push(format!("r.{} = {}", column, value));
+            columns.push(column.clone());
+            values.push(value);
+            conflict_columns.push(column);
+        }
+
+        let mut updates = vec![];
+        let mut retract_updates = vec![];
+        for aggregate in &rollup.aggregates {
+            let column = format!("\"{}\"", aggregate.name);
+            let value = match (aggregate.function, &aggregate.value) {
+                (RollupFunction::Count, _) | (_, None) => "1".to_string(),
+                (_, Some(expression)) => params.expression_sql(inputs, expression, "NUMERIC"),
+            };
+            updates.push(match aggregate.function {
+                RollupFunction::Sum | RollupFunction::Count => {
+                    retract_updates.push(format!("{column} = r.{column} - {value}"));
+                    format!("{column} = r.{column} + EXCLUDED.{column}")
+                }
+                RollupFunction::Min => format!("{column} = LEAST(r.{column}, EXCLUDED.{column})"),
+                RollupFunction::Max => {
+                    format!("{column} = GREATEST(r.{column}, EXCLUDED.{column})")
+                }
+            });
+            columns.push(column);
+            values.push(value);
+        }
+
+        let bucket_start = format!("to_timestamp(${}::NUMERIC)", params.len() + 1);
+        columns.push("\"bucket_start\"".to_string());
+        values.push(bucket_start.clone());
+
+        let table_name = projection_table_name(indexer_name, &rollup.name);
+        let retract_sql = (!retract_updates.is_empty()).then(|| {
+            format!(
+                "UPDATE {} AS r SET {} WHERE r.\"network\" = $1 AND r.\"bucket_start\" = {}{}",
+                table_name,
+                retract_updates.join(", "),
+                bucket_start,
+                group_conditions
+                    .iter()
+                    .map(|condition| format!(" AND {}", condition))
+                    .collect::<String>()
+            )
+        });
+
+        let sql = format!(
+            "INSERT INTO {} AS r ({}) VALUES ({}) ON CONFLICT ({}) DO UPDATE SET {}",
+            table_name,
+            columns.join(", "),
+            values.join(", "),
+            conflict_columns.join(", "),
+            updates.join(", ")
+        );
+
+        Self {
+            name: rollup.name.clone(),
+            sql,
+            retract_sql,
+            irreversible: rollup.aggregates.iter().any(|aggregate| {
+                matches!(aggregate.function, RollupFunction::Min | RollupFunction::Max)
+            }),
+            params,
+            interval_seconds: rollup.interval_seconds().unwrap_or(86_400),
+        }
+    }
+
+    fn values(
+        &self,
+        inputs: &[ABIInput],
+        log_params: &[LogParam],
+        tx_information: &TxInformation,
+        block_timestamp: u64,
+    ) -> Vec<Option<String>> {
+        let mut values = self.params.values(inputs, log_params, tx_information);
+        let bucket_start = block_timestamp - block_timestamp % self.interval_seconds;
+        values.push(Some(bucket_start.to_string()));
+        values
+    }
+
+    pub fn for_event(
+        &self,
+        inputs: &[ABIInput],
+        log_params: &[LogParam],
+        tx_information: &TxInformation,
+        block_timestamp: u64,
+    ) -> TextStatement {
+        (self.sql.clone(), self.values(inputs, log_params, tx_information, block_timestamp))
+    }
+
+    /// Takes an event a reorg removed back out of the sums and counts of its bucket
+    pub fn for_removed_event(
+        &self,
+        inputs: &[ABIInput],
+        log_params: &[LogParam],
+        tx_information: &TxInformation,
+        block_timestamp: u64,
+    ) -> Option<TextStatement> {
+        let retract_sql = self.retract_sql.clone()?;
+        Some((retract_sql, self.values(inputs, log_params, tx_information, block_timestamp)))
+    }
+}
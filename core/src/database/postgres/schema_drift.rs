@@ -0,0 +1,363 @@
+use std::path::Path;
+
+use tracing::{info, warn};
+
+use crate::{
+    abi::{ABIInput, ABIItem, EventInfo, ParamTypeError, ReadAbiError},
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        generate::{
+            generate_column_definitions, generate_computed_column_definitions,
+            generate_enum_column_definitions, generate_event_table_full_names,
+            generate_price_enrichment_column_definitions, generate_raw_log_column_definitions,
+            generate_template_column_definitions,
+        },
+    },
+    indexer::Indexer,
+    manifest::{
+        computed_column::{computed_columns_for_event, ComputedColumn},
+        contract::Contract,
+        price_enrichment::{price_enrichments_for_event, EventPriceEnrichment},
+        solidity_enum::enums_for_event,
+        storage::Storage,
+        template::template_columns_for_event,
+        transform::{transforms_for_event, EventInputTransform},
+    },
+    types::code::Code,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnDrift {
+    Missing { column: String, expected_type: String },
+    TypeChanged { column: String, live_type: String, expected_type: String },
+    NoLongerInAbi { column: String, live_type: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct TableDrift {
+    pub table_name: String,
+    pub columns: Vec<ColumnDrift>,
+}
+
+impl TableDrift {
+    /// Columns which are in the live table but not in the ABI do not break inserts
+    pub fn breaks_inserts(&self) -> bool {
+        self.columns.iter().any(|c| !matches!(c, ColumnDrift::NoLongerInAbi { .. }))
+    }
+
+    pub fn migration_sql(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|column| match column {
+                ColumnDrift::Missing { column, expected_type } => format!(
+                    "ALTER TABLE {} ADD COLUMN IF NOT EXISTS \"{}\" {};",
+                    self.table_name, column, expected_type
+                ),
+                ColumnDrift::TypeChanged { column, expected_type, .. } => {
+                    let expected_type = without_generated_expression(expected_type);
+                    format!(
+                        "ALTER TABLE {} ALTER COLUMN \"{}\" TYPE {} USING \"{}\"::{};",
+                        self.table_name, column, expected_type, column, expected_type
+                    )
+                }
+                // we never drop data automatically, leave it to the user to decide
+                ColumnDrift::NoLongerInAbi { column, .. } => {
+                    format!("-- ALTER TABLE {} DROP COLUMN \"{}\";", self.table_name, column)
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum DetectSchemaDriftError {
+    #[error("{0}")]
+    ReadAbiError(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
+
+    #[error("Could not read live table columns: {0}")]
+    CouldNotReadLiveColumns(#[from] PostgresError),
+}
+
+pub(crate) fn base_columns_with_data_types(
+    natural_primary_key: bool,
+    compact_bytea: bool,
+) -> Vec<(String, String)> {
+    let hash_db_type = if compact_bytea { "BYTEA" } else { "CHAR(66)" };
+    let mut columns: Vec<(String, String)> = [
+        ("contract_address", hash_db_type),
+        ("tx_hash", hash_db_type),
+        ("block_number", "NUMERIC"),
+        ("block_hash", hash_db_type),
+        ("network", "VARCHAR(50)"),
+        ("tx_index", "NUMERIC"),
+        ("log_index", "VARCHAR(78)"),
+    ]
+    .iter()
+    .map(|(name, db_type)| (name.to_string(), db_type.to_string()))
+    .collect();
+
+    if !natural_primary_key {
+        columns.insert(0, ("rindexer_id".to_string(), "INTEGER".to_string()));
+    }
+
+    columns
+}
+
+fn expected_columns_with_data_types(
+    inputs: &[ABIInput],
+    transforms: &[&EventInputTransform],
+    jsonb_inputs: &[String],
+    price_enrichments: &[&EventPriceEnrichment],
+    computed_columns: &[&ComputedColumn],
+    natural_primary_key: bool,
+    compact_bytea: bool,
+) -> Vec<(String, String)> {
+    let mut columns = base_columns_with_data_types(natural_primary_key, compact_bytea);
+    columns.extend(generate_column_definitions(inputs, transforms, jsonb_inputs, compact_bytea));
+    columns.extend(generate_price_enrichment_column_definitions(price_enrichments));
+    columns.extend(generate_computed_column_definitions(computed_columns));
+    columns
+}
+
+/// Every column of the event table as it is created
+pub(crate) fn event_table_columns_with_data_types(
+    contract: &Contract,
+    event_info: &EventInfo,
+    storage: &Storage,
+) -> Vec<(String, String)> {
+    let mut columns = expected_columns_with_data_types(
+        &event_info.inputs,
+        &transforms_for_event(&contract.transforms, &event_info.name),
+        &contract.jsonb_inputs(&event_info.name, &event_info.inputs),
+        &price_enrichments_for_event(&contract.price_enrichment, &event_info.name),
+        &computed_columns_for_event(&contract.computed_columns, &event_info.name),
+        storage.postgres_natural_primary_key(),
+        storage.postgres_compact_bytea(),
+    );
+    columns.extend(generate_enum_column_definitions(&enums_for_event(
+        &contract.enums,
+        &event_info.name,
+    )));
+    columns.extend(generate_template_column_definitions(template_columns_for_event(
+        &contract.template,
+        &event_info.name,
+    )));
+    columns.extend(generate_raw_log_column_definitions(contract));
+    columns
+}
+
+/// Computed columns are defined with their generation expression after the type
+pub(crate) fn without_generated_expression(db_type: &str) -> &str {
+    match db_type.to_lowercase().find(" generated always as ") {
+        Some(index) => &db_type[..index],
+        None => db_type,
+    }
+}
+
+/// Postgres reports types using `format_type` naming so map our DDL types to match
+fn normalize_db_type(db_type: &str) -> String {
+    let lower = without_generated_expression(db_type).trim().to_lowercase();
+    let (base, is_array) = match lower.strip_suffix("[]") {
+        Some(base) => (base.to_string(), true),
+        None => (lower, false),
+    };
+
+    let base = if let Some(size) = base.strip_prefix("char(") {
+        format!("character({}", size)
+    } else if let Some(size) = base.strip_prefix("varchar(") {
+        format!("character varying({}", size)
+    } else {
+        base
+    };
+
+    if is_array {
+        format!("{}[]", base)
+    } else {
+        base
+    }
+}
+
+fn diff_columns(
+    expected_columns: &[(String, String)],
+    live_columns: &[(String, String)],
+) -> Vec<ColumnDrift> {
+    let mut drift = vec![];
+
+    for (column, expected_type) in expected_columns {
+        match live_columns.iter().find(|(live_column, _)| live_column == column) {
+            None => drift.push(ColumnDrift::Missing {
+                column: column.clone(),
+                expected_type: expected_type.clone(),
+            }),
+            Some((_, live_type)) => {
+                if normalize_db_type(live_type) != normalize_db_type(expected_type) {
+                    drift.push(ColumnDrift::TypeChanged {
+                        column: column.clone(),
+                        live_type: live_type.clone(),
+                        expected_type: expected_type.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    for (live_column, live_type) in live_columns {
+        if !expected_columns.iter().any(|(column, _)| column == live_column) {
+            drift.push(ColumnDrift::NoLongerInAbi {
+                column: live_column.clone(),
+                live_type: live_type.clone(),
+            });
+        }
+    }
+
+    drift
+}
+
+async fn get_live_columns(
+    client: &PostgresClient,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<Vec<(String, String)>, PostgresError> {
+    let rows = client
+        .query(
+            "SELECT a.attname::TEXT AS column_name, format_type(a.atttypid, a.atttypmod) AS data_type \
+            FROM pg_attribute a \
+            JOIN pg_class c ON a.attrelid = c.oid \
+            JOIN pg_namespace n ON c.relnamespace = n.oid \
+            WHERE n.nspname = $1 AND c.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped \
+            ORDER BY a.attnum",
+            &[&schema_name, &table_name],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| (row.get::<_, String>("column_name"), row.get::<_, String>("data_type")))
+        .collect())
+}
+
+pub async fn detect_schema_drift(
+    client: &PostgresClient,
+    project_path: &Path,
+    indexer: &Indexer,
+    storage: &Storage,
+) -> Result<Vec<TableDrift>, DetectSchemaDriftError> {
+    let mut drifts = vec![];
+
+    for contract in &indexer.contracts {
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+        let abi_items = ABIItem::read_abi_items(project_path, contract)?;
+        let event_names = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
+
+        for event_info in event_names {
+            let expected_columns =
+                event_table_columns_with_data_types(contract, &event_info, storage);
+
+            for full_table_name in generate_event_table_full_names(
+                storage.postgres_layout(),
+                &indexer.name,
+                contract,
+                &contract_name,
+                &event_info.name,
+            ) {
+                let Some((schema_name, table_name)) = full_table_name.split_once('.') else {
+                    continue;
+                };
+                let live_columns = get_live_columns(client, schema_name, table_name).await?;
+                // table does not exist yet so it will be created fresh
+                if live_columns.is_empty() {
+                    continue;
+                }
+
+                let columns = diff_columns(&expected_columns, &live_columns);
+                if !columns.is_empty() {
+                    drifts.push(TableDrift { table_name: full_table_name, columns });
+                }
+            }
+        }
+    }
+
+    Ok(drifts)
+}
+
+pub fn log_schema_drift(drifts: &[TableDrift]) {
+    for drift in drifts {
+        warn!("Schema drift detected for table {}:", drift.table_name);
+        for column in &drift.columns {
+            match column {
+                ColumnDrift::Missing { column, expected_type } => {
+                    warn!("  + {} {} (missing from table)", column, expected_type)
+                }
+                ColumnDrift::TypeChanged { column, live_type, expected_type } => {
+                    warn!("  ~ {} {} -> {}", column, live_type, expected_type)
+                }
+                ColumnDrift::NoLongerInAbi { column, live_type } => {
+                    warn!("  - {} {} (no longer in ABI)", column, live_type)
+                }
+            }
+        }
+    }
+
+    info!("Generated migration plan:\n{}", schema_drift_migration_sql(drifts));
+}
+
+pub fn schema_drift_migration_sql(drifts: &[TableDrift]) -> Code {
+    Code::new(
+        drifts.iter().flat_map(|drift| drift.migration_sql()).collect::<Vec<String>>().join("\n"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_db_type() {
+        assert_eq!(normalize_db_type("CHAR(42)"), "character(42)");
+        assert_eq!(normalize_db_type("VARCHAR(78)[]"), "character varying(78)[]");
+        assert_eq!(normalize_db_type("TEXT[]"), "text[]");
+        assert_eq!(normalize_db_type("NUMERIC"), "numeric");
+        assert_eq!(
+            normalize_db_type("NUMERIC GENERATED ALWAYS AS (value / 10^18) STORED"),
+            "numeric"
+        );
+    }
+
+    #[test]
+    fn test_diff_columns() {
+        let expected = vec![
+            ("from".to_string(), "CHAR(42)".to_string()),
+            ("value".to_string(), "VARCHAR(78)".to_string()),
+            ("memo".to_string(), "TEXT".to_string()),
+        ];
+        let live = vec![
+            ("from".to_string(), "character(42)".to_string()),
+            ("value".to_string(), "numeric".to_string()),
+            ("old".to_string(), "boolean".to_string()),
+        ];
+
+        let drift = diff_columns(&expected, &live);
+
+        assert_eq!(
+            drift,
+            vec![
+                ColumnDrift::TypeChanged {
+                    column: "value".to_string(),
+                    live_type: "numeric".to_string(),
+                    expected_type: "VARCHAR(78)".to_string(),
+                },
+                ColumnDrift::Missing {
+                    column: "memo".to_string(),
+                    expected_type: "TEXT".to_string()
+                },
+                ColumnDrift::NoLongerInAbi {
+                    column: "old".to_string(),
+                    live_type: "boolean".to_string()
+                },
+            ]
+        );
+    }
+}
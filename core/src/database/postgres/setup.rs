@@ -1,14 +1,37 @@
 use std::path::Path;
 
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
     database::postgres::{
+        balance_change::generate_balance_changes_table_sql,
+        block_anomaly::generate_block_anomalies_tables_sql,
+        cdc::generate_cdc_sql_for_indexer,
         client::{PostgresClient, PostgresConnectionError, PostgresError},
-        generate::{generate_tables_for_indexer_sql, GenerateTablesForIndexerSqlError},
+        contract_metadata::{record_contract_metadata, ContractMetadataError},
+        ddl_audit::create_ddl_audit_table_sql,
+        gas_price::generate_gas_price_table_sql,
+        generate::{
+            generate_tables_for_indexer_sql, indexer_checkpoint_table_names,
+            GenerateTablesForIndexerSqlError,
+        },
+        indexes::{apply_cluster_indexes, ApplyClusterIndexesError},
+        internal_schema::{generate_internal_schema_migration_sql, internal_schema},
+        projection::generate_projection_tables_sql,
+        rollup::generate_rollup_tables_sql,
+        schema_drift::{
+            detect_schema_drift, log_schema_drift, schema_drift_migration_sql,
+            DetectSchemaDriftError,
+        },
+        user_migrations::{run_user_migrations, UserMigrationsError},
+        views::{
+            create_relationship_views, drop_last_known_relationship_views, RelationshipViewsError,
+        },
+        wallet_activity::generate_wallet_activity_tables_sql,
     },
     drop_tables_for_indexer_sql,
     manifest::core::{Manifest, ProjectType},
+    quarantine::create_failed_events_table_sql,
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -21,14 +44,43 @@ pub enum SetupPostgresError {
 
     #[error("Error creating tables for indexer: {0}")]
     GeneratingTables(#[from] GenerateTablesForIndexerSqlError),
+
+    #[error("Error detecting schema drift: {0}")]
+    DetectSchemaDrift(#[from] DetectSchemaDriftError),
+
+    #[error("The database schema does not match the ABIs, review the migration plan logged above and rerun with --apply-migrations to apply it")]
+    SchemaDriftDetected,
+
+    #[error("Error creating relationship views: {0}")]
+    RelationshipViews(#[from] RelationshipViewsError),
+
+    #[error("Error running migrations: {0}")]
+    UserMigrations(#[from] UserMigrationsError),
+
+    #[error("Error applying cluster indexes: {0}")]
+    ClusterIndexes(#[from] ApplyClusterIndexesError),
+
+    #[error("Error recording contract metadata: {0}")]
+    ContractMetadata(#[from] ContractMetadataError),
 }
 
 pub async fn setup_postgres(
     project_path: &Path,
     manifest: &Manifest,
+    apply_migrations: bool,
 ) -> Result<PostgresClient, SetupPostgresError> {
     info!("Setting up postgres");
-    let client = PostgresClient::new().await?;
+    let client = PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+
+    // the audit table is created once the internal tables moved, every DDL from here on
+    // including this one is recorded in it
+    let mut sql = generate_internal_schema_migration_sql(
+        &manifest.name,
+        internal_schema(),
+        &indexer_checkpoint_table_names(project_path, &manifest.to_indexer()),
+    );
+    sql.push_str(&create_ddl_audit_table_sql());
+    client.execute_ddl(sql.as_str(), "set up the internal schema").await?;
 
     // No-code will ignore this as it must have tables if postgres used
     if !manifest.storage.postgres_disable_create_tables() ||
@@ -40,16 +92,121 @@ pub async fn setup_postgres(
                 "`drop_each_run` enabled so dropping all data for {} before starting",
                 &manifest.name
             );
-            let sql = drop_tables_for_indexer_sql(project_path, &manifest.to_indexer());
-            client.batch_execute(sql.as_str()).await?;
+            let sql = drop_tables_for_indexer_sql(
+                project_path,
+                &manifest.to_indexer(),
+                manifest.storage.postgres_layout(),
+            );
+            client.execute_ddl(sql.as_str(), "drop all data with drop_each_run").await?;
             info!("Dropped all data for {}", manifest.name);
         }
 
         info!("Creating tables for {}", manifest.name);
-        let sql = generate_tables_for_indexer_sql(project_path, &manifest.to_indexer())?;
+        let sql = generate_tables_for_indexer_sql(
+            project_path,
+            &manifest.to_indexer(),
+            &manifest.storage,
+        )?;
         debug!("{}", sql);
-        client.batch_execute(sql.as_str()).await?;
+        client.execute_ddl(sql.as_str(), "create the event tables").await?;
         info!("Created tables for {}", manifest.name);
+
+        // logs which do not fit the ABI are quarantined in it instead of being dropped
+        client
+            .execute_ddl(&create_failed_events_table_sql(), "create the failed events table")
+            .await?;
+
+        record_contract_metadata(&client, project_path, manifest).await?;
+
+        let sql =
+            generate_cdc_sql_for_indexer(project_path, &manifest.to_indexer(), &manifest.storage)?;
+        if !sql.is_empty() {
+            client.execute_ddl(sql.as_str(), "apply the cdc table settings").await?;
+            info!("Applied cdc settings to the event tables of {}", manifest.name);
+        }
+
+        let projections = manifest.storage.postgres_projections();
+        let rollups = manifest.storage.postgres_rollups();
+        if !projections.is_empty() || !rollups.is_empty() {
+            let mut sql = generate_projection_tables_sql(&manifest.name, projections);
+            sql.push_str(&generate_rollup_tables_sql(&manifest.name, rollups));
+            client.execute_ddl(sql.as_str(), "create the projection and rollup tables").await?;
+            info!("Created projection and rollup tables for {}", manifest.name);
+        }
+
+        apply_cluster_indexes(&client, project_path, manifest).await?;
+
+        if !manifest.storage.postgres_balance_changes().is_empty() {
+            client
+                .execute_ddl(
+                    generate_balance_changes_table_sql(&manifest.name).as_str(),
+                    "create the balance changes table",
+                )
+                .await?;
+            info!("Created balance changes table for {}", manifest.name);
+        }
+
+        if let Some(wallets) = &manifest.wallets {
+            client
+                .execute_ddl(
+                    generate_wallet_activity_tables_sql(&manifest.name, &wallets.wallets).as_str(),
+                    "create the wallet activity tables",
+                )
+                .await?;
+            info!("Created wallet activity tables for {}", manifest.name);
+        }
+
+        if let Some(gas_prices) = &manifest.gas_prices {
+            client
+                .execute_ddl(
+                    generate_gas_price_table_sql(&gas_prices.table_name()).as_str(),
+                    "create the gas prices table",
+                )
+                .await?;
+            info!("Created gas prices table {}", gas_prices.table_name());
+        }
+
+        if let Some(block_anomalies) = &manifest.block_anomalies {
+            client
+                .execute_ddl(
+                    generate_block_anomalies_tables_sql(
+                        &block_anomalies.table_name(),
+                        &block_anomalies.last_synced_table_name(),
+                    )
+                    .as_str(),
+                    "create the block anomalies tables",
+                )
+                .await?;
+            info!("Created block anomalies table {}", block_anomalies.table_name());
+        }
+
+        drop_last_known_relationship_views(&client, &manifest.name).await?;
+
+        let drifts =
+            detect_schema_drift(&client, project_path, &manifest.to_indexer(), &manifest.storage)
+                .await?;
+        if !drifts.is_empty() {
+            log_schema_drift(&drifts);
+
+            if apply_migrations {
+                info!("Applying migration plan for {}", manifest.name);
+                client
+                    .execute_ddl(
+                        schema_drift_migration_sql(&drifts).as_str(),
+                        "apply the schema drift migration plan",
+                    )
+                    .await?;
+                info!("Applied migration plan for {}", manifest.name);
+            } else if drifts.iter().any(|drift| drift.breaks_inserts()) {
+                return Err(SetupPostgresError::SchemaDriftDetected);
+            } else {
+                warn!("Schema drift found will not break inserts so continuing, run with --apply-migrations to apply the migration plan");
+            }
+        }
+
+        create_relationship_views(&client, project_path, manifest).await?;
+
+        run_user_migrations(&client, project_path, &manifest.name).await?;
     }
 
     Ok(client)
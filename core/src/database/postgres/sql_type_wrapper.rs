@@ -1,17 +1,52 @@
+#![allow(clippy::manual_strip)]
+
 use std::str::FromStr;
 
 use bytes::BytesMut;
 use ethers::{
     abi::{Int, LogParam, Token},
     addressbook::Address,
-    prelude::{Bytes, H128, H160, H256, H512, U128, U256, U512, U64},
+    prelude::{Bytes, H128, H160, H256, H512, I256, U128, U256, U512, U64},
 };
 use rust_decimal::Decimal;
 use serde_json::{json, Value};
 use tokio_postgres::types::{to_sql_checked, IsNull, ToSql, Type as PgType};
-use tracing::error;
 
-use crate::{abi::ABIInput, event::callback_registry::TxInformation};
+use crate::{abi::ABIInput, event::callback_registry::TxInformation, helpers::format_address};
+
+/// A log param which does not have the shape its ABI input says it should
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum ParamDecodeError {
+    #[error("No ABI input found for log param at index {0}")]
+    MissingAbiInput(usize),
+
+    #[error("Tuple input {0} has no components in the ABI")]
+    MissingTupleComponents(String),
+
+    #[error("Unknown int type {0} for input {1}")]
+    UnknownIntType(String, String),
+
+    #[error("Int value {0} does not fit {1}")]
+    IntOutOfRange(U256, &'static str),
+
+    #[error("Ints can not be stored as {0}")]
+    UnsupportedIntTarget(&'static str),
+
+    #[error("Array input {0} mixes ints with other values")]
+    MixedIntArray(String),
+
+    #[error("Array input {0} has ints which were not converted to its type")]
+    IntArrayTypeMismatch(String),
+
+    #[error("Array input {0} holds arrays which the EVM does not log")]
+    NestedArray(String),
+
+    #[error("Array input {0} holds tuples which are not supported yet - please raise issue in github with ABI to recreate and we will fix")]
+    ArrayOfTuples(String),
+
+    #[error("Tuple input {0} has to be flattened into its components")]
+    UnexpectedTuple(String),
+}
 
 #[derive(Debug, Clone)]
 pub enum EthereumSqlTypeWrapper {
@@ -45,9 +80,28 @@ pub enum EthereumSqlTypeWrapper {
     VecString(Vec<String>),
     Bytes(Bytes),
     VecBytes(Vec<Bytes>),
+    // decimal string which can be bigger than rust_decimal can hold, e.g. a scaled uint256
+    Numeric(String),
+    // numeric which is NULL when the value is not known, e.g. no reward for an empty block
+    NullableNumeric(Option<String>),
+    // whole decoded parameter tree for inputs stored as JSONB
+    JSONB(Value),
 }
 
 impl EthereumSqlTypeWrapper {
+    /// Compact storage writes addresses and hashes as their raw bytes into BYTEA columns
+    pub fn into_compact_bytea(self) -> EthereumSqlTypeWrapper {
+        match self {
+            EthereumSqlTypeWrapper::Address(value) => {
+                EthereumSqlTypeWrapper::Bytes(Bytes::from(value.as_bytes().to_vec()))
+            }
+            EthereumSqlTypeWrapper::H256(value) => {
+                EthereumSqlTypeWrapper::Bytes(Bytes::from(value.as_bytes().to_vec()))
+            }
+            other => other,
+        }
+    }
+
     pub fn raw_name(&self) -> &'static str {
         match self {
             EthereumSqlTypeWrapper::U64(_) => "U64",
@@ -80,6 +134,9 @@ impl EthereumSqlTypeWrapper {
             EthereumSqlTypeWrapper::VecString(_) => "VecString",
             EthereumSqlTypeWrapper::Bytes(_) => "Bytes",
             EthereumSqlTypeWrapper::VecBytes(_) => "VecBytes",
+            EthereumSqlTypeWrapper::Numeric(_) => "Numeric",
+            EthereumSqlTypeWrapper::NullableNumeric(_) => "NullableNumeric",
+            EthereumSqlTypeWrapper::JSONB(_) => "JSONB",
         }
     }
 
@@ -117,6 +174,10 @@ impl EthereumSqlTypeWrapper {
             EthereumSqlTypeWrapper::VecU32(_) => PgType::INT2_ARRAY,
             EthereumSqlTypeWrapper::U8(_) => PgType::INT2,
             EthereumSqlTypeWrapper::VecU8(_) => PgType::INT2_ARRAY,
+            EthereumSqlTypeWrapper::Numeric(_) | EthereumSqlTypeWrapper::NullableNumeric(_) => {
+                PgType::NUMERIC
+            }
+            EthereumSqlTypeWrapper::JSONB(_) => PgType::JSONB,
         }
     }
 }
@@ -219,11 +280,11 @@ impl ToSql for EthereumSqlTypeWrapper {
                 }
             }
             EthereumSqlTypeWrapper::Address(value) => {
-                let hex = format!("{:?}", value);
+                let hex = format_address(value);
                 String::to_sql(&hex, _ty, out)
             }
             EthereumSqlTypeWrapper::VecAddress(values) => {
-                let addresses: Vec<String> = values.iter().map(|s| format!("{:?}", s)).collect();
+                let addresses: Vec<String> = values.iter().map(format_address).collect();
                 if addresses.is_empty() {
                     Ok(IsNull::Yes)
                 } else {
@@ -312,6 +373,17 @@ impl ToSql for EthereumSqlTypeWrapper {
                     int_values.to_sql(_ty, out)
                 }
             }
+            EthereumSqlTypeWrapper::Numeric(value) => serialize_numeric_string(value, out),
+            EthereumSqlTypeWrapper::NullableNumeric(value) => match value {
+                Some(value) => serialize_numeric_string(value, out),
+                None => Ok(IsNull::Yes),
+            },
+            EthereumSqlTypeWrapper::JSONB(value) => {
+                // JSONB binary format is a version byte followed by the JSON text
+                out.extend_from_slice(&[1]);
+                out.extend_from_slice(&serde_json::to_vec(value)?);
+                Ok(IsNull::No)
+            }
         }
     }
 
@@ -407,65 +479,112 @@ pub fn solidity_type_to_ethereum_sql_type_wrapper(
     }
 }
 
+/// Inputs named in `jsonb_inputs` are kept as one JSONB value instead of being flattened
 pub fn map_log_params_to_ethereum_wrapper(
     abi_inputs: &[ABIInput],
     params: &[LogParam],
-) -> Vec<EthereumSqlTypeWrapper> {
+    jsonb_inputs: &[String],
+) -> Result<Vec<EthereumSqlTypeWrapper>, ParamDecodeError> {
     let mut wrappers = vec![];
 
     for (index, param) in params.iter().enumerate() {
-        if let Some(abi_input) = abi_inputs.get(index) {
-            match &param.value {
-                Token::Tuple(tuple) => {
-                    wrappers.extend(process_tuple(
-                        abi_input
-                            .components
-                            .as_ref()
-                            .expect("tuple should have a component ABI on"),
-                        tuple,
-                    ));
-                }
-                _ => {
-                    wrappers.push(map_log_token_to_ethereum_wrapper(abi_input, &param.value));
-                }
+        let abi_input = abi_inputs.get(index).ok_or(ParamDecodeError::MissingAbiInput(index))?;
+        if jsonb_inputs.contains(&abi_input.name) {
+            wrappers
+                .push(EthereumSqlTypeWrapper::JSONB(map_token_to_json(abi_input, &param.value)));
+            continue;
+        }
+
+        match &param.value {
+            Token::Tuple(tuple) => {
+                wrappers.extend(process_tuple(tuple_components(abi_input)?, tuple)?);
+            }
+            _ => {
+                wrappers.push(map_log_token_to_ethereum_wrapper(abi_input, &param.value)?);
             }
-        } else {
-            panic!("No ABI input found for log param at index: {}", index)
         }
     }
 
-    wrappers
+    Ok(wrappers)
+}
+
+fn tuple_components(abi_input: &ABIInput) -> Result<&[ABIInput], ParamDecodeError> {
+    abi_input
+        .components
+        .as_deref()
+        .ok_or_else(|| ParamDecodeError::MissingTupleComponents(abi_input.name.clone()))
+}
+
+fn map_token_to_json(abi_input: &ABIInput, token: &Token) -> Value {
+    match token {
+        Token::Address(address) => json!(format_address(address)),
+        Token::Int(value) if abi_input.type_.starts_with("int") => {
+            json!(I256::from_raw(*value).to_string())
+        }
+        Token::Int(value) | Token::Uint(value) => json!(value.to_string()),
+        Token::Bool(b) => json!(b),
+        Token::String(s) => json!(s),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => {
+            json!(format!("0x{}", hex::encode(bytes)))
+        }
+        Token::FixedArray(tokens) | Token::Array(tokens) => {
+            // the element type is the array type with the last dimension removed
+            let element_input = ABIInput {
+                type_: abi_input
+                    .type_
+                    .rsplit_once('[')
+                    .map_or(abi_input.type_.clone(), |(element_type, _)| element_type.to_string()),
+                ..abi_input.clone()
+            };
+            Value::Array(
+                tokens.iter().map(|token| map_token_to_json(&element_input, token)).collect(),
+            )
+        }
+        Token::Tuple(tokens) => {
+            let components = abi_input.components.as_deref().unwrap_or_default();
+            Value::Object(
+                tokens
+                    .iter()
+                    .enumerate()
+                    .map(|(index, token)| match components.get(index) {
+                        Some(component) => {
+                            (component.name.clone(), map_token_to_json(component, token))
+                        }
+                        None => (index.to_string(), map_token_to_json(abi_input, token)),
+                    })
+                    .collect(),
+            )
+        }
+    }
 }
 
-fn process_tuple(abi_inputs: &[ABIInput], tokens: &[Token]) -> Vec<EthereumSqlTypeWrapper> {
+fn process_tuple(
+    abi_inputs: &[ABIInput],
+    tokens: &[Token],
+) -> Result<Vec<EthereumSqlTypeWrapper>, ParamDecodeError> {
     let mut wrappers = vec![];
 
     for (index, token) in tokens.iter().enumerate() {
-        if let Some(abi_input) = abi_inputs.get(index) {
-            match token {
-                Token::Tuple(tuple) => {
-                    wrappers.extend(process_tuple(
-                        abi_input
-                            .components
-                            .as_ref()
-                            .expect("tuple should have a component ABI on"),
-                        tuple,
-                    ));
-                }
-                _ => {
-                    wrappers.push(map_log_token_to_ethereum_wrapper(abi_input, token));
-                }
+        let abi_input = abi_inputs.get(index).ok_or(ParamDecodeError::MissingAbiInput(index))?;
+        match token {
+            Token::Tuple(tuple) => {
+                wrappers.extend(process_tuple(tuple_components(abi_input)?, tuple)?);
+            }
+            _ => {
+                wrappers.push(map_log_token_to_ethereum_wrapper(abi_input, token)?);
             }
-        } else {
-            panic!("No ABI input found for log param at index: {}", index)
         }
     }
 
-    wrappers
+    Ok(wrappers)
 }
 
-fn convert_int(value: &Int, target_type: &EthereumSqlTypeWrapper) -> EthereumSqlTypeWrapper {
-    match target_type {
+fn convert_int(
+    value: &Int,
+    target_type: &EthereumSqlTypeWrapper,
+    signed: bool,
+) -> Result<EthereumSqlTypeWrapper, ParamDecodeError> {
+    Ok(match target_type {
         EthereumSqlTypeWrapper::U256(_) | EthereumSqlTypeWrapper::VecU256(_) => {
             EthereumSqlTypeWrapper::U256(*value)
         }
@@ -473,7 +592,15 @@ fn convert_int(value: &Int, target_type: &EthereumSqlTypeWrapper) -> EthereumSql
             EthereumSqlTypeWrapper::U128(U128::from(value.low_u128()))
         }
         EthereumSqlTypeWrapper::U64(_) | EthereumSqlTypeWrapper::VecU64(_) => {
-            EthereumSqlTypeWrapper::U64(value.as_u64().into())
+            // negative int64 values come sign extended, their low bits are the value
+            let sign_extended = signed && {
+                let value = I256::from_raw(*value);
+                value.is_negative() && value >= I256::from(i64::MIN)
+            };
+            if value.bits() > 64 && !sign_extended {
+                return Err(ParamDecodeError::IntOutOfRange(*value, target_type.raw_name()));
+            }
+            EthereumSqlTypeWrapper::U64(value.low_u64().into())
         }
         EthereumSqlTypeWrapper::U32(_) | EthereumSqlTypeWrapper::VecU32(_) => {
             EthereumSqlTypeWrapper::U32(value.low_u32())
@@ -484,36 +611,24 @@ fn convert_int(value: &Int, target_type: &EthereumSqlTypeWrapper) -> EthereumSql
         EthereumSqlTypeWrapper::U8(_) | EthereumSqlTypeWrapper::VecU8(_) => {
             EthereumSqlTypeWrapper::U8(value.low_u32() as u8)
         }
-        _ => {
-            let error_message = format!("Unsupported target type - {:?}", target_type);
-            error!("{}", error_message);
-            panic!("{}", error_message)
-        }
-    }
+        _ => return Err(ParamDecodeError::UnsupportedIntTarget(target_type.raw_name())),
+    })
 }
 
-fn map_dynamic_int_to_ethereum_sql_type_wrapper(
-    abi_input: &ABIInput,
-    value: &Int,
-) -> EthereumSqlTypeWrapper {
-    let sql_type_wrapper = solidity_type_to_ethereum_sql_type_wrapper(&abi_input.type_);
-    if let Some(target_type) = sql_type_wrapper {
-        convert_int(value, &target_type)
-    } else {
-        let error_message = format!("Unknown int type for abi input: {:?}", abi_input);
-        error!("{}", error_message);
-        panic!("{}", error_message);
-    }
+fn int_target_type(abi_input: &ABIInput) -> Result<EthereumSqlTypeWrapper, ParamDecodeError> {
+    solidity_type_to_ethereum_sql_type_wrapper(&abi_input.type_).ok_or_else(|| {
+        ParamDecodeError::UnknownIntType(abi_input.type_.clone(), abi_input.name.clone())
+    })
 }
 
 fn map_log_token_to_ethereum_wrapper(
     abi_input: &ABIInput,
     token: &Token,
-) -> EthereumSqlTypeWrapper {
-    match &token {
+) -> Result<EthereumSqlTypeWrapper, ParamDecodeError> {
+    Ok(match &token {
         Token::Address(address) => EthereumSqlTypeWrapper::Address(*address),
         Token::Int(value) | Token::Uint(value) => {
-            map_dynamic_int_to_ethereum_sql_type_wrapper(abi_input, value)
+            convert_int(value, &int_target_type(abi_input)?, abi_input.type_.starts_with("int"))?
         }
         Token::Bool(b) => EthereumSqlTypeWrapper::Bool(*b),
         Token::String(s) => EthereumSqlTypeWrapper::String(s.clone()),
@@ -548,102 +663,91 @@ fn map_log_token_to_ethereum_wrapper(
                             EthereumSqlTypeWrapper::VecBytes(vec)
                         }
                         Token::Int(_) | Token::Uint(_) => {
-                            let sql_type_wrapper =
-                                solidity_type_to_ethereum_sql_type_wrapper(&abi_input.type_)
-                                    .unwrap_or_else(|| {
-                                        panic!("Unknown int type for abi input: {:?}", abi_input)
-                                    });
+                            let sql_type_wrapper = int_target_type(abi_input)?;
 
                             let vec_wrapper = tokens
                                 .iter()
-                                .map(|token| {
-                                    if let Token::Uint(uint) = token {
-                                        return convert_int(uint, &sql_type_wrapper);
+                                .map(|token| match token {
+                                    Token::Int(value) | Token::Uint(value) => convert_int(
+                                        value,
+                                        &sql_type_wrapper,
+                                        abi_input.type_.starts_with("int"),
+                                    ),
+                                    _ => {
+                                        Err(ParamDecodeError::MixedIntArray(abi_input.name.clone()))
                                     }
-
-                                    if let Token::Int(uint) = token {
-                                        return convert_int(uint, &sql_type_wrapper);
-                                    }
-
-                                    panic!(
-                                        "Expected uint or int token in array for abi input: {:?}",
-                                        abi_input
-                                    );
                                 })
-                                .collect::<Vec<_>>();
+                                .collect::<Result<Vec<_>, _>>()?;
 
                             match sql_type_wrapper {
                                 EthereumSqlTypeWrapper::U256(_) |
                                 EthereumSqlTypeWrapper::VecU256(_) => {
-                                    EthereumSqlTypeWrapper::VecU256(
-                                        vec_wrapper
-                                            .into_iter()
-                                            .map(|w| match w {
-                                                EthereumSqlTypeWrapper::U256(v) => v,
-                                                _ => unreachable!(),
-                                            })
-                                            .collect(),
-                                    )
+                                    EthereumSqlTypeWrapper::VecU256(collect_ints(
+                                        vec_wrapper,
+                                        abi_input,
+                                        |w| match w {
+                                            EthereumSqlTypeWrapper::U256(v) => Some(v),
+                                            _ => None,
+                                        },
+                                    )?)
                                 }
                                 EthereumSqlTypeWrapper::U128(_) |
                                 EthereumSqlTypeWrapper::VecU128(_) => {
-                                    EthereumSqlTypeWrapper::VecU128(
-                                        vec_wrapper
-                                            .into_iter()
-                                            .map(|w| match w {
-                                                EthereumSqlTypeWrapper::U128(v) => v,
-                                                _ => unreachable!(),
-                                            })
-                                            .collect(),
-                                    )
+                                    EthereumSqlTypeWrapper::VecU128(collect_ints(
+                                        vec_wrapper,
+                                        abi_input,
+                                        |w| match w {
+                                            EthereumSqlTypeWrapper::U128(v) => Some(v),
+                                            _ => None,
+                                        },
+                                    )?)
                                 }
                                 EthereumSqlTypeWrapper::U64(_) |
                                 EthereumSqlTypeWrapper::VecU64(_) => {
-                                    EthereumSqlTypeWrapper::VecU64(
-                                        vec_wrapper
-                                            .into_iter()
-                                            .map(|w| match w {
-                                                EthereumSqlTypeWrapper::U64(v) => v,
-                                                _ => unreachable!(),
-                                            })
-                                            .collect(),
-                                    )
+                                    EthereumSqlTypeWrapper::VecU64(collect_ints(
+                                        vec_wrapper,
+                                        abi_input,
+                                        |w| match w {
+                                            EthereumSqlTypeWrapper::U64(v) => Some(v),
+                                            _ => None,
+                                        },
+                                    )?)
                                 }
                                 EthereumSqlTypeWrapper::U32(_) |
                                 EthereumSqlTypeWrapper::VecU32(_) => {
-                                    EthereumSqlTypeWrapper::VecU32(
-                                        vec_wrapper
-                                            .into_iter()
-                                            .map(|w| match w {
-                                                EthereumSqlTypeWrapper::U32(v) => v,
-                                                _ => unreachable!(),
-                                            })
-                                            .collect(),
-                                    )
+                                    EthereumSqlTypeWrapper::VecU32(collect_ints(
+                                        vec_wrapper,
+                                        abi_input,
+                                        |w| match w {
+                                            EthereumSqlTypeWrapper::U32(v) => Some(v),
+                                            _ => None,
+                                        },
+                                    )?)
                                 }
                                 EthereumSqlTypeWrapper::U16(_) |
                                 EthereumSqlTypeWrapper::VecU16(_) => {
-                                    EthereumSqlTypeWrapper::VecU16(
-                                        vec_wrapper
-                                            .into_iter()
-                                            .map(|w| match w {
-                                                EthereumSqlTypeWrapper::U16(v) => v,
-                                                _ => unreachable!(),
-                                            })
-                                            .collect(),
-                                    )
+                                    EthereumSqlTypeWrapper::VecU16(collect_ints(
+                                        vec_wrapper,
+                                        abi_input,
+                                        |w| match w {
+                                            EthereumSqlTypeWrapper::U16(v) => Some(v),
+                                            _ => None,
+                                        },
+                                    )?)
                                 }
                                 EthereumSqlTypeWrapper::U8(_) |
                                 EthereumSqlTypeWrapper::VecU8(_) => EthereumSqlTypeWrapper::VecU8(
-                                    vec_wrapper
-                                        .into_iter()
-                                        .map(|w| match w {
-                                            EthereumSqlTypeWrapper::U8(v) => v,
-                                            _ => unreachable!(),
-                                        })
-                                        .collect(),
+                                    collect_ints(vec_wrapper, abi_input, |w| match w {
+                                        EthereumSqlTypeWrapper::U8(v) => Some(v),
+                                        _ => None,
+                                    })?,
                                 ),
-                                _ => panic!("Unknown int type for abi input: {:?}", abi_input),
+                                _ => {
+                                    return Err(ParamDecodeError::UnknownIntType(
+                                        abi_input.type_.clone(),
+                                        abi_input.name.clone(),
+                                    ))
+                                }
                             }
                         }
                         Token::Bool(_) => {
@@ -667,20 +771,35 @@ fn map_log_token_to_ethereum_wrapper(
                             EthereumSqlTypeWrapper::VecString(vec)
                         }
                         Token::FixedArray(_) | Token::Array(_) => {
-                            unreachable!("Nested arrays are not supported by the EVM")
+                            return Err(ParamDecodeError::NestedArray(abi_input.name.clone()))
                         }
                         Token::Tuple(_) => {
                             // TODO - this is not supported yet
-                            panic!("Array tuple not supported yet - please raise issue in github with ABI to recreate and we will fix")
+                            return Err(ParamDecodeError::ArrayOfTuples(abi_input.name.clone()));
                         }
                     }
                 }
             }
         }
         Token::Tuple(_tuple) => {
-            panic!("You should not be calling a tuple type in this function!")
+            return Err(ParamDecodeError::UnexpectedTuple(abi_input.name.clone()))
         }
-    }
+    })
+}
+
+/// Unwraps the converted ints of an array, they all have to be the type `unwrap` takes
+fn collect_ints<T>(
+    values: Vec<EthereumSqlTypeWrapper>,
+    abi_input: &ABIInput,
+    unwrap: impl Fn(EthereumSqlTypeWrapper) -> Option<T>,
+) -> Result<Vec<T>, ParamDecodeError> {
+    values
+        .into_iter()
+        .map(|value| {
+            unwrap(value)
+                .ok_or_else(|| ParamDecodeError::IntArrayTypeMismatch(abi_input.name.clone()))
+        })
+        .collect()
 }
 
 impl From<&Address> for EthereumSqlTypeWrapper {
@@ -720,6 +839,59 @@ fn serialize_vec_decimal<T: ToString>(
     Ok(IsNull::No)
 }
 
+/// Writes a decimal string in the postgres NUMERIC binary format which is base 10000 digits,
+/// this avoids the 28 digit limit on rust_decimal
+fn serialize_numeric_string(
+    value: &str,
+    out: &mut BytesMut,
+) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+    let (is_negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+    let (integer_part, fraction_part) = value.split_once('.').unwrap_or((value, ""));
+    if !integer_part.chars().chain(fraction_part.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid numeric value: {}", value).into());
+    }
+
+    let integer_part = integer_part.trim_start_matches('0');
+    let integer_padding = (4 - integer_part.len() % 4) % 4;
+    let integer_padded = format!("{}{}", "0".repeat(integer_padding), integer_part);
+    let fraction_padding = (4 - fraction_part.len() % 4) % 4;
+    let fraction_padded = format!("{}{}", fraction_part, "0".repeat(fraction_padding));
+
+    let mut digits: Vec<i16> = integer_padded
+        .as_bytes()
+        .chunks(4)
+        .chain(fraction_padded.as_bytes().chunks(4))
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or("0").parse::<i16>().unwrap_or(0))
+        .collect();
+    let mut weight = (integer_padded.len() / 4) as i16 - 1;
+
+    while digits.first() == Some(&0) {
+        digits.remove(0);
+        weight -= 1;
+    }
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    if digits.is_empty() {
+        weight = 0;
+    }
+
+    let sign: u16 = if is_negative && !digits.is_empty() { 0x4000 } else { 0x0000 };
+
+    out.extend_from_slice(&(digits.len() as i16).to_be_bytes());
+    out.extend_from_slice(&weight.to_be_bytes());
+    out.extend_from_slice(&sign.to_be_bytes());
+    out.extend_from_slice(&(fraction_part.len() as u16).to_be_bytes());
+    for digit in digits {
+        out.extend_from_slice(&digit.to_be_bytes());
+    }
+
+    Ok(IsNull::No)
+}
+
 fn count_components(components: &[ABIInput]) -> usize {
     components
         .iter()
@@ -735,6 +907,56 @@ fn count_components(components: &[ABIInput]) -> usize {
         .sum()
 }
 
+/// The JSON value of a single wrapper, tuples which were flattened into many wrappers are put
+/// back together by `map_ethereum_wrapper_to_json`
+pub fn ethereum_wrapper_to_json(wrapper: &EthereumSqlTypeWrapper) -> Value {
+    match wrapper {
+        EthereumSqlTypeWrapper::U64(u) => json!(u),
+        EthereumSqlTypeWrapper::VecU64(u64s) => json!(u64s),
+        EthereumSqlTypeWrapper::U128(u) => json!(u.to_string()),
+        EthereumSqlTypeWrapper::VecU128(u128s) => {
+            json!(u128s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::U256(u) => json!(u.to_string()),
+        EthereumSqlTypeWrapper::VecU256(u256s) => {
+            json!(u256s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::U512(u) => json!(u.to_string()),
+        EthereumSqlTypeWrapper::VecU512(u512s) => {
+            json!(u512s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::H128(h) => json!(h),
+        EthereumSqlTypeWrapper::VecH128(h128s) => json!(h128s),
+        EthereumSqlTypeWrapper::H160(h) => json!(h),
+        EthereumSqlTypeWrapper::VecH160(h160s) => json!(h160s),
+        EthereumSqlTypeWrapper::H256(h) => json!(h),
+        EthereumSqlTypeWrapper::VecH256(h256s) => json!(h256s),
+        EthereumSqlTypeWrapper::H512(h) => json!(h),
+        EthereumSqlTypeWrapper::VecH512(h512s) => json!(h512s),
+        EthereumSqlTypeWrapper::Address(address) => json!(format_address(address)),
+        EthereumSqlTypeWrapper::VecAddress(addresses) => {
+            json!(addresses.iter().map(format_address).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::Bool(b) => json!(b),
+        EthereumSqlTypeWrapper::VecBool(bools) => json!(bools),
+        EthereumSqlTypeWrapper::U32(u) => json!(u),
+        EthereumSqlTypeWrapper::VecU32(u32s) => json!(u32s),
+        EthereumSqlTypeWrapper::U16(u) => json!(u),
+        EthereumSqlTypeWrapper::VecU16(u16s) => json!(u16s),
+        EthereumSqlTypeWrapper::U8(u) => json!(u),
+        EthereumSqlTypeWrapper::VecU8(u8s) => json!(u8s),
+        EthereumSqlTypeWrapper::String(s) => json!(s),
+        EthereumSqlTypeWrapper::VecString(strings) => json!(strings),
+        EthereumSqlTypeWrapper::Bytes(bytes) => json!(hex::encode(bytes)),
+        EthereumSqlTypeWrapper::VecBytes(bytes) => {
+            json!(bytes.iter().map(hex::encode).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::Numeric(value) => json!(value),
+        EthereumSqlTypeWrapper::NullableNumeric(value) => json!(value),
+        EthereumSqlTypeWrapper::JSONB(value) => value.clone(),
+    }
+}
+
 pub fn map_ethereum_wrapper_to_json(
     abi_inputs: &[ABIInput],
     wrappers: &[EthereumSqlTypeWrapper],
@@ -751,7 +973,13 @@ pub fn map_ethereum_wrapper_to_json(
             continue;
         }
         if let Some(wrapper) = wrappers.get(current_wrapper_index) {
-            if abi_input.type_ == "tuple" {
+            if abi_input.is_hashed_topic() {
+                result.insert(abi_input.hashed_topic_name(), ethereum_wrapper_to_json(wrapper));
+                wrappers_index_processed.push(current_wrapper_index);
+                current_wrapper_index += 1;
+            } else if abi_input.type_ == "tuple" &&
+                !matches!(wrapper, EthereumSqlTypeWrapper::JSONB(_))
+            {
                 let components =
                     abi_input.components.as_ref().expect("Tuple should have components defined");
                 let total_properties = count_components(components);
@@ -767,46 +995,7 @@ pub fn map_ethereum_wrapper_to_json(
                 }
                 current_wrapper_index = total_properties;
             } else {
-                let value = match wrapper {
-                    EthereumSqlTypeWrapper::U64(u) => json!(u),
-                    EthereumSqlTypeWrapper::VecU64(u64s) => json!(u64s),
-                    EthereumSqlTypeWrapper::U128(u) => json!(u.to_string()),
-                    EthereumSqlTypeWrapper::VecU128(u128s) => {
-                        json!(u128s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
-                    }
-                    EthereumSqlTypeWrapper::U256(u) => json!(u.to_string()),
-                    EthereumSqlTypeWrapper::VecU256(u256s) => {
-                        json!(u256s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
-                    }
-                    EthereumSqlTypeWrapper::U512(u) => json!(u.to_string()),
-                    EthereumSqlTypeWrapper::VecU512(u512s) => {
-                        json!(u512s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
-                    }
-                    EthereumSqlTypeWrapper::H128(h) => json!(h),
-                    EthereumSqlTypeWrapper::VecH128(h128s) => json!(h128s),
-                    EthereumSqlTypeWrapper::H160(h) => json!(h),
-                    EthereumSqlTypeWrapper::VecH160(h160s) => json!(h160s),
-                    EthereumSqlTypeWrapper::H256(h) => json!(h),
-                    EthereumSqlTypeWrapper::VecH256(h256s) => json!(h256s),
-                    EthereumSqlTypeWrapper::H512(h) => json!(h),
-                    EthereumSqlTypeWrapper::VecH512(h512s) => json!(h512s),
-                    EthereumSqlTypeWrapper::Address(address) => json!(address),
-                    EthereumSqlTypeWrapper::VecAddress(addresses) => json!(addresses),
-                    EthereumSqlTypeWrapper::Bool(b) => json!(b),
-                    EthereumSqlTypeWrapper::VecBool(bools) => json!(bools),
-                    EthereumSqlTypeWrapper::U32(u) => json!(u),
-                    EthereumSqlTypeWrapper::VecU32(u32s) => json!(u32s),
-                    EthereumSqlTypeWrapper::U16(u) => json!(u),
-                    EthereumSqlTypeWrapper::VecU16(u16s) => json!(u16s),
-                    EthereumSqlTypeWrapper::U8(u) => json!(u),
-                    EthereumSqlTypeWrapper::VecU8(u8s) => json!(u8s),
-                    EthereumSqlTypeWrapper::String(s) => json!(s),
-                    EthereumSqlTypeWrapper::VecString(strings) => json!(strings),
-                    EthereumSqlTypeWrapper::Bytes(bytes) => json!(hex::encode(bytes)),
-                    EthereumSqlTypeWrapper::VecBytes(bytes) => {
-                        json!(bytes.iter().map(hex::encode).collect::<Vec<_>>())
-                    }
-                };
+                let value = ethereum_wrapper_to_json(wrapper);
                 result.insert(abi_input.name.clone(), value);
                 wrappers_index_processed.push(current_wrapper_index);
                 current_wrapper_index += 1;
@@ -826,3 +1015,162 @@ pub fn map_ethereum_wrapper_to_json(
 
     Value::Object(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_numeric_string() {
+        let mut out = BytesMut::new();
+        serialize_numeric_string("12345.000123", &mut out).unwrap();
+
+        // ndigits 4, weight 1, positive, dscale 6, digits 1 2345 0001 2300
+        let expected: Vec<u8> = [4i16, 1, 0, 6, 1, 2345, 1, 2300]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect();
+        assert_eq!(out.to_vec(), expected);
+    }
+
+    #[test]
+    fn test_map_token_to_json() {
+        let input = |name: &str, type_: &str, components: Option<Vec<ABIInput>>| ABIInput {
+            indexed: None,
+            name: name.to_string(),
+            type_: type_.to_string(),
+            components,
+        };
+        let order = input(
+            "orders",
+            "tuple[]",
+            Some(vec![input("amount", "int8", None), input("ids", "uint256[]", None)]),
+        );
+        let token = Token::Array(vec![Token::Tuple(vec![
+            Token::Int(I256::from(-5).into_raw()),
+            Token::Array(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]),
+        ])]);
+
+        assert_eq!(
+            map_token_to_json(&order, &token),
+            json!([{ "amount": "-5", "ids": ["1", "2"] }])
+        );
+    }
+
+    #[test]
+    fn test_unexpected_shapes_are_decode_errors() {
+        let input = |name: &str, type_: &str| ABIInput {
+            name: name.to_string(),
+            type_: type_.to_string(),
+            indexed: Some(false),
+            components: None,
+        };
+        let param = |value: Token| LogParam { name: "value".to_string(), value };
+
+        assert_eq!(
+            map_log_params_to_ethereum_wrapper(&[], &[param(Token::Bool(true))], &[]).unwrap_err(),
+            ParamDecodeError::MissingAbiInput(0)
+        );
+        assert_eq!(
+            map_log_params_to_ethereum_wrapper(
+                &[input("value", "uint64")],
+                &[param(Token::Uint(U256::MAX))],
+                &[]
+            )
+            .unwrap_err(),
+            ParamDecodeError::IntOutOfRange(U256::MAX, "U64")
+        );
+        assert_eq!(
+            map_log_params_to_ethereum_wrapper(
+                &[input("pair", "tuple")],
+                &[param(Token::Tuple(vec![Token::Bool(true)]))],
+                &[]
+            )
+            .unwrap_err(),
+            ParamDecodeError::MissingTupleComponents("pair".to_string())
+        );
+        assert!(map_log_params_to_ethereum_wrapper(
+            &[input("value", "uint64")],
+            &[param(Token::Uint(U256::from(7)))],
+            &[]
+        )
+        .is_ok());
+        assert_eq!(
+            collect_ints(
+                vec![EthereumSqlTypeWrapper::U64(U64::one())],
+                &input("values", "uint256[]"),
+                |w| {
+                    match w {
+                        EthereumSqlTypeWrapper::U256(v) => Some(v),
+                        _ => None,
+                    }
+                }
+            )
+            .unwrap_err(),
+            ParamDecodeError::IntArrayTypeMismatch("values".to_string())
+        );
+    }
+
+    #[test]
+    fn test_hashed_topics_are_stored_as_hashes() {
+        use crate::abi::GenerateAbiPropertiesType;
+
+        let inputs = vec![
+            ABIInput {
+                indexed: Some(true),
+                name: "label".to_string(),
+                type_: "string".to_string(),
+                components: None,
+            },
+            ABIInput {
+                indexed: Some(true),
+                name: "order".to_string(),
+                type_: "tuple".to_string(),
+                components: Some(vec![ABIInput {
+                    indexed: None,
+                    name: "amount".to_string(),
+                    type_: "uint256".to_string(),
+                    components: None,
+                }]),
+            },
+        ];
+
+        let columns: Vec<(String, String)> = ABIInput::generate_abi_name_properties(
+            &inputs,
+            &GenerateAbiPropertiesType::PostgresColumnsNamesOnly,
+            None,
+        )
+        .into_iter()
+        .map(|property| (property.value, property.abi_type))
+        .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("label_hash".to_string(), "bytes32".to_string()),
+                ("order_hash".to_string(), "bytes32".to_string())
+            ]
+        );
+
+        let params = vec![
+            LogParam { name: "label".to_string(), value: Token::FixedBytes(vec![1; 32]) },
+            LogParam { name: "order".to_string(), value: Token::FixedBytes(vec![2; 32]) },
+        ];
+        let wrappers = map_log_params_to_ethereum_wrapper(&inputs, &params, &[]).unwrap();
+        assert_eq!(wrappers.len(), 2);
+
+        let tx_information = TxInformation {
+            network: "ethereum".to_string(),
+            address: Address::zero(),
+            block_hash: H256::zero(),
+            block_number: U64::zero(),
+            transaction_hash: H256::zero(),
+            log_index: U256::zero(),
+            transaction_index: U64::zero(),
+            removed: false,
+            after_code_removed: false,
+        };
+        let json = map_ethereum_wrapper_to_json(&inputs, &wrappers, &tx_information, true);
+        assert!(json.get("label_hash").is_some());
+        assert!(json.get("order_hash").is_some());
+    }
+}
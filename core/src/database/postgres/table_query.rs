@@ -0,0 +1,325 @@
+use std::{collections::HashMap, str::FromStr};
+
+use ethers::types::{Address, Bytes, H256, I256, U256};
+use serde_json::Value;
+use tokio_postgres::types::ToSql;
+
+use crate::database::postgres::client::{PostgresClient, PostgresError};
+
+#[derive(thiserror::Error, Debug)]
+pub enum EventTableQueryError {
+    #[error("Tables are split per network so the query needs a network")]
+    NetworkRequired,
+
+    #[error("No table for network {0}")]
+    NoTableForNetwork(String),
+
+    #[error("Column {0} is missing from the row")]
+    MissingColumn(String),
+
+    #[error("Column {0} holds {1} which could not be parsed")]
+    InvalidValue(String, String),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+}
+
+/// Where the rows of an event live, one shared table with a network column or a table per network
+#[derive(Debug, Clone)]
+pub enum EventTables {
+    Shared(String),
+    PerNetwork(Vec<(String, String)>),
+}
+
+/// How a column is read back, everything else comes back as its text representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventColumnFormat {
+    Text,
+    /// Arrays, tuples and JSONB inputs
+    Json,
+}
+
+/// Read query over an event table built by the generated `{Event}Table::query()` helpers, values
+/// are compared as text cast to the column type so any column type keeps its indexes
+#[derive(Debug, Clone)]
+pub struct EventTableQuery {
+    tables: EventTables,
+    columns: Vec<(String, EventColumnFormat)>,
+    // column, column db type and the value as text
+    conditions: Vec<(String, String, String)>,
+    network: Option<String>,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+    descending: bool,
+    limit: Option<i64>,
+}
+
+impl EventTableQuery {
+    pub fn new(tables: EventTables, columns: Vec<(String, EventColumnFormat)>) -> Self {
+        Self {
+            tables,
+            columns,
+            conditions: vec![],
+            network: None,
+            from_block: None,
+            to_block: None,
+            descending: false,
+            limit: None,
+        }
+    }
+
+    pub fn where_eq(mut self, column: &str, db_type: &str, value: impl Into<String>) -> Self {
+        self.conditions.push((column.to_string(), db_type.to_string(), value.into()));
+        self
+    }
+
+    pub fn network(mut self, network: &str) -> Self {
+        self.network = Some(network.to_string());
+        self
+    }
+
+    /// Inclusive on both ends
+    pub fn between_blocks(mut self, from_block: u64, to_block: u64) -> Self {
+        self.from_block = Some(from_block);
+        self.to_block = Some(to_block);
+        self
+    }
+
+    pub fn from_block(mut self, from_block: u64) -> Self {
+        self.from_block = Some(from_block);
+        self
+    }
+
+    pub fn to_block(mut self, to_block: u64) -> Self {
+        self.to_block = Some(to_block);
+        self
+    }
+
+    /// Newest rows first, rows are returned in the order they were emitted by default
+    pub fn newest_first(mut self) -> Self {
+        self.descending = true;
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn table_name(&self) -> Result<&str, EventTableQueryError> {
+        match &self.tables {
+            EventTables::Shared(table_name) => Ok(table_name),
+            EventTables::PerNetwork(tables) => {
+                let network = self.network.as_ref().ok_or(EventTableQueryError::NetworkRequired)?;
+                tables
+                    .iter()
+                    .find(|(table_network, _)| table_network == network)
+                    .map(|(_, table_name)| table_name.as_str())
+                    .ok_or_else(|| EventTableQueryError::NoTableForNetwork(network.clone()))
+            }
+        }
+    }
+
+    /// The SQL and its text parameters
+    pub fn to_sql(&self) -> Result<(String, Vec<String>), EventTableQueryError> {
+        let mut params: Vec<String> = vec![];
+        let mut conditions: Vec<String> = vec![];
+
+        for (column, db_type, value) in &self.conditions {
+            params.push(value.clone());
+            conditions.push(format!(
+                "\"{}\" = CAST(${}::TEXT AS {})",
+                column,
+                params.len(),
+                db_type
+            ));
+        }
+        if let (Some(network), EventTables::Shared(_)) = (&self.network, &self.tables) {
+            params.push(network.clone());
+            conditions.push(format!("network = ${}::TEXT", params.len()));
+        }
+        if let Some(from_block) = self.from_block {
+            params.push(from_block.to_string());
+            conditions.push(format!("block_number >= ${}::TEXT::NUMERIC", params.len()));
+        }
+        if let Some(to_block) = self.to_block {
+            params.push(to_block.to_string());
+            conditions.push(format!("block_number <= ${}::TEXT::NUMERIC", params.len()));
+        }
+
+        let columns = self
+            .columns
+            .iter()
+            .map(|(column, format)| match format {
+                EventColumnFormat::Text => format!("\"{}\"::TEXT", column),
+                EventColumnFormat::Json => format!("to_json(\"{}\")::TEXT", column),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let direction = if self.descending { "DESC" } else { "ASC" };
+
+        let mut sql = format!("SELECT {} FROM {}", columns, self.table_name()?);
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        sql.push_str(&format!(
+            " ORDER BY block_number {direction}, tx_index {direction}, log_index::NUMERIC {direction}"
+        ));
+        if let Some(limit) = self.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        Ok((sql, params))
+    }
+
+    pub async fn fetch_rows(
+        &self,
+        client: &PostgresClient,
+    ) -> Result<Vec<EventTableRow>, EventTableQueryError> {
+        let (sql, params) = self.to_sql()?;
+        let params: Vec<&(dyn ToSql + Sync)> =
+            params.iter().map(|param| param as &(dyn ToSql + Sync)).collect();
+
+        let rows = client.query(&sql, &params).await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| EventTableRow {
+                values: self
+                    .columns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (column, _))| (column.clone(), row.get(index)))
+                    .collect(),
+            })
+            .collect())
+    }
+}
+
+/// A row read by `EventTableQuery` with every column as text, the generated row structs parse
+/// their typed fields from it
+#[derive(Debug, Clone)]
+pub struct EventTableRow {
+    values: HashMap<String, Option<String>>,
+}
+
+impl EventTableRow {
+    fn text(&self, column: &str) -> Result<&str, EventTableQueryError> {
+        self.values
+            .get(column)
+            .and_then(|value| value.as_deref())
+            .ok_or_else(|| EventTableQueryError::MissingColumn(column.to_string()))
+    }
+
+    fn invalid(&self, column: &str) -> EventTableQueryError {
+        EventTableQueryError::InvalidValue(
+            column.to_string(),
+            self.values.get(column).cloned().flatten().unwrap_or_default(),
+        )
+    }
+
+    /// Raw bytes of `0x` prefixed hex or BYTEA text output
+    pub fn bytes(&self, column: &str) -> Result<Bytes, EventTableQueryError> {
+        let text = self.text(column)?;
+        let hex = text.strip_prefix("\\x").or_else(|| text.strip_prefix("0x")).unwrap_or(text);
+        Bytes::from_str(hex.trim_end()).map_err(|_| self.invalid(column))
+    }
+
+    pub fn address(&self, column: &str) -> Result<Address, EventTableQueryError> {
+        let bytes = self.bytes(column)?;
+        if bytes.len() != 20 {
+            return Err(self.invalid(column));
+        }
+        Ok(Address::from_slice(&bytes))
+    }
+
+    pub fn h256(&self, column: &str) -> Result<H256, EventTableQueryError> {
+        let bytes = self.bytes(column)?;
+        if bytes.len() != 32 {
+            return Err(self.invalid(column));
+        }
+        Ok(H256::from_slice(&bytes))
+    }
+
+    pub fn u64(&self, column: &str) -> Result<u64, EventTableQueryError> {
+        self.text(column)?.parse().map_err(|_| self.invalid(column))
+    }
+
+    pub fn u256(&self, column: &str) -> Result<U256, EventTableQueryError> {
+        U256::from_dec_str(self.text(column)?).map_err(|_| self.invalid(column))
+    }
+
+    pub fn i256(&self, column: &str) -> Result<I256, EventTableQueryError> {
+        I256::from_dec_str(self.text(column)?).map_err(|_| self.invalid(column))
+    }
+
+    pub fn bool(&self, column: &str) -> Result<bool, EventTableQueryError> {
+        match self.text(column)? {
+            "true" | "t" => Ok(true),
+            "false" | "f" => Ok(false),
+            _ => Err(self.invalid(column)),
+        }
+    }
+
+    pub fn string(&self, column: &str) -> Result<String, EventTableQueryError> {
+        self.text(column).map(|text| text.to_string())
+    }
+
+    pub fn json(&self, column: &str) -> Result<Value, EventTableQueryError> {
+        match self.values.get(column) {
+            Some(Some(text)) => serde_json::from_str(text).map_err(|_| self.invalid(column)),
+            Some(None) => Ok(Value::Null),
+            None => Err(EventTableQueryError::MissingColumn(column.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_table_query_sql() {
+        let query = EventTableQuery::new(
+            EventTables::Shared("my_indexer_erc20.transfer".to_string()),
+            vec![
+                ("block_number".to_string(), EventColumnFormat::Text),
+                ("to".to_string(), EventColumnFormat::Text),
+            ],
+        )
+        .where_eq("to", "CHAR(42)", "0x0000000000000000000000000000000000000001")
+        .network("ethereum")
+        .between_blocks(10, 20)
+        .limit(5);
+
+        let (sql, params) = query.to_sql().unwrap();
+        assert_eq!(
+            sql,
+            "SELECT \"block_number\"::TEXT, \"to\"::TEXT FROM my_indexer_erc20.transfer WHERE \"to\" = CAST($1::TEXT AS CHAR(42)) AND network = $2::TEXT AND block_number >= $3::TEXT::NUMERIC AND block_number <= $4::TEXT::NUMERIC ORDER BY block_number ASC, tx_index ASC, log_index::NUMERIC ASC LIMIT 5"
+        );
+        assert_eq!(params.len(), 4);
+    }
+
+    #[test]
+    fn test_event_table_query_needs_network_per_network() {
+        let query = EventTableQuery::new(
+            EventTables::PerNetwork(vec![("base".to_string(), "base_erc20.transfer".to_string())]),
+            vec![],
+        );
+        assert!(matches!(query.to_sql(), Err(EventTableQueryError::NetworkRequired)));
+        assert!(query.network("base").to_sql().unwrap().0.contains("FROM base_erc20.transfer"));
+    }
+
+    #[test]
+    fn test_event_table_row_parses_bytea_and_hex() {
+        let row = EventTableRow {
+            values: [
+                ("a".to_string(), Some(format!("0x{}", "11".repeat(20)))),
+                ("b".to_string(), Some(format!("\\x{}", "11".repeat(20)))),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        assert_eq!(row.address("a").unwrap(), row.address("b").unwrap());
+        assert!(row.h256("a").is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use ethers::{
+    abi::{LogParam, Token},
+    utils::keccak256,
+};
+
+use crate::{
+    abi::ABIInput, database::postgres::internal_schema::internal_table, helpers::camel_to_snake,
+    types::code::Code,
+};
+
+pub fn topic_preimages_table_name(indexer_name: &str) -> String {
+    internal_table(&format!("{}_topic_preimages", camel_to_snake(indexer_name)))
+}
+
+/// Hashed topic columns can be joined on `hash` to get back the value which was indexed
+pub fn generate_topic_preimages_table_sql(indexer_name: &str) -> Code {
+    Code::new(format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {} (
+            hash BYTEA PRIMARY KEY,
+            preimage BYTEA NOT NULL,
+            preimage_text TEXT,
+            abi_type TEXT NOT NULL
+        );
+    "#,
+        topic_preimages_table_name(indexer_name)
+    ))
+}
+
+/// Records the keccak hash of every non indexed string and bytes value an event logs, the same
+/// value logged elsewhere as an indexed topic is only visible as that hash
+#[derive(Debug, Clone)]
+pub struct TopicPreimages {
+    insert_sql: String,
+    input_indexes: Vec<usize>,
+}
+
+impl TopicPreimages {
+    /// None when the event logs no values which could be the preimage of a topic
+    pub fn new(indexer_name: &str, inputs: &[ABIInput]) -> Option<Self> {
+        let input_indexes: Vec<usize> = inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| {
+                !input.indexed.unwrap_or_default() &&
+                    (input.type_ == "string" || input.type_ == "bytes")
+            })
+            .map(|(index, _)| index)
+            .collect();
+        if input_indexes.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            insert_sql: format!(
+                "INSERT INTO {} (hash, preimage, preimage_text, abi_type) \
+                VALUES (decode($1, 'hex'), decode($2, 'hex'), $3, $4) ON CONFLICT (hash) DO NOTHING",
+                topic_preimages_table_name(indexer_name)
+            ),
+            input_indexes,
+        })
+    }
+
+    pub fn for_event(&self, log_params: &[LogParam]) -> Vec<(String, Vec<Option<String>>)> {
+        self.input_indexes
+            .iter()
+            .filter_map(|index| {
+                let (preimage, preimage_text, abi_type) = match &log_params.get(*index)?.value {
+                    Token::String(value) => {
+                        (value.as_bytes().to_vec(), Some(value.clone()), "string")
+                    }
+                    Token::Bytes(value) => (value.clone(), None, "bytes"),
+                    _ => return None,
+                };
+
+                Some((
+                    self.insert_sql.clone(),
+                    vec![
+                        Some(hex::encode(keccak256(&preimage))),
+                        Some(hex::encode(&preimage)),
+                        preimage_text,
+                        Some(abi_type.to_string()),
+                    ],
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str, type_: &str, indexed: bool) -> ABIInput {
+        ABIInput {
+            indexed: Some(indexed),
+            name: name.to_string(),
+            type_: type_.to_string(),
+            components: None,
+        }
+    }
+
+    #[test]
+    fn test_topic_preimages_for_event() {
+        let inputs = vec![input("node", "string", true), input("name", "string", false)];
+        let preimages = TopicPreimages::new("MyIndexer", &inputs).unwrap();
+
+        let statements = preimages.for_event(&[
+            LogParam { name: "node".to_string(), value: Token::FixedBytes(vec![0; 32]) },
+            LogParam { name: "name".to_string(), value: Token::String("vitalik".to_string()) },
+        ]);
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].1[0], Some(hex::encode(keccak256(b"vitalik"))));
+        assert_eq!(statements[0].1[2], Some("vitalik".to_string()));
+
+        assert!(TopicPreimages::new("MyIndexer", &inputs[..1]).is_none());
+    }
+}
@@ -0,0 +1,129 @@
+use std::{fs, io, path::Path};
+
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        internal_schema::internal_table,
+    },
+    helpers::camel_to_snake,
+};
+
+pub const USER_MIGRATIONS_FOLDER: &str = "migrations";
+
+#[derive(thiserror::Error, Debug)]
+pub enum UserMigrationsError {
+    #[error("Could not read migrations: {0}")]
+    CouldNotReadMigrations(#[from] io::Error),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("Migration {0} failed: {1}")]
+    MigrationFailed(String, PostgresError),
+}
+
+fn applied_migrations_table_name(manifest_name: &str) -> String {
+    internal_table(&format!("{}_applied_migrations", camel_to_snake(manifest_name)))
+}
+
+pub fn generate_applied_migrations_table_sql(manifest_name: &str) -> String {
+    format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {} (
+            name TEXT PRIMARY KEY,
+            checksum TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    "#,
+        applied_migrations_table_name(manifest_name)
+    )
+}
+
+/// The `.sql` files in the migrations folder ordered by file name
+fn read_migrations(migrations_path: &Path) -> Result<Vec<(String, String)>, io::Error> {
+    let mut migrations = vec![];
+    for entry in fs::read_dir(migrations_path)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("sql") {
+            continue;
+        }
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        migrations.push((name.to_string(), fs::read_to_string(&path)?));
+    }
+    migrations.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(migrations)
+}
+
+/// Runs each `.sql` file in the project `migrations` folder which has not run before, every file
+/// runs in its own transaction along with recording it as applied. Applied files are never rerun
+/// so edits to them are only warned about.
+pub async fn run_user_migrations(
+    client: &PostgresClient,
+    project_path: &Path,
+    manifest_name: &str,
+) -> Result<(), UserMigrationsError> {
+    let migrations_path = project_path.join(USER_MIGRATIONS_FOLDER);
+    if !migrations_path.is_dir() {
+        return Ok(());
+    }
+
+    let table_name = applied_migrations_table_name(manifest_name);
+    let applied: Vec<(String, String)> = client
+        .query(&format!("SELECT name, checksum FROM {}", table_name), &[])
+        .await?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+
+    for (name, sql) in read_migrations(&migrations_path)? {
+        let checksum = hex::encode(Sha256::digest(sql.as_bytes()));
+        if let Some((_, applied_checksum)) = applied.iter().find(|(applied, _)| *applied == name) {
+            if *applied_checksum != checksum {
+                warn!("Migration {} has changed since it was applied, it will not be rerun", name);
+            }
+            continue;
+        }
+
+        info!("Applying migration {}", name);
+        let record_sql = format!(
+            "INSERT INTO {} (name, checksum) VALUES ('{}', '{}');",
+            table_name,
+            name.replace('\'', "''"),
+            checksum
+        );
+        client
+            .batch_execute_in_transaction(&format!("{}\n;\n{}", sql, record_sql))
+            .await
+            .map_err(|e| UserMigrationsError::MigrationFailed(name.clone(), e))?;
+        info!("Applied migration {}", name);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_sql_files_in_order() {
+        let dir = std::env::temp_dir().join(format!("rindexer-migrations-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("002_grants.sql"), "GRANT SELECT ON ALL TABLES TO reader;").unwrap();
+        fs::write(dir.join("001_extensions.sql"), "CREATE EXTENSION IF NOT EXISTS pg_trgm;")
+            .unwrap();
+        fs::write(dir.join("notes.md"), "not a migration").unwrap();
+
+        let names: Vec<String> =
+            read_migrations(&dir).unwrap().into_iter().map(|(name, _)| name).collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(names, vec!["001_extensions.sql", "002_grants.sql"]);
+    }
+}
@@ -0,0 +1,172 @@
+use std::path::Path;
+
+use tracing::info;
+
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        internal_schema::internal_schema,
+        relationship::{build_relationships, CreateRelationshipError, Relationship},
+    },
+    helpers::camel_to_snake,
+    manifest::core::Manifest,
+    types::code::Code,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum RelationshipViewsError {
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("Could not resolve relationships for views: {0}")]
+    RelationshipError(#[from] CreateRelationshipError),
+
+    #[error("Could not serialize relationship views: {0}")]
+    CouldNotParseViewsToJson(#[from] serde_json::Error),
+}
+
+fn split_table_name(table_name: &str) -> (&str, &str) {
+    table_name.split_once('.').unwrap_or(("public", table_name))
+}
+
+impl Relationship {
+    pub fn view_full_name(&self) -> String {
+        format!("{}.{}", split_table_name(&self.db_table_name).0, self.view_name)
+    }
+
+    /// Every column of the event table plus the linked table columns prefixed with the linked
+    /// event name
+    fn create_view_sql(&self, linked_columns: &[String]) -> Code {
+        let linked_prefix = camel_to_snake(&self.linked_to.event);
+        let linked_select: Vec<String> = linked_columns
+            .iter()
+            .map(|column| format!("l.\"{}\" AS \"{}_{}\"", column, linked_prefix, column))
+            .collect();
+
+        Code::new(format!(
+            r#"
+                CREATE VIEW {view_name} AS
+                SELECT e.*{linked_select}
+                FROM {db_table_name} e
+                LEFT JOIN {linked_db_table_name} l ON {join_condition};
+            "#,
+            view_name = self.view_full_name(),
+            linked_select = linked_select.iter().map(|c| format!(", {}", c)).collect::<String>(),
+            db_table_name = self.db_table_name,
+            linked_db_table_name = self.linked_to.db_table_name,
+            join_condition = self
+                .db_table_columns
+                .iter()
+                .zip(&self.linked_to.db_table_columns)
+                .map(|(column, linked_column)| format!("e.{} = l.{}", column, linked_column))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+        ))
+    }
+
+    fn drop_view_sql(&self) -> Code {
+        Code::new(format!("DROP VIEW IF EXISTS {};", self.view_full_name()))
+    }
+}
+
+async fn table_columns(
+    client: &PostgresClient,
+    table_name: &str,
+) -> Result<Vec<String>, PostgresError> {
+    let (schema, table) = split_table_name(table_name);
+    let rows = client
+        .query(
+            "SELECT column_name::TEXT FROM information_schema.columns WHERE table_schema = $1 AND table_name = $2 ORDER BY ordinal_position",
+            &[&schema, &table],
+        )
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Views depend on the event table columns so they have to go before any migration touches them
+pub async fn drop_last_known_relationship_views(
+    client: &PostgresClient,
+    manifest_name: &str,
+) -> Result<(), RelationshipViewsError> {
+    let row_opt = client
+        .query_one_or_none(
+            &format!(
+                "SELECT value FROM {}.{}_last_known_relationship_views_dropping_sql WHERE key = 1",
+                internal_schema(),
+                camel_to_snake(manifest_name)
+            ),
+            &[],
+        )
+        .await?;
+
+    if let Some(row) = row_opt {
+        let value: &str = row.get(0);
+        let dropping_sql: Vec<String> = serde_json::from_str(value)?;
+        for sql in dropping_sql {
+            client.execute_ddl(&sql, "drop the last known relationship views").await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a view per relationship joining the event table to the table it links to, the views
+/// created are saved so they can be dropped again when the relationships change
+pub async fn create_relationship_views(
+    client: &PostgresClient,
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<(), RelationshipViewsError> {
+    let relationships =
+        match manifest.storage.postgres.as_ref().and_then(|p| p.relationships.as_ref()) {
+            Some(foreign_keys) => build_relationships(
+                project_path,
+                &manifest.name,
+                &manifest.contracts,
+                foreign_keys,
+                manifest.storage.postgres_layout(),
+            )?,
+            None => vec![],
+        };
+
+    let mut dropping_sql: Vec<String> = vec![];
+    for relationship in &relationships {
+        let drop_sql = relationship.drop_view_sql();
+        if dropping_sql.iter().any(|sql| sql == drop_sql.as_str()) {
+            continue;
+        }
+
+        let linked_columns = table_columns(client, &relationship.linked_to.db_table_name).await?;
+        client.execute_ddl(drop_sql.as_str(), "recreate a relationship view").await?;
+        client
+            .execute_ddl(
+                relationship.create_view_sql(&linked_columns).as_str(),
+                "recreate a relationship view",
+            )
+            .await?;
+        info!(
+            "Created relationship view {} joining {} to {}",
+            relationship.view_full_name(),
+            relationship.db_table_name,
+            relationship.linked_to.db_table_name
+        );
+
+        dropping_sql.push(drop_sql.to_string());
+    }
+
+    client
+        .execute(
+            &format!(r#"
+                INSERT INTO {schema}.{manifest_name}_last_known_relationship_views_dropping_sql (key, value) VALUES (1, $1)
+                ON CONFLICT (key) DO UPDATE SET value = $1;
+            "#,
+                schema = internal_schema(),
+                manifest_name = camel_to_snake(&manifest.name)
+            ),
+            &[&serde_json::to_string(&dropping_sql)?],
+        )
+        .await?;
+
+    Ok(())
+}
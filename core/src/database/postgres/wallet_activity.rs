@@ -0,0 +1,117 @@
+use ethers::types::U64;
+
+use crate::{
+    database::postgres::client::{PostgresClient, PostgresError},
+    helpers::camel_to_snake,
+    manifest::wallet::TrackedWallet,
+    types::code::Code,
+};
+
+/// Inserted for every matched log, logs decoded with a manifest ABI also get
+/// `WALLET_ACTIVITY_DECODED_COLUMNS`
+pub const WALLET_ACTIVITY_COLUMNS: [&str; 11] = [
+    "wallet",
+    "network",
+    "block_number",
+    "block_hash",
+    "tx_hash",
+    "tx_index",
+    "log_index",
+    "contract_address",
+    "matched_by",
+    "topics",
+    "data",
+];
+
+pub const WALLET_ACTIVITY_DECODED_COLUMNS: [&str; 3] = ["contract_name", "event_name", "decoded"];
+
+pub fn generate_wallet_activity_schema_name(indexer_name: &str) -> String {
+    format!("{}_wallets", camel_to_snake(indexer_name))
+}
+
+pub fn wallet_activity_table_name(indexer_name: &str) -> String {
+    format!("{}.activity", generate_wallet_activity_schema_name(indexer_name))
+}
+
+fn wallet_activity_last_synced_table_name(indexer_name: &str) -> String {
+    format!("{}.last_synced", generate_wallet_activity_schema_name(indexer_name))
+}
+
+fn wallet_view_name(wallet: &TrackedWallet) -> String {
+    match &wallet.name {
+        Some(name) => format!("activity_{}", camel_to_snake(name)),
+        None => format!("activity_{}", hex::encode(wallet.address)),
+    }
+}
+
+/// One shared activity table with a view per wallet on top of it
+pub fn generate_wallet_activity_tables_sql(indexer_name: &str, wallets: &[TrackedWallet]) -> Code {
+    let schema_name = generate_wallet_activity_schema_name(indexer_name);
+    let table_name = wallet_activity_table_name(indexer_name);
+
+    let mut sql = format!(
+        "CREATE SCHEMA IF NOT EXISTS {schema_name};
+        CREATE TABLE IF NOT EXISTS {table_name} (
+            wallet CHAR(42) NOT NULL,
+            network VARCHAR(50) NOT NULL,
+            block_number NUMERIC NOT NULL,
+            block_hash CHAR(66) NOT NULL,
+            tx_hash CHAR(66) NOT NULL,
+            tx_index NUMERIC NOT NULL,
+            log_index VARCHAR(78) NOT NULL,
+            contract_address CHAR(42) NOT NULL,
+            matched_by VARCHAR(10) NOT NULL,
+            topics TEXT[] NOT NULL,
+            data TEXT NOT NULL,
+            contract_name TEXT,
+            event_name TEXT,
+            decoded JSONB,
+            PRIMARY KEY (network, tx_hash, log_index, wallet)
+        );
+        CREATE INDEX IF NOT EXISTS activity_wallet_block_number ON {table_name} (wallet, block_number);
+        CREATE TABLE IF NOT EXISTS {} (
+            network VARCHAR(50) PRIMARY KEY NOT NULL,
+            block_number NUMERIC NOT NULL
+        );",
+        wallet_activity_last_synced_table_name(indexer_name)
+    );
+
+    for wallet in wallets {
+        sql.push_str(&format!(
+            "CREATE OR REPLACE VIEW {}.{} AS SELECT * FROM {} WHERE LOWER(wallet) = '{:?}';",
+            schema_name,
+            wallet_view_name(wallet),
+            table_name,
+            wallet.address
+        ));
+    }
+
+    Code::new(sql)
+}
+
+pub async fn wallet_activity_last_synced(
+    client: &PostgresClient,
+    indexer_name: &str,
+    network: &str,
+) -> Result<Option<U64>, PostgresError> {
+    let row = client
+        .query_one_or_none(
+            &format!(
+                "SELECT block_number::TEXT FROM {} WHERE network = $1",
+                wallet_activity_last_synced_table_name(indexer_name)
+            ),
+            &[&network],
+        )
+        .await?;
+
+    Ok(row.and_then(|row| row.get::<_, String>(0).parse::<u64>().ok()).map(U64::from))
+}
+
+/// Takes the network and block number as TEXT params
+pub fn update_wallet_activity_last_synced_sql(indexer_name: &str) -> String {
+    format!(
+        "INSERT INTO {} (network, block_number) VALUES ($1, $2::NUMERIC) \
+        ON CONFLICT (network) DO UPDATE SET block_number = EXCLUDED.block_number",
+        wallet_activity_last_synced_table_name(indexer_name)
+    )
+}
@@ -0,0 +1,262 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    future::Future,
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use bb8::RunError;
+use rand::Rng;
+use tokio::{sync::Semaphore, time::sleep};
+use tokio_postgres::{error::SqlState, Error as PgError};
+use tracing::{info, warn};
+
+use crate::{
+    database::postgres::client::{BulkInsertPostgresError, PostgresError},
+    manifest::storage::PostgresWriteRetry,
+};
+
+static RETRIED_WRITES: AtomicU64 = AtomicU64::new(0);
+static RECOVERED_WRITES: AtomicU64 = AtomicU64::new(0);
+static EXHAUSTED_WRITES: AtomicU64 = AtomicU64::new(0);
+static REJECTED_WRITES: AtomicU64 = AtomicU64::new(0);
+
+/// Errors a write can hit while the database fails over or restarts
+pub trait WriteError: Display + Sized {
+    fn is_transient(&self) -> bool;
+
+    fn retries_exhausted(self, attempts: u32) -> Self;
+
+    fn is_retries_exhausted(&self) -> bool;
+}
+
+fn is_transient_pg_error(error: &PgError) -> bool {
+    if error.is_closed() {
+        return true;
+    }
+    match error.code() {
+        Some(code) => {
+            code.code().starts_with("08") ||
+                [
+                    SqlState::ADMIN_SHUTDOWN,
+                    SqlState::CRASH_SHUTDOWN,
+                    SqlState::CANNOT_CONNECT_NOW,
+                    SqlState::TOO_MANY_CONNECTIONS,
+                    SqlState::T_R_SERIALIZATION_FAILURE,
+                    SqlState::T_R_DEADLOCK_DETECTED,
+                    SqlState::READ_ONLY_SQL_TRANSACTION,
+                ]
+                .contains(code)
+        }
+        None => error.source().is_some_and(|source| source.is::<io::Error>()),
+    }
+}
+
+impl WriteError for PostgresError {
+    fn is_transient(&self) -> bool {
+        match self {
+            PostgresError::PgError(e) => is_transient_pg_error(e),
+            PostgresError::ConnectionPoolError(RunError::User(e)) => is_transient_pg_error(e),
+            PostgresError::ConnectionPoolError(RunError::TimedOut) => true,
+            PostgresError::WriteRetriesExhausted(..) => false,
+            PostgresError::DestructiveDdlNotConfirmed(_) => false,
+        }
+    }
+
+    fn retries_exhausted(self, attempts: u32) -> Self {
+        PostgresError::WriteRetriesExhausted(attempts, Box::new(self))
+    }
+
+    fn is_retries_exhausted(&self) -> bool {
+        matches!(self, PostgresError::WriteRetriesExhausted(..))
+    }
+}
+
+impl WriteError for BulkInsertPostgresError {
+    fn is_transient(&self) -> bool {
+        match self {
+            BulkInsertPostgresError::PostgresError(e) => e.is_transient(),
+            BulkInsertPostgresError::CouldNotWriteDataToPostgres(e) => is_transient_pg_error(e),
+        }
+    }
+
+    fn retries_exhausted(self, attempts: u32) -> Self {
+        BulkInsertPostgresError::PostgresError(match self {
+            BulkInsertPostgresError::PostgresError(e) => e.retries_exhausted(attempts),
+            BulkInsertPostgresError::CouldNotWriteDataToPostgres(e) => {
+                PostgresError::PgError(e).retries_exhausted(attempts)
+            }
+        })
+    }
+
+    fn is_retries_exhausted(&self) -> bool {
+        matches!(self, BulkInsertPostgresError::PostgresError(e) if e.is_retries_exhausted())
+    }
+}
+
+/// Retries writes which fail on transient errors with exponential backoff and jitter. Only
+/// `max_queued_writes` can be waiting at once so a long outage does not pile up every batch in
+/// memory.
+pub struct WriteRetryQueue {
+    settings: PostgresWriteRetry,
+    queued: Semaphore,
+}
+
+impl WriteRetryQueue {
+    pub fn new(settings: PostgresWriteRetry) -> Self {
+        let queued = Semaphore::new(settings.max_queued_writes());
+        Self { settings, queued }
+    }
+
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .settings
+            .initial_backoff()
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.settings.max_backoff());
+        let jitter_ms = rand::thread_rng().gen_range(0..=delay.as_millis() as u64 / 2);
+        delay + Duration::from_millis(jitter_ms)
+    }
+
+    pub async fn run<T, E, F, Fut>(&self, table_name: &str, mut write: F) -> Result<T, E>
+    where
+        E: WriteError,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let error = match write().await {
+            Ok(result) => return Ok(result),
+            Err(e) if !e.is_transient() => return Err(e),
+            Err(e) => e,
+        };
+
+        let Ok(_queued) = self.queued.try_acquire() else {
+            REJECTED_WRITES.fetch_add(1, Ordering::Relaxed);
+            warn!("Write retry queue is full - failing write to {}: {}", table_name, error);
+            return Err(error);
+        };
+
+        let max_attempts = self.settings.max_attempts();
+        let mut error = error;
+        for attempt in 2..=max_attempts {
+            let delay = self.backoff(attempt - 1);
+            warn!(
+                "Write to {} failed on a transient error, retrying in {:?} (attempt {} of {}): {}",
+                table_name, delay, attempt, max_attempts, error
+            );
+            RETRIED_WRITES.fetch_add(1, Ordering::Relaxed);
+            sleep(delay).await;
+
+            match write().await {
+                Ok(result) => {
+                    RECOVERED_WRITES.fetch_add(1, Ordering::Relaxed);
+                    info!("Write to {} succeeded after {} attempts", table_name, attempt);
+                    return Ok(result);
+                }
+                Err(e) if !e.is_transient() => return Err(e),
+                Err(e) => error = e,
+            }
+        }
+
+        EXHAUSTED_WRITES.fetch_add(1, Ordering::Relaxed);
+        Err(error.retries_exhausted(max_attempts))
+    }
+}
+
+/// Returns the (retried, recovered, exhausted, rejected) write counts since startup
+pub fn write_retry_metrics() -> (u64, u64, u64, u64) {
+    (
+        RETRIED_WRITES.load(Ordering::Relaxed),
+        RECOVERED_WRITES.load(Ordering::Relaxed),
+        EXHAUSTED_WRITES.load(Ordering::Relaxed),
+        REJECTED_WRITES.load(Ordering::Relaxed),
+    )
+}
+
+pub fn log_write_retry_metrics() {
+    let (retried, recovered, exhausted, rejected) = write_retry_metrics();
+    if retried + rejected == 0 {
+        return;
+    }
+
+    info!(
+        "Postgres write retries - {} retried, {} recovered, {} gave up, {} rejected by a full queue",
+        retried, recovered, exhausted, rejected
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum FakeError {
+        Transient,
+        Fatal,
+        Exhausted(u32),
+    }
+
+    impl Display for FakeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
+    impl WriteError for FakeError {
+        fn is_transient(&self) -> bool {
+            *self == FakeError::Transient
+        }
+
+        fn retries_exhausted(self, attempts: u32) -> Self {
+            FakeError::Exhausted(attempts)
+        }
+
+        fn is_retries_exhausted(&self) -> bool {
+            matches!(self, FakeError::Exhausted(_))
+        }
+    }
+
+    fn queue(max_attempts: u32) -> WriteRetryQueue {
+        WriteRetryQueue::new(PostgresWriteRetry {
+            max_attempts: Some(max_attempts),
+            initial_backoff_ms: Some(1),
+            max_backoff_ms: Some(1),
+            max_queued_writes: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors_until_attempts_run_out() {
+        let mut calls = 0;
+        let result = queue(5)
+            .run("table", || {
+                calls += 1;
+                let result = if calls < 3 { Err(FakeError::Transient) } else { Ok(calls) };
+                async move { result }
+            })
+            .await;
+        assert_eq!(result, Ok(3));
+
+        let mut calls = 0;
+        let result: Result<(), _> = queue(3)
+            .run("table", || {
+                calls += 1;
+                async { Err(FakeError::Transient) }
+            })
+            .await;
+        assert_eq!(result, Err(FakeError::Exhausted(3)));
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result: Result<(), _> = queue(3)
+            .run("table", || {
+                calls += 1;
+                async { Err(FakeError::Fatal) }
+            })
+            .await;
+        assert_eq!(result, Err(FakeError::Fatal));
+        assert_eq!(calls, 1);
+    }
+}
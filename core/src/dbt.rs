@@ -0,0 +1,270 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    abi::{ABIItem, ParamTypeError, ReadAbiError},
+    database::postgres::{
+        generate::{
+            event_column_descriptions, event_table_description, generate_event_table_full_names,
+        },
+        schema_drift::{event_table_columns_with_data_types, without_generated_expression},
+    },
+    manifest::{
+        computed_column::computed_columns_for_event, core::Manifest, solidity_enum::enums_for_event,
+    },
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum DbtError {
+    #[error("dbt models are generated from the postgres tables so postgres needs to be enabled")]
+    PostgresNotEnabled,
+
+    #[error("Could not read ABI items: {0}")]
+    CouldNotReadAbiItems(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
+
+    #[error("Could not write the dbt models: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Could not serialize the dbt yaml: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+}
+
+#[derive(Debug, Serialize)]
+struct DbtSources {
+    version: u8,
+    sources: Vec<DbtSource>,
+}
+
+#[derive(Debug, Serialize)]
+struct DbtSource {
+    name: String,
+    schema: String,
+    description: String,
+    tables: Vec<DbtTable>,
+}
+
+#[derive(Debug, Serialize)]
+struct DbtModels {
+    version: u8,
+    models: Vec<DbtTable>,
+}
+
+/// A source table or a staging model, both are documented the same way
+#[derive(Debug, Serialize)]
+struct DbtTable {
+    name: String,
+    description: String,
+    columns: Vec<DbtColumn>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DbtColumn {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    data_type: String,
+}
+
+#[derive(Debug, Default)]
+pub struct GeneratedDbtModels {
+    /// Names of the staging models written, one per event table
+    pub models: Vec<String>,
+}
+
+/// How a column is selected in the staging model and the type it ends up as, hashes and
+/// addresses are always hex text so models read the same with `compact_bytea` on or off
+fn staging_column(name: &str, db_type: &str) -> (String, String) {
+    let db_type = without_generated_expression(db_type).trim().to_lowercase();
+    let quoted = format!("\"{}\"", name);
+
+    let (select, data_type) = if db_type == "bytea" {
+        (format!("'0x' || encode({}, 'hex')", quoted), "text".to_string())
+    } else if db_type.ends_with("[]") {
+        (quoted.clone(), db_type)
+    } else if db_type.starts_with("char") || db_type.starts_with("varchar") {
+        // log_index is stored as text to fit any uint256 but it is always a number
+        if name == "log_index" {
+            (format!("cast({} as numeric)", quoted), "numeric".to_string())
+        } else {
+            (format!("cast({} as text)", quoted), "text".to_string())
+        }
+    } else {
+        (quoted.clone(), db_type)
+    };
+
+    (format!("{} as {}", select, quoted), data_type)
+}
+
+fn generate_staging_model_sql(schema: &str, table: &str, columns: &[(String, String)]) -> String {
+    let select = columns
+        .iter()
+        .map(|(name, db_type)| format!("    {}", staging_column(name, db_type).0))
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    format!(
+        "-- generated by `rindexer codegen dbt`, rerun it after changing the manifest\n\n\
+        with source as (\n    select * from {{{{ source('{schema}', '{table}') }}}}\n)\n\n\
+        select\n{select}\nfrom source\n"
+    )
+}
+
+/// dbt sources for every event table and a staging model over each of them, documented from the
+/// ABI like the table comments so the analytics pipeline can build on the indexed data
+pub fn generate_dbt_models(
+    project_path: &Path,
+    manifest: &Manifest,
+    output: &Path,
+) -> Result<GeneratedDbtModels, DbtError> {
+    if !manifest.storage.postgres_enabled() {
+        return Err(DbtError::PostgresNotEnabled);
+    }
+
+    let mut sources: BTreeMap<String, DbtSource> = BTreeMap::new();
+    let mut models = vec![];
+    let mut model_files: Vec<(PathBuf, String)> = vec![];
+
+    for contract in &manifest.contracts {
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+        let abi_items = ABIItem::read_abi_items(project_path, contract)?;
+
+        for event_info in ABIItem::extract_event_names_and_signatures_from_abi(abi_items)? {
+            let description = event_table_description(contract, &event_info);
+            let mut descriptions = event_column_descriptions(contract, &event_info);
+            for computed in computed_columns_for_event(&contract.computed_columns, &event_info.name)
+            {
+                descriptions
+                    .push((computed.name.clone(), format!("Computed as {}", computed.expression)));
+            }
+            for input_enum in enums_for_event(&contract.enums, &event_info.name) {
+                descriptions.push((
+                    input_enum.label_column_name(),
+                    format!("{} variant of {}", input_enum.name, input_enum.input_name),
+                ));
+            }
+
+            let columns =
+                event_table_columns_with_data_types(contract, &event_info, &manifest.storage);
+            let dbt_columns = |staged: bool| -> Vec<DbtColumn> {
+                columns
+                    .iter()
+                    .map(|(name, db_type)| DbtColumn {
+                        name: name.clone(),
+                        description: descriptions
+                            .iter()
+                            .find(|(column, _)| column == name)
+                            .map(|(_, description)| description.clone()),
+                        data_type: if staged {
+                            staging_column(name, db_type).1
+                        } else {
+                            without_generated_expression(db_type).trim().to_lowercase()
+                        },
+                    })
+                    .collect()
+            };
+
+            for full_table_name in generate_event_table_full_names(
+                manifest.storage.postgres_layout(),
+                &manifest.name,
+                contract,
+                &contract_name,
+                &event_info.name,
+            ) {
+                let Some((schema, table)) = full_table_name.split_once('.') else {
+                    continue;
+                };
+
+                sources
+                    .entry(schema.to_string())
+                    .or_insert_with(|| DbtSource {
+                        name: schema.to_string(),
+                        schema: schema.to_string(),
+                        description: format!(
+                            "Event tables indexed by rindexer for {}",
+                            manifest.name
+                        ),
+                        tables: vec![],
+                    })
+                    .tables
+                    .push(DbtTable {
+                        name: table.to_string(),
+                        description: description.clone(),
+                        columns: dbt_columns(false),
+                    });
+
+                let model_name = format!("stg_{}__{}", schema, table);
+                model_files.push((
+                    output.join(format!("{}.sql", model_name)),
+                    generate_staging_model_sql(schema, table, &columns),
+                ));
+                models.push(DbtTable {
+                    name: model_name,
+                    description: description.clone(),
+                    columns: dbt_columns(true),
+                });
+            }
+        }
+    }
+
+    fs::create_dir_all(output)?;
+    fs::write(
+        output.join("_rindexer__sources.yml"),
+        serde_yaml::to_string(&DbtSources {
+            version: 2,
+            sources: sources.into_values().collect(),
+        })?,
+    )?;
+    for (path, sql) in &model_files {
+        fs::write(path, sql)?;
+    }
+    let generated =
+        GeneratedDbtModels { models: models.iter().map(|model| model.name.clone()).collect() };
+    fs::write(
+        output.join("_rindexer__models.yml"),
+        serde_yaml::to_string(&DbtModels { version: 2, models })?,
+    )?;
+    info!("Wrote {} dbt staging models to {}", generated.models.len(), output.display());
+
+    Ok(generated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_staging_column_types() {
+        assert_eq!(
+            staging_column("tx_hash", "BYTEA"),
+            ("'0x' || encode(\"tx_hash\", 'hex') as \"tx_hash\"".to_string(), "text".to_string())
+        );
+        assert_eq!(
+            staging_column("log_index", "VARCHAR(78)"),
+            ("cast(\"log_index\" as numeric) as \"log_index\"".to_string(), "numeric".to_string())
+        );
+        assert_eq!(
+            staging_column("total", "NUMERIC GENERATED ALWAYS AS (a + b) STORED").1,
+            "numeric"
+        );
+    }
+
+    #[test]
+    fn test_generate_staging_model_sql() {
+        let sql = generate_staging_model_sql(
+            "my_indexer_erc20",
+            "transfer",
+            &[("from".to_string(), "CHAR(42)".to_string())],
+        );
+        assert!(sql.contains("select * from {{ source('my_indexer_erc20', 'transfer') }}"));
+        assert!(sql.contains("    cast(\"from\" as text) as \"from\"\nfrom source"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{any::Any, sync::Arc, time::Duration};
+use std::{any::Any, collections::HashMap, sync::Arc, time::Duration};
 
 use ethers::{
     addressbook::Address,
@@ -8,12 +8,15 @@ use ethers::{
 use futures::future::BoxFuture;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use tokio::time::sleep;
+use tokio::{sync::Semaphore, time::sleep};
 use tracing::{debug, error};
 
 use crate::{
     event::contract_setup::{ContractInformation, NetworkContract},
+    helpers::serialize_address,
     indexer::start::ProcessedNetworkContract,
+    manifest::{contract::Contract, runtime::RuntimeSettings},
+    quarantine::Quarantine,
 };
 
 pub type Decoder = Arc<dyn Fn(Vec<H256>, Bytes) -> Arc<dyn Any + Send + Sync> + Send + Sync>;
@@ -27,12 +30,21 @@ pub fn noop_decoder() -> Decoder {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TxInformation {
     pub network: String,
+    #[serde(serialize_with = "serialize_address")]
     pub address: Address,
     pub block_hash: H256,
     pub block_number: U64,
     pub transaction_hash: H256,
     pub log_index: U256,
     pub transaction_index: U64,
+    /// A reorg replaced the block of the log after it was handed to the handler, undo whatever
+    /// was done for it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub removed: bool,
+    /// The contract code was removed at or before the block so the log comes from whatever was
+    /// redeployed at the address, only set when the contract lifecycle annotates
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub after_code_removed: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +70,7 @@ impl EventResult {
     ) -> Self {
         let log_meta = LogMeta::from(&log);
         let log_address = log.address;
+        let removed = log.removed == Some(true);
         Self {
             log: log.clone(),
             decoded_data: network_contract.decode_log(log),
@@ -69,6 +82,8 @@ impl EventResult {
                 transaction_hash: log_meta.transaction_hash,
                 transaction_index: log_meta.transaction_index,
                 log_index: log_meta.log_index,
+                removed,
+                after_code_removed: false,
             },
             found_in_request: LogFoundInRequest { from_block: start_block, to_block: end_block },
         }
@@ -112,6 +127,10 @@ impl Clone for EventCallbackRegistryInformation {
 #[derive(Clone)]
 pub struct EventCallbackRegistry {
     pub events: Vec<EventCallbackRegistryInformation>,
+    /// Failing events are retried forever when not set
+    pub quarantine: Option<Arc<Quarantine>>,
+    /// Limits the handler calls in flight per event id, set by `apply_handler_limits`
+    pub(crate) handler_limits: HashMap<String, Arc<Semaphore>>,
 }
 
 impl Default for EventCallbackRegistry {
@@ -122,7 +141,11 @@ impl Default for EventCallbackRegistry {
 
 impl EventCallbackRegistry {
     pub fn new() -> Self {
-        EventCallbackRegistry { events: Vec::new() }
+        EventCallbackRegistry {
+            events: Vec::new(),
+            quarantine: None,
+            handler_limits: HashMap::new(),
+        }
     }
 
     pub fn find_event(&self, id: &String) -> Option<&EventCallbackRegistryInformation> {
@@ -140,6 +163,12 @@ impl EventCallbackRegistry {
         if let Some(event_information) = self.find_event(id) {
             debug!("{} - Pushed {} events", data.len(), event_information.info_log_name());
 
+            // held across retries so a failing handler does not let more calls in
+            let _permit = match self.handler_limits.get(id) {
+                Some(limit) => Some(limit.acquire().await.expect("Handler limit closed")),
+                None => None,
+            };
+
             loop {
                 match (event_information.callback)(data.clone()).await {
                     Ok(_) => {
@@ -156,6 +185,16 @@ impl EventCallbackRegistry {
                             event_information.info_log_name(), id, event_information.topic_id, attempts, e
                         );
 
+                        if let Some(quarantine) = &self.quarantine {
+                            if attempts >= quarantine.max_attempts() &&
+                                quarantine
+                                    .quarantine(event_information, &data, &e, attempts)
+                                    .await
+                            {
+                                break;
+                            }
+                        }
+
                         sleep(delay).await;
                         delay = (delay * 2).min(Duration::from_secs(15)); // Max delay of 15 seconds
 
@@ -170,6 +209,41 @@ impl EventCallbackRegistry {
         }
     }
 
+    /// Drops the networks an event has been restricted away from with `event_networks`
+    pub fn apply_event_networks(&mut self, contracts: &[Contract]) {
+        for event in self.events.iter_mut() {
+            let Some(contract) = contracts.iter().find(|c| {
+                c.name == event.contract.name ||
+                    c.before_modify_name_if_filter_readonly() == event.contract.name
+            }) else {
+                continue;
+            };
+
+            event.contract.details.retain(|details| {
+                contract.event_indexed_on_network(&event.event_name, &details.network)
+            });
+        }
+
+        self.events.retain(|event| !event.contract.details.is_empty());
+    }
+
+    /// Caps the handler calls in flight for events with a `handler_concurrency` or
+    /// `runtime.concurrency.handlers` limit
+    pub fn apply_handler_limits(&mut self, contracts: &[Contract], runtime: &RuntimeSettings) {
+        self.handler_limits = self
+            .events
+            .iter()
+            .filter_map(|event| {
+                let contract = contracts.iter().find(|c| {
+                    c.name == event.contract.name ||
+                        c.before_modify_name_if_filter_readonly() == event.contract.name
+                })?;
+                let permits = contract.event_max_concurrency(&event.event_name, runtime)?;
+                Some((event.id.clone(), Arc::new(Semaphore::new(permits))))
+            })
+            .collect();
+    }
+
     pub fn complete(&self) -> Arc<Self> {
         Arc::new(self.clone())
     }
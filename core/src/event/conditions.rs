@@ -1,4 +1,6 @@
-use ethers::prelude::U64;
+use std::cmp::Ordering;
+
+use ethers::prelude::U256;
 use serde_json::{Map, Value};
 
 fn get_nested_value(data: &Value, path: &str) -> Option<Value> {
@@ -41,24 +43,14 @@ fn evaluate_condition(value: &Value, condition: &str) -> bool {
                 };
 
                 and_result &= match op {
-                    ">=" => {
-                        U64::from_str_radix(value.as_str().unwrap_or("0"), 10).unwrap_or_default() >=
-                            U64::from_str_radix(comp, 10).unwrap_or_default()
-                    }
+                    ">=" => matches!(compare_numbers(value, comp), Some(o) if o != Ordering::Less),
                     "<=" => {
-                        U64::from_str_radix(value.as_str().unwrap_or("0"), 10).unwrap_or_default() <=
-                            U64::from_str_radix(comp, 10).unwrap_or_default()
-                    }
-                    ">" => {
-                        U64::from_str_radix(value.as_str().unwrap_or("0"), 10).unwrap_or_default() >
-                            U64::from_str_radix(comp, 10).unwrap_or_default()
+                        matches!(compare_numbers(value, comp), Some(o) if o != Ordering::Greater)
                     }
-                    "<" => {
-                        U64::from_str_radix(value.as_str().unwrap_or("0"), 10).unwrap_or_default() <
-                            U64::from_str_radix(comp, 10).unwrap_or_default()
-                    }
-                    "=" => value == &Value::String(comp.to_string()),
-                    "" => value == &Value::String(subpart.to_string()),
+                    ">" => compare_numbers(value, comp) == Some(Ordering::Greater),
+                    "<" => compare_numbers(value, comp) == Some(Ordering::Less),
+                    "=" => values_equal(value, comp),
+                    "" => values_equal(value, subpart),
                     _ => false,
                 };
             }
@@ -68,7 +60,82 @@ fn evaluate_condition(value: &Value, condition: &str) -> bool {
         }
         false
     } else {
-        value == &Value::String(condition.to_string())
+        values_equal(value, condition)
+    }
+}
+
+/// Decimal numbers with optional `10^21` or `1e21` exponent notation
+pub(crate) fn parse_number(value: &str) -> Option<U256> {
+    let value = value.trim();
+    if let Some((base, exponent)) = value.split_once('^') {
+        return U256::from_dec_str(base.trim()).ok()?.checked_pow(parse_exponent(exponent)?);
+    }
+    if let Some((mantissa, exponent)) = value.split_once(['e', 'E']) {
+        return U256::from_dec_str(mantissa.trim())
+            .ok()?
+            .checked_mul(U256::from(10).checked_pow(parse_exponent(exponent)?)?);
+    }
+    U256::from_dec_str(value).ok()
+}
+
+fn parse_exponent(exponent: &str) -> Option<U256> {
+    exponent.trim().parse::<u32>().ok().map(U256::from)
+}
+
+/// Numbers may be negative for signed inputs, `int*` inputs are decoded with a leading `-`
+pub(crate) fn parse_signed_number(value: &str) -> Option<(bool, U256)> {
+    let value = value.trim();
+    match value.strip_prefix('-') {
+        Some(value) => parse_number(value).map(|number| (!number.is_zero(), number)),
+        None => parse_number(value).map(|number| (false, number)),
+    }
+}
+
+/// Compares sign and magnitude so `int*` values order like I256 while `uint*` values keep the
+/// full U256 range
+pub(crate) fn compare_signed_numbers(left: (bool, U256), right: (bool, U256)) -> Ordering {
+    match (left.0, right.0) {
+        (false, false) => left.1.cmp(&right.1),
+        (true, true) => right.1.cmp(&left.1),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+    }
+}
+
+fn value_as_number(value: &Value) -> Option<(bool, U256)> {
+    match value {
+        Value::String(value) => parse_signed_number(value),
+        Value::Number(value) => parse_signed_number(&value.to_string()),
+        _ => None,
+    }
+}
+
+fn compare_numbers(value: &Value, comp: &str) -> Option<Ordering> {
+    Some(compare_signed_numbers(value_as_number(value)?, parse_signed_number(comp)?))
+}
+
+/// Hex values compare case insensitively so checksummed addresses match
+fn values_equal(value: &Value, comp: &str) -> bool {
+    let comp = comp.trim();
+    match value {
+        Value::String(value) if comp.starts_with("0x") => value.eq_ignore_ascii_case(comp),
+        Value::String(value) => value == comp,
+        Value::Number(value) => value.to_string() == comp,
+        Value::Bool(value) => value.to_string() == comp,
+        _ => false,
+    }
+}
+
+/// A list matches when any of its entries does
+fn evaluate_condition_value(value: &Value, condition: &Value) -> bool {
+    match condition {
+        Value::Array(options) => {
+            options.iter().any(|option| evaluate_condition_value(value, option))
+        }
+        Value::String(condition) => evaluate_condition(value, condition),
+        Value::Number(condition) => values_equal(value, &condition.to_string()),
+        Value::Bool(condition) => values_equal(value, &condition.to_string()),
+        _ => false,
     }
 }
 
@@ -79,7 +146,7 @@ pub fn filter_event_data_by_conditions(
     for condition in conditions {
         for (key, value) in condition {
             if let Some(event_value) = get_nested_value(event_data, key) {
-                if !evaluate_condition(&event_value, value.as_str().unwrap_or("")) {
+                if !evaluate_condition_value(&event_value, value) {
                     return false;
                 }
             } else {
@@ -89,3 +156,55 @@ pub fn filter_event_data_by_conditions(
     }
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn filters_large_values_and_lists() {
+        let conditions: Vec<Map<String, Value>> = serde_json::from_value(json!([{
+            "value": ">=10^21",
+            "to": ["0xAbC0000000000000000000000000000000000001", "0x2"]
+        }]))
+        .unwrap();
+
+        let matching = json!({
+            "value": "2000000000000000000000",
+            "to": "0xabc0000000000000000000000000000000000001"
+        });
+        let too_small = json!({ "value": "999", "to": "0x2" });
+        let not_listed = json!({ "value": "1e22", "to": "0x3" });
+
+        assert!(filter_event_data_by_conditions(&matching, &conditions));
+        assert!(!filter_event_data_by_conditions(&too_small, &conditions));
+        assert!(!filter_event_data_by_conditions(&not_listed, &conditions));
+    }
+
+    #[test]
+    fn compares_negative_int256_values() {
+        let conditions: Vec<Map<String, Value>> =
+            serde_json::from_value(json!([{ "delta": ">-10^18&&<0" }])).unwrap();
+
+        let int256_min = json!({
+            "delta": "-57896044618658097711785492504343953926634992332820282019728792003956564819968"
+        });
+
+        assert!(filter_event_data_by_conditions(&json!({ "delta": "-5" }), &conditions));
+        assert!(filter_event_data_by_conditions(&json!({ "delta": -5 }), &conditions));
+        assert!(!filter_event_data_by_conditions(&json!({ "delta": "-1e18" }), &conditions));
+        assert!(!filter_event_data_by_conditions(&int256_min, &conditions));
+        assert!(!filter_event_data_by_conditions(&json!({ "delta": "5" }), &conditions));
+        assert!(!filter_event_data_by_conditions(&json!({ "delta": "0" }), &conditions));
+
+        let above_zero: Vec<Map<String, Value>> =
+            serde_json::from_value(json!([{ "delta": ">=-1" }])).unwrap();
+        let uint256_max = json!({
+            "delta": "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+        });
+        assert!(filter_event_data_by_conditions(&uint256_max, &above_zero));
+        assert!(!filter_event_data_by_conditions(&int256_min, &above_zero));
+    }
+}
@@ -1,7 +1,7 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use ethers::prelude::{H256, U64};
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::Mutex;
 
 use crate::{
     event::{
@@ -9,8 +9,12 @@ use crate::{
         contract_setup::NetworkContract,
         BuildRindexerFilterError, RindexerEventFilter,
     },
-    indexer::IndexingEventsProgressState,
-    manifest::storage::CsvDetails,
+    indexer::{
+        CatchUp, CatchUpBackfill, ContractLifecycle, IndexingEventStats,
+        IndexingEventsProgressState, IndexingSlots, LastSyncedCheckpoint, LiveBlocks,
+        NetworkSchedule, RegistryAddresses, UnloggedHistoricSync,
+    },
+    manifest::{contract::ContractPriority, storage::CsvDetails},
     PostgresClient,
 };
 
@@ -21,30 +25,54 @@ pub struct EventProcessingConfig {
     pub contract_name: String,
     pub info_log_name: String,
     pub topic_id: H256,
+    pub anonymous: bool,
     pub event_name: String,
     pub network_contract: Arc<NetworkContract>,
     pub start_block: U64,
     pub end_block: U64,
-    pub semaphore: Arc<Semaphore>,
+    pub slots: Arc<IndexingSlots>,
+    pub priority: ContractPriority,
     pub registry: Arc<EventCallbackRegistry>,
     pub progress: Arc<Mutex<IndexingEventsProgressState>>,
+    pub stats: Arc<IndexingEventStats>,
     pub database: Option<Arc<PostgresClient>>,
+    pub unlogged_historic_sync: Option<Arc<UnloggedHistoricSync>>,
+    pub registry_addresses: Option<Arc<RegistryAddresses>>,
     pub csv_details: Option<CsvDetails>,
     pub stream_last_synced_block_file_path: Option<String>,
     pub index_event_in_order: bool,
     pub live_indexing: bool,
     pub indexing_distance_from_head: U64,
+    pub live_poll_interval: Duration,
+    pub live_blocks: Arc<LiveBlocks>,
+    pub checkpoint: LastSyncedCheckpoint,
+    pub lifecycle: Option<Arc<ContractLifecycle>>,
+    /// Set for every event on a network with `catch_up` settings
+    pub catch_up: Option<Arc<CatchUp>>,
+    /// Set when this event resumes far enough behind to be caught up, its historic requests and
+    /// writes are throttled by `catch_up` while live indexing runs from the head
+    pub catch_up_backfill: Option<Arc<CatchUpBackfill>>,
+    /// Set for every event on a network with a `schedule`, throttles all historic requests
+    pub schedule: Option<Arc<NetworkSchedule>>,
+    /// Fetched block ranges without logs are recorded in the heartbeats table
+    pub heartbeats: bool,
 }
 
 impl EventProcessingConfig {
     pub fn to_event_filter(&self) -> Result<RindexerEventFilter, BuildRindexerFilterError> {
-        RindexerEventFilter::new(
+        let filter = RindexerEventFilter::new(
             &self.topic_id,
             &self.event_name,
             &self.network_contract.indexing_contract_setup,
             self.start_block,
             self.end_block,
-        )
+        )?;
+
+        if self.anonymous {
+            Ok(filter.without_topic0())
+        } else {
+            Ok(filter)
+        }
     }
 
     pub async fn trigger_event(&self, fn_data: Vec<EventResult>) {
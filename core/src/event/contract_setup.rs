@@ -9,7 +9,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     event::callback_registry::Decoder,
     generate_random_id,
-    manifest::contract::{Contract, EventInputIndexedFilters},
+    manifest::contract::{AddressRegistry, CheckpointConfig, Contract, EventInputIndexedFilters},
     provider::{CreateNetworkProvider, JsonRpcCachedProvider},
 };
 
@@ -41,6 +41,7 @@ pub struct ContractInformation {
     pub details: Vec<NetworkContract>,
     pub abi: String,
     pub reorg_safe_distance: bool,
+    pub checkpoint: Option<CheckpointConfig>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -85,6 +86,7 @@ impl ContractInformation {
             details,
             abi: contract.abi.clone(),
             reorg_safe_distance: contract.reorg_safe_distance.unwrap_or_default(),
+            checkpoint: contract.checkpoint.clone(),
         })
     }
 }
@@ -100,6 +102,9 @@ pub struct AddressDetails {
     pub address: ValueOrArray<Address>,
 
     pub indexed_filters: Option<Vec<EventInputIndexedFilters>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<AddressRegistry>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
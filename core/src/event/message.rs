@@ -6,4 +6,7 @@ pub struct EventMessage {
     pub event_name: String,
     pub event_data: Value,
     pub network: String,
+    /// Set when the provider dropped the logs in a reorg, consumers should undo the events
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub removed: bool,
 }
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use ethers::{
+    prelude::ProviderError,
+    types::{Address, Block, Transaction, H256, U256},
+};
+
+use crate::{event::callback_registry::TxInformation, provider::JsonRpcCachedProvider};
+
+#[derive(thiserror::Error, Debug)]
+pub enum EventMetadataError {
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("Transaction {0:?} not found")]
+    TransactionNotFound(H256),
+}
+
+/// The generated `get_provider_cache_for_network` of the project
+pub type ProviderForNetwork = fn(&str) -> Arc<JsonRpcCachedProvider>;
+
+/// Block and transaction details of the events a handler is called with, fetched on first use
+/// through the block and transaction caches of the network provider
+pub struct EventMetadata {
+    provider_for_network: ProviderForNetwork,
+}
+
+impl EventMetadata {
+    pub fn new(provider_for_network: ProviderForNetwork) -> Self {
+        Self { provider_for_network }
+    }
+
+    pub async fn block(&self, tx: &TxInformation) -> Result<Arc<Block<H256>>, EventMetadataError> {
+        Ok((self.provider_for_network)(&tx.network).get_block_header(tx.block_number).await?)
+    }
+
+    pub async fn block_timestamp(&self, tx: &TxInformation) -> Result<U256, EventMetadataError> {
+        Ok(self.block(tx).await?.timestamp)
+    }
+
+    pub async fn transaction(
+        &self,
+        tx: &TxInformation,
+    ) -> Result<Arc<Transaction>, EventMetadataError> {
+        (self.provider_for_network)(&tx.network)
+            .get_transaction(tx.transaction_hash)
+            .await?
+            .ok_or(EventMetadataError::TransactionNotFound(tx.transaction_hash))
+    }
+
+    pub async fn tx_from(&self, tx: &TxInformation) -> Result<Address, EventMetadataError> {
+        Ok(self.transaction(tx).await?.from)
+    }
+
+    /// None for contract creations
+    pub async fn tx_to(&self, tx: &TxInformation) -> Result<Option<Address>, EventMetadataError> {
+        Ok(self.transaction(tx).await?.to)
+    }
+
+    pub async fn tx_value(&self, tx: &TxInformation) -> Result<U256, EventMetadataError> {
+        Ok(self.transaction(tx).await?.value)
+    }
+}
@@ -9,5 +9,11 @@ pub use rindexer_event_filter::{BuildRindexerFilterError, RindexerEventFilter};
 mod message;
 pub use message::EventMessage;
 
+mod metadata;
+pub use metadata::{EventMetadata, EventMetadataError, ProviderForNetwork};
+
 mod conditions;
 pub use conditions::filter_event_data_by_conditions;
+
+mod where_expression;
+pub use where_expression::{WhereExpression, WhereExpressionError};
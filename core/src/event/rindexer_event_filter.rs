@@ -1,7 +1,7 @@
 use ethers::{
     addressbook::Address,
     prelude::{BlockNumber, Filter, H256, U64},
-    types::ValueOrArray,
+    types::{Log, ValueOrArray},
 };
 
 use crate::event::contract_setup::IndexingContractSetup;
@@ -101,6 +101,27 @@ impl RindexerEventFilter {
         }
     }
 
+    /// Logs emitted by the addresses when `topic_position` is `None`, otherwise logs with one of
+    /// the addresses at that topic position
+    pub fn new_wallet_activity(
+        addresses: &[Address],
+        topic_position: Option<usize>,
+        current_block: U64,
+        next_block: U64,
+    ) -> RindexerEventFilter {
+        let mut filter = Filter::new().from_block(current_block).to_block(next_block);
+        match topic_position {
+            Some(position) => {
+                filter.topics[position] = Some(ValueOrArray::Array(
+                    addresses.iter().map(|address| Some(H256::from(*address))).collect(),
+                ));
+            }
+            None => filter = filter.address(addresses.to_vec()),
+        }
+
+        RindexerEventFilter::from_filter(filter)
+    }
+
     pub fn get_to_block(&self) -> U64 {
         self.filter
             .get_to_block()
@@ -123,6 +144,24 @@ impl RindexerEventFilter {
         self
     }
 
+    pub fn set_address(mut self, address: ValueOrArray<Address>) -> Self {
+        self.filter = self.filter.address(address);
+        self
+    }
+
+    /// Anonymous events do not emit the event signature as topic0 so only the address is used
+    pub fn without_topic0(mut self) -> Self {
+        self.filter.topics[0] = None;
+        self
+    }
+
+    pub fn topic0(&self) -> Option<H256> {
+        match &self.filter.topics[0] {
+            Some(ValueOrArray::Value(topic)) => *topic,
+            _ => None,
+        }
+    }
+
     pub fn contract_address(&self) -> Option<ValueOrArray<Address>> {
         self.filter.address.clone()
     }
@@ -130,4 +169,24 @@ impl RindexerEventFilter {
     pub fn raw_filter(&self) -> &Filter {
         &self.filter
     }
+
+    /// Whether `eth_getLogs` would return the log for this filter, the block range is not checked
+    pub fn matches(&self, log: &Log) -> bool {
+        let address_matches = match &self.filter.address {
+            Some(ValueOrArray::Value(address)) => *address == log.address,
+            Some(ValueOrArray::Array(addresses)) => {
+                addresses.is_empty() || addresses.contains(&log.address)
+            }
+            None => true,
+        };
+
+        address_matches &&
+            self.filter.topics.iter().enumerate().all(|(i, topic)| match topic {
+                Some(ValueOrArray::Value(Some(expected))) => log.topics.get(i) == Some(expected),
+                Some(ValueOrArray::Array(expected)) if expected.iter().any(Option::is_some) => {
+                    log.topics.get(i).is_some_and(|topic| expected.contains(&Some(*topic)))
+                }
+                _ => true,
+            })
+    }
 }
@@ -0,0 +1,325 @@
+use std::cmp::Ordering;
+
+use crate::event::conditions::{compare_signed_numbers, parse_signed_number};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum WhereExpressionError {
+    #[error("Unexpected character {0} at position {1}")]
+    UnexpectedCharacter(char, usize),
+
+    #[error("Unterminated string starting at position {0}")]
+    UnterminatedString(usize),
+
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+
+    #[error("Unexpected {0}")]
+    UnexpectedToken(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    Identifier(String),
+    Literal(String),
+    Comparison(Comparison),
+    And,
+    Or,
+    Not,
+    OpenParen,
+    CloseParen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Input(Vec<String>),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    Or(Box<Expression>, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Compare(Operand, Comparison, Operand),
+    Truthy(Operand),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Lexeme>, WhereExpressionError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut lexemes = vec![];
+    let mut position = 0;
+    while position < chars.len() {
+        let c = chars[position];
+        let next = chars.get(position + 1).copied();
+        let (lexeme, length) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                position += 1;
+                continue;
+            }
+            ('&', Some('&')) => (Lexeme::And, 2),
+            ('|', Some('|')) => (Lexeme::Or, 2),
+            ('=', Some('=')) => (Lexeme::Comparison(Comparison::Equal), 2),
+            ('!', Some('=')) => (Lexeme::Comparison(Comparison::NotEqual), 2),
+            ('>', Some('=')) => (Lexeme::Comparison(Comparison::GreaterOrEqual), 2),
+            ('<', Some('=')) => (Lexeme::Comparison(Comparison::LessOrEqual), 2),
+            ('=', _) => (Lexeme::Comparison(Comparison::Equal), 1),
+            ('>', _) => (Lexeme::Comparison(Comparison::Greater), 1),
+            ('<', _) => (Lexeme::Comparison(Comparison::Less), 1),
+            ('!', _) => (Lexeme::Not, 1),
+            ('(', _) => (Lexeme::OpenParen, 1),
+            (')', _) => (Lexeme::CloseParen, 1),
+            ('\'' | '"', _) => {
+                let end = chars[position + 1..]
+                    .iter()
+                    .position(|other| *other == c)
+                    .ok_or(WhereExpressionError::UnterminatedString(position))?;
+                let value: String = chars[position + 1..position + 1 + end].iter().collect();
+                (Lexeme::Literal(value), end + 2)
+            }
+            (c, _) if c.is_ascii_alphanumeric() || c == '_' || c == '-' => {
+                let length = chars[position + 1..]
+                    .iter()
+                    .take_while(|other| {
+                        other.is_ascii_alphanumeric() || matches!(other, '_' | '.' | '^')
+                    })
+                    .count() +
+                    1;
+                let word: String = chars[position..position + length].iter().collect();
+                let lexeme = if c.is_ascii_alphabetic() || c == '_' {
+                    match word.as_str() {
+                        "and" => Lexeme::And,
+                        "or" => Lexeme::Or,
+                        "not" => Lexeme::Not,
+                        "true" | "false" => Lexeme::Literal(word),
+                        _ => Lexeme::Identifier(word),
+                    }
+                } else {
+                    Lexeme::Literal(word)
+                };
+                (lexeme, length)
+            }
+            (c, _) => return Err(WhereExpressionError::UnexpectedCharacter(c, position)),
+        };
+        lexemes.push(lexeme);
+        position += length;
+    }
+
+    Ok(lexemes)
+}
+
+struct Parser {
+    lexemes: Vec<Lexeme>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Lexeme, WhereExpressionError> {
+        let lexeme = self.peek().cloned().ok_or(WhereExpressionError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(lexeme)
+    }
+
+    fn or(&mut self) -> Result<Expression, WhereExpressionError> {
+        let mut expression = self.and()?;
+        while self.peek() == Some(&Lexeme::Or) {
+            self.position += 1;
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<Expression, WhereExpressionError> {
+        let mut expression = self.unary()?;
+        while self.peek() == Some(&Lexeme::And) {
+            self.position += 1;
+            expression = Expression::And(Box::new(expression), Box::new(self.unary()?));
+        }
+        Ok(expression)
+    }
+
+    fn unary(&mut self) -> Result<Expression, WhereExpressionError> {
+        match self.peek() {
+            Some(Lexeme::Not) => {
+                self.position += 1;
+                Ok(Expression::Not(Box::new(self.unary()?)))
+            }
+            Some(Lexeme::OpenParen) => {
+                self.position += 1;
+                let expression = self.or()?;
+                match self.next()? {
+                    Lexeme::CloseParen => Ok(expression),
+                    other => Err(WhereExpressionError::UnexpectedToken(format!("{:?}", other))),
+                }
+            }
+            _ => {
+                let left = self.operand()?;
+                match self.peek() {
+                    Some(Lexeme::Comparison(comparison)) => {
+                        let comparison = *comparison;
+                        self.position += 1;
+                        Ok(Expression::Compare(left, comparison, self.operand()?))
+                    }
+                    _ => Ok(Expression::Truthy(left)),
+                }
+            }
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, WhereExpressionError> {
+        match self.next()? {
+            Lexeme::Identifier(name) => {
+                Ok(Operand::Input(name.split('.').map(str::to_string).collect()))
+            }
+            Lexeme::Literal(value) => Ok(Operand::Literal(value)),
+            other => Err(WhereExpressionError::UnexpectedToken(format!("{:?}", other))),
+        }
+    }
+}
+
+fn compare_values(left: &str, right: &str) -> Ordering {
+    if let (Some(left), Some(right)) = (parse_signed_number(left), parse_signed_number(right)) {
+        return compare_signed_numbers(left, right);
+    }
+    // hex compares case insensitively so checksummed addresses match
+    if left.starts_with("0x") && right.starts_with("0x") {
+        return left.to_lowercase().cmp(&right.to_lowercase());
+    }
+    left.cmp(right)
+}
+
+/// A `where:` expression deciding if a decoded event is stored, e.g.
+/// `value >= 10^21 && from != '0x0000000000000000000000000000000000000000'`. Inputs are referred
+/// to by name (dot separated for tuple fields), values are compared as numbers when both sides
+/// are numbers, a missing input makes its comparison false
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhereExpression {
+    expression: Expression,
+}
+
+impl WhereExpression {
+    pub fn parse(expression: &str) -> Result<Self, WhereExpressionError> {
+        let mut parser = Parser { lexemes: tokenize(expression)?, position: 0 };
+        let expression = parser.or()?;
+        if let Some(lexeme) = parser.peek() {
+            return Err(WhereExpressionError::UnexpectedToken(format!("{:?}", lexeme)));
+        }
+
+        Ok(Self { expression })
+    }
+
+    /// Every input path the expression refers to
+    pub fn inputs(&self) -> Vec<Vec<String>> {
+        fn collect(expression: &Expression, inputs: &mut Vec<Vec<String>>) {
+            let mut push = |operand: &Operand| {
+                if let Operand::Input(path) = operand {
+                    inputs.push(path.clone());
+                }
+            };
+            match expression {
+                Expression::Or(left, right) | Expression::And(left, right) => {
+                    collect(left, inputs);
+                    collect(right, inputs);
+                }
+                Expression::Not(inner) => collect(inner, inputs),
+                Expression::Compare(left, _, right) => {
+                    push(left);
+                    push(right);
+                }
+                Expression::Truthy(operand) => push(operand),
+            }
+        }
+
+        let mut inputs = vec![];
+        collect(&self.expression, &mut inputs);
+        inputs
+    }
+
+    /// `input_value` gives the decoded value of an input path as text
+    pub fn matches(&self, input_value: &impl Fn(&[String]) -> Option<String>) -> bool {
+        Self::evaluate(&self.expression, input_value)
+    }
+
+    fn evaluate(
+        expression: &Expression,
+        input_value: &impl Fn(&[String]) -> Option<String>,
+    ) -> bool {
+        let value = |operand: &Operand| match operand {
+            Operand::Input(path) => input_value(path),
+            Operand::Literal(value) => Some(value.clone()),
+        };
+        match expression {
+            Expression::Or(left, right) => {
+                Self::evaluate(left, input_value) || Self::evaluate(right, input_value)
+            }
+            Expression::And(left, right) => {
+                Self::evaluate(left, input_value) && Self::evaluate(right, input_value)
+            }
+            Expression::Not(inner) => !Self::evaluate(inner, input_value),
+            Expression::Truthy(operand) => value(operand).is_some_and(|value| value == "true"),
+            Expression::Compare(left, comparison, right) => {
+                let (Some(left), Some(right)) = (value(left), value(right)) else {
+                    return false;
+                };
+                let ordering = compare_values(&left, &right);
+                match comparison {
+                    Comparison::Equal => ordering == Ordering::Equal,
+                    Comparison::NotEqual => ordering != Ordering::Equal,
+                    Comparison::Greater => ordering == Ordering::Greater,
+                    Comparison::GreaterOrEqual => ordering != Ordering::Less,
+                    Comparison::Less => ordering == Ordering::Less,
+                    Comparison::LessOrEqual => ordering != Ordering::Greater,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn evaluates_where_expressions() {
+        let values = HashMap::from([
+            ("value", "2000000000000000000000"),
+            ("from", "0xAbC0000000000000000000000000000000000001"),
+            ("delta", "-5"),
+            ("approved", "true"),
+            ("order.tokenId", "42"),
+        ]);
+        let input_value =
+            |path: &[String]| values.get(path.join(".").as_str()).map(|value| value.to_string());
+        let matches =
+            |expression: &str| WhereExpression::parse(expression).unwrap().matches(&input_value);
+
+        assert!(matches("value >= 10^21"));
+        assert!(!matches("value < 1e21"));
+        assert!(matches("from == '0xabc0000000000000000000000000000000000001'"));
+        assert!(matches("delta < 0 && delta >= -5"));
+        assert!(matches("approved && !(order.tokenId > 100 || order.tokenId < 10)"));
+        assert!(!matches("missing == 1 || missing != 1"));
+
+        assert_eq!(
+            WhereExpression::parse("value >=").unwrap_err(),
+            WhereExpressionError::UnexpectedEnd
+        );
+        assert!(WhereExpression::parse("value > 1 )").is_err());
+        assert!(WhereExpression::parse("from == 'abc").is_err());
+    }
+}
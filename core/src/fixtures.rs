@@ -0,0 +1,421 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ethers::{
+    prelude::ProviderError,
+    types::{Filter, U64},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::info;
+
+use crate::{
+    abi::{ABIItem, EventInfo, ParamTypeError, ReadAbiError},
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        generate::generate_column_definitions,
+        schema_drift::base_columns_with_data_types,
+    },
+    indexer::{decode_event_row, DecodeError, DecodedLogOutcome},
+    manifest::{contract::Contract, core::Manifest, transform::transforms_for_event},
+    provider::{CreateNetworkProvider, RetryClientError},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum FixturesError {
+    #[error("Network {0} not found in the manifest")]
+    NetworkNotFound(String),
+
+    #[error("Could not read ABI items: {0}")]
+    CouldNotReadAbiItems(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
+
+    #[error("{0}")]
+    DecodeError(#[from] DecodeError),
+
+    #[error("{0}")]
+    RetryClientError(#[from] RetryClientError),
+
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("Could not read or write fixtures: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Fixture {0} is invalid: {1}")]
+    InvalidFixture(String, String),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+}
+
+/// The rows recorded for one event table along with the column types to recreate it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureTable {
+    /// Schema qualified name of the event table the rows were decoded for
+    pub table_name: String,
+    pub columns: Vec<FixtureColumn>,
+    pub rows: Vec<Map<String, Value>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureColumn {
+    pub name: String,
+    pub db_type: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenerateFixturesOptions {
+    /// Only record logs on this network
+    pub network: Option<String>,
+    /// How many of the latest blocks logs are recorded from
+    pub blocks: u64,
+    pub max_rows_per_table: usize,
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Default)]
+pub struct GeneratedFixtures {
+    /// Table name and how many rows were written for it
+    pub tables: Vec<(String, usize)>,
+}
+
+const RINDEXER_ID_COLUMN: &str = "rindexer_id";
+
+/// Columns of the event table the decoded rows fill, price enrichments, templates and computed
+/// columns need more than the log so they are left out
+fn fixture_columns(
+    manifest: &Manifest,
+    contract: &Contract,
+    event_info: &EventInfo,
+) -> Vec<FixtureColumn> {
+    let compact_bytea = manifest.storage.postgres_compact_bytea();
+    let mut columns = base_columns_with_data_types(
+        manifest.storage.postgres_natural_primary_key(),
+        compact_bytea,
+    );
+    columns.extend(generate_column_definitions(
+        &event_info.inputs,
+        &transforms_for_event(&contract.transforms, &event_info.name),
+        &contract.jsonb_inputs(&event_info.name, &event_info.inputs),
+        compact_bytea,
+    ));
+
+    columns
+        .into_iter()
+        .map(|(name, db_type)| {
+            // the id is generated as the indexer's tables do, rows never carry it
+            let db_type = if name == RINDEXER_ID_COLUMN { "SERIAL".to_string() } else { db_type };
+            FixtureColumn { name, db_type }
+        })
+        .collect()
+}
+
+/// BYTEA columns are loaded from postgres hex input so `0x` prefixed and bare hex become `\x`
+fn fixture_value(value: Value, db_type: &str) -> Value {
+    if !db_type.to_uppercase().starts_with("BYTEA") {
+        return value;
+    }
+
+    match value {
+        Value::String(hex) => {
+            Value::String(format!("\\x{}", hex.strip_prefix("0x").unwrap_or(&hex).to_lowercase()))
+        }
+        Value::Array(values) => {
+            Value::Array(values.into_iter().map(|value| fixture_value(value, "BYTEA")).collect())
+        }
+        value => value,
+    }
+}
+
+/// Records the logs of the latest blocks for every contract with addresses and decodes them the
+/// way the indexer would write them, one JSON fixture per event table in `output`
+pub async fn generate_fixtures(
+    project_path: &Path,
+    manifest: &Manifest,
+    options: &GenerateFixturesOptions,
+) -> Result<GeneratedFixtures, FixturesError> {
+    if let Some(network) = &options.network {
+        if !manifest.networks.iter().any(|n| &n.name == network) {
+            return Err(FixturesError::NetworkNotFound(network.clone()));
+        }
+    }
+
+    let providers = CreateNetworkProvider::create(manifest)?;
+    let mut tables: BTreeMap<String, FixtureTable> = BTreeMap::new();
+
+    for contract in &manifest.contracts {
+        let abi_items = ABIItem::get_abi_items(project_path, contract, contract.is_filter())?;
+        let event_infos = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
+
+        for details in &contract.details {
+            if options.network.as_ref().is_some_and(|network| network != &details.network) {
+                continue;
+            }
+            // filters index any address so there is nothing to record logs from
+            let Some(address) = details.address() else {
+                continue;
+            };
+            let Some(provider) = providers.iter().find(|p| p.network_name == details.network)
+            else {
+                return Err(FixturesError::NetworkNotFound(details.network.clone()));
+            };
+
+            let latest_block = provider.client.get_block_number().await?;
+            let from_block = latest_block.saturating_sub(U64::from(options.blocks.max(1) - 1));
+            let step = provider
+                .client
+                .max_block_range
+                .unwrap_or(latest_block - from_block + 1)
+                .max(U64::one());
+
+            let mut chunk_from = from_block;
+            while chunk_from <= latest_block {
+                let chunk_to = std::cmp::min(chunk_from + step - 1, latest_block);
+                let filter = Filter::new()
+                    .address(address.clone())
+                    .from_block(chunk_from)
+                    .to_block(chunk_to);
+                let logs = provider.client.get_filter_logs(&filter).await?;
+                chunk_from = chunk_to + 1;
+
+                for log in logs {
+                    let Some(event_info) = log.topics.first().and_then(|topic0| {
+                        event_infos.iter().find(|event_info| event_info.topic_id() == *topic0)
+                    }) else {
+                        continue;
+                    };
+                    if !contract.event_indexed_on_network(&event_info.name, &details.network) {
+                        continue;
+                    }
+
+                    let DecodedLogOutcome::Row { table_name, columns } = decode_event_row(
+                        project_path,
+                        manifest,
+                        &details.network,
+                        contract,
+                        event_info,
+                        &log,
+                    )?
+                    else {
+                        continue;
+                    };
+
+                    let table = tables.entry(table_name.clone()).or_insert_with(|| FixtureTable {
+                        table_name,
+                        columns: fixture_columns(manifest, contract, event_info),
+                        rows: vec![],
+                    });
+                    if table.rows.len() >= options.max_rows_per_table {
+                        continue;
+                    }
+
+                    let row = columns
+                        .into_iter()
+                        .map(|(name, value)| {
+                            let db_type = table
+                                .columns
+                                .iter()
+                                .find(|column| column.name == name)
+                                .map_or("", |column| column.db_type.as_str());
+                            let value = fixture_value(value, db_type);
+                            (name, value)
+                        })
+                        .collect();
+                    table.rows.push(row);
+                }
+            }
+        }
+    }
+
+    fs::create_dir_all(&options.output)?;
+    let mut generated = GeneratedFixtures::default();
+    for (table_name, table) in tables {
+        let path = options.output.join(format!("{}.json", table_name));
+        let json = serde_json::to_string_pretty(&table)
+            .map_err(|e| FixturesError::InvalidFixture(table_name.clone(), e.to_string()))?;
+        fs::write(&path, json)?;
+        info!("Wrote {} fixture rows for {} to {}", table.rows.len(), table_name, path.display());
+        generated.tables.push((table_name, table.rows.len()));
+    }
+
+    Ok(generated)
+}
+
+/// The fixture tables loaded into copies of their schemas, prefixed so tests never touch the
+/// indexer's own tables
+#[derive(Debug, Default)]
+pub struct LoadedFixtures {
+    /// Source schema and the schema it was loaded into
+    pub schemas: Vec<(String, String)>,
+    pub rows: usize,
+}
+
+impl LoadedFixtures {
+    /// Points the schema qualified table names in the query at the loaded copies, only whole
+    /// schema identifiers are replaced, quoted or not, and string literals are left alone
+    pub fn rewrite_sql(&self, sql: &str) -> String {
+        let is_identifier_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+
+        let mut rewritten = String::with_capacity(sql.len());
+        let mut in_literal = false;
+        let mut previous: Option<char> = None;
+        let mut rest = sql;
+        'scan: while let Some(c) = rest.chars().next() {
+            if c == '\'' {
+                in_literal = !in_literal;
+            }
+            let starts_identifier = !in_literal &&
+                !previous
+                    .is_some_and(|previous| is_identifier_char(previous) || previous == '.');
+            if starts_identifier {
+                for (source, loaded) in &self.schemas {
+                    for (source, loaded) in [
+                        (format!("{}.", source), format!("{}.", loaded)),
+                        (format!("\"{}\".", source), format!("\"{}\".", loaded)),
+                    ] {
+                        if rest.starts_with(&source) {
+                            rewritten.push_str(&loaded);
+                            rest = &rest[source.len()..];
+                            previous = Some('.');
+                            continue 'scan;
+                        }
+                    }
+                }
+            }
+            rewritten.push(c);
+            previous = Some(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        rewritten
+    }
+
+    pub async fn drop(&self, client: &PostgresClient) -> Result<(), PostgresError> {
+        let sql = self
+            .schemas
+            .iter()
+            .map(|(_, loaded)| format!("DROP SCHEMA IF EXISTS {} CASCADE;", loaded))
+            .collect::<String>();
+        client.execute_ddl(&sql, "drop the loaded fixture schemas").await
+    }
+}
+
+fn read_fixture(path: &Path) -> Result<FixtureTable, FixturesError> {
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents)
+        .map_err(|e| FixturesError::InvalidFixture(path.display().to_string(), e.to_string()))
+}
+
+/// Integration test harness loading every fixture in `fixtures_dir` into `{schema_prefix}_{schema}`
+/// copies of the event table schemas, run queries through `rewrite_sql` and `drop` when done
+pub async fn load_fixtures(
+    client: &PostgresClient,
+    fixtures_dir: &Path,
+    schema_prefix: &str,
+) -> Result<LoadedFixtures, FixturesError> {
+    let schema_prefix = schema_prefix.to_lowercase();
+    let mut paths: Vec<PathBuf> = fs::read_dir(fixtures_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    paths.sort();
+
+    let mut loaded = LoadedFixtures::default();
+    for path in paths {
+        let fixture = read_fixture(&path)?;
+        let Some((source_schema, table)) = fixture.table_name.split_once('.') else {
+            return Err(FixturesError::InvalidFixture(
+                path.display().to_string(),
+                format!("{} is not schema qualified", fixture.table_name),
+            ));
+        };
+
+        let loaded_schema = format!("{}_{}", schema_prefix, source_schema);
+        let loaded_table = format!("{}.{}", loaded_schema, table);
+        let columns = fixture
+            .columns
+            .iter()
+            .map(|column| format!("\"{}\" {}", column.name, column.db_type))
+            .collect::<Vec<_>>()
+            .join(", ");
+        client
+            .execute_ddl(
+                &format!(
+                    "CREATE SCHEMA IF NOT EXISTS {loaded_schema}; CREATE TABLE {loaded_table} ({columns});"
+                ),
+                "create a fixture table",
+            )
+            .await?;
+
+        if !fixture.rows.is_empty() {
+            let rows = serde_json::to_string(&fixture.rows).map_err(|e| {
+                FixturesError::InvalidFixture(path.display().to_string(), e.to_string())
+            })?;
+            let insert_columns = fixture
+                .columns
+                .iter()
+                .filter(|column| column.name != RINDEXER_ID_COLUMN)
+                .map(|column| format!("\"{}\"", column.name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            client
+                .execute(
+                    &format!(
+                        "INSERT INTO {loaded_table} ({insert_columns}) SELECT {insert_columns} FROM json_populate_recordset(NULL::{loaded_table}, $1::TEXT::JSON)"
+                    ),
+                    &[&rows],
+                )
+                .await?;
+        }
+
+        loaded.rows += fixture.rows.len();
+        if !loaded.schemas.iter().any(|(source, _)| source == source_schema) {
+            loaded.schemas.push((source_schema.to_string(), loaded_schema));
+        }
+    }
+
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_fixture_value_converts_bytea_hex() {
+        assert_eq!(fixture_value(json!("0xABcd"), "BYTEA"), json!("\\xabcd"));
+        assert_eq!(fixture_value(json!(["abcd"]), "BYTEA[]"), json!(["\\xabcd"]));
+        assert_eq!(fixture_value(json!("0xabcd"), "CHAR(42)"), json!("0xabcd"));
+    }
+
+    #[test]
+    fn test_rewrite_sql_points_at_loaded_schemas() {
+        let loaded = LoadedFixtures {
+            schemas: vec![
+                ("indexer_erc20".to_string(), "test_indexer_erc20".to_string()),
+                ("indexer_erc20_wrapped".to_string(), "test_indexer_erc20_wrapped".to_string()),
+            ],
+            rows: 0,
+        };
+
+        assert_eq!(
+            loaded.rewrite_sql(
+                "SELECT * FROM indexer_erc20.transfer JOIN indexer_erc20_wrapped.deposit USING (tx_hash)"
+            ),
+            "SELECT * FROM test_indexer_erc20.transfer JOIN test_indexer_erc20_wrapped.deposit USING (tx_hash)"
+        );
+        assert_eq!(
+            loaded.rewrite_sql(
+                "SELECT * FROM \"indexer_erc20\".transfer, my_indexer_erc20.transfer WHERE note = 'indexer_erc20.x'"
+            ),
+            "SELECT * FROM \"test_indexer_erc20\".transfer, my_indexer_erc20.transfer WHERE note = 'indexer_erc20.x'"
+        );
+    }
+}
@@ -24,6 +24,7 @@ use crate::{
         global::Global,
         network::Network,
         storage::Storage,
+        template::write_template_abi,
         yaml::{read_manifest, ReadManifestError, YAML_CONFIG_NAME},
     },
 };
@@ -86,6 +87,9 @@ pub enum WriteIndexerEvents {
 
     #[error("Could not find ABI path: {0}")]
     AbiPathDoesNotExist(String),
+
+    #[error("Could not write template ABI: {0}")]
+    CouldNotWriteTemplateAbi(std::io::Error),
 }
 
 fn write_indexer_events(
@@ -103,6 +107,12 @@ fn write_indexer_events(
             format!("{}/events/{}", camel_to_snake(&indexer.name), camel_to_snake(&contract.name));
         write_file(&generate_file_location(output, &event_path), events_code.as_str())?;
 
+        // abigen reads the ABI from a file so templates get theirs written to the project
+        if let Some(template) = contract.template.filter(|_| contract.abi.is_empty()) {
+            contract.abi = write_template_abi(project_path, template)
+                .map_err(WriteIndexerEvents::CouldNotWriteTemplateAbi)?;
+        }
+
         let abi_full_path = get_full_path(project_path, &contract.abi)
             .map_err(|_| WriteIndexerEvents::AbiPathDoesNotExist(contract.abi.clone()))?;
         match abi_full_path.to_str() {
@@ -374,21 +384,35 @@ serde = {{ version = "1.0.194", features = ["derive"] }}
     fs::create_dir_all(project_path.join("src"))?;
 
     let main_code = r#"
-            use std::env;
+            use std::{env, error::Error};
 
             use self::rindexer_lib::indexers::all_handlers::register_all_handlers;
             use rindexer::{
-                start_rindexer, GraphqlOverrideSettings, IndexingDetails, StartDetails,
+                build_runtime, manifest::yaml::read_manifest, replay_failed_events,
+                start_rindexer, GraphqlOverrideSettings, IndexingDetails,
+                ReplayFailedEventsOptions, StartDetails,
             };
 
             mod rindexer_lib;
 
-            #[tokio::main]
-            async fn main() {
+            // errors are returned from main so the process exits with a non zero code
+            fn main() -> Result<(), Box<dyn Error>> {
+                let runtime =
+                    env::current_dir().and_then(|path| build_runtime(&path.join("rindexer.yaml")))?;
+                runtime.block_on(run())
+            }
+
+            async fn run() -> Result<(), Box<dyn Error>> {
                 let args: Vec<String> = env::args().collect();
 
+                // `rindexer replay-failed` runs the quarantined events through these handlers
+                if args.iter().any(|arg| arg == "--replay-failed") {
+                    return replay_failed(&args).await;
+                }
+
                 let mut enable_graphql = false;
                 let mut enable_indexer = false;
+                let mut apply_migrations = false;
                 
                 let mut port: Option<u16> = None;
 
@@ -396,15 +420,13 @@ serde = {{ version = "1.0.194", features = ["derive"] }}
                     match arg.as_str() {
                         "--graphql" => enable_graphql = true,
                         "--indexer" => enable_indexer = true,
+                        "--apply-migrations" => apply_migrations = true,
                         _ if arg.starts_with("--port=") || arg.starts_with("--p") => {
                             if let Some(value) = arg.split('=').nth(1) {
                                 let overridden_port = value.parse::<u16>();
                                 match overridden_port {
                                     Ok(overridden_port) => port = Some(overridden_port),
-                                    Err(_) => {
-                                        println!("Invalid port number");
-                                        return;
-                                    }
+                                    Err(_) => return Err("Invalid port number".into()),
                                 }
                             }
                         },
@@ -416,37 +438,54 @@ serde = {{ version = "1.0.194", features = ["derive"] }}
                     }
                 }
 
-                let path = env::current_dir();
-                match path {
-                    Ok(path) => {
-                        let manifest_path = path.join("rindexer.yaml");
-                        let result = start_rindexer(StartDetails {
-                            manifest_path: &manifest_path,
-                            indexing_details: if enable_indexer {
-                                Some(IndexingDetails {
-                                    registry: register_all_handlers(&manifest_path).await,
-                                })
-                            } else {
-                                None
-                            },
-                            graphql_details: GraphqlOverrideSettings {
-                                enabled: enable_graphql,
-                                override_port: port,
-                            }
+                let manifest_path = env::current_dir()?.join("rindexer.yaml");
+                start_rindexer(StartDetails {
+                    manifest_path: &manifest_path,
+                    indexing_details: if enable_indexer {
+                        Some(IndexingDetails {
+                            registry: register_all_handlers(&manifest_path).await,
+                            apply_migrations,
                         })
-                        .await;
-                        
-                        match result {
-                            Ok(_) => {}
-                            Err(e) => {
-                                println!("Error starting rindexer: {:?}", e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        println!("Error getting current directory: {:?}", e);
+                    } else {
+                        None
+                    },
+                    graphql_details: GraphqlOverrideSettings {
+                        enabled: enable_graphql,
+                        override_port: port,
                     }
+                })
+                .await?;
+
+                Ok(())
+            }
+
+            async fn replay_failed(args: &[String]) -> Result<(), Box<dyn Error>> {
+                let value = |name: &str| {
+                    args.iter().find_map(|arg| arg.strip_prefix(name)).map(str::to_string)
+                };
+                let manifest_path = env::current_dir()?.join("rindexer.yaml");
+                let manifest = read_manifest(&manifest_path)?;
+                let registry = register_all_handlers(&manifest_path).await;
+
+                let report = replay_failed_events(
+                    &manifest,
+                    &registry,
+                    &ReplayFailedEventsOptions {
+                        contract_name: value("--contract-name="),
+                        event_name: value("--event-name="),
+                        network: value("--network="),
+                    },
+                )
+                .await?;
+
+                println!(
+                    "Replayed {} failed events, {} still fail and {} are no longer registered",
+                    report.replayed, report.still_failing, report.skipped
+                );
+                if report.still_failing > 0 {
+                    return Err(format!("{} failed events still fail", report.still_failing).into());
                 }
+                Ok(())
             }
           "#;
 
@@ -1,4 +1,4 @@
-use std::{fs, path::Path};
+use std::path::Path;
 
 use ethers::types::ValueOrArray;
 use serde_json::Value;
@@ -9,12 +9,16 @@ use crate::{
         ParamTypeError, ReadAbiError,
     },
     database::postgres::generate::{
+        contract_networks, generate_column_definitions,
         generate_column_names_only_with_base_properties, generate_event_table_full_name,
+        generate_network_event_table_full_name,
     },
-    helpers::{camel_to_snake, camel_to_snake_advanced, get_full_path},
+    helpers::{camel_to_snake, camel_to_snake_advanced},
     manifest::{
         contract::{Contract, ContractDetails},
-        storage::{CsvDetails, Storage},
+        solidity_enum::{enums_for_event, typed_addresses_for_event, EventInputEnum},
+        storage::{CsvDetails, PostgresLayout, Storage},
+        transform::transforms_for_event,
     },
     types::code::Code,
 };
@@ -51,9 +55,9 @@ fn generate_structs(
     contract: &Contract,
 ) -> Result<Code, GenerateStructsError> {
     // TODO - this could be shared with `get_abi_items`
-    let full_path = get_full_path(project_path, &contract.abi)
+    let abi_str = contract
+        .read_abi(project_path)
         .map_err(|_| GenerateStructsError::AbiPathDoesNotExist(contract.abi.clone()))?;
-    let abi_str = fs::read_to_string(full_path)?;
     let abi_json: Value = serde_json::from_str(&abi_str)?;
 
     let mut structs = Code::blank();
@@ -79,12 +83,130 @@ fn generate_structs(
                 abigen_mod_name = abigen_contract_mod_name(contract),
                 event_name = event_name
             )));
+
+            for input_enum in enums_for_event(&contract.enums, event_name) {
+                structs.push_str(&Code::new(format!(
+                    r#"
+                        impl {struct_data} {{
+                            pub fn {field}_enum(&self) -> Result<{enum_name}, u8> {{
+                                {enum_name}::try_from(self.{field})
+                            }}
+                        }}
+                    "#,
+                    struct_data = struct_data,
+                    field = camel_to_snake(&input_enum.input_name),
+                    enum_name = input_enum.name
+                )));
+            }
+
+            for typed_address in typed_addresses_for_event(&contract.typed_addresses, event_name) {
+                structs.push_str(&Code::new(format!(
+                    r#"
+                        impl {struct_data} {{
+                            pub fn {field}_typed(&self) -> {type_name} {{
+                                {type_name}(self.{field})
+                            }}
+                        }}
+                    "#,
+                    struct_data = struct_data,
+                    field = camel_to_snake(&typed_address.input_name),
+                    type_name = typed_address.name
+                )));
+            }
+        }
+    }
+
+    // declarations sharing a name are validated to generate the same type
+    let mut generated_types: Vec<&str> = vec![];
+    for input_enum in contract.enums.iter().flatten() {
+        if !generated_types.contains(&input_enum.name.as_str()) {
+            generated_types.push(&input_enum.name);
+            structs.push_str(&generate_solidity_enum_code(input_enum));
+        }
+    }
+    for typed_address in contract.typed_addresses.iter().flatten() {
+        if !generated_types.contains(&typed_address.name.as_str()) {
+            generated_types.push(&typed_address.name);
+            structs.push_str(&generate_typed_address_code(&typed_address.name));
         }
     }
 
     Ok(structs)
 }
 
+fn generate_solidity_enum_code(input_enum: &EventInputEnum) -> Code {
+    let variants = input_enum.variants.join(",\n");
+    let as_str_arms = input_enum
+        .variants
+        .iter()
+        .map(|variant| format!("{}::{} => \"{}\",", input_enum.name, variant, variant))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let try_from_arms = input_enum
+        .variants
+        .iter()
+        .enumerate()
+        .map(|(index, variant)| format!("{} => Ok({}::{}),", index, input_enum.name, variant))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Code::new(format!(
+        r#"
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub enum {name} {{
+                {variants}
+            }}
+
+            impl {name} {{
+                pub fn as_str(&self) -> &'static str {{
+                    match self {{
+                        {as_str_arms}
+                    }}
+                }}
+            }}
+
+            impl TryFrom<u8> for {name} {{
+                type Error = u8;
+
+                fn try_from(value: u8) -> Result<Self, Self::Error> {{
+                    match value {{
+                        {try_from_arms}
+                        _ => Err(value),
+                    }}
+                }}
+            }}
+        "#,
+        name = input_enum.name,
+        variants = variants,
+        as_str_arms = as_str_arms,
+        try_from_arms = try_from_arms
+    ))
+}
+
+fn generate_typed_address_code(name: &str) -> Code {
+    Code::new(format!(
+        r#"
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct {name}(pub ethers::types::Address);
+
+            impl std::ops::Deref for {name} {{
+                type Target = ethers::types::Address;
+
+                fn deref(&self) -> &Self::Target {{
+                    &self.0
+                }}
+            }}
+
+            impl From<{name}> for ethers::types::Address {{
+                fn from(value: {name}) -> Self {{
+                    value.0
+                }}
+            }}
+        "#,
+        name = name
+    ))
+}
+
 fn generate_event_enums_code(event_info: &[EventInfo]) -> Code {
     Code::new(
         event_info
@@ -223,7 +345,12 @@ fn generate_event_callback_structs_code(
     let mut parts = Vec::new();
 
     for info in event_info {
-        let csv_generator = generate_csv_instance(project_path, contract, info, &storage.csv)?;
+        // the csv types only exist when rindexer is built with the csv feature
+        let csv_generator = if storage.csv_enabled() {
+            generate_csv_instance(project_path, contract, info, &storage.csv)?
+        } else {
+            Code::blank()
+        };
 
         let part = format!(
             r#"
@@ -276,7 +403,9 @@ fn generate_event_callback_structs_code(
                         callback: {lower_name}_handler(closure),
                         context: Arc::new(EventContext {{
                             {database}
-                            csv: Arc::new(csv),
+                            {csv}
+                            cache: handler_cache(),
+                            metadata: EventMetadata::new(get_provider_cache_for_network),
                             extensions: Arc::new(extensions),
                         }}),
                     }}
@@ -313,11 +442,15 @@ fn generate_event_callback_structs_code(
             struct_result = info.struct_result(),
             struct_data = info.struct_data(),
             database = if databases_enabled {
-                r#"database: Arc::new(PostgresClient::new().await.expect("Failed to connect to Postgres")),"#
+                format!(
+                    r#"database: Arc::new(PostgresClient::new_from_env("{}").await.expect("Failed to connect to Postgres")),"#,
+                    storage.postgres_connection_string_env()
+                )
             } else {
-                ""
+                "".to_string()
             },
             csv_generator = csv_generator,
+            csv = if storage.csv_enabled() { "csv: Arc::new(csv)," } else { "" },
             event_callback_events_len =
                 if !is_filter { "let events_len = events.len();" } else { "" },
             event_callback_return = if !is_filter {
@@ -479,10 +612,13 @@ fn generate_event_bindings_code(
         use ethers::{{providers::{{Http, Provider, RetryClient}}, abi::Address, types::{{Bytes, H256}}}};
         use rindexer::{{
             async_trait,
-            AsyncCsvAppender,
+            {csv_import}
             generate_random_id,
+            handler_cache,
+            HandlerCache,
             FutureExt,
             event::{{
+                EventMetadata,
                 callback_registry::{{
                     EventCallbackRegistry, EventCallbackRegistryInformation, EventCallbackResult,
                     EventResult, TxInformation,
@@ -500,6 +636,8 @@ fn generate_event_bindings_code(
 
         {structs}
 
+        {table_queries}
+
         type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
         #[async_trait]
@@ -509,7 +647,11 @@ fn generate_event_bindings_code(
 
         pub struct EventContext<TExtensions> where TExtensions: Send + Sync {{
             {event_context_database}
-            pub csv: Arc<AsyncCsvAppender>,
+            {event_context_csv}
+            /// Shared by every handler, persisted with `persist_handler_cache`
+            pub cache: Arc<HandlerCache>,
+            /// Block and transaction details of the events, fetched lazily and memoized
+            pub metadata: EventMetadata,
             pub extensions: Arc<TExtensions>,
         }}
 
@@ -600,6 +742,7 @@ fn generate_event_bindings_code(
                         .collect(),
                     abi: contract_details.abi,
                     reorg_safe_distance: contract_details.reorg_safe_distance.unwrap_or_default(),
+                    checkpoint: contract_details.checkpoint,
                 }};
 
                 let callback: Arc<dyn Fn(Vec<EventResult>) -> BoxFuture<'static, EventCallbackResult<()>> + Send + Sync> = match self {{
@@ -618,14 +761,22 @@ fn generate_event_bindings_code(
             }}
         }}
         "#,
-        client_import = if storage.postgres_enabled() { "PostgresClient," } else { "" },
+        client_import = if storage.postgres_enabled() {
+            "PostgresClient, EventColumnFormat, EventTableQuery, EventTableQueryError, EventTableRow, EventTables,"
+        } else {
+            ""
+        },
         abigen_mod_name = abigen_contract_mod_name(contract),
         abigen_file_name = abigen_contract_file_name(contract),
         abigen_name = abigen_contract_name(contract),
         structs = generate_structs(project_path, contract)?,
+        table_queries = generate_table_queries_code(indexer_name, contract, storage, &event_info),
         event_type_name = &event_type_name,
         event_context_database =
             if storage.postgres_enabled() { "pub database: Arc<PostgresClient>," } else { "" },
+        csv_import = if storage.csv_enabled() { "AsyncCsvAppender," } else { "" },
+        event_context_csv =
+            if storage.csv_enabled() { "pub csv: Arc<AsyncCsvAppender>," } else { "" },
         event_callback_structs =
             generate_event_callback_structs_code(project_path, &event_info, contract, storage)?,
         event_enums = generate_event_enums_code(&event_info),
@@ -647,6 +798,258 @@ fn generate_event_bindings_code(
     Ok(code)
 }
 
+/// Rust type, the `EventTableRow` parser and how a filter value is written as text for a
+/// column, `None` for columns which are only read back as JSON
+fn table_query_column_types(
+    abi_type: &str,
+    compact_bytea: bool,
+) -> Option<(&'static str, &'static str, &'static str)> {
+    if abi_type.ends_with(']') || abi_type.starts_with("tuple") {
+        return None;
+    }
+
+    match abi_type {
+        "address" => Some(("ethers::types::Address", "address", address_text(compact_bytea))),
+        "bool" => Some(("bool", "bool", "value.to_string()")),
+        "string" => Some(("String", "string", "value.to_string()")),
+        t if t.starts_with("bytes") => {
+            Some(("ethers::types::Bytes", "bytes", r#"format!("\\x{}", &value.to_string()[2..])"#))
+        }
+        t if t.starts_with("uint") => Some(("ethers::types::U256", "u256", "value.to_string()")),
+        t if t.starts_with("int") => Some(("ethers::types::I256", "i256", "value.to_string()")),
+        _ => None,
+    }
+}
+
+/// Addresses are written the way the indexer stores them, `format_address` follows the
+/// manifest checksum setting
+fn address_text(compact_bytea: bool) -> &'static str {
+    if compact_bytea {
+        r#"format!("\\x{}", &format!("{:?}", value)[2..])"#
+    } else {
+        "rindexer::format_address(&value)"
+    }
+}
+
+fn rust_field_name(column: &str) -> String {
+    const KEYWORDS: [&str; 16] = [
+        "as", "async", "await", "box", "const", "enum", "fn", "impl", "loop", "match", "mod",
+        "move", "ref", "static", "type", "where",
+    ];
+    if KEYWORDS.contains(&column) {
+        format!("r#{}", column)
+    } else {
+        column.to_string()
+    }
+}
+
+/// Typed read helpers over the event tables, `TransferTable::query().where_to(address)` builds an
+/// `EventTableQuery` so handlers and services never hand write SQL against generated schemas
+fn generate_table_queries_code(
+    indexer_name: &str,
+    contract: &Contract,
+    storage: &Storage,
+    event_info: &[EventInfo],
+) -> Code {
+    if !storage.postgres_enabled() {
+        return Code::blank();
+    }
+
+    let compact_bytea = storage.postgres_compact_bytea();
+    let address_type = if compact_bytea { "BYTEA" } else { "CHAR(42)" };
+    let mut code = Code::blank();
+
+    for info in event_info {
+        let mut fields: Vec<(String, String, String)> = vec![
+            (
+                "contract_address".to_string(),
+                "ethers::types::Address".to_string(),
+                "address".to_string(),
+            ),
+            ("tx_hash".to_string(), "ethers::types::H256".to_string(), "h256".to_string()),
+            ("block_number".to_string(), "u64".to_string(), "u64".to_string()),
+            ("block_hash".to_string(), "ethers::types::H256".to_string(), "h256".to_string()),
+            ("network".to_string(), "String".to_string(), "string".to_string()),
+            ("tx_index".to_string(), "u64".to_string(), "u64".to_string()),
+            ("log_index".to_string(), "ethers::types::U256".to_string(), "u256".to_string()),
+        ];
+        let mut columns = vec![];
+        let mut filters = vec![(
+            "contract_address".to_string(),
+            address_type.to_string(),
+            "ethers::types::Address",
+            address_text(compact_bytea),
+        )];
+
+        let transforms = transforms_for_event(&contract.transforms, &info.name);
+        let jsonb_inputs = contract.jsonb_inputs(&info.name, &info.inputs);
+        let abi_types: Vec<(String, String)> = ABIInput::generate_abi_name_properties(
+            &info.inputs,
+            &GenerateAbiPropertiesType::PostgresColumnsNamesOnly,
+            None,
+        )
+        .into_iter()
+        .map(|property| (property.value, property.abi_type))
+        .collect();
+
+        for (column, db_type) in
+            generate_column_definitions(&info.inputs, &transforms, &jsonb_inputs, compact_bytea)
+        {
+            let column_types = abi_types
+                .iter()
+                .find(|(name, _)| *name == column)
+                .filter(|_| db_type != "JSONB")
+                .and_then(|(_, abi_type)| {
+                    let transformed =
+                        transforms.iter().any(|t| camel_to_snake(&t.input_name) == column);
+                    if transformed {
+                        Some(("String", "string", "value.to_string()"))
+                    } else {
+                        table_query_column_types(abi_type, compact_bytea)
+                    }
+                });
+
+            match column_types {
+                Some((rust_type, parser, value_text)) => {
+                    fields.push((column.clone(), rust_type.to_string(), parser.to_string()));
+                    filters.push((column, db_type, rust_type, value_text));
+                }
+                None => {
+                    fields.push((
+                        column.clone(),
+                        "rindexer::JsonValue".to_string(),
+                        "json".to_string(),
+                    ));
+                    columns.push(column);
+                }
+            }
+        }
+
+        let tables = match storage.postgres_layout() {
+            PostgresLayout::Shared => format!(
+                "EventTables::Shared(\"{}\".to_string())",
+                generate_event_table_full_name(indexer_name, &contract.name, &info.name)
+            ),
+            PostgresLayout::SchemaPerNetwork => format!(
+                "EventTables::PerNetwork(vec![{}])",
+                contract_networks(contract)
+                    .into_iter()
+                    .map(|network| format!(
+                        "(\"{}\".to_string(), \"{}\".to_string())",
+                        network,
+                        generate_network_event_table_full_name(network, &contract.name, &info.name)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+
+        let query_columns = fields
+            .iter()
+            .map(|(column, _, parser)| {
+                format!(
+                    "(\"{}\".to_string(), EventColumnFormat::{})",
+                    column,
+                    if parser == "json" { "Json" } else { "Text" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        code.push_str(&Code::new(format!(
+            r#"
+            #[derive(Debug, Clone)]
+            pub struct {name}Row {{
+                {row_fields}
+            }}
+
+            impl {name}Row {{
+                fn from_row(row: &EventTableRow) -> Result<Self, EventTableQueryError> {{
+                    Ok(Self {{
+                        {row_parsers}
+                    }})
+                }}
+            }}
+
+            pub struct {name}Table;
+
+            impl {name}Table {{
+                pub fn query() -> {name}Query {{
+                    {name}Query(EventTableQuery::new({tables}, vec![{query_columns}]))
+                }}
+            }}
+
+            #[derive(Debug, Clone)]
+            pub struct {name}Query(EventTableQuery);
+
+            impl {name}Query {{
+                {filter_fns}
+
+                pub fn network(self, network: &str) -> Self {{
+                    Self(self.0.network(network))
+                }}
+
+                pub fn between_blocks(self, from_block: u64, to_block: u64) -> Self {{
+                    Self(self.0.between_blocks(from_block, to_block))
+                }}
+
+                pub fn from_block(self, from_block: u64) -> Self {{
+                    Self(self.0.from_block(from_block))
+                }}
+
+                pub fn to_block(self, to_block: u64) -> Self {{
+                    Self(self.0.to_block(to_block))
+                }}
+
+                pub fn newest_first(self) -> Self {{
+                    Self(self.0.newest_first())
+                }}
+
+                pub fn limit(self, limit: i64) -> Self {{
+                    Self(self.0.limit(limit))
+                }}
+
+                pub async fn fetch(&self, client: &PostgresClient) -> Result<Vec<{name}Row>, EventTableQueryError> {{
+                    self.0.fetch_rows(client).await?.iter().map({name}Row::from_row).collect()
+                }}
+            }}
+            "#,
+            name = info.name,
+            row_fields = fields
+                .iter()
+                .map(|(column, rust_type, _)| format!("pub {}: {},", rust_field_name(column), rust_type))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            row_parsers = fields
+                .iter()
+                .map(|(column, _, parser)| format!(
+                    "{}: row.{}(\"{}\")?,",
+                    rust_field_name(column),
+                    parser,
+                    column
+                ))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            tables = tables,
+            query_columns = query_columns,
+            filter_fns = filters
+                .iter()
+                .map(|(column, db_type, rust_type, value_text)| {
+                    let value_type = if *rust_type == "String" { "&str" } else { rust_type };
+                    format!(
+                        r#"pub fn where_{column}(self, value: {value_type}) -> Self {{
+                            Self(self.0.where_eq("{column}", "{db_type}", {value_text}))
+                        }}"#,
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        )));
+    }
+
+    code
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum GenerateEventBindingsError {
     #[error("{0}")]
@@ -682,6 +1085,74 @@ pub enum GenerateEventHandlersError {
     ParamTypeError(#[from] ParamTypeError),
 }
 
+/// With the schema per network layout the table is picked from the network of the batch, each
+/// batch only ever holds results from one network
+fn generate_event_table_name_code(
+    layout: PostgresLayout,
+    indexer_name: &str,
+    contract: &Contract,
+    event_name: &str,
+) -> String {
+    match layout {
+        PostgresLayout::Shared => format!(
+            "\"{}\"",
+            generate_event_table_full_name(indexer_name, &contract.name, event_name)
+        ),
+        PostgresLayout::SchemaPerNetwork => {
+            let match_arms = contract_networks(contract)
+                .into_iter()
+                .map(|network| {
+                    format!(
+                        "\"{}\" => \"{}\",",
+                        network,
+                        generate_network_event_table_full_name(network, &contract.name, event_name)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            format!(
+                r#"match results[0].tx_information.network.as_str() {{
+                    {match_arms}
+                    network => return Err(format!("No table for network {{}}", network)),
+                }}"#,
+                match_arms = match_arms
+            )
+        }
+    }
+}
+
+/// Hands the rows the handler built to every backend registered with `register_storage_backend`,
+/// before the csv write as that takes the csv rows
+fn generate_registered_storage_write_code(
+    indexer_name: &str,
+    contract_name: &str,
+    event_name: &str,
+    columns_names: &str,
+    rows: &str,
+    text_rows: &str,
+) -> String {
+    format!(
+        r#"
+            if let Err(e) = rindexer::write_registered_storage_backends(&rindexer::StorageBatch {{
+                key: rindexer::CheckpointKey {{
+                    indexer_name: "{indexer_name}",
+                    contract_name: "{contract_name}",
+                    event_name: "{event_name}",
+                    network: &results[0].tx_information.network,
+                }},
+                column_names: &[{columns_names}],
+                rows: {rows},
+                text_rows: {text_rows},
+            }})
+            .await
+            {{
+                return Err(e.to_string());
+            }}
+        "#
+    )
+}
+
 pub fn generate_event_handlers(
     project_path: &Path,
     indexer_name: &str,
@@ -697,7 +1168,8 @@ pub fn generate_event_handlers(
         r#"
             use rindexer::{
                 event::callback_registry::EventCallbackRegistry,
-                EthereumSqlTypeWrapper, PgType, RindexerColorize, rindexer_error, rindexer_info
+                EthereumSqlTypeWrapper, PgType, RindexerColorize, format_address, rindexer_error,
+                rindexer_info
             };
         "#,
     );
@@ -729,16 +1201,22 @@ pub fn generate_event_handlers(
             None,
         );
 
+        let columns_names = generate_column_names_only_with_base_properties(&event.inputs, &[])
+            .iter()
+            .map(|item| format!("\"{}\".to_string()", item))
+            .collect::<Vec<String>>()
+            .join(", ");
+
         let mut csv_write = String::new();
         // this checks storage enabled as well
         if !storage.csv_disable_create_headers() {
             let mut csv_data = String::new();
-            csv_data.push_str(r#"format!("{:?}", result.tx_information.address),"#);
+            csv_data.push_str(r#"format_address(&result.tx_information.address),"#);
 
             for item in &abi_name_properties {
                 if item.abi_type == "address" {
                     let key = format!("result.event_data.{},", item.value);
-                    csv_data.push_str(&format!(r#"format!("{{:?}}", {}),"#, key));
+                    csv_data.push_str(&format!(r#"format_address(&{}),"#, key));
                 } else if item.abi_type.contains("bytes") {
                     csv_data.push_str(&format!(
                         r#"result.event_data.{}.iter().map(|byte| format!("{{:02x}}", byte)).collect::<Vec<_>>().join(""),"#,
@@ -767,10 +1245,13 @@ pub fn generate_event_handlers(
                 csv_write = format!(
                     r#"
                       let mut csv_bulk_data: Vec<Vec<String>> = vec![];
-                      for result in &results {{
+                      // a csv is append only so a log a reorg removed is only left out
+                      for result in results.iter().filter(|result| !result.tx_information.removed) {{
                         {inner_csv_write}
                       }}
-                    
+
+                      {registered_storage_write}
+
                       if !csv_bulk_data.is_empty() {{
                         let csv_result = context.csv.append_bulk(csv_bulk_data).await;
                         if let Err(e) = csv_result {{
@@ -780,6 +1261,14 @@ pub fn generate_event_handlers(
                       }}
                     "#,
                     inner_csv_write = csv_write,
+                    registered_storage_write = generate_registered_storage_write_code(
+                        indexer_name,
+                        &contract.name,
+                        &event.name,
+                        &columns_names,
+                        "&[]",
+                        "&csv_bulk_data",
+                    ),
                     event_type_name = event_type_name,
                     handler_name = event.name,
                 );
@@ -824,69 +1313,82 @@ pub fn generate_event_handlers(
             );
             data.push_str("EthereumSqlTypeWrapper::U64(result.tx_information.transaction_index),");
             data.push_str("EthereumSqlTypeWrapper::U256(result.tx_information.log_index)");
-            data.push_str("];");
+            data.push(']');
+            if storage.postgres_compact_bytea() {
+                data.push_str(
+                    ".into_iter().map(EthereumSqlTypeWrapper::into_compact_bytea).collect::<Vec<_>>()",
+                );
+            }
+            data.push(';');
 
             postgres_write = format!(
                 r#"
+                    let table_name = {table_name};
+
+                    let removed: Vec<_> = results
+                        .iter()
+                        .filter(|result| result.tx_information.removed)
+                        .map(|result| &result.tx_information)
+                        .collect();
+                    if !removed.is_empty() {{
+                        if let Err(e) = context.database.delete_removed_logs(table_name, &removed, {compact_bytea}).await {{
+                            rindexer_error!("{event_type_name}::{handler_name} deleting logs removed by a reorg: {{:?}}", e);
+                            return Err(e.to_string());
+                        }}
+                    }}
+
                     let mut postgres_bulk_data: Vec<Vec<EthereumSqlTypeWrapper>> = vec![];
                     {csv_bulk_data}
-                    for result in results.iter() {{
+                    for result in results.iter().filter(|result| !result.tx_information.removed) {{
                         {csv_write}
                         let data = {data};
                         postgres_bulk_data.push(data);
                     }}
 
+                    {registered_storage_write}
+
                     {csv_bulk_insert}
 
                     if postgres_bulk_data.is_empty() {{
                         return Ok(());
                     }}
 
-                     if postgres_bulk_data.len() > 100 {{
-                        let result = context
-                            .database
-                            .bulk_insert_via_copy(
-                                "{table_name}",
-                                &[{columns_names}],
-                                &postgres_bulk_data
-                                    .first()
-                                    .ok_or("No first element in bulk data, impossible")?
-                                    .iter()
-                                    .map(|param| param.to_type())
-                                    .collect::<Vec<PgType>>(),
-                                &postgres_bulk_data,
-                            )
-                            .await;
-
-                        if let Err(e) = result {{
-                            rindexer_error!("{event_type_name}::{handler_name} inserting bulk data via COPY: {{:?}}", e);
-                            return Err(e.to_string());
-                        }}
-                        }} else {{
-                            let result = context
-                                .database
-                                .bulk_insert(
-                                    "{table_name}",
-                                    &[{columns_names}],
-                                    &postgres_bulk_data,
-                                )
-                                .await;
-                            
-                            if let Err(e) = result {{
-                                rindexer_error!("{event_type_name}::{handler_name} inserting bulk data via INSERT: {{:?}}", e);
-                                return Err(e.to_string());
-                            }}
+                    let result = context
+                        .database
+                        .insert_bulk(
+                            table_name,
+                            &[{columns_names}],
+                            &postgres_bulk_data,
+                            rindexer::manifest::storage::InsertStrategy::{insert_strategy:?},
+                            {skip_insert_conflicts},
+                        )
+                        .await;
+
+                    if let Err(e) = result {{
+                        rindexer_error!("{event_type_name}::{handler_name} inserting bulk data: {{:?}}", e);
+                        return Err(e.to_string());
                     }}
                 "#,
-                table_name =
-                    generate_event_table_full_name(indexer_name, &contract.name, &event.name),
+                table_name = generate_event_table_name_code(
+                    storage.postgres_layout(),
+                    indexer_name,
+                    contract,
+                    &event.name,
+                ),
                 handler_name = event.name,
                 event_type_name = event_type_name,
-                columns_names = generate_column_names_only_with_base_properties(&event.inputs)
-                    .iter()
-                    .map(|item| format!("\"{}\".to_string()", item))
-                    .collect::<Vec<String>>()
-                    .join(", "),
+                compact_bytea = storage.postgres_compact_bytea(),
+                insert_strategy = storage.postgres_insert_strategy(),
+                skip_insert_conflicts = storage.postgres_natural_primary_key(),
+                registered_storage_write = generate_registered_storage_write_code(
+                    indexer_name,
+                    &contract.name,
+                    &event.name,
+                    &columns_names,
+                    "&postgres_bulk_data",
+                    if storage.csv_enabled() { "&csv_bulk_data" } else { "&[]" },
+                ),
+                columns_names = columns_names,
                 data = data,
                 csv_write = csv_write,
                 csv_bulk_data = if storage.csv_enabled() {
@@ -1,4 +1,10 @@
-use crate::{manifest::network::Network, types::code::Code};
+use crate::{
+    manifest::network::{
+        BlockCacheSettings, CircuitBreakerSettings, ComputeUnitCosts, Network, NetworkProxy,
+        NetworkRetryPolicy, RpcDebugSettings,
+    },
+    types::code::Code,
+};
 
 fn network_provider_name(network: &Network) -> String {
     network_provider_name_from_name(&network.name)
@@ -12,28 +18,150 @@ pub fn network_provider_fn_name(network: &Network) -> String {
     format!("get_{fn_name}", fn_name = network_provider_name(network).to_lowercase())
 }
 
+/// Batches eth_calls for the network through Multicall3
+pub fn network_multicall_fn_name(network: &Network) -> String {
+    format!("get_{network_name}_multicall", network_name = network.name.to_lowercase())
+}
+
+fn max_block_range_code(network: &Network) -> String {
+    if let Some(max_block_range) = network.max_block_range {
+        format!("Some(U64::from({}))", max_block_range)
+    } else {
+        "None".to_string()
+    }
+}
+
+fn option_code<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "None".to_string(), |value| format!("Some({})", value))
+}
+
+fn retry_policy_code(retry_policy: &NetworkRetryPolicy) -> String {
+    format!(
+        "rindexer::manifest::network::NetworkRetryPolicy {{ max_rate_limit_retries: {}, max_timeout_retries: {}, initial_backoff_ms: {}, request_timeout_ms: {}, rate_limit_error_codes: {} }}",
+        option_code(retry_policy.max_rate_limit_retries),
+        option_code(retry_policy.max_timeout_retries),
+        option_code(retry_policy.initial_backoff_ms),
+        option_code(retry_policy.request_timeout_ms),
+        option_code(retry_policy.rate_limit_error_codes.as_ref().map(|codes| {
+            format!(
+                "vec![{}]",
+                codes.iter().map(|code| code.to_string()).collect::<Vec<_>>().join(", ")
+            )
+        })),
+    )
+}
+
+fn compute_unit_costs_code(compute_unit_costs: &ComputeUnitCosts) -> String {
+    format!(
+        "rindexer::manifest::network::ComputeUnitCosts {{ preset: {}, methods: {} }}",
+        option_code(compute_unit_costs.preset.map(|preset| {
+            format!("rindexer::manifest::network::ComputeUnitPreset::{:?}", preset)
+        })),
+        option_code(compute_unit_costs.methods.as_ref().map(|methods| {
+            format!(
+                "[{}].into_iter().collect()",
+                methods
+                    .iter()
+                    .map(|(method, cost)| format!("(\"{}\".to_string(), {})", method, cost))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })),
+    )
+}
+
+fn circuit_breaker_code(circuit_breaker: &CircuitBreakerSettings) -> String {
+    format!(
+        "rindexer::manifest::network::CircuitBreakerSettings {{ error_rate_threshold: {}, window: {}, open_duration_ms: {} }}",
+        // debug keeps the decimal point so whole numbers still parse as f64
+        option_code(circuit_breaker.error_rate_threshold.map(|threshold| format!("{:?}", threshold))),
+        option_code(circuit_breaker.window),
+        option_code(circuit_breaker.open_duration_ms),
+    )
+}
+
+/// Values can be env variable names so secrets never end up in the generated code
+fn env_value_code(value: &str) -> String {
+    format!(r#"public_read_env_value("{value}").unwrap_or("{value}".to_string())"#)
+}
+
+/// Proxy credentials are always env variable names, only the lookup ends up in the code
+fn env_lookup_code(name: &str) -> String {
+    format!(r#"public_read_env_value("{name}").expect("{name} is not set")"#)
+}
+
+fn proxy_code(proxy: &NetworkProxy) -> String {
+    format!(
+        "rindexer::manifest::network::NetworkProxy {{ url: {}, username: {}, password: {} }}",
+        env_value_code(&proxy.url),
+        option_code(proxy.username.as_deref().map(env_lookup_code)),
+        option_code(proxy.password.as_deref().map(env_lookup_code)),
+    )
+}
+
+fn rpc_debug_code(rpc_debug: &RpcDebugSettings) -> String {
+    format!(
+        "rindexer::manifest::network::RpcDebugSettings {{ methods: {}, max_logs_per_minute: {} }}",
+        option_code(rpc_debug.methods.as_ref().map(|methods| {
+            format!(
+                "vec![{}]",
+                methods
+                    .iter()
+                    .map(|method| format!("\"{}\".to_string()", method))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })),
+        option_code(rpc_debug.max_logs_per_minute),
+    )
+}
+
+fn block_cache_code(block_cache: &BlockCacheSettings) -> String {
+    format!(
+        "rindexer::manifest::network::BlockCacheSettings {{ max_blocks: {}, disk_path: {} }}",
+        option_code(block_cache.max_blocks),
+        option_code(block_cache.disk_path.as_ref().map(|path| format!("{:?}.to_string()", path))),
+    )
+}
+
+/// Every network goes through the load balanced client even with a single rpc so all of the
+/// network settings are applied
 fn generate_network_lazy_provider_code(network: &Network) -> Code {
+    let endpoints = network
+        .rpc_endpoints()
+        .iter()
+        .map(|endpoint| {
+            format!(
+                r#"rindexer::manifest::network::NetworkRpcEndpoint {{ url: public_read_env_value("{url}").unwrap_or("{url}".to_string()), compute_units_per_second: {compute_units_per_second} }}"#,
+                url = endpoint.url,
+                compute_units_per_second =
+                    option_code(endpoint.compute_units_per_second),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
     Code::new(format!(
         r#"
-            static ref {network_name}: Arc<JsonRpcCachedProvider> = {client_fn}(&public_read_env_value("{network_url}").unwrap_or("{network_url}".to_string()), {compute_units_per_second}, {max_block_range} {placeholder_headers}).expect("Error creating provider");
+            static ref {network_name}: Arc<JsonRpcCachedProvider> = rindexer::provider::create_load_balanced_client(&[{endpoints}], &rindexer::provider::RpcClientOptions {{ max_block_range: {max_block_range}, retry_policy: {retry_policy}, compute_unit_costs: {compute_unit_costs}, merge_get_logs: {merge_get_logs}, max_addresses_per_get_logs: {max_addresses_per_get_logs}, block_poll_interval_ms: {block_poll_interval_ms}, circuit_breaker: {circuit_breaker}, log_fetch_strategy: rindexer::manifest::network::LogFetchStrategy::{log_fetch_strategy:?}, proxy: {proxy}, rpc_debug: {rpc_debug}, block_cache: {block_cache}, head_quorum: {head_quorum} }}, {headers}).expect("Error creating provider");
         "#,
         network_name = network_provider_name(network),
-        network_url = network.rpc,
-        compute_units_per_second =
-            if let Some(compute_units_per_second) = network.compute_units_per_second {
-                format!("Some({})", compute_units_per_second)
-            } else {
-                "None".to_string()
-            },
-        max_block_range = if let Some(max_block_range) = network.max_block_range {
-            format!("Some(U64::from({}))", max_block_range)
-        } else {
-            "None".to_string()
-        },
-        client_fn =
-            if network.rpc.contains("shadow") { "create_shadow_client" } else { "create_client" },
-        placeholder_headers =
-            if network.rpc.contains("shadow") { "" } else { ", HeaderMap::new()" },
+        endpoints = endpoints,
+        max_block_range = max_block_range_code(network),
+        retry_policy = retry_policy_code(&network.retry.clone().unwrap_or_default()),
+        merge_get_logs = network.merge_get_logs.unwrap_or_default(),
+        max_addresses_per_get_logs = option_code(network.max_addresses_per_get_logs),
+        block_poll_interval_ms = option_code(network.block_poll_interval_ms),
+        compute_unit_costs =
+            option_code(network.compute_unit_costs.as_ref().map(compute_unit_costs_code)),
+        circuit_breaker = option_code(network.circuit_breaker.as_ref().map(circuit_breaker_code)),
+        log_fetch_strategy = network.log_fetch_strategy.unwrap_or_default(),
+        proxy = option_code(network.proxy.as_ref().map(proxy_code)),
+        rpc_debug = option_code(network.rpc_debug.as_ref().map(rpc_debug_code)),
+        block_cache = option_code(network.block_cache.as_ref().map(block_cache_code)),
+        head_quorum = option_code(network.head_quorum()),
+        headers =
+            if network.rpc.contains("shadow") { "shadow_headers()" } else { "HeaderMap::new()" },
     ))
 }
 
@@ -47,8 +175,13 @@ fn generate_network_provider_code(network: &Network) -> Code {
             pub fn {fn_name}() -> Arc<Provider<RetryClient<Http>>> {{
                 {provider_lazy_name}.get_inner_provider()
             }}
+
+            pub fn {multicall_fn_name}() -> Multicall {{
+                Multicall::new(Arc::clone(&{provider_lazy_name}))
+            }}
         "#,
         fn_name = network_provider_fn_name(network),
+        multicall_fn_name = network_multicall_fn_name(network),
         provider_lazy_name = network_provider_name(network)
     ))
 }
@@ -89,26 +222,24 @@ pub fn generate_networks_code(networks: &[Network]) -> Code {
             /// Any manual changes to this file will be overwritten.
             
             use ethers::providers::{Provider, Http, RetryClient};
+            #[allow(unused_imports)]
             use ethers::types::U64;
             use rindexer::{
                 lazy_static,
-                provider::{create_client, JsonRpcCachedProvider, RetryClientError},
+                multicall::Multicall,
+                provider::JsonRpcCachedProvider,
                 public_read_env_value, HeaderMap,
             };
             use std::sync::Arc;
             
             #[allow(dead_code)]
-            fn create_shadow_client(
-                rpc_url: &str,
-                compute_units_per_second: Option<u64>,
-                max_block_range: Option<U64>,
-            ) -> Result<Arc<JsonRpcCachedProvider>, RetryClientError> {
+            fn shadow_headers() -> HeaderMap {
                 let mut header = HeaderMap::new();
                 header.insert(
                     "X-SHADOW-API-KEY",
                     public_read_env_value("RINDEXER_PHANTOM_API_KEY").unwrap().parse().unwrap(),
                 );
-                create_client(rpc_url, compute_units_per_second, max_block_range, header)
+                header
             }
 
             lazy_static! {
@@ -129,3 +260,69 @@ pub fn generate_networks_code(networks: &[Network]) -> Code {
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_rpc_network_gets_client_options() {
+        let network: Network = serde_yaml::from_str(
+            r#"
+            name: ethereum
+            chain_id: 1
+            rpc: https://eth.example.com
+            max_block_range: 1000
+            merge_get_logs: true
+            circuit_breaker:
+              window: 20
+            "#,
+        )
+        .unwrap();
+
+        let code = generate_network_lazy_provider_code(&network).to_string();
+        assert!(code.contains("rindexer::provider::create_load_balanced_client("));
+        assert!(code.contains("max_block_range: Some(U64::from(1000))"));
+        assert!(code.contains("merge_get_logs: true"));
+        assert!(code.contains("window: Some(20)"));
+        assert!(code.contains("HeaderMap::new()"));
+    }
+
+    #[test]
+    fn test_shadow_network_keeps_its_api_key_header() {
+        let network: Network = serde_yaml::from_str(
+            r#"
+            name: ethereum
+            chain_id: 1
+            rpc: https://shadow.example.com
+            "#,
+        )
+        .unwrap();
+
+        let code = generate_network_lazy_provider_code(&network).to_string();
+        assert!(code.contains("rindexer::provider::create_load_balanced_client("));
+        assert!(code.contains("shadow_headers()"));
+    }
+
+    #[test]
+    fn test_proxy_credentials_are_read_from_the_env() {
+        let network: Network = serde_yaml::from_str(
+            r#"
+            name: ethereum
+            chain_id: 1
+            rpc: https://eth.example.com
+            proxy:
+              url: http://proxy.internal:3128
+              username: PROXY_USERNAME
+              password: PROXY_PASSWORD
+            "#,
+        )
+        .unwrap();
+
+        let code = generate_network_lazy_provider_code(&network).to_string();
+        assert!(code.contains(
+            r#"password: Some(public_read_env_value("PROXY_PASSWORD").expect("PROXY_PASSWORD is not set"))"#
+        ));
+        assert!(!code.contains(r#"unwrap_or("PROXY_PASSWORD""#));
+    }
+}
@@ -0,0 +1,348 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    future::Future,
+    sync::{Arc, OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tracing::info;
+
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        internal_schema::{internal_schema, internal_table},
+    },
+    helpers::camel_to_snake,
+};
+
+/// Entries kept before the oldest written ones are evicted
+const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum HandlerCacheError {
+    #[error("Could not serialize the value of {0}: {1}")]
+    Serialize(String, serde_json::Error),
+
+    #[error("Could not persist the handler cache: {0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("Could not load the value of {0}: {1}")]
+    Loader(String, String),
+}
+
+pub fn handler_cache_table_name(indexer_name: &str) -> String {
+    internal_table(&format!("{}_handler_cache", camel_to_snake(indexer_name)))
+}
+
+struct CachedValue {
+    value: Value,
+    expires_at: Option<Instant>,
+    written: u64,
+}
+
+impl CachedValue {
+    fn is_live(&self) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > Instant::now())
+    }
+}
+
+/// Values with the order they were written in, oldest first
+#[derive(Default)]
+struct CachedValues {
+    values: HashMap<String, CachedValue>,
+    written: BTreeMap<u64, String>,
+    writes: u64,
+}
+
+impl CachedValues {
+    fn insert(&mut self, key: String, value: Value, ttl: Option<Duration>, max_entries: usize) {
+        self.remove(&key);
+        while self.values.len() >= max_entries.max(1) {
+            let Some((_, oldest)) = self.written.pop_first() else {
+                break;
+            };
+            self.values.remove(&oldest);
+        }
+
+        self.writes += 1;
+        self.written.insert(self.writes, key.clone());
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.values.insert(key, CachedValue { value, expires_at, written: self.writes });
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(cached) = self.values.remove(key) {
+            self.written.remove(&cached.written);
+        }
+    }
+}
+
+/// Key value scratchpad shared by every handler of the process, for lookups like token decimals
+/// which would otherwise be fetched for every event. Values can expire and the oldest written
+/// are evicted once the cache is full. With `persist_handler_cache` on the postgres storage every
+/// write also goes to postgres and is loaded back on the next start
+pub struct HandlerCache {
+    values: RwLock<CachedValues>,
+    max_entries: usize,
+    persistence: OnceLock<(Arc<PostgresClient>, String)>,
+}
+
+impl Default for HandlerCache {
+    fn default() -> Self {
+        Self::with_max_entries(DEFAULT_MAX_ENTRIES)
+    }
+}
+
+impl HandlerCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_entries(max_entries: usize) -> Self {
+        Self {
+            values: RwLock::new(CachedValues::default()),
+            max_entries,
+            persistence: OnceLock::new(),
+        }
+    }
+
+    // a panicking handler can not leave the map half written so a poisoned lock is still usable
+    fn read(&self) -> RwLockReadGuard<'_, CachedValues> {
+        self.values.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, CachedValues> {
+        self.values.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Loads the most recently written values earlier runs persisted which have not expired yet
+    /// and writes every change from now on through to postgres
+    pub async fn persist_to(
+        &self,
+        client: Arc<PostgresClient>,
+        indexer_name: &str,
+    ) -> Result<(), HandlerCacheError> {
+        let table_name = handler_cache_table_name(indexer_name);
+        client
+            .batch_execute(&format!(
+                r#"
+                CREATE SCHEMA IF NOT EXISTS {0};
+                CREATE TABLE IF NOT EXISTS {1} (
+                    key TEXT PRIMARY KEY,
+                    value JSONB NOT NULL,
+                    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+                );
+                ALTER TABLE {1} ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+                DELETE FROM {1} WHERE expires_at <= NOW();
+            "#,
+                internal_schema(),
+                table_name
+            ))
+            .await?;
+
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT key, value::TEXT, EXTRACT(EPOCH FROM expires_at - NOW())::FLOAT8 \
+                    FROM {} ORDER BY updated_at DESC LIMIT $1",
+                    table_name
+                ),
+                &[&(self.max_entries as i64)],
+            )
+            .await?;
+        {
+            let mut values = self.write();
+            // oldest first so the most recently written are the last to be evicted
+            for row in rows.iter().rev() {
+                let key: String = row.get(0);
+                let value: String = row.get(1);
+                let ttl: Option<f64> = row.get(2);
+                if values.values.contains_key(&key) {
+                    continue;
+                }
+                if let Ok(value) = serde_json::from_str(&value) {
+                    let ttl = ttl.map(|ttl| Duration::from_secs_f64(ttl.max(0.0)));
+                    values.insert(key, value, ttl, self.max_entries);
+                }
+            }
+        }
+        info!("Loaded {} handler cache entries from {}", rows.len(), table_name);
+
+        let _ = self.persistence.set((client, table_name));
+        Ok(())
+    }
+
+    /// None when the key is missing, expired or its value is not a `T`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.read().values.get(key).filter(|cached| cached.is_live())?.value.clone();
+        serde_json::from_value(value).ok()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.read().values.get(key).is_some_and(CachedValue::is_live)
+    }
+
+    /// Includes expired values which have not been overwritten or evicted yet
+    pub fn len(&self) -> usize {
+        self.read().values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub async fn insert<T: Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), HandlerCacheError> {
+        self.insert_with_ttl(key, value, None).await
+    }
+
+    /// With a ttl the value is treated as missing once it expires
+    pub async fn insert_with_ttl<T: Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> Result<(), HandlerCacheError> {
+        let value = serde_json::to_value(value)
+            .map_err(|e| HandlerCacheError::Serialize(key.to_string(), e))?;
+
+        if let Some((client, table_name)) = self.persistence.get() {
+            let ttl_seconds = ttl.map(|ttl| ttl.as_secs_f64());
+            client
+                .execute(
+                    &format!(
+                        "INSERT INTO {} (key, value, expires_at) \
+                        VALUES ($1, $2::TEXT::JSONB, NOW() + $3::FLOAT8 * INTERVAL '1 second') \
+                        ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value, \
+                        expires_at = EXCLUDED.expires_at, updated_at = NOW()",
+                        table_name
+                    ),
+                    &[&key, &value.to_string(), &ttl_seconds],
+                )
+                .await?;
+        }
+
+        self.write().insert(key.to_string(), value, ttl, self.max_entries);
+        Ok(())
+    }
+
+    pub async fn remove(&self, key: &str) -> Result<(), HandlerCacheError> {
+        if let Some((client, table_name)) = self.persistence.get() {
+            client.execute(&format!("DELETE FROM {} WHERE key = $1", table_name), &[&key]).await?;
+        }
+
+        self.write().remove(key);
+        Ok(())
+    }
+
+    /// The cached value or the one `load` returns, which is cached for the next call. Concurrent
+    /// misses on the same key may both load, the last write wins
+    pub async fn get_or_try_insert_with<T, E, F, Fut>(
+        &self,
+        key: &str,
+        load: F,
+    ) -> Result<T, HandlerCacheError>
+    where
+        T: Serialize + DeserializeOwned,
+        E: Display,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(value) = self.get(key) {
+            return Ok(value);
+        }
+
+        let value =
+            load().await.map_err(|e| HandlerCacheError::Loader(key.to_string(), e.to_string()))?;
+        self.insert(key, &value).await?;
+        Ok(value)
+    }
+}
+
+/// The cache every generated handler context shares
+pub fn handler_cache() -> Arc<HandlerCache> {
+    static HANDLER_CACHE: OnceLock<Arc<HandlerCache>> = OnceLock::new();
+    Arc::clone(HANDLER_CACHE.get_or_init(|| Arc::new(HandlerCache::new())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handler_cache_get_or_try_insert_with() {
+        let cache = HandlerCache::new();
+
+        let decimals: u8 = cache
+            .get_or_try_insert_with("decimals:0x01", || async { Ok::<_, String>(18) })
+            .await
+            .unwrap();
+        assert_eq!(decimals, 18);
+
+        // cached so the loader does not run again
+        let decimals: u8 = cache
+            .get_or_try_insert_with("decimals:0x01", || async {
+                Err::<u8, _>("should not load".to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(decimals, 18);
+        assert!(cache.get::<String>("decimals:0x01").is_none());
+
+        cache.remove("decimals:0x01").await.unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handler_cache_evicts_oldest_and_expires() {
+        let cache = HandlerCache::with_max_entries(2);
+        cache.insert("a", &1).await.unwrap();
+        cache.insert("b", &2).await.unwrap();
+        // rewriting a makes b the oldest write
+        cache.insert("a", &3).await.unwrap();
+        cache.insert("c", &4).await.unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get::<u8>("a"), Some(3));
+        assert!(!cache.contains_key("b"));
+        assert_eq!(cache.get::<u8>("c"), Some(4));
+
+        cache.insert_with_ttl("d", &5, Some(Duration::ZERO)).await.unwrap();
+        assert!(cache.get::<u8>("d").is_none());
+        assert!(!cache.contains_key("d"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database in DATABASE_URL"]
+    async fn test_handler_cache_persists_ttl() {
+        let postgres = PostgresClient::new().await.expect("DATABASE_URL has to point to postgres");
+        let postgres = Arc::new(postgres);
+        let indexer_name = "HandlerCacheTtlTest";
+        postgres
+            .batch_execute(&format!(
+                "DROP TABLE IF EXISTS {}",
+                handler_cache_table_name(indexer_name)
+            ))
+            .await
+            .unwrap();
+
+        let cache = HandlerCache::new();
+        cache.persist_to(Arc::clone(&postgres), indexer_name).await.unwrap();
+        cache.insert("kept", &1).await.unwrap();
+        cache.insert_with_ttl("expired", &2, Some(Duration::ZERO)).await.unwrap();
+        cache.insert_with_ttl("expiring", &3, Some(Duration::from_secs(3600))).await.unwrap();
+
+        let reloaded = HandlerCache::new();
+        reloaded.persist_to(Arc::clone(&postgres), indexer_name).await.unwrap();
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded.get::<u8>("kept"), Some(1));
+        assert_eq!(reloaded.get::<u8>("expiring"), Some(3));
+        assert!(!reloaded.contains_key("expired"));
+    }
+}
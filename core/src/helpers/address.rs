@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ethers::{types::Address, utils::to_checksum};
+use serde::Serializer;
+
+use crate::manifest::global::AddressFormat;
+
+// set once on startup from the manifest, addresses get written from too many places to thread it
+static CHECKSUM_ADDRESSES: AtomicBool = AtomicBool::new(false);
+
+pub fn set_address_format(address_format: AddressFormat) {
+    CHECKSUM_ADDRESSES.store(address_format == AddressFormat::Checksum, Ordering::Relaxed);
+}
+
+fn format_address_with(address: &Address, checksum: bool) -> String {
+    if checksum {
+        to_checksum(address, None)
+    } else {
+        format!("{:?}", address)
+    }
+}
+
+/// Formats the address how it is written to postgres, csv and streams
+pub fn format_address(address: &Address) -> String {
+    format_address_with(address, CHECKSUM_ADDRESSES.load(Ordering::Relaxed))
+}
+
+pub fn serialize_address<S>(address: &Address, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_address(address))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_format_address_with() {
+        let address = Address::from_str("0xae78736cd615f374d3085123a210448e74fc6393").unwrap();
+
+        assert_eq!(
+            format_address_with(&address, false),
+            "0xae78736cd615f374d3085123a210448e74fc6393"
+        );
+        assert_eq!(
+            format_address_with(&address, true),
+            "0xae78736Cd615f374D3085123A210448E74Fc6393"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::unnecessary_map_or)]
+
 use std::{fs, fs::File, io::Write, path::Path, process::Command};
 
 use dotenv::{dotenv, from_path};
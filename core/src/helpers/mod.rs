@@ -1,17 +1,22 @@
+#[cfg(feature = "graphql")]
 mod thread;
 
+#[cfg(feature = "graphql")]
 pub use thread::set_thread_no_logging;
 
 mod file;
 
+mod address;
+#[cfg(feature = "graphql")]
+use std::process::Command;
 use std::{
     env,
     env::VarError,
     path::{Path, PathBuf},
-    process::Command,
     str,
 };
 
+pub use address::{format_address, serialize_address, set_address_format};
 use dotenv::dotenv;
 pub use file::{
     create_mod_file, format_all_files_for_project, load_env_from_path, write_file,
@@ -80,6 +85,7 @@ pub fn get_full_path(project_path: &Path, file_path: &str) -> Result<PathBuf, st
     }
 }
 
+#[cfg(feature = "graphql")]
 pub fn kill_process_on_port(port: u16) -> Result<(), String> {
     // Use lsof to find the process using the port
     let output = Command::new("lsof")
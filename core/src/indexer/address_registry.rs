@@ -0,0 +1,269 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use ethers::{
+    abi::{decode, ParamType, Token},
+    prelude::ProviderError,
+    types::{
+        transaction::eip2718::TypedTransaction, Address, TransactionRequest, ValueOrArray, U64,
+    },
+    utils::id,
+};
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+use crate::{
+    event::contract_setup::{IndexingContractSetup, NetworkContract},
+    indexer::DeploymentBlocks,
+    manifest::contract::AddressRegistry,
+    provider::JsonRpcCachedProvider,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum AddressRegistryError {
+    #[error("Provider error calling {0}: {1}")]
+    ProviderError(String, ProviderError),
+
+    #[error("Registry {0} did not return an address[]: {1}")]
+    InvalidResponse(String, String),
+
+    #[error("Registry {0} on {1} returned no addresses")]
+    NoAddresses(String, String),
+}
+
+async fn fetch_registry_addresses(
+    provider: &JsonRpcCachedProvider,
+    registry: &AddressRegistry,
+) -> Result<Vec<Address>, AddressRegistryError> {
+    let tx: TypedTransaction =
+        TransactionRequest::new().to(registry.address).data(id(&registry.function).to_vec()).into();
+
+    let response = provider
+        .call(&tx, None)
+        .await
+        .map_err(|e| AddressRegistryError::ProviderError(registry.function.clone(), e))?;
+
+    let tokens =
+        decode(&[ParamType::Array(Box::new(ParamType::Address))], &response).map_err(|e| {
+            AddressRegistryError::InvalidResponse(registry.function.clone(), e.to_string())
+        })?;
+
+    match tokens.into_iter().next() {
+        Some(Token::Array(tokens)) => {
+            Ok(tokens.into_iter().filter_map(Token::into_address).collect())
+        }
+        _ => Err(AddressRegistryError::InvalidResponse(
+            registry.function.clone(),
+            "empty response".to_string(),
+        )),
+    }
+}
+
+fn merge_addresses(inline: &ValueOrArray<Address>, discovered: Vec<Address>) -> Vec<Address> {
+    let mut addresses = match inline {
+        ValueOrArray::Value(address) => vec![*address],
+        ValueOrArray::Array(addresses) => addresses.clone(),
+    };
+    addresses.extend(discovered);
+
+    let mut seen = HashSet::new();
+    addresses.retain(|address| seen.insert(*address));
+    addresses
+}
+
+/// The latest address set of a registry, the version is bumped on every refresh which changes it
+/// so live indexing knows to rebuild its filter
+pub struct RegistryAddresses {
+    network: String,
+    addresses: RwLock<Vec<Address>>,
+    version: AtomicU64,
+    deployment_blocks: Arc<DeploymentBlocks>,
+}
+
+impl RegistryAddresses {
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Relaxed)
+    }
+
+    pub async fn addresses(&self) -> Vec<Address> {
+        self.addresses.read().await.clone()
+    }
+
+    /// Addresses in the set which the filter does not cover yet
+    pub fn added(filter: &Option<ValueOrArray<Address>>, addresses: &[Address]) -> Vec<Address> {
+        addresses
+            .iter()
+            .filter(|address| match filter {
+                Some(ValueOrArray::Value(filter_address)) => filter_address != *address,
+                Some(ValueOrArray::Array(filter_addresses)) => !filter_addresses.contains(address),
+                None => true,
+            })
+            .copied()
+            .collect()
+    }
+
+    /// The first block any of the addresses was deployed at so their history can be backfilled
+    pub async fn deployed_from(
+        &self,
+        provider: &JsonRpcCachedProvider,
+        addresses: &[Address],
+        latest_block: U64,
+    ) -> Option<U64> {
+        self.deployment_blocks.detect(provider, &self.network, addresses, latest_block).await
+    }
+
+    async fn update(&self, addresses: Vec<Address>) -> bool {
+        let mut current = self.addresses.write().await;
+        if *current == addresses {
+            return false;
+        }
+        *current = addresses;
+        self.version.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+}
+
+type RegistryKey = (String, Address, String);
+
+/// Resolves each registry once per network no matter how many events share it and spawns a
+/// refresh task for those with an interval
+pub struct AddressRegistryResolver {
+    resolved: HashMap<RegistryKey, Arc<RegistryAddresses>>,
+    deployment_blocks: Arc<DeploymentBlocks>,
+}
+
+impl AddressRegistryResolver {
+    pub fn new(deployment_blocks: Arc<DeploymentBlocks>) -> Self {
+        Self { resolved: HashMap::new(), deployment_blocks }
+    }
+
+    /// Returns the network contract with the registry addresses merged into its address set
+    pub async fn resolve(
+        &mut self,
+        network_contract: &NetworkContract,
+    ) -> Result<(NetworkContract, Option<Arc<RegistryAddresses>>), AddressRegistryError> {
+        let IndexingContractSetup::Address(address_details) =
+            &network_contract.indexing_contract_setup
+        else {
+            return Ok((network_contract.clone(), None));
+        };
+        let Some(registry) = &address_details.registry else {
+            return Ok((network_contract.clone(), None));
+        };
+
+        let key = (network_contract.network.clone(), registry.address, registry.function.clone());
+        let registry_addresses = match self.resolved.get(&key) {
+            Some(registry_addresses) => Arc::clone(registry_addresses),
+            None => {
+                let discovered =
+                    fetch_registry_addresses(&network_contract.cached_provider, registry).await?;
+                info!(
+                    "Address registry {} {} on {} returned {} addresses",
+                    registry.address,
+                    registry.function,
+                    network_contract.network,
+                    discovered.len()
+                );
+
+                // an empty address list in eth_getLogs matches every contract
+                let addresses = merge_addresses(&address_details.address, discovered);
+                if addresses.is_empty() {
+                    return Err(AddressRegistryError::NoAddresses(
+                        registry.function.clone(),
+                        network_contract.network.clone(),
+                    ));
+                }
+
+                let registry_addresses = Arc::new(RegistryAddresses {
+                    network: network_contract.network.clone(),
+                    addresses: RwLock::new(addresses),
+                    version: AtomicU64::new(0),
+                    deployment_blocks: Arc::clone(&self.deployment_blocks),
+                });
+
+                if let Some(refresh_interval_seconds) = registry.refresh_interval_seconds {
+                    spawn_registry_refresh(
+                        Arc::clone(&network_contract.cached_provider),
+                        registry.clone(),
+                        address_details.address.clone(),
+                        Arc::clone(&registry_addresses),
+                        Duration::from_secs(refresh_interval_seconds),
+                    );
+                }
+
+                self.resolved.insert(key, Arc::clone(&registry_addresses));
+                registry_addresses
+            }
+        };
+
+        let mut resolved_address_details = address_details.clone();
+        resolved_address_details.address =
+            ValueOrArray::Array(registry_addresses.addresses().await);
+
+        let mut resolved = network_contract.clone();
+        resolved.indexing_contract_setup = IndexingContractSetup::Address(resolved_address_details);
+
+        Ok((resolved, Some(registry_addresses)))
+    }
+}
+
+fn spawn_registry_refresh(
+    provider: Arc<JsonRpcCachedProvider>,
+    registry: AddressRegistry,
+    inline: ValueOrArray<Address>,
+    registry_addresses: Arc<RegistryAddresses>,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            match fetch_registry_addresses(&provider, &registry).await {
+                Ok(discovered) if discovered.is_empty() => {
+                    error!(
+                        "Address registry {} {} returned no addresses - keeping the previous set",
+                        registry.address, registry.function
+                    );
+                }
+                Ok(discovered) => {
+                    let addresses = merge_addresses(&inline, discovered);
+                    let count = addresses.len();
+                    if registry_addresses.update(addresses).await {
+                        info!(
+                            "Address registry {} {} changed - now indexing {} addresses",
+                            registry.address, registry.function, count
+                        );
+                    }
+                }
+                Err(e) => {
+                    error!("Error refreshing address registry {}: {}", registry.address, e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_added_addresses() {
+        let (a, b, c) =
+            (Address::from_low_u64_be(1), Address::from_low_u64_be(2), Address::from_low_u64_be(3));
+
+        assert_eq!(RegistryAddresses::added(&Some(ValueOrArray::Value(a)), &[a, b]), vec![b]);
+        assert_eq!(
+            RegistryAddresses::added(&Some(ValueOrArray::Array(vec![a, b])), &[a, b, c]),
+            vec![c]
+        );
+        assert!(RegistryAddresses::added(&Some(ValueOrArray::Array(vec![a, b])), &[b]).is_empty());
+        assert_eq!(RegistryAddresses::added(&None, &[a]), vec![a]);
+    }
+}
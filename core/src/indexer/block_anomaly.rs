@@ -0,0 +1,394 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use ethers::{
+    prelude::ProviderError,
+    types::{Block, H256, U64},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    database::postgres::{
+        block_anomaly::{
+            block_anomalies_last_synced, delete_replaced_block_anomalies_sql,
+            insert_missed_slots_sql, update_block_anomalies_last_synced_sql, BLOCK_ANOMALY_COLUMNS,
+        },
+        client::{PostgresClient, PostgresError, TextStatement},
+        sql_type_wrapper::EthereumSqlTypeWrapper,
+    },
+    manifest::block_anomaly::{BlockAnomalyNetwork, BlockAnomalyTracking},
+    provider::{CreateNetworkProvider, JsonRpcCachedProvider},
+};
+
+/// Headers are fetched one call per block so ranges are kept small
+const BLOCK_ANOMALY_BLOCK_RANGE: u64 = 100;
+
+const BLOCK_ANOMALY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(thiserror::Error, Debug)]
+pub enum BlockAnomalyError {
+    #[error("Block anomaly network {0} is not a configured network")]
+    UnknownNetwork(String),
+
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+}
+
+#[derive(Debug, PartialEq)]
+enum BlockAnomaly {
+    Uncle { block_number: U64, uncle_hash: H256 },
+    MissedSlots { block_number: U64, block_hash: H256, missed_slots: u64 },
+}
+
+/// Headers must be consecutive and in order, the first one is only used as the parent of the
+/// second so the gap before it is not lost across ranges
+fn block_anomalies(
+    headers: &[Block<H256>],
+    from_block: U64,
+    slot_time: Option<u64>,
+) -> Vec<BlockAnomaly> {
+    let mut anomalies = vec![];
+    for (index, header) in headers.iter().enumerate() {
+        let (Some(block_number), Some(block_hash)) = (header.number, header.hash) else {
+            continue;
+        };
+        if block_number < from_block {
+            continue;
+        }
+
+        anomalies.extend(
+            header
+                .uncles
+                .iter()
+                .map(|uncle_hash| BlockAnomaly::Uncle { block_number, uncle_hash: *uncle_hash }),
+        );
+
+        let Some(slot_time) = slot_time.filter(|slot_time| *slot_time > 0) else {
+            continue;
+        };
+        let Some(parent) = index.checked_sub(1).map(|parent| &headers[parent]) else {
+            continue;
+        };
+        let gap = header.timestamp.saturating_sub(parent.timestamp).as_u64();
+        let missed_slots = (gap / slot_time).saturating_sub(1);
+        if missed_slots > 0 {
+            anomalies.push(BlockAnomaly::MissedSlots { block_number, block_hash, missed_slots });
+        }
+    }
+    anomalies
+}
+
+/// The first recorded block whose hash is no longer the one on the chain
+fn fork_block(recorded: &BTreeMap<U64, H256>, canonical: &BTreeMap<U64, H256>) -> Option<U64> {
+    recorded
+        .iter()
+        .find(|(block_number, block_hash)| canonical.get(*block_number) != Some(*block_hash))
+        .map(|(block_number, _)| *block_number)
+}
+
+fn hash_row(
+    network: &str,
+    block_number: U64,
+    kind: &str,
+    hash: H256,
+) -> Vec<EthereumSqlTypeWrapper> {
+    vec![
+        EthereumSqlTypeWrapper::String(network.to_string()),
+        EthereumSqlTypeWrapper::U64(block_number),
+        EthereumSqlTypeWrapper::String(kind.to_string()),
+        EthereumSqlTypeWrapper::H256(hash),
+    ]
+}
+
+struct BlockAnomalyRecorder {
+    table_name: String,
+    last_synced_table_name: String,
+    postgres: Arc<PostgresClient>,
+}
+
+impl BlockAnomalyRecorder {
+    fn last_synced_statement(&self, network: &str, block_number: U64) -> TextStatement {
+        (
+            update_block_anomalies_last_synced_sql(&self.last_synced_table_name),
+            vec![Some(network.to_string()), Some(block_number.to_string())],
+        )
+    }
+
+    /// Records the replaced hashes and drops what was recorded about the replaced blocks, the
+    /// blocks are recorded again from the fork
+    async fn record_reorg(
+        &self,
+        network: &str,
+        fork_block: U64,
+        replaced: BTreeMap<U64, H256>,
+    ) -> Result<(), BlockAnomalyError> {
+        let rows: Vec<Vec<EthereumSqlTypeWrapper>> = replaced
+            .into_iter()
+            .map(|(block_number, block_hash)| {
+                hash_row(network, block_number, "reorged", block_hash)
+            })
+            .collect();
+        let columns: Vec<String> = BLOCK_ANOMALY_COLUMNS.iter().map(|c| c.to_string()).collect();
+
+        self.postgres
+            .insert_bulk_with_statements(
+                &self.table_name,
+                &columns,
+                &rows,
+                true,
+                &[],
+                &[
+                    (
+                        delete_replaced_block_anomalies_sql(&self.table_name),
+                        vec![Some(network.to_string()), Some(fork_block.to_string())],
+                    ),
+                    self.last_synced_statement(network, fork_block.saturating_sub(U64::one())),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Records the range and returns the block to carry on from, which goes back to the fork when
+    /// a reorg replaced blocks recorded before
+    async fn record_range(
+        &self,
+        network: &BlockAnomalyNetwork,
+        provider: &JsonRpcCachedProvider,
+        recent: &mut BTreeMap<U64, H256>,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<U64, BlockAnomalyError> {
+        let headers =
+            provider.get_block_headers(from_block.saturating_sub(U64::one()), to_block).await?;
+
+        let parent = headers.first().filter(|_| !from_block.is_zero());
+        let parent_replaced = parent.is_some_and(|parent| {
+            parent
+                .number
+                .and_then(|number| recent.get(&number))
+                .is_some_and(|recorded| parent.hash.as_ref() != Some(recorded))
+        });
+        if parent_replaced {
+            let (Some(first), Some(last)) = (recent.keys().next(), recent.keys().next_back())
+            else {
+                return Ok(from_block);
+            };
+            let canonical: BTreeMap<U64, H256> = provider
+                .get_block_headers(*first, *last)
+                .await?
+                .into_iter()
+                .filter_map(|header| Some((header.number?, header.hash?)))
+                .collect();
+            if let Some(fork_block) = fork_block(recent, &canonical) {
+                let replaced = recent.split_off(&fork_block);
+                warn!(
+                    "Block anomalies - {} - reorg replaced {} blocks from {}",
+                    network.network,
+                    replaced.len(),
+                    fork_block
+                );
+                self.record_reorg(&network.network, fork_block, replaced).await?;
+                return Ok(fork_block);
+            }
+        }
+
+        let mut rows = vec![];
+        let mut statements = vec![];
+        for anomaly in block_anomalies(&headers, from_block, network.slot_time) {
+            match anomaly {
+                BlockAnomaly::Uncle { block_number, uncle_hash } => {
+                    rows.push(hash_row(&network.network, block_number, "uncle", uncle_hash));
+                }
+                BlockAnomaly::MissedSlots { block_number, block_hash, missed_slots } => {
+                    statements.push((
+                        insert_missed_slots_sql(&self.table_name),
+                        vec![
+                            Some(network.network.clone()),
+                            Some(block_number.to_string()),
+                            Some(format!("{:?}", block_hash)),
+                            Some(missed_slots.to_string()),
+                        ],
+                    ));
+                }
+            }
+        }
+        let anomalies = rows.len() + statements.len();
+        // progress is written with the anomalies so a restart carries on from here
+        statements.push(self.last_synced_statement(&network.network, to_block));
+
+        let columns: Vec<String> = BLOCK_ANOMALY_COLUMNS.iter().map(|c| c.to_string()).collect();
+        self.postgres
+            .insert_bulk_with_statements(&self.table_name, &columns, &rows, true, &[], &statements)
+            .await?;
+
+        if anomalies > 0 {
+            info!(
+                "Block anomalies - {} - {} anomalies - blocks: {} - {}",
+                network.network, anomalies, from_block, to_block
+            );
+        }
+        recent.extend(
+            headers
+                .iter()
+                .filter_map(|header| Some((header.number?, header.hash?)))
+                .filter(|(block_number, _)| *block_number >= from_block),
+        );
+
+        Ok(to_block + 1)
+    }
+
+    async fn follow_network(
+        self: Arc<Self>,
+        network: BlockAnomalyNetwork,
+        provider: Arc<JsonRpcCachedProvider>,
+    ) -> Result<(), BlockAnomalyError> {
+        let reorg_distance = provider.reorg_safe_distance().await?;
+
+        let last_synced = block_anomalies_last_synced(
+            &self.postgres,
+            &self.last_synced_table_name,
+            &network.network,
+        )
+        .await?;
+        let mut from_block = match (last_synced, network.start_block) {
+            (Some(last_synced), _) => last_synced + 1,
+            (None, Some(start_block)) => start_block,
+            (None, None) => provider.get_block_number().await?,
+        };
+        // hashes of the recorded blocks a reorg can still replace
+        let mut recent: BTreeMap<U64, H256> = BTreeMap::new();
+
+        loop {
+            let latest_block = match provider.get_block_number().await {
+                Ok(latest_block) => latest_block,
+                Err(e) => {
+                    error!(
+                        "Block anomalies for {} could not get the latest block: {}",
+                        network.network, e
+                    );
+                    tokio::time::sleep(BLOCK_ANOMALY_POLL_INTERVAL).await;
+                    continue;
+                }
+            };
+            if from_block > latest_block {
+                tokio::time::sleep(BLOCK_ANOMALY_POLL_INTERVAL).await;
+                continue;
+            }
+
+            let to_block = latest_block.min(from_block + BLOCK_ANOMALY_BLOCK_RANGE - 1);
+            match self.record_range(&network, &provider, &mut recent, from_block, to_block).await {
+                Ok(next_block) => {
+                    from_block = next_block;
+                    recent = recent.split_off(&latest_block.saturating_sub(reorg_distance));
+                }
+                Err(e) => {
+                    error!(
+                        "Block anomalies for {} failed for blocks {} - {}: {}",
+                        network.network, from_block, to_block, e
+                    );
+                    tokio::time::sleep(BLOCK_ANOMALY_POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+}
+
+/// Starts a task per network which catches up and then follows the head of the chain, blocks
+/// are recorded before a reorg can no longer replace them so replaced hashes are seen
+pub fn start_block_anomaly_tracking(
+    block_anomalies: &BlockAnomalyTracking,
+    postgres: Arc<PostgresClient>,
+    network_providers: &[CreateNetworkProvider],
+) -> Result<(), BlockAnomalyError> {
+    let recorder = Arc::new(BlockAnomalyRecorder {
+        table_name: block_anomalies.table_name(),
+        last_synced_table_name: block_anomalies.last_synced_table_name(),
+        postgres,
+    });
+
+    for network in &block_anomalies.networks {
+        let provider = network_providers
+            .iter()
+            .find(|provider| provider.network_name == network.network)
+            .map(|provider| Arc::clone(&provider.client))
+            .ok_or_else(|| BlockAnomalyError::UnknownNetwork(network.network.clone()))?;
+
+        info!("Recording block anomalies on {}", network.network);
+        let recorder = Arc::clone(&recorder);
+        let network = network.clone();
+        tokio::spawn(async move {
+            let network_name = network.network.clone();
+            if let Err(e) = recorder.follow_network(network, provider).await {
+                error!("Block anomalies for {} stopped: {}", network_name, e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+
+    use super::*;
+
+    fn header(number: u64, timestamp: u64, uncles: Vec<H256>) -> Block<H256> {
+        Block {
+            number: Some(U64::from(number)),
+            hash: Some(H256::from_low_u64_be(number)),
+            timestamp: U256::from(timestamp),
+            uncles,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_block_anomalies_records_uncles_from_the_range_only() {
+        let headers = vec![
+            header(9, 100, vec![H256::from_low_u64_be(1)]),
+            header(10, 112, vec![H256::from_low_u64_be(2)]),
+        ];
+
+        assert_eq!(
+            block_anomalies(&headers, U64::from(10), None),
+            vec![BlockAnomaly::Uncle {
+                block_number: U64::from(10),
+                uncle_hash: H256::from_low_u64_be(2)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_block_anomalies_records_missed_slots() {
+        let headers =
+            vec![header(9, 100, vec![]), header(10, 112, vec![]), header(11, 148, vec![])];
+
+        assert_eq!(
+            block_anomalies(&headers, U64::from(10), Some(12)),
+            vec![BlockAnomaly::MissedSlots {
+                block_number: U64::from(11),
+                block_hash: H256::from_low_u64_be(11),
+                missed_slots: 2
+            }]
+        );
+        assert!(block_anomalies(&headers, U64::from(10), None).is_empty());
+    }
+
+    #[test]
+    fn test_fork_block_is_the_first_replaced_hash() {
+        let recorded: BTreeMap<U64, H256> =
+            (10..=13).map(|block| (U64::from(block), H256::from_low_u64_be(block))).collect();
+
+        let mut canonical = recorded.clone();
+        assert_eq!(fork_block(&recorded, &canonical), None);
+
+        canonical.insert(U64::from(12), H256::from_low_u64_be(112));
+        canonical.insert(U64::from(13), H256::from_low_u64_be(113));
+        assert_eq!(fork_block(&recorded, &canonical), Some(U64::from(12)));
+    }
+}
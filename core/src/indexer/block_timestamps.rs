@@ -0,0 +1,46 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ethers::{prelude::ProviderError, types::U64};
+
+use crate::provider::{CreateNetworkProvider, JsonRpcCachedProvider};
+
+#[derive(thiserror::Error, Debug)]
+pub enum BlockTimestampError {
+    #[error("No provider found for network {0}")]
+    NoProviderForNetwork(String),
+
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+}
+
+/// Looks up block timestamps for everything which needs to bucket events by time, the headers
+/// come from the network block cache shared with every other enrichment
+pub struct BlockTimestamps {
+    providers: HashMap<String, Arc<JsonRpcCachedProvider>>,
+}
+
+impl BlockTimestamps {
+    pub fn new(network_providers: &[CreateNetworkProvider]) -> Self {
+        Self {
+            providers: network_providers
+                .iter()
+                .map(|provider| (provider.network_name.clone(), Arc::clone(&provider.client)))
+                .collect(),
+        }
+    }
+
+    pub async fn timestamp(
+        &self,
+        network: &str,
+        block_number: U64,
+    ) -> Result<u64, BlockTimestampError> {
+        let block = self
+            .providers
+            .get(network)
+            .ok_or_else(|| BlockTimestampError::NoProviderForNetwork(network.to_string()))?
+            .get_block_header(block_number)
+            .await?;
+
+        Ok(block.timestamp.as_u64())
+    }
+}
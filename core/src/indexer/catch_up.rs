@@ -0,0 +1,233 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ethers::types::U64;
+use tokio::{
+    sync::{Mutex, Notify},
+    time::Instant,
+};
+
+use crate::manifest::network::{CatchUpSettings, ComputeUnitCosts};
+
+/// Refilled at `per_second`, holds at most one second worth
+#[derive(Debug)]
+pub(crate) struct RateBucket {
+    per_second: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateBucket {
+    pub(crate) fn new(per_second: u64) -> Self {
+        let per_second = per_second.max(1) as f64;
+        Self { per_second, state: Mutex::new((per_second, Instant::now())) }
+    }
+
+    pub(crate) async fn spend(&self, amount: u64) {
+        let amount = (amount as f64).min(self.per_second);
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                let (available, last_refill) = &mut *state;
+                let now = Instant::now();
+                *available = (*available +
+                    now.duration_since(*last_refill).as_secs_f64() * self.per_second)
+                    .min(self.per_second);
+                *last_refill = now;
+
+                if *available >= amount {
+                    *available -= amount;
+                    return;
+                }
+                Duration::from_secs_f64((amount - *available) / self.per_second)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Shared by every event on a network, historic requests of events resuming far behind the head
+/// are throttled and wait for in flight live requests so live indexing stays near the head
+#[derive(Debug)]
+pub struct CatchUp {
+    settings: CatchUpSettings,
+    blocks: Option<RateBucket>,
+    compute_units: Option<RateBucket>,
+    get_logs_cost: u64,
+    live_in_flight: AtomicUsize,
+    live_done: Notify,
+}
+
+/// Held while a live request runs, catch-up requests wait until none are left
+pub struct LiveRequest<'a> {
+    catch_up: &'a CatchUp,
+}
+
+impl Drop for LiveRequest<'_> {
+    fn drop(&mut self) {
+        if self.catch_up.live_in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.catch_up.live_done.notify_waiters();
+        }
+    }
+}
+
+impl CatchUp {
+    pub fn new(settings: &CatchUpSettings, compute_unit_costs: Option<&ComputeUnitCosts>) -> Self {
+        Self {
+            settings: settings.clone(),
+            blocks: settings.max_blocks_per_second.map(RateBucket::new),
+            compute_units: settings.max_compute_units_per_second.map(RateBucket::new),
+            get_logs_cost: compute_unit_costs
+                .map_or(1, |costs| costs.cost("eth_getLogs"))
+                .min(settings.max_compute_units_per_second.unwrap_or(u64::MAX)),
+            live_in_flight: AtomicUsize::new(0),
+            live_done: Notify::new(),
+        }
+    }
+
+    /// Whether an event resuming from `start_block` is far enough behind to be throttled
+    pub fn applies(&self, start_block: U64, end_block: U64) -> bool {
+        end_block.saturating_sub(start_block) >= U64::from(self.settings.min_blocks_behind())
+    }
+
+    /// Caps the range of a catch-up request to what the block rate allows in one second
+    pub fn limit_to_block(&self, from_block: U64, to_block: U64) -> U64 {
+        match self.settings.max_blocks_per_second {
+            Some(max_blocks) => std::cmp::min(to_block, from_block + max_blocks.max(1) - 1),
+            None => to_block,
+        }
+    }
+
+    pub fn live_request(&self) -> LiveRequest<'_> {
+        self.live_in_flight.fetch_add(1, Ordering::SeqCst);
+        LiveRequest { catch_up: self }
+    }
+
+    /// Waits until no live request or live write is in flight, historic writes wait on this so
+    /// the database is kept free for live indexing as well
+    pub async fn wait_for_live(&self) {
+        loop {
+            let live_done = self.live_done.notified();
+            if self.live_in_flight.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            live_done.await;
+        }
+    }
+
+    /// Waits for the live requests in flight and the budget of a request over the blocks
+    pub async fn wait_for_historic_request(&self, from_block: U64, to_block: U64) {
+        self.wait_for_live().await;
+
+        if let Some(blocks) = &self.blocks {
+            blocks.spend((to_block.saturating_sub(from_block) + 1).as_u64()).await;
+        }
+        if let Some(compute_units) = &self.compute_units {
+            compute_units.spend(self.get_logs_cost).await;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BackfillState {
+    complete: bool,
+    live_to_block: Option<U64>,
+}
+
+/// A catching up event indexes live from the head while its backfill runs behind, the checkpoint
+/// only follows the backfill until it is done so a restart never skips the blocks in between
+#[derive(Debug)]
+pub struct CatchUpBackfill {
+    to_block: U64,
+    state: std::sync::Mutex<BackfillState>,
+}
+
+impl CatchUpBackfill {
+    pub fn new(to_block: U64) -> Self {
+        Self { to_block, state: Default::default() }
+    }
+
+    /// Last block the backfill covers, live indexing starts after it
+    pub fn to_block(&self) -> U64 {
+        self.to_block
+    }
+
+    pub fn is_live_range(&self, to_block: U64) -> bool {
+        to_block > self.to_block
+    }
+
+    /// Block the checkpoint can move to once the range up to `to_block` is handled, None holds
+    /// it back while the backfill is still running
+    pub fn checkpoint(&self, to_block: U64) -> Option<U64> {
+        let mut state = self.state.lock().expect("catch-up backfill lock poisoned");
+        if !self.is_live_range(to_block) {
+            if to_block == self.to_block {
+                state.complete = true;
+                return Some(state.live_to_block.map_or(to_block, |live| live.max(to_block)));
+            }
+            return Some(to_block);
+        }
+
+        if state.complete {
+            return Some(to_block);
+        }
+        state.live_to_block = state.live_to_block.max(Some(to_block));
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catch_up_applies_and_limits_range() {
+        let catch_up = CatchUp::new(
+            &CatchUpSettings {
+                min_blocks_behind: Some(100),
+                max_blocks_per_second: Some(50),
+                max_compute_units_per_second: None,
+            },
+            None,
+        );
+
+        assert!(catch_up.applies(U64::from(1000), U64::from(1100)));
+        assert!(!catch_up.applies(U64::from(1050), U64::from(1100)));
+        assert_eq!(catch_up.limit_to_block(U64::from(1000), U64::from(1100)), U64::from(1049));
+        assert_eq!(catch_up.limit_to_block(U64::from(1090), U64::from(1100)), U64::from(1100));
+    }
+
+    #[test]
+    fn test_backfill_holds_the_checkpoint_until_it_is_done() {
+        let backfill = CatchUpBackfill::new(U64::from(100));
+
+        assert_eq!(backfill.checkpoint(U64::from(50)), Some(U64::from(50)));
+        assert_eq!(backfill.checkpoint(U64::from(120)), None);
+        assert_eq!(backfill.checkpoint(U64::from(130)), None);
+        assert_eq!(backfill.checkpoint(U64::from(100)), Some(U64::from(130)));
+        assert_eq!(backfill.checkpoint(U64::from(140)), Some(U64::from(140)));
+    }
+
+    #[tokio::test]
+    async fn test_historic_request_waits_for_live_requests() {
+        let catch_up = std::sync::Arc::new(CatchUp::new(
+            &CatchUpSettings { max_blocks_per_second: Some(1000), ..Default::default() },
+            None,
+        ));
+
+        let live = catch_up.live_request();
+        let waiting = tokio::spawn({
+            let catch_up = std::sync::Arc::clone(&catch_up);
+            async move { catch_up.wait_for_historic_request(U64::from(1), U64::from(10)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        drop(live);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("the historic request runs once live is done")
+            .unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+use std::{fmt, path::Path};
+
+use ethers::{
+    abi::Abi,
+    prelude::ProviderError,
+    types::{Address, Log, ValueOrArray, H256, U256},
+};
+use serde_json::Value;
+
+use crate::{
+    abi::{ABIItem, EventInfo, ParamTypeError, ReadAbiError},
+    database::postgres::{
+        generate::{
+            generate_column_names_only_with_base_properties,
+            generate_event_table_full_name_for_network,
+        },
+        sql_type_wrapper::{
+            ethereum_wrapper_to_json, map_log_params_to_ethereum_wrapper, EthereumSqlTypeWrapper,
+        },
+    },
+    event::{WhereExpression, WhereExpressionError},
+    helpers::format_address,
+    indexer::{
+        log_helpers::{log_param_text, parse_log},
+        transform::EventTransforms,
+    },
+    manifest::{contract::Contract, core::Manifest, transform::transforms_for_event},
+    provider::{CreateNetworkProvider, RetryClientError},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum DecodeError {
+    #[error("Network {0} not found in the manifest")]
+    NetworkNotFound(String),
+
+    #[error("Transaction {0:?} not found on {1}")]
+    TransactionNotFound(H256, String),
+
+    #[error("Could not read ABI {0}: {1}")]
+    CouldNotReadAbi(String, String),
+
+    #[error("Could not read ABI items: {0}")]
+    CouldNotReadAbiItems(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
+
+    #[error("Invalid event condition: {0}")]
+    InvalidEventCondition(#[from] WhereExpressionError),
+
+    #[error("{0}")]
+    RetryClientError(#[from] RetryClientError),
+
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+}
+
+pub struct DecodeOptions {
+    pub tx_hash: H256,
+    pub network: String,
+}
+
+pub enum DecodedLogOutcome {
+    /// The columns and values which would be written to the event table
+    Row { table_name: String, columns: Vec<(String, Value)> },
+    /// Matched an event but its `where` condition leaves it out
+    FilteredByCondition { condition: String },
+    /// Matched an event signature but the topics or data do not fit its ABI inputs
+    DecodeFailed,
+    /// No contract in the manifest indexes this log
+    NotIndexed,
+}
+
+pub struct DecodedLog {
+    pub log_index: Option<U256>,
+    pub address: Address,
+    pub topic0: Option<H256>,
+    /// `contract.event` when the log matched an event in the manifest
+    pub event: Option<String>,
+    pub outcome: DecodedLogOutcome,
+}
+
+pub struct DecodeReport {
+    pub tx_hash: H256,
+    pub network: String,
+    pub logs: Vec<DecodedLog>,
+}
+
+impl fmt::Display for DecodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:?} on {} - {} logs", self.tx_hash, self.network, self.logs.len())?;
+        for log in &self.logs {
+            let log_index = log.log_index.map_or("?".to_string(), |index| index.to_string());
+            write!(f, "\nlog {} from {}", log_index, format_address(&log.address))?;
+            if let Some(event) = &log.event {
+                write!(f, " - {}", event)?;
+            }
+            writeln!(f)?;
+
+            match &log.outcome {
+                DecodedLogOutcome::Row { table_name, columns } => {
+                    writeln!(f, "  would write to {}", table_name)?;
+                    let width = columns.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+                    for (name, value) in columns {
+                        let value = match value {
+                            Value::String(value) => value.clone(),
+                            value => value.to_string(),
+                        };
+                        writeln!(f, "    {:width$}  {}", name, value, width = width)?;
+                    }
+                }
+                DecodedLogOutcome::FilteredByCondition { condition } => {
+                    writeln!(f, "  skipped by the event condition `{}`", condition)?;
+                }
+                DecodedLogOutcome::DecodeFailed => {
+                    let topic0 =
+                        log.topic0.map_or("none".to_string(), |topic| format!("{:?}", topic));
+                    writeln!(
+                        f,
+                        "  topic0 {} matches but the topics or data do not fit the ABI inputs, check which inputs are indexed",
+                        topic0
+                    )?;
+                }
+                DecodedLogOutcome::NotIndexed => {
+                    writeln!(f, "  not indexed by any contract in the manifest")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the contract indexes logs from this address on the network, filters and factories
+/// index any address emitting their events
+fn contract_indexes_address(contract: &Contract, network: &str, address: Address) -> bool {
+    contract.details.iter().filter(|details| details.network == network).any(
+        |details| match details.address() {
+            Some(ValueOrArray::Value(contract_address)) => *contract_address == address,
+            Some(ValueOrArray::Array(contract_addresses)) => contract_addresses.contains(&address),
+            None => true,
+        },
+    )
+}
+
+fn decode_log(
+    project_path: &Path,
+    manifest: &Manifest,
+    network: &str,
+    log: &Log,
+) -> Result<DecodedLog, DecodeError> {
+    let mut decoded = DecodedLog {
+        log_index: log.log_index,
+        address: log.address,
+        topic0: log.topics.first().copied(),
+        event: None,
+        outcome: DecodedLogOutcome::NotIndexed,
+    };
+    let Some(topic0) = decoded.topic0 else {
+        return Ok(decoded);
+    };
+
+    for contract in &manifest.contracts {
+        if !contract_indexes_address(contract, network, log.address) {
+            continue;
+        }
+
+        let abi_items = ABIItem::get_abi_items(project_path, contract, contract.is_filter())?;
+        let Some(event_info) = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?
+            .into_iter()
+            .find(|event_info| event_info.topic_id() == topic0)
+        else {
+            continue;
+        };
+        if !contract.event_indexed_on_network(&event_info.name, network) {
+            continue;
+        }
+
+        decoded.event = Some(format!("{}.{}", contract.name, event_info.name));
+        decoded.outcome =
+            decode_event_row(project_path, manifest, network, contract, &event_info, log)?;
+        return Ok(decoded);
+    }
+
+    Ok(decoded)
+}
+
+/// Runs the log through the same mapping the no-code indexer writes with, price enrichments and
+/// templates need more RPC calls so their columns are left out
+pub(crate) fn decode_event_row(
+    project_path: &Path,
+    manifest: &Manifest,
+    network: &str,
+    contract: &Contract,
+    event_info: &EventInfo,
+    log: &Log,
+) -> Result<DecodedLogOutcome, DecodeError> {
+    let abi: Abi = contract
+        .read_abi(project_path)
+        .map_err(|e| e.to_string())
+        .and_then(|abi| serde_json::from_str(&abi).map_err(|e| e.to_string()))
+        .map_err(|e| DecodeError::CouldNotReadAbi(contract.abi.clone(), e))?;
+    let topic_id = event_info.topic_id();
+    let Some(event) = abi.events().find(|event| event.signature() == topic_id) else {
+        return Ok(DecodedLogOutcome::DecodeFailed);
+    };
+    let Some(parsed) = parse_log(event, log) else {
+        return Ok(DecodedLogOutcome::DecodeFailed);
+    };
+
+    if let Some(condition) = contract.event_condition(&event_info.name) {
+        let input_value =
+            |path: &[String]| log_param_text(&event_info.inputs, &parsed.params, path);
+        if !WhereExpression::parse(condition)?.matches(&input_value) {
+            return Ok(DecodedLogOutcome::FilteredByCondition { condition: condition.to_string() });
+        }
+    }
+
+    let jsonb_inputs = contract.jsonb_inputs(&event_info.name, &event_info.inputs);
+    let Ok(mut event_parameters) =
+        map_log_params_to_ethereum_wrapper(&event_info.inputs, &parsed.params, &jsonb_inputs)
+    else {
+        return Ok(DecodedLogOutcome::DecodeFailed);
+    };
+    EventTransforms::new(
+        &event_info.inputs,
+        transforms_for_event(&contract.transforms, &event_info.name),
+        &jsonb_inputs,
+    )
+    .apply(&mut event_parameters);
+
+    let mut values = vec![EthereumSqlTypeWrapper::Address(log.address)];
+    values.extend(event_parameters);
+    values.extend([
+        EthereumSqlTypeWrapper::H256(log.transaction_hash.unwrap_or_default()),
+        EthereumSqlTypeWrapper::U64(log.block_number.unwrap_or_default()),
+        EthereumSqlTypeWrapper::H256(log.block_hash.unwrap_or_default()),
+        EthereumSqlTypeWrapper::String(network.to_string()),
+        EthereumSqlTypeWrapper::U64(log.transaction_index.unwrap_or_default()),
+        EthereumSqlTypeWrapper::U256(log.log_index.unwrap_or_default()),
+    ]);
+
+    let columns =
+        generate_column_names_only_with_base_properties(&event_info.inputs, &jsonb_inputs)
+            .into_iter()
+            .zip(values.iter().map(ethereum_wrapper_to_json))
+            .collect();
+
+    Ok(DecodedLogOutcome::Row {
+        table_name: generate_event_table_full_name_for_network(
+            manifest.storage.postgres_layout(),
+            &manifest.name,
+            &contract.before_modify_name_if_filter_readonly(),
+            &event_info.name,
+            network,
+        ),
+        columns,
+    })
+}
+
+/// Fetches the logs of a transaction and decodes them with the manifest ABIs without writing
+/// anything, shows what each log would be stored as or why it would not be
+pub async fn decode_transaction(
+    project_path: &Path,
+    manifest: &Manifest,
+    options: &DecodeOptions,
+) -> Result<DecodeReport, DecodeError> {
+    let provider = CreateNetworkProvider::create(manifest)?
+        .into_iter()
+        .find(|provider| provider.network_name == options.network)
+        .ok_or_else(|| DecodeError::NetworkNotFound(options.network.clone()))?;
+
+    let receipt =
+        provider.client.get_transaction_receipt(options.tx_hash).await?.ok_or_else(|| {
+            DecodeError::TransactionNotFound(options.tx_hash, options.network.clone())
+        })?;
+
+    let logs = receipt
+        .logs
+        .iter()
+        .map(|log| decode_log(project_path, manifest, &options.network, log))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(DecodeReport { tx_hash: options.tx_hash, network: options.network.clone(), logs })
+}
@@ -1,8 +1,12 @@
+#![allow(clippy::replace_box, clippy::unnecessary_map_or)]
+
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
 };
 
+use tracing::info;
+
 use crate::{
     database::postgres::relationship::Relationship,
     event::{config::EventProcessingConfig, contract_setup::ContractEventMapping},
@@ -59,29 +63,73 @@ pub struct ContractEventDependencies {
 
 #[derive(thiserror::Error, Debug)]
 pub enum ContractEventDependenciesMapFromRelationshipsError {
-    #[error("Cross contract relationships are need manually mapping in the dependency_events, https://rindexer.xyz/docs/start-building/yaml-config/contracts#dependency_events")]
+    #[error("Cross contract relationships are need manually mapping in the dependency_events or depends_on, https://rindexer.xyz/docs/start-building/yaml-config/contracts#dependency_events")]
     CrossContractRelationshipsNotDefinedInDependencyEvents,
 }
 
 impl ContractEventDependencies {
-    pub fn map_from_relationships(
+    /// `(dependency, dependent)` pairs from the relationships, a row can only be written once the
+    /// row it links to exists
+    fn relationship_edges(
         relationships: &[Relationship],
-    ) -> Result<Vec<ContractEventDependencies>, ContractEventDependenciesMapFromRelationshipsError>
-    {
-        if Relationship::has_cross_contract_dependency(relationships) {
-            return Err(ContractEventDependenciesMapFromRelationshipsError::CrossContractRelationshipsNotDefinedInDependencyEvents);
-        }
+    ) -> Vec<(ContractEventMapping, ContractEventMapping)> {
+        relationships
+            .iter()
+            .map(|relationship| {
+                (
+                    ContractEventMapping {
+                        contract_name: relationship.linked_to.contract_name.clone(),
+                        event_name: relationship.linked_to.event.clone(),
+                    },
+                    ContractEventMapping {
+                        contract_name: relationship.contract_name.clone(),
+                        event_name: relationship.event.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
 
-        Ok(ContractEventDependencies::map_all_dependencies(relationships))
+    /// Adds the relationship edges to the `depends_on` ones, a cross contract relationship has to
+    /// be declared in `depends_on`
+    fn merge_edges(
+        mut edges: Vec<(ContractEventMapping, ContractEventMapping)>,
+        relationship_edges: Vec<(ContractEventMapping, ContractEventMapping)>,
+    ) -> Result<
+        Vec<(ContractEventMapping, ContractEventMapping)>,
+        ContractEventDependenciesMapFromRelationshipsError,
+    > {
+        for edge in relationship_edges {
+            if edges.contains(&edge) {
+                continue;
+            }
+            if edge.0.contract_name != edge.1.contract_name {
+                return Err(ContractEventDependenciesMapFromRelationshipsError::CrossContractRelationshipsNotDefinedInDependencyEvents);
+            }
+            edges.push(edge);
+        }
+        Ok(edges)
     }
 
-    fn map_all_dependencies(relationships: &[Relationship]) -> Vec<ContractEventDependencies> {
-        let relationships_map =
-            ContractEventDependencies::generate_relationships_map(relationships);
+    /// Builds the trees from `(dependency, dependent)` pairs, each tree is owned by the contract
+    /// of the event it starts from
+    fn map_all_dependencies(
+        edges: &[(ContractEventMapping, ContractEventMapping)],
+    ) -> Vec<ContractEventDependencies> {
+        let relationships_map = ContractEventDependencies::generate_dependents_map(edges);
         let mut result_map = HashMap::new();
         let mut visited = HashSet::new();
 
-        for event in relationships_map.keys() {
+        // start from events nothing depends on so chains stay in one tree, cycles have no roots
+        let dependents: HashSet<&ContractEventMapping> =
+            edges.iter().map(|(_, dependent)| dependent).collect();
+        let mut roots: Vec<&ContractEventMapping> =
+            relationships_map.keys().filter(|event| !dependents.contains(event)).collect();
+        if roots.is_empty() {
+            roots = relationships_map.keys().collect();
+        }
+
+        for event in roots {
             let tree = ContractEventDependencies::build_dependency_tree(
                 event,
                 &relationships_map,
@@ -173,26 +221,19 @@ impl ContractEventDependencies {
         Arc::new(EventsDependencyTree { contract_events, then: Box::new(next_tree) })
     }
 
-    fn generate_relationships_map(
-        relationships: &[Relationship],
+    fn generate_dependents_map(
+        edges: &[(ContractEventMapping, ContractEventMapping)],
     ) -> HashMap<ContractEventMapping, Vec<ContractEventMapping>> {
-        let mut relationships_map = HashMap::new();
-
-        for relationship in relationships {
-            let event = ContractEventMapping {
-                contract_name: relationship.contract_name.clone(),
-                event_name: relationship.event.clone(),
-            };
+        let mut dependents_map = HashMap::new();
 
-            let linked_event = ContractEventMapping {
-                contract_name: relationship.linked_to.contract_name.clone(),
-                event_name: relationship.linked_to.event.clone(),
-            };
-
-            relationships_map.entry(linked_event).or_insert_with(Vec::new).push(event);
+        for (dependency, dependent) in edges {
+            dependents_map
+                .entry(dependency.clone())
+                .or_insert_with(Vec::new)
+                .push(dependent.clone());
         }
 
-        relationships_map
+        dependents_map
     }
 
     fn collect_dependency_events(tree: &EventsDependencyTree) -> Vec<ContractEventMapping> {
@@ -225,7 +266,13 @@ impl DependencyStatus {
 }
 
 impl ContractEventDependencies {
-    pub fn parse(manifest: &Manifest) -> Vec<ContractEventDependencies> {
+    /// The manual `dependency_events` trees replace the relationships ordering, `depends_on` is
+    /// merged with it as cross contract relationships have to be declared there
+    pub fn parse(
+        manifest: &Manifest,
+        relationships: &[Relationship],
+    ) -> Result<Vec<ContractEventDependencies>, ContractEventDependenciesMapFromRelationshipsError>
+    {
         let mut dependencies: Vec<ContractEventDependencies> = vec![];
         for contract in &manifest.contracts {
             if let Some(dependency) = contract.dependency_events.clone() {
@@ -243,7 +290,37 @@ impl ContractEventDependencies {
             }
         }
 
-        dependencies
+        let depends_on_edges: Vec<(ContractEventMapping, ContractEventMapping)> =
+            manifest.contracts.iter().flat_map(|contract| contract.depends_on_edges()).collect();
+        let edges = if dependencies.is_empty() {
+            ContractEventDependencies::merge_edges(
+                depends_on_edges,
+                ContractEventDependencies::relationship_edges(relationships),
+            )?
+        } else {
+            if !relationships.is_empty() {
+                info!("Manual dependency_events found, skipping auto-applying the dependency_events with the relationships");
+            }
+            depends_on_edges
+        };
+
+        for mapped in ContractEventDependencies::map_all_dependencies(&edges) {
+            match dependencies.iter_mut().find(|d| d.contract_name == mapped.contract_name) {
+                Some(existing) => {
+                    let event_dependencies = &mut existing.event_dependencies;
+                    event_dependencies.tree = Arc::new(ContractEventDependencies::merge_trees(
+                        &event_dependencies.tree,
+                        &mapped.event_dependencies.tree,
+                    ));
+                    event_dependencies
+                        .dependency_events
+                        .extend(mapped.event_dependencies.dependency_events);
+                }
+                None => dependencies.push(mapped),
+            }
+        }
+
+        Ok(dependencies)
     }
 
     pub fn dependencies_status(
@@ -342,3 +419,56 @@ impl ContractEventsDependenciesConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(contract_name: &str, event_name: &str) -> ContractEventMapping {
+        ContractEventMapping {
+            contract_name: contract_name.to_string(),
+            event_name: event_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_map_dependency_chain() {
+        let edges = vec![
+            (event("Vault", "Deposit"), event("Vault", "Withdraw")),
+            (event("Positions", "PositionCreated"), event("Vault", "Deposit")),
+        ];
+
+        let dependencies = ContractEventDependencies::map_all_dependencies(&edges);
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(dependencies[0].contract_name, "Positions");
+
+        let tree = &dependencies[0].event_dependencies.tree;
+        assert_eq!(tree.contract_events, vec![event("Positions", "PositionCreated")]);
+        let then = tree.then.as_ref().as_ref().unwrap();
+        assert_eq!(then.contract_events, vec![event("Vault", "Deposit")]);
+        let then = then.then.as_ref().as_ref().unwrap();
+        assert_eq!(then.contract_events, vec![event("Vault", "Withdraw")]);
+    }
+
+    #[test]
+    fn test_merge_relationships_with_depends_on() {
+        let depends_on = vec![(event("Positions", "PositionCreated"), event("Vault", "Deposit"))];
+        let relationships = vec![
+            (event("Vault", "Deposit"), event("Vault", "Withdraw")),
+            (event("Positions", "PositionCreated"), event("Vault", "Deposit")),
+        ];
+
+        let edges =
+            ContractEventDependencies::merge_edges(depends_on.clone(), relationships).unwrap();
+        assert_eq!(
+            edges,
+            vec![
+                (event("Positions", "PositionCreated"), event("Vault", "Deposit")),
+                (event("Vault", "Deposit"), event("Vault", "Withdraw")),
+            ]
+        );
+
+        let undeclared = vec![(event("Vault", "Deposit"), event("Positions", "PositionClosed"))];
+        assert!(ContractEventDependencies::merge_edges(depends_on, undeclared).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ethers::{
+    prelude::ProviderError,
+    types::{Address, U64},
+};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::{
+    database::postgres::internal_schema::{internal_schema, internal_table},
+    provider::JsonRpcCachedProvider,
+    PostgresClient,
+};
+
+fn deployment_blocks_table_name() -> String {
+    internal_table("deployment_blocks")
+}
+
+pub(crate) fn create_deployment_blocks_table_sql() -> String {
+    format!(
+        r#"
+        CREATE SCHEMA IF NOT EXISTS {};
+        CREATE TABLE IF NOT EXISTS {} (
+            network TEXT NOT NULL,
+            address CHAR(42) NOT NULL,
+            block_number NUMERIC NOT NULL,
+            PRIMARY KEY (network, address)
+        );
+    "#,
+        internal_schema(),
+        deployment_blocks_table_name()
+    )
+}
+
+/// Finds the block contracts were deployed at when no `start_block` is set, found blocks are
+/// cached in rindexer_internal so the search only ever runs once per address
+pub struct DeploymentBlocks {
+    database: Option<Arc<PostgresClient>>,
+    found: Mutex<HashMap<(String, Address), U64>>,
+}
+
+impl DeploymentBlocks {
+    pub fn new(database: Option<Arc<PostgresClient>>) -> Self {
+        Self { database, found: Mutex::new(HashMap::new()) }
+    }
+
+    async fn cached(&self, network: &str, address: Address) -> Option<U64> {
+        if let Some(block) = self.found.lock().await.get(&(network.to_string(), address)) {
+            return Some(*block);
+        }
+
+        let database = self.database.as_ref()?;
+        if let Err(e) = database
+            .execute_ddl(
+                &create_deployment_blocks_table_sql(),
+                "create the deployment blocks table",
+            )
+            .await
+        {
+            error!("Could not create the deployment blocks table: {}", e);
+            return None;
+        }
+        let row = database
+            .query_one_or_none(
+                &format!(
+                    "SELECT block_number::TEXT FROM {} WHERE network = $1 AND address = $2",
+                    deployment_blocks_table_name()
+                ),
+                &[&network, &format!("{:?}", address)],
+            )
+            .await
+            .ok()??;
+
+        row.get::<_, String>(0).parse::<u64>().ok().map(U64::from)
+    }
+
+    async fn store(&self, network: &str, address: Address, block: U64) {
+        self.found.lock().await.insert((network.to_string(), address), block);
+
+        let Some(database) = &self.database else {
+            return;
+        };
+        let sql = format!(
+            "INSERT INTO {} (network, address, block_number) VALUES ($1, $2, $3::TEXT::NUMERIC) ON CONFLICT DO NOTHING",
+            deployment_blocks_table_name()
+        );
+        if let Err(e) =
+            database.execute(&sql, &[&network, &format!("{:?}", address), &block.to_string()]).await
+        {
+            error!("Could not cache the deployment block of {:?}: {}", address, e);
+        }
+    }
+
+    /// First block which has code at the address, needs an archive node
+    async fn search(
+        provider: &JsonRpcCachedProvider,
+        address: Address,
+        latest_block: U64,
+    ) -> Option<U64> {
+        let has_code = |block: U64| async move {
+            provider.get_code(address, block).await.map(|code| !code.is_empty())
+        };
+
+        let result: Result<Option<U64>, ProviderError> = async {
+            if !has_code(latest_block).await? {
+                return Ok(None);
+            }
+            let (mut from, mut to) = (U64::zero(), latest_block);
+            while from < to {
+                let middle = from + (to - from) / 2;
+                if has_code(middle).await? {
+                    to = middle;
+                } else {
+                    from = middle + 1;
+                }
+            }
+            Ok(Some(to))
+        }
+        .await;
+
+        result.unwrap_or_else(|e| {
+            warn!("Could not search for the deployment block of {:?}: {}", address, e);
+            None
+        })
+    }
+
+    /// Earliest deployment block across the addresses, `None` when it could not be found
+    pub async fn detect(
+        &self,
+        provider: &JsonRpcCachedProvider,
+        network: &str,
+        addresses: &[Address],
+        latest_block: U64,
+    ) -> Option<U64> {
+        let mut earliest: Option<U64> = None;
+        for address in addresses {
+            let block = match self.cached(network, *address).await {
+                Some(block) => block,
+                None => {
+                    let block = Self::search(provider, *address, latest_block).await?;
+                    info!("{:?} on {} was deployed at block {}", address, network, block);
+                    self.store(network, *address, block).await;
+                    block
+                }
+            };
+            earliest = Some(earliest.map_or(block, |earliest| earliest.min(block)));
+        }
+
+        earliest
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::unnecessary_unwrap)]
+
 use std::{error::Error, str::FromStr, sync::Arc};
 
 use ethers::{
@@ -6,13 +8,18 @@ use ethers::{
     prelude::{BlockNumber, JsonRpcError, Log, ValueOrArray, H256, U64},
 };
 use regex::Regex;
-use tokio::sync::{mpsc, Semaphore};
+use tokio::{sync::mpsc, time::Instant};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tracing::{debug, error, info, warn};
 
 use crate::{
     event::{config::EventProcessingConfig, RindexerEventFilter},
-    indexer::{log_helpers::is_relevant_block, IndexingEventProgressStatus},
+    indexer::{
+        log_helpers::is_relevant_block, reorg::UnconfirmedLogs, CatchUp,
+        IndexingEventProgressStatus, IndexingEventStats, IndexingSlots, LiveBlocks,
+        RegistryAddresses,
+    },
+    manifest::contract::ContractPriority,
     provider::JsonRpcCachedProvider,
 };
 
@@ -35,6 +42,21 @@ pub fn fetch_logs_stream(
     tokio::spawn(async move {
         let snapshot_to_block = initial_filter.get_to_block();
         let from_block = initial_filter.get_from_block();
+        let live_indexing = config.live_indexing && !force_no_live_indexing;
+        // a catching up event indexes live from the head straight away, the backfill runs behind
+        let concurrent_live = match &config.catch_up_backfill {
+            Some(backfill) if live_indexing => {
+                let config = Arc::clone(&config);
+                let tx = tx.clone();
+                let contract_address = contract_address.clone();
+                let live_filter = initial_filter.clone().set_from_block(backfill.to_block() + 1);
+                tokio::spawn(async move {
+                    run_live_indexing(&config, &tx, &contract_address, live_filter).await
+                });
+                true
+            }
+            _ => false,
+        };
         let mut current_filter = initial_filter;
 
         // add any max block range limitation before we start processing
@@ -54,13 +76,28 @@ pub fn fetch_logs_stream(
             );
         }
         while current_filter.get_from_block() <= snapshot_to_block {
-            let semaphore_client = Arc::clone(&config.semaphore);
-            let permit = semaphore_client.acquire_owned().await;
+            if let Some(schedule) = &config.schedule {
+                let from_block = current_filter.get_from_block();
+                let to_block = schedule.limit_to_block(from_block, current_filter.get_to_block());
+                current_filter = current_filter.set_to_block(to_block);
+                schedule.wait_for_historic_request(from_block, to_block).await;
+            }
+            if let Some(catch_up) =
+                config.catch_up.as_ref().filter(|_| config.catch_up_backfill.is_some())
+            {
+                let from_block = current_filter.get_from_block();
+                let to_block = catch_up.limit_to_block(from_block, current_filter.get_to_block());
+                current_filter = current_filter.set_to_block(to_block);
+                catch_up.wait_for_historic_request(from_block, to_block).await;
+            }
+
+            let permit = config.slots.acquire(config.priority).await;
 
             match permit {
                 Ok(permit) => {
                     let result = fetch_historic_logs_stream(
                         &config.network_contract.cached_provider,
+                        &config.stats,
                         &tx,
                         &config.topic_id,
                         current_filter.clone(),
@@ -106,33 +143,60 @@ pub fn fetch_logs_stream(
             IndexingEventProgressStatus::Completed.log()
         );
 
+        if let Some(unlogged_historic_sync) = &config.unlogged_historic_sync {
+            unlogged_historic_sync
+                .historic_sync_complete(
+                    &config.indexer_name,
+                    &config.contract_name,
+                    &config.event_name,
+                    &config.network_contract.network,
+                )
+                .await;
+        }
+
         // Live indexing mode
-        if config.live_indexing && !force_no_live_indexing {
-            live_indexing_stream(
-                &config.network_contract.cached_provider,
-                &tx,
-                &contract_address,
-                &config.topic_id,
-                &config.indexing_distance_from_head,
-                current_filter,
-                &config.info_log_name,
-                &config.semaphore,
-                config.network_contract.disable_logs_bloom_checks,
-            )
-            .await;
+        if live_indexing && !concurrent_live {
+            run_live_indexing(&config, &tx, &contract_address, current_filter).await;
         }
     });
 
     UnboundedReceiverStream::new(rx)
 }
 
+async fn run_live_indexing(
+    config: &EventProcessingConfig,
+    tx: &mpsc::UnboundedSender<Result<FetchLogsResult, Box<dyn Error + Send>>>,
+    contract_address: &Option<ValueOrArray<Address>>,
+    current_filter: RindexerEventFilter,
+) {
+    live_indexing_stream(
+        &config.network_contract.cached_provider,
+        &config.stats,
+        tx,
+        contract_address,
+        &config.topic_id,
+        &config.indexing_distance_from_head,
+        current_filter,
+        &config.info_log_name,
+        &config.slots,
+        config.priority,
+        config.network_contract.disable_logs_bloom_checks,
+        config.registry_addresses.as_deref(),
+        &config.live_blocks,
+        config.catch_up.as_deref(),
+    )
+    .await;
+}
+
 struct ProcessHistoricLogsStreamResult {
     pub next: RindexerEventFilter,
     pub max_block_range_limitation: Option<U64>,
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn fetch_historic_logs_stream(
     cached_provider: &Arc<JsonRpcCachedProvider>,
+    stats: &IndexingEventStats,
     tx: &mpsc::UnboundedSender<Result<FetchLogsResult, Box<dyn Error + Send>>>,
     topic_id: &H256,
     current_filter: RindexerEventFilter,
@@ -172,7 +236,11 @@ async fn fetch_historic_logs_stream(
         current_filter
     );
 
-    match cached_provider.get_logs(&current_filter).await {
+    let rpc_start = Instant::now();
+    let logs_result = cached_provider.get_logs(&current_filter).await;
+    stats.record_rpc_call(rpc_start.elapsed());
+
+    match logs_result {
         Ok(logs) => {
             debug!(
                 "{} - {} - topic_id {}, Logs: {} from {} to {}",
@@ -310,185 +378,339 @@ async fn fetch_historic_logs_stream(
     None
 }
 
-/// Handles live indexing mode, continuously checking for new blocks, ensuring they are
-/// within a safe range, updating the filter, and sending the logs to the provided channel.
+/// Handles live indexing mode, waiting on the network's live block strategy for new blocks,
+/// ensuring they are within a safe range, updating the filter, and sending the logs to the provided
+/// channel.
 #[allow(clippy::too_many_arguments)]
 async fn live_indexing_stream(
     cached_provider: &Arc<JsonRpcCachedProvider>,
+    stats: &IndexingEventStats,
     tx: &mpsc::UnboundedSender<Result<FetchLogsResult, Box<dyn Error + Send>>>,
     contract_address: &Option<ValueOrArray<Address>>,
     topic_id: &H256,
     reorg_safe_distance: &U64,
     mut current_filter: RindexerEventFilter,
     info_log_name: &str,
-    semaphore: &Arc<Semaphore>,
+    slots: &IndexingSlots,
+    priority: ContractPriority,
     disable_logs_bloom_checks: bool,
+    registry_addresses: Option<&RegistryAddresses>,
+    live_blocks: &LiveBlocks,
+    catch_up: Option<&CatchUp>,
 ) {
     let mut last_seen_block_number = U64::from(0);
+    let mut contract_address = contract_address.clone();
+    // unset so addresses the registry added while the historic sync ran are backfilled too
+    let mut registry_version = None;
+    let mut live_block_watcher = live_blocks.watch();
+    // indexing closer to the head than a reorg reaches keeps the logs so a reorg can retract them
+    let chain_reorg_distance = match cached_provider.reorg_safe_distance().await {
+        Ok(distance) => distance,
+        Err(e) => {
+            error!(
+                "{} - {} - Could not get the chain id for the reorg distance, assuming logs can be reorged: {}",
+                info_log_name,
+                IndexingEventProgressStatus::Live.log(),
+                e
+            );
+            U64::MAX
+        }
+    };
+    let mut unconfirmed_logs =
+        (*reorg_safe_distance < chain_reorg_distance).then(UnconfirmedLogs::default);
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-
-        let latest_block = cached_provider.get_latest_block().await;
-        match latest_block {
-            Ok(latest_block) => {
-                if let Some(latest_block) = latest_block {
-                    if let Some(latest_block_number) = latest_block.number {
-                        if last_seen_block_number == latest_block_number {
-                            debug!(
-                                "{} - {} - No new blocks to process...",
-                                info_log_name,
-                                IndexingEventProgressStatus::Live.log()
-                            );
-                            continue;
-                        }
-                        info!(
-                            "{} - {} - New block seen {} - Last seen block {}",
+        let Some(latest_block) = live_block_watcher.next_block().await else {
+            error!(
+                "{} - {} - Live block strategy stopped, no new blocks will be seen",
+                info_log_name,
+                IndexingEventProgressStatus::Live.log()
+            );
+            break;
+        };
+
+        let Some(latest_block_number) = latest_block.number else {
+            info!("WARNING - latest block returned without a number, waiting for the next block");
+            continue;
+        };
+
+        if let Some(registry_addresses) = registry_addresses {
+            if registry_version != Some(registry_addresses.version()) {
+                registry_version = Some(registry_addresses.version());
+                let addresses = registry_addresses.addresses().await;
+                let added = RegistryAddresses::added(&contract_address, &addresses);
+                let addresses = ValueOrArray::Array(addresses);
+                contract_address = Some(addresses.clone());
+                current_filter = current_filter.set_address(addresses);
+
+                if !added.is_empty() &&
+                    !backfill_registry_addresses(
+                        cached_provider,
+                        stats,
+                        tx,
+                        topic_id,
+                        &current_filter,
+                        registry_addresses,
+                        added,
+                        latest_block_number,
+                        info_log_name,
+                    )
+                    .await
+                {
+                    break;
+                }
+            }
+        }
+        if last_seen_block_number == latest_block_number {
+            debug!(
+                "{} - {} - No new blocks to process...",
+                info_log_name,
+                IndexingEventProgressStatus::Live.log()
+            );
+            continue;
+        }
+        info!(
+            "{} - {} - New block seen {} - Last seen block {}",
+            info_log_name,
+            IndexingEventProgressStatus::Live.log(),
+            latest_block_number,
+            last_seen_block_number
+        );
+
+        if let Some(unconfirmed) = unconfirmed_logs.as_mut().filter(|logs| !logs.is_empty()) {
+            match unconfirmed.reorged(cached_provider).await {
+                Ok(removed) => {
+                    if let Some(fork_block) = removed.first().and_then(|log| log.block_number) {
+                        warn!(
+                            "{} - {} - Reorg replaced block {}, retracting {} logs and indexing again from it",
                             info_log_name,
                             IndexingEventProgressStatus::Live.log(),
-                            latest_block_number,
-                            last_seen_block_number
+                            fork_block,
+                            removed.len()
                         );
-
-                        let safe_block_number = latest_block_number - reorg_safe_distance;
-                        let from_block = current_filter.get_from_block();
-                        // check reorg distance and skip if not safe
-                        if from_block > safe_block_number {
-                            info!(
-                                "{} - {} - not in safe reorg block range yet block: {} > range: {}",
+                        let removed_result = FetchLogsResult {
+                            logs: removed,
+                            from_block: fork_block,
+                            to_block: last_seen_block_number.max(fork_block),
+                        };
+                        if tx.send(Ok(removed_result)).is_err() {
+                            error!(
+                                "{} - {} - Failed to send removed logs to stream consumer!",
                                 info_log_name,
-                                IndexingEventProgressStatus::Live.log(),
-                                from_block,
-                                safe_block_number
+                                IndexingEventProgressStatus::Live.log()
                             );
-                            continue;
+                            break;
                         }
+                        current_filter = current_filter.set_from_block(fork_block);
+                    }
+                }
+                Err(e) => error!(
+                    "{} - {} - Could not check blocks for reorgs, checking again on the next block: {}",
+                    info_log_name,
+                    IndexingEventProgressStatus::Live.log(),
+                    e
+                ),
+            }
+            unconfirmed.confirm(latest_block_number.saturating_sub(chain_reorg_distance));
+        }
 
-                        let to_block = safe_block_number;
-                        if from_block == to_block &&
-                            !disable_logs_bloom_checks &&
-                            !is_relevant_block(contract_address, topic_id, &latest_block)
-                        {
-                            debug!(
-                                "{} - {} - Skipping block {} as it's not relevant",
-                                info_log_name,
-                                IndexingEventProgressStatus::Live.log(),
-                                from_block
-                            );
-                            info!(
-                                "{} - {} - Did not need to hit RPC as no events in {} block - LogsBloom for block checked",
-                                info_log_name,
-                                IndexingEventProgressStatus::Live.log(),
-                                from_block
-                            );
-                            current_filter = current_filter.set_from_block(to_block + 1);
-                            last_seen_block_number = to_block;
-                            continue;
-                        }
+        let safe_block_number = latest_block_number - reorg_safe_distance;
+        let from_block = current_filter.get_from_block();
+        // check reorg distance and skip if not safe
+        if from_block > safe_block_number {
+            info!(
+                "{} - {} - not in safe reorg block range yet block: {} > range: {}",
+                info_log_name,
+                IndexingEventProgressStatus::Live.log(),
+                from_block,
+                safe_block_number
+            );
+            continue;
+        }
+
+        let to_block = safe_block_number;
+        if from_block == to_block &&
+            !disable_logs_bloom_checks &&
+            !is_relevant_block(&contract_address, current_filter.topic0(), &latest_block)
+        {
+            debug!(
+                "{} - {} - Skipping block {} as it's not relevant",
+                info_log_name,
+                IndexingEventProgressStatus::Live.log(),
+                from_block
+            );
+            info!(
+                "{} - {} - Did not need to hit RPC as no events in {} block - LogsBloom for block checked",
+                info_log_name,
+                IndexingEventProgressStatus::Live.log(),
+                from_block
+            );
+            current_filter = current_filter.set_from_block(to_block + 1);
+            last_seen_block_number = to_block;
+            continue;
+        }
+
+        current_filter = current_filter.set_to_block(to_block);
+
+        debug!(
+            "{} - {} - Processing live filter: {:?}",
+            info_log_name,
+            IndexingEventProgressStatus::Live.log(),
+            current_filter
+        );
+
+        let permit = slots.acquire(priority).await;
 
-                        current_filter = current_filter.set_to_block(to_block);
+        if let Ok(permit) = permit {
+            let live_request = catch_up.map(|catch_up| catch_up.live_request());
+            let rpc_start = Instant::now();
+            let logs_result = cached_provider.get_logs(&current_filter).await;
+            stats.record_rpc_call(rpc_start.elapsed());
+            drop(live_request);
+
+            match logs_result {
+                Ok(logs) => {
+                    debug!(
+                        "{} - {} - Live topic_id {}, Logs: {} from {} to {}",
+                        info_log_name,
+                        IndexingEventProgressStatus::Live.log(),
+                        topic_id,
+                        logs.len(),
+                        from_block,
+                        to_block
+                    );
 
-                        debug!(
-                            "{} - {} - Processing live filter: {:?}",
+                    debug!(
+                        "{} - {} - Fetched {} event logs - blocks: {} - {}",
+                        info_log_name,
+                        IndexingEventProgressStatus::Live.log(),
+                        logs.len(),
+                        from_block,
+                        to_block
+                    );
+
+                    last_seen_block_number = to_block;
+                    if let Some(unconfirmed) = unconfirmed_logs.as_mut() {
+                        unconfirmed.track(&logs);
+                    }
+
+                    let logs_empty = logs.is_empty();
+                    // clone here over the full logs way less overhead
+                    let last_log = logs.last().cloned();
+
+                    if tx.send(Ok(FetchLogsResult { logs, from_block, to_block })).is_err() {
+                        error!(
+                            "{} - {} - Failed to send logs to stream consumer!",
                             info_log_name,
-                            IndexingEventProgressStatus::Live.log(),
-                            current_filter
+                            IndexingEventProgressStatus::Live.log()
                         );
+                        drop(permit);
+                        break;
+                    }
 
-                        let semaphore_client = Arc::clone(semaphore);
-                        let permit = semaphore_client.acquire_owned().await;
-
-                        if let Ok(permit) = permit {
-                            match cached_provider.get_logs(&current_filter).await {
-                                Ok(logs) => {
-                                    debug!(
-                                        "{} - {} - Live topic_id {}, Logs: {} from {} to {}",
-                                        info_log_name,
-                                        IndexingEventProgressStatus::Live.log(),
-                                        topic_id,
-                                        logs.len(),
-                                        from_block,
-                                        to_block
-                                    );
-
-                                    debug!(
-                                        "{} - {} - Fetched {} event logs - blocks: {} - {}",
-                                        info_log_name,
-                                        IndexingEventProgressStatus::Live.log(),
-                                        logs.len(),
-                                        from_block,
-                                        to_block
-                                    );
-
-                                    last_seen_block_number = to_block;
-
-                                    let logs_empty = logs.is_empty();
-                                    // clone here over the full logs way less overhead
-                                    let last_log = logs.last().cloned();
-
-                                    if tx
-                                        .send(Ok(FetchLogsResult { logs, from_block, to_block }))
-                                        .is_err()
-                                    {
-                                        error!(
-                                            "{} - {} - Failed to send logs to stream consumer!",
-                                            info_log_name,
-                                            IndexingEventProgressStatus::Live.log()
-                                        );
-                                        drop(permit);
-                                        break;
-                                    }
-
-                                    if logs_empty {
-                                        current_filter =
-                                            current_filter.set_from_block(to_block + 1);
-                                        info!(
-                                            "{} - {} - No events found between blocks {} - {}",
-                                            info_log_name,
-                                            IndexingEventProgressStatus::Live.log(),
-                                            from_block,
-                                            to_block
-                                        );
-                                    } else if let Some(last_log) = last_log {
-                                        if let Some(last_log_block_number) = last_log.block_number {
-                                            current_filter = current_filter.set_from_block(
-                                                last_log_block_number + U64::from(1),
-                                            );
-                                        } else {
-                                            error!("Failed to get last log block number the provider returned null (should never happen) - try again in 200ms");
-                                        }
-                                    }
-
-                                    drop(permit);
-                                }
-                                Err(err) => {
-                                    error!(
-                                        "{} - {} - Error fetching logs: {}",
-                                        info_log_name,
-                                        IndexingEventProgressStatus::Live.log(),
-                                        err
-                                    );
-                                    drop(permit);
-                                }
-                            }
+                    if logs_empty {
+                        current_filter = current_filter.set_from_block(to_block + 1);
+                        info!(
+                            "{} - {} - No events found between blocks {} - {}",
+                            info_log_name,
+                            IndexingEventProgressStatus::Live.log(),
+                            from_block,
+                            to_block
+                        );
+                    } else if let Some(last_log) = last_log {
+                        if let Some(last_log_block_number) = last_log.block_number {
+                            current_filter =
+                                current_filter.set_from_block(last_log_block_number + U64::from(1));
+                        } else {
+                            error!("Failed to get last log block number the provider returned null (should never happen) - try again on the next block");
                         }
-                    } else {
-                        info!("WARNING - empty latest block returned from provider, will try again in 200ms");
                     }
-                } else {
-                    info!("WARNING - empty latest block returned from provider, will try again in 200ms");
+
+                    drop(permit);
+                }
+                Err(err) => {
+                    error!(
+                        "{} - {} - Error fetching logs: {}",
+                        info_log_name,
+                        IndexingEventProgressStatus::Live.log(),
+                        err
+                    );
+                    drop(permit);
                 }
-            }
-            Err(e) => {
-                error!(
-                    "Error getting latest block, will try again in 1 seconds - err: {}",
-                    e.to_string()
-                );
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
             }
         }
     }
 }
 
+/// Fetches the logs of addresses the registry added from the block they were deployed at up to
+/// where live indexing has got to, returns false when the stream consumer has gone
+#[allow(clippy::too_many_arguments)]
+async fn backfill_registry_addresses(
+    cached_provider: &Arc<JsonRpcCachedProvider>,
+    stats: &IndexingEventStats,
+    tx: &mpsc::UnboundedSender<Result<FetchLogsResult, Box<dyn Error + Send>>>,
+    topic_id: &H256,
+    live_filter: &RindexerEventFilter,
+    registry_addresses: &RegistryAddresses,
+    added: Vec<Address>,
+    latest_block_number: U64,
+    info_log_name: &str,
+) -> bool {
+    let Some(to_block) = live_filter.get_from_block().checked_sub(U64::one()) else {
+        return true;
+    };
+    let Some(from_block) =
+        registry_addresses.deployed_from(cached_provider, &added, latest_block_number).await
+    else {
+        error!(
+            "{} - {} - Could not find the deployment block of {} new registry addresses, their events before block {} are not indexed",
+            info_log_name,
+            IndexingEventProgressStatus::Live.log(),
+            added.len(),
+            to_block + 1
+        );
+        return true;
+    };
+    if from_block > to_block {
+        return true;
+    }
+
+    info!(
+        "{} - {} - Backfilling {} new registry addresses from block {} to {}",
+        info_log_name,
+        IndexingEventProgressStatus::Live.log(),
+        added.len(),
+        from_block,
+        to_block
+    );
+    let mut max_block_range_limitation = cached_provider.max_block_range;
+    let mut filter = live_filter
+        .clone()
+        .set_address(ValueOrArray::Array(added))
+        .set_from_block(from_block)
+        .set_to_block(calculate_process_historic_log_to_block(
+            &from_block,
+            &to_block,
+            &max_block_range_limitation,
+        ));
+    while let Some(result) = fetch_historic_logs_stream(
+        cached_provider,
+        stats,
+        tx,
+        topic_id,
+        filter,
+        max_block_range_limitation,
+        to_block,
+        info_log_name,
+    )
+    .await
+    {
+        filter = result.next;
+        max_block_range_limitation = result.max_block_range_limitation;
+    }
+
+    !tx.is_closed()
+}
+
 #[derive(Debug)]
 struct RetryWithBlockRangeResult {
     from: BlockNumber,
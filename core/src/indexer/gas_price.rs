@@ -0,0 +1,280 @@
+use std::{sync::Arc, time::Duration};
+
+use ethers::{
+    prelude::ProviderError,
+    types::{FeeHistory, U64},
+};
+use tracing::{error, info};
+
+use crate::{
+    database::postgres::{
+        client::{BulkInsertPostgresError, PostgresClient, PostgresError},
+        gas_price::{gas_price_last_sampled, GAS_PRICE_COLUMNS, GAS_PRICE_REWARD_PERCENTILES},
+        sql_type_wrapper::EthereumSqlTypeWrapper,
+    },
+    indexer::reorg::reorg_safe_distance_for_chain,
+    manifest::{
+        gas_price::{GasPriceNetwork, GasPriceTracking},
+        storage::InsertStrategy,
+    },
+    provider::{CreateNetworkProvider, JsonRpcCachedProvider},
+};
+
+/// Most nodes cap `eth_feeHistory` at 1024 blocks, kept lower so a single call stays cheap
+const GAS_PRICE_BLOCK_RANGE: u64 = 500;
+
+const GAS_PRICE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const GAS_PRICE_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Consecutive failures before a network stops sampling, e.g. a chain without `eth_feeHistory`
+const GAS_PRICE_MAX_ATTEMPTS: u32 = 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum GasPriceError {
+    #[error("Gas price network {0} is not a configured network")]
+    UnknownNetwork(String),
+
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("eth_feeHistory returned no blocks up to {0}")]
+    EmptyFeeHistory(U64),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("{0}")]
+    BulkInsertError(#[from] BulkInsertPostgresError),
+}
+
+/// The rows of the blocks in `fee_history` within the range, chains without a basefee and empty
+/// blocks without rewards are written as NULL
+fn fee_history_rows(
+    network: &str,
+    fee_history: &FeeHistory,
+    from_block: U64,
+    to_block: U64,
+) -> Vec<Vec<EthereumSqlTypeWrapper>> {
+    let oldest_block = fee_history.oldest_block.as_u64();
+
+    // base_fee_per_gas also holds the basefee of the block after the newest one
+    fee_history
+        .gas_used_ratio
+        .iter()
+        .enumerate()
+        .map(|(index, gas_used_ratio)| {
+            (U64::from(oldest_block + index as u64), index, gas_used_ratio)
+        })
+        .filter(|(block_number, _, _)| *block_number >= from_block && *block_number <= to_block)
+        .map(|(block_number, index, gas_used_ratio)| {
+            let reward = fee_history.reward.get(index);
+            let mut row = vec![
+                EthereumSqlTypeWrapper::String(network.to_string()),
+                EthereumSqlTypeWrapper::U64(block_number),
+                EthereumSqlTypeWrapper::NullableNumeric(
+                    fee_history.base_fee_per_gas.get(index).map(|fee| fee.to_string()),
+                ),
+                EthereumSqlTypeWrapper::Numeric(gas_used_ratio.to_string()),
+            ];
+            row.extend((0..GAS_PRICE_REWARD_PERCENTILES.len()).map(|percentile| {
+                EthereumSqlTypeWrapper::NullableNumeric(
+                    reward.and_then(|reward| reward.get(percentile)).map(|fee| fee.to_string()),
+                )
+            }));
+            row
+        })
+        .collect()
+}
+
+struct GasPriceSampler {
+    table_name: String,
+    postgres: Arc<PostgresClient>,
+    insert_strategy: InsertStrategy,
+}
+
+impl GasPriceSampler {
+    /// Returns the last block sampled, nodes can return fewer blocks than asked for so the older
+    /// blocks are asked for again and the range ends early when the newest ones are missing
+    async fn sample_range(
+        &self,
+        network: &str,
+        provider: &JsonRpcCachedProvider,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<(usize, U64), GasPriceError> {
+        let mut rows = Vec::new();
+        let mut sampled_to = None;
+        let mut newest_block = to_block;
+
+        loop {
+            let block_count = (newest_block - from_block).as_u64() + 1;
+            let fee_history = provider
+                .fee_history(block_count, newest_block, &GAS_PRICE_REWARD_PERCENTILES)
+                .await?;
+
+            let oldest_block = U64::from(fee_history.oldest_block.as_u64());
+            let returned = fee_history.gas_used_ratio.len() as u64;
+            if returned == 0 || oldest_block > newest_block {
+                return Err(GasPriceError::EmptyFeeHistory(newest_block));
+            }
+
+            let returned_newest = (oldest_block + returned - 1).min(newest_block);
+            match sampled_to {
+                None => sampled_to = Some(returned_newest),
+                // a gap in the middle of the range can not be filled in later
+                Some(_) if returned_newest < newest_block => {
+                    return Err(GasPriceError::EmptyFeeHistory(newest_block));
+                }
+                Some(_) => {}
+            }
+            rows.extend(fee_history_rows(network, &fee_history, from_block, returned_newest));
+
+            if oldest_block <= from_block {
+                break;
+            }
+            newest_block = oldest_block - 1;
+        }
+
+        let columns: Vec<String> = GAS_PRICE_COLUMNS.iter().map(|c| c.to_string()).collect();
+        self.postgres
+            .insert_bulk(&self.table_name, &columns, &rows, self.insert_strategy, true)
+            .await?;
+
+        Ok((rows.len(), sampled_to.unwrap_or(to_block)))
+    }
+
+    async fn sample_network(
+        self: Arc<Self>,
+        network: GasPriceNetwork,
+        provider: Arc<JsonRpcCachedProvider>,
+    ) -> Result<(), GasPriceError> {
+        let reorg_safe_distance = reorg_safe_distance_for_chain(&provider.get_chain_id().await?);
+
+        let last_sampled =
+            gas_price_last_sampled(&self.postgres, &self.table_name, &network.network).await?;
+        let mut from_block = match (last_sampled, network.start_block) {
+            (Some(last_sampled), _) => last_sampled + 1,
+            (None, Some(start_block)) => start_block,
+            (None, None) => provider.get_block_number().await?.saturating_sub(reorg_safe_distance),
+        };
+
+        let mut failures = 0;
+        loop {
+            let result = match provider.get_block_number().await {
+                Ok(latest) => {
+                    let safe_block = latest.saturating_sub(reorg_safe_distance);
+                    if from_block > safe_block {
+                        failures = 0;
+                        tokio::time::sleep(GAS_PRICE_POLL_INTERVAL).await;
+                        continue;
+                    }
+
+                    let to_block = safe_block.min(from_block + GAS_PRICE_BLOCK_RANGE - 1);
+                    self.sample_range(&network.network, &provider, from_block, to_block).await
+                }
+                Err(e) => Err(e.into()),
+            };
+
+            match result {
+                Ok((sampled, sampled_to)) => {
+                    info!(
+                        "Gas prices - {} - {} blocks - blocks: {} - {}",
+                        network.network, sampled, from_block, sampled_to
+                    );
+                    failures = 0;
+                    from_block = sampled_to + 1;
+                }
+                Err(e) => {
+                    failures += 1;
+                    if failures >= GAS_PRICE_MAX_ATTEMPTS {
+                        return Err(e);
+                    }
+
+                    error!(
+                        "Gas prices for {} failed from block {} (attempt {}): {}",
+                        network.network, from_block, failures, e
+                    );
+                    let backoff =
+                        (GAS_PRICE_POLL_INTERVAL * 2u32.pow(failures)).min(GAS_PRICE_MAX_BACKOFF);
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+}
+
+/// Starts a task per gas price network which catches up and then follows the chain
+pub fn start_gas_price_sampling(
+    gas_prices: &GasPriceTracking,
+    postgres: Arc<PostgresClient>,
+    insert_strategy: InsertStrategy,
+    network_providers: &[CreateNetworkProvider],
+) -> Result<(), GasPriceError> {
+    let sampler = Arc::new(GasPriceSampler {
+        table_name: gas_prices.table_name(),
+        postgres,
+        insert_strategy,
+    });
+
+    for network in &gas_prices.networks {
+        let provider = network_providers
+            .iter()
+            .find(|provider| provider.network_name == network.network)
+            .map(|provider| Arc::clone(&provider.client))
+            .ok_or_else(|| GasPriceError::UnknownNetwork(network.network.clone()))?;
+
+        info!("Sampling gas prices on {}", network.network);
+        let sampler = Arc::clone(&sampler);
+        let network = network.clone();
+        tokio::spawn(async move {
+            let network_name = network.network.clone();
+            if let Err(e) = sampler.sample_network(network, provider).await {
+                error!("Gas prices for {} stopped: {}", network_name, e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+
+    use super::*;
+
+    fn fee_history(oldest_block: u64, blocks: u64, base_fees: usize) -> FeeHistory {
+        FeeHistory {
+            oldest_block: U256::from(oldest_block),
+            base_fee_per_gas: (0..base_fees).map(|fee| U256::from(fee + 1)).collect(),
+            gas_used_ratio: (0..blocks).map(|_| 0.5).collect(),
+            reward: (0..blocks).map(|_| vec![U256::from(1), U256::from(2)]).collect(),
+        }
+    }
+
+    #[test]
+    fn test_fee_history_rows() {
+        let rows =
+            fee_history_rows("ethereum", &fee_history(10, 5, 6), U64::from(12), U64::from(13));
+
+        assert_eq!(rows.len(), 2);
+        assert!(matches!(rows[0][1], EthereumSqlTypeWrapper::U64(block) if block == U64::from(12)));
+        assert!(matches!(rows[1][1], EthereumSqlTypeWrapper::U64(block) if block == U64::from(13)));
+        assert!(
+            matches!(&rows[0][2], EthereumSqlTypeWrapper::NullableNumeric(Some(fee)) if fee == "3")
+        );
+        // the third percentile is missing from the reward
+        assert!(matches!(&rows[0][6], EthereumSqlTypeWrapper::NullableNumeric(None)));
+    }
+
+    #[test]
+    fn test_fee_history_rows_without_basefee() {
+        let rows = fee_history_rows("bsc", &fee_history(10, 2, 0), U64::from(10), U64::from(11));
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows
+            .iter()
+            .all(|row| matches!(row[2], EthereumSqlTypeWrapper::NullableNumeric(None))));
+    }
+}
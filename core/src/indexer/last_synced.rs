@@ -1,4 +1,9 @@
-use std::{path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex as StdMutex, OnceLock, Weak},
+    time::{Duration, Instant},
+};
 
 use ethers::prelude::U64;
 use rust_decimal::Decimal;
@@ -6,17 +11,22 @@ use tokio::{
     fs,
     fs::File,
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    sync::Mutex,
 };
 use tracing::error;
 
 use crate::{
+    database::{
+        backend::{registered_storage_backends, CheckpointKey},
+        postgres::{client::PostgresError, internal_schema::internal_schema},
+    },
     event::config::EventProcessingConfig,
     helpers::{camel_to_snake, get_full_path},
-    manifest::{storage::CsvDetails, stream::StreamsConfig},
+    manifest::{contract::CheckpointConfig, storage::CsvDetails, stream::StreamsConfig},
     EthereumSqlTypeWrapper, PostgresClient,
 };
 
-async fn get_last_synced_block_number_file(
+pub(crate) async fn get_last_synced_block_number_file(
     full_path: &Path,
     contract_name: &str,
     network: &str,
@@ -142,34 +152,93 @@ pub async fn get_last_synced_block_number(config: SyncConfig<'_>) -> Option<U64>
 
     // Query database for last synced block
     if let Some(database) = config.database {
-        let query = format!(
-            "SELECT last_synced_block FROM rindexer_internal.{}_{}_{} WHERE network = $1",
-            camel_to_snake(config.indexer_name),
-            camel_to_snake(config.contract_name),
-            camel_to_snake(config.event_name)
-        );
-
-        match database.query_one(&query, &[&config.network]).await {
-            Ok(row) => {
-                let result: Decimal = row.get("last_synced_block");
-                let parsed = U64::from_dec_str(&result.to_string())
-                    .expect("Failed to parse last_synced_block");
-                if parsed.is_zero() {
-                    None
-                } else {
-                    Some(parsed)
-                }
-            }
+        match get_last_synced_block_number_postgres(
+            database,
+            config.indexer_name,
+            config.contract_name,
+            config.event_name,
+            config.network,
+        )
+        .await
+        {
+            Ok(result) => result,
             Err(e) => {
                 error!("Error fetching last synced block: {:?}", e);
                 None
             }
         }
+    } else if !config.contract_csv_enabled && config.stream_details.is_none() {
+        // nothing built in stores the checkpoint so ask the registered storage backends
+        let key = CheckpointKey {
+            indexer_name: config.indexer_name,
+            contract_name: config.contract_name,
+            event_name: config.event_name,
+            network: config.network,
+        };
+        for backend in registered_storage_backends() {
+            match backend.read_checkpoint(&key).await {
+                Ok(Some(value)) if !value.is_zero() => return Some(value),
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Error fetching last synced block from {}: {}", backend.name(), e);
+                }
+            }
+        }
+        None
     } else {
         None
     }
 }
 
+pub(crate) async fn get_last_synced_block_number_postgres(
+    database: &PostgresClient,
+    indexer_name: &str,
+    contract_name: &str,
+    event_name: &str,
+    network: &str,
+) -> Result<Option<U64>, PostgresError> {
+    let query = format!(
+        "SELECT last_synced_block FROM {}.{}_{}_{} WHERE network = $1",
+        internal_schema(),
+        camel_to_snake(indexer_name),
+        camel_to_snake(contract_name),
+        camel_to_snake(event_name)
+    );
+
+    let row = database.query_one(&query, &[&network]).await?;
+    let result: Decimal = row.get("last_synced_block");
+    let parsed = U64::from_dec_str(&result.to_string()).expect("Failed to parse last_synced_block");
+
+    Ok(if parsed.is_zero() { None } else { Some(parsed) })
+}
+
+pub(crate) async fn update_last_synced_block_number_postgres(
+    database: &PostgresClient,
+    indexer_name: &str,
+    contract_name: &str,
+    event_name: &str,
+    network: &str,
+    to_block: U64,
+) -> Result<u64, PostgresError> {
+    let table_name = format!(
+        "{}.{}_{}_{}",
+        internal_schema(),
+        camel_to_snake(indexer_name),
+        camel_to_snake(contract_name),
+        camel_to_snake(event_name)
+    );
+    let query = format!(
+        "UPDATE {} SET last_synced_block = $1 WHERE network = $2 AND $1 > last_synced_block",
+        table_name
+    );
+    // only ever moves forward so running it again is harmless
+    database
+        .with_write_retry(&table_name, || async {
+            database.execute(&query, &[&EthereumSqlTypeWrapper::U64(to_block), &network]).await
+        })
+        .await
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum UpdateLastSyncedBlockNumberFile {
     #[error("File IO error: {0}")]
@@ -179,25 +248,18 @@ pub enum UpdateLastSyncedBlockNumberFile {
     ParseError(String, String),
 }
 
-async fn update_last_synced_block_number_for_file(
-    config: &Arc<EventProcessingConfig>,
+pub(crate) async fn update_last_synced_block_number_for_file(
     full_path: &Path,
+    contract_name: &str,
+    network: &str,
+    event_name: &str,
     to_block: U64,
 ) -> Result<(), UpdateLastSyncedBlockNumberFile> {
-    let file_path = build_last_synced_block_number_file(
-        full_path,
-        &config.contract_name,
-        &config.network_contract.network,
-        &config.event_name,
-    );
+    let file_path =
+        build_last_synced_block_number_file(full_path, contract_name, network, event_name);
 
-    let last_block = get_last_synced_block_number_file(
-        full_path,
-        &config.contract_name,
-        &config.network_contract.network,
-        &config.event_name,
-    )
-    .await?;
+    let last_block =
+        get_last_synced_block_number_file(full_path, contract_name, network, event_name).await?;
 
     let to_block_higher_then_last_block =
         if let Some(last_block_value) = last_block { to_block > last_block_value } else { true };
@@ -215,8 +277,99 @@ async fn update_last_synced_block_number_for_file(
     Ok(())
 }
 
+/// Tracks when the last synced block was last written to decide if the next one should be
+pub struct LastSyncedCheckpoint {
+    every_blocks: Option<U64>,
+    every: Option<Duration>,
+    state: Mutex<CheckpointState>,
+}
+
+#[derive(Default)]
+struct CheckpointState {
+    last_written: Option<(U64, Instant)>,
+    /// The highest block held back since the last write
+    pending: Option<U64>,
+}
+
+impl LastSyncedCheckpoint {
+    pub fn new(config: Option<&CheckpointConfig>) -> Self {
+        Self {
+            every_blocks: config.and_then(|c| c.every_blocks).map(U64::from),
+            every: config.and_then(|c| c.every_seconds).map(Duration::from_secs),
+            state: Mutex::new(CheckpointState::default()),
+        }
+    }
+
+    fn throttled(&self) -> bool {
+        self.every_blocks.is_some() || self.every.is_some()
+    }
+
+    /// Marks the block as written when it is due, the final block of a range is always due
+    async fn due(&self, to_block: U64, is_final: bool) -> bool {
+        if !self.throttled() {
+            return true;
+        }
+
+        let mut state = self.state.lock().await;
+        let due = is_final ||
+            match state.last_written {
+                None => true,
+                Some((block, at)) => {
+                    self.every_blocks.is_some_and(|every| to_block >= block + every) ||
+                        self.every.is_some_and(|every| at.elapsed() >= every)
+                }
+            };
+        if due {
+            state.last_written = Some((to_block, Instant::now()));
+            state.pending = None;
+        } else {
+            state.pending = state.pending.max(Some(to_block));
+        }
+
+        due
+    }
+
+    /// Takes the block held back since the last write and marks it as written
+    async fn take_pending(&self) -> Option<U64> {
+        let mut state = self.state.lock().await;
+        let pending = state.pending.take()?;
+        state.last_written = Some((pending, Instant::now()));
+        Some(pending)
+    }
+}
+
+/// Events with a held back checkpoint, written by `flush_last_synced_checkpoints` on shutdown
+fn pending_checkpoints() -> &'static StdMutex<HashMap<String, Weak<EventProcessingConfig>>> {
+    static PENDING: OnceLock<StdMutex<HashMap<String, Weak<EventProcessingConfig>>>> =
+        OnceLock::new();
+    PENDING.get_or_init(|| StdMutex::new(HashMap::new()))
+}
+
+/// Writes the last synced block every throttled checkpoint held back, called before shutting down
+/// so a restart does not reprocess the ranges since the last write
+pub async fn flush_last_synced_checkpoints() {
+    let configs: Vec<Arc<EventProcessingConfig>> = pending_checkpoints()
+        .lock()
+        .map(|mut pending| pending.drain().filter_map(|(_, config)| config.upgrade()).collect())
+        .unwrap_or_default();
+
+    for config in configs {
+        if let Some(to_block) = config.checkpoint.take_pending().await {
+            write_last_synced(&config, to_block).await;
+        }
+    }
+}
+
 pub fn update_progress_and_last_synced(config: Arc<EventProcessingConfig>, to_block: U64) {
     tokio::spawn(async move {
+        let to_block = match &config.catch_up_backfill {
+            Some(backfill) => match backfill.checkpoint(to_block) {
+                Some(to_block) => to_block,
+                None => return,
+            },
+            None => to_block,
+        };
+
         let update_last_synced_block_result = config
             .progress
             .lock()
@@ -227,59 +380,129 @@ pub fn update_progress_and_last_synced(config: Arc<EventProcessingConfig>, to_bl
             error!("Error updating last synced block: {:?}", e);
         }
 
-        if let Some(database) = &config.database {
-            let result = database
-                .execute(
-                    &format!(
-                        "UPDATE rindexer_internal.{}_{}_{} SET last_synced_block = $1 WHERE network = $2 AND $1 > last_synced_block",
-                        camel_to_snake(&config.indexer_name),
-                        camel_to_snake(&config.contract_name),
-                        camel_to_snake(&config.event_name)
-                    ),
-                    &[
-                        &EthereumSqlTypeWrapper::U64(to_block),
-                        &config.network_contract.network,
-                    ],
-                )
-                .await;
-
-            if let Err(e) = result {
-                error!("Error updating last synced block: {:?}", e);
-            }
-        } else if let Some(csv_details) = &config.csv_details {
-            if let Err(e) = update_last_synced_block_number_for_file(
-                &config,
-                &get_full_path(&config.project_path, &csv_details.path).unwrap_or_else(|_| {
-                    panic!("failed to get full path {}", config.project_path.display())
-                }),
-                to_block,
-            )
-            .await
-            {
-                error!(
-                    "Error updating last synced block to CSV - path - {} error - {:?}",
-                    csv_details.path, e
-                );
-            }
-        } else if let Some(stream_last_synced_block_file_path) =
-            &config.stream_last_synced_block_file_path
-        {
-            if let Err(e) = update_last_synced_block_number_for_file(
-                &config,
-                &config
-                    .project_path
-                    .join(stream_last_synced_block_file_path)
-                    .canonicalize()
-                    .expect("Failed to canonicalize path"),
-                to_block,
-            )
-            .await
-            {
-                error!(
-                    "Error updating last synced block to stream - path - {} error - {:?}",
-                    stream_last_synced_block_file_path, e
-                );
+        let is_final = !config.live_indexing && to_block >= config.end_block;
+        if !config.checkpoint.due(to_block, is_final).await {
+            if let Ok(mut pending) = pending_checkpoints().lock() {
+                pending.entry(config.id.clone()).or_insert_with(|| Arc::downgrade(&config));
             }
+            return;
         }
+
+        write_last_synced(&config, to_block).await;
     });
 }
+
+async fn write_last_synced(config: &EventProcessingConfig, to_block: U64) {
+    let key = CheckpointKey {
+        indexer_name: &config.indexer_name,
+        contract_name: &config.contract_name,
+        event_name: &config.event_name,
+        network: &config.network_contract.network,
+    };
+    for backend in registered_storage_backends() {
+        if let Err(e) = backend.write_checkpoint(&key, to_block).await {
+            error!("Error updating last synced block in {}: {}", backend.name(), e);
+        }
+    }
+
+    if let Some(database) = &config.database {
+        let result = update_last_synced_block_number_postgres(
+            database,
+            &config.indexer_name,
+            &config.contract_name,
+            &config.event_name,
+            &config.network_contract.network,
+            to_block,
+        )
+        .await;
+
+        if let Err(e) = result {
+            error!("Error updating last synced block: {:?}", e);
+        }
+    } else if let Some(csv_details) = &config.csv_details {
+        if let Err(e) = update_last_synced_block_number_for_file(
+            &get_full_path(&config.project_path, &csv_details.path).unwrap_or_else(|_| {
+                panic!("failed to get full path {}", config.project_path.display())
+            }),
+            &config.contract_name,
+            &config.network_contract.network,
+            &config.event_name,
+            to_block,
+        )
+        .await
+        {
+            error!(
+                "Error updating last synced block to CSV - path - {} error - {:?}",
+                csv_details.path, e
+            );
+        }
+    } else if let Some(stream_last_synced_block_file_path) =
+        &config.stream_last_synced_block_file_path
+    {
+        if let Err(e) = update_last_synced_block_number_for_file(
+            &config
+                .project_path
+                .join(stream_last_synced_block_file_path)
+                .canonicalize()
+                .expect("Failed to canonicalize path"),
+            &config.contract_name,
+            &config.network_contract.network,
+            &config.event_name,
+            to_block,
+        )
+        .await
+        {
+            error!(
+                "Error updating last synced block to stream - path - {} error - {:?}",
+                stream_last_synced_block_file_path, e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checkpoint_due() {
+        let checkpoint = LastSyncedCheckpoint::new(Some(&CheckpointConfig {
+            every_blocks: Some(100),
+            every_seconds: None,
+        }));
+
+        assert!(checkpoint.due(U64::from(10), false).await);
+        assert!(!checkpoint.due(U64::from(50), false).await);
+        assert!(!checkpoint.due(U64::from(80), false).await);
+        assert!(checkpoint.due(U64::from(110), false).await);
+        assert_eq!(checkpoint.take_pending().await, None);
+
+        // the final range is written even when the interval has not passed
+        assert!(!checkpoint.due(U64::from(150), false).await);
+        assert!(checkpoint.due(U64::from(160), true).await);
+        assert_eq!(checkpoint.take_pending().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_pending_before_shutdown() {
+        let checkpoint = LastSyncedCheckpoint::new(Some(&CheckpointConfig {
+            every_blocks: None,
+            every_seconds: Some(3600),
+        }));
+
+        assert!(checkpoint.due(U64::from(10), false).await);
+        assert!(!checkpoint.due(U64::from(30), false).await);
+        assert!(!checkpoint.due(U64::from(20), false).await);
+        assert_eq!(checkpoint.take_pending().await, Some(U64::from(30)));
+        assert_eq!(checkpoint.take_pending().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_unthrottled() {
+        let checkpoint = LastSyncedCheckpoint::new(None);
+
+        assert!(checkpoint.due(U64::from(10), false).await);
+        assert!(checkpoint.due(U64::from(11), false).await);
+        assert_eq!(checkpoint.take_pending().await, None);
+    }
+}
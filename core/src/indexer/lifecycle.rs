@@ -0,0 +1,384 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use ethers::types::{Address, U64};
+use tokio::{sync::Mutex, time::sleep};
+use tracing::{error, warn};
+
+use crate::{
+    database::postgres::internal_schema::{internal_schema, internal_table},
+    manifest::contract::{CodeRemovedAction, ContractLifecycleConfig},
+    provider::JsonRpcCachedProvider,
+    PostgresClient,
+};
+
+const MAX_CODE_CHECK_BACKOFF: Duration = Duration::from_secs(30);
+
+fn contract_lifecycle_table_name() -> String {
+    internal_table("contract_lifecycle")
+}
+
+pub(crate) fn create_contract_lifecycle_table_sql() -> String {
+    format!(
+        r#"
+        CREATE SCHEMA IF NOT EXISTS {};
+        CREATE TABLE IF NOT EXISTS {} (
+            indexer_name TEXT NOT NULL,
+            contract_name TEXT NOT NULL,
+            network TEXT NOT NULL,
+            address CHAR(42) NOT NULL,
+            event TEXT NOT NULL,
+            block_number NUMERIC NOT NULL,
+            recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (indexer_name, contract_name, network, address, event, block_number)
+        );
+    "#,
+        internal_schema(),
+        contract_lifecycle_table_name()
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LifecycleEvent {
+    CodeRemoved,
+    CodeDeployed,
+}
+
+impl LifecycleEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            LifecycleEvent::CodeRemoved => "code_removed",
+            LifecycleEvent::CodeDeployed => "code_deployed",
+        }
+    }
+}
+
+/// Where the code of the contract stands once a range has been checked
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LifecycleView {
+    /// Last block events are indexed for when the contract stops on code removal
+    pub stopped_at: Option<U64>,
+    // first block each address had its code removed in
+    code_removed: HashMap<Address, U64>,
+}
+
+impl LifecycleView {
+    /// Whether the code of the address was removed at or before the block, events after it come
+    /// from whatever was redeployed at the address
+    pub fn after_code_removed(&self, address: Address, block: U64) -> bool {
+        self.code_removed.get(&address).is_some_and(|removed_at| block >= *removed_at)
+    }
+
+    fn removed(&mut self, address: Address, block: U64, action: CodeRemovedAction) {
+        let removed_at = self.code_removed.entry(address).or_insert(block);
+        *removed_at = (*removed_at).min(block);
+        if action == CodeRemovedAction::Stop {
+            self.stopped_at =
+                Some(self.stopped_at.map_or(block, |stopped_at| stopped_at.min(block)));
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LifecycleState {
+    loaded: bool,
+    has_code: HashMap<Address, bool>,
+    checked_to: Option<U64>,
+    view: LifecycleView,
+}
+
+/// Tracks the code of a contract on one network, shared by all of its events so every range is
+/// only checked once
+pub struct ContractLifecycle {
+    indexer_name: String,
+    contract_name: String,
+    network: String,
+    addresses: Vec<Address>,
+    action: CodeRemovedAction,
+    provider: Arc<JsonRpcCachedProvider>,
+    database: Option<Arc<PostgresClient>>,
+    state: Mutex<LifecycleState>,
+}
+
+impl ContractLifecycle {
+    pub fn new(
+        indexer_name: &str,
+        contract_name: &str,
+        network: &str,
+        addresses: Vec<Address>,
+        config: &ContractLifecycleConfig,
+        provider: Arc<JsonRpcCachedProvider>,
+        database: Option<Arc<PostgresClient>>,
+    ) -> Self {
+        Self {
+            indexer_name: indexer_name.to_string(),
+            contract_name: contract_name.to_string(),
+            network: network.to_string(),
+            addresses,
+            action: config.on_code_removed.unwrap_or_default(),
+            provider,
+            database,
+            state: Mutex::new(LifecycleState::default()),
+        }
+    }
+
+    /// Retries until the provider answers, indexing past a range whose code was not checked
+    /// could index events after the contract stopped
+    async fn has_code(&self, address: Address, block: U64) -> bool {
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            match self.provider.get_code(address, block).await {
+                Ok(code) => return !code.is_empty(),
+                Err(e) => {
+                    error!(
+                        "{} - Could not read the code of {:?} at block {}, retrying in {:?}: {}",
+                        self.contract_name, address, block, backoff, e
+                    );
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_CODE_CHECK_BACKOFF);
+                }
+            }
+        }
+    }
+
+    /// First block in `(from, to]` where the code presence is `has_code`, it is at `to`
+    async fn first_block_with(
+        &self,
+        address: Address,
+        mut from: U64,
+        mut to: U64,
+        has_code: bool,
+    ) -> U64 {
+        while to - from > U64::one() {
+            let middle = from + (to - from) / 2;
+            if self.has_code(address, middle).await == has_code {
+                to = middle;
+            } else {
+                from = middle;
+            }
+        }
+        to
+    }
+
+    /// Reads back what was recorded before a restart so a stopped contract stays stopped
+    async fn load(&self, state: &mut LifecycleState) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        if let Err(e) = database
+            .execute_ddl(
+                &create_contract_lifecycle_table_sql(),
+                "create the contract lifecycle table",
+            )
+            .await
+        {
+            error!("{} - Could not create the contract lifecycle table: {}", self.contract_name, e);
+            return;
+        }
+
+        let rows = match database
+            .query(
+                &format!(
+                    "SELECT address::TEXT, block_number::TEXT FROM {} WHERE indexer_name = $1 AND contract_name = $2 AND network = $3 AND event = $4",
+                    contract_lifecycle_table_name()
+                ),
+                &[
+                    &self.indexer_name,
+                    &self.contract_name,
+                    &self.network,
+                    &LifecycleEvent::CodeRemoved.name(),
+                ],
+            )
+            .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                error!("{} - Could not read the contract lifecycle: {}", self.contract_name, e);
+                return;
+            }
+        };
+        for row in rows {
+            let (Ok(address), Ok(block)) = (
+                row.get::<_, String>(0).trim().parse::<Address>(),
+                U64::from_dec_str(&row.get::<_, String>(1)),
+            ) else {
+                continue;
+            };
+            if self.addresses.contains(&address) {
+                state.view.removed(address, block, self.action);
+            }
+        }
+    }
+
+    async fn record(&self, address: Address, event: LifecycleEvent, block: U64) {
+        warn!(
+            "{} - {} - {:?} {} at block {}",
+            self.contract_name,
+            self.network,
+            address,
+            event.name(),
+            block
+        );
+
+        let Some(database) = &self.database else {
+            return;
+        };
+
+        let sql = format!(
+            "INSERT INTO {} (indexer_name, contract_name, network, address, event, block_number) VALUES ($1, $2, $3, $4, $5, $6::TEXT::NUMERIC) ON CONFLICT DO NOTHING",
+            contract_lifecycle_table_name()
+        );
+        if let Err(e) = database
+            .execute(
+                &sql,
+                &[
+                    &self.indexer_name,
+                    &self.contract_name,
+                    &self.network,
+                    &format!("{:?}", address),
+                    &event.name(),
+                    &block.to_string(),
+                ],
+            )
+            .await
+        {
+            error!("{} - Could not record {}: {}", self.contract_name, event.name(), e);
+        }
+    }
+
+    /// Checks the code up to `to_block`, the view says which events of the range are indexed and
+    /// which come after a code removal
+    pub async fn observe(&self, from_block: U64, to_block: U64) -> LifecycleView {
+        let mut state = self.state.lock().await;
+        if !state.loaded {
+            state.loaded = true;
+            self.load(&mut state).await;
+        }
+        if state.view.stopped_at.is_some() {
+            return state.view.clone();
+        }
+
+        // the code before the first range is where changes are looked for from
+        let checked_to = match state.checked_to {
+            Some(checked_to) => checked_to,
+            None => {
+                let baseline = from_block.saturating_sub(U64::one());
+                for address in &self.addresses {
+                    let has_code = self.has_code(*address, baseline).await;
+                    state.has_code.insert(*address, has_code);
+                }
+                state.checked_to = Some(baseline);
+                baseline
+            }
+        };
+        if to_block <= checked_to {
+            return state.view.clone();
+        }
+
+        for address in &self.addresses {
+            let has_code = self.has_code(*address, to_block).await;
+            let had_code = state.has_code.insert(*address, has_code).unwrap_or(has_code);
+            if had_code == has_code {
+                continue;
+            }
+
+            let block = self.first_block_with(*address, checked_to, to_block, has_code).await;
+            if has_code {
+                self.record(*address, LifecycleEvent::CodeDeployed, block).await;
+            } else {
+                self.record(*address, LifecycleEvent::CodeRemoved, block).await;
+                state.view.removed(*address, block, self.action);
+                if self.action == CodeRemovedAction::Stop {
+                    warn!(
+                        "{} - {} - code removed, stopping indexing after block {}",
+                        self.contract_name, self.network, block
+                    );
+                }
+            }
+        }
+
+        state.checked_to = Some(to_block);
+        state.view.clone()
+    }
+
+    pub async fn stopped_at(&self) -> Option<U64> {
+        self.state.lock().await.view.stopped_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderMap;
+
+    use super::*;
+    use crate::provider::create_client;
+
+    #[test]
+    fn test_lifecycle_view() {
+        let (a, b) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+
+        let mut annotated = LifecycleView::default();
+        annotated.removed(a, U64::from(100), CodeRemovedAction::Annotate);
+        annotated.removed(a, U64::from(150), CodeRemovedAction::Annotate);
+        assert_eq!(annotated.stopped_at, None);
+        assert!(!annotated.after_code_removed(a, U64::from(99)));
+        assert!(annotated.after_code_removed(a, U64::from(100)));
+        assert!(annotated.after_code_removed(a, U64::from(200)));
+        assert!(!annotated.after_code_removed(b, U64::from(200)));
+
+        let mut stopped = LifecycleView::default();
+        stopped.removed(b, U64::from(300), CodeRemovedAction::Stop);
+        stopped.removed(a, U64::from(200), CodeRemovedAction::Stop);
+        assert_eq!(stopped.stopped_at, Some(U64::from(200)));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database in DATABASE_URL"]
+    async fn test_stop_survives_restart() {
+        let database = PostgresClient::new().await.expect("DATABASE_URL has to point to postgres");
+        let database = Arc::new(database);
+        let address = Address::from_low_u64_be(1);
+        let config = ContractLifecycleConfig { on_code_removed: Some(CodeRemovedAction::Stop) };
+        // nothing listens here so any code check would hang retrying
+        let provider = create_client("http://127.0.0.1:1", None, None, HeaderMap::new()).unwrap();
+        let lifecycle = || {
+            ContractLifecycle::new(
+                "lifecycle_test",
+                "Vault",
+                "ethereum",
+                vec![address],
+                &config,
+                Arc::clone(&provider),
+                Some(Arc::clone(&database)),
+            )
+        };
+
+        let before_restart = lifecycle();
+        before_restart.load(&mut LifecycleState::default()).await;
+        database
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE indexer_name = 'lifecycle_test'",
+                    contract_lifecycle_table_name()
+                ),
+                &[],
+            )
+            .await
+            .unwrap();
+        before_restart.record(address, LifecycleEvent::CodeRemoved, U64::from(120)).await;
+
+        let view = lifecycle().observe(U64::from(200), U64::from(300)).await;
+        assert_eq!(view.stopped_at, Some(U64::from(120)));
+        assert!(view.after_code_removed(address, U64::from(120)));
+
+        database
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE indexer_name = 'lifecycle_test'",
+                    contract_lifecycle_table_name()
+                ),
+                &[],
+            )
+            .await
+            .unwrap();
+    }
+}
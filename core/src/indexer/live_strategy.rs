@@ -0,0 +1,248 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use ethers::{
+    providers::{Middleware, Provider, Ws},
+    types::{Block, H256, U256},
+};
+use futures::StreamExt;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::{
+    manifest::network::{LiveIndexingStrategy, Network, DEFAULT_BLOCK_POLL_INTERVAL_MS},
+    provider::JsonRpcCachedProvider,
+};
+
+pub type LatestBlock = Option<Arc<Block<H256>>>;
+
+/// Publishes new head blocks for a network, live indexing only ever waits on what is sent to
+/// `heads` so new ways of following the chain can be added by implementing this
+#[async_trait]
+pub trait LiveBlockStrategy: Send + Sync {
+    /// Runs until every watcher is dropped, sending each new head block to `heads`
+    async fn run(&self, network: &str, heads: watch::Sender<LatestBlock>);
+}
+
+/// Only notifies watchers when the head actually moves on
+fn publish_block(heads: &watch::Sender<LatestBlock>, block: Arc<Block<H256>>) {
+    heads.send_if_modified(|latest| {
+        let is_new = latest.as_ref().map(|latest| latest.number) != Some(block.number);
+        if is_new {
+            *latest = Some(block);
+        }
+        is_new
+    });
+}
+
+pub struct PollStrategy {
+    provider: Arc<JsonRpcCachedProvider>,
+    interval: Duration,
+}
+
+#[async_trait]
+impl LiveBlockStrategy for PollStrategy {
+    async fn run(&self, network: &str, heads: watch::Sender<LatestBlock>) {
+        while !heads.is_closed() {
+            match self.provider.get_latest_block().await {
+                Ok(Some(block)) => publish_block(&heads, block),
+                Ok(None) => {
+                    info!("WARNING - {} - empty latest block returned from provider, will try again in {:?}", network, self.interval);
+                }
+                Err(e) => {
+                    error!(
+                        "{} - Error getting latest block, will try again in 1 seconds - err: {}",
+                        network, e
+                    );
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+}
+
+pub struct FilterStrategy {
+    provider: Arc<JsonRpcCachedProvider>,
+    interval: Duration,
+}
+
+#[async_trait]
+impl LiveBlockStrategy for FilterStrategy {
+    async fn run(&self, network: &str, heads: watch::Sender<LatestBlock>) {
+        let mut filter_id: Option<U256> = None;
+        while !heads.is_closed() {
+            tokio::time::sleep(self.interval).await;
+
+            let id = match filter_id {
+                Some(id) => id,
+                None => match self.provider.new_block_filter().await {
+                    Ok(id) => {
+                        filter_id = Some(id);
+                        id
+                    }
+                    Err(e) => {
+                        error!(
+                            "{} - Error creating block filter, will try again - err: {}",
+                            network, e
+                        );
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                },
+            };
+
+            match self.provider.get_block_filter_changes(id).await {
+                Ok(hashes) if hashes.is_empty() => {}
+                Ok(_) => match self.provider.get_latest_block().await {
+                    Ok(Some(block)) => publish_block(&heads, block),
+                    Ok(None) => {}
+                    Err(e) => error!("{} - Error getting latest block - err: {}", network, e),
+                },
+                Err(e) => {
+                    // nodes drop filters which are not polled often enough
+                    warn!(
+                        "{} - Block filter changes failed, recreating filter - err: {}",
+                        network, e
+                    );
+                    filter_id = None;
+                }
+            }
+        }
+    }
+}
+
+pub struct SubscribeStrategy {
+    ws: String,
+}
+
+#[async_trait]
+impl LiveBlockStrategy for SubscribeStrategy {
+    async fn run(&self, network: &str, heads: watch::Sender<LatestBlock>) {
+        while !heads.is_closed() {
+            let provider = match Provider::<Ws>::connect(&self.ws).await {
+                Ok(provider) => provider,
+                Err(e) => {
+                    error!(
+                        "{} - Could not connect to ws, will try again in 1 seconds - err: {}",
+                        network, e
+                    );
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            let subscription = provider.subscribe_blocks().await;
+            match subscription {
+                Ok(mut stream) => {
+                    info!("{} - Subscribed to newHeads", network);
+                    while let Some(block) = stream.next().await {
+                        if heads.is_closed() {
+                            return;
+                        }
+                        publish_block(&heads, Arc::new(block));
+                    }
+                    warn!("{} - newHeads subscription ended, resubscribing", network);
+                }
+                Err(e) => {
+                    error!("{} - Could not subscribe to newHeads - err: {}", network, e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+}
+
+fn custom_strategies() -> &'static Mutex<HashMap<String, Arc<dyn LiveBlockStrategy>>> {
+    static CUSTOM_STRATEGIES: OnceLock<Mutex<HashMap<String, Arc<dyn LiveBlockStrategy>>>> =
+        OnceLock::new();
+    CUSTOM_STRATEGIES.get_or_init(Default::default)
+}
+
+/// Use your own strategy for a network instead of the one in the manifest, register it before
+/// starting rindexer
+pub fn register_live_block_strategy(network: &str, strategy: Arc<dyn LiveBlockStrategy>) {
+    custom_strategies().lock().unwrap().insert(network.to_string(), strategy);
+}
+
+fn strategy_for_network(
+    network_name: &str,
+    network: Option<&Network>,
+    provider: &Arc<JsonRpcCachedProvider>,
+) -> Arc<dyn LiveBlockStrategy> {
+    if let Some(strategy) = custom_strategies().lock().unwrap().get(network_name) {
+        return Arc::clone(strategy);
+    }
+
+    let Some(network) = network else {
+        return Arc::new(PollStrategy {
+            provider: Arc::clone(provider),
+            interval: Duration::from_millis(DEFAULT_BLOCK_POLL_INTERVAL_MS),
+        });
+    };
+
+    let interval = network.block_poll_interval();
+    match (network.live_indexing_strategy.unwrap_or_default(), &network.ws) {
+        (LiveIndexingStrategy::Filter, _) => {
+            Arc::new(FilterStrategy { provider: Arc::clone(provider), interval })
+        }
+        (LiveIndexingStrategy::Subscribe, Some(ws)) => {
+            Arc::new(SubscribeStrategy { ws: ws.clone() })
+        }
+        _ => Arc::new(PollStrategy { provider: Arc::clone(provider), interval }),
+    }
+}
+
+/// The head blocks of a network shared by all its live indexing streams, the strategy only starts
+/// once something watches it
+pub struct LiveBlocks {
+    network: String,
+    strategy: Arc<dyn LiveBlockStrategy>,
+    heads: OnceLock<watch::Receiver<LatestBlock>>,
+}
+
+impl LiveBlocks {
+    pub fn new(
+        network_name: &str,
+        network: Option<&Network>,
+        provider: &Arc<JsonRpcCachedProvider>,
+    ) -> Self {
+        Self {
+            network: network_name.to_string(),
+            strategy: strategy_for_network(network_name, network, provider),
+            heads: OnceLock::new(),
+        }
+    }
+
+    pub fn watch(&self) -> LiveBlockWatcher {
+        let receiver = self.heads.get_or_init(|| {
+            let (sender, receiver) = watch::channel(None);
+            let strategy = Arc::clone(&self.strategy);
+            let network = self.network.clone();
+            tokio::spawn(async move { strategy.run(&network, sender).await });
+            receiver
+        });
+
+        LiveBlockWatcher { receiver: receiver.clone() }
+    }
+}
+
+pub struct LiveBlockWatcher {
+    receiver: watch::Receiver<LatestBlock>,
+}
+
+impl LiveBlockWatcher {
+    /// Waits for a head block this watcher has not seen yet, `None` if the strategy stopped
+    pub async fn next_block(&mut self) -> LatestBlock {
+        self.receiver.changed().await.ok()?;
+        self.receiver.borrow_and_update().clone()
+    }
+
+    pub fn latest_block(&self) -> LatestBlock {
+        self.receiver.borrow().clone()
+    }
+}
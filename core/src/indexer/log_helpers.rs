@@ -1,13 +1,17 @@
+#![allow(clippy::manual_ok_err)]
+
 use std::str::FromStr;
 
 use ethers::{
     abi::{Event, Log as ParsedLog, LogParam, RawLog, Token},
     addressbook::Address,
     prelude::{Block, Bloom, FilteredParams, ValueOrArray, H256, U256},
-    types::{BigEndianHash, Log},
+    types::{BigEndianHash, Log, I256},
     utils::keccak256,
 };
 
+use crate::{abi::ABIInput, helpers::format_address};
+
 pub fn parse_log(event: &Event, log: &Log) -> Option<ParsedLog> {
     let raw_log = RawLog { topics: log.topics.clone(), data: log.data.to_vec() };
 
@@ -30,7 +34,7 @@ pub fn parse_log(event: &Event, log: &Log) -> Option<ParsedLog> {
 
 fn map_token_to_raw_values(token: &Token) -> Vec<String> {
     match token {
-        Token::Address(addr) => vec![format!("{:?}", addr)],
+        Token::Address(addr) => vec![format_address(addr)],
         Token::FixedBytes(bytes) | Token::Bytes(bytes) => vec![format!("{:?}", bytes)],
         Token::Int(int) => vec![int.to_string()],
         Token::Uint(uint) => vec![uint.to_string()],
@@ -50,6 +54,43 @@ fn map_token_to_raw_values(token: &Token) -> Vec<String> {
     }
 }
 
+/// The decoded value of an input as text, `path` walks into tuple fields by name
+pub fn log_param_text(
+    inputs: &[ABIInput],
+    log_params: &[LogParam],
+    path: &[String],
+) -> Option<String> {
+    let (first, rest) = path.split_first()?;
+    let mut token = &log_params.iter().find(|param| param.name == *first)?.value;
+    let mut components = inputs.iter().find(|input| input.name == *first)?.components.as_deref();
+    for name in rest {
+        let fields = components?;
+        let index = fields.iter().position(|field| field.name == *name)?;
+        token = match token {
+            Token::Tuple(tokens) => tokens.get(index)?,
+            _ => return None,
+        };
+        components = fields[index].components.as_deref();
+    }
+
+    Some(token_text(token))
+}
+
+fn token_text(token: &Token) -> String {
+    match token {
+        Token::Address(address) => format_address(address),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => format!("0x{}", hex::encode(bytes)),
+        Token::Int(int) => I256::from_raw(*int).to_string(),
+        Token::Uint(uint) => uint.to_string(),
+        Token::Bool(value) => value.to_string(),
+        Token::String(value) => value.clone(),
+        Token::FixedArray(tokens) | Token::Array(tokens) | Token::Tuple(tokens) => {
+            serde_json::to_string(&tokens.iter().map(token_text).collect::<Vec<_>>())
+                .unwrap_or_default()
+        }
+    }
+}
+
 pub fn map_log_params_to_raw_values(params: &[LogParam]) -> Vec<String> {
     let mut raw_values = vec![];
     for param in params {
@@ -88,7 +129,7 @@ pub fn topic_in_bloom(topic_id: H256, logs_bloom: Bloom) -> bool {
 
 pub fn is_relevant_block(
     contract_address: &Option<ValueOrArray<Address>>,
-    topic_id: &H256,
+    topic_id: Option<H256>,
     latest_block: &Block<H256>,
 ) -> bool {
     match latest_block.logs_bloom {
@@ -109,8 +150,10 @@ pub fn is_relevant_block(
                 }
             }
 
-            if !topic_in_bloom(*topic_id, logs_bloom) {
-                return false;
+            if let Some(topic_id) = topic_id {
+                if !topic_in_bloom(topic_id, logs_bloom) {
+                    return false;
+                }
             }
 
             true
@@ -5,15 +5,74 @@ pub use progress::{IndexingEventProgressStatus, IndexingEventsProgressState};
 use serde::{Deserialize, Serialize};
 
 mod log_helpers;
-pub use log_helpers::parse_topic;
+pub(crate) use log_helpers::parse_log;
+pub use log_helpers::{log_param_text, parse_topic};
 mod dependency;
 pub use dependency::ContractEventDependenciesMapFromRelationshipsError;
+mod address_registry;
+mod block_anomaly;
+mod block_timestamps;
+mod catch_up;
+mod decode;
+mod deployment_block;
 mod fetch_logs;
+mod gas_price;
 mod last_synced;
+mod lifecycle;
+mod live_strategy;
 pub mod no_code;
-mod reorg;
+mod price_enrichment;
+mod priority;
+mod redecode;
+pub(crate) mod reorg;
+mod schedule;
 pub mod start;
+mod stats;
+mod transform;
+mod unlogged;
+mod user_operation;
+mod verify;
+mod wallet_activity;
+mod watermark;
+mod webhook_ingest;
+pub use address_registry::{AddressRegistryError, AddressRegistryResolver, RegistryAddresses};
+pub(crate) use block_anomaly::{start_block_anomaly_tracking, BlockAnomalyError};
+pub use catch_up::{CatchUp, CatchUpBackfill, LiveRequest};
+pub(crate) use decode::decode_event_row;
+pub use decode::{
+    decode_transaction, DecodeError, DecodeOptions, DecodeReport, DecodedLog, DecodedLogOutcome,
+};
 pub use dependency::{ContractEventDependencies, EventDependencies, EventsDependencyTree};
+pub(crate) use deployment_block::create_deployment_blocks_table_sql;
+pub use deployment_block::DeploymentBlocks;
+pub(crate) use gas_price::{start_gas_price_sampling, GasPriceError};
+pub use last_synced::{flush_last_synced_checkpoints, LastSyncedCheckpoint};
+#[cfg(feature = "csv")]
+pub(crate) use last_synced::{
+    get_last_synced_block_number_file, update_last_synced_block_number_for_file,
+    UpdateLastSyncedBlockNumberFile,
+};
+pub(crate) use last_synced::{
+    get_last_synced_block_number_postgres, update_last_synced_block_number_postgres,
+};
+pub(crate) use lifecycle::create_contract_lifecycle_table_sql;
+pub use lifecycle::ContractLifecycle;
+pub use live_strategy::{
+    register_live_block_strategy, LatestBlock, LiveBlockStrategy, LiveBlockWatcher, LiveBlocks,
+};
+pub use priority::{IndexingSlot, IndexingSlots};
+pub use redecode::{redecode_event, RedecodeError, RedecodeOptions, RedecodeReport};
+pub use schedule::NetworkSchedule;
+pub(crate) use stats::create_indexing_stats_table_sql;
+pub use stats::{flush_pending_indexing_stats, IndexingEventStats};
+pub use unlogged::UnloggedHistoricSync;
+pub(crate) use verify::create_verify_tables_sql;
+pub use verify::{
+    start_verify_schedule, verify_indexed_events, EventGap, VerifyError, VerifyOptions,
+    VerifyReport, DEFAULT_VERIFY_CONCURRENCY, DEFAULT_VERIFY_WINDOW_BLOCKS,
+};
+pub use watermark::start_stream_watermarks;
+pub use webhook_ingest::{start_webhook_ingest, StartWebhookIngestError};
 
 use crate::manifest::contract::Contract;
 
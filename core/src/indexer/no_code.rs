@@ -1,44 +1,80 @@
-use std::{fs, io, path::Path, sync::Arc};
+#![allow(clippy::unnecessary_map_or)]
+
+use std::{io, path::Path, sync::Arc};
 
 use colored::Colorize;
-use ethers::abi::{Abi, Contract as EthersContract, Event};
+use ethers::{
+    abi::{Abi, Contract as EthersContract, Event, LogParam},
+    types::{H256, U64},
+};
+use futures::future::join_all;
 use serde_json::Value;
-use tokio_postgres::types::Type as PgType;
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    abi::{ABIItem, CreateCsvFileForEvent, EventInfo, ParamTypeError, ReadAbiError},
+    abi::{ABIItem, EventInfo, ParamTypeError, ReadAbiError},
+    api::{start_event_push_server, EventPushHub, PushedEvent, StartEventPushServerError},
     chat::ChatClients,
-    database::postgres::{
-        client::PostgresClient,
-        generate::{
-            generate_column_names_only_with_base_properties, generate_event_table_full_name,
+    database::{
+        backend::{
+            registered_storage_backends, write_registered_storage_backends, CheckpointKey,
+            PostgresStorageBackend, StorageBackend, StorageBackendError, StorageBatch,
         },
-        setup::{setup_postgres, SetupPostgresError},
-        sql_type_wrapper::{
-            map_ethereum_wrapper_to_json, map_log_params_to_ethereum_wrapper,
-            EthereumSqlTypeWrapper,
+        postgres::{
+            balance_change::BalanceChangeStatements,
+            client::{
+                removed_log_row, removed_logs_delete_sql, PostgresClient, PostgresConnectionError,
+                PostgresError, TextStatement,
+            },
+            generate::{
+                generate_column_names_only_with_base_properties,
+                generate_event_table_full_name_for_network, generate_raw_log_column_definitions,
+            },
+            heartbeat::retract_heartbeats_sql,
+            metrics::postgres_write_metrics,
+            projection::ProjectionStatements,
+            rollup::RollupStatement,
+            setup::{setup_postgres, SetupPostgresError},
+            sql_type_wrapper::{
+                map_ethereum_wrapper_to_json, map_log_params_to_ethereum_wrapper,
+                EthereumSqlTypeWrapper,
+            },
+            topic_preimages::TopicPreimages,
         },
     },
     event::{
         callback_registry::{
             noop_decoder, EventCallbackRegistry, EventCallbackRegistryInformation,
-            EventCallbackType, TxInformation,
+            EventCallbackType, EventResult, TxInformation,
         },
         contract_setup::{ContractInformation, CreateContractInformationError},
-        EventMessage,
+        EventMessage, WhereExpression, WhereExpressionError,
     },
     generate_random_id,
-    helpers::get_full_path,
-    indexer::log_helpers::{map_log_params_to_raw_values, parse_log},
+    helpers::format_address,
+    indexer::{
+        block_timestamps::BlockTimestamps,
+        log_helpers::{log_param_text, map_log_params_to_raw_values, parse_log},
+        price_enrichment::PriceEnricher,
+        start_block_anomaly_tracking, start_gas_price_sampling,
+        transform::EventTransforms,
+        user_operation::UserOperationDecoder,
+        wallet_activity::{start_wallet_activity_indexing, WalletActivityError},
+        BlockAnomalyError, GasPriceError,
+    },
     manifest::{
         core::Manifest,
+        price_enrichment::price_enrichments_for_event,
+        storage::PostgresLayout,
+        template::template_columns_for_event,
+        transform::transforms_for_event,
         yaml::{read_manifest, ReadManifestError},
     },
     provider::{CreateNetworkProvider, RetryClientError},
+    quarantine::quarantine_undecodable,
     setup_info_logger,
-    streams::StreamsClients,
-    AsyncCsvAppender, FutureExt, IndexingDetails, StartDetails, StartNoCodeDetails,
+    streams::{contract_streams_clients, StreamsClients},
+    FutureExt, IndexingDetails, StartDetails, StartNoCodeDetails,
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -58,8 +94,45 @@ pub enum SetupNoCodeError {
     #[error("Could not process indexers: {0}")]
     ProcessIndexersError(#[from] ProcessIndexersError),
 
+    #[error("Could not start event push server: {0}")]
+    StartEventPushServerError(#[from] StartEventPushServerError),
+
+    #[error("Could not start wallet activity indexing: {0}")]
+    WalletActivityError(#[from] WalletActivityError),
+
+    #[error("Could not start gas price sampling: {0}")]
+    GasPriceError(#[from] GasPriceError),
+
+    #[error("Could not start block anomaly tracking: {0}")]
+    BlockAnomalyError(#[from] BlockAnomalyError),
+
     #[error("You have graphql disabled as well as indexer so nothing can startup")]
     NothingToStartNoCode,
+
+    #[error("Could not connect to postgres: {0}")]
+    PostgresConnectionError(#[from] PostgresConnectionError),
+}
+
+/// The no-code handlers without starting anything, used to replay quarantined events
+pub async fn no_code_registry(
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<EventCallbackRegistry, SetupNoCodeError> {
+    let mut manifest = manifest.clone();
+    let postgres = if manifest.storage.postgres_enabled() {
+        Some(Arc::new(PostgresClient::from_storage(&manifest.storage, &manifest.name).await?))
+    } else {
+        None
+    };
+
+    let network_providers = CreateNetworkProvider::create(&manifest)?;
+    let events =
+        process_events(project_path, &mut manifest, postgres, None, &network_providers).await?;
+
+    let mut registry = EventCallbackRegistry { events, ..EventCallbackRegistry::new() };
+    registry.apply_event_networks(&manifest.contracts);
+    registry.apply_handler_limits(&manifest.contracts, &manifest.runtime_settings());
+    Ok(registry)
 }
 
 pub async fn setup_no_code(
@@ -78,7 +151,14 @@ pub async fn setup_no_code(
 
             let mut postgres: Option<Arc<PostgresClient>> = None;
             if manifest.storage.postgres_enabled() {
-                postgres = Some(Arc::new(setup_postgres(project_path, &manifest).await?));
+                postgres = Some(Arc::new(
+                    setup_postgres(
+                        project_path,
+                        &manifest,
+                        details.indexing_details.apply_migrations,
+                    )
+                    .await?,
+                ));
             }
 
             if !details.indexing_details.enabled {
@@ -99,10 +179,53 @@ pub async fn setup_no_code(
                     .join(", ")
             );
 
+            let mut push_hub: Option<Arc<EventPushHub>> = None;
+            if let Some(push) = &manifest.push {
+                let hub = Arc::new(EventPushHub::new(push.buffer));
+                start_event_push_server(
+                    project_path,
+                    &manifest,
+                    push,
+                    postgres.clone(),
+                    Arc::clone(&hub),
+                )
+                .await?;
+                push_hub = Some(hub);
+            }
+
+            if let (Some(wallets), Some(postgres)) = (&manifest.wallets, &postgres) {
+                start_wallet_activity_indexing(
+                    project_path,
+                    &manifest,
+                    wallets,
+                    Arc::clone(postgres),
+                    &network_providers,
+                )?;
+            }
+
+            if let (Some(gas_prices), Some(postgres)) = (&manifest.gas_prices, &postgres) {
+                start_gas_price_sampling(
+                    gas_prices,
+                    Arc::clone(postgres),
+                    manifest.storage.postgres_insert_strategy(),
+                    &network_providers,
+                )?;
+            }
+
+            if let (Some(block_anomalies), Some(postgres)) = (&manifest.block_anomalies, &postgres)
+            {
+                start_block_anomaly_tracking(
+                    block_anomalies,
+                    Arc::clone(postgres),
+                    &network_providers,
+                )?;
+            }
+
             let events =
-                process_events(project_path, &mut manifest, postgres, &network_providers).await?;
+                process_events(project_path, &mut manifest, postgres, push_hub, &network_providers)
+                    .await?;
 
-            let registry = EventCallbackRegistry { events };
+            let registry = EventCallbackRegistry { events, ..EventCallbackRegistry::new() };
             info!(
                 "Events registered to index:{}",
                 registry
@@ -115,7 +238,10 @@ pub async fn setup_no_code(
 
             Ok(StartDetails {
                 manifest_path: details.manifest_path,
-                indexing_details: Some(IndexingDetails { registry }),
+                indexing_details: Some(IndexingDetails {
+                    registry,
+                    apply_migrations: details.indexing_details.apply_migrations,
+                }),
                 graphql_details: details.graphql_details,
             })
         }
@@ -130,12 +256,208 @@ struct NoCodeCallbackParams {
     contract_name: String,
     event: Event,
     index_event_in_order: bool,
-    csv: Option<Arc<AsyncCsvAppender>>,
+    csv: Option<Arc<dyn StorageBackend>>,
     postgres: Option<Arc<PostgresClient>>,
-    postgres_event_table_name: String,
+    postgres_backend: Option<Arc<PostgresStorageBackend>>,
+    postgres_layout: PostgresLayout,
     postgres_column_names: Vec<String>,
-    streams_clients: Arc<Option<StreamsClients>>,
+    streams_clients: Option<Arc<StreamsClients>>,
     chat_clients: Arc<Option<ChatClients>>,
+    condition: Option<WhereExpression>,
+    transforms: EventTransforms,
+    price_enrichers: Vec<Arc<PriceEnricher>>,
+    user_operation_decoder: Option<Arc<UserOperationDecoder>>,
+    ignore_topic_ids: Vec<H256>,
+    jsonb_inputs: Vec<String>,
+    compact_bytea: bool,
+    store_raw_logs: bool,
+    projections: Vec<ProjectionStatements>,
+    rollups: Vec<RollupStatement>,
+    balance_changes: Vec<BalanceChangeStatements>,
+    topic_preimages: Option<TopicPreimages>,
+    block_timestamps: Arc<BlockTimestamps>,
+    push_hub: Option<Arc<EventPushHub>>,
+    storage_backends: Vec<Arc<dyn StorageBackend>>,
+    heartbeats: bool,
+}
+
+/// Deletes the rows of logs flagged `removed` by a reorg and takes them back out of the balance
+/// changes and the sums and counts of rollups, projections with a history and heartbeats go back
+/// to before the replaced block. The logs are streamed again with `removed` set so consumers can
+/// undo them
+async fn retract_removed_logs(
+    params: &NoCodeCallbackParams,
+    removed: &[EventResult],
+    network: &str,
+    from_block: U64,
+    to_block: U64,
+) -> Result<(), String> {
+    warn!(
+        "{}::{} - {} logs were removed by a reorg - blocks: {} - {} - network: {}",
+        params.contract_name,
+        params.event_info.name,
+        removed.len(),
+        from_block,
+        to_block,
+        network
+    );
+
+    if let Some(postgres) = &params.postgres {
+        let table_name = generate_event_table_full_name_for_network(
+            params.postgres_layout,
+            &params.indexer_name,
+            &params.contract_name,
+            &params.event_info.name,
+            network,
+        );
+        let fork_block = removed
+            .iter()
+            .map(|result| result.tx_information.block_number)
+            .min()
+            .unwrap_or(from_block);
+        let retract = async {
+            let transaction = postgres.transaction().await?;
+            let mut deleted = 0;
+            for result in removed {
+                let row = removed_log_row(&result.tx_information, params.compact_bytea);
+                // only a log which made it into the table made it into the aggregates
+                if transaction
+                    .execute_for_rows(&removed_logs_delete_sql(&table_name), &[row])
+                    .await? ==
+                    0
+                {
+                    continue;
+                }
+                deleted += 1;
+
+                let mut statements: Vec<TextStatement> = params
+                    .balance_changes
+                    .iter()
+                    .map(|balance_change| balance_change.for_removed_event(&result.tx_information))
+                    .collect();
+                if let Some(log) = parse_log(&params.event, &result.log) {
+                    for rollup in &params.rollups {
+                        let block_timestamp = params
+                            .block_timestamps
+                            .timestamp(network, result.tx_information.block_number)
+                            .await
+                            .map_err(|e| RetractError::BlockTimestamp(e.to_string()))?;
+                        statements.extend(rollup.for_removed_event(
+                            &params.event_info.inputs,
+                            &log.params,
+                            &result.tx_information,
+                            block_timestamp,
+                        ));
+                    }
+                }
+                transaction.execute_text_statements(&statements).await?;
+            }
+
+            if deleted > 0 {
+                for projection in &params.projections {
+                    match projection.for_reorg(network, fork_block) {
+                        Some(statements) => {
+                            transaction.execute_text_statements(&statements).await?;
+                        }
+                        None => warn!(
+                            "{}::{} - projection {} has no history so it keeps the changes of the removed logs",
+                            params.contract_name, params.event_info.name, projection.name
+                        ),
+                    }
+                }
+                for rollup in params.rollups.iter().filter(|rollup| rollup.irreversible) {
+                    warn!(
+                        "{}::{} - the min and max of rollup {} keep the values of the removed logs",
+                        params.contract_name, params.event_info.name, rollup.name
+                    );
+                }
+            }
+
+            if params.heartbeats {
+                let statements = retract_heartbeats_sql(&params.indexer_name).map(|sql| {
+                    (
+                        sql,
+                        vec![
+                            Some(params.contract_name.clone()),
+                            Some(params.event_info.name.clone()),
+                            Some(network.to_string()),
+                            Some(fork_block.to_string()),
+                        ],
+                    )
+                });
+                transaction.execute_text_statements(&statements).await?;
+            }
+
+            transaction.commit().await?;
+            Ok::<_, RetractError>(deleted)
+        };
+        let deleted = retract.await.map_err(|e| {
+            error!(
+                "{}::{} - Error retracting removed logs: {}",
+                params.contract_name, params.event_info.name, e
+            );
+            e.to_string()
+        })?;
+        info!(
+            "{}::{} - Deleted {} rows of removed logs from {}",
+            params.contract_name, params.event_info.name, deleted, table_name
+        );
+    }
+
+    if let Some(streams_clients) = params.streams_clients.as_ref() {
+        let event_data = removed
+            .iter()
+            .filter_map(|result| {
+                let log = parse_log(&params.event, &result.log)?;
+                let event_parameters = map_log_params_to_ethereum_wrapper(
+                    &params.event_info.inputs,
+                    &log.params,
+                    &params.jsonb_inputs,
+                )
+                .ok()?;
+                Some(map_ethereum_wrapper_to_json(
+                    &params.event_info.inputs,
+                    &event_parameters,
+                    &result.tx_information,
+                    false,
+                ))
+            })
+            .collect();
+        let event_message = EventMessage {
+            event_name: params.event_info.name.clone(),
+            event_data: Value::Array(event_data),
+            network: network.to_string(),
+            removed: true,
+        };
+        let stream_id =
+            format!("{}-{}-{}-removed", params.contract_name, params.event_info.name, network);
+        streams_clients
+            .stream(stream_id, &event_message, params.index_event_in_order)
+            .await
+            .map_err(|e| {
+                error!("Error streaming removed events: {}", e);
+                e.to_string()
+            })?;
+    }
+
+    Ok(())
+}
+
+#[derive(thiserror::Error, Debug)]
+enum RetractError {
+    #[error("{0}")]
+    Postgres(#[from] PostgresError),
+
+    #[error("Could not get the block timestamp for rollups: {0}")]
+    BlockTimestamp(String),
+}
+
+/// A log of the batch which decoded and matched the condition
+struct DecodedLog {
+    log_params: Vec<LogParam>,
+    tx_information: TxInformation,
+    event_parameters: Vec<EthereumSqlTypeWrapper>,
+    raw_log_parameters: Vec<EthereumSqlTypeWrapper>,
 }
 
 fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
@@ -166,105 +488,285 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
 
             let network = results.first().unwrap().tx_information.network.clone();
 
+            let (removed, results): (Vec<_>, Vec<_>) =
+                results.into_iter().partition(|result| result.log.removed == Some(true));
+            if !removed.is_empty() {
+                retract_removed_logs(&params, &removed, &network, from_block, to_block).await?;
+                if results.is_empty() {
+                    return Ok(());
+                }
+            }
+
             let mut indexed_count = 0;
             let mut postgres_bulk_data: Vec<Vec<EthereumSqlTypeWrapper>> = Vec::new();
-            let mut postgres_bulk_column_types: Vec<PgType> = Vec::new();
             let mut csv_bulk_data: Vec<Vec<String>> = Vec::new();
 
             // stream and chat info
             let mut event_message_data: Vec<Value> = Vec::new();
+            // one list per row so only the statements of rows which get inserted run
+            let mut projection_statements: Vec<Vec<TextStatement>> = Vec::new();
+            let mut pushed_events: Vec<PushedEvent> = Vec::new();
 
+            let mut undecodable = vec![];
             // Collect owned results to avoid lifetime issues
             let owned_results: Vec<_> = results
                 .iter()
                 .filter_map(|result| {
+                    if result
+                        .log
+                        .topics
+                        .first()
+                        .is_some_and(|topic| params.ignore_topic_ids.contains(topic))
+                    {
+                        return None;
+                    }
+
                     let log = parse_log(&params.event, &result.log)?;
+                    if let Some(condition) = &params.condition {
+                        let input_value = |path: &[String]| {
+                            log_param_text(&params.event_info.inputs, &log.params, path)
+                        };
+                        if !condition.matches(&input_value) {
+                            return None;
+                        }
+                    }
 
-                    let address = result.tx_information.address;
-                    let transaction_hash = result.tx_information.transaction_hash;
-                    let block_number = result.tx_information.block_number;
-                    let block_hash = result.tx_information.block_hash;
-                    let network = result.tx_information.network.to_string();
-                    let transaction_index = result.tx_information.transaction_index;
-                    let log_index = result.tx_information.log_index;
-
-                    let event_parameters: Vec<EthereumSqlTypeWrapper> =
-                        map_log_params_to_ethereum_wrapper(&params.event_info.inputs, &log.params);
-
-                    let contract_address = EthereumSqlTypeWrapper::Address(address);
-                    let end_global_parameters = vec![
-                        EthereumSqlTypeWrapper::H256(transaction_hash),
-                        EthereumSqlTypeWrapper::U64(block_number),
-                        EthereumSqlTypeWrapper::H256(block_hash),
-                        EthereumSqlTypeWrapper::String(network.to_string()),
-                        EthereumSqlTypeWrapper::U64(transaction_index),
-                        EthereumSqlTypeWrapper::U256(log_index),
-                    ];
-
-                    Some((
-                        log.params,
-                        address,
-                        transaction_hash,
-                        log_index,
-                        transaction_index,
-                        block_number,
-                        block_hash,
-                        network,
-                        contract_address,
+                    let mut event_parameters: Vec<EthereumSqlTypeWrapper> =
+                        match map_log_params_to_ethereum_wrapper(
+                            &params.event_info.inputs,
+                            &log.params,
+                            &params.jsonb_inputs,
+                        ) {
+                            Ok(event_parameters) => event_parameters,
+                            Err(e) => {
+                                undecodable.push((result, e));
+                                return None;
+                            }
+                        };
+                    params.transforms.apply(&mut event_parameters);
+
+                    let raw_log_parameters = if params.store_raw_logs {
+                        vec![
+                            EthereumSqlTypeWrapper::VecH256(result.log.topics.clone()),
+                            EthereumSqlTypeWrapper::Bytes(result.log.data.clone()),
+                        ]
+                    } else {
+                        vec![]
+                    };
+
+                    Some(DecodedLog {
+                        log_params: log.params,
+                        tx_information: TxInformation {
+                            removed: false,
+                            ..result.tx_information.clone()
+                        },
                         event_parameters,
-                        end_global_parameters,
-                    ))
+                        raw_log_parameters,
+                    })
                 })
                 .collect();
 
-            for (
-                log_params,
-                address,
-                transaction_hash,
-                log_index,
-                transaction_index,
-                block_number,
-                block_hash,
-                network,
-                contract_address,
-                event_parameters,
-                end_global_parameters,
-            ) in owned_results
+            if !undecodable.is_empty() {
+                for (result, e) in &undecodable {
+                    error!(
+                        "{}::{} - Skipping log {:?} index {} which does not fit the ABI: {}",
+                        params.contract_name,
+                        params.event_info.name,
+                        result.tx_information.transaction_hash,
+                        result.tx_information.log_index,
+                        e
+                    );
+                }
+                postgres_write_metrics().observe_undecodable(undecodable.len());
+                // skipped logs are kept to be replayed where there is postgres to keep them in
+                match &params.postgres {
+                    Some(postgres) => {
+                        if let Err(e) = quarantine_undecodable(
+                            postgres,
+                            &params.indexer_name,
+                            &params.contract_name,
+                            &params.event_info.name,
+                            &undecodable,
+                        )
+                        .await
+                        {
+                            error!(
+                                "{}::{} - Could not quarantine undecodable logs: {}",
+                                params.contract_name, params.event_info.name, e
+                            );
+                            return Err(e);
+                        }
+                    }
+                    None => warn!(
+                        "{}::{} - {} logs which do not fit the ABI are not kept, they can only be quarantined with postgres storage",
+                        params.contract_name,
+                        params.event_info.name,
+                        undecodable.len()
+                    ),
+                }
+            }
+
+            if params.postgres.is_some() {
+                let user_operations = async {
+                    if let Some(decoder) = &params.user_operation_decoder {
+                        decoder
+                            .prefetch(
+                                owned_results.iter().map(|result| {
+                                    (
+                                        result.tx_information.network.clone(),
+                                        result.tx_information.transaction_hash,
+                                    )
+                                }),
+                            )
+                            .await;
+                    }
+                };
+                let prices = join_all(params.price_enrichers.iter().map(|price_enricher| {
+                    price_enricher.prefetch(
+                        owned_results.iter().map(|result| {
+                            (
+                                result.tx_information.network.clone(),
+                                result.tx_information.address,
+                                result.tx_information.block_number,
+                            )
+                        }),
+                    )
+                }));
+                tokio::join!(user_operations, prices);
+            }
+
+            for DecodedLog { log_params, tx_information, event_parameters, raw_log_parameters } in
+                owned_results
             {
-                if params.streams_clients.is_some() || params.chat_clients.is_some() {
+                let network = tx_information.network.clone();
+                let address = tx_information.address;
+                let transaction_hash = tx_information.transaction_hash;
+                let block_number = tx_information.block_number;
+                let block_hash = tx_information.block_hash;
+                let log_index = tx_information.log_index;
+
+                if params.streams_clients.is_some() ||
+                    params.chat_clients.is_some() ||
+                    params.push_hub.is_some()
+                {
                     let event_result = map_ethereum_wrapper_to_json(
                         &params.event_info.inputs,
                         &event_parameters,
-                        &TxInformation {
+                        &tx_information,
+                        false,
+                    );
+                    if params.push_hub.is_some() {
+                        pushed_events.push(PushedEvent {
+                            contract_name: params.contract_name.clone(),
+                            event_name: params.event_info.name.clone(),
                             network: network.clone(),
-                            address,
-                            block_hash,
                             block_number,
-                            transaction_hash,
                             log_index,
-                            transaction_index,
-                        },
-                        false,
-                    );
+                            data: event_result.clone(),
+                        });
+                    }
                     event_message_data.push(event_result);
                 }
 
-                let mut all_params: Vec<EthereumSqlTypeWrapper> = vec![contract_address];
+                let mut row_statements = vec![];
+                for projection in &params.projections {
+                    row_statements.extend(projection.for_event(
+                        &params.event_info.inputs,
+                        &log_params,
+                        &tx_information,
+                    ));
+                }
+
+                for balance_change in &params.balance_changes {
+                    row_statements.extend(balance_change.for_event(
+                        &params.event_info.inputs,
+                        &log_params,
+                        &tx_information,
+                    ));
+                }
+
+                if let Some(topic_preimages) = &params.topic_preimages {
+                    row_statements.extend(topic_preimages.for_event(&log_params));
+                }
+
+                if !params.rollups.is_empty() {
+                    let block_timestamp =
+                        params.block_timestamps.timestamp(&network, block_number).await.map_err(
+                            |e| {
+                                error!(
+                                    "{}::{} - Error getting block timestamp for rollups: {}",
+                                    params.contract_name, params.event_info.name, e
+                                );
+                                e.to_string()
+                            },
+                        )?;
+                    for rollup in &params.rollups {
+                        row_statements.push(rollup.for_event(
+                            &params.event_info.inputs,
+                            &log_params,
+                            &tx_information,
+                            block_timestamp,
+                        ));
+                    }
+                }
+
+                let text_rows_needed = params.csv.is_some() || !params.storage_backends.is_empty();
+                let mut raw_values = if text_rows_needed {
+                    map_log_params_to_raw_values(&log_params)
+                } else {
+                    vec![]
+                };
+                if !params.transforms.is_empty() {
+                    params.transforms.sync_raw_values(&event_parameters, &mut raw_values);
+                }
+
+                let mut all_params: Vec<EthereumSqlTypeWrapper> =
+                    vec![EthereumSqlTypeWrapper::Address(address)];
                 all_params.extend(event_parameters);
-                all_params.extend(end_global_parameters);
+                all_params.extend([
+                    EthereumSqlTypeWrapper::H256(transaction_hash),
+                    EthereumSqlTypeWrapper::U64(block_number),
+                    EthereumSqlTypeWrapper::H256(block_hash),
+                    EthereumSqlTypeWrapper::String(network.clone()),
+                    EthereumSqlTypeWrapper::U64(tx_information.transaction_index),
+                    EthereumSqlTypeWrapper::U256(log_index),
+                ]);
+
+                if params.postgres.is_some() {
+                    for price_enricher in &params.price_enrichers {
+                        all_params
+                            .push(price_enricher.enrich(&network, address, block_number).await);
+                    }
+                    if let Some(decoder) = &params.user_operation_decoder {
+                        let param = |name: &str| {
+                            log_params.iter().find(|p| p.name == name).map(|p| p.value.clone())
+                        };
+                        all_params.extend(
+                            decoder
+                                .decode(
+                                    &network,
+                                    transaction_hash,
+                                    param("sender").and_then(|value| value.into_address()),
+                                    param("nonce").and_then(|value| value.into_uint()),
+                                )
+                                .await,
+                        );
+                    }
+                }
+                all_params.extend(raw_log_parameters);
 
-                // Set column types dynamically based on first result
-                if postgres_bulk_column_types.is_empty() {
-                    postgres_bulk_column_types =
-                        all_params.iter().map(|param| param.to_type()).collect();
+                if params.compact_bytea {
+                    all_params = all_params
+                        .into_iter()
+                        .map(EthereumSqlTypeWrapper::into_compact_bytea)
+                        .collect();
                 }
 
                 postgres_bulk_data.push(all_params);
+                projection_statements.push(row_statements);
 
-                if params.csv.is_some() {
-                    let mut csv_data: Vec<String> = vec![format!("{:?}", address)];
-
-                    let raw_values = map_log_params_to_raw_values(&log_params);
+                if text_rows_needed {
+                    let mut csv_data: Vec<String> = vec![format_address(&address)];
 
                     for param in raw_values {
                         csv_data.push(param);
@@ -281,48 +783,49 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
                 indexed_count += 1;
             }
 
-            if let Some(postgres) = &params.postgres {
-                let bulk_data_length = postgres_bulk_data.len();
-                if bulk_data_length > 0 {
-                    // anything over 100 events is considered bulk and goes the COPY route
-                    if bulk_data_length > 100 {
-                        if let Err(e) = postgres
-                            .bulk_insert_via_copy(
-                                &params.postgres_event_table_name,
-                                &params.postgres_column_names,
-                                &postgres_bulk_column_types,
-                                &postgres_bulk_data,
-                            )
-                            .await
-                        {
-                            error!(
-                                "{}::{} - Error performing bulk insert: {}",
-                                params.contract_name, params.event_info.name, e
-                            );
-                            return Err(e.to_string());
-                        }
-                    } else if let Err(e) = postgres
-                        .bulk_insert(
-                            &params.postgres_event_table_name,
-                            &params.postgres_column_names,
-                            &postgres_bulk_data,
-                        )
-                        .await
-                    {
-                        error!(
-                            "{}::{} - Error performing bulk insert: {}",
-                            params.contract_name, params.event_info.name, e
-                        );
-                        return Err(e.to_string());
+            let batch = StorageBatch {
+                key: CheckpointKey {
+                    indexer_name: &params.indexer_name,
+                    contract_name: &params.contract_name,
+                    event_name: &params.event_info.name,
+                    network: &network,
+                },
+                column_names: &params.postgres_column_names,
+                rows: &postgres_bulk_data,
+                text_rows: &csv_bulk_data,
+            };
+
+            if let Some(postgres_backend) = &params.postgres_backend {
+                if let Err(e) =
+                    postgres_backend.write_batch_with_statements(&batch, &projection_statements).await
+                {
+                    error!(
+                        "{}::{} - Error performing bulk insert: {}",
+                        params.contract_name, params.event_info.name, e
+                    );
+                    if e.is_retries_exhausted() {
+                        // the handler retries the whole batch, the checkpoint does not move past it
+                        error!("Postgres writes keep failing - retrying the batch until postgres is back");
                     }
+                    return Err(e.to_string());
                 }
             }
 
             if let Some(csv) = &params.csv {
-                if !csv_bulk_data.is_empty() {
-                    if let Err(e) = csv.append_bulk(csv_bulk_data).await {
-                        return Err(e.to_string());
-                    }
+                if let Err(e) = csv.write_batch(&batch).await {
+                    return Err(e.to_string());
+                }
+            }
+
+            if !params.storage_backends.is_empty() {
+                if let Err(e) = write_registered_storage_backends(&batch).await {
+                    return Err(e.to_string());
+                }
+            }
+
+            if let Some(push_hub) = &params.push_hub {
+                for pushed_event in pushed_events {
+                    push_hub.publish(pushed_event);
                 }
             }
 
@@ -330,18 +833,22 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
                 event_name: params.event_info.name.clone(),
                 event_data: Value::Array(event_message_data),
                 network: network.clone(),
+                removed: false,
             };
 
             if let Some(streams_clients) = params.streams_clients.as_ref() {
-                let stream_id = format!(
-                    "{}-{}-{}-{}-{}",
-                    params.contract_name, params.event_info.name, network, from_block, to_block
-                );
+                let stream_id =
+                    format!("{}-{}-{}", params.contract_name, params.event_info.name, network);
 
-                match streams_clients
+                // the checkpoint only moves on once queued messages, like kafka's, are delivered
+                let streamed = match streams_clients
                     .stream(stream_id, &event_message, params.index_event_in_order)
                     .await
                 {
+                    Ok(streamed) => streams_clients.flush().await.map(|_| streamed),
+                    Err(e) => Err(e),
+                };
+                match streamed {
                     Ok(streamed) => {
                         if streamed > 0 {
                             info!(
@@ -435,35 +942,42 @@ pub enum ProcessIndexersError {
     #[error("Could not read ABI items: {0}")]
     CouldNotReadAbiItems(#[from] ReadAbiError),
 
-    #[error("Could not append headers to csv: {0}")]
-    CsvHeadersAppendError(#[from] csv::Error),
+    #[error("Could not create the csv file: {0}")]
+    CreateCsvFileError(#[from] StorageBackendError),
 
     #[error("{0}")]
     CreateContractInformationError(#[from] CreateContractInformationError),
 
-    #[error("{0}")]
-    CreateCsvFileForEventError(#[from] CreateCsvFileForEvent),
-
     #[error("{0}")]
     ParamTypeError(#[from] ParamTypeError),
 
     #[error("Event name not found in ABI for contract: {0} - event: {1}")]
     EventNameNotFoundInAbi(String, String),
+
+    #[error("Invalid event condition: {0}")]
+    InvalidEventCondition(#[from] WhereExpressionError),
+
+    #[error("Could not open the stream spill directory: {0}")]
+    CouldNotOpenStreamSpill(io::Error),
 }
 
 pub async fn process_events(
     project_path: &Path,
     manifest: &mut Manifest,
     postgres: Option<Arc<PostgresClient>>,
+    push_hub: Option<Arc<EventPushHub>>,
     network_providers: &[CreateNetworkProvider],
 ) -> Result<Vec<EventCallbackRegistryInformation>, ProcessIndexersError> {
     let mut events: Vec<EventCallbackRegistryInformation> = vec![];
+    let block_timestamps = Arc::new(BlockTimestamps::new(network_providers));
+    let user_operation_decoder = Arc::new(UserOperationDecoder::new(network_providers));
+    let streams_concurrency = manifest.runtime_settings().streams_concurrency();
 
     for contract in &mut manifest.contracts {
         // TODO - this could be shared with `get_abi_items`
-        let full_path = get_full_path(project_path, &contract.abi)
+        let abi_str = contract
+            .read_abi(project_path)
             .map_err(|_| ProcessIndexersError::AbiPathDoesNotExist(contract.abi.clone()))?;
-        let abi_str = fs::read_to_string(full_path)?;
 
         let abi: Abi = serde_json::from_str(&abi_str)?;
 
@@ -474,20 +988,13 @@ pub async fn process_events(
         let abi_items = ABIItem::get_abi_items(project_path, contract, is_filter)?;
         let event_names = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
 
-        for event_info in event_names {
+        for mut event_info in event_names {
             let event_name = event_info.name.clone();
-            let event = &abi_gen
-                .events
-                .iter()
-                .find(|(name, _)| *name == &event_name)
-                .map(|(_, event)| event)
-                .ok_or_else(|| {
-                    ProcessIndexersError::EventNameNotFoundInAbi(
-                        contract.name.clone(),
-                        event_name.clone(),
-                    )
-                })?
-                .first()
+            // overloaded events share a name so match on the signature
+            let topic_id = event_info.topic_id();
+            let mut event = abi_gen
+                .events()
+                .find(|event| event.signature() == topic_id)
                 .ok_or_else(|| {
                     ProcessIndexersError::EventNameNotFoundInAbi(
                         contract.name.clone(),
@@ -496,34 +1003,98 @@ pub async fn process_events(
                 })?
                 .clone();
 
+            // anonymous events get every log for the contract address so drop any log whose
+            // topic0 belongs to another event in the ABI
+            let mut ignore_topic_ids = vec![];
+            if let Some(layout) = contract.anonymous_event_layout(&event_info.name) {
+                event.anonymous = true;
+                for input in event.inputs.iter_mut() {
+                    input.indexed = layout.topics.contains(&input.name);
+                }
+                for input in event_info.inputs.iter_mut() {
+                    input.indexed = Some(layout.topics.contains(&input.name));
+                }
+                ignore_topic_ids = abi_gen
+                    .events()
+                    .filter(|e| !e.anonymous && e.signature() != topic_id)
+                    .map(|e| e.signature())
+                    .collect();
+            }
+
             let contract_information =
                 ContractInformation::create(contract, network_providers, noop_decoder())?;
 
-            let mut csv: Option<Arc<AsyncCsvAppender>> = None;
+            #[cfg_attr(not(feature = "csv"), allow(unused_mut))]
+            let mut csv: Option<Arc<dyn StorageBackend>> = None;
+            #[cfg(feature = "csv")]
             if contract.generate_csv.unwrap_or(true) && manifest.storage.csv_enabled() {
                 let csv_path = manifest.storage.csv.as_ref().map_or("./generated_csv", |c| &c.path);
-                let headers: Vec<String> = event_info.csv_headers_for_event();
-
-                let csv_path =
-                    event_info.create_csv_file_for_event(project_path, contract, csv_path)?;
-                let csv_appender = AsyncCsvAppender::new(&csv_path);
-                if !Path::new(&csv_path).exists() {
-                    csv_appender.append_header(headers).await?;
-                }
+                let csv_backend =
+                    crate::database::backend::CsvStorageBackend::new(project_path.join(csv_path));
+                csv_backend
+                    .create_event_file(
+                        &contract.name,
+                        &event_info.name,
+                        event_info.csv_headers_for_event(),
+                    )
+                    .await?;
 
-                csv = Some(Arc::new(csv_appender));
+                csv = Some(Arc::new(csv_backend));
             }
 
-            let postgres_column_names =
-                generate_column_names_only_with_base_properties(&event_info.inputs);
-            let postgres_event_table_name =
-                generate_event_table_full_name(&manifest.name, &contract.name, &event_info.name);
+            let condition = contract
+                .event_condition(&event_info.name)
+                .map(WhereExpression::parse)
+                .transpose()?;
+            let jsonb_inputs = contract.jsonb_inputs(&event_info.name, &event_info.inputs);
+            let transforms = EventTransforms::new(
+                &event_info.inputs,
+                transforms_for_event(&contract.transforms, &event_info.name),
+                &jsonb_inputs,
+            );
+
+            let price_enrichers: Vec<Arc<PriceEnricher>> =
+                price_enrichments_for_event(&contract.price_enrichment, &event_info.name)
+                    .into_iter()
+                    .map(|enrichment| {
+                        Arc::new(PriceEnricher::new(enrichment.clone(), network_providers))
+                    })
+                    .collect();
+
+            let mut postgres_column_names =
+                generate_column_names_only_with_base_properties(&event_info.inputs, &jsonb_inputs);
+            postgres_column_names
+                .extend(price_enrichers.iter().map(|enricher| enricher.column_name().to_string()));
+
+            let template_columns = template_columns_for_event(&contract.template, &event_info.name);
+            postgres_column_names.extend(template_columns.iter().map(|column| column.to_string()));
+            let user_operation_decoder =
+                (!template_columns.is_empty()).then(|| Arc::clone(&user_operation_decoder));
+            postgres_column_names.extend(
+                generate_raw_log_column_definitions(contract).into_iter().map(|(name, _)| name),
+            );
 
             let streams_client = if let Some(streams) = &contract.streams {
-                Some(StreamsClients::new(streams.clone()).await)
+                let streams_clients = StreamsClients::new(
+                    streams.clone(),
+                    &event_info.name,
+                    &event_info.inputs,
+                    streams_concurrency,
+                )
+                .await;
+                Some(match &streams.spill {
+                    Some(spill) => streams_clients
+                        .with_spill(project_path, spill, &contract.name, &event_info.name)
+                        .map_err(ProcessIndexersError::CouldNotOpenStreamSpill)?,
+                    None => streams_clients,
+                })
             } else {
-                None
-            };
+                StreamsClients::registered()
+            }
+            .map(Arc::new);
+            if let Some(streams_clients) = &streams_client {
+                contract_streams_clients().register(&contract.name, streams_clients);
+            }
 
             let chat_clients = if let Some(chats) = &contract.chat {
                 Some(ChatClients::new(chats.clone()).await)
@@ -531,6 +1102,49 @@ pub async fn process_events(
                 None
             };
 
+            let contract_name = contract.before_modify_name_if_filter_readonly().to_string();
+            let projections: Vec<ProjectionStatements> = manifest
+                .storage
+                .postgres_projections()
+                .iter()
+                .flat_map(|projection| {
+                    projection.upserts_for_event(&contract_name, &event_info.name).map(|upsert| {
+                        ProjectionStatements::new(
+                            &manifest.name,
+                            projection,
+                            upsert,
+                            &event_info.inputs,
+                        )
+                    })
+                })
+                .collect();
+            let rollups: Vec<RollupStatement> = manifest
+                .storage
+                .postgres_rollups()
+                .iter()
+                .filter(|rollup| {
+                    rollup.contract_name == contract_name && rollup.event_name == event_info.name
+                })
+                .map(|rollup| RollupStatement::new(&manifest.name, rollup, &event_info.inputs))
+                .collect();
+            let balance_changes: Vec<BalanceChangeStatements> = manifest
+                .storage
+                .postgres_balance_changes()
+                .iter()
+                .filter(|source| {
+                    source.contract_name == contract_name && source.event_name == event_info.name
+                })
+                .map(|source| {
+                    BalanceChangeStatements::new(&manifest.name, source, &event_info.inputs)
+                })
+                .collect();
+
+            let topic_preimages = manifest
+                .storage
+                .postgres_hashed_topic_preimages()
+                .then(|| TopicPreimages::new(&manifest.name, &event_info.inputs))
+                .flatten();
+
             let index_event_in_order = contract
                 .index_event_in_order
                 .as_ref()
@@ -541,20 +1155,39 @@ pub async fn process_events(
                 indexer_name: manifest.name.clone(),
                 event_name: event_info.name.clone(),
                 index_event_in_order,
-                topic_id: event_info.topic_id(),
+                topic_id,
                 contract: contract_information,
                 callback: no_code_callback(Arc::new(NoCodeCallbackParams {
                     event_info,
                     indexer_name: manifest.name.clone(),
                     contract_name: contract.name.clone(),
-                    event: event.clone(),
+                    event,
                     index_event_in_order,
                     csv,
                     postgres: postgres.clone(),
-                    postgres_event_table_name,
+                    postgres_backend: postgres.as_ref().map(|client| {
+                        Arc::new(PostgresStorageBackend::new(Arc::clone(client), &manifest.storage))
+                    }),
+                    postgres_layout: manifest.storage.postgres_layout(),
                     postgres_column_names,
-                    streams_clients: Arc::new(streams_client),
+                    streams_clients: streams_client,
                     chat_clients: Arc::new(chat_clients),
+                    condition,
+                    transforms,
+                    price_enrichers,
+                    user_operation_decoder,
+                    ignore_topic_ids,
+                    jsonb_inputs,
+                    compact_bytea: manifest.storage.postgres_compact_bytea(),
+                    store_raw_logs: contract.stores_raw_logs(),
+                    projections,
+                    rollups,
+                    balance_changes,
+                    topic_preimages,
+                    block_timestamps: Arc::clone(&block_timestamps),
+                    push_hub: push_hub.clone(),
+                    storage_backends: registered_storage_backends(),
+                    heartbeats: manifest.storage.postgres_heartbeats(),
                 })),
             };
 
@@ -0,0 +1,387 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ethers::{
+    prelude::ProviderError,
+    types::{
+        transaction::eip2718::TypedTransaction, Address, BlockId, BlockNumber, Bytes,
+        TransactionRequest, I256, U256, U64,
+    },
+    utils::{format_units, id},
+};
+use futures::{stream, StreamExt};
+use reqwest::Client;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::{debug, error};
+
+use crate::{
+    manifest::price_enrichment::{EventPriceEnrichment, PriceOracle},
+    multicall::{Multicall, MulticallCall},
+    provider::{CreateNetworkProvider, JsonRpcCachedProvider},
+    EthereumSqlTypeWrapper,
+};
+
+/// Once the cache gets this big it gets cleared, historic syncs move forward so old blocks are
+/// rarely looked up again
+const MAX_CACHED_PRICES: usize = 100_000;
+
+/// Price lookups of a batch of events in flight at once
+const PRICE_LOOKUP_CONCURRENCY: usize = 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PriceLookupError {
+    #[error("No provider found for network {0}")]
+    NoProviderForNetwork(String),
+
+    #[error("No chainlink feed configured for network {0}")]
+    NoFeedForNetwork(String),
+
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("Chainlink feed returned an invalid response: {0}")]
+    InvalidFeedResponse(String),
+
+    #[error("Request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    #[error("Price not found in response at {0}")]
+    PriceNotFoundInResponse(String),
+}
+
+type PriceCacheKey = (String, Address, u64);
+
+pub struct PriceEnricher {
+    enrichment: EventPriceEnrichment,
+    providers: HashMap<String, Arc<JsonRpcCachedProvider>>,
+    client: Client,
+    feed_decimals: Mutex<HashMap<String, u32>>,
+    cache: Mutex<HashMap<PriceCacheKey, String>>,
+}
+
+impl PriceEnricher {
+    pub fn new(
+        enrichment: EventPriceEnrichment,
+        network_providers: &[CreateNetworkProvider],
+    ) -> Self {
+        Self {
+            enrichment,
+            providers: network_providers
+                .iter()
+                .map(|provider| (provider.network_name.clone(), Arc::clone(&provider.client)))
+                .collect(),
+            client: Client::new(),
+            feed_decimals: Mutex::new(HashMap::new()),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn column_name(&self) -> &str {
+        &self.enrichment.column_name
+    }
+
+    fn provider(&self, network: &str) -> Result<&Arc<JsonRpcCachedProvider>, PriceLookupError> {
+        self.providers
+            .get(network)
+            .ok_or_else(|| PriceLookupError::NoProviderForNetwork(network.to_string()))
+    }
+
+    async fn call_feed(
+        &self,
+        network: &str,
+        feed_address: Address,
+        signature: &str,
+        block_number: Option<U64>,
+    ) -> Result<Bytes, PriceLookupError> {
+        let tx: TypedTransaction =
+            TransactionRequest::new().to(feed_address).data(id(signature).to_vec()).into();
+        let block = block_number.map(|block| BlockId::Number(BlockNumber::Number(block)));
+
+        Ok(self.provider(network)?.call(&tx, block).await?)
+    }
+
+    async fn chainlink_decimals(
+        &self,
+        network: &str,
+        feed_address: Address,
+    ) -> Result<u32, PriceLookupError> {
+        if let Some(decimals) = self.feed_decimals.lock().await.get(network) {
+            return Ok(*decimals);
+        }
+
+        let result = self.call_feed(network, feed_address, "decimals()", None).await?;
+        if result.len() < 32 {
+            return Err(PriceLookupError::InvalidFeedResponse(format!("decimals - {}", result)));
+        }
+        let decimals = U256::from_big_endian(&result[0..32]).as_u32();
+        self.feed_decimals.lock().await.insert(network.to_string(), decimals);
+
+        Ok(decimals)
+    }
+
+    /// Fetches the feed decimals and round data in one multicall
+    async fn chainlink_batched(
+        &self,
+        network: &str,
+        feed_address: Address,
+        block_number: U64,
+    ) -> Option<(u32, Bytes)> {
+        let multicall = Multicall::new(Arc::clone(self.provider(network).ok()?));
+        let calls = [
+            MulticallCall::from_signature(feed_address, "decimals()").allow_failure(false),
+            MulticallCall::from_signature(feed_address, "latestRoundData()").allow_failure(false),
+        ];
+        let block = BlockId::Number(BlockNumber::Number(block_number));
+        let mut results = match multicall.call(&calls, Some(block)).await {
+            Ok(results) => results.into_iter().flatten(),
+            Err(e) => {
+                debug!("Multicall lookup for {} failed, falling back: {}", network, e);
+                return None;
+            }
+        };
+
+        let decimals = results.next().filter(|result| result.len() >= 32)?;
+        let decimals = U256::from_big_endian(&decimals[0..32]).as_u32();
+        self.feed_decimals.lock().await.insert(network.to_string(), decimals);
+
+        Some((decimals, results.next()?))
+    }
+
+    async fn chainlink_price(
+        &self,
+        feeds: &HashMap<String, Address>,
+        network: &str,
+        block_number: U64,
+    ) -> Result<String, PriceLookupError> {
+        let feed_address = *feeds
+            .get(network)
+            .ok_or_else(|| PriceLookupError::NoFeedForNetwork(network.to_string()))?;
+
+        let cached_decimals = self.feed_decimals.lock().await.get(network).copied();
+        let (decimals, result) = match cached_decimals {
+            Some(decimals) => (
+                decimals,
+                self.call_feed(network, feed_address, "latestRoundData()", Some(block_number))
+                    .await?,
+            ),
+            None => match self.chainlink_batched(network, feed_address, block_number).await {
+                Some(batched) => batched,
+                // multicall3 may not be deployed yet at historic blocks
+                None => (
+                    self.chainlink_decimals(network, feed_address).await?,
+                    self.call_feed(network, feed_address, "latestRoundData()", Some(block_number))
+                        .await?,
+                ),
+            },
+        };
+        // latestRoundData returns (roundId, answer, startedAt, updatedAt, answeredInRound)
+        if result.len() < 64 {
+            return Err(PriceLookupError::InvalidFeedResponse(format!(
+                "latestRoundData - {}",
+                result
+            )));
+        }
+
+        let answer = I256::from_raw(U256::from_big_endian(&result[32..64]));
+        if answer.is_negative() {
+            return Err(PriceLookupError::InvalidFeedResponse(format!(
+                "negative answer {}",
+                answer
+            )));
+        }
+
+        format_units(answer.into_raw(), decimals)
+            .map_err(|e| PriceLookupError::InvalidFeedResponse(e.to_string()))
+    }
+
+    async fn http_price(
+        &self,
+        url: &str,
+        json_path: &Option<String>,
+        network: &str,
+        token_address: Address,
+        block_number: U64,
+    ) -> Result<String, PriceLookupError> {
+        let mut url = url
+            .replace("{network}", network)
+            .replace("{block_number}", &block_number.to_string())
+            .replace("{token_address}", &format!("{:?}", token_address));
+
+        if url.contains("{timestamp}") {
+            let block = self.provider(network)?.get_block_header(block_number).await?;
+            url = url.replace("{timestamp}", &block.timestamp.to_string());
+        }
+
+        let response: Value =
+            self.client.get(&url).send().await?.error_for_status()?.json().await?;
+
+        let json_path = json_path.as_deref().unwrap_or("price");
+        let price = json_path
+            .split('.')
+            .try_fold(&response, |value, key| value.get(key))
+            .and_then(|value| match value {
+                Value::Number(number) => Some(number.to_string()),
+                Value::String(value) => Some(value.clone()),
+                _ => None,
+            })
+            // only plain decimals can be written as NUMERIC
+            .filter(|value| {
+                !value.is_empty() && value.chars().all(|c| c.is_ascii_digit() || c == '.')
+            })
+            .ok_or_else(|| PriceLookupError::PriceNotFoundInResponse(json_path.to_string()))?;
+
+        Ok(price)
+    }
+
+    async fn lookup_price(
+        &self,
+        network: &str,
+        token_address: Address,
+        block_number: U64,
+    ) -> Result<String, PriceLookupError> {
+        match &self.enrichment.oracle {
+            PriceOracle::Chainlink { feeds } => {
+                self.chainlink_price(feeds, network, block_number).await
+            }
+            PriceOracle::Http { url, json_path } => {
+                self.http_price(url, json_path, network, token_address, block_number).await
+            }
+        }
+    }
+
+    fn cache_key(
+        &self,
+        network: &str,
+        contract_address: Address,
+        block_number: U64,
+    ) -> PriceCacheKey {
+        let token_address = self.enrichment.token_address.unwrap_or(contract_address);
+        let cache_block_range = self.enrichment.cache_block_range.unwrap_or(1).max(1);
+        (network.to_string(), token_address, block_number.as_u64() / cache_block_range)
+    }
+
+    async fn price(
+        &self,
+        network: &str,
+        contract_address: Address,
+        block_number: U64,
+    ) -> Result<String, PriceLookupError> {
+        let cache_key = self.cache_key(network, contract_address, block_number);
+        if let Some(price) = self.cache.lock().await.get(&cache_key) {
+            return Ok(price.clone());
+        }
+
+        // look up the price at the start of the range so every block in it gets the same price
+        let (_, token_address, bucket) = cache_key;
+        let cache_block_range = self.enrichment.cache_block_range.unwrap_or(1).max(1);
+        let lookup_block = U64::from(bucket * cache_block_range);
+        let price = self.lookup_price(network, token_address, lookup_block).await?;
+
+        let mut cache = self.cache.lock().await;
+        if cache.len() >= MAX_CACHED_PRICES {
+            debug!("Price cache for {} is full, clearing", self.enrichment.column_name);
+            cache.clear();
+        }
+        cache.insert(cache_key, price.clone());
+        Ok(price)
+    }
+
+    /// Looks up the prices of a batch of events at once so `enrich` finds them cached, a failed
+    /// lookup is tried again and logged by `enrich`
+    pub async fn prefetch(&self, events: impl IntoIterator<Item = (String, Address, U64)>) {
+        let missing: HashMap<PriceCacheKey, (String, Address, U64)> = {
+            let cache = self.cache.lock().await;
+            events
+                .into_iter()
+                .map(|(network, contract_address, block_number)| {
+                    (
+                        self.cache_key(&network, contract_address, block_number),
+                        (network, contract_address, block_number),
+                    )
+                })
+                .filter(|(cache_key, _)| !cache.contains_key(cache_key))
+                .collect()
+        };
+
+        stream::iter(missing.into_values())
+            .for_each_concurrent(
+                PRICE_LOOKUP_CONCURRENCY,
+                |(network, contract_address, block_number)| async move {
+                    let _ = self.price(&network, contract_address, block_number).await;
+                },
+            )
+            .await;
+    }
+
+    /// Returns the USD price for the event as a NUMERIC, a failed lookup is written as NULL so
+    /// it does not hold up indexing
+    pub async fn enrich(
+        &self,
+        network: &str,
+        contract_address: Address,
+        block_number: U64,
+    ) -> EthereumSqlTypeWrapper {
+        match self.price(network, contract_address, block_number).await {
+            Ok(price) => EthereumSqlTypeWrapper::NullableNumeric(Some(price)),
+            Err(e) => {
+                error!(
+                    "Could not look up {} for {:?} on {} at block {}: {}",
+                    self.enrichment.column_name,
+                    self.enrichment.token_address.unwrap_or(contract_address),
+                    network,
+                    block_number,
+                    e
+                );
+                EthereumSqlTypeWrapper::NullableNumeric(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::mock;
+
+    use super::*;
+
+    fn http_enricher(path: &str) -> PriceEnricher {
+        PriceEnricher::new(
+            EventPriceEnrichment {
+                event_name: "Transfer".to_string(),
+                oracle: PriceOracle::Http {
+                    url: format!("{}{}/{{block_number}}", mockito::server_url(), path),
+                    json_path: None,
+                },
+                column_name: "price_usd".to_string(),
+                token_address: None,
+                cache_block_range: Some(10),
+            },
+            &[],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_looks_up_each_range_once() {
+        let lookup = mock("GET", "/price/10").with_body(r#"{"price": "1.5"}"#).expect(1).create();
+        let enricher = http_enricher("/price");
+
+        let events = (11..15).map(|block| ("base".to_string(), Address::zero(), U64::from(block)));
+        enricher.prefetch(events).await;
+        let price = enricher.enrich("base", Address::zero(), U64::from(12)).await;
+
+        assert!(
+            matches!(price, EthereumSqlTypeWrapper::NullableNumeric(Some(price)) if price == "1.5")
+        );
+        lookup.assert();
+    }
+
+    #[tokio::test]
+    async fn test_failed_lookup_is_written_as_null() {
+        let _lookup = mock("GET", "/missing/10").with_status(500).create();
+        let enricher = http_enricher("/missing");
+
+        let price = enricher.enrich("base", Address::zero(), U64::from(12)).await;
+
+        assert!(matches!(price, EthereumSqlTypeWrapper::NullableNumeric(None)));
+    }
+}
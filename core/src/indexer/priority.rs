@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore};
+
+use crate::manifest::contract::ContractPriority;
+
+/// Slots getLogs requests and their processing wait for. Every request takes a shared slot plus
+/// one from its priority, lower priorities only get a share of the slots so they can never starve
+/// the highest configured priority. Without mixed priorities every contract gets all slots.
+#[derive(Debug)]
+pub struct IndexingSlots {
+    shared: Arc<Semaphore>,
+    priorities: Vec<(ContractPriority, Arc<Semaphore>)>,
+}
+
+/// Held for as long as the request and its processing run
+pub struct IndexingSlot {
+    _priority: OwnedSemaphorePermit,
+    _shared: OwnedSemaphorePermit,
+}
+
+fn priority_slots(total: usize, priority: ContractPriority, highest: ContractPriority) -> usize {
+    if priority <= highest {
+        return total;
+    }
+    ((total as f64 * priority.slot_share()).ceil() as usize).max(1)
+}
+
+impl IndexingSlots {
+    pub fn new(total: usize, priorities: &[ContractPriority]) -> Self {
+        let highest = priorities.iter().min().copied().unwrap_or_default();
+        let priorities = [
+            ContractPriority::Critical,
+            ContractPriority::High,
+            ContractPriority::Normal,
+            ContractPriority::Low,
+        ]
+        .into_iter()
+        .map(|priority| {
+            (priority, Arc::new(Semaphore::new(priority_slots(total, priority, highest))))
+        })
+        .collect();
+
+        Self { shared: Arc::new(Semaphore::new(total)), priorities }
+    }
+
+    pub async fn acquire(&self, priority: ContractPriority) -> Result<IndexingSlot, AcquireError> {
+        let semaphore = self
+            .priorities
+            .iter()
+            .find(|(slots_priority, _)| *slots_priority == priority)
+            .map(|(_, semaphore)| Arc::clone(semaphore))
+            .expect("every priority has slots");
+
+        // the priority slot first so a backlog of low priority requests never queues ahead of
+        // more than their share of the shared slots
+        let priority_permit = semaphore.acquire_owned().await?;
+        let shared_permit = Arc::clone(&self.shared).acquire_owned().await?;
+
+        Ok(IndexingSlot { _priority: priority_permit, _shared: shared_permit })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_slots() {
+        assert_eq!(priority_slots(100, ContractPriority::Normal, ContractPriority::Normal), 100);
+        assert_eq!(priority_slots(100, ContractPriority::Low, ContractPriority::Normal), 25);
+        assert_eq!(priority_slots(100, ContractPriority::Normal, ContractPriority::Critical), 50);
+        assert_eq!(priority_slots(3, ContractPriority::Low, ContractPriority::High), 1);
+    }
+}
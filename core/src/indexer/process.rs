@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use async_std::prelude::StreamExt;
 use ethers::{
@@ -9,10 +9,12 @@ use futures::future::join_all;
 use tokio::{
     sync::{Mutex, MutexGuard},
     task::{JoinError, JoinHandle},
+    time::Instant,
 };
 use tracing::{debug, error, info};
 
 use crate::{
+    database::postgres::heartbeat::record_heartbeat,
     event::{
         callback_registry::EventResult, config::EventProcessingConfig, BuildRindexerFilterError,
         RindexerEventFilter,
@@ -24,6 +26,8 @@ use crate::{
         log_helpers::is_relevant_block,
         progress::IndexingEventProgressStatus,
     },
+    manifest::network::DEFAULT_BLOCK_POLL_INTERVAL_MS,
+    streams::published_blocks,
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -53,6 +57,12 @@ async fn process_event_logs(
         handle_logs_result(Arc::clone(&config), result)
             .await
             .map_err(|e| Box::new(ProviderError::CustomError(e.to_string())))?;
+
+        if let Some(lifecycle) = &config.lifecycle {
+            if lifecycle.stopped_at().await.is_some() {
+                break;
+            }
+        }
     }
 
     Ok(())
@@ -200,6 +210,13 @@ async fn live_indexing_for_contract_event_dependencies<'a>(
         Arc<Mutex<OrderedLiveIndexingDetails>>,
     > = HashMap::new();
 
+    // events can span networks so poll at the fastest configured cadence
+    let poll_interval = live_indexing_events
+        .iter()
+        .map(|(config, _)| config.live_poll_interval)
+        .min()
+        .unwrap_or(Duration::from_millis(DEFAULT_BLOCK_POLL_INTERVAL_MS));
+
     for (config, event_filter) in live_indexing_events.iter() {
         let mut filter = event_filter.clone();
         let last_seen_block_number = filter.get_to_block();
@@ -214,7 +231,7 @@ async fn live_indexing_for_contract_event_dependencies<'a>(
     }
 
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        tokio::time::sleep(poll_interval).await;
 
         for (config, _) in live_indexing_events.iter() {
             let mut ordering_live_indexing_details = ordering_live_indexing_details_map
@@ -224,186 +241,171 @@ async fn live_indexing_for_contract_event_dependencies<'a>(
                 .await
                 .clone();
 
-            let latest_block = &config.network_contract.cached_provider.get_latest_block().await;
+            let latest_block = config.live_blocks.watch().latest_block();
+
+            if let Some(latest_block) = latest_block {
+                if let Some(latest_block_number) = latest_block.number {
+                    if ordering_live_indexing_details.last_seen_block_number == latest_block_number
+                    {
+                        debug!(
+                            "{} - {} - No new blocks to process...",
+                            &config.info_log_name,
+                            IndexingEventProgressStatus::Live.log()
+                        );
+                        continue;
+                    }
+                    info!(
+                        "{} - {} - New block seen {} - Last seen block {}",
+                        &config.info_log_name,
+                        IndexingEventProgressStatus::Live.log(),
+                        latest_block_number,
+                        ordering_live_indexing_details.last_seen_block_number
+                    );
+                    let reorg_safe_distance = &config.indexing_distance_from_head;
+                    let safe_block_number = latest_block_number - reorg_safe_distance;
+                    let from_block = ordering_live_indexing_details.filter.get_from_block();
+                    // check reorg distance and skip if not safe
+                    if from_block > safe_block_number {
+                        info!(
+                            "{} - {} - not in safe reorg block range yet block: {} > range: {}",
+                            &config.info_log_name,
+                            IndexingEventProgressStatus::Live.log(),
+                            from_block,
+                            safe_block_number
+                        );
+                        continue;
+                    }
+
+                    let to_block = safe_block_number;
+                    if from_block == to_block &&
+                        !config.network_contract.disable_logs_bloom_checks &&
+                        !is_relevant_block(
+                            &ordering_live_indexing_details.filter.raw_filter().address,
+                            ordering_live_indexing_details.filter.topic0(),
+                            &latest_block,
+                        )
+                    {
+                        debug!(
+                            "{} - {} - Skipping block {} as it's not relevant",
+                            &config.info_log_name,
+                            IndexingEventProgressStatus::Live.log(),
+                            from_block
+                        );
+                        info!(
+                            "{} - {} - Did not need to hit RPC as no events in {} block - LogsBloom for block checked",
+                            &config.info_log_name,
+                            IndexingEventProgressStatus::Live.log(),
+                            from_block
+                        );
+
+                        ordering_live_indexing_details.filter =
+                            ordering_live_indexing_details.filter.set_from_block(to_block + 1);
+
+                        ordering_live_indexing_details.last_seen_block_number = to_block;
+                        *ordering_live_indexing_details_map
+                            .get(&config.topic_id)
+                            .expect("Failed to get ordering_live_indexing_details_map")
+                            .lock()
+                            .await = ordering_live_indexing_details;
+                        continue;
+                    }
 
-            match latest_block {
-                Ok(latest_block) => {
-                    if let Some(latest_block) = latest_block {
-                        if let Some(latest_block_number) = latest_block.number {
-                            if ordering_live_indexing_details.last_seen_block_number ==
-                                latest_block_number
-                            {
+                    ordering_live_indexing_details.filter =
+                        ordering_live_indexing_details.filter.set_to_block(to_block);
+
+                    debug!(
+                        "{} - {} - Processing live filter: {:?}",
+                        &config.info_log_name,
+                        IndexingEventProgressStatus::Live.log(),
+                        ordering_live_indexing_details.filter
+                    );
+
+                    let permit = config.slots.acquire(config.priority).await;
+
+                    if let Ok(permit) = permit {
+                        let rpc_start = Instant::now();
+                        let logs_result = config
+                            .network_contract
+                            .cached_provider
+                            .get_logs(&ordering_live_indexing_details.filter)
+                            .await;
+                        config.stats.record_rpc_call(rpc_start.elapsed());
+
+                        match logs_result {
+                            Ok(logs) => {
                                 debug!(
-                                    "{} - {} - No new blocks to process...",
-                                    &config.info_log_name,
-                                    IndexingEventProgressStatus::Live.log()
-                                );
-                                continue;
-                            }
-                            info!(
-                                "{} - {} - New block seen {} - Last seen block {}",
-                                &config.info_log_name,
-                                IndexingEventProgressStatus::Live.log(),
-                                latest_block_number,
-                                ordering_live_indexing_details.last_seen_block_number
-                            );
-                            let reorg_safe_distance = &config.indexing_distance_from_head;
-                            let safe_block_number = latest_block_number - reorg_safe_distance;
-                            let from_block = ordering_live_indexing_details.filter.get_from_block();
-                            // check reorg distance and skip if not safe
-                            if from_block > safe_block_number {
-                                info!(
-                                    "{} - {} - not in safe reorg block range yet block: {} > range: {}",
+                                    "{} - {} - Live topic_id {}, Logs: {} from {} to {}",
                                     &config.info_log_name,
                                     IndexingEventProgressStatus::Live.log(),
+                                    &config.topic_id,
+                                    logs.len(),
                                     from_block,
-                                    safe_block_number
+                                    to_block
                                 );
-                                continue;
-                            }
 
-                            let to_block = safe_block_number;
-                            if from_block == to_block &&
-                                !config.network_contract.disable_logs_bloom_checks &&
-                                !is_relevant_block(
-                                    &ordering_live_indexing_details.filter.raw_filter().address,
-                                    &config.topic_id,
-                                    latest_block,
-                                )
-                            {
                                 debug!(
-                                    "{} - {} - Skipping block {} as it's not relevant",
-                                    &config.info_log_name,
-                                    IndexingEventProgressStatus::Live.log(),
-                                    from_block
-                                );
-                                info!(
-                                    "{} - {} - Did not need to hit RPC as no events in {} block - LogsBloom for block checked",
+                                    "{} - {} - Fetched {} event logs - blocks: {} - {}",
                                     &config.info_log_name,
                                     IndexingEventProgressStatus::Live.log(),
-                                    from_block
+                                    logs.len(),
+                                    from_block,
+                                    to_block
                                 );
 
-                                ordering_live_indexing_details.filter =
-                                    ordering_live_indexing_details
-                                        .filter
-                                        .set_from_block(to_block + 1);
-
-                                ordering_live_indexing_details.last_seen_block_number = to_block;
-                                *ordering_live_indexing_details_map
-                                    .get(&config.topic_id)
-                                    .expect("Failed to get ordering_live_indexing_details_map")
-                                    .lock()
-                                    .await = ordering_live_indexing_details;
-                                continue;
-                            }
-
-                            ordering_live_indexing_details.filter =
-                                ordering_live_indexing_details.filter.set_to_block(to_block);
-
-                            debug!(
-                                "{} - {} - Processing live filter: {:?}",
-                                &config.info_log_name,
-                                IndexingEventProgressStatus::Live.log(),
-                                ordering_live_indexing_details.filter
-                            );
-
-                            let semaphore_client = Arc::clone(&config.semaphore);
-                            let permit = semaphore_client.acquire_owned().await;
-
-                            if let Ok(permit) = permit {
-                                match config
-                                    .network_contract
-                                    .cached_provider
-                                    .get_logs(&ordering_live_indexing_details.filter)
-                                    .await
-                                {
-                                    Ok(logs) => {
-                                        debug!(
-                                            "{} - {} - Live topic_id {}, Logs: {} from {} to {}",
-                                            &config.info_log_name,
-                                            IndexingEventProgressStatus::Live.log(),
-                                            &config.topic_id,
-                                            logs.len(),
-                                            from_block,
-                                            to_block
-                                        );
-
-                                        debug!(
-                                            "{} - {} - Fetched {} event logs - blocks: {} - {}",
-                                            &config.info_log_name,
-                                            IndexingEventProgressStatus::Live.log(),
-                                            logs.len(),
-                                            from_block,
-                                            to_block
-                                        );
+                                let logs_empty = logs.is_empty();
+                                // clone here over the full logs way less overhead
+                                let last_log = logs.last().cloned();
 
-                                        let logs_empty = logs.is_empty();
-                                        // clone here over the full logs way less overhead
-                                        let last_log = logs.last().cloned();
+                                let fetched_logs =
+                                    Ok(FetchLogsResult { logs, from_block, to_block });
 
-                                        let fetched_logs =
-                                            Ok(FetchLogsResult { logs, from_block, to_block });
+                                let result =
+                                    handle_logs_result(Arc::clone(config), fetched_logs).await;
 
-                                        let result =
-                                            handle_logs_result(Arc::clone(config), fetched_logs)
-                                                .await;
-
-                                        match result {
-                                            Ok(_) => {
+                                match result {
+                                    Ok(_) => {
+                                        ordering_live_indexing_details.last_seen_block_number =
+                                            to_block;
+                                        if logs_empty {
+                                            ordering_live_indexing_details.filter =
                                                 ordering_live_indexing_details
-                                                    .last_seen_block_number = to_block;
-                                                if logs_empty {
-                                                    ordering_live_indexing_details.filter =
-                                                        ordering_live_indexing_details
-                                                            .filter
-                                                            .set_from_block(to_block + 1);
-                                                    info!(
-                                                        "{} - {} - No events found between blocks {} - {}",
-                                                        &config.info_log_name,
-                                                        IndexingEventProgressStatus::Live.log(),
-                                                        from_block,
-                                                        to_block
-                                                    );
-                                                } else if let Some(last_log) = last_log {
-                                                    if let Some(last_log_block_number) =
-                                                        last_log.block_number
-                                                    {
-                                                        ordering_live_indexing_details.filter =
-                                                            ordering_live_indexing_details
-                                                                .filter
-                                                                .set_from_block(
-                                                                    last_log_block_number +
-                                                                        U64::from(1),
-                                                                );
-                                                    } else {
-                                                        error!("Failed to get last log block number the provider returned null (should never happen) - try again in 200ms");
-                                                    }
-                                                }
-
-                                                *ordering_live_indexing_details_map
-                                                    .get(&config.topic_id)
-                                                    .expect("Failed to get ordering_live_indexing_details_map")
-                                                    .lock()
-                                                    .await = ordering_live_indexing_details;
-
-                                                drop(permit);
-                                            }
-                                            Err(err) => {
-                                                error!(
-                                                    "{} - {} - Error fetching logs: {} - will try again in 200ms",
-                                                    &config.info_log_name,
-                                                    IndexingEventProgressStatus::Live.log(),
-                                                    err
-                                                );
-                                                drop(permit);
-                                                break;
+                                                    .filter
+                                                    .set_from_block(to_block + 1);
+                                            info!(
+                                                "{} - {} - No events found between blocks {} - {}",
+                                                &config.info_log_name,
+                                                IndexingEventProgressStatus::Live.log(),
+                                                from_block,
+                                                to_block
+                                            );
+                                        } else if let Some(last_log) = last_log {
+                                            if let Some(last_log_block_number) =
+                                                last_log.block_number
+                                            {
+                                                ordering_live_indexing_details.filter =
+                                                    ordering_live_indexing_details
+                                                        .filter
+                                                        .set_from_block(
+                                                            last_log_block_number + U64::from(1),
+                                                        );
+                                            } else {
+                                                error!("Failed to get last log block number the provider returned null (should never happen) - try again in 200ms");
                                             }
                                         }
+
+                                        *ordering_live_indexing_details_map
+                                            .get(&config.topic_id)
+                                            .expect(
+                                                "Failed to get ordering_live_indexing_details_map",
+                                            )
+                                            .lock()
+                                            .await = ordering_live_indexing_details;
+
+                                        drop(permit);
                                     }
                                     Err(err) => {
                                         error!(
-                                            "{} - {} - Error fetching logs: {} - will try again in 200ms",
+                                            "{} - {} - Error fetching logs: {} - will try again shortly",
                                             &config.info_log_name,
                                             IndexingEventProgressStatus::Live.log(),
                                             err
@@ -413,24 +415,77 @@ async fn live_indexing_for_contract_event_dependencies<'a>(
                                     }
                                 }
                             }
-                        } else {
-                            info!("WARNING - empty latest block returned from provider, will try again in 200ms");
+                            Err(err) => {
+                                error!(
+                                    "{} - {} - Error fetching logs: {} - will try again shortly",
+                                    &config.info_log_name,
+                                    IndexingEventProgressStatus::Live.log(),
+                                    err
+                                );
+                                drop(permit);
+                                break;
+                            }
                         }
-                    } else {
-                        info!("WARNING - empty latest block returned from provider, will try again in 200ms");
                     }
+                } else {
+                    info!("WARNING - empty latest block returned from provider, will try again shortly");
                 }
-                Err(error) => {
-                    error!(
-                        "Failed to get latest block, will try again in 200ms - error: {}",
-                        error.to_string()
-                    );
-                }
+            } else {
+                info!(
+                    "WARNING - empty latest block returned from provider, will try again shortly"
+                );
             }
         }
     }
 }
 
+async fn trigger_event_with_stats(
+    config: &EventProcessingConfig,
+    fn_data: Vec<EventResult>,
+    to_block: U64,
+) {
+    // backfilled writes of a catching up event wait for live writes so live keeps the database
+    let _live_write = match (&config.catch_up, &config.catch_up_backfill) {
+        (Some(catch_up), Some(backfill)) if backfill.is_live_range(to_block) => {
+            Some(catch_up.live_request())
+        }
+        (Some(catch_up), Some(_)) => {
+            catch_up.wait_for_live().await;
+            None
+        }
+        _ => None,
+    };
+    let rows = fn_data.len();
+    let start = Instant::now();
+    config.trigger_event(fn_data).await;
+    config.stats.record_processed(rows, start.elapsed());
+}
+
+/// Awaited before the next range is handled so the ranges are written in block order and keep
+/// extending the same row
+async fn record_empty_range(config: &EventProcessingConfig, from_block: U64, to_block: U64) {
+    let Some(database) = config.database.as_ref().filter(|_| config.heartbeats) else {
+        return;
+    };
+
+    if let Err(e) = record_heartbeat(
+        database,
+        &config.indexer_name,
+        &config.contract_name,
+        &config.event_name,
+        &config.network_contract.network,
+        from_block,
+        to_block,
+    )
+    .await
+    {
+        error!(
+            "Error recording heartbeat for {} - {}: {}",
+            config.event_name, config.network_contract.network, e
+        );
+    }
+}
+
 async fn handle_logs_result(
     config: Arc<EventProcessingConfig>,
     result: Result<FetchLogsResult, Box<dyn std::error::Error + Send>>,
@@ -439,29 +494,72 @@ async fn handle_logs_result(
         Ok(result) => {
             debug!("Processing logs {} - length {}", config.event_name, result.logs.len());
 
-            let fn_data = result
-                .logs
+            config.stats.record_blocks(result.from_block, result.to_block);
+
+            let mut logs = result.logs;
+            let lifecycle = match &config.lifecycle {
+                Some(lifecycle) => {
+                    Some(lifecycle.observe(result.from_block, result.to_block).await)
+                }
+                None => None,
+            };
+            if let Some(stopped_at) = lifecycle.as_ref().and_then(|view| view.stopped_at) {
+                logs.retain(|log| log.block_number.is_some_and(|block| block <= stopped_at));
+            }
+
+            let fn_data = logs
                 .into_iter()
                 .map(|log| {
-                    EventResult::new(
+                    let mut event_result = EventResult::new(
                         Arc::clone(&config.network_contract),
                         log,
                         result.from_block,
                         result.to_block,
-                    )
+                    );
+                    if let Some(view) = &lifecycle {
+                        event_result.tx_information.after_code_removed = view.after_code_removed(
+                            event_result.tx_information.address,
+                            event_result.tx_information.block_number,
+                        );
+                    }
+                    event_result
                 })
                 .collect::<Vec<_>>();
 
             if !fn_data.is_empty() {
-                if config.index_event_in_order {
-                    config.trigger_event(fn_data).await;
+                published_blocks().start(&config.network_contract.id, result.from_block);
+                // logs a reorg removed are retracted before the logs replacing them are indexed
+                let removed = fn_data.iter().any(|result| result.tx_information.removed);
+                if config.index_event_in_order || removed {
+                    trigger_event_with_stats(&config, fn_data, result.to_block).await;
+                    published_blocks().finish(
+                        &config.network_contract.id,
+                        result.from_block,
+                        result.to_block,
+                    );
                     update_progress_and_last_synced(config, result.to_block);
                 } else {
                     tokio::spawn(async move {
-                        config.trigger_event(fn_data).await;
+                        trigger_event_with_stats(&config, fn_data, result.to_block).await;
+                        published_blocks().finish(
+                            &config.network_contract.id,
+                            result.from_block,
+                            result.to_block,
+                        );
                         update_progress_and_last_synced(config, result.to_block);
                     });
                 }
+            } else {
+                published_blocks().skip(&config.network_contract.id, result.to_block);
+                record_empty_range(&config, result.from_block, result.to_block).await;
+                // the checkpoint held back by live indexing moves once the backfill is done
+                if config
+                    .catch_up_backfill
+                    .as_ref()
+                    .is_some_and(|backfill| backfill.to_block() == result.to_block)
+                {
+                    update_progress_and_last_synced(config, result.to_block);
+                }
             }
 
             Ok(())
@@ -0,0 +1,269 @@
+use std::path::Path;
+
+use ethers::{
+    abi::Abi,
+    types::{Bytes, Log, H256},
+};
+use tracing::info;
+
+use crate::{
+    abi::{ABIItem, ParamTypeError, ReadAbiError},
+    database::postgres::{
+        client::{PostgresClient, PostgresConnectionError, PostgresError},
+        generate::{generate_columns_names_only, generate_event_table_full_names},
+        sql_type_wrapper::{map_log_params_to_ethereum_wrapper, EthereumSqlTypeWrapper},
+    },
+    indexer::{log_helpers::parse_log, transform::EventTransforms},
+    manifest::{core::Manifest, transform::transforms_for_event},
+};
+
+/// Rows are read and rewritten this many blocks at a time so large tables are not loaded at once
+const REDECODE_WINDOW_BLOCKS: u64 = 10_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum RedecodeError {
+    #[error("Postgres storage is not enabled - redecode rewrites the postgres tables")]
+    PostgresNotEnabled,
+
+    #[error("Contract {0} not found in the manifest")]
+    ContractNotFound(String),
+
+    #[error("Contract {0} does not set store_raw_logs so there are no raw logs to decode")]
+    RawLogsNotStored(String),
+
+    #[error("Event {1} not found in the ABI of {0}")]
+    EventNotFound(String, String),
+
+    #[error("Could not read ABI {0}: {1}")]
+    CouldNotReadAbi(String, String),
+
+    #[error("Could not read ABI items: {0}")]
+    CouldNotReadAbiItems(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
+
+    #[error("Could not connect to postgres: {0}")]
+    PostgresConnectionError(#[from] PostgresConnectionError),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+}
+
+#[derive(Debug, Clone)]
+pub struct RedecodeOptions {
+    pub contract_name: String,
+    pub event_name: String,
+}
+
+#[derive(Debug, Default)]
+pub struct RedecodeReport {
+    pub rewritten: u64,
+    /// Rows whose raw log does not fit the current ABI, they are left as they were
+    pub undecodable: u64,
+}
+
+fn raw_log(raw_topics: Vec<Vec<u8>>, raw_data: Vec<u8>) -> Option<Log> {
+    if raw_topics.is_empty() || raw_topics.iter().any(|topic| topic.len() != 32) {
+        return None;
+    }
+
+    Some(Log {
+        topics: raw_topics.iter().map(|topic| H256::from_slice(topic)).collect(),
+        data: Bytes::from(raw_data),
+        ..Default::default()
+    })
+}
+
+/// Rows are matched on the log they came from as that identifies them on every layout, the hash
+/// is read back as text and cast to the column type so its index can be used
+fn update_sql(table_name: &str, column_names: &[String], compact_bytea: bool) -> String {
+    let set_columns = column_names
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!("\"{}\" = ${}", column, i + 1))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let key = column_names.len();
+
+    format!(
+        "UPDATE {} SET {} WHERE network = ${} AND tx_hash = (${}::TEXT)::{} AND log_index = ${}",
+        table_name,
+        set_columns,
+        key + 1,
+        key + 2,
+        if compact_bytea { "BYTEA" } else { "CHAR(66)" },
+        key + 3
+    )
+}
+
+/// Decodes the stored raw logs of an event again with the current ABI and rewrites the decoded
+/// columns in place, so fixing an ABI does not need a resync. Computed and enum columns follow
+/// as postgres generates them, the table should already match the ABI so apply any migration
+/// plan first.
+pub async fn redecode_event(
+    project_path: &Path,
+    manifest: &Manifest,
+    options: &RedecodeOptions,
+) -> Result<RedecodeReport, RedecodeError> {
+    if !manifest.storage.postgres_enabled() {
+        return Err(RedecodeError::PostgresNotEnabled);
+    }
+
+    let contract = manifest
+        .contracts
+        .iter()
+        .find(|contract| contract.name == options.contract_name)
+        .ok_or_else(|| RedecodeError::ContractNotFound(options.contract_name.clone()))?;
+    if !contract.stores_raw_logs() {
+        return Err(RedecodeError::RawLogsNotStored(contract.name.clone()));
+    }
+
+    let abi_items = ABIItem::get_abi_items(project_path, contract, contract.is_filter())?;
+    let mut event_info = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?
+        .into_iter()
+        .find(|event_info| event_info.name == options.event_name)
+        .ok_or_else(|| {
+            RedecodeError::EventNotFound(contract.name.clone(), options.event_name.clone())
+        })?;
+    let abi: Abi = contract
+        .read_abi(project_path)
+        .map_err(|e| e.to_string())
+        .and_then(|abi| serde_json::from_str(&abi).map_err(|e| e.to_string()))
+        .map_err(|e| RedecodeError::CouldNotReadAbi(contract.abi.clone(), e))?;
+    let topic_id = event_info.topic_id();
+    let mut event = abi
+        .events()
+        .find(|event| event.signature() == topic_id)
+        .ok_or_else(|| {
+            RedecodeError::EventNotFound(contract.name.clone(), options.event_name.clone())
+        })?
+        .clone();
+    if let Some(layout) = contract.anonymous_event_layout(&event_info.name) {
+        event.anonymous = true;
+        for input in event.inputs.iter_mut() {
+            input.indexed = layout.topics.contains(&input.name);
+        }
+        for input in event_info.inputs.iter_mut() {
+            input.indexed = Some(layout.topics.contains(&input.name));
+        }
+    }
+
+    let jsonb_inputs = contract.jsonb_inputs(&event_info.name, &event_info.inputs);
+    let transforms = EventTransforms::new(
+        &event_info.inputs,
+        transforms_for_event(&contract.transforms, &event_info.name),
+        &jsonb_inputs,
+    );
+    let column_names = generate_columns_names_only(&event_info.inputs, &jsonb_inputs);
+    if column_names.is_empty() {
+        return Ok(RedecodeReport::default());
+    }
+    let compact_bytea = manifest.storage.postgres_compact_bytea();
+
+    let postgres = PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+    let mut report = RedecodeReport::default();
+
+    for table_name in generate_event_table_full_names(
+        manifest.storage.postgres_layout(),
+        &manifest.name,
+        contract,
+        &contract.before_modify_name_if_filter_readonly(),
+        &event_info.name,
+    ) {
+        let range = postgres
+            .query_one(
+                &format!(
+                    "SELECT MIN(block_number)::TEXT, MAX(block_number)::TEXT FROM {} WHERE raw_topics IS NOT NULL",
+                    table_name
+                ),
+                &[],
+            )
+            .await?;
+        let (Some(min_block), Some(max_block)) =
+            (range.get::<_, Option<String>>(0), range.get::<_, Option<String>>(1))
+        else {
+            continue;
+        };
+        let (Ok(min_block), Ok(max_block)) = (min_block.parse::<u64>(), max_block.parse::<u64>())
+        else {
+            continue;
+        };
+
+        info!("Decoding the raw logs of {} again", table_name);
+        let update_sql = update_sql(&table_name, &column_names, compact_bytea);
+        let mut from_block = min_block;
+        while from_block <= max_block {
+            let to_block = from_block.saturating_add(REDECODE_WINDOW_BLOCKS);
+            let rows = postgres
+                .query(
+                    &format!(
+                        "SELECT network, tx_hash::TEXT, log_index::TEXT, raw_topics, raw_data FROM {} \
+                        WHERE block_number >= {} AND block_number < {} AND raw_topics IS NOT NULL",
+                        table_name, from_block, to_block
+                    ),
+                    &[],
+                )
+                .await?;
+
+            let mut updates = vec![];
+            for row in rows {
+                let Some(parsed) =
+                    raw_log(row.get(3), row.get(4)).and_then(|log| parse_log(&event, &log))
+                else {
+                    report.undecodable += 1;
+                    continue;
+                };
+
+                let Ok(mut values) = map_log_params_to_ethereum_wrapper(
+                    &event_info.inputs,
+                    &parsed.params,
+                    &jsonb_inputs,
+                ) else {
+                    report.undecodable += 1;
+                    continue;
+                };
+                transforms.apply(&mut values);
+                if compact_bytea {
+                    values = values
+                        .into_iter()
+                        .map(EthereumSqlTypeWrapper::into_compact_bytea)
+                        .collect();
+                }
+                for key in 0..3 {
+                    values.push(EthereumSqlTypeWrapper::String(row.get(key)));
+                }
+                updates.push(values);
+            }
+
+            if !updates.is_empty() {
+                report.rewritten += postgres.execute_for_rows(&update_sql, &updates).await?;
+            }
+            from_block = to_block;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_sql() {
+        assert_eq!(
+            update_sql("erc20.transfer", &["from".to_string(), "value".to_string()], false),
+            "UPDATE erc20.transfer SET \"from\" = $1, \"value\" = $2 WHERE network = $3 AND tx_hash = ($4::TEXT)::CHAR(66) AND log_index = $5"
+        );
+        assert!(update_sql("erc20.transfer", &["from".to_string()], true)
+            .ends_with("tx_hash = ($3::TEXT)::BYTEA AND log_index = $4"));
+    }
+
+    #[test]
+    fn test_raw_log_needs_full_topics() {
+        assert!(raw_log(vec![vec![0u8; 32]], vec![1, 2]).is_some());
+        assert!(raw_log(vec![vec![0u8; 20]], vec![]).is_none());
+        assert!(raw_log(vec![], vec![]).is_none());
+    }
+}
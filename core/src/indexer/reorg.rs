@@ -1,4 +1,11 @@
-use ethers::types::{U256, U64};
+use std::collections::BTreeMap;
+
+use ethers::{
+    providers::ProviderError,
+    types::{Log, H256, U256, U64},
+};
+
+use crate::provider::JsonRpcCachedProvider;
 
 pub fn reorg_safe_distance_for_chain(chain_id: &U256) -> U64 {
     match chain_id.as_u64() {
@@ -7,6 +14,74 @@ pub fn reorg_safe_distance_for_chain(chain_id: &U256) -> U64 {
     }
 }
 
+/// Logs handed to the handlers from blocks a reorg can still replace, kept with the hash of their
+/// block so the logs of a replaced block can be handed back flagged `removed`
+#[derive(Debug, Default)]
+pub struct UnconfirmedLogs {
+    blocks: BTreeMap<U64, (H256, Vec<Log>)>,
+}
+
+impl UnconfirmedLogs {
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn track(&mut self, logs: &[Log]) {
+        for log in logs {
+            if let (Some(block_number), Some(block_hash)) = (log.block_number, log.block_hash) {
+                self.blocks
+                    .entry(block_number)
+                    .or_insert_with(|| (block_hash, vec![]))
+                    .1
+                    .push(log.clone());
+            }
+        }
+    }
+
+    /// Forgets the blocks a reorg can no longer replace
+    pub fn confirm(&mut self, safe_block: U64) {
+        self.blocks = self.blocks.split_off(&(safe_block + 1));
+    }
+
+    /// Takes the logs of the first block whose hash is no longer `canonical` and of every block
+    /// after it, flagged `removed`
+    fn take_reorged(&mut self, canonical: &BTreeMap<U64, H256>) -> Vec<Log> {
+        let Some(fork_block) = self
+            .blocks
+            .iter()
+            .find(|(block_number, (block_hash, _))| {
+                canonical.get(*block_number) != Some(block_hash)
+            })
+            .map(|(block_number, _)| *block_number)
+        else {
+            return vec![];
+        };
+
+        self.blocks
+            .split_off(&fork_block)
+            .into_values()
+            .flat_map(|(_, logs)| logs)
+            .map(|log| Log { removed: Some(true), ..log })
+            .collect()
+    }
+
+    /// Checks the blocks against the chain and takes the logs of the ones which were replaced
+    pub async fn reorged(
+        &mut self,
+        provider: &JsonRpcCachedProvider,
+    ) -> Result<Vec<Log>, ProviderError> {
+        let mut canonical = BTreeMap::new();
+        for block_number in self.blocks.keys() {
+            let block = provider.get_block_header(*block_number).await?;
+            if let Some(block_hash) = block.hash {
+                canonical.insert(*block_number, block_hash);
+            }
+        }
+
+        Ok(self.take_reorged(&canonical))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use ethers::types::U256;
@@ -24,4 +99,41 @@ mod tests {
         let other_chain_id = U256::from(42);
         assert_eq!(reorg_safe_distance_for_chain(&other_chain_id), U64::from(64));
     }
+
+    fn log(block_number: u64, block_hash: u64, log_index: u64) -> Log {
+        Log {
+            block_number: Some(U64::from(block_number)),
+            block_hash: Some(H256::from_low_u64_be(block_hash)),
+            log_index: Some(U256::from(log_index)),
+            removed: Some(false),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unconfirmed_logs_takes_reorged_blocks() {
+        let mut unconfirmed = UnconfirmedLogs::default();
+        unconfirmed.track(&[log(10, 10, 0), log(11, 11, 0), log(11, 11, 1), log(12, 12, 0)]);
+
+        let mut canonical = BTreeMap::from([
+            (U64::from(10), H256::from_low_u64_be(10)),
+            (U64::from(11), H256::from_low_u64_be(11)),
+            (U64::from(12), H256::from_low_u64_be(12)),
+        ]);
+        assert!(unconfirmed.take_reorged(&canonical).is_empty());
+
+        canonical.insert(U64::from(11), H256::from_low_u64_be(111));
+        let removed = unconfirmed.take_reorged(&canonical);
+        assert_eq!(
+            removed
+                .iter()
+                .map(|log| (log.block_number.unwrap().as_u64(), log.log_index.unwrap().as_u64()))
+                .collect::<Vec<_>>(),
+            vec![(11, 0), (11, 1), (12, 0)]
+        );
+        assert!(removed.iter().all(|log| log.removed == Some(true)));
+
+        unconfirmed.confirm(U64::from(10));
+        assert!(unconfirmed.is_empty());
+    }
 }
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use ethers::types::U64;
+
+use crate::{
+    indexer::catch_up::RateBucket,
+    manifest::network::{ComputeUnitCosts, ScheduleWindow},
+};
+
+/// How often a paused historic sync checks whether its window ended
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct ScheduledWindow {
+    window: ScheduleWindow,
+    blocks: Option<RateBucket>,
+    compute_units: Option<RateBucket>,
+}
+
+/// Shared by every event on a network, historic requests are throttled by the window active at
+/// the time they are sent and run at full speed outside of every window
+#[derive(Debug)]
+pub struct NetworkSchedule {
+    windows: Vec<ScheduledWindow>,
+    get_logs_cost: u64,
+}
+
+impl NetworkSchedule {
+    pub fn new(windows: &[ScheduleWindow], compute_unit_costs: Option<&ComputeUnitCosts>) -> Self {
+        Self {
+            windows: windows
+                .iter()
+                .map(|window| ScheduledWindow {
+                    window: window.clone(),
+                    blocks: window.max_blocks_per_second.map(RateBucket::new),
+                    compute_units: window.max_compute_units_per_second.map(RateBucket::new),
+                })
+                .collect(),
+            get_logs_cost: compute_unit_costs.map_or(1, |costs| costs.cost("eth_getLogs")),
+        }
+    }
+
+    fn active_window(&self) -> Option<&ScheduledWindow> {
+        let now = Utc::now();
+        self.windows.iter().find(|scheduled| scheduled.window.is_active_at(now))
+    }
+
+    /// Caps the range of a historic request to what the active window allows in one second
+    pub fn limit_to_block(&self, from_block: U64, to_block: U64) -> U64 {
+        match self.active_window().and_then(|scheduled| scheduled.window.max_blocks_per_second) {
+            Some(max_blocks) => std::cmp::min(to_block, from_block + max_blocks.max(1) - 1),
+            None => to_block,
+        }
+    }
+
+    /// Waits out a paused window and then for the budget of a request over the blocks
+    pub async fn wait_for_historic_request(&self, from_block: U64, to_block: U64) {
+        loop {
+            let Some(scheduled) = self.active_window() else {
+                return;
+            };
+            if scheduled.window.paused() {
+                tokio::time::sleep(PAUSED_POLL_INTERVAL).await;
+                continue;
+            }
+
+            if let Some(blocks) = &scheduled.blocks {
+                blocks.spend((to_block.saturating_sub(from_block) + 1).as_u64()).await;
+            }
+            if let Some(compute_units) = &scheduled.compute_units {
+                compute_units.spend(self.get_logs_cost).await;
+            }
+            return;
+        }
+    }
+}
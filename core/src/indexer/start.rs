@@ -1,32 +1,44 @@
-use std::{path::Path, sync::Arc};
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
 
-use ethers::{providers::ProviderError, types::U64};
+use ethers::{
+    providers::ProviderError,
+    types::{ValueOrArray, U64},
+};
 use futures::future::try_join_all;
 use tokio::{
-    sync::Semaphore,
     task::{JoinError, JoinHandle},
     time::Instant,
 };
 use tracing::{error, info};
 
 use crate::{
-    database::postgres::client::PostgresConnectionError,
+    api::{start_metrics_server, StartMetricsServerError},
+    database::postgres::{
+        client::{PostgresConnectionError, PostgresError},
+        reconciliation::{start_reconciliation_checks, ReconciliationError},
+    },
     event::{
-        callback_registry::EventCallbackRegistry, config::EventProcessingConfig,
-        contract_setup::NetworkContract,
+        callback_registry::EventCallbackRegistry,
+        config::EventProcessingConfig,
+        contract_setup::{IndexingContractSetup, NetworkContract},
     },
+    handler_cache::{handler_cache, HandlerCacheError},
     indexer::{
         dependency::ContractEventsDependenciesConfig,
-        last_synced::{get_last_synced_block_number, SyncConfig},
+        last_synced::{get_last_synced_block_number, LastSyncedCheckpoint, SyncConfig},
         process::{
             process_contracts_events_with_dependencies, process_event,
             ProcessContractsEventsWithDependenciesError, ProcessEventError,
         },
         progress::IndexingEventsProgressState,
         reorg::reorg_safe_distance_for_chain,
-        ContractEventDependencies,
+        start_stream_watermarks, start_webhook_ingest,
+        stats::start_indexing_stats_flush,
+        AddressRegistryError, AddressRegistryResolver, CatchUp, CatchUpBackfill,
+        ContractEventDependencies, ContractLifecycle, DeploymentBlocks, IndexingEventStats,
+        IndexingSlots, LiveBlocks, NetworkSchedule, StartWebhookIngestError, UnloggedHistoricSync,
     },
-    manifest::core::Manifest,
+    manifest::{core::Manifest, network::DEFAULT_BLOCK_POLL_INTERVAL_MS},
     PostgresClient,
 };
 
@@ -68,6 +80,24 @@ pub enum StartIndexingError {
 
     #[error("The end block set for {0} is higher than the latest block: {1} - end block: {2}")]
     EndBlockIsHigherThanLatestBlockError(String, U64, U64),
+
+    #[error("Could not resolve address registry: {0}")]
+    AddressRegistryError(#[from] AddressRegistryError),
+
+    #[error("{0}")]
+    HandlerCacheError(#[from] HandlerCacheError),
+
+    #[error("{0}")]
+    StartMetricsServerError(#[from] StartMetricsServerError),
+
+    #[error("Could not start reconciliation checks: {0}")]
+    ReconciliationError(#[from] ReconciliationError),
+
+    #[error("{0}")]
+    StartWebhookIngestError(#[from] StartWebhookIngestError),
+
+    #[error("Could not reset an unlogged table: {0}")]
+    ResetUnloggedTableError(#[from] PostgresError),
 }
 
 pub struct ProcessedNetworkContract {
@@ -85,10 +115,37 @@ pub async fn start_indexing(
     let start = Instant::now();
 
     let database = initialize_database(manifest).await?;
+    if let Some(database) =
+        database.as_ref().filter(|_| manifest.storage.postgres_persist_handler_cache())
+    {
+        handler_cache().persist_to(Arc::clone(database), &manifest.name).await?;
+    }
+    if let Some(port) = manifest.storage.postgres_metrics_port().filter(|_| database.is_some()) {
+        start_metrics_server(manifest.storage.postgres_metrics_host(), port).await?;
+    }
+    if let Some(database) = &database {
+        start_reconciliation_checks(manifest, project_path, Arc::clone(database)).await?;
+    }
     let event_progress_state = IndexingEventsProgressState::monitor(&registry.events).await;
+    // webhook networks are not polled, their new blocks come from the provider deliveries
+    let webhooks = manifest.webhooks.as_ref().filter(|_| !no_live_indexing_forced);
+    let mut webhook_server = None;
+    if let (Some(webhooks), Some(database)) = (webhooks, &database) {
+        webhook_server = Some(
+            start_webhook_ingest(
+                webhooks,
+                Arc::clone(&registry),
+                Arc::clone(database),
+                Arc::clone(&event_progress_state),
+            )
+            .await?,
+        );
+    }
 
-    // we can bring this into the yaml file later if required
-    let semaphore = Arc::new(Semaphore::new(100));
+    let slots = Arc::new(IndexingSlots::new(
+        manifest.runtime_settings().indexing_concurrency(),
+        &manifest.contracts.iter().map(|c| c.priority.unwrap_or_default()).collect::<Vec<_>>(),
+    ));
     // need this to keep track of dependency_events cross contracts and events
     let mut event_processing_configs: Vec<Arc<EventProcessingConfig>> = vec![];
     // any events which are non-blocking and can be fired in parallel
@@ -101,12 +158,32 @@ pub async fn start_indexing(
 
     let mut processed_network_contracts: Vec<ProcessedNetworkContract> = Vec::new();
 
+    let mut indexing_stats: Vec<Arc<IndexingEventStats>> = Vec::new();
+
+    let unlogged_historic_sync = match &database {
+        Some(database) if manifest.storage.postgres_unlogged_historic_sync() => {
+            Some(Arc::new(UnloggedHistoricSync::new(
+                Arc::clone(database),
+                manifest.storage.postgres_default_indexes(),
+                manifest.storage.postgres_layout(),
+            )))
+        }
+        _ => None,
+    };
+
+    let deployment_blocks = Arc::new(DeploymentBlocks::new(database.clone()));
+    let mut address_registry_resolver =
+        AddressRegistryResolver::new(Arc::clone(&deployment_blocks));
+    let mut live_blocks: HashMap<String, Arc<LiveBlocks>> = HashMap::new();
+    let mut catch_ups: HashMap<String, Option<Arc<CatchUp>>> = HashMap::new();
+    let mut schedules: HashMap<String, Option<Arc<NetworkSchedule>>> = HashMap::new();
+    let mut lifecycles: HashMap<(String, String), Arc<ContractLifecycle>> = HashMap::new();
+
     for event in registry.events.iter() {
-        let stream_details = manifest
-            .contracts
-            .iter()
-            .find(|c| c.name == event.contract.name)
-            .and_then(|c| c.streams.as_ref());
+        let contract = manifest.contracts.iter().find(|c| c.name == event.contract.name);
+        let stream_details = contract.and_then(|c| c.streams.as_ref());
+        let anonymous =
+            contract.is_some_and(|c| c.anonymous_event_layout(&event.event_name).is_some());
         for network_contract in event.contract.details.iter() {
             let config = SyncConfig {
                 project_path,
@@ -144,7 +221,38 @@ pub async fn start_indexing(
                 }
             }
 
-            let last_known_start_block = if network_contract.start_block.is_some() {
+            let configured_start_block =
+                match (network_contract.start_block, &network_contract.indexing_contract_setup) {
+                    (Some(start_block), _) => Some(start_block),
+                    (None, IndexingContractSetup::Address(details)) => {
+                        let addresses = match &details.address {
+                            ValueOrArray::Value(address) => vec![*address],
+                            ValueOrArray::Array(addresses) => addresses.clone(),
+                        };
+                        deployment_blocks
+                            .detect(
+                                &network_contract.cached_provider,
+                                &network_contract.network,
+                                &addresses,
+                                latest_block,
+                            )
+                            .await
+                    }
+                    _ => None,
+                };
+
+            if let Some(unlogged_historic_sync) = &unlogged_historic_sync {
+                unlogged_historic_sync
+                    .reset_unlogged_table(
+                        &event.indexer_name,
+                        &event.contract.name,
+                        &event.event_name,
+                        &network_contract.network,
+                    )
+                    .await?;
+            }
+
+            let last_known_start_block = if configured_start_block.is_some() {
                 let last_synced_block = get_last_synced_block_number(config).await;
 
                 if let Some(value) = last_synced_block {
@@ -156,8 +264,8 @@ pub async fn start_indexing(
                 None
             };
 
-            let start_block = last_known_start_block
-                .unwrap_or(network_contract.start_block.unwrap_or(latest_block));
+            let start_block =
+                last_known_start_block.unwrap_or(configured_start_block.unwrap_or(latest_block));
             let end_block =
                 std::cmp::min(network_contract.end_block.unwrap_or(latest_block), latest_block);
             if let Some(end_block) = network_contract.end_block {
@@ -166,20 +274,106 @@ pub async fn start_indexing(
                 }
             }
 
+            let network = manifest.networks.iter().find(|n| n.name == network_contract.network);
             let (end_block, indexing_distance_from_head) = calculate_safe_block_number(
                 event.contract.reorg_safe_distance,
+                network.and_then(|n| n.block_processing_delay).unwrap_or_default(),
                 network_contract,
                 latest_block,
                 end_block,
             )
             .await?;
 
+            let catch_up = catch_ups
+                .entry(network_contract.network.clone())
+                .or_insert_with(|| {
+                    network.and_then(|n| {
+                        n.catch_up.as_ref().map(|catch_up| {
+                            Arc::new(CatchUp::new(catch_up, n.compute_unit_costs.as_ref()))
+                        })
+                    })
+                })
+                .clone();
+            let schedule = schedules
+                .entry(network_contract.network.clone())
+                .or_insert_with(|| {
+                    network.and_then(|n| {
+                        n.schedule.as_ref().filter(|windows| !windows.is_empty()).map(|windows| {
+                            Arc::new(NetworkSchedule::new(windows, n.compute_unit_costs.as_ref()))
+                        })
+                    })
+                })
+                .clone();
+            // only a resume after downtime is throttled, a first sync backfills at full speed
+            let catching_up = last_known_start_block.is_some() &&
+                catch_up
+                    .as_ref()
+                    .is_some_and(|catch_up| catch_up.applies(start_block, end_block));
+            if catching_up {
+                info!(
+                    "{} - {} blocks behind on {} - catch-up mode indexes live from the head while the backfill is throttled behind it",
+                    event.info_log_name(),
+                    end_block.saturating_sub(start_block),
+                    network_contract.network
+                );
+            }
+
             // push status to the processed state
             processed_network_contracts.push(ProcessedNetworkContract {
                 id: network_contract.id.clone(),
                 processed_up_to: end_block,
             });
 
+            let (network_contract, registry_addresses) =
+                address_registry_resolver.resolve(network_contract).await?;
+
+            let stats = Arc::new(IndexingEventStats::new(
+                &event.indexer_name,
+                &event.contract.name,
+                &event.event_name,
+                &network_contract.network,
+            ));
+            indexing_stats.push(Arc::clone(&stats));
+
+            if let Some(unlogged_historic_sync) = &unlogged_historic_sync {
+                unlogged_historic_sync
+                    .register(
+                        &event.indexer_name,
+                        &event.contract.name,
+                        &event.event_name,
+                        &network_contract.network,
+                    )
+                    .await;
+            }
+
+            let lifecycle = match (
+                contract.and_then(|c| c.lifecycle.as_ref()),
+                &network_contract.indexing_contract_setup,
+            ) {
+                (Some(lifecycle_config), IndexingContractSetup::Address(details)) => {
+                    let addresses = match &details.address {
+                        ValueOrArray::Value(address) => vec![*address],
+                        ValueOrArray::Array(addresses) => addresses.clone(),
+                    };
+                    Some(Arc::clone(
+                        lifecycles
+                            .entry((event.contract.name.clone(), network_contract.network.clone()))
+                            .or_insert_with(|| {
+                                Arc::new(ContractLifecycle::new(
+                                    &event.indexer_name,
+                                    &event.contract.name,
+                                    &network_contract.network,
+                                    addresses,
+                                    lifecycle_config,
+                                    Arc::clone(&network_contract.cached_provider),
+                                    database.clone(),
+                                ))
+                            }),
+                    ))
+                }
+                _ => None,
+            };
+
             let event_processing_config = EventProcessingConfig {
                 id: event.id.clone(),
                 project_path: project_path.to_path_buf(),
@@ -187,25 +381,51 @@ pub async fn start_indexing(
                 contract_name: event.contract.name.clone(),
                 info_log_name: event.info_log_name(),
                 topic_id: event.topic_id,
+                anonymous,
                 event_name: event.event_name.clone(),
                 network_contract: Arc::new(network_contract.clone()),
                 start_block,
                 end_block,
-                semaphore: Arc::clone(&semaphore),
+                slots: Arc::clone(&slots),
+                priority: contract.and_then(|c| c.priority).unwrap_or_default(),
                 registry: Arc::clone(&registry),
                 progress: Arc::clone(&event_progress_state),
+                stats,
                 database: database.clone(),
+                unlogged_historic_sync: unlogged_historic_sync.clone(),
+                registry_addresses,
                 csv_details: manifest.storage.csv.clone(),
                 stream_last_synced_block_file_path: stream_details
                     .as_ref()
                     .map(|s| s.get_streams_last_synced_block_path()),
-                live_indexing: if no_live_indexing_forced {
+                live_indexing: if no_live_indexing_forced ||
+                    webhooks.is_some_and(|w| w.source(&network_contract.network).is_some())
+                {
                     false
                 } else {
                     network_contract.is_live_indexing()
                 },
                 index_event_in_order: event.index_event_in_order,
                 indexing_distance_from_head,
+                live_poll_interval: network
+                    .map_or(Duration::from_millis(DEFAULT_BLOCK_POLL_INTERVAL_MS), |n| {
+                        n.block_poll_interval()
+                    }),
+                checkpoint: LastSyncedCheckpoint::new(event.contract.checkpoint.as_ref()),
+                lifecycle,
+                catch_up,
+                catch_up_backfill: catching_up.then(|| Arc::new(CatchUpBackfill::new(end_block))),
+                schedule,
+                heartbeats: manifest.storage.postgres_heartbeats(),
+                live_blocks: Arc::clone(
+                    live_blocks.entry(network_contract.network.clone()).or_insert_with(|| {
+                        Arc::new(LiveBlocks::new(
+                            &network_contract.network,
+                            network,
+                            &network_contract.cached_provider,
+                        ))
+                    }),
+                ),
             };
 
             let dependencies_status = ContractEventDependencies::dependencies_status(
@@ -244,6 +464,19 @@ pub async fn start_indexing(
         }
     }
 
+    if let (Some(stats_details), Some(database)) =
+        (manifest.storage.postgres_indexing_stats(), &database)
+    {
+        start_indexing_stats_flush(
+            Arc::clone(database),
+            indexing_stats,
+            stats_details.interval_seconds,
+        )
+        .await;
+    }
+
+    start_stream_watermarks(manifest, project_path, &registry).await;
+
     // apply dependency events config after processing to avoid ordering issues
     for apply in apply_cross_contract_dependency_events_config_after_processing {
         let (dependency_in_other_contract, event_processing_config) = apply;
@@ -278,6 +511,13 @@ pub async fn start_indexing(
         }));
     }
 
+    // the webhook server keeps running like live indexing does for the networks it serves
+    if let Some(server) = webhook_server {
+        handles.push(tokio::spawn(async {
+            server.await.map_err(CombinedLogEventProcessingError::from)
+        }));
+    }
+
     let results = try_join_all(handles).await?;
 
     for result in results {
@@ -298,7 +538,7 @@ async fn initialize_database(
     manifest: &Manifest,
 ) -> Result<Option<Arc<PostgresClient>>, StartIndexingError> {
     if manifest.storage.postgres_enabled() {
-        match PostgresClient::new().await {
+        match PostgresClient::from_storage(&manifest.storage, &manifest.name).await {
             Ok(postgres) => Ok(Some(Arc::new(postgres))),
             Err(e) => {
                 error!("Error connecting to Postgres: {:?}", e);
@@ -312,6 +552,7 @@ async fn initialize_database(
 
 async fn calculate_safe_block_number(
     reorg_safe_distance: bool,
+    block_processing_delay: u64,
     network_contract: &NetworkContract,
     latest_block: U64,
     mut end_block: U64,
@@ -330,5 +571,12 @@ async fn calculate_safe_block_number(
         }
         indexing_distance_from_head = reorg_safe_distance;
     }
+
+    if block_processing_delay > 0 {
+        indexing_distance_from_head += U64::from(block_processing_delay);
+        end_block =
+            std::cmp::min(end_block, latest_block.saturating_sub(indexing_distance_from_head));
+    }
+
     Ok((end_block, indexing_distance_from_head))
 }
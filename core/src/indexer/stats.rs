@@ -0,0 +1,312 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex as StdMutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use ethers::types::U64;
+use tracing::{debug, error, info};
+
+use crate::{
+    database::postgres::internal_schema::{internal_schema, internal_table},
+    EthereumSqlTypeWrapper, PostgresClient,
+};
+
+fn indexing_stats_table_name() -> String {
+    internal_table("indexing_stats")
+}
+
+/// Throughput counters for a single event on a single network, these get reset every time they
+/// are written to the database so each row in `rindexer_internal.indexing_stats` is a delta
+#[derive(Debug)]
+pub struct IndexingEventStats {
+    pub indexer_name: String,
+    pub contract_name: String,
+    pub event_name: String,
+    pub network: String,
+    rows_written: AtomicU64,
+    blocks_processed: AtomicU64,
+    rpc_calls: AtomicU64,
+    rpc_duration_ms: AtomicU64,
+    processing_duration_ms: AtomicU64,
+    last_processed_block: AtomicU64,
+}
+
+struct IndexingEventStatsSnapshot {
+    rows_written: u64,
+    blocks_processed: u64,
+    rpc_calls: u64,
+    rpc_duration_ms: u64,
+    processing_duration_ms: u64,
+}
+
+impl IndexingEventStatsSnapshot {
+    fn is_empty(&self) -> bool {
+        self.rows_written == 0 &&
+            self.blocks_processed == 0 &&
+            self.rpc_calls == 0 &&
+            self.rpc_duration_ms == 0 &&
+            self.processing_duration_ms == 0
+    }
+}
+
+impl IndexingEventStats {
+    pub fn new(indexer_name: &str, contract_name: &str, event_name: &str, network: &str) -> Self {
+        Self {
+            indexer_name: indexer_name.to_string(),
+            contract_name: contract_name.to_string(),
+            event_name: event_name.to_string(),
+            network: network.to_string(),
+            rows_written: AtomicU64::new(0),
+            blocks_processed: AtomicU64::new(0),
+            rpc_calls: AtomicU64::new(0),
+            rpc_duration_ms: AtomicU64::new(0),
+            processing_duration_ms: AtomicU64::new(0),
+            last_processed_block: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_rpc_call(&self, duration: Duration) {
+        self.rpc_calls.fetch_add(1, Ordering::Relaxed);
+        self.rpc_duration_ms.fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_processed(&self, rows: usize, duration: Duration) {
+        self.rows_written.fetch_add(rows as u64, Ordering::Relaxed);
+        self.processing_duration_ms.fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Block ranges overlap when a fetch is cut short by the last log seen, so only count the
+    /// blocks which have not been counted before
+    pub fn record_blocks(&self, from_block: U64, to_block: U64) {
+        let to_block = to_block.as_u64();
+        let previous = self.last_processed_block.fetch_max(to_block, Ordering::Relaxed);
+        if to_block <= previous {
+            return;
+        }
+
+        let counted_from = std::cmp::max(previous, from_block.as_u64().saturating_sub(1));
+        self.blocks_processed.fetch_add(to_block - counted_from, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> IndexingEventStatsSnapshot {
+        IndexingEventStatsSnapshot {
+            rows_written: self.rows_written.swap(0, Ordering::Relaxed),
+            blocks_processed: self.blocks_processed.swap(0, Ordering::Relaxed),
+            rpc_calls: self.rpc_calls.swap(0, Ordering::Relaxed),
+            rpc_duration_ms: self.rpc_duration_ms.swap(0, Ordering::Relaxed),
+            processing_duration_ms: self.processing_duration_ms.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    /// Puts a taken snapshot back when it could not be written so the next flush includes it
+    fn restore(&self, snapshot: &IndexingEventStatsSnapshot) {
+        self.rows_written.fetch_add(snapshot.rows_written, Ordering::Relaxed);
+        self.blocks_processed.fetch_add(snapshot.blocks_processed, Ordering::Relaxed);
+        self.rpc_calls.fetch_add(snapshot.rpc_calls, Ordering::Relaxed);
+        self.rpc_duration_ms.fetch_add(snapshot.rpc_duration_ms, Ordering::Relaxed);
+        self.processing_duration_ms.fetch_add(snapshot.processing_duration_ms, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn create_indexing_stats_table_sql() -> String {
+    format!(
+        r#"
+        CREATE SCHEMA IF NOT EXISTS {};
+        CREATE TABLE IF NOT EXISTS {} (
+            id SERIAL PRIMARY KEY,
+            indexer_name TEXT NOT NULL,
+            contract_name TEXT NOT NULL,
+            event_name TEXT NOT NULL,
+            network TEXT NOT NULL,
+            rows_written NUMERIC NOT NULL,
+            blocks_processed NUMERIC NOT NULL,
+            rpc_calls NUMERIC NOT NULL,
+            rpc_duration_ms NUMERIC NOT NULL,
+            processing_duration_ms NUMERIC NOT NULL,
+            interval_seconds NUMERIC NOT NULL,
+            recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+        CREATE INDEX IF NOT EXISTS indexing_stats_lookup_idx ON {} (indexer_name, network, event_name, recorded_at);
+    "#,
+        internal_schema(),
+        indexing_stats_table_name(),
+        indexing_stats_table_name()
+    )
+}
+
+async fn flush_indexing_stats(
+    database: &PostgresClient,
+    stats: &[Arc<IndexingEventStats>],
+    interval_seconds: u64,
+) {
+    let snapshots: Vec<(&Arc<IndexingEventStats>, IndexingEventStatsSnapshot)> = stats
+        .iter()
+        .map(|event_stats| (event_stats, event_stats.take()))
+        .filter(|(_, snapshot)| !snapshot.is_empty())
+        .collect();
+
+    let rows: Vec<Vec<EthereumSqlTypeWrapper>> = snapshots
+        .iter()
+        .map(|(event_stats, snapshot)| {
+            vec![
+                EthereumSqlTypeWrapper::String(event_stats.indexer_name.clone()),
+                EthereumSqlTypeWrapper::String(event_stats.contract_name.clone()),
+                EthereumSqlTypeWrapper::String(event_stats.event_name.clone()),
+                EthereumSqlTypeWrapper::String(event_stats.network.clone()),
+                EthereumSqlTypeWrapper::U64(U64::from(snapshot.rows_written)),
+                EthereumSqlTypeWrapper::U64(U64::from(snapshot.blocks_processed)),
+                EthereumSqlTypeWrapper::U64(U64::from(snapshot.rpc_calls)),
+                EthereumSqlTypeWrapper::U64(U64::from(snapshot.rpc_duration_ms)),
+                EthereumSqlTypeWrapper::U64(U64::from(snapshot.processing_duration_ms)),
+                EthereumSqlTypeWrapper::U64(U64::from(interval_seconds)),
+            ]
+        })
+        .collect();
+
+    if rows.is_empty() {
+        debug!("No indexing stats to flush");
+        return;
+    }
+
+    let column_names: Vec<String> = [
+        "indexer_name",
+        "contract_name",
+        "event_name",
+        "network",
+        "rows_written",
+        "blocks_processed",
+        "rpc_calls",
+        "rpc_duration_ms",
+        "processing_duration_ms",
+        "interval_seconds",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect();
+
+    if let Err(e) = database.bulk_insert(&indexing_stats_table_name(), &column_names, &rows).await {
+        error!("Error writing indexing stats, they will be retried on the next flush: {:?}", e);
+        for (event_stats, snapshot) in &snapshots {
+            event_stats.restore(snapshot);
+        }
+    }
+}
+
+struct IndexingStatsFlush {
+    database: Arc<PostgresClient>,
+    stats: Vec<Arc<IndexingEventStats>>,
+    last_flushed: StdMutex<Instant>,
+}
+
+impl IndexingStatsFlush {
+    async fn flush(&self, interval_seconds: u64) {
+        if let Ok(mut last_flushed) = self.last_flushed.lock() {
+            *last_flushed = Instant::now();
+        }
+        flush_indexing_stats(&self.database, &self.stats, interval_seconds).await;
+    }
+
+    fn seconds_since_last_flush(&self) -> u64 {
+        self.last_flushed
+            .lock()
+            .map(|last_flushed| last_flushed.elapsed().as_secs().max(1))
+            .unwrap_or(1)
+    }
+}
+
+fn running_stats_flushes() -> &'static StdMutex<Vec<Arc<IndexingStatsFlush>>> {
+    static RUNNING: OnceLock<StdMutex<Vec<Arc<IndexingStatsFlush>>>> = OnceLock::new();
+    RUNNING.get_or_init(|| StdMutex::new(Vec::new()))
+}
+
+/// Writes the stats counted since the last interval tick, called before shutting down so the last
+/// partial interval is not lost
+pub async fn flush_pending_indexing_stats() {
+    let flushes: Vec<Arc<IndexingStatsFlush>> =
+        running_stats_flushes().lock().map(|running| running.clone()).unwrap_or_default();
+
+    for flush in flushes {
+        flush.flush(flush.seconds_since_last_flush()).await;
+    }
+}
+
+/// Spawns a background task which writes the stats to `rindexer_internal.indexing_stats` on
+/// every interval tick until the process exits
+pub async fn start_indexing_stats_flush(
+    database: Arc<PostgresClient>,
+    stats: Vec<Arc<IndexingEventStats>>,
+    interval_seconds: u64,
+) {
+    if let Err(e) = database
+        .execute_ddl(&create_indexing_stats_table_sql(), "create the indexing stats table")
+        .await
+    {
+        error!("Error creating indexing stats table, stats will not be recorded: {:?}", e);
+        return;
+    }
+
+    info!(
+        "Indexing stats will be written to {} every {}s",
+        indexing_stats_table_name(),
+        interval_seconds
+    );
+
+    let flush = Arc::new(IndexingStatsFlush {
+        database,
+        stats,
+        last_flushed: StdMutex::new(Instant::now()),
+    });
+    if let Ok(mut running) = running_stats_flushes().lock() {
+        running.push(Arc::clone(&flush));
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds.max(1)));
+        // first tick completes straight away
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            flush.flush(interval_seconds).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_blocks_does_not_double_count_overlapping_ranges() {
+        let stats = IndexingEventStats::new("Indexer", "Contract", "Transfer", "ethereum");
+
+        stats.record_blocks(U64::from(100), U64::from(199));
+        stats.record_blocks(U64::from(150), U64::from(249));
+        stats.record_blocks(U64::from(200), U64::from(220));
+
+        let snapshot = stats.take();
+        assert_eq!(snapshot.blocks_processed, 150);
+
+        let snapshot = stats.take();
+        assert!(snapshot.is_empty());
+    }
+
+    #[test]
+    fn test_restore_keeps_a_failed_flush_for_the_next_one() {
+        let stats = IndexingEventStats::new("Indexer", "Contract", "Transfer", "ethereum");
+        stats.record_processed(10, Duration::from_millis(40));
+        stats.record_rpc_call(Duration::from_millis(15));
+
+        let failed = stats.take();
+        stats.record_processed(5, Duration::from_millis(20));
+        stats.restore(&failed);
+
+        let snapshot = stats.take();
+        assert_eq!(snapshot.rows_written, 15);
+        assert_eq!(snapshot.processing_duration_ms, 60);
+        assert_eq!(snapshot.rpc_calls, 1);
+        assert_eq!(snapshot.rpc_duration_ms, 15);
+    }
+}
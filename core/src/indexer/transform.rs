@@ -0,0 +1,147 @@
+use ethers::{
+    types::{H256, U256},
+    utils::{format_units, keccak256},
+};
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use crate::{
+    abi::ABIInput,
+    manifest::transform::{EventInputTransform, HashAlgorithm, ValueTransformType, REDACTED_VALUE},
+    EthereumSqlTypeWrapper,
+};
+
+fn count_leaf_values(input: &ABIInput) -> usize {
+    match &input.components {
+        Some(components) if input.type_ == "tuple" && !input.is_hashed_topic() => {
+            components.iter().map(count_leaf_values).sum()
+        }
+        _ => 1,
+    }
+}
+
+fn wrapper_as_u256(wrapper: &EthereumSqlTypeWrapper) -> Option<U256> {
+    match wrapper {
+        EthereumSqlTypeWrapper::U8(value) => Some(U256::from(*value)),
+        EthereumSqlTypeWrapper::U16(value) => Some(U256::from(*value)),
+        EthereumSqlTypeWrapper::U32(value) => Some(U256::from(*value)),
+        EthereumSqlTypeWrapper::U64(value) => Some(U256::from(value.as_u64())),
+        EthereumSqlTypeWrapper::U128(value) => Some(U256::from(value.as_u128())),
+        EthereumSqlTypeWrapper::U256(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn apply_transform(
+    transform: &EventInputTransform,
+    wrapper: &EthereumSqlTypeWrapper,
+) -> Option<EthereumSqlTypeWrapper> {
+    match transform.transform {
+        ValueTransformType::ScaleDecimals => {
+            let value = wrapper_as_u256(wrapper)?;
+            let decimals = transform.decimals.unwrap_or_default();
+            format_units(value, decimals).ok().map(EthereumSqlTypeWrapper::Numeric)
+        }
+        ValueTransformType::Lowercase => match wrapper {
+            EthereumSqlTypeWrapper::String(value) => {
+                Some(EthereumSqlTypeWrapper::String(value.to_lowercase()))
+            }
+            EthereumSqlTypeWrapper::Address(value) => {
+                Some(EthereumSqlTypeWrapper::String(format!("{:?}", value)))
+            }
+            _ => None,
+        },
+        ValueTransformType::Hash => {
+            let value: &[u8] = match wrapper {
+                EthereumSqlTypeWrapper::String(value) => value.as_bytes(),
+                EthereumSqlTypeWrapper::Bytes(value) => value,
+                EthereumSqlTypeWrapper::H128(value) => value.as_bytes(),
+                EthereumSqlTypeWrapper::H256(value) => value.as_bytes(),
+                _ => return None,
+            };
+            let mut salted = transform.salt.as_deref().unwrap_or_default().as_bytes().to_vec();
+            salted.extend_from_slice(value);
+            let hash: [u8; 32] = match transform.algorithm.unwrap_or_default() {
+                HashAlgorithm::Keccak256 => keccak256(salted),
+                HashAlgorithm::Sha256 => Sha256::digest(salted).into(),
+            };
+            Some(EthereumSqlTypeWrapper::String(format!("{:?}", H256::from(hash))))
+        }
+        ValueTransformType::Redact => {
+            Some(EthereumSqlTypeWrapper::String(REDACTED_VALUE.to_string()))
+        }
+    }
+}
+
+fn transformed_raw_value(wrapper: &EthereumSqlTypeWrapper) -> Option<String> {
+    match wrapper {
+        EthereumSqlTypeWrapper::Numeric(value) | EthereumSqlTypeWrapper::String(value) => {
+            Some(value.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Transforms resolved to the index of the decoded value they apply to, tuples get flattened
+/// when decoded so the index is not the same as the ABI input index. JSONB inputs are a single
+/// decoded value but are still flattened in the csv raw values so both indexes are kept
+#[derive(Debug, Clone, Default)]
+pub struct EventTransforms {
+    transforms: Vec<(usize, usize, EventInputTransform)>,
+}
+
+impl EventTransforms {
+    pub fn new(
+        inputs: &[ABIInput],
+        transforms: Vec<&EventInputTransform>,
+        jsonb_inputs: &[String],
+    ) -> Self {
+        let mut resolved = vec![];
+        let mut value_index = 0;
+        let mut raw_value_index = 0;
+        for input in inputs {
+            if let Some(transform) = transforms.iter().find(|t| t.input_name == input.name) {
+                resolved.push((value_index, raw_value_index, (*transform).clone()));
+            }
+            let leaf_values = count_leaf_values(input);
+            value_index += if jsonb_inputs.contains(&input.name) { 1 } else { leaf_values };
+            raw_value_index += leaf_values;
+        }
+
+        Self { transforms: resolved }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    pub fn apply(&self, wrappers: &mut [EthereumSqlTypeWrapper]) {
+        for (index, _, transform) in &self.transforms {
+            let Some(wrapper) = wrappers.get(*index) else {
+                continue;
+            };
+
+            match apply_transform(transform, wrapper) {
+                Some(transformed) => wrappers[*index] = transformed,
+                None => {
+                    error!(
+                        "Could not apply {:?} transform to {} - value: {:?}",
+                        transform.transform, transform.input_name, wrapper
+                    );
+                }
+            }
+        }
+    }
+
+    /// Keep the csv values in line with the already transformed values
+    pub fn sync_raw_values(&self, wrappers: &[EthereumSqlTypeWrapper], raw_values: &mut [String]) {
+        for (index, raw_index, _) in &self.transforms {
+            if let (Some(raw_value), Some(value)) = (
+                raw_values.get_mut(*raw_index),
+                wrappers.get(*index).and_then(transformed_raw_value),
+            ) {
+                *raw_value = value;
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::{
+    database::postgres::{
+        client::PostgresError,
+        generate::{generate_default_indexes_sql, generate_event_table_full_name_for_network},
+        internal_schema::internal_schema,
+    },
+    helpers::camel_to_snake,
+    manifest::storage::PostgresLayout,
+    PostgresClient,
+};
+
+/// Tracks how many networks are still historic syncing into each UNLOGGED event table, once the
+/// last one is done the table is switched to LOGGED and any deferred default indexes are created
+pub struct UnloggedHistoricSync {
+    database: Arc<PostgresClient>,
+    default_indexes: bool,
+    layout: PostgresLayout,
+    pending: Mutex<HashMap<String, usize>>,
+}
+
+impl UnloggedHistoricSync {
+    pub fn new(
+        database: Arc<PostgresClient>,
+        default_indexes: bool,
+        layout: PostgresLayout,
+    ) -> Self {
+        Self { database, default_indexes, layout, pending: Mutex::new(HashMap::new()) }
+    }
+
+    /// Postgres truncates UNLOGGED tables after a crash while the checkpoints survive, so a table
+    /// still UNLOGGED at startup has the network rows cleared and its checkpoint reset to sync
+    /// it again from the start block
+    pub async fn reset_unlogged_table(
+        &self,
+        indexer_name: &str,
+        contract_name: &str,
+        event_name: &str,
+        network: &str,
+    ) -> Result<(), PostgresError> {
+        let table_name = generate_event_table_full_name_for_network(
+            self.layout,
+            indexer_name,
+            contract_name,
+            event_name,
+            network,
+        );
+        let unlogged = self
+            .database
+            .query_one_or_none(
+                "SELECT 1 FROM pg_class WHERE oid = to_regclass($1) AND relpersistence = 'u'",
+                &[&table_name],
+            )
+            .await?
+            .is_some();
+        if !unlogged {
+            return Ok(());
+        }
+
+        info!(
+            "{} is still UNLOGGED from an unfinished historic sync - resyncing {} from the start",
+            table_name, network
+        );
+        let transaction = self.database.transaction().await?;
+        transaction
+            .execute(&format!("DELETE FROM {} WHERE network = $1", table_name), &[&network])
+            .await?;
+        transaction
+            .execute(
+                &format!(
+                    "UPDATE {}.{}_{}_{} SET last_synced_block = 0 WHERE network = $1",
+                    internal_schema(),
+                    camel_to_snake(indexer_name),
+                    camel_to_snake(contract_name),
+                    camel_to_snake(event_name)
+                ),
+                &[&network],
+            )
+            .await?;
+        transaction.commit().await
+    }
+
+    pub async fn register(
+        &self,
+        indexer_name: &str,
+        contract_name: &str,
+        event_name: &str,
+        network: &str,
+    ) {
+        let table_name = generate_event_table_full_name_for_network(
+            self.layout,
+            indexer_name,
+            contract_name,
+            event_name,
+            network,
+        );
+        *self.pending.lock().await.entry(table_name).or_insert(0) += 1;
+    }
+
+    pub async fn historic_sync_complete(
+        &self,
+        indexer_name: &str,
+        contract_name: &str,
+        event_name: &str,
+        network: &str,
+    ) {
+        let table_name = generate_event_table_full_name_for_network(
+            self.layout,
+            indexer_name,
+            contract_name,
+            event_name,
+            network,
+        );
+        {
+            let mut pending = self.pending.lock().await;
+            let Some(remaining) = pending.get_mut(&table_name) else {
+                return;
+            };
+            *remaining = remaining.saturating_sub(1);
+            if *remaining > 0 {
+                return;
+            }
+            pending.remove(&table_name);
+        }
+
+        let sql = if self.default_indexes {
+            format!(
+                "ALTER TABLE {} SET LOGGED;\n{}",
+                table_name,
+                generate_default_indexes_sql(&table_name)
+            )
+        } else {
+            format!("ALTER TABLE {} SET LOGGED;", table_name)
+        };
+
+        info!("Historic sync complete for {} - switching table to LOGGED", table_name);
+        if let Err(e) = self.database.execute_ddl(&sql, "toggle unlogged historic sync").await {
+            error!("Error switching {} to LOGGED: {:?}", table_name, e);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use ethers::{
+    abi::{decode, ParamType, Token},
+    prelude::ProviderError,
+    types::{Address, H256, U256},
+    utils::id,
+};
+use futures::{stream, StreamExt};
+use tokio::sync::Mutex;
+use tracing::{debug, error};
+
+use crate::{
+    manifest::template::USER_OPERATION_GAS_COLUMNS,
+    provider::{CreateNetworkProvider, JsonRpcCachedProvider},
+    EthereumSqlTypeWrapper,
+};
+
+/// Once the cache gets this big it gets cleared, bundles are only looked up while their events
+/// are being written
+const MAX_CACHED_BUNDLES: usize = 10_000;
+
+/// Bundle lookups running at once while a batch of events is prefetched
+const BUNDLE_FETCH_CONCURRENCY: usize = 10;
+
+const HANDLE_OPS_V06: &str = "handleOps((address,uint256,bytes,bytes,uint256,uint256,uint256,uint256,uint256,bytes,bytes)[],address)";
+const HANDLE_OPS_V07: &str =
+    "handleOps((address,uint256,bytes,bytes,bytes32,uint256,bytes32,bytes,bytes)[],address)";
+const HANDLE_AGGREGATED_OPS_V06: &str = "handleAggregatedOps(((address,uint256,bytes,bytes,uint256,uint256,uint256,uint256,uint256,bytes,bytes)[],address,bytes)[],address)";
+const HANDLE_AGGREGATED_OPS_V07: &str = "handleAggregatedOps(((address,uint256,bytes,bytes,bytes32,uint256,bytes32,bytes,bytes)[],address,bytes)[],address)";
+
+#[derive(thiserror::Error, Debug)]
+pub enum UserOperationDecodeError {
+    #[error("No provider found for network {0}")]
+    NoProviderForNetwork(String),
+
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("Transaction {0} not found")]
+    TransactionNotFound(H256),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct UserOperationGas {
+    sender: Address,
+    nonce: U256,
+    /// In the order of `USER_OPERATION_GAS_COLUMNS`
+    gas: [U256; 5],
+}
+
+/// v0.7 packs two 128 bit values into one word, the high half first
+fn split_packed_word(token: &Token) -> Option<(U256, U256)> {
+    let word = token.clone().into_fixed_bytes()?;
+    if word.len() != 32 {
+        return None;
+    }
+    Some((U256::from_big_endian(&word[..16]), U256::from_big_endian(&word[16..])))
+}
+
+fn user_operation_type(packed: bool) -> ParamType {
+    let bytes = || ParamType::Bytes;
+    let uint = || ParamType::Uint(256);
+    ParamType::Tuple(if packed {
+        vec![
+            ParamType::Address,
+            uint(),
+            bytes(),
+            bytes(),
+            ParamType::FixedBytes(32),
+            uint(),
+            ParamType::FixedBytes(32),
+            bytes(),
+            bytes(),
+        ]
+    } else {
+        vec![
+            ParamType::Address,
+            uint(),
+            bytes(),
+            bytes(),
+            uint(),
+            uint(),
+            uint(),
+            uint(),
+            uint(),
+            bytes(),
+            bytes(),
+        ]
+    })
+}
+
+fn decode_user_operation(operation: Token, packed: bool) -> Option<UserOperationGas> {
+    let fields = operation.into_tuple()?;
+    let uint_at = |index: usize| fields.get(index)?.clone().into_uint();
+    let gas = if packed {
+        let (verification_gas_limit, call_gas_limit) = split_packed_word(fields.get(4)?)?;
+        let (max_priority_fee_per_gas, max_fee_per_gas) = split_packed_word(fields.get(6)?)?;
+        [
+            call_gas_limit,
+            verification_gas_limit,
+            uint_at(5)?,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        ]
+    } else {
+        [uint_at(4)?, uint_at(5)?, uint_at(6)?, uint_at(7)?, uint_at(8)?]
+    };
+
+    Some(UserOperationGas {
+        sender: fields.first()?.clone().into_address()?,
+        nonce: uint_at(1)?,
+        gas,
+    })
+}
+
+/// Every user operation in a `handleOps` or `handleAggregatedOps` call, `None` when the input is
+/// neither
+fn decode_handle_ops(input: &[u8]) -> Option<Vec<UserOperationGas>> {
+    if input.len() < 4 {
+        return None;
+    }
+    let (selector, data) = input.split_at(4);
+
+    let (packed, aggregated) = if selector == id(HANDLE_OPS_V06) {
+        (false, false)
+    } else if selector == id(HANDLE_OPS_V07) {
+        (true, false)
+    } else if selector == id(HANDLE_AGGREGATED_OPS_V06) {
+        (false, true)
+    } else if selector == id(HANDLE_AGGREGATED_OPS_V07) {
+        (true, true)
+    } else {
+        return None;
+    };
+    let operations = ParamType::Array(Box::new(user_operation_type(packed)));
+    let bundle = if aggregated {
+        // each aggregator signs its own list of operations
+        ParamType::Array(Box::new(ParamType::Tuple(vec![
+            operations,
+            ParamType::Address,
+            ParamType::Bytes,
+        ])))
+    } else {
+        operations
+    };
+
+    let bundle =
+        decode(&[bundle, ParamType::Address], data).ok()?.into_iter().next()?.into_array()?;
+    let operations = if aggregated {
+        let mut operations = vec![];
+        for ops_per_aggregator in bundle {
+            operations.extend(ops_per_aggregator.into_tuple()?.into_iter().next()?.into_array()?);
+        }
+        operations
+    } else {
+        bundle
+    };
+
+    operations.into_iter().map(|operation| decode_user_operation(operation, packed)).collect()
+}
+
+/// Fills the gas columns of `UserOperationEvent` rows by decoding the bundle transaction
+pub struct UserOperationDecoder {
+    providers: HashMap<String, Arc<JsonRpcCachedProvider>>,
+    cache: Mutex<HashMap<H256, Arc<Vec<UserOperationGas>>>>,
+}
+
+impl UserOperationDecoder {
+    pub fn new(network_providers: &[CreateNetworkProvider]) -> Self {
+        Self {
+            providers: network_providers
+                .iter()
+                .map(|provider| (provider.network_name.clone(), Arc::clone(&provider.client)))
+                .collect(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn bundle(
+        &self,
+        network: &str,
+        transaction_hash: H256,
+    ) -> Result<Arc<Vec<UserOperationGas>>, UserOperationDecodeError> {
+        if let Some(bundle) = self.cache.lock().await.get(&transaction_hash) {
+            return Ok(Arc::clone(bundle));
+        }
+
+        let provider = self
+            .providers
+            .get(network)
+            .ok_or_else(|| UserOperationDecodeError::NoProviderForNetwork(network.to_string()))?;
+        let transaction = provider
+            .get_transaction(transaction_hash)
+            .await?
+            .ok_or(UserOperationDecodeError::TransactionNotFound(transaction_hash))?;
+
+        // bundles sent through another contract have no handleOps input to decode
+        let bundle = Arc::new(decode_handle_ops(&transaction.input).unwrap_or_else(|| {
+            debug!("Transaction {:?} is not a direct handleOps call", transaction_hash);
+            vec![]
+        }));
+
+        let mut cache = self.cache.lock().await;
+        if cache.len() >= MAX_CACHED_BUNDLES {
+            cache.clear();
+        }
+        cache.insert(transaction_hash, Arc::clone(&bundle));
+
+        Ok(bundle)
+    }
+
+    /// Looks up the bundles of a batch of events at once so `decode` finds them cached, a failed
+    /// lookup is tried again and logged by `decode`
+    pub async fn prefetch(&self, transactions: impl IntoIterator<Item = (String, H256)>) {
+        let missing: HashSet<(String, H256)> = {
+            let cache = self.cache.lock().await;
+            transactions
+                .into_iter()
+                .filter(|(_, transaction_hash)| !cache.contains_key(transaction_hash))
+                .collect()
+        };
+
+        stream::iter(missing)
+            .for_each_concurrent(
+                BUNDLE_FETCH_CONCURRENCY,
+                |(network, transaction_hash)| async move {
+                    let _ = self.bundle(&network, transaction_hash).await;
+                },
+            )
+            .await;
+    }
+
+    /// Returns the gas columns as NUMERIC, anything which can not be decoded is written as NULL
+    /// so it does not hold up indexing
+    pub async fn decode(
+        &self,
+        network: &str,
+        transaction_hash: H256,
+        sender: Option<Address>,
+        nonce: Option<U256>,
+    ) -> Vec<EthereumSqlTypeWrapper> {
+        let gas = match self.bundle(network, transaction_hash).await {
+            Ok(bundle) => bundle
+                .iter()
+                .find(|operation| {
+                    Some(operation.sender) == sender && Some(operation.nonce) == nonce
+                })
+                .map(|operation| operation.gas),
+            Err(e) => {
+                error!(
+                    "Could not decode user operation in {:?} on {}: {}",
+                    transaction_hash, network, e
+                );
+                None
+            }
+        };
+
+        match gas {
+            Some(gas) => gas
+                .iter()
+                .map(|value| EthereumSqlTypeWrapper::NullableNumeric(Some(value.to_string())))
+                .collect(),
+            None => USER_OPERATION_GAS_COLUMNS
+                .iter()
+                .map(|_| EthereumSqlTypeWrapper::NullableNumeric(None))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::encode;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_handle_ops() {
+        let sender = Address::repeat_byte(1);
+        let uint = |value: u64| Token::Uint(U256::from(value));
+        let packed = |high: u64, low: u64| {
+            let mut word = [0u8; 32];
+            word[8..16].copy_from_slice(&high.to_be_bytes());
+            word[24..].copy_from_slice(&low.to_be_bytes());
+            Token::FixedBytes(word.to_vec())
+        };
+
+        let v06 = Token::Tuple(vec![
+            Token::Address(sender),
+            uint(7),
+            Token::Bytes(vec![]),
+            Token::Bytes(vec![1, 2]),
+            uint(100),
+            uint(200),
+            uint(300),
+            uint(400),
+            uint(500),
+            Token::Bytes(vec![]),
+            Token::Bytes(vec![3]),
+        ]);
+        let mut input = id(HANDLE_OPS_V06).to_vec();
+        input.extend(encode(&[Token::Array(vec![v06]), Token::Address(Address::zero())]));
+        assert_eq!(
+            decode_handle_ops(&input),
+            Some(vec![UserOperationGas {
+                sender,
+                nonce: U256::from(7),
+                gas: [100, 200, 300, 400, 500].map(U256::from),
+            }])
+        );
+
+        let v07 = Token::Tuple(vec![
+            Token::Address(sender),
+            uint(8),
+            Token::Bytes(vec![]),
+            Token::Bytes(vec![1, 2]),
+            packed(200, 100),
+            uint(300),
+            packed(500, 400),
+            Token::Bytes(vec![]),
+            Token::Bytes(vec![3]),
+        ]);
+        let mut input = id(HANDLE_OPS_V07).to_vec();
+        input.extend(encode(&[Token::Array(vec![v07.clone()]), Token::Address(Address::zero())]));
+        assert_eq!(
+            decode_handle_ops(&input),
+            Some(vec![UserOperationGas {
+                sender,
+                nonce: U256::from(8),
+                gas: [100, 200, 300, 400, 500].map(U256::from),
+            }])
+        );
+
+        let mut input = id(HANDLE_AGGREGATED_OPS_V07).to_vec();
+        input.extend(encode(&[
+            Token::Array(vec![Token::Tuple(vec![
+                Token::Array(vec![v07]),
+                Token::Address(Address::repeat_byte(2)),
+                Token::Bytes(vec![4]),
+            ])]),
+            Token::Address(Address::zero()),
+        ]));
+        assert_eq!(
+            decode_handle_ops(&input),
+            Some(vec![UserOperationGas {
+                sender,
+                nonce: U256::from(8),
+                gas: [100, 200, 300, 400, 500].map(U256::from),
+            }])
+        );
+
+        assert_eq!(decode_handle_ops(&id("transfer(address,uint256)")), None);
+    }
+}
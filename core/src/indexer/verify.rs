@@ -0,0 +1,776 @@
+use std::{collections::HashSet, str::FromStr, sync::Arc};
+
+use ethers::{
+    prelude::ProviderError,
+    types::{Log, ValueOrArray, H256, U256, U64},
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use rust_decimal::Decimal;
+use tokio::time::{interval_at, Instant};
+use tracing::{error, info, warn};
+
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresConnectionError, PostgresError},
+        generate::generate_event_table_full_name_for_network,
+        internal_schema::{internal_schema, internal_table},
+    },
+    event::{
+        callback_registry::{EventCallbackRegistry, EventCallbackRegistryInformation, EventResult},
+        contract_setup::{IndexingContractSetup, NetworkContract},
+        BuildRindexerFilterError, RindexerEventFilter,
+    },
+    indexer::{
+        deployment_block::DeploymentBlocks, last_synced::get_last_synced_block_number_postgres,
+    },
+    manifest::core::Manifest,
+};
+
+fn repair_ranges_table_name() -> String {
+    internal_table("repair_ranges")
+}
+
+fn verify_progress_table_name() -> String {
+    internal_table("verify_progress")
+}
+
+pub const DEFAULT_VERIFY_WINDOW_BLOCKS: u64 = 10_000;
+
+/// Windows compared against the chain at once
+pub const DEFAULT_VERIFY_CONCURRENCY: usize = 4;
+
+pub(crate) fn create_verify_tables_sql() -> String {
+    format!(
+        r#"
+        CREATE SCHEMA IF NOT EXISTS {};
+        CREATE TABLE IF NOT EXISTS {} (
+            indexer_name TEXT NOT NULL,
+            contract_name TEXT NOT NULL,
+            event_name TEXT NOT NULL,
+            network TEXT NOT NULL,
+            from_block NUMERIC NOT NULL,
+            to_block NUMERIC NOT NULL,
+            missing BIGINT NOT NULL,
+            scheduled_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (indexer_name, contract_name, event_name, network, from_block)
+        );
+        CREATE TABLE IF NOT EXISTS {} (
+            indexer_name TEXT NOT NULL,
+            contract_name TEXT NOT NULL,
+            event_name TEXT NOT NULL,
+            network TEXT NOT NULL,
+            verified_to NUMERIC NOT NULL,
+            verified_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (indexer_name, contract_name, event_name, network)
+        );
+    "#,
+        internal_schema(),
+        repair_ranges_table_name(),
+        verify_progress_table_name()
+    )
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum VerifyError {
+    #[error(
+        "Postgres storage is not enabled - verify compares the chain with the postgres tables"
+    )]
+    PostgresNotEnabled,
+
+    #[error("Could not connect to postgres: {0}")]
+    PostgresConnectionError(#[from] PostgresConnectionError),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("Could not build the log filter: {0}")]
+    BuildFilterError(#[from] BuildRindexerFilterError),
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    pub contract_name: Option<String>,
+    pub event_name: Option<String>,
+    pub network: Option<String>,
+    /// Logs are compared against the rows in windows of this many blocks, the gaps reported are
+    /// whole windows
+    pub window_blocks: u64,
+    /// Windows compared at once
+    pub concurrency: usize,
+    /// Verifies from the start block again instead of from where the last verify got to
+    pub full: bool,
+    /// Runs every scheduled repair through the handlers once verifying is done
+    pub repair: bool,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            contract_name: None,
+            event_name: None,
+            network: None,
+            window_blocks: DEFAULT_VERIFY_WINDOW_BLOCKS,
+            concurrency: DEFAULT_VERIFY_CONCURRENCY,
+            full: false,
+            repair: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventGap {
+    pub contract_name: String,
+    pub event_name: String,
+    pub network: String,
+    pub from_block: U64,
+    pub to_block: U64,
+    /// Logs on chain which have no row in the range
+    pub missing: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Events and networks whose rows were compared against the chain
+    pub verified: usize,
+    pub gaps: Vec<EventGap>,
+    /// Events and networks which could not be verified with the reason
+    pub unverifiable: Vec<String>,
+    pub repaired: Vec<EventGap>,
+    pub repair_failed: Vec<(EventGap, String)>,
+}
+
+fn matches_options(
+    options: &VerifyOptions,
+    contract_name: &str,
+    event_name: &str,
+    network: &str,
+) -> bool {
+    options.contract_name.as_ref().is_none_or(|name| name == contract_name) &&
+        options.event_name.as_ref().is_none_or(|name| name == event_name) &&
+        options.network.as_ref().is_none_or(|name| name == network)
+}
+
+fn event_table_name(
+    manifest: &Manifest,
+    contract_name: &str,
+    event_name: &str,
+    network: &str,
+) -> String {
+    generate_event_table_full_name_for_network(
+        manifest.storage.postgres_layout(),
+        &manifest.name,
+        contract_name,
+        event_name,
+        network,
+    )
+}
+
+/// Inclusive block ranges of `step` blocks covering `from_block` to `to_block`
+fn block_ranges(from_block: u64, to_block: u64, step: u64) -> Vec<(u64, u64)> {
+    let step = step.max(1);
+    let mut ranges = vec![];
+    let mut start = from_block;
+    while start <= to_block {
+        let end = start.saturating_add(step - 1).min(to_block);
+        ranges.push((start, end));
+        if end == u64::MAX {
+            break;
+        }
+        start = end + 1;
+    }
+    ranges
+}
+
+/// Joins windows which follow on from each other into a single range
+fn merge_gap_windows(windows: Vec<(u64, u64, u64)>) -> Vec<(u64, u64, u64)> {
+    let mut merged: Vec<(u64, u64, u64)> = vec![];
+    for (from_block, to_block, missing) in windows {
+        match merged.last_mut() {
+            Some(last) if last.1 + 1 == from_block => {
+                last.1 = to_block;
+                last.2 += missing;
+            }
+            _ => merged.push((from_block, to_block, missing)),
+        }
+    }
+    merged
+}
+
+/// Logs the event filter matches in the range, split by the provider max block range
+pub(crate) async fn fetch_event_logs(
+    event: &EventCallbackRegistryInformation,
+    network_contract: &NetworkContract,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<Log>, VerifyError> {
+    let provider = &network_contract.cached_provider;
+    let step = provider.max_block_range.map_or(to_block - from_block + 1, |range| range.as_u64());
+
+    let mut logs = vec![];
+    for (from_block, to_block) in block_ranges(from_block, to_block, step) {
+        let filter = RindexerEventFilter::new(
+            &event.topic_id,
+            &event.event_name,
+            &network_contract.indexing_contract_setup,
+            U64::from(from_block),
+            U64::from(to_block),
+        )?;
+        logs.extend(
+            provider.get_logs(&filter).await?.into_iter().filter(|log| log.removed != Some(true)),
+        );
+    }
+    Ok(logs)
+}
+
+/// Hashes are `0x` hex in the standard layout and BYTEA, read as `\x` hex, in the compact one
+fn parse_stored_hash(value: &str) -> Option<H256> {
+    let value = value.trim();
+    let hex = value.strip_prefix("\\x").or_else(|| value.strip_prefix("0x")).unwrap_or(value);
+    H256::from_str(hex).ok()
+}
+
+/// The `(tx_hash, log_index)` of every row of the network in the range
+async fn stored_log_keys(
+    postgres: &PostgresClient,
+    table_name: &str,
+    network: &str,
+    from_block: u64,
+    to_block: u64,
+) -> Result<HashSet<(H256, U256)>, PostgresError> {
+    let rows = postgres
+        .query(
+            &format!(
+                "SELECT tx_hash::TEXT, log_index::TEXT FROM {} \
+                WHERE network = $1 AND block_number BETWEEN $2::TEXT::NUMERIC AND $3::TEXT::NUMERIC",
+                table_name
+            ),
+            &[&network, &from_block.to_string(), &to_block.to_string()],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let tx_hash = parse_stored_hash(&row.get::<_, String>(0))?;
+            let log_index = U256::from_dec_str(row.get::<_, String>(1).trim()).ok()?;
+            Some((tx_hash, log_index))
+        })
+        .collect())
+}
+
+/// Logs on chain in the range which have no row, matched on the transaction hash and log index
+/// so rows of other logs can not make up for missing ones
+async fn missing_logs(
+    postgres: &PostgresClient,
+    event: &EventCallbackRegistryInformation,
+    network_contract: &NetworkContract,
+    table_name: &str,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<Log>, VerifyError> {
+    let logs = fetch_event_logs(event, network_contract, from_block, to_block).await?;
+    if logs.is_empty() {
+        return Ok(logs);
+    }
+
+    let stored =
+        stored_log_keys(postgres, table_name, &network_contract.network, from_block, to_block)
+            .await?;
+    Ok(logs
+        .into_iter()
+        .filter(|log| match (log.transaction_hash, log.log_index) {
+            (Some(tx_hash), Some(log_index)) => !stored.contains(&(tx_hash, log_index)),
+            _ => false,
+        })
+        .collect())
+}
+
+/// Compares the rows of every window in the range against the logs on chain, windows with logs
+/// which have no row are gaps
+async fn find_event_gaps(
+    postgres: &PostgresClient,
+    event: &EventCallbackRegistryInformation,
+    network_contract: &NetworkContract,
+    table_name: &str,
+    from_block: u64,
+    to_block: u64,
+    options: &VerifyOptions,
+) -> Result<Vec<(u64, u64, u64)>, VerifyError> {
+    let gap_windows: Vec<Option<(u64, u64, u64)>> =
+        stream::iter(block_ranges(from_block, to_block, options.window_blocks))
+            .map(|(window_from, window_to)| async move {
+                let missing = missing_logs(
+                    postgres,
+                    event,
+                    network_contract,
+                    table_name,
+                    window_from,
+                    window_to,
+                )
+                .await?
+                .len() as u64;
+                Ok::<_, VerifyError>((missing > 0).then_some((window_from, window_to, missing)))
+            })
+            .buffered(options.concurrency.max(1))
+            .try_collect()
+            .await?;
+
+    Ok(merge_gap_windows(gap_windows.into_iter().flatten().collect()))
+}
+
+/// Why the rows of the event can not be compared against the logs on chain
+fn unverifiable_reason(
+    manifest: &Manifest,
+    event: &EventCallbackRegistryInformation,
+    network_contract: &NetworkContract,
+) -> Option<&'static str> {
+    if let Some(contract) = manifest.contracts.iter().find(|c| c.name == event.contract.name) {
+        if contract.event_condition(&event.event_name).is_some() {
+            return Some("its `where` condition leaves logs out of the table");
+        }
+        if contract.anonymous_event_layout(&event.event_name).is_some() {
+            return Some("anonymous events are not matched by topic0");
+        }
+    }
+    if !matches!(network_contract.indexing_contract_setup, IndexingContractSetup::Address(_)) {
+        return Some("filter and factory contracts index addresses only known while indexing");
+    }
+    None
+}
+
+async fn read_verified_to(
+    postgres: &PostgresClient,
+    indexer_name: &str,
+    event: &EventCallbackRegistryInformation,
+    network: &str,
+) -> Result<Option<U64>, PostgresError> {
+    let row = postgres
+        .query_one_or_none(
+            &format!(
+                "SELECT verified_to FROM {} WHERE indexer_name = $1 AND contract_name = $2 AND event_name = $3 AND network = $4",
+                verify_progress_table_name()
+            ),
+            &[&indexer_name, &event.contract.name, &event.event_name, &network],
+        )
+        .await?;
+
+    Ok(row.and_then(|row| U64::from_dec_str(&row.get::<_, Decimal>(0).to_string()).ok()))
+}
+
+async fn record_verified_to(
+    postgres: &PostgresClient,
+    indexer_name: &str,
+    event: &EventCallbackRegistryInformation,
+    network: &str,
+    verified_to: U64,
+) -> Result<(), PostgresError> {
+    postgres
+        .execute(
+            &format!(
+                "INSERT INTO {} (indexer_name, contract_name, event_name, network, verified_to) \
+                VALUES ($1, $2, $3, $4, $5::TEXT::NUMERIC) \
+                ON CONFLICT (indexer_name, contract_name, event_name, network) \
+                DO UPDATE SET verified_to = EXCLUDED.verified_to, verified_at = NOW()",
+                verify_progress_table_name()
+            ),
+            &[
+                &indexer_name,
+                &event.contract.name,
+                &event.event_name,
+                &network,
+                &verified_to.to_string(),
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn schedule_repair(
+    postgres: &PostgresClient,
+    indexer_name: &str,
+    gap: &EventGap,
+) -> Result<(), PostgresError> {
+    postgres
+        .execute(
+            &format!(
+                "INSERT INTO {} (indexer_name, contract_name, event_name, network, from_block, to_block, missing) \
+                VALUES ($1, $2, $3, $4, $5::TEXT::NUMERIC, $6::TEXT::NUMERIC, $7) \
+                ON CONFLICT (indexer_name, contract_name, event_name, network, from_block) \
+                DO UPDATE SET to_block = EXCLUDED.to_block, missing = EXCLUDED.missing, scheduled_at = NOW()",
+                repair_ranges_table_name()
+            ),
+            &[
+                &indexer_name,
+                &gap.contract_name,
+                &gap.event_name,
+                &gap.network,
+                &gap.from_block.to_string(),
+                &gap.to_block.to_string(),
+                &(gap.missing as i64),
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn read_scheduled_repairs(
+    postgres: &PostgresClient,
+    indexer_name: &str,
+    options: &VerifyOptions,
+) -> Result<Vec<EventGap>, PostgresError> {
+    let rows = postgres
+        .query(
+            &format!(
+                "SELECT contract_name, event_name, network, from_block, to_block, missing FROM {} \
+                WHERE indexer_name = $1 \
+                AND ($2::TEXT IS NULL OR contract_name = $2) \
+                AND ($3::TEXT IS NULL OR event_name = $3) \
+                AND ($4::TEXT IS NULL OR network = $4) \
+                ORDER BY contract_name, event_name, network, from_block",
+                repair_ranges_table_name()
+            ),
+            &[&indexer_name, &options.contract_name, &options.event_name, &options.network],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let from_block = U64::from_dec_str(&row.get::<_, Decimal>(3).to_string()).ok()?;
+            let to_block = U64::from_dec_str(&row.get::<_, Decimal>(4).to_string()).ok()?;
+            Some(EventGap {
+                contract_name: row.get(0),
+                event_name: row.get(1),
+                network: row.get(2),
+                from_block,
+                to_block,
+                missing: row.get::<_, i64>(5) as u64,
+            })
+        })
+        .collect())
+}
+
+/// Hands only the logs of the range which still have no row to the handler, rows already there
+/// are left alone so their streams and projections are not fired again. The handler writes the
+/// batch in one transaction, running a repair again after a failure only picks up what is still
+/// missing
+async fn repair_gap(
+    postgres: &PostgresClient,
+    manifest: &Manifest,
+    indexer_name: &str,
+    event: &EventCallbackRegistryInformation,
+    network_contract: Arc<NetworkContract>,
+    gap: &EventGap,
+) -> Result<(), String> {
+    let table_name = event_table_name(manifest, &gap.contract_name, &gap.event_name, &gap.network);
+    let logs = missing_logs(
+        postgres,
+        event,
+        &network_contract,
+        &table_name,
+        gap.from_block.as_u64(),
+        gap.to_block.as_u64(),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    if !logs.is_empty() {
+        let data = logs
+            .into_iter()
+            .map(|log| {
+                EventResult::new(Arc::clone(&network_contract), log, gap.from_block, gap.to_block)
+            })
+            .collect();
+        (event.callback)(data).await?;
+    }
+
+    postgres
+        .execute(
+            &format!(
+                "DELETE FROM {} WHERE indexer_name = $1 AND contract_name = $2 AND event_name = $3 AND network = $4 AND from_block = $5::TEXT::NUMERIC",
+                repair_ranges_table_name()
+            ),
+            &[&indexer_name, &gap.contract_name, &gap.event_name, &gap.network, &gap.from_block.to_string()],
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Compares the rows of each event up to its checkpoint against the logs on chain and schedules
+/// a repair for every range with missing rows, with `repair` the scheduled ranges are indexed
+/// again through the registered handlers. Each run carries on from where the last one got to
+/// unless `full` is set
+pub async fn verify_indexed_events(
+    manifest: &Manifest,
+    registry: &EventCallbackRegistry,
+    options: &VerifyOptions,
+) -> Result<VerifyReport, VerifyError> {
+    if !manifest.storage.postgres_enabled() {
+        return Err(VerifyError::PostgresNotEnabled);
+    }
+
+    let postgres = Arc::new(PostgresClient::from_storage(&manifest.storage, &manifest.name).await?);
+    postgres.execute_ddl(&create_verify_tables_sql(), "create the verify tables").await?;
+
+    let deployment_blocks = DeploymentBlocks::new(Some(Arc::clone(&postgres)));
+    let mut report = VerifyReport::default();
+
+    for event in &registry.events {
+        for network_contract in &event.contract.details {
+            if !matches_options(
+                options,
+                &event.contract.name,
+                &event.event_name,
+                &network_contract.network,
+            ) {
+                continue;
+            }
+            let label = format!("{} on {}", event.info_log_name(), network_contract.network);
+
+            if let Some(reason) = unverifiable_reason(manifest, event, network_contract) {
+                report.unverifiable.push(format!("{} - {}", label, reason));
+                continue;
+            }
+
+            let last_synced_block = get_last_synced_block_number_postgres(
+                &postgres,
+                &event.indexer_name,
+                &event.contract.name,
+                &event.event_name,
+                &network_contract.network,
+            )
+            .await?;
+            let Some(last_synced_block) = last_synced_block else {
+                report.unverifiable.push(format!("{} - nothing synced yet", label));
+                continue;
+            };
+
+            let verified_to = if options.full {
+                None
+            } else {
+                read_verified_to(&postgres, &manifest.name, event, &network_contract.network)
+                    .await?
+            };
+            let start_block = match (
+                verified_to,
+                network_contract.start_block,
+                &network_contract.indexing_contract_setup,
+            ) {
+                (Some(verified_to), _, _) => Some(verified_to + 1),
+                (None, Some(start_block), _) => Some(start_block),
+                (None, None, IndexingContractSetup::Address(details)) => {
+                    let addresses = match &details.address {
+                        ValueOrArray::Value(address) => vec![*address],
+                        ValueOrArray::Array(addresses) => addresses.clone(),
+                    };
+                    deployment_blocks
+                        .detect(
+                            &network_contract.cached_provider,
+                            &network_contract.network,
+                            &addresses,
+                            last_synced_block,
+                        )
+                        .await
+                }
+                _ => None,
+            };
+            let Some(start_block) = start_block else {
+                report.unverifiable.push(format!(
+                    "{} - no start_block and the deployment block could not be found",
+                    label
+                ));
+                continue;
+            };
+            if start_block > last_synced_block {
+                report.verified += 1;
+                continue;
+            }
+
+            info!("{} - verifying blocks {} to {}", label, start_block, last_synced_block);
+            let table_name = event_table_name(
+                manifest,
+                &event.contract.name,
+                &event.event_name,
+                &network_contract.network,
+            );
+            let gaps = find_event_gaps(
+                &postgres,
+                event,
+                network_contract,
+                &table_name,
+                start_block.as_u64(),
+                last_synced_block.as_u64(),
+                options,
+            )
+            .await?;
+
+            for (from_block, to_block, missing) in gaps {
+                let gap = EventGap {
+                    contract_name: event.contract.name.clone(),
+                    event_name: event.event_name.clone(),
+                    network: network_contract.network.clone(),
+                    from_block: U64::from(from_block),
+                    to_block: U64::from(to_block),
+                    missing,
+                };
+                warn!(
+                    "{} - {} rows missing in blocks {} to {}, scheduled a repair",
+                    label, missing, from_block, to_block
+                );
+                schedule_repair(&postgres, &manifest.name, &gap).await?;
+                report.gaps.push(gap);
+            }
+            // the gaps found are scheduled so the next run does not have to look at them again
+            record_verified_to(
+                &postgres,
+                &manifest.name,
+                event,
+                &network_contract.network,
+                last_synced_block,
+            )
+            .await?;
+            report.verified += 1;
+        }
+    }
+
+    if options.repair {
+        for gap in read_scheduled_repairs(&postgres, &manifest.name, options).await? {
+            let registered = registry.events.iter().find_map(|event| {
+                if event.contract.name != gap.contract_name || event.event_name != gap.event_name {
+                    return None;
+                }
+                event
+                    .contract
+                    .details
+                    .iter()
+                    .find(|details| details.network == gap.network)
+                    .map(|details| (event, Arc::new(details.clone())))
+            });
+            let Some((event, network_contract)) = registered else {
+                report.repair_failed.push((gap, "no longer registered".to_string()));
+                continue;
+            };
+
+            match repair_gap(&postgres, manifest, &manifest.name, event, network_contract, &gap)
+                .await
+            {
+                Ok(()) => {
+                    info!(
+                        "{} - repaired blocks {} to {} on {}",
+                        event.info_log_name(),
+                        gap.from_block,
+                        gap.to_block,
+                        gap.network
+                    );
+                    report.repaired.push(gap);
+                }
+                Err(e) => report.repair_failed.push((gap, e)),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Runs verify on the `storage.postgres.verify` interval while indexing, for rust projects this
+/// is how their handlers repair gaps as the CLI can not run them
+pub fn start_verify_schedule(manifest: &Manifest, registry: Arc<EventCallbackRegistry>) {
+    let Some(settings) = manifest.storage.postgres_verify() else {
+        return;
+    };
+    let Some(every) = settings.every() else {
+        error!("Verify every {} is not valid - not verifying", settings.every);
+        return;
+    };
+    let options = VerifyOptions {
+        window_blocks: settings.window_blocks.unwrap_or(DEFAULT_VERIFY_WINDOW_BLOCKS),
+        repair: settings.repair.unwrap_or_default(),
+        ..Default::default()
+    };
+
+    info!("Verifying the indexed events every {}", settings.every);
+    let manifest = manifest.clone();
+    tokio::spawn(async move {
+        // the first run waits so the checkpoints have had a chance to move
+        let mut interval = interval_at(Instant::now() + every, every);
+        loop {
+            interval.tick().await;
+            match verify_indexed_events(&manifest, &registry, &options).await {
+                Ok(report) => {
+                    info!(
+                        "Verified {} events - {} ranges with missing rows, {} repaired, {} repairs failed",
+                        report.verified,
+                        report.gaps.len(),
+                        report.repaired.len(),
+                        report.repair_failed.len()
+                    );
+                    for (gap, e) in &report.repair_failed {
+                        error!(
+                            "Could not repair {}::{} on {} blocks {} to {}: {}",
+                            gap.contract_name,
+                            gap.event_name,
+                            gap.network,
+                            gap.from_block,
+                            gap.to_block,
+                            e
+                        );
+                    }
+                }
+                Err(e) => error!("Could not verify the indexed events: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_ranges() {
+        assert_eq!(block_ranges(10, 34, 10), vec![(10, 19), (20, 29), (30, 34)]);
+        assert_eq!(block_ranges(5, 5, 10), vec![(5, 5)]);
+        assert!(block_ranges(6, 5, 10).is_empty());
+    }
+
+    #[test]
+    fn test_merge_gap_windows() {
+        assert_eq!(
+            merge_gap_windows(vec![(0, 9, 1), (10, 19, 2), (30, 39, 4)]),
+            vec![(0, 19, 3), (30, 39, 4)]
+        );
+    }
+
+    #[test]
+    fn test_parse_stored_hash() {
+        let hash = H256::from_low_u64_be(0xabc);
+        let hex = format!("{:x}", hash);
+        assert_eq!(parse_stored_hash(&format!("0x{}", hex)), Some(hash));
+        assert_eq!(parse_stored_hash(&format!("\\x{}", hex)), Some(hash));
+        assert_eq!(parse_stored_hash("not a hash"), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database in DATABASE_URL"]
+    async fn test_stored_log_keys() {
+        let postgres = PostgresClient::new().await.expect("DATABASE_URL has to point to postgres");
+        let hash = H256::from_low_u64_be(0xabc);
+        postgres
+            .batch_execute(&format!(
+                "DROP TABLE IF EXISTS verify_test_events; \
+                CREATE TABLE verify_test_events (tx_hash BYTEA, block_number NUMERIC, network TEXT, log_index VARCHAR(78)); \
+                INSERT INTO verify_test_events VALUES ('\\x{hash:x}', 10, 'ethereum', '3'), ('\\x{hash:x}', 30, 'ethereum', '4'), ('\\x{hash:x}', 10, 'base', '5');",
+            ))
+            .await
+            .unwrap();
+
+        let keys =
+            stored_log_keys(&postgres, "verify_test_events", "ethereum", 0, 20).await.unwrap();
+        assert_eq!(keys, HashSet::from([(hash, U256::from(3))]));
+
+        postgres.batch_execute("DROP TABLE verify_test_events").await.unwrap();
+    }
+}
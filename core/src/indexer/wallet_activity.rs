@@ -0,0 +1,378 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use ethers::{
+    abi::{Abi, Event},
+    prelude::ProviderError,
+    types::{Address, Log, H256, U64},
+};
+use serde_json::Value;
+use tracing::{error, info};
+
+use crate::{
+    abi::{ABIItem, EventInfo, ParamTypeError, ReadAbiError},
+    database::postgres::{
+        client::{BulkInsertPostgresError, PostgresClient, PostgresError},
+        sql_type_wrapper::{
+            map_ethereum_wrapper_to_json, map_log_params_to_ethereum_wrapper,
+            EthereumSqlTypeWrapper,
+        },
+        wallet_activity::{
+            update_wallet_activity_last_synced_sql, wallet_activity_last_synced,
+            wallet_activity_table_name, WALLET_ACTIVITY_COLUMNS, WALLET_ACTIVITY_DECODED_COLUMNS,
+        },
+    },
+    event::{callback_registry::TxInformation, RindexerEventFilter},
+    indexer::{log_helpers::parse_log, reorg::reorg_safe_distance_for_chain},
+    manifest::{
+        core::Manifest,
+        storage::InsertStrategy,
+        wallet::{WalletActivity, WalletNetwork},
+    },
+    provider::{CreateNetworkProvider, JsonRpcCachedProvider},
+};
+
+/// Used when the network has no max block range set
+const DEFAULT_WALLET_BLOCK_RANGE: u64 = 2_000;
+
+const WALLET_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(thiserror::Error, Debug)]
+pub enum WalletActivityError {
+    #[error("Could not read ABI items: {0}")]
+    CouldNotReadAbiItems(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
+
+    #[error("Could not read ABI {0}: {1}")]
+    CouldNotReadAbi(String, String),
+
+    #[error("Wallet network {0} is not a configured network")]
+    UnknownNetwork(String),
+
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("{0}")]
+    BulkInsertError(#[from] BulkInsertPostgresError),
+}
+
+struct KnownEvent {
+    contract_name: String,
+    event_info: EventInfo,
+    event: Event,
+}
+
+/// Every event in the manifest ABIs keyed by topic0, the same signature can be in more than one
+/// ABI with different indexed inputs so all of them are tried
+fn known_events(
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<HashMap<H256, Vec<KnownEvent>>, WalletActivityError> {
+    let mut known_events: HashMap<H256, Vec<KnownEvent>> = HashMap::new();
+    for contract in &manifest.contracts {
+        let abi: Abi = contract
+            .read_abi(project_path)
+            .map_err(|e| e.to_string())
+            .and_then(|abi| serde_json::from_str(&abi).map_err(|e| e.to_string()))
+            .map_err(|e| WalletActivityError::CouldNotReadAbi(contract.abi.clone(), e))?;
+
+        let abi_items = ABIItem::read_abi_items(project_path, contract)?;
+        for event_info in ABIItem::extract_event_names_and_signatures_from_abi(abi_items)? {
+            let topic_id = event_info.topic_id();
+            let Some(event) = abi.events().find(|event| event.signature() == topic_id) else {
+                continue;
+            };
+            known_events.entry(topic_id).or_default().push(KnownEvent {
+                contract_name: contract.name.clone(),
+                event: event.clone(),
+                event_info,
+            });
+        }
+    }
+
+    Ok(known_events)
+}
+
+struct WalletActivityIndexer {
+    indexer_name: String,
+    wallets: HashSet<Address>,
+    known_events: HashMap<H256, Vec<KnownEvent>>,
+    postgres: Arc<PostgresClient>,
+    insert_strategy: InsertStrategy,
+}
+
+impl WalletActivityIndexer {
+    /// How the log matched each wallet it involves, the emitter first then topics in order
+    fn matches(&self, log: &Log) -> Vec<(Address, String)> {
+        let mut matches = vec![];
+        let mut matched = HashSet::new();
+        if self.wallets.contains(&log.address) && matched.insert(log.address) {
+            matches.push((log.address, "address".to_string()));
+        }
+        for (position, topic) in log.topics.iter().enumerate().skip(1) {
+            // addresses are left padded to 32 bytes in topics
+            if topic.as_bytes()[..12].iter().any(|byte| *byte != 0) {
+                continue;
+            }
+            let address = Address::from(*topic);
+            if self.wallets.contains(&address) && matched.insert(address) {
+                matches.push((address, format!("topic{}", position)));
+            }
+        }
+        matches
+    }
+
+    fn decode(&self, log: &Log, tx_information: &TxInformation) -> Option<(String, String, Value)> {
+        let candidates = self.known_events.get(log.topics.first()?)?;
+        candidates.iter().find_map(|known| {
+            let parsed = parse_log(&known.event, log)?;
+            let wrappers =
+                map_log_params_to_ethereum_wrapper(&known.event_info.inputs, &parsed.params, &[])
+                    .ok()?;
+            let mut decoded = map_ethereum_wrapper_to_json(
+                &known.event_info.inputs,
+                &wrappers,
+                tx_information,
+                false,
+            );
+            if let Value::Object(values) = &mut decoded {
+                values.remove("transaction_information");
+            }
+            Some((known.contract_name.clone(), known.event_info.name.clone(), decoded))
+        })
+    }
+
+    async fn fetch_logs(
+        &self,
+        provider: &JsonRpcCachedProvider,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<Vec<Log>, ProviderError> {
+        let wallets: Vec<Address> = self.wallets.iter().copied().collect();
+
+        // a single filter can not OR across the address and topic positions
+        let mut logs = vec![];
+        for topic_position in [None, Some(1), Some(2), Some(3)] {
+            let filter = RindexerEventFilter::new_wallet_activity(
+                &wallets,
+                topic_position,
+                from_block,
+                to_block,
+            );
+            logs.extend(provider.get_logs(&filter).await?);
+        }
+
+        let mut seen = HashSet::new();
+        logs.retain(|log| seen.insert((log.transaction_hash, log.log_index)));
+        Ok(logs)
+    }
+
+    async fn index_range(
+        &self,
+        network: &str,
+        provider: &JsonRpcCachedProvider,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<usize, WalletActivityError> {
+        let logs = self.fetch_logs(provider, from_block, to_block).await?;
+
+        let mut raw_rows = vec![];
+        let mut decoded_rows = vec![];
+        for log in &logs {
+            let (
+                Some(transaction_hash),
+                Some(block_number),
+                Some(block_hash),
+                Some(transaction_index),
+                Some(log_index),
+            ) = (
+                log.transaction_hash,
+                log.block_number,
+                log.block_hash,
+                log.transaction_index,
+                log.log_index,
+            )
+            else {
+                continue;
+            };
+            let tx_information = TxInformation {
+                network: network.to_string(),
+                address: log.address,
+                block_hash,
+                block_number,
+                transaction_hash,
+                log_index,
+                transaction_index,
+                removed: false,
+                after_code_removed: false,
+            };
+            let decoded = self.decode(log, &tx_information);
+
+            for (wallet, matched_by) in self.matches(log) {
+                let mut row = vec![
+                    EthereumSqlTypeWrapper::Address(wallet),
+                    EthereumSqlTypeWrapper::String(network.to_string()),
+                    EthereumSqlTypeWrapper::U64(block_number),
+                    EthereumSqlTypeWrapper::H256(block_hash),
+                    EthereumSqlTypeWrapper::H256(transaction_hash),
+                    EthereumSqlTypeWrapper::U64(transaction_index),
+                    EthereumSqlTypeWrapper::U256(log_index),
+                    EthereumSqlTypeWrapper::Address(log.address),
+                    EthereumSqlTypeWrapper::String(matched_by),
+                    EthereumSqlTypeWrapper::VecString(
+                        log.topics.iter().map(|topic| format!("{:?}", topic)).collect(),
+                    ),
+                    EthereumSqlTypeWrapper::String(format!("{}", log.data)),
+                ];
+                match &decoded {
+                    Some((contract_name, event_name, values)) => {
+                        row.push(EthereumSqlTypeWrapper::String(contract_name.clone()));
+                        row.push(EthereumSqlTypeWrapper::String(event_name.clone()));
+                        row.push(EthereumSqlTypeWrapper::JSONB(values.clone()));
+                        decoded_rows.push(row);
+                    }
+                    None => raw_rows.push(row),
+                }
+            }
+        }
+
+        let table_name = wallet_activity_table_name(&self.indexer_name);
+        let raw_columns: Vec<String> =
+            WALLET_ACTIVITY_COLUMNS.iter().map(|column| column.to_string()).collect();
+        let mut decoded_columns = raw_columns.clone();
+        decoded_columns.extend(WALLET_ACTIVITY_DECODED_COLUMNS.iter().map(|c| c.to_string()));
+
+        self.postgres
+            .insert_bulk(&table_name, &raw_columns, &raw_rows, self.insert_strategy, true)
+            .await?;
+        // progress is written with the last rows so a restart carries on from here
+        self.postgres
+            .insert_bulk_with_statements(
+                &table_name,
+                &decoded_columns,
+                &decoded_rows,
+                true,
+                &[],
+                &[(
+                    update_wallet_activity_last_synced_sql(&self.indexer_name),
+                    vec![Some(network.to_string()), Some(to_block.to_string())],
+                )],
+            )
+            .await?;
+
+        Ok(raw_rows.len() + decoded_rows.len())
+    }
+
+    async fn index_network(
+        self: Arc<Self>,
+        network: WalletNetwork,
+        provider: Arc<JsonRpcCachedProvider>,
+    ) -> Result<(), WalletActivityError> {
+        let reorg_safe_distance = reorg_safe_distance_for_chain(&provider.get_chain_id().await?);
+        let block_range =
+            provider.max_block_range.map_or(DEFAULT_WALLET_BLOCK_RANGE, |r| r.as_u64());
+
+        let last_synced =
+            wallet_activity_last_synced(&self.postgres, &self.indexer_name, &network.network)
+                .await?;
+        let mut from_block = match (last_synced, network.start_block) {
+            (Some(last_synced), _) => last_synced + 1,
+            (None, Some(start_block)) => start_block,
+            (None, None) => provider.get_block_number().await?.saturating_sub(reorg_safe_distance),
+        };
+
+        loop {
+            if network.end_block.is_some_and(|end_block| from_block > end_block) {
+                info!("Wallet activity for {} is complete", network.network);
+                return Ok(());
+            }
+
+            let safe_block = match provider.get_block_number().await {
+                Ok(latest) => latest.saturating_sub(reorg_safe_distance),
+                Err(e) => {
+                    error!(
+                        "Wallet activity for {} could not get the latest block: {}",
+                        network.network, e
+                    );
+                    tokio::time::sleep(WALLET_POLL_INTERVAL).await;
+                    continue;
+                }
+            };
+            let last_block = network.end_block.map_or(safe_block, |end| end.min(safe_block));
+            if from_block > last_block {
+                tokio::time::sleep(WALLET_POLL_INTERVAL).await;
+                continue;
+            }
+
+            let to_block = last_block.min(from_block + block_range - 1);
+            match self.index_range(&network.network, &provider, from_block, to_block).await {
+                Ok(indexed) => {
+                    if indexed > 0 {
+                        info!(
+                            "Wallet activity - {} - {} logs - blocks: {} - {}",
+                            network.network, indexed, from_block, to_block
+                        );
+                    }
+                    from_block = to_block + 1;
+                }
+                Err(e) => {
+                    error!(
+                        "Wallet activity for {} failed for blocks {} - {}: {}",
+                        network.network, from_block, to_block, e
+                    );
+                    tokio::time::sleep(WALLET_POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+}
+
+/// Starts a task per wallet network which catches up and then follows the chain
+pub fn start_wallet_activity_indexing(
+    project_path: &Path,
+    manifest: &Manifest,
+    wallet_activity: &WalletActivity,
+    postgres: Arc<PostgresClient>,
+    network_providers: &[CreateNetworkProvider],
+) -> Result<(), WalletActivityError> {
+    let indexer = Arc::new(WalletActivityIndexer {
+        indexer_name: manifest.name.clone(),
+        wallets: wallet_activity.wallets.iter().map(|wallet| wallet.address).collect(),
+        known_events: known_events(project_path, manifest)?,
+        postgres,
+        insert_strategy: manifest.storage.postgres_insert_strategy(),
+    });
+
+    for network in &wallet_activity.networks {
+        let provider = network_providers
+            .iter()
+            .find(|provider| provider.network_name == network.network)
+            .map(|provider| Arc::clone(&provider.client))
+            .ok_or_else(|| WalletActivityError::UnknownNetwork(network.network.clone()))?;
+
+        info!(
+            "Indexing wallet activity for {} wallets on {}",
+            wallet_activity.wallets.len(),
+            network.network
+        );
+        let indexer = Arc::clone(&indexer);
+        let network = network.clone();
+        tokio::spawn(async move {
+            let network_name = network.network.clone();
+            if let Err(e) = indexer.index_network(network, provider).await {
+                error!("Wallet activity for {} stopped: {}", network_name, e);
+            }
+        });
+    }
+
+    Ok(())
+}
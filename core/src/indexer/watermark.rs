@@ -0,0 +1,154 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ethers::types::U64;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    event::callback_registry::EventCallbackRegistry,
+    manifest::core::Manifest,
+    provider::JsonRpcCachedProvider,
+    streams::{
+        contract_streams_clients, has_spilled_batches, published_blocks, StreamWatermark,
+        StreamsClients,
+    },
+};
+
+struct NetworkWatermark {
+    network: String,
+    // one per event of the contract on the network
+    network_contract_ids: Vec<String>,
+    provider: Arc<JsonRpcCachedProvider>,
+    last_published: Option<U64>,
+}
+
+impl NetworkWatermark {
+    /// The block every event of the contract on the network has been published up to
+    fn published_up_to(&self) -> Option<U64> {
+        self.network_contract_ids
+            .iter()
+            .map(|id| published_blocks().published_up_to(id))
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .min()
+    }
+}
+
+struct ContractWatermarks {
+    contract_name: String,
+    clients: Arc<StreamsClients>,
+    spill_root: Option<PathBuf>,
+    networks: Vec<NetworkWatermark>,
+}
+
+impl ContractWatermarks {
+    async fn publish(&mut self) {
+        for network in &mut self.networks {
+            let Some(block_number) = network.published_up_to() else {
+                continue;
+            };
+            if network.last_published.is_some_and(|last| block_number <= last) {
+                continue;
+            }
+            if self
+                .spill_root
+                .as_ref()
+                .is_some_and(|root| has_spilled_batches(root, &self.contract_name))
+            {
+                debug!(
+                    "Holding back {} watermarks until its spilled stream batches are delivered",
+                    self.contract_name
+                );
+                return;
+            }
+
+            let block_timestamp = match network.provider.get_block_header(block_number).await {
+                Ok(block) => Some(block.timestamp.as_u64()),
+                Err(e) => {
+                    warn!(
+                        "Could not get block {} on {} for the {} watermark: {}",
+                        block_number, network.network, self.contract_name, e
+                    );
+                    None
+                }
+            };
+
+            let watermark = StreamWatermark::new(
+                &self.contract_name,
+                &network.network,
+                block_number.as_u64(),
+                block_timestamp,
+            );
+            match self.clients.publish_watermark(&watermark).await {
+                Ok(()) => network.last_published = Some(block_number),
+                Err(e) => error!(
+                    "Could not publish {} watermark for {} at block {}: {}",
+                    self.contract_name, network.network, block_number, e
+                ),
+            }
+        }
+    }
+}
+
+/// Publishes a watermark to the streams of every contract which configures one whenever all of
+/// its events on a network have been published further
+pub async fn start_stream_watermarks(
+    manifest: &Manifest,
+    project_path: &Path,
+    registry: &EventCallbackRegistry,
+) {
+    for contract in &manifest.contracts {
+        let Some(streams) = &contract.streams else {
+            continue;
+        };
+        let Some(watermark) = &streams.watermark else {
+            continue;
+        };
+
+        let mut networks: BTreeMap<String, NetworkWatermark> = BTreeMap::new();
+        for event in registry.events.iter().filter(|event| event.contract.name == contract.name) {
+            for network_contract in &event.contract.details {
+                networks
+                    .entry(network_contract.network.clone())
+                    .or_insert_with(|| NetworkWatermark {
+                        network: network_contract.network.clone(),
+                        network_contract_ids: vec![],
+                        provider: Arc::clone(&network_contract.cached_provider),
+                        last_published: None,
+                    })
+                    .network_contract_ids
+                    .push(network_contract.id.clone());
+            }
+        }
+        if networks.is_empty() {
+            continue;
+        }
+        let Some(clients) = contract_streams_clients().get(&contract.name) else {
+            debug!("{} has no stream clients to publish watermarks through", contract.name);
+            continue;
+        };
+
+        let mut watermarks = ContractWatermarks {
+            contract_name: contract.name.clone(),
+            clients,
+            spill_root: streams.spill.as_ref().map(|spill| project_path.join(spill.path())),
+            networks: networks.into_values().collect(),
+        };
+
+        let interval = watermark.interval();
+        info!("{} stream watermarks will be published every {:?}", contract.name, interval);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            // first tick completes straight away
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                watermarks.publish().await;
+            }
+        });
+    }
+}
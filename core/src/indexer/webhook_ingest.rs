@@ -0,0 +1,572 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ethers::types::{Address, Bytes, Log, H256, U256, U64};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tokio::{net::TcpStream, sync::Mutex, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::{
+    api::http::{read_request, serve, write_response, HttpRequest},
+    database::postgres::client::{PostgresClient, PostgresError},
+    event::{
+        callback_registry::{EventCallbackRegistry, EventResult},
+        RindexerEventFilter,
+    },
+    indexer::{
+        last_synced::{
+            get_last_synced_block_number_postgres, update_last_synced_block_number_postgres,
+        },
+        progress::IndexingEventsProgressState,
+        verify::fetch_event_logs,
+    },
+    manifest::webhook::{WebhookIngestSettings, WebhookProvider, WebhookSource},
+};
+
+/// Deliveries bigger than this are refused before their body is read
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum StartWebhookIngestError {
+    #[error("Could not bind webhook ingest to port {0}: {1}")]
+    CouldNotBind(u16, std::io::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+enum WebhookIngestError {
+    #[error("No webhook source for network {0}")]
+    UnknownNetwork(String),
+
+    #[error("Missing or invalid {0} signature")]
+    InvalidSignature(&'static str),
+
+    #[error("Invalid payload: {0}")]
+    InvalidPayload(String),
+
+    #[error("{0} is still syncing historic blocks on {1}")]
+    NotReady(String, String),
+
+    #[error("Could not read or write the last synced block: {0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("Could not backfill {0} blocks {1} to {2}: {3}")]
+    BackfillError(String, u64, u64, String),
+
+    #[error("{0} handler failed: {1}")]
+    HandlerError(String, String),
+}
+
+impl WebhookIngestError {
+    fn status(&self) -> &'static str {
+        match self {
+            WebhookIngestError::UnknownNetwork(_) => "404 Not Found",
+            WebhookIngestError::InvalidSignature(_) => "401 Unauthorized",
+            WebhookIngestError::InvalidPayload(_) => "400 Bad Request",
+            // providers retry failed deliveries so anything missed is backfilled on the next one
+            WebhookIngestError::NotReady(_, _) => "503 Service Unavailable",
+            _ => "500 Internal Server Error",
+        }
+    }
+}
+
+/// The signature is checked in constant time so it can not be guessed from how long the check
+/// takes
+fn signature_matches(signing_key: &str, body: &[u8], signature: &str) -> bool {
+    let signature = signature.trim();
+    let Ok(signature) = hex::decode(signature.strip_prefix("sha256=").unwrap_or(signature)) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(signing_key.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[derive(Deserialize)]
+struct AlchemyPayload {
+    event: AlchemyEvent,
+}
+
+#[derive(Deserialize)]
+struct AlchemyEvent {
+    data: AlchemyData,
+}
+
+#[derive(Deserialize)]
+struct AlchemyData {
+    block: AlchemyBlock,
+}
+
+#[derive(Deserialize)]
+struct AlchemyBlock {
+    hash: H256,
+    number: u64,
+    #[serde(default)]
+    logs: Vec<AlchemyLog>,
+}
+
+#[derive(Deserialize)]
+struct AlchemyLog {
+    data: Bytes,
+    topics: Vec<H256>,
+    index: u64,
+    account: AlchemyAccount,
+    transaction: AlchemyTransaction,
+}
+
+#[derive(Deserialize)]
+struct AlchemyAccount {
+    address: Address,
+}
+
+#[derive(Deserialize)]
+struct AlchemyTransaction {
+    hash: H256,
+    index: u64,
+}
+
+#[derive(Deserialize)]
+struct LogsPayload {
+    /// Block the delivery is for, defaults to the highest block of its logs
+    #[serde(default)]
+    block_number: Option<U64>,
+    logs: Vec<Log>,
+}
+
+/// The logs of a delivery and the block the chain is known to be at
+#[derive(Debug)]
+struct WebhookDelivery {
+    to_block: u64,
+    logs: Vec<Log>,
+}
+
+impl WebhookDelivery {
+    fn parse(provider: WebhookProvider, body: &[u8]) -> Result<Option<Self>, WebhookIngestError> {
+        let invalid = |e: serde_json::Error| WebhookIngestError::InvalidPayload(e.to_string());
+        let (to_block, logs) = match provider {
+            WebhookProvider::Alchemy => {
+                let block = serde_json::from_slice::<AlchemyPayload>(body)
+                    .map_err(invalid)?
+                    .event
+                    .data
+                    .block;
+                let logs = block
+                    .logs
+                    .into_iter()
+                    .map(|log| Log {
+                        address: log.account.address,
+                        topics: log.topics,
+                        data: log.data,
+                        block_hash: Some(block.hash),
+                        block_number: Some(U64::from(block.number)),
+                        transaction_hash: Some(log.transaction.hash),
+                        transaction_index: Some(U64::from(log.transaction.index)),
+                        log_index: Some(U256::from(log.index)),
+                        removed: Some(false),
+                        ..Default::default()
+                    })
+                    .collect();
+                (Some(block.number), logs)
+            }
+            WebhookProvider::Logs => {
+                let payload = serde_json::from_slice::<LogsPayload>(body).map_err(invalid)?;
+                if payload.logs.iter().any(|log| log.block_number.is_none()) {
+                    return Err(WebhookIngestError::InvalidPayload(
+                        "every log needs a blockNumber".to_string(),
+                    ));
+                }
+                let to_block = payload.block_number.map(|block| block.as_u64()).or_else(|| {
+                    payload.logs.iter().filter_map(|log| log.block_number).max().map(|b| b.as_u64())
+                });
+                (to_block, payload.logs)
+            }
+        };
+
+        Ok(to_block.map(|to_block| WebhookDelivery { to_block, logs }))
+    }
+
+    /// Lowest block the delivery has logs for, blocks before it are backfilled
+    fn lowest_block(&self) -> u64 {
+        self.logs
+            .iter()
+            .filter(|log| log.removed != Some(true))
+            .filter_map(|log| log.block_number)
+            .map(|block| block.as_u64())
+            .min()
+            .map_or(self.to_block, |block| block.min(self.to_block))
+    }
+}
+
+struct WebhookReceiver {
+    sources: HashMap<String, WebhookSource>,
+    registry: Arc<EventCallbackRegistry>,
+    database: Arc<PostgresClient>,
+    progress: Arc<Mutex<IndexingEventsProgressState>>,
+    /// Deliveries of a network are handled one at a time so their backfills do not overlap
+    network_locks: HashMap<String, Mutex<()>>,
+}
+
+impl WebhookReceiver {
+    /// Deliveries are refused until the historic sync of every event on the network reached the
+    /// head, otherwise the backfill would write the same blocks as the historic sync
+    async fn ensure_ready(&self, network: &str) -> Result<(), WebhookIngestError> {
+        let progress = self.progress.lock().await;
+        for event in progress.events.iter().filter(|event| event.network == network) {
+            if event.progress < 1.0 && event.last_synced_block < event.syncing_to_block {
+                return Err(WebhookIngestError::NotReady(
+                    event.info_log.clone(),
+                    network.to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs every event on the network from its last synced block up to the delivered block, or
+    /// the reorg safe block before it. Events of contracts the source covers take the delivered
+    /// logs with the blocks before them fetched from the provider, every other event fetches its
+    /// whole range. The last synced block only moves once the handler succeeded so a failed
+    /// delivery is picked up again by the next one
+    async fn ingest(
+        &self,
+        source: &WebhookSource,
+        delivery: &WebhookDelivery,
+    ) -> Result<(), WebhookIngestError> {
+        let network = source.network.as_str();
+        for event in &self.registry.events {
+            let covered = source.covers(&event.contract.name);
+            for network_contract in event
+                .contract
+                .details
+                .iter()
+                .filter(|details| details.network == network && details.is_live_indexing())
+            {
+                let backfill_error = |from_block: u64, to_block: u64, e: String| {
+                    WebhookIngestError::BackfillError(
+                        event.info_log_name(),
+                        from_block,
+                        to_block,
+                        e,
+                    )
+                };
+
+                let to_block = if event.contract.reorg_safe_distance {
+                    let distance =
+                        network_contract.cached_provider.reorg_safe_distance().await.map_err(
+                            |e| backfill_error(delivery.to_block, delivery.to_block, e.to_string()),
+                        )?;
+                    // logs past the safe block are fetched again once a later delivery passes them
+                    delivery.to_block.saturating_sub(distance.as_u64())
+                } else {
+                    delivery.to_block
+                };
+
+                let last_synced_block = get_last_synced_block_number_postgres(
+                    &self.database,
+                    &event.indexer_name,
+                    &event.contract.name,
+                    &event.event_name,
+                    network,
+                )
+                .await?;
+                let from_block = last_synced_block.map_or_else(
+                    || delivery.lowest_block().min(to_block),
+                    |block| block.as_u64() + 1,
+                );
+
+                let filter = RindexerEventFilter::new(
+                    &event.topic_id,
+                    &event.event_name,
+                    &network_contract.indexing_contract_setup,
+                    U64::from(from_block),
+                    U64::from(to_block.max(from_block)),
+                )
+                .map_err(|e| backfill_error(from_block, to_block, e.to_string()))?;
+
+                // logs a reorg removed from blocks already indexed are handed to the handler
+                // flagged as removed so it retracts them, the logs replacing them come with them
+                let removed: Vec<Log> = delivery
+                    .logs
+                    .iter()
+                    .filter(|log| {
+                        covered &&
+                            log.removed == Some(true) &&
+                            log.block_number.is_some_and(|block| block.as_u64() < from_block) &&
+                            filter.matches(log)
+                    })
+                    .cloned()
+                    .collect();
+                let replaced_from = removed
+                    .iter()
+                    .filter_map(|log| log.block_number)
+                    .map(|block| block.as_u64())
+                    .min()
+                    .unwrap_or(from_block);
+                if removed.is_empty() && from_block > to_block {
+                    continue;
+                }
+
+                let mut logs = vec![];
+                if from_block <= to_block {
+                    let backfill_to = if covered {
+                        delivery.lowest_block().min(to_block + 1)
+                    } else {
+                        to_block + 1
+                    };
+                    if from_block < backfill_to {
+                        let backfill_to = backfill_to - 1;
+                        info!(
+                            "{} - network {} - backfilling blocks {} to {} the webhook did not deliver",
+                            event.info_log_name(),
+                            network,
+                            from_block,
+                            backfill_to
+                        );
+                        logs = fetch_event_logs(event, network_contract, from_block, backfill_to)
+                            .await
+                            .map_err(|e| backfill_error(from_block, backfill_to, e.to_string()))?;
+                    }
+                }
+
+                if covered {
+                    logs.extend(
+                        delivery
+                            .logs
+                            .iter()
+                            .filter(|log| {
+                                log.removed != Some(true) &&
+                                    log.block_number.is_some_and(|block| {
+                                        block.as_u64() >= replaced_from &&
+                                            block.as_u64() <= to_block
+                                    }) &&
+                                    filter.matches(log)
+                            })
+                            .cloned(),
+                    );
+                }
+                logs.sort_by_key(|log| (log.block_number, log.log_index));
+                // retracted before anything replacing them is written
+                let logs: Vec<Log> = removed.into_iter().chain(logs).collect();
+
+                if !logs.is_empty() {
+                    let network_contract = Arc::new(network_contract.clone());
+                    let data = logs
+                        .into_iter()
+                        .map(|log| {
+                            EventResult::new(
+                                Arc::clone(&network_contract),
+                                log,
+                                U64::from(from_block),
+                                U64::from(to_block),
+                            )
+                        })
+                        .collect();
+                    (event.callback)(data)
+                        .await
+                        .map_err(|e| WebhookIngestError::HandlerError(event.info_log_name(), e))?;
+                }
+
+                if from_block > to_block {
+                    continue;
+                }
+                update_last_synced_block_number_postgres(
+                    &self.database,
+                    &event.indexer_name,
+                    &event.contract.name,
+                    &event.event_name,
+                    network,
+                    U64::from(to_block),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle(&self, network: &str, request: &HttpRequest) -> Result<(), WebhookIngestError> {
+        let source = self
+            .sources
+            .get(network)
+            .ok_or_else(|| WebhookIngestError::UnknownNetwork(network.to_string()))?;
+        let header = source.provider.signature_header();
+        let signature =
+            request.header(header).ok_or(WebhookIngestError::InvalidSignature(header))?;
+        if !signature_matches(&source.signing_key, &request.body, signature) {
+            return Err(WebhookIngestError::InvalidSignature(header));
+        }
+
+        let Some(delivery) = WebhookDelivery::parse(source.provider, &request.body)? else {
+            return Ok(());
+        };
+
+        let _guard = match self.network_locks.get(network) {
+            Some(lock) => Some(lock.lock().await),
+            None => None,
+        };
+        self.ensure_ready(network).await?;
+        self.ingest(source, &delivery).await
+    }
+}
+
+async fn serve_webhook(mut stream: TcpStream, receiver: Arc<WebhookReceiver>) {
+    let Some(request) = read_request(&mut stream, MAX_BODY_BYTES).await else {
+        write_response(&mut stream, "400 Bad Request", "text/plain", "").await;
+        return;
+    };
+
+    let result = match request.path().strip_prefix("/webhooks/") {
+        Some(network) if request.method == "POST" => receiver.handle(network, &request).await,
+        _ => Err(WebhookIngestError::UnknownNetwork(request.target.clone())),
+    };
+    let (status, message) = match result {
+        Ok(()) => ("200 OK", String::new()),
+        Err(e) => {
+            match e {
+                WebhookIngestError::NotReady(_, _) => info!("Webhook delivery deferred: {}", e),
+                WebhookIngestError::UnknownNetwork(_) |
+                WebhookIngestError::InvalidSignature(_) |
+                WebhookIngestError::InvalidPayload(_) => warn!("Webhook delivery refused: {}", e),
+                _ => error!("Webhook delivery failed: {}", e),
+            }
+            (e.status(), e.to_string())
+        }
+    };
+
+    write_response(&mut stream, status, "text/plain", &message).await;
+}
+
+/// Serves `POST /webhooks/{network}` for every configured webhook source, the returned handle
+/// runs for as long as the server does
+pub async fn start_webhook_ingest(
+    settings: &WebhookIngestSettings,
+    registry: Arc<EventCallbackRegistry>,
+    database: Arc<PostgresClient>,
+    progress: Arc<Mutex<IndexingEventsProgressState>>,
+) -> Result<JoinHandle<()>, StartWebhookIngestError> {
+    let receiver = Arc::new(WebhookReceiver {
+        sources: settings
+            .sources
+            .iter()
+            .map(|source| (source.network.clone(), source.clone()))
+            .collect(),
+        registry,
+        database,
+        progress,
+        network_locks: settings
+            .sources
+            .iter()
+            .map(|source| (source.network.clone(), Mutex::new(())))
+            .collect(),
+    });
+
+    let server = serve("Webhook ingest", &settings.host, settings.port, move |stream| {
+        serve_webhook(stream, Arc::clone(&receiver))
+    })
+    .await
+    .map_err(|e| StartWebhookIngestError::CouldNotBind(settings.port, e))?;
+
+    info!(
+        "Webhook ingest running on: http://{}:{}/webhooks/{{network}}",
+        settings.host, settings.port
+    );
+
+    Ok(server)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::ValueOrArray;
+
+    use super::*;
+    use crate::event::contract_setup::{AddressDetails, IndexingContractSetup};
+
+    #[test]
+    fn test_signature_matches() {
+        // RFC 4231 test case 2
+        let signature = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+        let body = b"what do ya want for nothing?";
+        assert!(signature_matches("Jefe", body, signature));
+        assert!(signature_matches("Jefe", body, &format!("sha256={}", signature)));
+        assert!(!signature_matches("Jefe", b"what do ya want for something?", signature));
+        assert!(!signature_matches("other", body, signature));
+        assert!(!signature_matches("Jefe", body, &signature[..32]));
+        assert!(!signature_matches("Jefe", body, "not hex"));
+    }
+
+    #[test]
+    fn test_parse_alchemy_delivery() {
+        let body = br#"{"webhookId":"wh_1","type":"GRAPHQL","event":{"data":{"block":{
+            "hash":"0x0000000000000000000000000000000000000000000000000000000000000001",
+            "number":100,
+            "logs":[{"data":"0x01","index":3,
+                "topics":["0x0000000000000000000000000000000000000000000000000000000000000002"],
+                "account":{"address":"0x0000000000000000000000000000000000000003"},
+                "transaction":{"hash":"0x0000000000000000000000000000000000000000000000000000000000000004","index":7}}]
+        }}}}"#;
+
+        let delivery = WebhookDelivery::parse(WebhookProvider::Alchemy, body).unwrap().unwrap();
+        assert_eq!(delivery.to_block, 100);
+        assert_eq!(delivery.lowest_block(), 100);
+        assert_eq!(delivery.logs[0].log_index, Some(U256::from(3)));
+        assert_eq!(delivery.logs[0].transaction_index, Some(U64::from(7)));
+        assert_eq!(delivery.logs[0].address, Address::from_low_u64_be(3));
+    }
+
+    #[test]
+    fn test_parse_logs_delivery() {
+        let empty = WebhookDelivery::parse(WebhookProvider::Logs, br#"{"logs":[]}"#).unwrap();
+        assert!(empty.is_none());
+
+        let ahead =
+            WebhookDelivery::parse(WebhookProvider::Logs, br#"{"block_number":"0x10","logs":[]}"#)
+                .unwrap()
+                .unwrap();
+        assert_eq!(ahead.to_block, 16);
+
+        assert!(WebhookDelivery::parse(WebhookProvider::Logs, b"not json").is_err());
+    }
+
+    #[test]
+    fn test_removed_logs_are_kept_but_not_backfilled_from() {
+        let log = |block: u64, removed: bool| Log {
+            block_number: Some(U64::from(block)),
+            removed: Some(removed),
+            ..Default::default()
+        };
+        let body = serde_json::to_vec(&serde_json::json!({
+            "block_number": "0x14",
+            "logs": [log(12, true), log(15, false)]
+        }))
+        .unwrap();
+
+        let delivery = WebhookDelivery::parse(WebhookProvider::Logs, &body).unwrap().unwrap();
+        assert_eq!(delivery.logs.len(), 2);
+        assert_eq!(delivery.lowest_block(), 15);
+    }
+
+    #[test]
+    fn test_filter_matches_delivered_logs() {
+        let topic_id = H256::from_low_u64_be(2);
+        let setup = IndexingContractSetup::Address(AddressDetails {
+            address: ValueOrArray::Value(Address::from_low_u64_be(3)),
+            indexed_filters: None,
+            registry: None,
+        });
+        let filter =
+            RindexerEventFilter::new(&topic_id, "Transfer", &setup, U64::from(1), U64::from(2))
+                .unwrap();
+
+        let log = Log {
+            address: Address::from_low_u64_be(3),
+            topics: vec![topic_id],
+            ..Default::default()
+        };
+        assert!(filter.matches(&log));
+        assert!(!filter.matches(&Log { address: Address::from_low_u64_be(4), ..log.clone() }));
+        assert!(!filter.matches(&Log { topics: vec![H256::from_low_u64_be(5)], ..log }));
+    }
+}
@@ -4,21 +4,42 @@ pub mod indexer;
 pub mod manifest;
 
 mod database;
-pub use database::postgres::{
-    client::PostgresClient, generate::drop_tables_for_indexer_sql, setup::setup_postgres,
-    sql_type_wrapper::EthereumSqlTypeWrapper,
+#[cfg(feature = "csv")]
+pub use database::{
+    backend::CsvStorageBackend,
+    postgres::import::{import_events, ImportEventsError, ImportEventsOptions, ImportedEvents},
+};
+pub use database::{
+    backend::{
+        register_storage_backend, write_registered_storage_backends, CheckpointKey,
+        PostgresStorageBackend, StorageBackend, StorageBackendError, StorageBatch,
+    },
+    postgres::{
+        client::PostgresClient,
+        ddl_audit::{set_destructive_ddl_confirmer, DestructiveDdlConfirmer},
+        generate::drop_tables_for_indexer_sql,
+        managed_index::{managed_indexes, ManagedIndex},
+        setup::setup_postgres,
+        snapshot::{export_snapshot, import_snapshot, SnapshotError},
+        sql_type_wrapper::EthereumSqlTypeWrapper,
+        table_query::{
+            EventColumnFormat, EventTableQuery, EventTableQueryError, EventTableRow, EventTables,
+        },
+    },
 };
 
+#[cfg(feature = "csv")]
 mod simple_file_formatters;
+#[cfg(feature = "csv")]
 pub use simple_file_formatters::csv::AsyncCsvAppender;
 
 mod helpers;
 pub use helpers::{
-    format_all_files_for_project, generate_random_id, load_env_from_path, public_read_env_value,
-    write_file, WriteFileError,
+    format_address, format_all_files_for_project, generate_random_id, load_env_from_path,
+    public_read_env_value, write_file, WriteFileError,
 };
 mod api;
-pub use api::{generate_graphql_queries, GraphqlOverrideSettings};
+pub use api::{generate_graphql_queries, persisted_query_hash, GraphqlOverrideSettings};
 
 mod logger;
 pub use logger::setup_info_logger;
@@ -26,22 +47,57 @@ mod abi;
 pub use abi::ABIItem;
 mod chat;
 pub mod event;
+pub mod multicall;
 pub mod phantom;
 pub mod provider;
 mod start;
 mod streams;
+pub use streams::{
+    register_stream_sink, registered_stream_sinks, replay_streams, ReplayStreamsError,
+    ReplayStreamsOptions, StreamBatch, StreamError, StreamSink, StreamWatermark,
+};
+mod bench;
+mod builder;
+mod bundle;
+mod dbt;
+mod fixtures;
+mod handler_cache;
+mod quarantine;
+mod teardown;
 mod types;
-
 // export 3rd party dependencies
 pub use async_trait::async_trait;
+pub use bench::{run_bench, BenchError, BenchOptions, BenchReport};
+pub use builder::{decoded_log, IndexedEvents, IndexerBuilder, IndexerBuilderError, IndexerHandle};
+pub use bundle::{
+    pack_bundle, unpack_bundle, unpack_bundle_to_temp_dir, BundleEncryption, BundleError,
+    BUNDLE_IDENTITY_ENV,
+};
 pub use colored::Colorize as RindexerColorize;
+pub use dbt::{generate_dbt_models, DbtError, GeneratedDbtModels};
+pub use fixtures::{
+    generate_fixtures, load_fixtures, FixtureColumn, FixtureTable, FixturesError,
+    GenerateFixturesOptions, GeneratedFixtures, LoadedFixtures,
+};
 pub use futures::FutureExt;
+pub use handler_cache::{handler_cache, HandlerCache, HandlerCacheError};
+pub use indexer::{
+    decode_transaction, no_code::no_code_registry, redecode_event, verify_indexed_events,
+    DecodeError, DecodeOptions, DecodeReport, RedecodeError, RedecodeOptions, RedecodeReport,
+    VerifyError, VerifyOptions, VerifyReport,
+};
 pub use lazy_static::lazy_static;
+pub use quarantine::{
+    replay_failed_events, Quarantine, ReplayFailedEventsError, ReplayFailedEventsOptions,
+    ReplayFailedEventsReport,
+};
 pub use reqwest::header::HeaderMap;
+pub use serde_json::Value as JsonValue;
 pub use start::{
-    start_rindexer, start_rindexer_no_code, IndexerNoCodeDetails, IndexingDetails, StartDetails,
-    StartNoCodeDetails,
+    build_runtime, start_rindexer, start_rindexer_no_code, IndexerNoCodeDetails, IndexingDetails,
+    StartDetails, StartNoCodeDetails,
 };
+pub use teardown::{plan_teardown, run_teardown, TeardownError, TeardownOptions, TeardownPlan};
 pub use tokio::main as rindexer_main;
 pub use tokio_postgres::types::Type as PgType;
 pub use tracing::{error as rindexer_error, info as rindexer_info};
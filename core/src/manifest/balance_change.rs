@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// A transfer like event turned into Rosetta style balance changes, one debit of `from` and one
+/// credit of `to`. The values are SQL expressions where `$input` is the event value, like
+/// projection upserts
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BalanceChangeSource {
+    pub contract_name: String,
+
+    pub event_name: String,
+
+    /// Account debited, left out for events which only mint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+
+    /// Account credited, left out for events which only burn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+
+    pub amount: String,
+
+    /// Currency symbol, defaults to `$contract_address`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<u8>,
+}
+
+impl BalanceChangeSource {
+    pub fn currency(&self) -> &str {
+        self.currency.as_deref().unwrap_or("$contract_address")
+    }
+
+    /// Every expression so unknown `$name` references can be checked
+    pub fn expressions(&self) -> impl Iterator<Item = &String> {
+        self.from.iter().chain(&self.to).chain([&self.amount]).chain(&self.currency)
+    }
+}
@@ -0,0 +1,50 @@
+use ethers::types::U64;
+use serde::{Deserialize, Serialize};
+
+use super::core::{deserialize_option_u64_from_string, serialize_option_u64_as_string};
+use crate::database::postgres::internal_schema::internal_schema;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockAnomalyNetwork {
+    pub network: String,
+
+    /// Defaults to the latest block when nothing has been recorded yet
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_option_u64_from_string",
+        serialize_with = "serialize_option_u64_as_string"
+    )]
+    pub start_block: Option<U64>,
+
+    /// Seconds between slots on PoS chains, a bigger gap between two blocks is recorded as missed
+    /// slots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot_time: Option<u64>,
+}
+
+/// Follows every block header up to the head of the chain and records what would otherwise be
+/// skipped silently into a `block_anomalies` table: uncles, missed slots and the hashes of blocks
+/// a reorg replaced
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockAnomalyTracking {
+    pub networks: Vec<BlockAnomalyNetwork>,
+
+    /// Schema of the `block_anomalies` table, defaults to the internal schema of the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+}
+
+impl BlockAnomalyTracking {
+    fn schema_name(&self) -> &str {
+        self.schema.as_deref().unwrap_or(internal_schema())
+    }
+
+    pub fn table_name(&self) -> String {
+        format!("{}.block_anomalies", self.schema_name())
+    }
+
+    pub fn last_synced_table_name(&self) -> String {
+        format!("{}.block_anomalies_last_synced", self.schema_name())
+    }
+}
@@ -5,7 +5,9 @@ use serde_json::{Map, Value};
 pub struct TelegramConfig {
     pub bot_token: String,
     pub chat_id: i64,
+    #[serde(default)]
     pub networks: Vec<String>,
+    #[serde(default)]
     pub messages: Vec<TelegramEvent>,
 }
 
@@ -23,7 +25,9 @@ pub struct TelegramEvent {
 pub struct DiscordConfig {
     pub bot_token: String,
     pub channel_id: u64,
+    #[serde(default)]
     pub networks: Vec<String>,
+    #[serde(default)]
     pub messages: Vec<DiscordEvent>,
 }
 
@@ -41,7 +45,9 @@ pub struct DiscordEvent {
 pub struct SlackConfig {
     pub bot_token: String,
     pub channel: String,
+    #[serde(default)]
     pub networks: Vec<String>,
+    #[serde(default)]
     pub messages: Vec<SlackEvent>,
 }
 
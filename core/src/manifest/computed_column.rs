@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+fn default_computed_column_type() -> String {
+    "TEXT".to_string()
+}
+
+/// An extra event table column postgres computes from the other columns as rows are inserted,
+/// the expression is immutable SQL over the event table columns e.g. `amount / 10^18` or
+/// `token_0 || '-' || token_1`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ComputedColumn {
+    pub event_name: String,
+
+    pub name: String,
+
+    /// Postgres column type, defaults to TEXT
+    #[serde(rename = "type", default = "default_computed_column_type")]
+    pub type_: String,
+
+    pub expression: String,
+}
+
+/// Keywords which start a subquery, the expression may only read the row it is computed for
+const SUBQUERY_KEYWORDS: [&str; 4] = ["SELECT", "TABLE", "VALUES", "WITH"];
+
+/// Checks the expression is a single SQL expression before it goes into the
+/// `GENERATED ALWAYS AS (...) STORED` column definition, returns why it is not
+pub fn validate_computed_column_expression(expression: &str) -> Result<(), String> {
+    let mut depth = 0usize;
+    let mut chars = expression.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                let mut closed = false;
+                while let Some(next) = chars.next() {
+                    if next == c {
+                        if chars.peek() == Some(&c) {
+                            chars.next();
+                        } else {
+                            closed = true;
+                            break;
+                        }
+                    }
+                }
+                if !closed {
+                    return Err("has an unterminated quote".to_string());
+                }
+            }
+            '(' => depth += 1,
+            ')' => {
+                depth = depth.checked_sub(1).ok_or("has unbalanced parentheses")?;
+            }
+            ',' if depth == 0 => return Err("has to be a single expression".to_string()),
+            ';' => return Err("can not hold a `;`".to_string()),
+            '$' => return Err("can not hold a `$`".to_string()),
+            '-' if chars.peek() == Some(&'-') => return Err("can not hold comments".to_string()),
+            '/' if chars.peek() == Some(&'*') => return Err("can not hold comments".to_string()),
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = c.to_uppercase().to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_alphanumeric() || next == '_') {
+                        break;
+                    }
+                    word.extend(next.to_uppercase());
+                    chars.next();
+                }
+                if SUBQUERY_KEYWORDS.contains(&word.as_str()) {
+                    return Err("can not hold subqueries".to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 0 {
+        return Err("has unbalanced parentheses".to_string());
+    }
+    Ok(())
+}
+
+pub fn computed_columns_for_event<'a>(
+    computed_columns: &'a Option<Vec<ComputedColumn>>,
+    event_name: &str,
+) -> Vec<&'a ComputedColumn> {
+    computed_columns
+        .as_ref()
+        .map(|columns| columns.iter().filter(|c| c.event_name == event_name).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_computed_column_expression() {
+        assert!(validate_computed_column_expression("amount / 10^18").is_ok());
+        assert!(validate_computed_column_expression("token_0 || '-' || token_1").is_ok());
+        assert!(validate_computed_column_expression("coalesce(\"from\", 'it''s; (')").is_ok());
+
+        for expression in [
+            "amount); DROP TABLE transfer; --",
+            "1) STORED, evil TEXT GENERATED ALWAYS AS (1",
+            "(amount",
+            "amount)",
+            "amount, block_number",
+            "(SELECT max(amount) FROM transfer)",
+            "amount -- comment",
+            "amount /* comment */",
+            "$$ x $$",
+            "'unterminated",
+        ] {
+            assert!(validate_computed_column_expression(expression).is_err(), "{}", expression);
+        }
+    }
+}
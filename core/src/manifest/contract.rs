@@ -1,4 +1,12 @@
-use std::borrow::Cow;
+#![allow(mismatched_lifetime_syntaxes)]
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+    str::FromStr,
+};
 
 use ethers::{
     addressbook::Address,
@@ -8,11 +16,23 @@ use serde::{Deserialize, Serialize};
 
 use super::core::{deserialize_option_u64_from_string, serialize_option_u64_as_string};
 use crate::{
+    abi::ABIInput,
     event::contract_setup::{
         AddressDetails, ContractEventMapping, FilterDetails, IndexingContractSetup,
     },
+    helpers::get_full_path,
     indexer::parse_topic,
-    manifest::{chat::ChatConfig, stream::StreamsConfig},
+    manifest::{
+        chat::ChatConfig,
+        computed_column::ComputedColumn,
+        price_enrichment::EventPriceEnrichment,
+        runtime::RuntimeSettings,
+        solidity_enum::{EventInputEnum, TypedAddressInput},
+        stream::StreamsConfig,
+        table_settings::EventTableSettings,
+        template::ContractTemplate,
+        transform::EventInputTransform,
+    },
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -44,18 +64,158 @@ impl EventInputIndexedFilters {
     }
 }
 
+/// Anonymous events have no topic0 so the logs are matched on the contract address and the topic
+/// layout alone
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnonymousEventLayout {
+    pub event_name: String,
+
+    /// Inputs emitted as topics in the order they are declared on the event, up to 4 as there
+    /// is no topic0
+    pub topics: Vec<String>,
+}
+
+/// Stores inputs as a single JSONB column holding the whole decoded value instead of flattening
+/// tuples into a column per field
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventJsonbStorage {
+    pub event_name: String,
+
+    /// Inputs to store as JSONB, defaults to every tuple and tuple array input on the event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Vec<String>>,
+}
+
+/// Names an overload of an event which shares its name with other events in the ABI
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OverloadedEventName {
+    /// Full event signature e.g. `Swap(address,uint256)`
+    pub signature: String,
+
+    pub name: String,
+}
+
+/// Restricts an event to a subset of the contract networks, the event is not fetched on any other
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventNetworks {
+    pub event_name: String,
+
+    pub networks: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeRemovedAction {
+    /// Keep indexing, events after the removal are handed over with
+    /// `tx_information.after_code_removed` set
+    #[default]
+    Annotate,
+    /// Stop indexing the contract after the block its code was removed in
+    Stop,
+}
+
+/// Checks the contract code at the end of every synced range, self-destructs and CREATE2
+/// redeploys are recorded in `rindexer_internal.contract_lifecycle`. Needs an archive node as
+/// the code is read at historic blocks.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ContractLifecycleConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_code_removed: Option<CodeRemovedAction>,
+}
+
+/// Contracts sharing constrained RPC or database capacity, a lower priority can only hold part of
+/// the indexing slots so higher priority contracts keep up with head while it backfills
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ContractPriority {
+    Critical,
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl ContractPriority {
+    /// Share of the indexing slots the priority can hold when a higher priority is configured
+    pub fn slot_share(&self) -> f64 {
+        match self {
+            ContractPriority::Critical => 1.0,
+            ContractPriority::High => 0.75,
+            ContractPriority::Normal => 0.5,
+            ContractPriority::Low => 0.25,
+        }
+    }
+}
+
+/// How often the last synced block is written, it is written after every processed range when
+/// neither is set. The end of a historic sync and the block held back on ctrl-c are always written.
+/// After a crash indexing resumes from the last written block so the ranges since
+/// get processed again, enable `natural_primary_key` for those rows to be skipped on conflict.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CheckpointConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every_blocks: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every_seconds: Option<u64>,
+}
+
+/// Only stores and streams the decoded events matching the `where:` expression, e.g.
+/// `value >= 10^21`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventCondition {
+    pub event_name: String,
+
+    #[serde(rename = "where")]
+    pub where_: String,
+}
+
+/// Caps how many handler calls for the event run at once, for handlers hitting rate limited
+/// external APIs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventHandlerConcurrency {
+    pub event_name: String,
+
+    pub max_concurrency: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FilterDetailsYaml {
     pub event_name: String,
 }
 
+/// A view function on an on-chain registry returning `address[]`, e.g. `getAllPools()`, which is
+/// called at startup and on an interval to discover the contract addresses to index
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AddressRegistry {
+    pub address: Address,
+
+    pub function: String,
+
+    /// Addresses found on a refresh are indexed from the live head onwards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_interval_seconds: Option<u64>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ContractDetails {
+    /// Left out on a details block which is used for every network of the contract `addresses`
+    #[serde(default)]
     pub network: String,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     address: Option<ValueOrArray<Address>>,
 
+    /// Path relative to the project to a `.json` array or a newline separated list of addresses,
+    /// merged with any `address` when the manifest is read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_file: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_registry: Option<AddressRegistry>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub filter: Option<FilterDetailsYaml>,
 
@@ -81,12 +241,72 @@ pub struct ContractDetails {
     pub end_block: Option<U64>,
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum LoadAddressFileError {
+    #[error("Could not read address file {0}: {1}")]
+    CouldNotReadFile(String, std::io::Error),
+
+    #[error("Could not parse address file {0}: {1}")]
+    CouldNotParseJson(String, serde_json::Error),
+
+    #[error("Invalid address {1} in address file {0}")]
+    InvalidAddress(String, String),
+}
+
+fn parse_address_file(path: &str, contents: &str) -> Result<Vec<Address>, LoadAddressFileError> {
+    let raw_addresses: Vec<String> = if path.ends_with(".json") {
+        serde_json::from_str(contents)
+            .map_err(|e| LoadAddressFileError::CouldNotParseJson(path.to_string(), e))?
+    } else {
+        contents
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string())
+            .collect()
+    };
+
+    raw_addresses
+        .iter()
+        .map(|address| {
+            Address::from_str(address.trim()).map_err(|_| {
+                LoadAddressFileError::InvalidAddress(path.to_string(), address.clone())
+            })
+        })
+        .collect()
+}
+
 impl ContractDetails {
+    /// Loads the `address_file` into `address` keeping any inline addresses and dropping duplicates
+    pub fn load_address_file(&mut self, project_path: &Path) -> Result<(), LoadAddressFileError> {
+        let Some(address_file) = &self.address_file else {
+            return Ok(());
+        };
+
+        let contents = fs::read_to_string(project_path.join(address_file))
+            .map_err(|e| LoadAddressFileError::CouldNotReadFile(address_file.clone(), e))?;
+
+        let mut addresses = match self.address.take() {
+            Some(ValueOrArray::Value(address)) => vec![address],
+            Some(ValueOrArray::Array(addresses)) => addresses,
+            None => vec![],
+        };
+        addresses.extend(parse_address_file(address_file, &contents)?);
+
+        let mut seen = HashSet::new();
+        addresses.retain(|address| seen.insert(*address));
+
+        self.address = Some(ValueOrArray::Array(addresses));
+
+        Ok(())
+    }
+
     pub fn indexing_contract_setup(&self) -> IndexingContractSetup {
-        if let Some(address) = &self.address {
+        if self.address.is_some() || self.address_registry.is_some() {
             IndexingContractSetup::Address(AddressDetails {
-                address: address.clone(),
+                address: self.address.clone().unwrap_or(ValueOrArray::Array(vec![])),
                 indexed_filters: self.indexed_filters.clone(),
+                registry: self.address_registry.clone(),
             })
             // } else if let Some(factory) = &self.factory {
             //     IndexingContractSetup::Factory(factory.clone())
@@ -120,6 +340,8 @@ impl ContractDetails {
         Self {
             network,
             address: Some(address),
+            address_file: None,
+            address_registry: None,
             filter: None,
             indexed_filters,
             //factory: None,
@@ -138,6 +360,8 @@ impl ContractDetails {
         Self {
             network,
             address: None,
+            address_file: None,
+            address_registry: None,
             filter: Some(filter),
             indexed_filters,
             //factory: None,
@@ -170,6 +394,15 @@ pub enum SimpleEventOrContractEvent {
     ContractEvent(ContractEventMapping),
 }
 
+/// The event's handler only runs once the `events` it depends on have been processed for the
+/// same block range, events of other contracts are referenced with `contract_name`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventDependsOn {
+    pub event_name: String,
+
+    pub events: Vec<SimpleEventOrContractEvent>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DependencyEventTreeYaml {
     pub events: Vec<SimpleEventOrContractEvent>,
@@ -204,19 +437,33 @@ impl DependencyEventTree {
 pub struct Contract {
     pub name: String,
 
+    #[serde(default)]
     pub details: Vec<ContractDetails>,
 
+    /// Address of the contract on each network, expanded into `details` when the manifest is read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub addresses: Option<BTreeMap<String, ValueOrArray<Address>>>,
+
+    /// Can be left out when a template is used
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub abi: String,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub include_events: Option<Vec<String>>,
 
+    /// Events to skip, applied after `include_events`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_events: Option<Vec<String>>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub index_event_in_order: Option<Vec<String>>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub dependency_events: Option<DependencyEventTreeYaml>,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<EventDependsOn>>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reorg_safe_distance: Option<bool>,
 
@@ -228,6 +475,67 @@ pub struct Contract {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub chat: Option<ChatConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transforms: Option<Vec<EventInputTransform>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_enrichment: Option<Vec<EventPriceEnrichment>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anonymous_events: Option<Vec<AnonymousEventLayout>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overloaded_events: Option<Vec<OverloadedEventName>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jsonb_storage: Option<Vec<EventJsonbStorage>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_networks: Option<Vec<EventNetworks>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_conditions: Option<Vec<EventCondition>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub computed_columns: Option<Vec<ComputedColumn>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<CheckpointConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<ContractTemplate>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<ContractPriority>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<ContractLifecycleConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handler_concurrency: Option<Vec<EventHandlerConcurrency>>,
+
+    /// uint8 inputs backed by a Solidity enum, generates a Rust enum and a text label column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enums: Option<Vec<EventInputEnum>>,
+
+    /// address inputs of a known kind of contract, generates an address newtype
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typed_addresses: Option<Vec<TypedAddressInput>>,
+
+    /// Fillfactor, autovacuum and cluster settings of the postgres tables of each event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table_settings: Option<Vec<EventTableSettings>>,
+
+    /// Keeps the topics and data of every log next to the decoded columns so `rindexer redecode`
+    /// can decode them again after an ABI fix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_raw_logs: Option<bool>,
+
+    /// Overloaded events get unique names, only set for no-code projects as rust projects keep
+    /// the names their generated bindings use
+    #[serde(skip)]
+    pub rename_overloaded_events: bool,
 }
 
 impl Contract {
@@ -235,6 +543,62 @@ impl Contract {
         self.name = name;
     }
 
+    /// The ABI json of the contract, a template without an `abi` uses the ABI rindexer ships
+    pub fn read_abi(&self, project_path: &Path) -> Result<String, std::io::Error> {
+        match self.template {
+            Some(template) if self.abi.is_empty() => Ok(template.abi().to_string()),
+            _ => get_full_path(project_path, &self.abi).and_then(fs::read_to_string),
+        }
+    }
+
+    /// Fills the `addresses` of each network into its details, networks without details get a
+    /// copy of the details block without a network or only their address when there is none
+    pub fn expand_network_addresses(&mut self) {
+        let Some(addresses) = &self.addresses else {
+            return;
+        };
+
+        let template = self
+            .details
+            .iter()
+            .position(|details| details.network.is_empty())
+            .map(|index| self.details.remove(index));
+        for (network, address) in addresses {
+            match self.details.iter_mut().find(|details| details.network == *network) {
+                Some(details) => {
+                    if details.address.is_none() {
+                        details.address = Some(address.clone());
+                    }
+                }
+                None => {
+                    let mut details = template.clone().unwrap_or_else(|| {
+                        ContractDetails::new_with_address(
+                            network.clone(),
+                            address.clone(),
+                            None,
+                            None,
+                            None,
+                        )
+                    });
+                    details.network = network.clone();
+                    details.address = Some(address.clone());
+                    self.details.push(details);
+                }
+            }
+        }
+    }
+
+    /// Network whose details set a different address than `addresses` does
+    pub fn conflicting_network_address(&self) -> Option<&str> {
+        self.addresses.iter().flatten().find_map(|(network, address)| {
+            self.details
+                .iter()
+                .find(|details| details.network == *network)
+                .filter(|details| details.address.as_ref() != Some(address))
+                .map(|_| network.as_str())
+        })
+    }
+
     pub fn convert_dependency_event_tree_yaml(
         &self,
         yaml: DependencyEventTreeYaml,
@@ -256,6 +620,97 @@ impl Contract {
         }
     }
 
+    /// `(dependency, dependent)` pairs from `depends_on`
+    pub fn depends_on_edges(&self) -> Vec<(ContractEventMapping, ContractEventMapping)> {
+        self.depends_on
+            .iter()
+            .flatten()
+            .flat_map(|depends_on| {
+                let dependent = ContractEventMapping {
+                    contract_name: self.name.clone(),
+                    event_name: depends_on.event_name.clone(),
+                };
+                depends_on.events.iter().map(move |event| {
+                    let dependency = match event {
+                        SimpleEventOrContractEvent::ContractEvent(contract_event) => {
+                            contract_event.clone()
+                        }
+                        SimpleEventOrContractEvent::SimpleEvent(event_name) => {
+                            ContractEventMapping {
+                                contract_name: self.name.clone(),
+                                event_name: event_name.clone(),
+                            }
+                        }
+                    };
+                    (dependency, dependent.clone())
+                })
+            })
+            .collect()
+    }
+
+    pub fn anonymous_event_layout(&self, event_name: &str) -> Option<&AnonymousEventLayout> {
+        self.anonymous_events.as_ref()?.iter().find(|layout| layout.event_name == event_name)
+    }
+
+    pub fn event_indexed_on_network(&self, event_name: &str, network: &str) -> bool {
+        self.event_networks
+            .as_ref()
+            .and_then(|overrides| overrides.iter().find(|o| o.event_name == event_name))
+            .is_none_or(|overrides| overrides.networks.iter().any(|n| n == network))
+    }
+
+    /// Falls back to the `runtime.concurrency.handlers` default
+    pub fn event_max_concurrency(
+        &self,
+        event_name: &str,
+        runtime: &RuntimeSettings,
+    ) -> Option<usize> {
+        self.handler_concurrency
+            .as_ref()
+            .and_then(|limits| limits.iter().find(|limit| limit.event_name == event_name))
+            .map(|limit| limit.max_concurrency)
+            .or_else(|| runtime.handlers_concurrency())
+    }
+
+    pub fn event_condition(&self, event_name: &str) -> Option<&str> {
+        self.event_conditions
+            .as_ref()?
+            .iter()
+            .find(|condition| condition.event_name == event_name)
+            .map(|condition| condition.where_.as_str())
+    }
+
+    pub fn stores_raw_logs(&self) -> bool {
+        self.store_raw_logs.unwrap_or_default()
+    }
+
+    pub fn jsonb_inputs(&self, event_name: &str, inputs: &[ABIInput]) -> Vec<String> {
+        let Some(jsonb_storage) = self
+            .jsonb_storage
+            .as_ref()
+            .and_then(|storage| storage.iter().find(|s| s.event_name == event_name))
+        else {
+            return vec![];
+        };
+
+        match &jsonb_storage.inputs {
+            Some(input_names) => input_names.clone(),
+            None => inputs
+                .iter()
+                .filter(|input| input.type_.starts_with("tuple") && !input.is_hashed_topic())
+                .map(|input| input.name.clone())
+                .collect(),
+        }
+    }
+
+    pub fn overloaded_event_name(&self, signature: &str) -> Option<&str> {
+        self.overloaded_events
+            .as_ref()?
+            .iter()
+            .find(|overload| overload.signature.replace(' ', "") == signature)
+            .map(|overload| overload.name.as_str())
+    }
+
     pub fn is_filter(&self) -> bool {
         let filter_count = self
             .details
@@ -300,3 +755,49 @@ impl Contract {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address_file() {
+        let text = "# pools\n0xae78736Cd615f374D3085123A210448E74Fc6393\n\n  0x0000000000000000000000000000000000000001  \n";
+        let addresses = parse_address_file("pools.txt", text).unwrap();
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses[1], Address::from_low_u64_be(1));
+
+        let json = r#"["0xae78736Cd615f374D3085123A210448E74Fc6393"]"#;
+        assert_eq!(parse_address_file("pools.json", json).unwrap().len(), 1);
+
+        assert!(parse_address_file("pools.txt", "not-an-address").is_err());
+    }
+
+    #[test]
+    fn test_expand_network_addresses() {
+        let mut contract: Contract = serde_yaml::from_str(
+            r#"
+name: Vault
+abi: ./abis/Vault.json
+addresses:
+  ethereum: "0x0000000000000000000000000000000000000001"
+  base: "0x0000000000000000000000000000000000000002"
+details:
+  - start_block: "100"
+  - network: base
+    start_block: "200"
+"#,
+        )
+        .unwrap();
+        contract.expand_network_addresses();
+
+        assert_eq!(contract.details.len(), 2);
+        let ethereum = contract.details.iter().find(|d| d.network == "ethereum").unwrap();
+        assert_eq!(ethereum.start_block, Some(U64::from(100)));
+        assert_eq!(ethereum.address(), Some(&ValueOrArray::Value(Address::from_low_u64_be(1))));
+        let base = contract.details.iter().find(|d| d.network == "base").unwrap();
+        assert_eq!(base.start_block, Some(U64::from(200)));
+        assert_eq!(base.address(), Some(&ValueOrArray::Value(Address::from_low_u64_be(2))));
+        assert!(contract.conflicting_network_address().is_none());
+    }
+}
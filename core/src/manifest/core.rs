@@ -1,3 +1,5 @@
+#![allow(clippy::unnecessary_map_or)]
+
 use ethers::prelude::U64;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_yaml::Value;
@@ -5,8 +7,10 @@ use serde_yaml::Value;
 use crate::{
     indexer::Indexer,
     manifest::{
-        contract::Contract, global::Global, graphql::GraphQLSettings, network::Network,
-        phantom::Phantom, storage::Storage,
+        block_anomaly::BlockAnomalyTracking, contract::Contract, gas_price::GasPriceTracking,
+        global::Global, graphql::GraphQLSettings, network::Network, phantom::Phantom,
+        push::PushSettings, runtime::RuntimeSettings, storage::Storage, wallet::WalletActivity,
+        webhook::WebhookIngestSettings,
     },
 };
 
@@ -57,6 +61,11 @@ pub struct Manifest {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub repository: Option<String>,
 
+    /// Stable id namespacing the internal tables as `rindexer_internal_{project_id}` so indexers
+    /// sharing a database never see each others bookkeeping
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+
     #[serde(deserialize_with = "deserialize_project_type")]
     #[serde(serialize_with = "serialize_project_type")]
     pub project_type: ProjectType,
@@ -76,6 +85,24 @@ pub struct Manifest {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub graphql: Option<GraphQLSettings>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push: Option<PushSettings>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wallets: Option<WalletActivity>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_prices: Option<GasPriceTracking>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_anomalies: Option<BlockAnomalyTracking>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<RuntimeSettings>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhooks: Option<WebhookIngestSettings>,
 }
 
 impl Manifest {
@@ -98,6 +125,10 @@ impl Manifest {
         self.storage.csv_enabled() && contract_csv_enabled
     }
 
+    pub fn runtime_settings(&self) -> RuntimeSettings {
+        self.runtime.clone().unwrap_or_default()
+    }
+
     pub fn get_custom_headers(&self) -> reqwest::header::HeaderMap {
         let mut headers = reqwest::header::HeaderMap::new();
         if let Some(phantom) = &self.phantom {
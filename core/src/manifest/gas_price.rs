@@ -0,0 +1,40 @@
+use ethers::types::U64;
+use serde::{Deserialize, Serialize};
+
+use super::core::{deserialize_option_u64_from_string, serialize_option_u64_as_string};
+use crate::database::postgres::internal_schema::internal_schema;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GasPriceNetwork {
+    pub network: String,
+
+    /// Defaults to the latest safe block when nothing has been sampled yet
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_option_u64_from_string",
+        serialize_with = "serialize_option_u64_as_string"
+    )]
+    pub start_block: Option<U64>,
+}
+
+/// Samples the basefee and priority fee percentiles of every block into a `gas_prices` table so
+/// events can be joined with the gas context they were mined in on `network` and `block_number`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GasPriceTracking {
+    pub networks: Vec<GasPriceNetwork>,
+
+    /// Schema of the `gas_prices` table, defaults to the internal schema of the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+}
+
+impl GasPriceTracking {
+    fn schema_name(&self) -> &str {
+        self.schema.as_deref().unwrap_or(internal_schema())
+    }
+
+    pub fn table_name(&self) -> String {
+        format!("{}.gas_prices", self.schema_name())
+    }
+}
@@ -9,4 +9,16 @@ pub struct Global {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub etherscan_api_key: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_format: Option<AddressFormat>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressFormat {
+    #[default]
+    Lowercase,
+    /// EIP-55 mixed case checksum
+    Checksum,
 }
@@ -4,6 +4,10 @@ fn default_port() -> u16 {
     3001
 }
 
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GraphQLSettings {
     #[serde(default = "default_port")]
@@ -14,11 +18,40 @@ pub struct GraphQLSettings {
 
     #[serde(default)]
     pub filter_only_on_indexed_columns: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persisted_queries: Option<PersistedQueriesSettings>,
+
+    /// Serve the API as an Apollo Federation subgraph, table types get an `@key` on `nodeId`,
+    /// `_service` returns their SDL and `_entities` looks them up by `nodeId`
+    #[serde(default)]
+    pub federation: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PersistedQueriesSettings {
+    /// A folder of `.graphql` files or a json file mapping sha256 hashes to queries
+    pub path: String,
+
+    /// Only the persisted queries can be executed, anything else is rejected
+    #[serde(default)]
+    pub allow_list_only: bool,
+
+    /// Host the persisted queries server binds to, the graphql server behind it only listens
+    /// locally, put a reverse proxy in front to serve clients outside, defaults to `127.0.0.1`
+    #[serde(default = "default_host")]
+    pub host: String,
 }
 
 impl Default for GraphQLSettings {
     fn default() -> Self {
-        Self { port: 3001, disable_advanced_filters: false, filter_only_on_indexed_columns: false }
+        Self {
+            port: 3001,
+            disable_advanced_filters: false,
+            filter_only_on_indexed_columns: false,
+            persisted_queries: None,
+            federation: false,
+        }
     }
 }
 
@@ -1,10 +1,26 @@
+pub mod balance_change;
+pub mod block_anomaly;
 pub mod chat;
+pub mod computed_column;
 pub mod contract;
 pub mod core;
+pub mod gas_price;
 pub mod global;
 pub mod graphql;
 pub mod network;
 pub mod phantom;
+pub mod price_enrichment;
+pub mod projection;
+pub mod push;
+pub mod reconciliation;
+pub mod rollup;
+pub mod runtime;
+pub mod solidity_enum;
 pub mod storage;
 pub mod stream;
+pub mod table_settings;
+pub mod template;
+pub mod transform;
+pub mod wallet;
+pub mod webhook;
 pub mod yaml;
@@ -1,8 +1,334 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
 use ethers::prelude::U64;
 use serde::{Deserialize, Serialize};
 
 use super::core::{deserialize_option_u64_from_string, serialize_option_u64_as_string};
 
+/// An extra RPC endpoint for a network, equally trusted as `rpc`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NetworkRpcEndpoint {
+    pub url: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_units_per_second: Option<u64>,
+}
+
+/// Overrides the RPC retry client defaults for a network
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NetworkRetryPolicy {
+    /// Defaults to 5000
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rate_limit_retries: Option<u32>,
+
+    /// Defaults to 1000
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_timeout_retries: Option<u32>,
+
+    /// Defaults to 500ms, doubled on every retry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_backoff_ms: Option<u64>,
+
+    /// No timeout by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout_ms: Option<u64>,
+
+    /// JSON-RPC error codes to treat as rate limits on top of the well known provider ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_error_codes: Option<Vec<i64>>,
+}
+
+/// Stops sending requests to an endpoint when most of its recent requests fail, the other
+/// endpoints take its share until a probe request after `open_duration_ms` succeeds
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CircuitBreakerSettings {
+    /// Share of failed requests out of the last `window` which trips the breaker, defaults to 0.5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_rate_threshold: Option<f64>,
+
+    /// How many of the latest requests the error rate is measured over, defaults to 20
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<usize>,
+
+    /// Defaults to 30000ms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_duration_ms: Option<u64>,
+}
+
+impl CircuitBreakerSettings {
+    pub fn error_rate_threshold(&self) -> f64 {
+        self.error_rate_threshold.unwrap_or(0.5)
+    }
+
+    pub fn window(&self) -> usize {
+        self.window.unwrap_or(20).max(1)
+    }
+
+    pub fn open_duration(&self) -> Duration {
+        Duration::from_millis(self.open_duration_ms.unwrap_or(30_000))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComputeUnitPreset {
+    Alchemy,
+    Infura,
+    Quicknode,
+}
+
+impl ComputeUnitPreset {
+    fn method_cost(&self, method: &str) -> u64 {
+        match self {
+            ComputeUnitPreset::Alchemy => match method {
+                "eth_chainId" => 0,
+                "eth_blockNumber" => 10,
+                "eth_getBlockByNumber" | "eth_getBlockByHash" => 16,
+                "eth_getTransactionReceipt" => 15,
+                "eth_call" => 26,
+                "eth_getLogs" => 75,
+                "debug_traceTransaction" => 309,
+                m if m.starts_with("trace_") => 40,
+                _ => 26,
+            },
+            ComputeUnitPreset::Infura => match method {
+                "eth_chainId" => 5,
+                "eth_getLogs" => 255,
+                "debug_traceTransaction" => 1000,
+                m if m.starts_with("trace_") => 300,
+                _ => 80,
+            },
+            ComputeUnitPreset::Quicknode => match method {
+                "debug_traceTransaction" => 40,
+                m if m.starts_with("trace_") => 40,
+                _ => 20,
+            },
+        }
+    }
+}
+
+/// How live indexing learns about new blocks
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveIndexingStrategy {
+    /// eth_getBlockByNumber every `block_poll_interval_ms`
+    #[default]
+    Poll,
+    /// eth_newBlockFilter checked with eth_getFilterChanges every `block_poll_interval_ms`
+    Filter,
+    /// newHeads websocket subscription, needs `ws`
+    Subscribe,
+}
+
+/// How logs are fetched for a block range
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFetchStrategy {
+    #[default]
+    GetLogs,
+    /// eth_getBlockReceipts for every block in the range with the logs filtered locally, for
+    /// providers whose getLogs is broken. Ranges default to 100 blocks without `max_block_range`.
+    BlockReceipts,
+}
+
+/// Weights RPC methods by what the provider bills them at so `compute_units_per_second` is spent
+/// as a real budget, `methods` overrides the preset, without a preset unlisted methods cost 1
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ComputeUnitCosts {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<ComputeUnitPreset>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub methods: Option<HashMap<String, u64>>,
+}
+
+impl ComputeUnitCosts {
+    pub fn cost(&self, method: &str) -> u64 {
+        if let Some(cost) = self.methods.as_ref().and_then(|methods| methods.get(method)) {
+            return *cost;
+        }
+
+        self.preset.map_or(1, |preset| preset.method_cost(method))
+    }
+}
+
+/// Egress proxy every RPC request of the network goes through, `http://`, `https://` or with the
+/// `socks` feature `socks5://` urls
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NetworkProxy {
+    pub url: String,
+
+    /// Rust projects need `${ENV}` variables for the credentials
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl NetworkProxy {
+    pub fn is_socks(&self) -> bool {
+        self.url.starts_with("socks5://") || self.url.starts_with("socks5h://")
+    }
+}
+
+/// Block headers are cached in memory per network so contracts and enrichments asking for the
+/// same block share one getBlock, `disk_path` keeps them across restarts as well
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct BlockCacheSettings {
+    /// Defaults to 10000 headers, the least recently used are evicted once it is full
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_blocks: Option<usize>,
+
+    /// Directory only this network's headers are written to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_path: Option<String>,
+}
+
+impl BlockCacheSettings {
+    pub fn max_blocks(&self) -> usize {
+        self.max_blocks.unwrap_or(10_000)
+    }
+}
+
+/// Takes the head block from what the endpoints of a network agree on, so failing over between
+/// endpoints which are at different heads does not skip blocks or go backwards
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct HeadConsensusSettings {
+    /// How many endpoints need to have reached a block before it is the head, defaults to every
+    /// endpoint so the slowest one decides. Endpoints which fail to answer are left out so the
+    /// quorum never exceeds the endpoints which are up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<usize>,
+}
+
+/// Bounds how fast the indexer backfills the blocks missed while it was down so the catch-up
+/// does not starve live indexing of the RPC and database
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct CatchUpSettings {
+    /// Only resumes at least this many blocks behind the head are throttled, defaults to 1000
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_blocks_behind: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_blocks_per_second: Option<u64>,
+
+    /// Compute units catch-up getLogs requests may spend, weighted by `compute_unit_costs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_compute_units_per_second: Option<u64>,
+}
+
+impl CatchUpSettings {
+    pub fn min_blocks_behind(&self) -> u64 {
+        self.min_blocks_behind.unwrap_or(1000)
+    }
+
+    pub fn has_limit(&self) -> bool {
+        self.max_blocks_per_second.is_some() || self.max_compute_units_per_second.is_some()
+    }
+}
+
+/// A daily window in UTC with its own throughput caps for historic sync on the network, a `to`
+/// before `from` wraps past midnight
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ScheduleWindow {
+    /// Days the window starts on, `mon` to `sun`, defaults to every day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<Vec<String>>,
+
+    /// `HH:MM`
+    pub from: String,
+
+    /// `HH:MM`
+    pub to: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_blocks_per_second: Option<u64>,
+
+    /// Compute units historic getLogs requests may spend, weighted by `compute_unit_costs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_compute_units_per_second: Option<u64>,
+
+    /// Historic sync waits for the window to end, live indexing carries on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause: Option<bool>,
+}
+
+impl ScheduleWindow {
+    fn parse_time(time: &str) -> Option<NaiveTime> {
+        NaiveTime::parse_from_str(time, "%H:%M").ok()
+    }
+
+    pub fn from_time(&self) -> Option<NaiveTime> {
+        Self::parse_time(&self.from)
+    }
+
+    pub fn to_time(&self) -> Option<NaiveTime> {
+        Self::parse_time(&self.to)
+    }
+
+    /// None when a day is not a valid weekday
+    pub fn weekdays(&self) -> Option<Vec<Weekday>> {
+        match &self.days {
+            Some(days) => days.iter().map(|day| Weekday::from_str(day).ok()).collect(),
+            None => Some(vec![]),
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.pause.unwrap_or_default()
+    }
+
+    pub fn is_active_at(&self, at: DateTime<Utc>) -> bool {
+        let (Some(from), Some(to), Some(weekdays)) =
+            (self.from_time(), self.to_time(), self.weekdays())
+        else {
+            return false;
+        };
+
+        let time = at.time();
+        let start_day = if from <= to {
+            if time < from || time >= to {
+                return false;
+            }
+            at.weekday()
+        } else if time >= from {
+            at.weekday()
+        } else if time < to {
+            // started the day before
+            at.weekday().pred()
+        } else {
+            return false;
+        };
+
+        weekdays.is_empty() || weekdays.contains(&start_day)
+    }
+}
+
+/// Logs the full JSON-RPC request and response of each call on the network to capture what a
+/// misbehaving provider returned, api keys in the endpoint urls are never logged
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct RpcDebugSettings {
+    /// Only log these methods, for example `eth_getLogs`, defaults to every method
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub methods: Option<Vec<String>>,
+
+    /// Calls beyond this are counted but not logged, defaults to 60
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_logs_per_minute: Option<u32>,
+}
+
+impl RpcDebugSettings {
+    pub fn logs_method(&self, method: &str) -> bool {
+        self.methods.as_ref().is_none_or(|methods| methods.iter().any(|m| m == method))
+    }
+
+    pub fn max_logs_per_minute(&self) -> u32 {
+        self.max_logs_per_minute.unwrap_or(60)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Network {
     pub name: String,
@@ -24,4 +350,155 @@ pub struct Network {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub disable_logs_bloom_checks: Option<bool>,
+
+    /// Requests are load balanced round-robin across `rpc` and these endpoints, weighted by
+    /// their compute units per second
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpcs: Option<Vec<NetworkRpcEndpoint>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<NetworkRetryPolicy>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_unit_costs: Option<ComputeUnitCosts>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<CircuitBreakerSettings>,
+
+    /// getLogs requests from different contracts and events over the same block range are sent as
+    /// one request with the addresses and topic0s combined and the logs split back out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_get_logs: Option<bool>,
+
+    /// Address lists longer than this are split over multiple getLogs requests, the cap is
+    /// also lowered automatically if the provider rejects a list as too long
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_addresses_per_get_logs: Option<usize>,
+
+    /// How often live indexing checks for a new block, defaults to 200ms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_poll_interval_ms: Option<u64>,
+
+    /// Stay this many blocks behind the head on top of any reorg safe distance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_processing_delay: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub live_indexing_strategy: Option<LiveIndexingStrategy>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_fetch_strategy: Option<LogFetchStrategy>,
+
+    /// Websocket rpc used by the `subscribe` live indexing strategy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ws: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<NetworkProxy>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc_debug: Option<RpcDebugSettings>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catch_up: Option<CatchUpSettings>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_cache: Option<BlockCacheSettings>,
+
+    /// Throughput caps for historic sync by time of day, the first active window applies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Vec<ScheduleWindow>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head_consensus: Option<HeadConsensusSettings>,
+}
+
+pub const DEFAULT_BLOCK_POLL_INTERVAL_MS: u64 = 200;
+
+impl Network {
+    /// A network with every optional setting left to its default
+    pub fn new(name: &str, chain_id: u64, rpc: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            chain_id,
+            rpc: rpc.to_string(),
+            compute_units_per_second: None,
+            max_block_range: None,
+            disable_logs_bloom_checks: None,
+            rpcs: None,
+            retry: None,
+            compute_unit_costs: None,
+            circuit_breaker: None,
+            merge_get_logs: None,
+            max_addresses_per_get_logs: None,
+            block_poll_interval_ms: None,
+            block_processing_delay: None,
+            live_indexing_strategy: None,
+            log_fetch_strategy: None,
+            ws: None,
+            proxy: None,
+            rpc_debug: None,
+            catch_up: None,
+            block_cache: None,
+            schedule: None,
+            head_consensus: None,
+        }
+    }
+
+    pub fn block_poll_interval(&self) -> Duration {
+        Duration::from_millis(self.block_poll_interval_ms.unwrap_or(DEFAULT_BLOCK_POLL_INTERVAL_MS))
+    }
+
+    /// `rpc` first followed by any load balanced endpoints
+    pub fn rpc_endpoints(&self) -> Vec<NetworkRpcEndpoint> {
+        let mut endpoints = vec![NetworkRpcEndpoint {
+            url: self.rpc.clone(),
+            compute_units_per_second: self.compute_units_per_second,
+        }];
+        endpoints.extend(self.rpcs.iter().flatten().cloned());
+        endpoints
+    }
+
+    /// Endpoints which need to agree on a head block, None with a single endpoint
+    pub fn head_quorum(&self) -> Option<usize> {
+        let endpoints = self.rpc_endpoints().len();
+        self.head_consensus
+            .as_ref()
+            .filter(|_| endpoints > 1)
+            .map(|consensus| consensus.quorum.unwrap_or(endpoints))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_unit_costs() {
+        let costs = ComputeUnitCosts {
+            preset: Some(ComputeUnitPreset::Alchemy),
+            methods: Some(HashMap::from([("eth_getLogs".to_string(), 100)])),
+        };
+        assert_eq!(costs.cost("eth_getLogs"), 100);
+        assert_eq!(costs.cost("eth_call"), 26);
+        assert_eq!(costs.cost("trace_block"), 40);
+        assert_eq!(ComputeUnitCosts::default().cost("eth_getLogs"), 1);
+    }
+    #[test]
+    fn test_schedule_window_wraps_past_midnight() {
+        let window = ScheduleWindow {
+            days: Some(vec!["fri".to_string()]),
+            from: "22:00".to_string(),
+            to: "06:00".to_string(),
+            ..Default::default()
+        };
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc);
+
+        // 2024-05-03 is a friday
+        assert!(window.is_active_at(at("2024-05-03T23:00:00Z")));
+        assert!(window.is_active_at(at("2024-05-04T05:59:00Z")));
+        assert!(!window.is_active_at(at("2024-05-04T06:00:00Z")));
+        assert!(!window.is_active_at(at("2024-05-04T23:00:00Z")));
+        assert!(!window.is_active_at(at("2024-05-03T05:00:00Z")));
+    }
 }
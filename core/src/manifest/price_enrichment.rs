@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceOracle {
+    /// Reads `latestRoundData` from the chainlink feed on the network the event was emitted on
+    /// at the block the event was emitted in
+    Chainlink { feeds: HashMap<String, Address> },
+    /// GET request to a user provided price API, the url supports `{network}`, `{block_number}`,
+    /// `{timestamp}` and `{token_address}` placeholders
+    Http {
+        url: String,
+
+        /// Dot separated path to the price in the JSON response, defaults to `price`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        json_path: Option<String>,
+    },
+}
+
+fn default_price_column_name() -> String {
+    "usd_price".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventPriceEnrichment {
+    pub event_name: String,
+
+    pub oracle: PriceOracle,
+
+    #[serde(default = "default_price_column_name")]
+    pub column_name: String,
+
+    /// Token to price, defaults to the contract address which emitted the event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_address: Option<Address>,
+
+    /// Reuse a looked up price for every block within the range, defaults to 1 (every block)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_block_range: Option<u64>,
+}
+
+pub fn price_enrichments_for_event<'a>(
+    price_enrichments: &'a Option<Vec<EventPriceEnrichment>>,
+    event_name: &str,
+) -> Vec<&'a EventPriceEnrichment> {
+    price_enrichments
+        .as_ref()
+        .map(|enrichments| enrichments.iter().filter(|e| e.event_name == event_name).collect())
+        .unwrap_or_default()
+}
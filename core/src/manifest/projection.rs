@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::table_settings::TableStorageSettings;
+
+fn default_projection_column_type() -> String {
+    "TEXT".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectionColumn {
+    pub name: String,
+
+    /// Postgres column type, defaults to TEXT
+    #[serde(rename = "type", default = "default_projection_column_type")]
+    pub type_: String,
+
+    /// SQL default the column starts from when a key is first seen, e.g. `0` for a balance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+/// How an event updates a projection, expressions are SQL where `$input` (dot separated for tuple
+/// fields) is the event value and plain column names are the current projection values
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectionUpsert {
+    pub contract_name: String,
+
+    pub event_name: String,
+
+    /// Key column to the expression which gives its value
+    pub keys: BTreeMap<String, String>,
+
+    /// Column to the expression which gives its new value
+    pub set: BTreeMap<String, String>,
+}
+
+/// A keyed table holding the current state built from event history, updated in the same
+/// transaction as the event rows are inserted
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Projection {
+    pub name: String,
+
+    pub keys: Vec<ProjectionColumn>,
+
+    pub columns: Vec<ProjectionColumn>,
+
+    pub upserts: Vec<ProjectionUpsert>,
+
+    /// Keeps every version of the rows so `{name}_as_of(block_number)` returns the state at a
+    /// block, only changes made after it is turned on are kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<bool>,
+
+    /// Projections are updated in place so a lower fillfactor keeps updates HOT
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table_settings: Option<TableStorageSettings>,
+}
+
+impl Projection {
+    pub fn upserts_for_event<'a>(
+        &'a self,
+        contract_name: &'a str,
+        event_name: &'a str,
+    ) -> impl Iterator<Item = &'a ProjectionUpsert> {
+        self.upserts
+            .iter()
+            .filter(move |u| u.contract_name == contract_name && u.event_name == event_name)
+    }
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+fn default_port() -> u16 {
+    3002
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_buffer() -> usize {
+    10_000
+}
+
+/// Pushes decoded events to clients as they are written
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PushSettings {
+    /// Put a reverse proxy in front to push to clients outside, defaults to `127.0.0.1`
+    #[serde(default = "default_host")]
+    pub host: String,
+
+    #[serde(default = "default_port")]
+    pub port: u16,
+
+    /// Clients send it as `Authorization: Bearer {token}` or the `token` query param, use a
+    /// `${ENV}` value to keep it out of the manifest
+    pub token: String,
+
+    /// Browser origins allowed to connect, requests sent with any other `Origin` are refused
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+
+    /// How many events a client can fall behind before it is disconnected
+    #[serde(default = "default_buffer")]
+    pub buffer: usize,
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::{chat::ChatConfig, rollup::parse_interval_seconds};
+
+fn default_every() -> String {
+    "5m".to_string()
+}
+
+/// A sum over an event table or a projection, rows are counted when `sum` is left out
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReconciliationTerm {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_name: Option<String>,
+
+    /// Name of a projection to sum instead of an event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projection: Option<String>,
+
+    /// Event input or projection column summed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sum: Option<String>,
+
+    /// Subtracts the term instead of adding it, e.g. withdrawals from deposits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub negate: Option<bool>,
+}
+
+/// An invariant between indexed tables which is checked periodically, e.g. deposits minus
+/// withdrawals equal the TVL projection. Both sides are summed across every network so checks
+/// which run while events are synced up to different blocks can report a transient violation
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Reconciliation {
+    pub name: String,
+
+    /// How often to check as a number with a `s`, `m`, `h`, `d` or `w` suffix, defaults to `5m`
+    #[serde(default = "default_every")]
+    pub every: String,
+
+    pub left: Vec<ReconciliationTerm>,
+
+    pub right: Vec<ReconciliationTerm>,
+
+    /// Largest difference between the sides which still passes, defaults to 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<String>,
+
+    /// Chats violations are sent to, they are always logged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat: Option<ChatConfig>,
+}
+
+impl Reconciliation {
+    pub fn every(&self) -> Option<Duration> {
+        parse_interval_seconds(&self.every).map(Duration::from_secs)
+    }
+
+    pub fn tolerance(&self) -> &str {
+        self.tolerance.as_deref().unwrap_or("0")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconciliation_defaults() {
+        let reconciliation: Reconciliation = serde_yaml::from_str(
+            r#"
+name: tvl
+left:
+  - contract_name: Vault
+    event_name: Deposit
+    sum: assets
+  - contract_name: Vault
+    event_name: Withdraw
+    sum: assets
+    negate: true
+right:
+  - projection: tvl
+    sum: total
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(reconciliation.every(), Some(Duration::from_secs(300)));
+        assert_eq!(reconciliation.tolerance(), "0");
+        assert_eq!(reconciliation.left[1].negate, Some(true));
+        assert!(reconciliation.chat.is_none());
+    }
+}
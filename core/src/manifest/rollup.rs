@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::projection::ProjectionColumn;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RollupFunction {
+    Sum,
+    Count,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RollupAggregate {
+    pub name: String,
+
+    pub function: RollupFunction,
+
+    /// SQL expression aggregated for every event, `$input` is the event value, not needed for
+    /// `count`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RollupGroupBy {
+    #[serde(flatten)]
+    pub column: ProjectionColumn,
+
+    /// SQL expression the events are grouped on, e.g. `$contract_address`
+    pub value: String,
+}
+
+/// Time bucketed aggregates of an event maintained incrementally as the events are inserted,
+/// averages can be worked out from a `sum` and a `count`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Rollup {
+    pub name: String,
+
+    pub contract_name: String,
+
+    pub event_name: String,
+
+    /// Bucket size as a number with a `s`, `m`, `h`, `d` or `w` suffix, e.g. `1d`
+    pub interval: String,
+
+    #[serde(default)]
+    pub group_by: Vec<RollupGroupBy>,
+
+    pub aggregates: Vec<RollupAggregate>,
+}
+
+impl Rollup {
+    pub fn interval_seconds(&self) -> Option<u64> {
+        parse_interval_seconds(&self.interval)
+    }
+}
+
+pub(crate) fn parse_interval_seconds(interval: &str) -> Option<u64> {
+    let interval = interval.trim();
+    let split = interval.find(|c: char| !c.is_ascii_digit()).unwrap_or(interval.len());
+    let (value, unit) = interval.split_at(split);
+    let value: u64 = value.parse().ok()?;
+    let multiplier = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return None,
+    };
+
+    Some(value * multiplier).filter(|seconds| *seconds > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(parse_interval_seconds("30"), Some(30));
+        assert_eq!(parse_interval_seconds("15m"), Some(900));
+        assert_eq!(parse_interval_seconds("1d"), Some(86_400));
+        assert_eq!(parse_interval_seconds("0h"), None);
+        assert_eq!(parse_interval_seconds("1y"), None);
+    }
+}
@@ -0,0 +1,90 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use tokio::runtime::{Builder, Runtime};
+
+/// Default cap on log fetches and handler calls in flight across every event
+pub const DEFAULT_INDEXING_CONCURRENCY: usize = 100;
+
+/// How many tasks each subsystem can have in flight
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TaskConcurrency {
+    /// Log fetches and handler calls across every event, defaults to 100
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexing: Option<usize>,
+
+    /// Stream publishes for each event, unlimited by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub streams: Option<usize>,
+
+    /// Handler calls for each event, unlimited by default and overridden per event with
+    /// `handler_concurrency` on the contract
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handlers: Option<usize>,
+}
+
+/// The tokio runtime rindexer runs on, tokio defaults apply to anything not set
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RuntimeSettings {
+    /// Defaults to one per CPU core
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub worker_threads: Option<usize>,
+
+    /// Threads for blocking work such as csv writes, defaults to 512
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_blocking_threads: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<TaskConcurrency>,
+
+    /// Failed handler calls after which the events are quarantined in postgres and indexing
+    /// moves on, handlers are retried forever when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handler_max_attempts: Option<u32>,
+}
+
+impl RuntimeSettings {
+    pub fn build(&self) -> io::Result<Runtime> {
+        let mut builder = Builder::new_multi_thread();
+        builder.enable_all();
+        if let Some(worker_threads) = self.worker_threads {
+            builder.worker_threads(worker_threads);
+        }
+        if let Some(max_blocking_threads) = self.max_blocking_threads {
+            builder.max_blocking_threads(max_blocking_threads);
+        }
+        builder.build()
+    }
+
+    pub fn indexing_concurrency(&self) -> usize {
+        self.concurrency
+            .as_ref()
+            .and_then(|concurrency| concurrency.indexing)
+            .unwrap_or(DEFAULT_INDEXING_CONCURRENCY)
+    }
+
+    pub fn streams_concurrency(&self) -> Option<usize> {
+        self.concurrency.as_ref().and_then(|concurrency| concurrency.streams)
+    }
+
+    pub fn handlers_concurrency(&self) -> Option<usize> {
+        self.concurrency.as_ref().and_then(|concurrency| concurrency.handlers)
+    }
+
+    /// Names of settings set to 0, tokio panics on zero threads and a zero permit cap would
+    /// never let anything run
+    pub fn zero_settings(&self) -> Vec<&'static str> {
+        let concurrency = self.concurrency.clone().unwrap_or_default();
+        [
+            ("worker_threads", self.worker_threads),
+            ("max_blocking_threads", self.max_blocking_threads),
+            ("concurrency.indexing", concurrency.indexing),
+            ("concurrency.streams", concurrency.streams),
+            ("concurrency.handlers", concurrency.handlers),
+        ]
+        .into_iter()
+        .filter(|(_, value)| *value == Some(0))
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::camel_to_snake;
+
+/// A uint8 event input backed by a Solidity enum, the variants are listed in declaration order
+/// so the first variant is 0
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventInputEnum {
+    pub event_name: String,
+
+    pub input_name: String,
+
+    /// The Solidity enum name, also the name of the generated Rust enum
+    pub name: String,
+
+    pub variants: Vec<String>,
+}
+
+impl EventInputEnum {
+    /// The readable text column stored next to the raw value
+    pub fn label_column_name(&self) -> String {
+        format!("{}_label", camel_to_snake(&self.input_name))
+    }
+
+    /// Maps the raw value to its variant name, values outside the enum are left NULL
+    pub fn label_expression(&self) -> String {
+        let cases = self
+            .variants
+            .iter()
+            .enumerate()
+            .map(|(index, variant)| {
+                format!("WHEN {} THEN '{}'", index, variant.replace('\'', "''"))
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        format!("CASE \"{}\" {} END", camel_to_snake(&self.input_name), cases)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !is_rust_identifier(&self.name) {
+            return Err(format!("{} is not a valid enum name", self.name));
+        }
+        if self.variants.is_empty() || self.variants.len() > 256 {
+            return Err("a uint8 enum needs between 1 and 256 variants".to_string());
+        }
+        if let Some(variant) = self.variants.iter().find(|v| !is_rust_identifier(v)) {
+            return Err(format!("{} is not a valid variant name", variant));
+        }
+        for (index, variant) in self.variants.iter().enumerate() {
+            if self.variants[..index].contains(variant) {
+                return Err(format!("variant {} is listed twice", variant));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An address input which always holds a known kind of contract, generates an address newtype
+/// so handlers can not mix it up with other addresses
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TypedAddressInput {
+    pub event_name: String,
+
+    pub input_name: String,
+
+    /// Name of the generated newtype, inputs sharing a name share the type
+    pub name: String,
+}
+
+/// Enums are generated once per name so every declaration of a name has to list the same
+/// variants, and no typed address can take an enum name
+pub fn validate_generated_type_names(
+    enums: &[EventInputEnum],
+    typed_addresses: &[TypedAddressInput],
+) -> Result<(), String> {
+    for (index, input_enum) in enums.iter().enumerate() {
+        if enums[..index]
+            .iter()
+            .any(|other| other.name == input_enum.name && other.variants != input_enum.variants)
+        {
+            return Err(format!(
+                "enum {} is declared more than once with different variants",
+                input_enum.name
+            ));
+        }
+    }
+
+    for typed_address in typed_addresses {
+        if !is_rust_identifier(&typed_address.name) {
+            return Err(format!("{} is not a valid type name", typed_address.name));
+        }
+        if enums.iter().any(|input_enum| input_enum.name == typed_address.name) {
+            return Err(format!("{} is both an enum and a typed address", typed_address.name));
+        }
+    }
+
+    Ok(())
+}
+
+fn is_rust_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') &&
+        chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn enums_for_event<'a>(
+    enums: &'a Option<Vec<EventInputEnum>>,
+    event_name: &str,
+) -> Vec<&'a EventInputEnum> {
+    enums
+        .as_ref()
+        .map(|enums| enums.iter().filter(|e| e.event_name == event_name).collect())
+        .unwrap_or_default()
+}
+
+pub fn typed_addresses_for_event<'a>(
+    typed_addresses: &'a Option<Vec<TypedAddressInput>>,
+    event_name: &str,
+) -> Vec<&'a TypedAddressInput> {
+    typed_addresses
+        .as_ref()
+        .map(|typed_addresses| {
+            typed_addresses.iter().filter(|t| t.event_name == event_name).collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_expression() {
+        let input_enum = EventInputEnum {
+            event_name: "OrderUpdated".to_string(),
+            input_name: "orderStatus".to_string(),
+            name: "OrderStatus".to_string(),
+            variants: vec!["Open".to_string(), "Filled".to_string()],
+        };
+
+        assert_eq!(input_enum.label_column_name(), "order_status_label");
+        assert_eq!(
+            input_enum.label_expression(),
+            "CASE \"order_status\" WHEN 0 THEN 'Open' WHEN 1 THEN 'Filled' END"
+        );
+        assert!(input_enum.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_generated_type_names() {
+        let input_enum = |input_name: &str, variants: &[&str]| EventInputEnum {
+            event_name: "OrderUpdated".to_string(),
+            input_name: input_name.to_string(),
+            name: "OrderStatus".to_string(),
+            variants: variants.iter().map(|v| v.to_string()).collect(),
+        };
+        let typed_address = |name: &str| TypedAddressInput {
+            event_name: "OrderUpdated".to_string(),
+            input_name: "token".to_string(),
+            name: name.to_string(),
+        };
+
+        let same_variants =
+            [input_enum("from", &["Open", "Filled"]), input_enum("to", &["Open", "Filled"])];
+        assert!(validate_generated_type_names(&same_variants, &[typed_address("Token")]).is_ok());
+
+        let different_variants =
+            [input_enum("from", &["Open", "Filled"]), input_enum("to", &["Open"])];
+        assert!(validate_generated_type_names(&different_variants, &[]).is_err());
+
+        assert!(
+            validate_generated_type_names(&same_variants, &[typed_address("OrderStatus")]).is_err()
+        );
+        assert!(validate_generated_type_names(&[], &[typed_address("0Token")]).is_err());
+    }
+}
@@ -1,20 +1,30 @@
-use std::path::Path;
+#![allow(clippy::unnecessary_map_or)]
+
+use std::{env, path::Path, time::Duration};
 
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
 use crate::{
     database::postgres::{
+        client::{connection_string_from_env, DEFAULT_CONNECTION_STRING_ENV},
         indexes::{
-            drop_last_known_indexes, prepare_indexes, DropLastKnownIndexesError,
-            PostgresIndexResult, PrepareIndexesError,
+            drop_last_known_indexes, prepare_indexes, ApplyPostgresIndexesError,
+            DropLastKnownIndexesError, PostgresIndexResult, PrepareIndexesError,
         },
         relationship::{
-            create_relationships, drop_last_known_relationships, CreateRelationshipError,
-            DropLastKnownRelationshipsError, Relationship,
+            create_relationships, drop_last_known_relationships, ApplyAllRelationships,
+            CreateRelationshipError, DropLastKnownRelationshipsError, Relationship,
         },
     },
-    manifest::contract::Contract,
+    helpers::camel_to_snake,
+    manifest::{
+        balance_change::BalanceChangeSource,
+        contract::Contract,
+        projection::Projection,
+        reconciliation::Reconciliation,
+        rollup::{parse_interval_seconds, Rollup},
+    },
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,7 +33,76 @@ pub struct ForeignKey {
 
     pub event_name: String,
 
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub event_input_name: String,
+
+    /// Ordered inputs for a multi-column link, used instead of `event_input_name`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_input_names: Option<Vec<String>>,
+
+    /// Name of the view joining both event tables, defaults to `{event}_with_{linked_event}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apply: Option<ApplyPolicy>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_delete: Option<ForeignKeyOnDelete>,
+
+    /// `DEFERRABLE INITIALLY DEFERRED` so the constraint is only checked on commit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deferrable: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation: Option<ForeignKeyValidation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForeignKeyOnDelete {
+    #[default]
+    NoAction,
+    Restrict,
+    Cascade,
+    SetNull,
+}
+
+impl ForeignKeyOnDelete {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            ForeignKeyOnDelete::NoAction => "NO ACTION",
+            ForeignKeyOnDelete::Restrict => "RESTRICT",
+            ForeignKeyOnDelete::Cascade => "CASCADE",
+            ForeignKeyOnDelete::SetNull => "SET NULL",
+        }
+    }
+}
+
+/// How existing rows are checked when the foreign key is added
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForeignKeyValidation {
+    /// Checked while adding the constraint which blocks writes to the table
+    #[default]
+    Immediate,
+    /// Added `NOT VALID` then checked with `VALIDATE CONSTRAINT` which does not block writes
+    NotValidThenValidate,
+    /// Added `NOT VALID` and existing rows are never checked, only new rows are
+    NotValid,
+}
+
+/// When a managed index or relationship gets created, they are always dropped at startup so
+/// historic indexing writes are not slowed down by them
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplyPolicy {
+    /// Before indexing starts
+    Immediately,
+    #[default]
+    AfterHistoricSync,
+    /// Only when running `rindexer indexes apply`
+    Manual,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,15 +111,47 @@ pub struct ForeignKeys {
 
     pub event_name: String,
 
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub event_input_name: String,
 
+    /// Ordered inputs for a multi-column link, used instead of `event_input_name`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_input_names: Option<Vec<String>>,
+
     #[serde(rename = "linked_to")]
     pub foreign_keys: Vec<ForeignKey>,
 }
 
+/// `event_input_names` or else the single `event_input_name`, inputs may also be injected
+/// parameters like `network`
+fn relationship_input_names(
+    event_input_name: &str,
+    event_input_names: &Option<Vec<String>>,
+) -> Vec<String> {
+    match event_input_names {
+        Some(names) => names.clone(),
+        None => vec![event_input_name.to_string()],
+    }
+}
+
+impl ForeignKey {
+    pub fn input_names(&self) -> Vec<String> {
+        relationship_input_names(&self.event_input_name, &self.event_input_names)
+    }
+}
+
+impl ForeignKeys {
+    pub fn input_names(&self) -> Vec<String> {
+        relationship_input_names(&self.event_input_name, &self.event_input_names)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EventIndex {
     pub event_input_names: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apply: Option<ApplyPolicy>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -63,8 +174,95 @@ pub struct EventIndexes {
     pub indexes: Vec<EventIndex>,
 }
 
+/// Settings applied to every pooled postgres connection
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PostgresSessionSettings {
+    /// Shown in `pg_stat_activity`, defaults to `rindexer_{indexer name}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub application_name: Option<String>,
+
+    /// Any postgres duration e.g. `30s`, queries running longer are cancelled. Generated DDL
+    /// such as index builds and COPYs run without it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statement_timeout: Option<String>,
+
+    /// Comma separated schemas, each is quoted so it is matched case sensitively
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_path: Option<String>,
+}
+
+/// How writes which fail on a transient error such as a failover are retried
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PostgresWriteRetry {
+    /// Attempts including the first before the indexer stops, defaults to 10
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+
+    /// Defaults to 100ms, doubled on every retry with up to half again added as jitter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_backoff_ms: Option<u64>,
+
+    /// Defaults to 15000ms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_backoff_ms: Option<u64>,
+
+    /// Writes which can wait on a retry at once, any failing beyond this fail straight away,
+    /// defaults to 100
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_queued_writes: Option<usize>,
+}
+
+impl PostgresWriteRetry {
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts.unwrap_or(10).max(1)
+    }
+
+    pub fn initial_backoff(&self) -> Duration {
+        Duration::from_millis(self.initial_backoff_ms.unwrap_or(100))
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_ms.unwrap_or(15_000))
+    }
+
+    pub fn max_queued_writes(&self) -> usize {
+        self.max_queued_writes.unwrap_or(100)
+    }
+}
+
+fn default_verify_every() -> String {
+    "1h".to_string()
+}
+
+/// Verifies the event tables against the chain on an interval while indexing, the same check
+/// `rindexer verify` runs
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PostgresVerify {
+    /// How often to verify as a number with a `s`, `m`, `h`, `d` or `w` suffix, defaults to `1h`
+    #[serde(default = "default_verify_every")]
+    pub every: String,
+
+    /// Blocks covered by each comparison against the chain, defaults to 10000
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_blocks: Option<u64>,
+
+    /// Index the missing rows again once they are found instead of only scheduling a repair
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repair: Option<bool>,
+}
+
+impl PostgresVerify {
+    pub fn every(&self) -> Option<Duration> {
+        parse_interval_seconds(&self.every).map(Duration::from_secs)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PostgresIndexes {
+    /// Default apply policy for every index, individual indexes can override it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apply: Option<ApplyPolicy>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub global_injected_parameters: Option<Vec<String>>,
 
@@ -87,6 +285,166 @@ pub struct PostgresDetails {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub disable_create_tables: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexing_stats: Option<IndexingStatsDetails>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_indexes: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unlogged_historic_sync: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insert_strategy: Option<InsertStrategy>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<PostgresLayout>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary_key: Option<PrimaryKeyType>,
+
+    /// Store addresses, tx hashes and block hashes as raw BYTEA instead of hex text, a `_hex`
+    /// view is created alongside each event table for reading them back as hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compact_bytea: Option<bool>,
+    /// Current state tables maintained from events, only applied by no-code projects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projections: Option<Vec<Projection>>,
+
+    /// Time bucketed aggregates maintained from events, only applied by no-code projects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollups: Option<Vec<Rollup>>,
+
+    /// Rosetta style balance changes derived from transfer like events, only applied by no-code
+    /// projects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_changes: Option<Vec<BalanceChangeSource>>,
+
+    /// Connection string to use instead of DATABASE_URL, `${ENV}` variables are substituted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_string: Option<String>,
+
+    /// Name of the env var holding the connection string, defaults to DATABASE_URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_string_env: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<PostgresSessionSettings>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_retry: Option<PostgresWriteRetry>,
+
+    /// Ask on the terminal before dropping tables, schemas or columns, startups which can not
+    /// ask fail instead. Rust projects confirm through `set_destructive_ddl_confirmer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm_destructive_ddl: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cdc: Option<PostgresCdc>,
+
+    /// Keep the handler cache of rust projects in postgres so it survives restarts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persist_handler_cache: Option<bool>,
+
+    /// Keep the keccak hash of every non indexed string and bytes value logged, so the `_hash`
+    /// columns of indexed strings and bytes can be looked up, only applied by no-code projects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashed_topic_preimages: Option<bool>,
+
+    /// Invariants between event and projection tables checked periodically
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconciliations: Option<Vec<Reconciliation>>,
+
+    /// Serve prometheus metrics of the postgres write path on this port at `/metrics`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_port: Option<u16>,
+
+    /// Host the metrics server binds to, defaults to `127.0.0.1`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_host: Option<String>,
+
+    /// Record the block ranges every event was fetched for without any logs into an internal
+    /// `heartbeats` table, so sparse events show the indexer was alive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeats: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify: Option<PostgresVerify>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplicaIdentity {
+    /// Old rows carry only their primary key columns
+    #[default]
+    Default,
+    /// Old rows carry every column, what Debezium needs for complete update and delete events
+    Full,
+}
+
+impl ReplicaIdentity {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            ReplicaIdentity::Default => "DEFAULT",
+            ReplicaIdentity::Full => "FULL",
+        }
+    }
+}
+
+/// Logical decoding settings applied to every event table so CDC pipelines work without manual
+/// DDL, event tables default to the natural primary key with this set
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PostgresCdc {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replica_identity: Option<ReplicaIdentity>,
+
+    /// Publication the event tables are added to, created when it does not exist
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publication: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PrimaryKeyType {
+    /// `rindexer_id SERIAL`
+    #[default]
+    Serial,
+    /// `(network, tx_hash, log_index)` which also dedupes any log indexed twice
+    Natural,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PostgresLayout {
+    /// One `{indexer}_{contract}` schema with every network in the same event tables
+    #[default]
+    Shared,
+    /// One schema per network with a `{contract}_{event}` table per event
+    SchemaPerNetwork,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InsertStrategy {
+    /// Multi VALUES for small batches, UNNEST for medium batches and COPY for large batches
+    #[default]
+    Auto,
+    Copy,
+    Unnest,
+    Values,
+}
+
+fn default_indexing_stats_interval_seconds() -> u64 {
+    60
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexingStatsDetails {
+    pub enabled: bool,
+
+    #[serde(default = "default_indexing_stats_interval_seconds")]
+    pub interval_seconds: u64,
 }
 
 fn default_csv_path() -> String {
@@ -126,6 +484,12 @@ pub enum RelationshipsAndIndexersError {
 
     #[error("Could not prepare and drop indexes: {0}")]
     FailedToPrepareAndDropIndexes(#[from] PrepareIndexesError),
+
+    #[error("{0}")]
+    ApplyRelationshipsError(#[from] ApplyAllRelationships),
+
+    #[error("{0}")]
+    ApplyIndexesError(#[from] ApplyPostgresIndexesError),
 }
 
 impl Storage {
@@ -136,6 +500,31 @@ impl Storage {
         }
     }
 
+    /// The env var the postgres connection string is read from when it is not in the manifest
+    pub fn postgres_connection_string_env(&self) -> &str {
+        self.postgres
+            .as_ref()
+            .and_then(|details| details.connection_string_env.as_deref())
+            .unwrap_or(DEFAULT_CONNECTION_STRING_ENV)
+    }
+
+    pub fn postgres_connection_string(&self) -> Result<String, env::VarError> {
+        match self.postgres.as_ref().and_then(|details| details.connection_string.clone()) {
+            Some(connection_string) => Ok(connection_string),
+            None => connection_string_from_env(self.postgres_connection_string_env()),
+        }
+    }
+
+    /// The session settings with the application name defaulted to one tagged with the indexer
+    pub fn postgres_session_settings(&self, indexer_name: &str) -> PostgresSessionSettings {
+        let mut settings =
+            self.postgres.as_ref().and_then(|details| details.session.clone()).unwrap_or_default();
+        if settings.application_name.is_none() {
+            settings.application_name = Some(format!("rindexer_{}", camel_to_snake(indexer_name)));
+        }
+        settings
+    }
+
     pub fn postgres_disable_create_tables(&self) -> bool {
         let enabled = self.postgres_enabled();
         if !enabled {
@@ -156,6 +545,151 @@ impl Storage {
         self.postgres.as_ref().map_or(false, |details| details.drop_each_run.unwrap_or_default())
     }
 
+    /// BRIN index on block_number and btree on contract_address for every event table, on unless
+    /// turned off
+    pub fn postgres_default_indexes(&self) -> bool {
+        self.postgres_enabled() &&
+            self.postgres.as_ref().is_none_or(|details| details.default_indexes.unwrap_or(true))
+    }
+
+    /// New event tables get created UNLOGGED and switched to LOGGED once historic sync is done,
+    /// if postgres crashes before then the unlogged tables are truncated so the data has to be
+    /// resynced
+    pub fn postgres_unlogged_historic_sync(&self) -> bool {
+        self.postgres_enabled() &&
+            self.postgres
+                .as_ref()
+                .is_some_and(|details| details.unlogged_historic_sync.unwrap_or_default())
+    }
+
+    pub fn postgres_insert_strategy(&self) -> InsertStrategy {
+        self.postgres.as_ref().and_then(|details| details.insert_strategy).unwrap_or_default()
+    }
+
+    pub fn postgres_write_retry(&self) -> PostgresWriteRetry {
+        self.postgres.as_ref().and_then(|details| details.write_retry.clone()).unwrap_or_default()
+    }
+
+    pub fn postgres_confirm_destructive_ddl(&self) -> bool {
+        self.postgres
+            .as_ref()
+            .and_then(|details| details.confirm_destructive_ddl)
+            .unwrap_or_default()
+    }
+
+    pub fn postgres_layout(&self) -> PostgresLayout {
+        self.postgres.as_ref().and_then(|details| details.layout).unwrap_or_default()
+    }
+
+    /// CDC consumers key rows on the primary key so with `cdc` set it defaults to the natural one,
+    /// which stays the same when the events are indexed again
+    pub fn postgres_natural_primary_key(&self) -> bool {
+        let default = if self.postgres_cdc().is_some() {
+            PrimaryKeyType::Natural
+        } else {
+            PrimaryKeyType::Serial
+        };
+        self.postgres.as_ref().and_then(|details| details.primary_key).unwrap_or(default) ==
+            PrimaryKeyType::Natural
+    }
+
+    pub fn postgres_cdc(&self) -> Option<&PostgresCdc> {
+        self.postgres
+            .as_ref()
+            .filter(|details| details.enabled)
+            .and_then(|details| details.cdc.as_ref())
+    }
+
+    pub fn postgres_persist_handler_cache(&self) -> bool {
+        self.postgres_enabled() &&
+            self.postgres
+                .as_ref()
+                .is_some_and(|details| details.persist_handler_cache.unwrap_or_default())
+    }
+
+    pub fn postgres_heartbeats(&self) -> bool {
+        self.postgres_enabled() &&
+            self.postgres.as_ref().is_some_and(|details| details.heartbeats.unwrap_or_default())
+    }
+
+    pub fn postgres_verify(&self) -> Option<&PostgresVerify> {
+        self.postgres
+            .as_ref()
+            .filter(|details| details.enabled)
+            .and_then(|details| details.verify.as_ref())
+    }
+
+    pub fn postgres_metrics_port(&self) -> Option<u16> {
+        self.postgres
+            .as_ref()
+            .filter(|details| details.enabled)
+            .and_then(|details| details.metrics_port)
+    }
+
+    pub fn postgres_metrics_host(&self) -> &str {
+        self.postgres
+            .as_ref()
+            .and_then(|details| details.metrics_host.as_deref())
+            .unwrap_or("127.0.0.1")
+    }
+
+    pub fn postgres_hashed_topic_preimages(&self) -> bool {
+        self.postgres_enabled() &&
+            self.postgres
+                .as_ref()
+                .is_some_and(|details| details.hashed_topic_preimages.unwrap_or_default())
+    }
+
+    pub fn postgres_compact_bytea(&self) -> bool {
+        self.postgres_enabled() &&
+            self.postgres
+                .as_ref()
+                .is_some_and(|details| details.compact_bytea.unwrap_or_default())
+    }
+
+    pub fn postgres_projections(&self) -> &[Projection] {
+        self.postgres
+            .as_ref()
+            .filter(|details| details.enabled)
+            .and_then(|details| details.projections.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn postgres_reconciliations(&self) -> &[Reconciliation] {
+        self.postgres
+            .as_ref()
+            .filter(|details| details.enabled)
+            .and_then(|details| details.reconciliations.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn postgres_rollups(&self) -> &[Rollup] {
+        self.postgres
+            .as_ref()
+            .filter(|details| details.enabled)
+            .and_then(|details| details.rollups.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn postgres_balance_changes(&self) -> &[BalanceChangeSource] {
+        self.postgres
+            .as_ref()
+            .filter(|details| details.enabled)
+            .and_then(|details| details.balance_changes.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn postgres_indexing_stats(&self) -> Option<&IndexingStatsDetails> {
+        if !self.postgres_enabled() {
+            return None;
+        }
+
+        self.postgres
+            .as_ref()
+            .and_then(|details| details.indexing_stats.as_ref())
+            .filter(|stats| stats.enabled)
+    }
+
     pub fn csv_enabled(&self) -> bool {
         match &self.csv {
             Some(details) => details.enabled,
@@ -188,15 +722,17 @@ impl Storage {
                 let mut postgres_indexes: Vec<PostgresIndexResult> = vec![];
 
                 info!("Temp dropping constraints relationships from the database for historic indexing for speed reasons");
-                drop_last_known_relationships(manifest_name).await?;
+                drop_last_known_relationships(self, manifest_name).await?;
 
                 let mapped_relationships = &storage.relationships;
                 if let Some(mapped_relationships) = mapped_relationships {
                     let relationships_result = create_relationships(
+                        self,
                         project_path,
                         manifest_name,
                         contracts,
                         mapped_relationships,
+                        self.postgres_layout(),
                     )
                     .await;
                     match relationships_result {
@@ -210,11 +746,18 @@ impl Storage {
                 }
 
                 info!("Temp dropping indexes from the database for historic indexing for speed reasons");
-                drop_last_known_indexes(manifest_name).await?;
+                drop_last_known_indexes(self, manifest_name).await?;
 
                 if let Some(indexes) = &storage.indexes {
-                    let indexes_result =
-                        prepare_indexes(project_path, manifest_name, indexes, contracts).await;
+                    let indexes_result = prepare_indexes(
+                        self,
+                        project_path,
+                        manifest_name,
+                        indexes,
+                        contracts,
+                        self.postgres_layout(),
+                    )
+                    .await;
 
                     match indexes_result {
                         Ok(result) => {
@@ -234,4 +777,52 @@ impl Storage {
 
         Ok((vec![], vec![]))
     }
+
+    /// Creates the relationships and indexes with the manual apply policy, anything already
+    /// created is left as is. Returns how many relationships and indexes there are.
+    pub async fn apply_manual_relationships_and_indexes(
+        &self,
+        project_path: &Path,
+        manifest_name: &str,
+        contracts: &[Contract],
+    ) -> Result<(usize, usize), RelationshipsAndIndexersError> {
+        let Some(storage) = self.postgres.as_ref().filter(|details| details.enabled) else {
+            return Ok((0, 0));
+        };
+
+        let mut relationships = vec![];
+        if let Some(mapped_relationships) = &storage.relationships {
+            relationships = create_relationships(
+                self,
+                project_path,
+                manifest_name,
+                contracts,
+                mapped_relationships,
+                self.postgres_layout(),
+            )
+            .await?;
+        }
+        let relationships = Relationship::with_apply_policy(&relationships, ApplyPolicy::Manual);
+
+        let mut postgres_indexes = vec![];
+        if let Some(indexes) = &storage.indexes {
+            postgres_indexes = prepare_indexes(
+                self,
+                project_path,
+                manifest_name,
+                indexes,
+                contracts,
+                self.postgres_layout(),
+            )
+            .await?;
+        }
+        let postgres_indexes =
+            PostgresIndexResult::with_apply_policy(&postgres_indexes, ApplyPolicy::Manual);
+        let counts = (relationships.len(), postgres_indexes.len());
+
+        PostgresIndexResult::apply_indexes(self, manifest_name, postgres_indexes).await?;
+        Relationship::apply_all(self, manifest_name, &relationships).await?;
+
+        Ok(counts)
+    }
 }
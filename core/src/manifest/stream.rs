@@ -1,5 +1,6 @@
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
+#[cfg(feature = "rabbitmq")]
 use lapin::ExchangeKind;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{Map, Value};
@@ -13,6 +14,11 @@ pub struct StreamEvent {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub conditions: Option<Vec<Map<String, Value>>>,
+
+    /// Shapes each streamed event, string values can use {{placeholders}} of the event fields,
+    /// transaction_information, event_name and network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -21,6 +27,10 @@ pub struct SNSStreamTopicConfig {
     pub topic_arn: String,
     pub networks: Vec<String>,
     pub events: Vec<StreamEvent>,
+
+    /// Applied to every event of this stream on top of each event's own conditions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<Map<String, Value>>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -35,6 +45,21 @@ pub struct WebhookStreamConfig {
     pub shared_secret: String,
     pub networks: Vec<String>,
     pub events: Vec<StreamEvent>,
+
+    /// Applied to every event of this stream on top of each event's own conditions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<Map<String, Value>>>,
+}
+
+/// Mirrors `lapin::ExchangeKind` so manifests still parse without the rabbitmq feature
+#[cfg(not(feature = "rabbitmq"))]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum ExchangeKind {
+    Custom(String),
+    Direct,
+    Fanout,
+    Headers,
+    Topic,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
@@ -66,6 +91,10 @@ pub struct RabbitMQStreamQueueConfig {
     pub routing_key: Option<String>,
     pub networks: Vec<String>,
     pub events: Vec<StreamEvent>,
+
+    /// Applied to every event of this stream on top of each event's own conditions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<Map<String, Value>>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -105,14 +134,53 @@ impl RabbitMQStreamConfig {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamSerialization {
+    #[default]
+    Json,
+    Avro,
+    Protobuf,
+}
+
+impl StreamSerialization {
+    pub fn schema_type(&self) -> &'static str {
+        match self {
+            StreamSerialization::Json => "JSON",
+            StreamSerialization::Avro => "AVRO",
+            StreamSerialization::Protobuf => "PROTOBUF",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SchemaRegistryConfig {
+    pub url: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct KafkaStreamQueueConfig {
     pub topic: String,
 
+    /// Avro and protobuf messages are encoded against a schema generated from the ABI and
+    /// registered with the `schema_registry`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serialization: Option<StreamSerialization>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
     pub networks: Vec<String>,
     pub events: Vec<StreamEvent>,
+
+    /// Applied to every event of this stream on top of each event's own conditions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<Map<String, Value>>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -131,6 +199,74 @@ pub struct KafkaStreamConfig {
 
     pub acks: String,
     pub topics: Vec<KafkaStreamQueueConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_registry: Option<SchemaRegistryConfig>,
+}
+
+impl KafkaStreamConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for topic in &self.topics {
+            if topic.serialization.unwrap_or_default() == StreamSerialization::Json {
+                continue;
+            }
+
+            if self.schema_registry.is_none() {
+                return Err(format!(
+                    "Kafka topic {} uses {:?} serialization which needs a schema_registry",
+                    topic.topic,
+                    topic.serialization.unwrap_or_default()
+                ));
+            }
+
+            if topic.events.iter().any(|e| e.template.is_some()) {
+                return Err(format!(
+                    "Kafka topic {} can not use templates with avro or protobuf serialization",
+                    topic.topic
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Messages a sink could not take are written to disk and delivered in order once it recovers
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StreamSpillConfig {
+    /// Defaults to `.rindexer/stream_spill` in the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    /// Once a sink has this many MB spilled publishing fails again so indexing waits instead of
+    /// dropping messages, defaults to 512
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+}
+
+impl StreamSpillConfig {
+    pub fn path(&self) -> &str {
+        self.path.as_deref().unwrap_or(".rindexer/stream_spill")
+    }
+
+    pub fn max_size_bytes(&self) -> u64 {
+        self.max_size_mb.unwrap_or(512) * 1024 * 1024
+    }
+}
+
+/// Periodically tells every sink how far each network has been published so downstream stream
+/// processors can close their event time windows
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StreamWatermarkConfig {
+    /// Defaults to 10
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_seconds: Option<u64>,
+}
+
+impl StreamWatermarkConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_seconds.unwrap_or(10).max(1))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -146,17 +282,57 @@ pub struct StreamsConfig {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub kafka: Option<KafkaStreamConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spill: Option<StreamSpillConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<StreamWatermarkConfig>,
 }
 
 impl StreamsConfig {
     pub fn validate(&self) -> Result<(), String> {
         if let Some(rabbitmq) = &self.rabbitmq {
-            return rabbitmq.validate();
+            rabbitmq.validate()?;
+        }
+
+        if let Some(kafka) = &self.kafka {
+            kafka.validate()?;
         }
 
         Ok(())
     }
 
+    /// The first configured sink whose cargo feature rindexer was built without
+    pub fn disabled_feature(&self) -> Option<&'static str> {
+        if self.sns.is_some() && !cfg!(feature = "sns") {
+            return Some("sns");
+        }
+        if self.rabbitmq.is_some() && !cfg!(feature = "rabbitmq") {
+            return Some("rabbitmq");
+        }
+        if self.kafka.is_some() && !cfg!(feature = "kafka") {
+            return Some("kafka");
+        }
+
+        None
+    }
+
+    /// Whether any sink streams the event
+    pub fn has_event(&self, event_name: &str) -> bool {
+        let streams_event =
+            |events: &[StreamEvent]| events.iter().any(|e| e.event_name == event_name);
+
+        self.sns.as_ref().is_some_and(|sns| sns.topics.iter().any(|t| streams_event(&t.events))) ||
+            self.webhooks.iter().flatten().any(|w| streams_event(&w.events)) ||
+            self.rabbitmq
+                .as_ref()
+                .is_some_and(|r| r.exchanges.iter().any(|e| streams_event(&e.events))) ||
+            self.kafka
+                .as_ref()
+                .is_some_and(|k| k.topics.iter().any(|t| streams_event(&t.events)))
+    }
+
     pub fn get_streams_last_synced_block_path(&self) -> String {
         let mut path = ".rindexer/".to_string();
         if self.rabbitmq.is_some() {
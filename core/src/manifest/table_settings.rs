@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+/// Per table autovacuum overrides, anything left out keeps the server default
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct AutovacuumSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vacuum_scale_factor: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vacuum_threshold: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vacuum_insert_scale_factor: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vacuum_insert_threshold: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analyze_scale_factor: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analyze_threshold: Option<u64>,
+}
+
+/// Storage parameters of a generated table, append only event tables and heavily updated
+/// projection tables want different ones
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct TableStorageSettings {
+    /// Percentage of each page filled on insert, lower leaves room for HOT updates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fillfactor: Option<u8>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autovacuum: Option<AutovacuumSettings>,
+
+    /// Columns of a btree index which is created and marked for `CLUSTER`, so a maintenance
+    /// `CLUSTER` run orders the table by them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster_on: Option<Vec<String>>,
+}
+
+impl TableStorageSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(fillfactor) = self.fillfactor {
+            if !(10..=100).contains(&fillfactor) {
+                return Err(format!("fillfactor {} must be between 10 and 100", fillfactor));
+            }
+        }
+
+        if let Some(autovacuum) = &self.autovacuum {
+            for (name, scale_factor) in [
+                ("vacuum_scale_factor", autovacuum.vacuum_scale_factor),
+                ("vacuum_insert_scale_factor", autovacuum.vacuum_insert_scale_factor),
+                ("analyze_scale_factor", autovacuum.analyze_scale_factor),
+            ] {
+                if scale_factor.is_some_and(|value| !(0.0..=100.0).contains(&value)) {
+                    return Err(format!("autovacuum {} must be between 0 and 100", name));
+                }
+            }
+        }
+
+        if self.cluster_on.as_ref().is_some_and(|columns| columns.is_empty()) {
+            return Err("cluster_on needs at least one column".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// The `WITH` storage parameters, empty when nothing is set
+    pub fn storage_parameters(&self) -> Vec<String> {
+        let mut parameters = vec![];
+        if let Some(fillfactor) = self.fillfactor {
+            parameters.push(format!("fillfactor = {}", fillfactor));
+        }
+
+        if let Some(autovacuum) = &self.autovacuum {
+            if let Some(enabled) = autovacuum.enabled {
+                parameters.push(format!("autovacuum_enabled = {}", enabled));
+            }
+            let numbers = [
+                ("autovacuum_vacuum_scale_factor", autovacuum.vacuum_scale_factor),
+                ("autovacuum_vacuum_threshold", autovacuum.vacuum_threshold.map(|v| v as f64)),
+                ("autovacuum_vacuum_insert_scale_factor", autovacuum.vacuum_insert_scale_factor),
+                (
+                    "autovacuum_vacuum_insert_threshold",
+                    autovacuum.vacuum_insert_threshold.map(|v| v as f64),
+                ),
+                ("autovacuum_analyze_scale_factor", autovacuum.analyze_scale_factor),
+                ("autovacuum_analyze_threshold", autovacuum.analyze_threshold.map(|v| v as f64)),
+            ];
+            for (name, value) in numbers {
+                if let Some(value) = value {
+                    parameters.push(format!("{} = {}", name, value));
+                }
+            }
+        }
+
+        parameters
+    }
+}
+
+/// Storage settings for the tables of one event
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventTableSettings {
+    pub event_name: String,
+
+    #[serde(flatten)]
+    pub settings: TableStorageSettings,
+}
+
+pub fn table_settings_for_event<'a>(
+    table_settings: &'a Option<Vec<EventTableSettings>>,
+    event_name: &str,
+) -> Option<&'a TableStorageSettings> {
+    table_settings
+        .as_ref()?
+        .iter()
+        .find(|settings| settings.event_name == event_name)
+        .map(|settings| &settings.settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_parameters() {
+        let settings = TableStorageSettings {
+            fillfactor: Some(70),
+            autovacuum: Some(AutovacuumSettings {
+                vacuum_scale_factor: Some(0.01),
+                analyze_threshold: Some(1000),
+                ..Default::default()
+            }),
+            cluster_on: None,
+        };
+
+        assert_eq!(
+            settings.storage_parameters(),
+            vec![
+                "fillfactor = 70",
+                "autovacuum_vacuum_scale_factor = 0.01",
+                "autovacuum_analyze_threshold = 1000"
+            ]
+        );
+        assert!(settings.validate().is_ok());
+        assert!(TableStorageSettings { fillfactor: Some(5), ..Default::default() }
+            .validate()
+            .is_err());
+    }
+}
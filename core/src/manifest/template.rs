@@ -0,0 +1,223 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::{
+    contract::Contract,
+    core::Manifest,
+    storage::{ContractEventsIndexes, EventIndex, EventIndexes, PostgresIndexes},
+};
+
+/// Gas fields of a user operation are only in the `handleOps` calldata, they are decoded onto
+/// the `UserOperationEvent` rows
+pub const USER_OPERATION_GAS_COLUMNS: [&str; 5] = [
+    "call_gas_limit",
+    "verification_gas_limit",
+    "pre_verification_gas",
+    "max_fee_per_gas",
+    "max_priority_fee_per_gas",
+];
+
+/// Well known contracts rindexer ships the ABI for
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContractTemplate {
+    Erc20,
+    Erc721,
+    Erc1155,
+    Weth,
+    /// ERC-4337 EntryPoint (v0.6 and v0.7), user operations get their gas fields decoded from
+    /// the bundle calldata
+    Erc4337,
+}
+
+impl ContractTemplate {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContractTemplate::Erc20 => "erc20",
+            ContractTemplate::Erc721 => "erc721",
+            ContractTemplate::Erc1155 => "erc1155",
+            ContractTemplate::Weth => "weth",
+            ContractTemplate::Erc4337 => "erc4337",
+        }
+    }
+
+    pub fn abi(&self) -> &'static str {
+        match self {
+            ContractTemplate::Erc20 => include_str!("templates/erc20.abi.json"),
+            ContractTemplate::Erc721 => include_str!("templates/erc721.abi.json"),
+            ContractTemplate::Erc1155 => include_str!("templates/erc1155.abi.json"),
+            ContractTemplate::Weth => include_str!("templates/weth.abi.json"),
+            ContractTemplate::Erc4337 => include_str!("templates/erc4337_entry_point.abi.json"),
+        }
+    }
+
+    /// Event inputs worth an index each, the ones token holders and collections are looked up by
+    fn indexed_inputs(&self) -> &'static [(&'static str, &'static [&'static str])] {
+        match self {
+            ContractTemplate::Erc20 => {
+                &[("Transfer", &["from", "to"]), ("Approval", &["owner", "spender"])]
+            }
+            ContractTemplate::Erc721 => &[
+                ("Transfer", &["from", "to", "tokenId"]),
+                ("Approval", &["owner", "tokenId"]),
+                ("ApprovalForAll", &["owner", "operator"]),
+            ],
+            ContractTemplate::Erc1155 => &[
+                ("TransferSingle", &["from", "to", "id"]),
+                ("TransferBatch", &["from", "to"]),
+                ("ApprovalForAll", &["account", "operator"]),
+            ],
+            ContractTemplate::Weth => &[
+                ("Transfer", &["src", "dst"]),
+                ("Approval", &["src", "guy"]),
+                ("Deposit", &["dst"]),
+                ("Withdrawal", &["src"]),
+            ],
+            ContractTemplate::Erc4337 => &[
+                ("UserOperationEvent", &["sender", "paymaster", "userOpHash"]),
+                ("AccountDeployed", &["sender", "factory"]),
+                ("UserOperationRevertReason", &["sender", "userOpHash"]),
+            ],
+        }
+    }
+
+    fn default_indexes(&self, contract: &Contract) -> ContractEventsIndexes {
+        let is_indexed = |event_name: &str| {
+            contract
+                .include_events
+                .as_ref()
+                .is_none_or(|events| events.iter().any(|event| event == event_name)) &&
+                !contract
+                    .exclude_events
+                    .as_ref()
+                    .is_some_and(|events| events.iter().any(|event| event == event_name))
+        };
+
+        ContractEventsIndexes {
+            name: contract.name.clone(),
+            injected_parameters: None,
+            events: self
+                .indexed_inputs()
+                .iter()
+                .filter(|(event_name, _)| is_indexed(event_name))
+                .map(|(event_name, inputs)| EventIndexes {
+                    name: event_name.to_string(),
+                    injected_parameters: None,
+                    indexes: inputs
+                        .iter()
+                        .map(|input| EventIndex {
+                            event_input_names: vec![input.to_string()],
+                            apply: None,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Templates which decode transaction calldata can only run in no-code projects
+    pub fn decodes_calldata(&self) -> bool {
+        matches!(self, ContractTemplate::Erc4337)
+    }
+
+    /// Columns the template writes beyond the event inputs, all NUMERIC
+    pub fn columns_for_event(&self, event_name: &str) -> &'static [&'static str] {
+        match self {
+            ContractTemplate::Erc4337 if event_name == "UserOperationEvent" => {
+                &USER_OPERATION_GAS_COLUMNS
+            }
+            _ => &[],
+        }
+    }
+}
+
+pub fn template_columns_for_event(
+    template: &Option<ContractTemplate>,
+    event_name: &str,
+) -> &'static [&'static str] {
+    template.map_or(&[], |template| template.columns_for_event(event_name))
+}
+
+/// Writes the template ABI to the project `abis` folder for the generated code which points
+/// `abigen!` at a file, returns its path relative to the project. Only written when it differs
+pub fn write_template_abi(
+    project_path: &Path,
+    template: ContractTemplate,
+) -> Result<String, std::io::Error> {
+    let relative_path = format!("./abis/{}.abi.json", template.name());
+    let full_path = project_path.join(&relative_path);
+    if fs::read_to_string(&full_path).ok().as_deref() != Some(template.abi()) {
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&full_path, template.abi())?;
+    }
+
+    Ok(relative_path)
+}
+
+/// Templated contracts get the template indexes unless the manifest already has indexes for them
+pub fn add_template_indexes(manifest: &mut Manifest) {
+    let Some(postgres) = manifest.storage.postgres.as_mut() else {
+        return;
+    };
+
+    if !manifest.contracts.iter().any(|contract| contract.template.is_some()) {
+        return;
+    }
+
+    let indexes = postgres.indexes.get_or_insert(PostgresIndexes {
+        apply: None,
+        global_injected_parameters: None,
+        contracts: None,
+    });
+    let contracts = indexes.contracts.get_or_insert_with(Vec::new);
+    for contract in &manifest.contracts {
+        if let Some(template) = contract.template {
+            if !contracts.iter().any(|indexes| indexes.name == contract.name) {
+                contracts.push(template.default_indexes(contract));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::ABIItem;
+
+    #[test]
+    fn test_template_abi_is_read_without_touching_the_project() {
+        let project = tempfile::tempdir().unwrap();
+        let contract: Contract = serde_yaml::from_str("name: Token\ntemplate: erc20").unwrap();
+
+        assert_eq!(contract.read_abi(project.path()).unwrap(), ContractTemplate::Erc20.abi());
+        assert_eq!(fs::read_dir(project.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_template_indexes_exist_in_abi() {
+        for template in [
+            ContractTemplate::Erc20,
+            ContractTemplate::Erc721,
+            ContractTemplate::Erc1155,
+            ContractTemplate::Weth,
+            ContractTemplate::Erc4337,
+        ] {
+            let abi_items: Vec<ABIItem> = serde_json::from_str(template.abi()).unwrap();
+            for (event_name, inputs) in template.indexed_inputs() {
+                let event = abi_items.iter().find(|item| item.name == *event_name).unwrap();
+                for input in *inputs {
+                    assert!(
+                        event.inputs.iter().any(|abi_input| abi_input.name == *input),
+                        "{} {} has no input {}",
+                        template.name(),
+                        event_name,
+                        input
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueTransformType {
+    /// Divide an unsigned integer by 10^decimals and store it as a DECIMAL
+    ScaleDecimals,
+    /// Lowercase an address or string
+    Lowercase,
+    /// Hash a string or bytes and store the hash, optionally salted
+    Hash,
+    /// Replace the value before it reaches any storage or stream
+    Redact,
+}
+
+/// The value every redacted input is written as
+pub const REDACTED_VALUE: &str = "[redacted]";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Keccak256,
+    Sha256,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventInputTransform {
+    pub event_name: String,
+
+    pub input_name: String,
+
+    pub transform: ValueTransformType,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<u32>,
+
+    /// Only for `hash`, defaults to keccak256
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<HashAlgorithm>,
+
+    /// Only for `hash`, prepended to the value so low entropy inputs can not be brute forced
+    /// from the hash, use a `${ENV}` variable to keep it out of the manifest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+}
+
+impl EventInputTransform {
+    pub fn db_type(&self, abi_type: &str) -> &'static str {
+        match self.transform {
+            ValueTransformType::ScaleDecimals => "NUMERIC",
+            ValueTransformType::Hash => "CHAR(66)",
+            ValueTransformType::Redact => "TEXT",
+            // keep the same column type the ABI would generate
+            ValueTransformType::Lowercase => {
+                if abi_type == "address" {
+                    "CHAR(42)"
+                } else {
+                    "TEXT"
+                }
+            }
+        }
+    }
+
+    /// Returns an error message if the transform can not be applied to the ABI type
+    pub fn validate_abi_type(&self, abi_type: &str) -> Result<(), String> {
+        if self.transform != ValueTransformType::Hash &&
+            (self.algorithm.is_some() || self.salt.is_some())
+        {
+            return Err("algorithm and salt can only be set for the hash transform".to_string());
+        }
+
+        let supported = match self.transform {
+            ValueTransformType::ScaleDecimals => {
+                if self.decimals.is_none() {
+                    return Err("scale_decimals requires decimals to be set".to_string());
+                }
+                abi_type.starts_with("uint") && !abi_type.ends_with("[]")
+            }
+            ValueTransformType::Lowercase => abi_type == "address" || abi_type == "string",
+            ValueTransformType::Hash => {
+                abi_type == "string" || (abi_type.starts_with("bytes") && !abi_type.ends_with("[]"))
+            }
+            // tuples are flattened into a value per component
+            ValueTransformType::Redact => !abi_type.starts_with("tuple"),
+        };
+
+        if supported {
+            Ok(())
+        } else {
+            Err(format!("{:?} transform is not supported for type {}", self.transform, abi_type))
+        }
+    }
+}
+
+pub fn transforms_for_event<'a>(
+    transforms: &'a Option<Vec<EventInputTransform>>,
+    event_name: &str,
+) -> Vec<&'a EventInputTransform> {
+    transforms
+        .as_ref()
+        .map(|transforms| transforms.iter().filter(|t| t.event_name == event_name).collect())
+        .unwrap_or_default()
+}
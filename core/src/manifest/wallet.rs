@@ -0,0 +1,43 @@
+use ethers::types::{Address, U64};
+use serde::{Deserialize, Serialize};
+
+use super::core::{deserialize_option_u64_from_string, serialize_option_u64_as_string};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrackedWallet {
+    pub address: Address,
+
+    /// Names the activity view of the wallet, defaults to the address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WalletNetwork {
+    pub network: String,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_option_u64_from_string",
+        serialize_with = "serialize_option_u64_as_string"
+    )]
+    pub start_block: Option<U64>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_option_u64_from_string",
+        serialize_with = "serialize_option_u64_as_string"
+    )]
+    pub end_block: Option<U64>,
+}
+
+/// Indexes every log a wallet shows up in, as the emitter or in any topic, decoded with the ABIs
+/// of every contract in the manifest and kept raw when none match
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WalletActivity {
+    pub wallets: Vec<TrackedWallet>,
+
+    pub networks: Vec<WalletNetwork>,
+}
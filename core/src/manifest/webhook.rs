@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+fn default_port() -> u16 {
+    3003
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookProvider {
+    /// Alchemy custom webhooks whose graphql query selects the block `hash` and `number` and the
+    /// `data`, `topics`, `index`, `account { address }` and `transaction { hash index }` of its
+    /// logs, signed with `x-alchemy-signature`
+    Alchemy,
+
+    /// `{ "logs": [...] }` with logs as returned by `eth_getLogs`, signed with
+    /// `x-rindexer-signature`
+    Logs,
+}
+
+impl WebhookProvider {
+    pub fn signature_header(&self) -> &'static str {
+        match self {
+            WebhookProvider::Alchemy => "x-alchemy-signature",
+            WebhookProvider::Logs => "x-rindexer-signature",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookSource {
+    pub network: String,
+
+    pub provider: WebhookProvider,
+
+    /// Key the HMAC-SHA256 signature of the payload is checked with, use a `${ENV}` value to keep
+    /// it out of the manifest
+    pub signing_key: String,
+
+    /// Contracts whose logs the webhook delivers, the events of every other contract on the
+    /// network are fetched with `eth_getLogs` for each delivered block range
+    #[serde(default)]
+    pub contracts: Vec<String>,
+}
+
+impl WebhookSource {
+    pub fn covers(&self, contract_name: &str) -> bool {
+        self.contracts.iter().any(|name| name == contract_name)
+    }
+}
+
+/// Receives logs pushed by provider webhooks on `POST /webhooks/{network}` instead of polling
+/// the network for new blocks once the historic sync is done, any blocks between deliveries are
+/// backfilled from the provider
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookIngestSettings {
+    /// Put a reverse proxy in front to receive deliveries from outside, defaults to `127.0.0.1`
+    #[serde(default = "default_host")]
+    pub host: String,
+
+    #[serde(default = "default_port")]
+    pub port: u16,
+
+    pub sources: Vec<WebhookSource>,
+}
+
+impl WebhookIngestSettings {
+    pub fn source(&self, network: &str) -> Option<&WebhookSource> {
+        self.sources.iter().find(|source| source.network == network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_ingest_defaults() {
+        let settings: WebhookIngestSettings = serde_yaml::from_str(
+            r#"
+sources:
+  - network: ethereum
+    provider: alchemy
+    signing_key: whsec_test
+    contracts: [RocketPoolETH]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.host, "127.0.0.1");
+        assert_eq!(settings.port, 3003);
+        assert!(settings.sources[0].covers("RocketPoolETH"));
+        assert!(!settings.sources[0].covers("USDT"));
+        assert_eq!(settings.source("ethereum").unwrap().provider, WebhookProvider::Alchemy);
+        assert!(settings.source("base").is_none());
+    }
+}
@@ -10,12 +10,40 @@ use tracing::error;
 
 use crate::{
     abi::ABIItem,
-    helpers::replace_env_variable_to_raw_name,
-    manifest::core::{Manifest, ProjectType},
+    database::postgres::{
+        generate::event_table_column_names,
+        internal_schema::{set_internal_schema_project, DEFAULT_INTERNAL_SCHEMA},
+        projection::unknown_expression_params,
+        reconciliation::generate_reconciliation_sql,
+    },
+    event::WhereExpression,
+    helpers::{camel_to_snake, replace_env_variable_to_raw_name},
+    manifest::{
+        computed_column::validate_computed_column_expression,
+        contract::{Contract, LoadAddressFileError},
+        core::{Manifest, ProjectType},
+        network::LiveIndexingStrategy,
+        price_enrichment::{price_enrichments_for_event, PriceOracle},
+        rollup::RollupFunction,
+        solidity_enum::validate_generated_type_names,
+        template::add_template_indexes,
+        transform::transforms_for_event,
+    },
 };
 
 pub const YAML_CONFIG_NAME: &str = "rindexer.yaml";
 
+const BASE_EVENT_COLUMN_NAMES: [&str; 8] = [
+    "rindexer_id",
+    "contract_address",
+    "tx_hash",
+    "block_number",
+    "block_hash",
+    "network",
+    "tx_index",
+    "log_index",
+];
+
 fn substitute_env_variables(contents: &str) -> Result<String, regex::Error> {
     let re = Regex::new(r"\$\{([^}]+)\}")?;
     let result = re.replace_all(contents, |caps: &Captures| {
@@ -36,6 +64,14 @@ pub enum ValidateManifestError {
     #[error("Invalid network mapped to contract: network - {0} contract - {1}")]
     InvalidNetworkMappedToContract(String, String),
 
+    #[error(
+        "Contract {0} has a details block without a network but no addresses to expand it for"
+    )]
+    ContractDetailsNetworkRequired(String),
+
+    #[error("Contract {0} sets a different address for network {1} in details than in addresses")]
+    ConflictingContractAddress(String, String),
+
     #[error("Invalid filter event name {0} for contract {1} does not exist in ABI")]
     InvalidFilterEventNameDoesntExistInABI(String, String),
 
@@ -45,9 +81,33 @@ pub enum ValidateManifestError {
     #[error("Event {0} included in include_events for contract {1} but not found in ABI - it must be an event type and match the name exactly")]
     EventIncludedNotFoundInABI(String, String),
 
+    #[error("Event {0} excluded in exclude_events for contract {1} but not found in ABI - it must be an event type and match the name exactly")]
+    EventExcludedNotFoundInABI(String, String),
+
+    #[error("Event {0} in event_networks for contract {1} not found in ABI")]
+    EventNetworksEventNotFoundInABI(String, String),
+
+    #[error("Network {0} in event_networks for event {1} is not a network of contract {2}")]
+    EventNetworksNetworkNotOnContract(String, String, String),
+
+    #[error("Event {0} in handler_concurrency for contract {1} not found in ABI")]
+    HandlerConcurrencyEventNotFoundInABI(String, String),
+
+    #[error("max_concurrency for event {0} on contract {1} must be greater than 0")]
+    InvalidHandlerConcurrency(String, String),
+
     #[error("Event {0} not found in ABI for contract {1}")]
     IndexedFilterEventNotFoundInABI(String, String),
 
+    #[error("Verify every {0} is not valid, use a number with a s, m, h, d or w suffix")]
+    InvalidVerifyEvery(String),
+
+    #[error("Event {0} in depends_on for contract {1} not found in ABI")]
+    DependsOnEventNotFoundInABI(String, String),
+
+    #[error("Contract {0} in depends_on for contract {1} is not in the manifest")]
+    DependsOnContractNotFound(String, String),
+
     #[error("Indexed filter defined more than allowed for event {0} for contract {1} - indexed expected: {2} defined: {3}")]
     IndexedFilterDefinedMoreThanAllowed(String, String, usize, usize),
 
@@ -57,19 +117,238 @@ pub enum ValidateManifestError {
     #[error("Relationship foreign key contract {0} not found")]
     RelationshipForeignKeyContractNotFound(String),
 
+    #[error(
+        "Relationship on {0}.{1} must set either event_input_name or a non empty event_input_names"
+    )]
+    InvalidRelationshipInputNames(String, String),
+
+    #[error("{1} is configured but rindexer was built without the `{0}` feature, rebuild with `--features {0}`")]
+    FeatureNotEnabled(String, String),
+
     #[error("Streams config is invalid: {0}")]
     StreamsConfigValidationError(String),
+
+    #[error("Transforms are only supported for no-code projects - contract {0}")]
+    TransformsNotSupportedForRustProjects(String),
+
+    #[error("Transform for event {0} input {1} on contract {2} is invalid: {3}")]
+    InvalidTransform(String, String, String, String),
+
+    #[error("Event conditions are only supported for no-code projects - contract {0}")]
+    EventConditionsNotSupportedForRustProjects(String),
+
+    #[error("Condition for event {0} on contract {1} is invalid: {2}")]
+    InvalidEventCondition(String, String, String),
+
+    #[error("Computed column {0} for event {1} on contract {2} is invalid: {3}")]
+    InvalidComputedColumn(String, String, String, String),
+
+    #[error("Enum for input {1} on event {0} on contract {2} is invalid: {3}")]
+    InvalidEnum(String, String, String, String),
+
+    #[error("Typed address for input {1} on event {0} on contract {2} is invalid: {3}")]
+    InvalidTypedAddress(String, String, String, String),
+
+    #[error("Generated types of contract {0} are invalid: {1}")]
+    InvalidGeneratedTypes(String, String),
+
+    #[error("Price enrichment is only supported for no-code projects - contract {0}")]
+    PriceEnrichmentNotSupportedForRustProjects(String),
+
+    #[error("Price enrichment for event {0} on contract {1} is invalid: {2}")]
+    InvalidPriceEnrichment(String, String, String),
+
+    #[error("Anonymous events are only supported for no-code projects - contract {0}")]
+    AnonymousEventsNotSupportedForRustProjects(String),
+
+    #[error("Anonymous event {0} on contract {1} is invalid: {2}")]
+    InvalidAnonymousEvent(String, String, String),
+
+    #[error("overloaded_events is only supported for no-code projects - contract {0}")]
+    OverloadedEventsNotSupportedForRustProjects(String),
+
+    #[error("Overloaded event {0} on contract {1} is invalid: {2}")]
+    InvalidOverloadedEvent(String, String, String),
+
+    #[error("store_raw_logs is only supported for no-code projects - contract {0}")]
+    RawLogsNotSupportedForRustProjects(String),
+
+    #[error("JSONB storage is only supported for no-code projects - contract {0}")]
+    JsonbStorageNotSupportedForRustProjects(String),
+
+    #[error("JSONB storage for event {0} on contract {1} is invalid: {2}")]
+    InvalidJsonbStorage(String, String, String),
+
+    #[error("Network {0} uses the subscribe live indexing strategy but has no ws url")]
+    SubscribeLiveIndexingStrategyWithoutWs(String),
+
+    #[error("Network {0} has a proxy but websocket subscriptions can not go through one, use the poll or filter live indexing strategy")]
+    SubscribeLiveIndexingStrategyWithProxy(String),
+
+    #[error("Network {0} catch_up needs max_blocks_per_second or max_compute_units_per_second greater than 0")]
+    InvalidCatchUpLimit(String),
+
+    #[error("Network {0} schedule window {1}-{2} is invalid: {3}")]
+    InvalidScheduleWindow(String, String, String, String),
+
+    #[error("Network {0} head_consensus quorum {1} has to be between 1 and its {2} rpc endpoints")]
+    InvalidHeadQuorum(String, usize, usize),
+
+    #[error("Projections are only supported for no-code projects - projection {0}")]
+    ProjectionsNotSupportedForRustProjects(String),
+
+    #[error("Projection {0} upsert for {1}::{2} is invalid: {3}")]
+    InvalidProjection(String, String, String, String),
+
+    #[error("Projection {0} table settings are invalid: {1}")]
+    InvalidProjectionTableSettings(String, String),
+
+    #[error("Table settings for event {0} on contract {1} are invalid: {2}")]
+    InvalidTableSettings(String, String, String),
+
+    #[error("Rollups are only supported for no-code projects - rollup {0}")]
+    RollupsNotSupportedForRustProjects(String),
+
+    #[error("Rollup {0} is invalid: {1}")]
+    InvalidRollup(String, String),
+
+    #[error("Reconciliation {0} is invalid: {1}")]
+    InvalidReconciliation(String, String),
+
+    #[error("Balance changes are only supported for no-code projects - contract {0}")]
+    BalanceChangesNotSupportedForRustProjects(String),
+
+    #[error("Balance changes for event {1} on contract {0} are invalid: {2}")]
+    InvalidBalanceChange(String, String, String),
+
+    #[error("Only one of postgres connection_string or connection_string_env can be set")]
+    PostgresConnectionStringAndEnvSet,
+
+    #[error("Postgres connection_string is only supported for no-code projects, use connection_string_env so it is not generated into the code")]
+    PostgresConnectionStringNotSupportedForRustProjects,
+
+    #[error(
+        "Invalid postgres cdc publication name {0} - use lowercase letters, digits and underscores"
+    )]
+    InvalidPostgresCdcPublication(String),
+
+    #[error("Postgres cdc can not be used with unlogged_historic_sync, unlogged tables are not written to the WAL so logical decoding never sees their rows")]
+    PostgresCdcWithUnloggedHistoricSync,
+
+    #[error("Event push needs a token")]
+    PushTokenMissing,
+
+    #[error("Contract {0} needs an abi or a template")]
+    ContractAbiOrTemplateRequired(String),
+
+    #[error("The {1} template is only supported for no-code projects - contract {0}")]
+    TemplateNotSupportedForRustProjects(String, String),
+
+    #[error("Runtime setting {0} must be greater than 0")]
+    InvalidRuntimeSetting(String),
+
+    #[error("Wallet activity is only supported for no-code projects")]
+    WalletsNotSupportedForRustProjects,
+
+    #[error("Wallet activity is written to postgres so postgres storage needs to be enabled")]
+    WalletsRequirePostgres,
+
+    #[error("Invalid network for wallet activity: {0}")]
+    InvalidWalletNetwork(String),
+
+    #[error("Gas prices are written to postgres so postgres storage needs to be enabled")]
+    GasPricesRequirePostgres,
+
+    #[error("Invalid network for gas price tracking: {0}")]
+    InvalidGasPriceNetwork(String),
+
+    #[error("Invalid schema for gas price tracking: {0}")]
+    InvalidGasPriceSchema(String),
+
+    #[error("Webhook ingest keeps the last synced blocks in postgres so postgres storage needs to be enabled")]
+    WebhooksRequirePostgres,
+
+    #[error("Invalid network for webhook ingest: {0}")]
+    InvalidWebhookNetwork(String),
+
+    #[error("Network {0} has more than one webhook source")]
+    DuplicateWebhookNetwork(String),
+
+    #[error("Block anomalies are written to postgres so postgres storage needs to be enabled")]
+    BlockAnomaliesRequirePostgres,
+
+    #[error("Invalid network for block anomalies: {0}")]
+    InvalidBlockAnomalyNetwork(String),
+
+    #[error("Invalid schema for block anomalies: {0}")]
+    InvalidBlockAnomalySchema(String),
+
+    #[error(
+        "Invalid project_id {0} - it must be 1 to {1} lowercase letters, digits or underscores"
+    )]
+    InvalidProjectId(String, usize),
+}
+
+/// Exactly one of the single input or the list of inputs
+fn valid_relationship_input_names(
+    event_input_name: &str,
+    event_input_names: &Option<Vec<String>>,
+) -> bool {
+    match event_input_names {
+        Some(names) => event_input_name.is_empty() && !names.is_empty(),
+        None => !event_input_name.is_empty(),
+    }
 }
 
-fn validate_manifest(
+fn is_valid_schema_name(schema: &str) -> bool {
+    !schema.is_empty() &&
+        schema.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+pub(crate) fn validate_manifest(
     project_path: &Path,
     manifest: &Manifest,
 ) -> Result<(), ValidateManifestError> {
+    if let Some(project_id) = &manifest.project_id {
+        // postgres truncates identifiers over 63 bytes
+        let max_length = 63 - DEFAULT_INTERNAL_SCHEMA.len() - 1;
+        if project_id.is_empty() ||
+            project_id.len() > max_length ||
+            !project_id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(ValidateManifestError::InvalidProjectId(project_id.clone(), max_length));
+        }
+    }
+
     for contract in &manifest.contracts {
+        if contract.abi.is_empty() && contract.template.is_none() {
+            return Err(ValidateManifestError::ContractAbiOrTemplateRequired(contract.name.clone()));
+        }
+        if let Some(template) = &contract.template {
+            if template.decodes_calldata() && manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::TemplateNotSupportedForRustProjects(
+                    contract.name.clone(),
+                    template.name().to_string(),
+                ));
+            }
+        }
+
         let events = ABIItem::read_abi_items(project_path, contract)
             .map_err(|e| ValidateManifestError::InvalidABI(contract.name.clone(), e.to_string()))?;
 
+        if let Some(network) = contract.conflicting_network_address() {
+            return Err(ValidateManifestError::ConflictingContractAddress(
+                contract.name.clone(),
+                network.to_string(),
+            ));
+        }
+
         for detail in &contract.details {
+            if detail.network.is_empty() {
+                return Err(ValidateManifestError::ContractDetailsNetworkRequired(
+                    contract.name.clone(),
+                ));
+            }
             let has_network = manifest.networks.iter().any(|n| n.name == detail.network);
             if !has_network {
                 return Err(ValidateManifestError::InvalidNetworkMappedToContract(
@@ -116,6 +395,43 @@ fn validate_manifest(
             }
         }
 
+        // rust projects keep overloaded events under the names of their generated bindings
+        if manifest.project_type != ProjectType::NoCode {
+            if contract.overloaded_events.is_some() {
+                return Err(ValidateManifestError::OverloadedEventsNotSupportedForRustProjects(
+                    contract.name.clone(),
+                ));
+            }
+        } else {
+            for event in events.iter().filter(|e| e.type_ == "event") {
+                if events.iter().filter(|e| e.type_ == "event" && e.name == event.name).count() > 1
+                {
+                    return Err(ValidateManifestError::InvalidOverloadedEvent(
+                        event.name.clone(),
+                        contract.name.clone(),
+                        "event name is used more than once".to_string(),
+                    ));
+                }
+            }
+        }
+
+        if let Some(overloaded_events) = &contract.overloaded_events {
+            for overloaded_event in overloaded_events {
+                let matches_overload = events.iter().any(|e| {
+                    e.abi_name.is_some() &&
+                        e.full_event_signature().ok().as_deref() ==
+                            Some(overloaded_event.signature.replace(' ', "").as_str())
+                });
+                if !matches_overload {
+                    return Err(ValidateManifestError::InvalidOverloadedEvent(
+                        overloaded_event.signature.clone(),
+                        contract.name.clone(),
+                        "signature does not match an overloaded event in the ABI".to_string(),
+                    ));
+                }
+            }
+        }
+
         if let Some(include_events) = &contract.include_events {
             for event in include_events {
                 if !events.iter().any(|e| e.name == *event && e.type_ == "event") {
@@ -127,18 +443,692 @@ fn validate_manifest(
             }
         }
 
+        if let Some(exclude_events) = &contract.exclude_events {
+            // excluded events are already filtered out of `events`
+            let unfiltered_contract = Contract { exclude_events: None, ..contract.clone() };
+            let all_events =
+                ABIItem::read_abi_items(project_path, &unfiltered_contract).map_err(|e| {
+                    ValidateManifestError::InvalidABI(contract.name.clone(), e.to_string())
+                })?;
+            for event in exclude_events {
+                if !all_events.iter().any(|e| e.name == *event && e.type_ == "event") {
+                    return Err(ValidateManifestError::EventExcludedNotFoundInABI(
+                        event.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+            }
+        }
+
+        if let Some(event_networks) = &contract.event_networks {
+            for event_network in event_networks {
+                if !events.iter().any(|e| e.name == event_network.event_name && e.type_ == "event")
+                {
+                    return Err(ValidateManifestError::EventNetworksEventNotFoundInABI(
+                        event_network.event_name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+
+                for network in &event_network.networks {
+                    if !contract.details.iter().any(|d| d.network == *network) {
+                        return Err(ValidateManifestError::EventNetworksNetworkNotOnContract(
+                            network.clone(),
+                            event_network.event_name.clone(),
+                            contract.name.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if let Some(limits) = &contract.handler_concurrency {
+            for limit in limits {
+                if !events.iter().any(|e| e.name == limit.event_name && e.type_ == "event") {
+                    return Err(ValidateManifestError::HandlerConcurrencyEventNotFoundInABI(
+                        limit.event_name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+
+                if limit.max_concurrency == 0 {
+                    return Err(ValidateManifestError::InvalidHandlerConcurrency(
+                        limit.event_name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+            }
+        }
+
         if let Some(_dependency_events) = &contract.dependency_events {
             // TODO - validate the events all exist in the contract ABIs
         }
 
+        for (dependency, dependent) in contract.depends_on_edges() {
+            if !events.iter().any(|e| e.name == dependent.event_name && e.type_ == "event") {
+                return Err(ValidateManifestError::DependsOnEventNotFoundInABI(
+                    dependent.event_name,
+                    contract.name.clone(),
+                ));
+            }
+
+            let Some(dependency_contract) =
+                manifest.contracts.iter().find(|c| c.name == dependency.contract_name)
+            else {
+                return Err(ValidateManifestError::DependsOnContractNotFound(
+                    dependency.contract_name,
+                    contract.name.clone(),
+                ));
+            };
+            let dependency_events = if dependency_contract.name == contract.name {
+                events.clone()
+            } else {
+                ABIItem::read_abi_items(project_path, dependency_contract).map_err(|e| {
+                    ValidateManifestError::InvalidABI(
+                        dependency_contract.name.clone(),
+                        e.to_string(),
+                    )
+                })?
+            };
+            if !dependency_events
+                .iter()
+                .any(|e| e.name == dependency.event_name && e.type_ == "event")
+            {
+                return Err(ValidateManifestError::DependsOnEventNotFoundInABI(
+                    dependency.event_name,
+                    dependency_contract.name.clone(),
+                ));
+            }
+        }
+
         if let Some(streams) = &contract.streams {
+            if let Some(feature) = streams.disabled_feature() {
+                return Err(ValidateManifestError::FeatureNotEnabled(
+                    feature.to_string(),
+                    format!("The {} stream on contract {}", feature, contract.name),
+                ));
+            }
             if let Err(e) = streams.validate() {
                 return Err(ValidateManifestError::StreamsConfigValidationError(e));
             }
         }
+
+        if let Some(transforms) = &contract.transforms {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::TransformsNotSupportedForRustProjects(
+                    contract.name.clone(),
+                ));
+            }
+
+            for transform in transforms {
+                let invalid = |reason: String| {
+                    ValidateManifestError::InvalidTransform(
+                        transform.event_name.clone(),
+                        transform.input_name.clone(),
+                        contract.name.clone(),
+                        reason,
+                    )
+                };
+
+                let input = events
+                    .iter()
+                    .find(|e| e.name == transform.event_name && e.type_ == "event")
+                    .ok_or_else(|| invalid("event not found in ABI".to_string()))?
+                    .inputs
+                    .iter()
+                    .find(|i| i.name == transform.input_name)
+                    .ok_or_else(|| invalid("input not found on event".to_string()))?;
+
+                if input.is_hashed_topic() {
+                    return Err(invalid(format!(
+                        "input is an indexed {} which is only logged as its hash",
+                        input.type_
+                    )));
+                }
+                transform.validate_abi_type(&input.type_).map_err(invalid)?;
+            }
+        }
+
+        if let Some(conditions) = &contract.event_conditions {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::EventConditionsNotSupportedForRustProjects(
+                    contract.name.clone(),
+                ));
+            }
+
+            for condition in conditions {
+                let invalid = |reason: String| {
+                    ValidateManifestError::InvalidEventCondition(
+                        condition.event_name.clone(),
+                        contract.name.clone(),
+                        reason,
+                    )
+                };
+
+                let event = events
+                    .iter()
+                    .find(|e| e.name == condition.event_name && e.type_ == "event")
+                    .ok_or_else(|| invalid("event not found in ABI".to_string()))?;
+                let expression = WhereExpression::parse(&condition.where_)
+                    .map_err(|e| invalid(e.to_string()))?;
+
+                for path in expression.inputs() {
+                    let mut inputs = Some(event.inputs.as_slice());
+                    let found = path.iter().all(|name| {
+                        match inputs.and_then(|fields| fields.iter().find(|i| i.name == *name)) {
+                            Some(input) => {
+                                inputs = input.components.as_deref();
+                                true
+                            }
+                            None => false,
+                        }
+                    });
+                    if !found {
+                        return Err(invalid(format!("input {} not found on event", path.join("."))));
+                    }
+                }
+            }
+        }
+
+        if let Some(anonymous_events) = &contract.anonymous_events {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::AnonymousEventsNotSupportedForRustProjects(
+                    contract.name.clone(),
+                ));
+            }
+
+            for layout in anonymous_events {
+                let invalid = |reason: &str| {
+                    ValidateManifestError::InvalidAnonymousEvent(
+                        layout.event_name.clone(),
+                        contract.name.clone(),
+                        reason.to_string(),
+                    )
+                };
+
+                let event = events
+                    .iter()
+                    .find(|e| e.name == layout.event_name && e.type_ == "event")
+                    .ok_or_else(|| invalid("event not found in ABI"))?;
+
+                if layout.topics.len() > 4 {
+                    return Err(invalid("a log can have at most 4 topics"));
+                }
+
+                let mut last_position = None;
+                for topic in &layout.topics {
+                    let position = event
+                        .inputs
+                        .iter()
+                        .position(|i| i.name == *topic)
+                        .ok_or_else(|| invalid("topic input not found on event"))?;
+                    if last_position.is_some_and(|last| position <= last) {
+                        return Err(invalid("topics must be in the order the inputs are declared"));
+                    }
+                    last_position = Some(position);
+
+                    let type_ = &event.inputs[position].type_;
+                    if type_ == "string" ||
+                        type_ == "bytes" ||
+                        type_ == "tuple" ||
+                        type_.ends_with(']')
+                    {
+                        return Err(invalid(
+                            "dynamic types are hashed in topics so can not be decoded",
+                        ));
+                    }
+                }
+
+                for detail in &contract.details {
+                    if detail.address().is_none() {
+                        return Err(invalid(
+                            "anonymous events can only be indexed by contract address",
+                        ));
+                    }
+
+                    if detail.indexed_filters.as_ref().is_some_and(|filters| {
+                        filters.iter().any(|f| f.event_name == layout.event_name)
+                    }) {
+                        return Err(invalid(
+                            "indexed filters are not supported for anonymous events",
+                        ));
+                    }
+                }
+            }
+        }
+
+        for table_settings in contract.table_settings.iter().flatten() {
+            let invalid = |reason: String| {
+                ValidateManifestError::InvalidTableSettings(
+                    table_settings.event_name.clone(),
+                    contract.name.clone(),
+                    reason,
+                )
+            };
+
+            let event_info = ABIItem::extract_event_names_and_signatures_from_abi(events.clone())
+                .map_err(|e| invalid(e.to_string()))?
+                .into_iter()
+                .find(|e| e.name == table_settings.event_name)
+                .ok_or_else(|| invalid("event not found in ABI".to_string()))?;
+            table_settings.settings.validate().map_err(invalid)?;
+
+            let column_names = event_table_column_names(contract, &event_info, &manifest.storage);
+            if let Some(column) = table_settings
+                .settings
+                .cluster_on
+                .iter()
+                .flatten()
+                .find(|column| !column_names.contains(column))
+            {
+                return Err(invalid(format!(
+                    "cluster_on column {} is not a column of the table",
+                    column
+                )));
+            }
+        }
+
+        if contract.stores_raw_logs() && manifest.project_type != ProjectType::NoCode {
+            return Err(ValidateManifestError::RawLogsNotSupportedForRustProjects(
+                contract.name.clone(),
+            ));
+        }
+
+        if let Some(jsonb_storage) = &contract.jsonb_storage {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::JsonbStorageNotSupportedForRustProjects(
+                    contract.name.clone(),
+                ));
+            }
+
+            for storage in jsonb_storage {
+                let invalid = |reason: String| {
+                    ValidateManifestError::InvalidJsonbStorage(
+                        storage.event_name.clone(),
+                        contract.name.clone(),
+                        reason,
+                    )
+                };
+
+                let event = events
+                    .iter()
+                    .find(|e| e.name == storage.event_name && e.type_ == "event")
+                    .ok_or_else(|| invalid("event not found in ABI".to_string()))?;
+
+                for input_name in contract.jsonb_inputs(&event.name, &event.inputs) {
+                    let Some(input) = event.inputs.iter().find(|i| i.name == input_name) else {
+                        return Err(invalid(format!("input {} not found on event", input_name)));
+                    };
+
+                    if input.is_hashed_topic() {
+                        return Err(invalid(format!(
+                            "input {} is an indexed {} which is only logged as its hash",
+                            input_name, input.type_
+                        )));
+                    }
+
+                    if transforms_for_event(&contract.transforms, &event.name)
+                        .iter()
+                        .any(|t| t.input_name == input_name)
+                    {
+                        return Err(invalid(format!(
+                            "input {} can not be transformed and stored as JSONB",
+                            input_name
+                        )));
+                    }
+                }
+            }
+        }
+
+        if let Some(computed_columns) = &contract.computed_columns {
+            for computed_column in computed_columns {
+                let invalid = |reason: String| {
+                    ValidateManifestError::InvalidComputedColumn(
+                        computed_column.name.clone(),
+                        computed_column.event_name.clone(),
+                        contract.name.clone(),
+                        reason,
+                    )
+                };
+
+                let event = events
+                    .iter()
+                    .find(|e| e.name == computed_column.event_name && e.type_ == "event")
+                    .ok_or_else(|| invalid("event not found in ABI".to_string()))?;
+
+                let column_name = &computed_column.name;
+                let clashes_with_price_column = price_enrichments_for_event(
+                    &contract.price_enrichment,
+                    &computed_column.event_name,
+                )
+                .iter()
+                .any(|enrichment| enrichment.column_name == *column_name);
+                if BASE_EVENT_COLUMN_NAMES.contains(&column_name.as_str()) ||
+                    event.inputs.iter().any(|i| camel_to_snake(&i.name) == *column_name) ||
+                    clashes_with_price_column
+                {
+                    return Err(invalid("clashes with an existing column".to_string()));
+                }
+
+                if computed_column.expression.trim().is_empty() {
+                    return Err(invalid("expression is empty".to_string()));
+                }
+                validate_computed_column_expression(&computed_column.expression)
+                    .map_err(|reason| invalid(format!("expression {}", reason)))?;
+                // the type goes into the column definition as it is too
+                validate_computed_column_expression(&computed_column.type_)
+                    .map_err(|reason| invalid(format!("type {}", reason)))?;
+            }
+        }
+
+        if let Some(enums) = &contract.enums {
+            for input_enum in enums {
+                let invalid = |reason: String| {
+                    ValidateManifestError::InvalidEnum(
+                        input_enum.event_name.clone(),
+                        input_enum.input_name.clone(),
+                        contract.name.clone(),
+                        reason,
+                    )
+                };
+
+                let event = events
+                    .iter()
+                    .find(|e| e.name == input_enum.event_name && e.type_ == "event")
+                    .ok_or_else(|| invalid("event not found in ABI".to_string()))?;
+
+                let input = event
+                    .inputs
+                    .iter()
+                    .find(|i| i.name == input_enum.input_name)
+                    .ok_or_else(|| invalid("input not found on event".to_string()))?;
+                if input.type_ != "uint8" {
+                    return Err(invalid(format!("input is {} but must be uint8", input.type_)));
+                }
+
+                if transforms_for_event(&contract.transforms, &event.name)
+                    .iter()
+                    .any(|t| t.input_name == input.name) ||
+                    contract.jsonb_inputs(&event.name, &event.inputs).contains(&input.name)
+                {
+                    return Err(invalid(
+                        "input can not be transformed or stored as JSONB".to_string(),
+                    ));
+                }
+
+                input_enum.validate().map_err(invalid)?;
+
+                let column_name = input_enum.label_column_name();
+                let clashes_with_computed_column = contract
+                    .computed_columns
+                    .iter()
+                    .flatten()
+                    .any(|c| c.event_name == event.name && c.name == column_name);
+                if BASE_EVENT_COLUMN_NAMES.contains(&column_name.as_str()) ||
+                    event.inputs.iter().any(|i| camel_to_snake(&i.name) == column_name) ||
+                    clashes_with_computed_column
+                {
+                    return Err(invalid(format!("{} clashes with an existing column", column_name)));
+                }
+            }
+        }
+
+        if let Some(typed_addresses) = &contract.typed_addresses {
+            for typed_address in typed_addresses {
+                let invalid = |reason: String| {
+                    ValidateManifestError::InvalidTypedAddress(
+                        typed_address.event_name.clone(),
+                        typed_address.input_name.clone(),
+                        contract.name.clone(),
+                        reason,
+                    )
+                };
+
+                let event = events
+                    .iter()
+                    .find(|e| e.name == typed_address.event_name && e.type_ == "event")
+                    .ok_or_else(|| invalid("event not found in ABI".to_string()))?;
+
+                let input = event
+                    .inputs
+                    .iter()
+                    .find(|i| i.name == typed_address.input_name)
+                    .ok_or_else(|| invalid("input not found on event".to_string()))?;
+                if input.type_ != "address" {
+                    return Err(invalid(format!("input is {} but must be address", input.type_)));
+                }
+            }
+        }
+
+        validate_generated_type_names(
+            contract.enums.as_deref().unwrap_or_default(),
+            contract.typed_addresses.as_deref().unwrap_or_default(),
+        )
+        .map_err(|e| ValidateManifestError::InvalidGeneratedTypes(contract.name.clone(), e))?;
+
+        if let Some(price_enrichments) = &contract.price_enrichment {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::PriceEnrichmentNotSupportedForRustProjects(
+                    contract.name.clone(),
+                ));
+            }
+
+            for price_enrichment in price_enrichments {
+                let invalid = |reason: String| {
+                    ValidateManifestError::InvalidPriceEnrichment(
+                        price_enrichment.event_name.clone(),
+                        contract.name.clone(),
+                        reason,
+                    )
+                };
+
+                let event = events
+                    .iter()
+                    .find(|e| e.name == price_enrichment.event_name && e.type_ == "event")
+                    .ok_or_else(|| invalid("event not found in ABI".to_string()))?;
+
+                let column_name = &price_enrichment.column_name;
+                if BASE_EVENT_COLUMN_NAMES.contains(&column_name.as_str()) ||
+                    event.inputs.iter().any(|i| camel_to_snake(&i.name) == *column_name)
+                {
+                    return Err(invalid(format!(
+                        "column {} clashes with an existing column",
+                        price_enrichment.column_name
+                    )));
+                }
+
+                if let PriceOracle::Chainlink { feeds } = &price_enrichment.oracle {
+                    for network in feeds.keys() {
+                        if !manifest.networks.iter().any(|n| n.name == *network) {
+                            return Err(invalid(format!(
+                                "chainlink feed network {} is not defined",
+                                network
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for network in &manifest.networks {
+        if network.live_indexing_strategy == Some(LiveIndexingStrategy::Subscribe) &&
+            network.ws.is_none()
+        {
+            return Err(ValidateManifestError::SubscribeLiveIndexingStrategyWithoutWs(
+                network.name.clone(),
+            ));
+        }
+        if network.live_indexing_strategy == Some(LiveIndexingStrategy::Subscribe) &&
+            network.proxy.is_some()
+        {
+            return Err(ValidateManifestError::SubscribeLiveIndexingStrategyWithProxy(
+                network.name.clone(),
+            ));
+        }
+        if let Some(catch_up) = &network.catch_up {
+            if !catch_up.has_limit() ||
+                catch_up.max_blocks_per_second == Some(0) ||
+                catch_up.max_compute_units_per_second == Some(0)
+            {
+                return Err(ValidateManifestError::InvalidCatchUpLimit(network.name.clone()));
+            }
+        }
+        for window in network.schedule.iter().flatten() {
+            let reason = if window.from_time().is_none() || window.to_time().is_none() {
+                Some("from and to must be HH:MM")
+            } else if window.from == window.to {
+                Some("from and to must differ")
+            } else if window.weekdays().is_none() {
+                Some("days must be mon to sun")
+            } else if window.max_blocks_per_second == Some(0) ||
+                window.max_compute_units_per_second == Some(0)
+            {
+                Some("limits must be greater than 0, use pause to stop historic sync")
+            } else if !window.paused() &&
+                window.max_blocks_per_second.is_none() &&
+                window.max_compute_units_per_second.is_none()
+            {
+                Some("needs pause, max_blocks_per_second or max_compute_units_per_second")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                return Err(ValidateManifestError::InvalidScheduleWindow(
+                    network.name.clone(),
+                    window.from.clone(),
+                    window.to.clone(),
+                    reason.to_string(),
+                ));
+            }
+        }
+        if let Some(quorum) = network.head_consensus.as_ref().and_then(|consensus| consensus.quorum)
+        {
+            let endpoints = network.rpc_endpoints().len();
+            if quorum == 0 || quorum > endpoints {
+                return Err(ValidateManifestError::InvalidHeadQuorum(
+                    network.name.clone(),
+                    quorum,
+                    endpoints,
+                ));
+            }
+        }
+        if network.proxy.as_ref().is_some_and(|proxy| proxy.is_socks()) && !cfg!(feature = "socks")
+        {
+            return Err(ValidateManifestError::FeatureNotEnabled(
+                "socks".to_string(),
+                format!("The socks5 proxy on network {}", network.name),
+            ));
+        }
+    }
+
+    if manifest.push.as_ref().is_some_and(|push| push.token.trim().is_empty()) {
+        return Err(ValidateManifestError::PushTokenMissing);
+    }
+
+    if let Some(setting) = manifest.runtime_settings().zero_settings().first() {
+        return Err(ValidateManifestError::InvalidRuntimeSetting(setting.to_string()));
+    }
+
+    if let Some(wallets) = &manifest.wallets {
+        if manifest.project_type != ProjectType::NoCode {
+            return Err(ValidateManifestError::WalletsNotSupportedForRustProjects);
+        }
+        if !manifest.storage.postgres_enabled() {
+            return Err(ValidateManifestError::WalletsRequirePostgres);
+        }
+        for wallet_network in &wallets.networks {
+            if !manifest.networks.iter().any(|network| network.name == wallet_network.network) {
+                return Err(ValidateManifestError::InvalidWalletNetwork(
+                    wallet_network.network.clone(),
+                ));
+            }
+        }
+    }
+
+    if let Some(gas_prices) = &manifest.gas_prices {
+        if !manifest.storage.postgres_enabled() {
+            return Err(ValidateManifestError::GasPricesRequirePostgres);
+        }
+        if let Some(schema) = gas_prices.schema.as_ref().filter(|s| !is_valid_schema_name(s)) {
+            return Err(ValidateManifestError::InvalidGasPriceSchema(schema.clone()));
+        }
+        for gas_price_network in &gas_prices.networks {
+            if !manifest.networks.iter().any(|network| network.name == gas_price_network.network) {
+                return Err(ValidateManifestError::InvalidGasPriceNetwork(
+                    gas_price_network.network.clone(),
+                ));
+            }
+        }
+    }
+
+    if let Some(block_anomalies) = &manifest.block_anomalies {
+        if !manifest.storage.postgres_enabled() {
+            return Err(ValidateManifestError::BlockAnomaliesRequirePostgres);
+        }
+        if let Some(schema) = block_anomalies.schema.as_ref().filter(|s| !is_valid_schema_name(s)) {
+            return Err(ValidateManifestError::InvalidBlockAnomalySchema(schema.clone()));
+        }
+        for anomaly_network in &block_anomalies.networks {
+            if !manifest.networks.iter().any(|network| network.name == anomaly_network.network) {
+                return Err(ValidateManifestError::InvalidBlockAnomalyNetwork(
+                    anomaly_network.network.clone(),
+                ));
+            }
+        }
+    }
+
+    if let Some(webhooks) = &manifest.webhooks {
+        if !manifest.storage.postgres_enabled() {
+            return Err(ValidateManifestError::WebhooksRequirePostgres);
+        }
+        for (i, source) in webhooks.sources.iter().enumerate() {
+            if !manifest.networks.iter().any(|network| network.name == source.network) {
+                return Err(ValidateManifestError::InvalidWebhookNetwork(source.network.clone()));
+            }
+            if webhooks.sources[..i].iter().any(|other| other.network == source.network) {
+                return Err(ValidateManifestError::DuplicateWebhookNetwork(source.network.clone()));
+            }
+        }
+    }
+
+    if manifest.storage.csv_enabled() && !cfg!(feature = "csv") {
+        return Err(ValidateManifestError::FeatureNotEnabled(
+            "csv".to_string(),
+            "CSV storage".to_string(),
+        ));
     }
 
     if let Some(postgres) = &manifest.storage.postgres {
+        if postgres.connection_string.is_some() {
+            if postgres.connection_string_env.is_some() {
+                return Err(ValidateManifestError::PostgresConnectionStringAndEnvSet);
+            }
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(
+                    ValidateManifestError::PostgresConnectionStringNotSupportedForRustProjects,
+                );
+            }
+        }
+
+        if let Some(cdc) = &postgres.cdc {
+            if manifest.storage.postgres_unlogged_historic_sync() {
+                return Err(ValidateManifestError::PostgresCdcWithUnloggedHistoricSync);
+            }
+            if let Some(publication) = &cdc.publication {
+                if publication.is_empty() ||
+                    publication.starts_with(|c: char| c.is_ascii_digit()) ||
+                    !publication
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+                {
+                    return Err(ValidateManifestError::InvalidPostgresCdcPublication(
+                        publication.clone(),
+                    ));
+                }
+            }
+        }
+
         if let Some(relationships) = &postgres.relationships {
             for relationship in relationships {
                 if !manifest.contracts.iter().any(|c| c.name == relationship.contract_name) {
@@ -146,6 +1136,15 @@ fn validate_manifest(
                         relationship.contract_name.clone(),
                     ));
                 }
+                if !valid_relationship_input_names(
+                    &relationship.event_input_name,
+                    &relationship.event_input_names,
+                ) {
+                    return Err(ValidateManifestError::InvalidRelationshipInputNames(
+                        relationship.contract_name.clone(),
+                        relationship.event_name.clone(),
+                    ));
+                }
 
                 for foreign_key in &relationship.foreign_keys {
                     if !manifest.contracts.iter().any(|c| c.name == foreign_key.contract_name) {
@@ -153,11 +1152,211 @@ fn validate_manifest(
                             foreign_key.contract_name.clone(),
                         ));
                     }
+                    if !valid_relationship_input_names(
+                        &foreign_key.event_input_name,
+                        &foreign_key.event_input_names,
+                    ) {
+                        return Err(ValidateManifestError::InvalidRelationshipInputNames(
+                            foreign_key.contract_name.clone(),
+                            foreign_key.event_name.clone(),
+                        ));
+                    }
                 }
 
                 // TODO - Add validation for the event names and event inputs match the ABIs
             }
         }
+
+        for projection in postgres.projections.iter().flatten() {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::ProjectionsNotSupportedForRustProjects(
+                    projection.name.clone(),
+                ));
+            }
+
+            if let Some(table_settings) = &projection.table_settings {
+                let invalid = |reason: String| {
+                    ValidateManifestError::InvalidProjectionTableSettings(
+                        projection.name.clone(),
+                        reason,
+                    )
+                };
+                table_settings.validate().map_err(invalid)?;
+
+                if let Some(column) = table_settings.cluster_on.iter().flatten().find(|column| {
+                    *column != "network" &&
+                        *column != "last_updated_block" &&
+                        !projection
+                            .keys
+                            .iter()
+                            .chain(&projection.columns)
+                            .any(|c| c.name == **column)
+                }) {
+                    return Err(invalid(format!(
+                        "cluster_on column {} is not a column of the projection",
+                        column
+                    )));
+                }
+            }
+
+            for upsert in &projection.upserts {
+                let invalid = |reason: String| {
+                    ValidateManifestError::InvalidProjection(
+                        projection.name.clone(),
+                        upsert.contract_name.clone(),
+                        upsert.event_name.clone(),
+                        reason,
+                    )
+                };
+
+                let contract = manifest
+                    .contracts
+                    .iter()
+                    .find(|c| c.name == upsert.contract_name)
+                    .ok_or_else(|| invalid("contract not found".to_string()))?;
+                let events = ABIItem::read_abi_items(project_path, contract)
+                    .map_err(|e| invalid(e.to_string()))?;
+                let event = events
+                    .iter()
+                    .find(|e| e.name == upsert.event_name)
+                    .ok_or_else(|| invalid("event not found in ABI".to_string()))?;
+
+                if let Some(key) =
+                    projection.keys.iter().find(|k| !upsert.keys.contains_key(&k.name))
+                {
+                    return Err(invalid(format!("key {} has no expression", key.name)));
+                }
+                if let Some(key) =
+                    upsert.keys.keys().find(|k| !projection.keys.iter().any(|p| p.name == **k))
+                {
+                    return Err(invalid(format!("{} is not a key of the projection", key)));
+                }
+                if let Some(column) =
+                    upsert.set.keys().find(|c| !projection.columns.iter().any(|p| p.name == **c))
+                {
+                    return Err(invalid(format!("{} is not a column of the projection", column)));
+                }
+                if let Some(param) = unknown_expression_params(
+                    &event.inputs,
+                    upsert.keys.values().chain(upsert.set.values()),
+                )
+                .first()
+                {
+                    return Err(invalid(format!(
+                        "${} is not an event input or transaction field",
+                        param
+                    )));
+                }
+            }
+        }
+
+        for rollup in postgres.rollups.iter().flatten() {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::RollupsNotSupportedForRustProjects(
+                    rollup.name.clone(),
+                ));
+            }
+
+            let invalid =
+                |reason: String| ValidateManifestError::InvalidRollup(rollup.name.clone(), reason);
+
+            if postgres.projections.iter().flatten().any(|p| p.name == rollup.name) {
+                return Err(invalid("a projection has the same name".to_string()));
+            }
+            if rollup.interval_seconds().is_none() {
+                return Err(invalid(format!("interval {} is not valid", rollup.interval)));
+            }
+            if rollup.aggregates.is_empty() {
+                return Err(invalid("no aggregates defined".to_string()));
+            }
+            if let Some(aggregate) = rollup
+                .aggregates
+                .iter()
+                .find(|a| a.function != RollupFunction::Count && a.value.is_none())
+            {
+                return Err(invalid(format!("aggregate {} needs a value", aggregate.name)));
+            }
+
+            let contract =
+                manifest.contracts.iter().find(|c| c.name == rollup.contract_name).ok_or_else(
+                    || invalid(format!("contract {} not found", rollup.contract_name)),
+                )?;
+            let events = ABIItem::read_abi_items(project_path, contract)
+                .map_err(|e| invalid(e.to_string()))?;
+            let event = events
+                .iter()
+                .find(|e| e.name == rollup.event_name)
+                .ok_or_else(|| invalid(format!("event {} not found in ABI", rollup.event_name)))?;
+
+            let expressions = rollup
+                .group_by
+                .iter()
+                .map(|group| &group.value)
+                .chain(rollup.aggregates.iter().filter_map(|a| a.value.as_ref()));
+            if let Some(param) = unknown_expression_params(&event.inputs, expressions).first() {
+                return Err(invalid(format!(
+                    "${} is not an event input or transaction field",
+                    param
+                )));
+            }
+        }
+
+        for reconciliation in postgres.reconciliations.iter().flatten() {
+            let invalid = |reason: String| {
+                ValidateManifestError::InvalidReconciliation(reconciliation.name.clone(), reason)
+            };
+
+            if reconciliation.every().is_none() {
+                return Err(invalid(format!("every {} is not valid", reconciliation.every)));
+            }
+            generate_reconciliation_sql(manifest, project_path, reconciliation)
+                .map_err(|e| invalid(e.to_string()))?;
+        }
+
+        if let Some(verify) = &postgres.verify {
+            if verify.every().is_none() {
+                return Err(ValidateManifestError::InvalidVerifyEvery(verify.every.clone()));
+            }
+        }
+
+        for source in postgres.balance_changes.iter().flatten() {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::BalanceChangesNotSupportedForRustProjects(
+                    source.contract_name.clone(),
+                ));
+            }
+
+            let invalid = |reason: String| {
+                ValidateManifestError::InvalidBalanceChange(
+                    source.contract_name.clone(),
+                    source.event_name.clone(),
+                    reason,
+                )
+            };
+
+            if source.from.is_none() && source.to.is_none() {
+                return Err(invalid("needs a from or a to account".to_string()));
+            }
+            let contract = manifest
+                .contracts
+                .iter()
+                .find(|c| c.name == source.contract_name)
+                .ok_or_else(|| invalid("contract not found".to_string()))?;
+            let events = ABIItem::read_abi_items(project_path, contract)
+                .map_err(|e| invalid(e.to_string()))?;
+            let event = events
+                .iter()
+                .find(|e| e.name == source.event_name)
+                .ok_or_else(|| invalid("event not found in ABI".to_string()))?;
+            if let Some(param) =
+                unknown_expression_params(&event.inputs, source.expressions()).first()
+            {
+                return Err(invalid(format!(
+                    "${} is not an event input or transaction field",
+                    param
+                )));
+            }
+        }
     }
 
     Ok(())
@@ -179,6 +1378,14 @@ pub enum ReadManifestError {
 
     #[error("No project path found using parent of manifest path")]
     NoProjectPathFoundUsingParentOfManifestPath,
+
+    #[error("{0}")]
+    CouldNotLoadAddressFile(#[from] LoadAddressFileError),
+
+    #[error(
+        "Network {0} proxy username and password have to be ${{ENV}} variables in rust projects"
+    )]
+    ProxyCredentialsNotInEnv(String),
 }
 
 pub fn read_manifest_raw(file_path: &PathBuf) -> Result<Manifest, ReadManifestError> {
@@ -187,12 +1394,15 @@ pub fn read_manifest_raw(file_path: &PathBuf) -> Result<Manifest, ReadManifestEr
 
     file.read_to_string(&mut contents)?;
 
-    let manifest: Manifest = serde_yaml::from_str(&contents)?;
+    let mut manifest: Manifest = serde_yaml::from_str(&contents)?;
 
     let project_path = file_path.parent();
     match project_path {
         None => Err(ReadManifestError::NoProjectPathFoundUsingParentOfManifestPath),
         Some(project_path) => {
+            for contract in &mut manifest.contracts {
+                contract.expand_network_addresses();
+            }
             validate_manifest(project_path, &manifest)?;
             Ok(manifest)
         }
@@ -223,6 +1433,45 @@ pub fn read_manifest(file_path: &PathBuf) -> Result<Manifest, ReadManifestError>
                     || replace_env_variable_to_raw_name(&network.rpc),
                     |n| replace_env_variable_to_raw_name(&n.rpc),
                 );
+
+            let rpcs_before_transform = manifest_before_transform
+                .networks
+                .iter()
+                .find(|n| n.name == network.name)
+                .and_then(|n| n.rpcs.clone());
+            if let (Some(rpcs), Some(rpcs_before_transform)) =
+                (&mut network.rpcs, rpcs_before_transform)
+            {
+                for (rpc, before_transform) in rpcs.iter_mut().zip(rpcs_before_transform) {
+                    rpc.url = replace_env_variable_to_raw_name(&before_transform.url);
+                }
+            }
+
+            let proxy_before_transform = manifest_before_transform
+                .networks
+                .iter()
+                .find(|n| n.name == network.name)
+                .and_then(|n| n.proxy.clone());
+            if let (Some(proxy), Some(before_transform)) =
+                (&mut network.proxy, proxy_before_transform)
+            {
+                // credentials are read from the env by the generated code, never written into it
+                let is_env_variable =
+                    |value: &String| value.starts_with("${") && value.ends_with('}');
+                if !before_transform
+                    .username
+                    .iter()
+                    .chain(&before_transform.password)
+                    .all(is_env_variable)
+                {
+                    return Err(ReadManifestError::ProxyCredentialsNotInEnv(network.name.clone()));
+                }
+                proxy.url = replace_env_variable_to_raw_name(&before_transform.url);
+                proxy.username =
+                    before_transform.username.as_deref().map(replace_env_variable_to_raw_name);
+                proxy.password =
+                    before_transform.password.as_deref().map(replace_env_variable_to_raw_name);
+            }
         }
     }
 
@@ -230,7 +1479,21 @@ pub fn read_manifest(file_path: &PathBuf) -> Result<Manifest, ReadManifestError>
     match project_path {
         None => Err(ReadManifestError::NoProjectPathFoundUsingParentOfManifestPath),
         Some(project_path) => {
+            for contract in &mut manifest_after_transform.contracts {
+                contract.expand_network_addresses();
+                for detail in &mut contract.details {
+                    detail.load_address_file(project_path)?;
+                }
+            }
+            add_template_indexes(&mut manifest_after_transform);
+            if manifest_after_transform.project_type == ProjectType::NoCode {
+                for contract in &mut manifest_after_transform.contracts {
+                    contract.rename_overloaded_events = true;
+                }
+            }
+
             validate_manifest(project_path, &manifest_after_transform)?;
+            set_internal_schema_project(manifest_after_transform.project_id.as_deref());
             Ok(manifest_after_transform)
         }
     }
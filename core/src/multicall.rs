@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use ethers::{
+    abi::{decode, encode, ParamType, Token},
+    prelude::ProviderError,
+    types::{transaction::eip2718::TypedTransaction, Address, BlockId, Bytes, TransactionRequest},
+    utils::id,
+};
+use thiserror::Error;
+
+use crate::provider::JsonRpcCachedProvider;
+
+/// Multicall3 is deployed at the same address on nearly every EVM chain
+pub const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+/// Keeps a single aggregate3 call well under the usual node gas and response size limits
+const MAX_CALLS_PER_BATCH: usize = 500;
+
+#[derive(Error, Debug)]
+pub enum MulticallError {
+    #[error("Provider error: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("Could not decode aggregate3 response: {0}")]
+    DecodeError(String),
+
+    #[error("Call {0} reverted and does not allow failure")]
+    CallReverted(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct MulticallCall {
+    pub target: Address,
+    pub call_data: Bytes,
+    pub allow_failure: bool,
+}
+
+impl MulticallCall {
+    pub fn new(target: Address, call_data: impl Into<Bytes>) -> Self {
+        Self { target, call_data: call_data.into(), allow_failure: true }
+    }
+
+    /// A call with no arguments, e.g. `decimals()`
+    pub fn from_signature(target: Address, signature: &str) -> Self {
+        Self::new(target, id(signature).to_vec())
+    }
+
+    pub fn allow_failure(mut self, allow_failure: bool) -> Self {
+        self.allow_failure = allow_failure;
+        self
+    }
+}
+
+/// Batches many eth_calls at the same block into a single Multicall3 `aggregate3` request
+#[derive(Clone)]
+pub struct Multicall {
+    provider: Arc<JsonRpcCachedProvider>,
+    address: Address,
+}
+
+impl Multicall {
+    pub fn new(provider: Arc<JsonRpcCachedProvider>) -> Self {
+        Self { provider, address: MULTICALL3_ADDRESS.parse().expect("Invalid multicall3 address") }
+    }
+
+    /// For chains where Multicall3 lives at a non-standard address
+    pub fn with_address(mut self, address: Address) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Returns the return data of each call in order, `None` for calls which reverted
+    pub async fn call(
+        &self,
+        calls: &[MulticallCall],
+        block: Option<BlockId>,
+    ) -> Result<Vec<Option<Bytes>>, MulticallError> {
+        let mut results = Vec::with_capacity(calls.len());
+        for (batch_index, batch) in calls.chunks(MAX_CALLS_PER_BATCH).enumerate() {
+            let tx: TypedTransaction =
+                TransactionRequest::new().to(self.address).data(encode_aggregate3(batch)).into();
+            let response = self.provider.call(&tx, block).await?;
+
+            for (index, result) in decode_aggregate3(&response)?.into_iter().enumerate() {
+                if result.is_none() && !batch[index].allow_failure {
+                    return Err(MulticallError::CallReverted(
+                        batch_index * MAX_CALLS_PER_BATCH + index,
+                    ));
+                }
+                results.push(result);
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+fn encode_aggregate3(calls: &[MulticallCall]) -> Bytes {
+    let calls = calls
+        .iter()
+        .map(|call| {
+            Token::Tuple(vec![
+                Token::Address(call.target),
+                Token::Bool(call.allow_failure),
+                Token::Bytes(call.call_data.to_vec()),
+            ])
+        })
+        .collect();
+
+    let mut data = id("aggregate3((address,bool,bytes)[])").to_vec();
+    data.extend(encode(&[Token::Array(calls)]));
+    data.into()
+}
+
+fn decode_aggregate3(response: &[u8]) -> Result<Vec<Option<Bytes>>, MulticallError> {
+    let result_type =
+        ParamType::Array(Box::new(ParamType::Tuple(vec![ParamType::Bool, ParamType::Bytes])));
+    let tokens =
+        decode(&[result_type], response).map_err(|e| MulticallError::DecodeError(e.to_string()))?;
+
+    let invalid = || MulticallError::DecodeError("unexpected result layout".to_string());
+    let Some(Token::Array(results)) = tokens.into_iter().next() else {
+        return Err(invalid());
+    };
+
+    results
+        .into_iter()
+        .map(|result| match result {
+            Token::Tuple(values) => match values.as_slice() {
+                [Token::Bool(success), Token::Bytes(data)] => {
+                    Ok(success.then(|| Bytes::from(data.clone())))
+                }
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate3_encoding() {
+        let target: Address = "0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419".parse().unwrap();
+        let encoded = encode_aggregate3(&[MulticallCall::from_signature(target, "decimals()")]);
+        assert_eq!(encoded[0..4], [0x82, 0xad, 0x56, 0xcb]);
+
+        let response = encode(&[Token::Array(vec![
+            Token::Tuple(vec![Token::Bool(true), Token::Bytes(vec![1, 2])]),
+            Token::Tuple(vec![Token::Bool(false), Token::Bytes(vec![])]),
+        ])]);
+        let decoded = decode_aggregate3(&response).unwrap();
+        assert_eq!(decoded, vec![Some(Bytes::from(vec![1, 2])), None]);
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::double_ended_iterator_last)]
+
 use std::{error::Error, fs::File, io::Read, path::Path};
 
 use ethers::abi::Abi;
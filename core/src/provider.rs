@@ -1,34 +1,957 @@
 use std::{
-    sync::Arc,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
 use ethers::{
     middleware::Middleware,
     prelude::Log,
-    providers::{Http, Provider, ProviderError, RetryClient, RetryClientBuilder},
-    types::{Block, BlockNumber, H256, U256, U64},
+    providers::{
+        FilterKind, Http, HttpClientError, HttpRateLimitRetryPolicy, Provider, ProviderError,
+        RetryClient, RetryClientBuilder, RetryPolicy, RpcError,
+    },
+    types::{
+        transaction::eip2718::TypedTransaction, Address, Block, BlockId, BlockNumber, Bytes,
+        FeeHistory, Filter, Transaction, TransactionReceipt, ValueOrArray, H256, U256, U64,
+    },
 };
+use futures::{stream, StreamExt, TryStreamExt};
 use reqwest::header::HeaderMap;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::value::RawValue;
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::sync::{oneshot, Mutex, OnceCell};
+use tracing::{debug, info, warn};
 use url::Url;
 
-use crate::{event::RindexerEventFilter, manifest::core::Manifest};
+use crate::{
+    event::RindexerEventFilter,
+    indexer::reorg::reorg_safe_distance_for_chain,
+    manifest::{
+        core::Manifest,
+        network::{
+            BlockCacheSettings, CircuitBreakerSettings, ComputeUnitCosts, LogFetchStrategy,
+            Network, NetworkProxy, NetworkRetryPolicy, NetworkRpcEndpoint, RpcDebugSettings,
+        },
+    },
+};
+
+/// Assume the minimum compute units per second if not provided as growth plan standard
+const DEFAULT_COMPUTE_UNITS_PER_SECOND: u64 = 660;
+
+const DEFAULT_RATE_LIMIT_RETRIES: u32 = 5000;
+
+const DEFAULT_TIMEOUT_RETRIES: u32 = 1000;
+
+const DEFAULT_INITIAL_BACKOFF_MS: u64 = 500;
+
+/// Transactions kept per network for handlers reading tx metadata
+const MAX_CACHED_TRANSACTIONS: usize = 10_000;
+
+const MAX_LATEST_BLOCK_CACHE_TTL: Duration = Duration::from_millis(300);
+
+/// Block range used by the block receipts strategy when no `max_block_range` is set
+const BLOCK_RECEIPTS_MAX_BLOCK_RANGE: u64 = 100;
+
+const BLOCK_RECEIPTS_CONCURRENCY: usize = 10;
+
+const BLOCK_HEADERS_CONCURRENCY: usize = 10;
+
+/// How long the first getLogs over a block range waits for others to join it
+const MERGE_GET_LOGS_WINDOW: Duration = Duration::from_millis(25);
+
+/// Longer request or response bodies are cut off in rpc debug logs
+const RPC_DEBUG_MAX_BODY_LENGTH: usize = 16 * 1024;
+
+const RPC_DEBUG_WINDOW: Duration = Duration::from_secs(60);
+
+/// Params of methods which take none, serializes to an empty JSON array
+const NO_PARAMS: [(); 0] = [];
+
+static CIRCUIT_BREAKER_TRIPS: AtomicU64 = AtomicU64::new(0);
+static OPEN_CIRCUITS: AtomicU64 = AtomicU64::new(0);
+
+/// The well known provider rate limit errors plus any extra JSON-RPC codes from the manifest
+#[derive(Debug, Default)]
+struct RateLimitRetryPolicy {
+    inner: HttpRateLimitRetryPolicy,
+    error_codes: Vec<i64>,
+}
+
+impl RetryPolicy<HttpClientError> for RateLimitRetryPolicy {
+    fn should_retry(&self, error: &HttpClientError) -> bool {
+        if self.inner.should_retry(error) {
+            return true;
+        }
+
+        match error {
+            HttpClientError::JsonRpcError(err) => self.error_codes.contains(&err.code),
+            _ => false,
+        }
+    }
+
+    fn backoff_hint(&self, error: &HttpClientError) -> Option<Duration> {
+        self.inner.backoff_hint(error)
+    }
+}
+
+/// Token bucket refilled at the endpoint compute units per second, each request waits until its
+/// method cost is available
+#[derive(Debug)]
+struct ComputeUnitBudget {
+    costs: ComputeUnitCosts,
+    per_second: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl ComputeUnitBudget {
+    fn new(costs: ComputeUnitCosts, compute_units_per_second: u64) -> Self {
+        let per_second = compute_units_per_second.max(1) as f64;
+        Self { costs, per_second, state: Mutex::new((per_second, Instant::now())) }
+    }
+
+    async fn spend(&self, method: &str) {
+        let cost = (self.costs.cost(method) as f64).min(self.per_second);
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                let (available, last_refill) = &mut *state;
+                let now = Instant::now();
+                *available = (*available +
+                    now.duration_since(*last_refill).as_secs_f64() * self.per_second)
+                    .min(self.per_second);
+                *last_refill = now;
+
+                if *available >= cost {
+                    *available -= cost;
+                    return;
+                }
+                Duration::from_secs_f64((cost - *available) / self.per_second)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    /// The open duration passed, the next request is a probe deciding whether it closes again
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct CircuitBreakerState {
+    // true for a failed request, newest last
+    outcomes: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    probing: bool,
+}
+
+/// Trips when the share of failed requests in the rolling window reaches the threshold, an open
+/// endpoint gets no requests until a single probe after the open duration succeeds
+#[derive(Debug)]
+struct CircuitBreaker {
+    endpoint: String,
+    error_rate_threshold: f64,
+    window: usize,
+    open_duration: Duration,
+    state: std::sync::Mutex<CircuitBreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(url: &str, settings: &CircuitBreakerSettings) -> Self {
+        Self {
+            endpoint: endpoint_host(url),
+            error_rate_threshold: settings.error_rate_threshold(),
+            window: settings.window(),
+            open_duration: settings.open_duration(),
+            state: std::sync::Mutex::new(CircuitBreakerState::default()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CircuitBreakerState> {
+        self.state.lock().expect("circuit breaker lock poisoned")
+    }
+
+    fn state(&self) -> CircuitState {
+        match self.lock().opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.open_duration => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    fn opened_at(&self) -> Option<Instant> {
+        self.lock().opened_at
+    }
+
+    /// Whether the endpoint takes the next request, a half open breaker lets one probe through
+    fn try_acquire(&self) -> Option<CircuitPermit<'_>> {
+        let mut state = self.lock();
+        match state.opened_at {
+            None => Some(CircuitPermit { circuit: self, probe: false }),
+            Some(opened_at) if opened_at.elapsed() < self.open_duration => None,
+            Some(_) if state.probing => None,
+            Some(_) => {
+                state.probing = true;
+                info!("RPC endpoint {} circuit half open - sending a probe request", self.endpoint);
+                Some(CircuitPermit { circuit: self, probe: true })
+            }
+        }
+    }
+
+    fn record(&self, failed: bool) {
+        let mut state = self.lock();
+        if state.opened_at.is_some() {
+            // requests sent before it tripped, or while every endpoint was open, do not count
+            if !state.probing {
+                return;
+            }
+            state.probing = false;
+            if failed {
+                state.opened_at = Some(Instant::now());
+                warn!(
+                    "RPC endpoint {} probe request failed - circuit open for another {:?}",
+                    self.endpoint, self.open_duration
+                );
+            } else {
+                state.opened_at = None;
+                state.outcomes.clear();
+                OPEN_CIRCUITS.fetch_sub(1, Ordering::Relaxed);
+                info!("RPC endpoint {} recovered - circuit closed", self.endpoint);
+            }
+            return;
+        }
+
+        state.outcomes.push_back(failed);
+        if state.outcomes.len() > self.window {
+            state.outcomes.pop_front();
+        }
+        if state.outcomes.len() < self.window {
+            return;
+        }
+
+        let failures = state.outcomes.iter().filter(|failed| **failed).count();
+        if failures as f64 / self.window as f64 >= self.error_rate_threshold {
+            state.opened_at = Some(Instant::now());
+            CIRCUIT_BREAKER_TRIPS.fetch_add(1, Ordering::Relaxed);
+            OPEN_CIRCUITS.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "RPC endpoint {} circuit open - {} of the last {} requests failed, routing to other endpoints for {:?}",
+                self.endpoint, failures, self.window, self.open_duration
+            );
+        }
+    }
+}
+
+/// A request let through by the circuit breaker, a probe dropped without an outcome (the request
+/// future was cancelled) releases the half open breaker so the next request probes instead
+struct CircuitPermit<'a> {
+    circuit: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl CircuitPermit<'_> {
+    fn record(mut self, failed: bool) {
+        self.probe = false;
+        self.circuit.record(failed);
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.circuit.lock().probing = false;
+        }
+    }
+}
+
+/// Only the host of an endpoint gets logged, urls often carry an api key
+fn endpoint_host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_else(|| "rpc".to_string())
+}
+
+/// Replaces the endpoint url in logged text with its host, transport errors include the full url
+fn scrub_endpoint_url(text: &str, url: &str) -> String {
+    if url.is_empty() {
+        return text.to_string();
+    }
+
+    let host = endpoint_host(url);
+    let mut scrubbed = text.replace(url, &host);
+    if let Ok(parsed) = Url::parse(url) {
+        scrubbed = scrubbed.replace(parsed.as_str(), &host);
+    }
+    scrubbed
+}
+
+fn truncate_rpc_debug_body(body: String) -> String {
+    if body.len() <= RPC_DEBUG_MAX_BODY_LENGTH {
+        return body;
+    }
+
+    let mut end = RPC_DEBUG_MAX_BODY_LENGTH;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} more bytes)", &body[..end], body.len() - end)
+}
+
+#[derive(Debug)]
+struct RpcDebugWindow {
+    started_at: Instant,
+    logged: u32,
+    dropped: u64,
+}
+
+/// Logs requests and responses of the configured methods, at most `max_logs_per_minute` so a
+/// busy backfill does not flood the logs
+#[derive(Debug)]
+struct RpcDebugLog {
+    settings: RpcDebugSettings,
+    window: std::sync::Mutex<RpcDebugWindow>,
+}
+
+impl RpcDebugLog {
+    fn new(settings: RpcDebugSettings) -> Self {
+        Self {
+            settings,
+            window: std::sync::Mutex::new(RpcDebugWindow {
+                started_at: Instant::now(),
+                logged: 0,
+                dropped: 0,
+            }),
+        }
+    }
+
+    fn try_acquire(&self, method: &str) -> bool {
+        if !self.settings.logs_method(method) {
+            return false;
+        }
+
+        let mut window = self.window.lock().expect("rpc debug lock poisoned");
+        if window.started_at.elapsed() >= RPC_DEBUG_WINDOW {
+            if window.dropped > 0 {
+                info!(
+                    "RPC debug - {} calls over the limit of {} a minute were not logged",
+                    window.dropped,
+                    self.settings.max_logs_per_minute()
+                );
+            }
+            *window = RpcDebugWindow { started_at: Instant::now(), logged: 0, dropped: 0 };
+        }
+
+        if window.logged < self.settings.max_logs_per_minute() {
+            window.logged += 1;
+            true
+        } else {
+            window.dropped += 1;
+            false
+        }
+    }
+
+    /// Logs the params as they are sent and the result as the endpoint returned it
+    fn log<P: Serialize + ?Sized>(
+        &self,
+        endpoint: &RpcEndpoint,
+        method: &str,
+        params: &P,
+        result: &Result<Box<RawValue>, ProviderError>,
+        elapsed: Duration,
+    ) {
+        let params = serde_json::to_string(params).unwrap_or_default();
+        let request = format!(r#"{{"jsonrpc":"2.0","method":"{}","params":{}}}"#, method, params);
+        let response = match result {
+            Ok(value) => format!(r#"{{"jsonrpc":"2.0","result":{}}}"#, value.get()),
+            Err(e) => format!("error: {}", e),
+        };
+
+        info!(
+            "RPC debug {} {} in {:?}\n  request: {}\n  response: {}",
+            endpoint_host(&endpoint.url),
+            method,
+            elapsed,
+            truncate_rpc_debug_body(scrub_endpoint_url(&request, &endpoint.url)),
+            truncate_rpc_debug_body(scrub_endpoint_url(&response, &endpoint.url)),
+        );
+    }
+}
+
+/// Only transport failures count against an endpoint, a JSON-RPC error response is about the
+/// request and any other endpoint would answer the same
+fn is_endpoint_failure(error: &ProviderError) -> bool {
+    error.as_error_response().is_none()
+}
+
+pub fn log_circuit_breaker_metrics() {
+    let trips = CIRCUIT_BREAKER_TRIPS.load(Ordering::Relaxed);
+    if trips == 0 {
+        return;
+    }
+
+    info!(
+        "RPC circuit breakers - tripped {} times, {} currently open",
+        trips,
+        OPEN_CIRCUITS.load(Ordering::Relaxed)
+    );
+}
+
+type CachedCell<V> = Arc<OnceCell<V>>;
+
+/// Least recently used entries are evicted first once the cache is full
+#[derive(Debug)]
+struct CachedCells<K, V> {
+    cells: HashMap<K, (CachedCell<V>, u64)>,
+    // last use of every cached key, oldest first
+    recency: BTreeMap<u64, K>,
+    uses: u64,
+}
+
+impl<K, V> Default for CachedCells<K, V> {
+    fn default() -> Self {
+        Self { cells: HashMap::new(), recency: BTreeMap::new(), uses: 0 }
+    }
+}
+
+impl<K: Copy + Eq + std::hash::Hash, V> CachedCells<K, V> {
+    fn get_or_insert(&mut self, key: K, max_entries: usize) -> CachedCell<V> {
+        self.uses += 1;
+        if let Some((cell, last_used)) = self.cells.get_mut(&key) {
+            self.recency.remove(last_used);
+            *last_used = self.uses;
+            self.recency.insert(self.uses, key);
+            return Arc::clone(cell);
+        }
+
+        while self.cells.len() >= max_entries.max(1) {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.cells.remove(&oldest);
+        }
+        let cell = CachedCell::default();
+        self.cells.insert(key, (Arc::clone(&cell), self.uses));
+        self.recency.insert(self.uses, key);
+        cell
+    }
+}
+
+/// Headers by block number shared by everything using the network's provider, concurrent lookups
+/// of the same block wait on a single getBlock. Only blocks past the reorg safe distance are
+/// cached as a reorg can still replace anything newer
+#[derive(Debug)]
+struct BlockCache {
+    max_blocks: usize,
+    disk_path: Option<PathBuf>,
+    blocks: std::sync::Mutex<CachedCells<U64, Arc<Block<H256>>>>,
+}
+
+impl BlockCache {
+    fn new(settings: Option<&BlockCacheSettings>) -> Self {
+        Self {
+            max_blocks: settings.map_or(BlockCacheSettings::default().max_blocks(), |settings| {
+                settings.max_blocks()
+            }),
+            disk_path: settings.and_then(|settings| settings.disk_path.as_ref().map(PathBuf::from)),
+            blocks: std::sync::Mutex::new(CachedCells::default()),
+        }
+    }
+
+    /// Grouped in directories of 10000 so no single directory gets huge
+    fn disk_file(&self, block_number: U64) -> Option<PathBuf> {
+        let block_number = block_number.as_u64();
+        self.disk_path.as_ref().map(|path| {
+            path.join((block_number / 10_000).to_string()).join(format!("{}.json", block_number))
+        })
+    }
+
+    async fn read_disk(&self, block_number: U64) -> Option<Block<H256>> {
+        let contents = tokio::fs::read(self.disk_file(block_number)?).await.ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+
+    async fn write_disk(&self, block: &Block<H256>) {
+        let (Some(block_number), Some(file)) =
+            (block.number, block.number.and_then(|number| self.disk_file(number)))
+        else {
+            return;
+        };
+
+        let result = async {
+            if let Some(directory) = file.parent() {
+                tokio::fs::create_dir_all(directory).await?;
+            }
+            let contents = serde_json::to_vec(block).map_err(std::io::Error::other)?;
+            tokio::fs::write(&file, contents).await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("Could not write block {} to the block cache: {}", block_number, e);
+        }
+    }
+
+    async fn get_or_fetch<F, Fut>(
+        &self,
+        block_number: U64,
+        fetch: F,
+    ) -> Result<Arc<Block<H256>>, ProviderError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<Block<H256>, ProviderError>>,
+    {
+        let cell = self
+            .blocks
+            .lock()
+            .expect("block cache lock poisoned")
+            .get_or_insert(block_number, self.max_blocks);
+
+        cell.get_or_try_init(|| async {
+            if let Some(block) = self.read_disk(block_number).await {
+                return Ok(Arc::new(block));
+            }
+
+            let block = fetch().await?;
+            self.write_disk(&block).await;
+            Ok(Arc::new(block))
+        })
+        .await
+        .cloned()
+    }
+}
+
+#[derive(Debug)]
+struct RpcEndpoint {
+    url: String,
+    provider: Arc<Provider<RetryClient<Http>>>,
+    requests: AtomicU64,
+    budget: Option<ComputeUnitBudget>,
+    circuit: Option<CircuitBreaker>,
+    // last head the endpoint answered with when heads are agreed on, 0 until it answers
+    head: AtomicU64,
+}
+
+impl RpcEndpoint {
+    async fn provider_for(&self, method: &str) -> &Arc<Provider<RetryClient<Http>>> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if let Some(budget) = &self.budget {
+            budget.spend(method).await;
+        }
+        &self.provider
+    }
+}
 
 #[derive(Debug)]
 pub struct JsonRpcCachedProvider {
     provider: Arc<Provider<RetryClient<Http>>>,
     cache: Mutex<Option<(Instant, Arc<Block<H256>>)>>,
     pub max_block_range: Option<U64>,
+    endpoints: Vec<RpcEndpoint>,
+    schedule: Vec<usize>,
+    next_endpoint: AtomicUsize,
+    merged_get_logs: Option<MergedGetLogs>,
+    // 0 means no cap, lowered automatically when a provider rejects the address list size
+    max_addresses_per_get_logs: AtomicUsize,
+    latest_block_cache_ttl: Duration,
+    log_fetch_strategy: LogFetchStrategy,
+    rpc_debug: Option<RpcDebugLog>,
+    block_cache: BlockCache,
+    transactions: std::sync::Mutex<CachedCells<H256, Arc<Transaction>>>,
+    reorg_safe_distance: OnceCell<U64>,
+    head_quorum: Option<usize>,
+    agreed_head: std::sync::Mutex<Option<Arc<Block<H256>>>>,
+}
+
+/// Block range and whether the filter has addresses, only filters sharing these are merged
+type MergeKey = (U64, U64, bool);
+
+#[derive(Debug)]
+struct PendingGetLogs {
+    filter: Filter,
+    // `None` tells the caller to fetch on its own
+    reply: oneshot::Sender<Option<Vec<Log>>>,
+}
+
+#[derive(Debug, Default)]
+struct MergedGetLogs {
+    pending: std::sync::Mutex<HashMap<MergeKey, Vec<PendingGetLogs>>>,
+}
+
+/// Only a single topic0 with no indexed topic filters can be merged and split back out again
+fn merge_key(filter: &Filter) -> Option<MergeKey> {
+    let has_single_topic0 = matches!(filter.topics[0], Some(ValueOrArray::Value(Some(_))));
+    if !has_single_topic0 || filter.topics[1..].iter().any(|topic| topic.is_some()) {
+        return None;
+    }
+
+    Some((filter.get_from_block()?, filter.get_to_block()?, filter.address.is_some()))
+}
+
+fn merge_filters(filters: &[&Filter]) -> Filter {
+    let mut addresses: Vec<Address> = vec![];
+    let mut topic0s: Vec<H256> = vec![];
+    for filter in filters {
+        match &filter.address {
+            Some(ValueOrArray::Value(address)) => addresses.push(*address),
+            Some(ValueOrArray::Array(filter_addresses)) => addresses.extend(filter_addresses),
+            None => {}
+        }
+        if let Some(ValueOrArray::Value(Some(topic0))) = &filter.topics[0] {
+            topic0s.push(*topic0);
+        }
+    }
+
+    let mut seen_addresses = HashSet::new();
+    addresses.retain(|address| seen_addresses.insert(*address));
+    let mut seen_topic0s = HashSet::new();
+    topic0s.retain(|topic0| seen_topic0s.insert(*topic0));
+
+    let mut merged = filters[0].clone().topic0(topic0s);
+    if !addresses.is_empty() {
+        merged = merged.address(addresses);
+    }
+    merged
+}
+
+/// Providers word this differently but all mention the addresses and some kind of limit
+fn is_too_many_addresses_error(error: &ProviderError) -> bool {
+    let message = error.to_string().to_lowercase();
+    message.contains("address") &&
+        (message.contains("too many") ||
+            message.contains("exceed") ||
+            message.contains("limit") ||
+            message.contains("too long"))
+}
+
+fn chunk_filter_addresses(filter: &Filter, max_addresses: usize) -> Option<Vec<Filter>> {
+    let Some(ValueOrArray::Array(addresses)) = &filter.address else {
+        return None;
+    };
+    if max_addresses == 0 || addresses.len() <= max_addresses {
+        return None;
+    }
+
+    Some(
+        addresses
+            .chunks(max_addresses)
+            .map(|chunk| filter.clone().address(chunk.to_vec()))
+            .collect(),
+    )
+}
+
+fn log_matches_filter(filter: &Filter, log: &Log) -> bool {
+    let address_matches = match &filter.address {
+        Some(ValueOrArray::Value(address)) => log.address == *address,
+        Some(ValueOrArray::Array(addresses)) => addresses.contains(&log.address),
+        None => true,
+    };
+
+    let topics_match = filter.topics.iter().enumerate().all(|(position, topic)| match topic {
+        Some(ValueOrArray::Value(Some(topic))) => log.topics.get(position) == Some(topic),
+        Some(ValueOrArray::Array(topics)) => {
+            topics.iter().any(|topic| topic.is_none() || log.topics.get(position) == topic.as_ref())
+        }
+        _ => true,
+    });
+
+    address_matches && topics_match
+}
+
+/// Highest block at least `quorum` of the heads have reached, endpoints which failed to answer
+/// do not count towards the quorum so one dead endpoint can not hold the head back
+fn agreed_block(mut heads: Vec<Block<H256>>, quorum: usize) -> Option<Block<H256>> {
+    heads.retain(|block| block.number.is_some());
+    heads.sort_by_key(|block| std::cmp::Reverse(block.number));
+    let quorum = quorum.min(heads.len()).max(1);
+    heads.into_iter().nth(quorum - 1)
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Smooth weighted round-robin order of endpoint indexes, weights are scaled against the highest
+/// so the schedule stays short
+fn weighted_round_robin_schedule(weights: &[u64]) -> Vec<usize> {
+    let max_weight = weights.iter().copied().max().unwrap_or(1).max(1);
+    let weights: Vec<i64> = weights
+        .iter()
+        .map(|weight| ((*weight as f64 / max_weight as f64) * 10.0).round().max(1.0) as i64)
+        .collect();
+    let divisor = weights.iter().copied().fold(0, gcd).max(1);
+    let weights: Vec<i64> = weights.iter().map(|weight| weight / divisor).collect();
+    let total: i64 = weights.iter().sum();
+
+    let mut current = vec![0i64; weights.len()];
+    let mut schedule = Vec::with_capacity(total as usize);
+    for _ in 0..total {
+        for (current, weight) in current.iter_mut().zip(&weights) {
+            *current += weight;
+        }
+        let (index, _) = current
+            .iter()
+            .enumerate()
+            .max_by_key(|(index, value)| (**value, -(*index as i64)))
+            .expect("schedule needs at least one endpoint");
+        current[index] -= total;
+        schedule.push(index);
+    }
+    schedule
 }
 
 impl JsonRpcCachedProvider {
     pub fn new(provider: Provider<RetryClient<Http>>, max_block_range: Option<U64>) -> Self {
+        Self::new_load_balanced(
+            vec![("".to_string(), provider, 1)],
+            &RpcClientOptions { max_block_range, ..Default::default() },
+        )
+    }
+
+    fn new_load_balanced(
+        providers: Vec<(String, Provider<RetryClient<Http>>, u64)>,
+        options: &RpcClientOptions,
+    ) -> Self {
+        let log_fetch_strategy = options.log_fetch_strategy;
+        let head_quorum = options.head_quorum.filter(|_| providers.len() > 1);
+        let schedule = weighted_round_robin_schedule(
+            &providers.iter().map(|(_, _, weight)| *weight).collect::<Vec<_>>(),
+        );
+        let endpoints: Vec<RpcEndpoint> = providers
+            .into_iter()
+            .map(|(url, provider, compute_units_per_second)| RpcEndpoint {
+                circuit: options
+                    .circuit_breaker
+                    .as_ref()
+                    .map(|settings| CircuitBreaker::new(&url, settings)),
+                url,
+                provider: Arc::new(provider),
+                requests: AtomicU64::new(0),
+                budget: options
+                    .compute_unit_costs
+                    .as_ref()
+                    .map(|costs| ComputeUnitBudget::new(costs.clone(), compute_units_per_second)),
+                head: AtomicU64::new(0),
+            })
+            .collect();
+
         JsonRpcCachedProvider {
-            provider: Arc::new(provider),
+            provider: Arc::clone(&endpoints[0].provider),
             cache: Mutex::new(None),
-            max_block_range,
+            max_block_range: options.max_block_range.or_else(|| {
+                (log_fetch_strategy == LogFetchStrategy::BlockReceipts)
+                    .then(|| U64::from(BLOCK_RECEIPTS_MAX_BLOCK_RANGE))
+            }),
+            endpoints,
+            schedule,
+            next_endpoint: AtomicUsize::new(0),
+            merged_get_logs: options.merge_get_logs.then(MergedGetLogs::default),
+            max_addresses_per_get_logs: AtomicUsize::new(
+                options.max_addresses_per_get_logs.unwrap_or(0),
+            ),
+            // never cache the head longer than the network is polled
+            latest_block_cache_ttl: options
+                .block_poll_interval_ms
+                .map(Duration::from_millis)
+                .map_or(MAX_LATEST_BLOCK_CACHE_TTL, |interval| {
+                    interval.min(MAX_LATEST_BLOCK_CACHE_TTL)
+                }),
+            log_fetch_strategy,
+            rpc_debug: options.rpc_debug.clone().map(RpcDebugLog::new),
+            block_cache: BlockCache::new(options.block_cache.as_ref()),
+            transactions: std::sync::Mutex::new(CachedCells::default()),
+            reorg_safe_distance: OnceCell::new(),
+            head_quorum,
+            agreed_head: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Next endpoint in the schedule skipping open circuits, when every circuit is open the one
+    /// which tripped first still gets the request rather than stalling indexing. With agreed heads
+    /// a request for `min_block` skips endpoints which have not reached it yet, falling back to
+    /// the furthest endpoint when none has
+    fn next_endpoint(&self, min_block: Option<U64>) -> (&RpcEndpoint, Option<CircuitPermit<'_>>) {
+        let position = if self.endpoints.len() == 1 {
+            0
+        } else {
+            self.next_endpoint.fetch_add(1, Ordering::Relaxed)
+        };
+        let min_block =
+            min_block.filter(|_| self.head_quorum.is_some()).map(|block| block.as_u64());
+        let has_reached = |endpoint: &RpcEndpoint| {
+            min_block.is_none_or(|block| endpoint.head.load(Ordering::Relaxed) >= block)
+        };
+
+        for offset in 0..self.schedule.len() {
+            let endpoint =
+                &self.endpoints[self.schedule[(position + offset) % self.schedule.len()]];
+            if !has_reached(endpoint) {
+                continue;
+            }
+            match &endpoint.circuit {
+                None => return (endpoint, None),
+                Some(circuit) => {
+                    if let Some(permit) = circuit.try_acquire() {
+                        return (endpoint, Some(permit));
+                    }
+                }
+            }
+        }
+
+        if min_block.is_some() {
+            if let Some(endpoint) = self
+                .endpoints
+                .iter()
+                .filter(|endpoint| endpoint.head.load(Ordering::Relaxed) > 0)
+                .max_by_key(|endpoint| endpoint.head.load(Ordering::Relaxed))
+            {
+                return (endpoint, None);
+            }
+        }
+
+        let endpoint = self
+            .endpoints
+            .iter()
+            .min_by_key(|endpoint| {
+                endpoint.circuit.as_ref().and_then(|circuit| circuit.opened_at())
+            })
+            .expect("provider needs at least one endpoint");
+        (endpoint, None)
+    }
+
+    /// Sends the request to the next endpoint and records the outcome on its circuit breaker,
+    /// calls logged in rpc debug mode are sent as `method` with `params` to log the raw response
+    async fn request<T, P, F, Fut>(
+        &self,
+        method: &str,
+        params: &P,
+        request: F,
+    ) -> Result<T, ProviderError>
+    where
+        T: DeserializeOwned,
+        P: Serialize + std::fmt::Debug + Sync + ?Sized,
+        F: FnOnce(Arc<Provider<RetryClient<Http>>>) -> Fut,
+        Fut: std::future::Future<Output = Result<T, ProviderError>>,
+    {
+        self.request_at(method, params, None, request).await
+    }
+
+    /// Same as `request` but only sent to an endpoint which has reached `min_block`
+    async fn request_at<T, P, F, Fut>(
+        &self,
+        method: &str,
+        params: &P,
+        min_block: Option<U64>,
+        request: F,
+    ) -> Result<T, ProviderError>
+    where
+        T: DeserializeOwned,
+        P: Serialize + std::fmt::Debug + Sync + ?Sized,
+        F: FnOnce(Arc<Provider<RetryClient<Http>>>) -> Fut,
+        Fut: std::future::Future<Output = Result<T, ProviderError>>,
+    {
+        let (endpoint, permit) = self.next_endpoint(min_block);
+        let provider = Arc::clone(endpoint.provider_for(method).await);
+        let result = match self.rpc_debug.as_ref().filter(|rpc_debug| rpc_debug.try_acquire(method))
+        {
+            Some(rpc_debug) => {
+                let started_at = Instant::now();
+                let raw = provider.request::<_, Box<RawValue>>(method, params).await;
+                rpc_debug.log(endpoint, method, params, &raw, started_at.elapsed());
+                raw.and_then(|raw| Ok(serde_json::from_str(raw.get())?))
+            }
+            None => request(provider).await,
+        };
+        let failed = result.as_ref().is_err_and(is_endpoint_failure);
+        match (permit, &endpoint.circuit) {
+            (Some(permit), _) => permit.record(failed),
+            (None, Some(circuit)) => circuit.record(failed),
+            (None, None) => {}
+        }
+        result
+    }
+
+    /// Circuit state of each endpoint with a circuit breaker configured
+    pub fn endpoint_circuit_states(&self) -> Vec<(String, CircuitState)> {
+        self.endpoints
+            .iter()
+            .filter_map(|endpoint| {
+                endpoint.circuit.as_ref().map(|circuit| (endpoint.url.clone(), circuit.state()))
+            })
+            .collect()
+    }
+
+    /// Number of requests sent to each endpoint since startup
+    pub fn endpoint_request_counts(&self) -> Vec<(String, u64)> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| (endpoint.url.clone(), endpoint.requests.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Asks every endpoint for its head and keeps the highest block `quorum` of them reached, the
+    /// agreed head never moves back even when the endpoints which agreed fall behind
+    async fn agreed_latest_block(
+        &self,
+        quorum: usize,
+    ) -> Result<Option<Arc<Block<H256>>>, ProviderError> {
+        let results = futures::future::join_all(self.endpoints.iter().map(|endpoint| async move {
+            let provider = Arc::clone(endpoint.provider_for("eth_getBlockByNumber").await);
+            let result = provider.get_block(BlockNumber::Latest).await;
+            if let Some(circuit) = &endpoint.circuit {
+                circuit.record(result.as_ref().is_err_and(is_endpoint_failure));
+            }
+            if let Ok(Some(number)) = result.as_ref().map(|block| block.as_ref()?.number) {
+                endpoint.head.store(number.as_u64(), Ordering::Relaxed);
+            }
+            result
+        }))
+        .await;
+
+        let mut heads = vec![];
+        let mut last_error = None;
+        for result in results {
+            match result {
+                Ok(Some(block)) => heads.push(block),
+                Ok(None) => {}
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        // the previous head can not stand in for the current one when no endpoint answered
+        if heads.is_empty() {
+            if let Some(e) = last_error {
+                warn!("Every endpoint failed to return its latest block: {}", e);
+                return Err(e);
+            }
+        }
+
+        let mut agreed_head = self.agreed_head.lock().expect("agreed head lock poisoned");
+        let advanced = agreed_block(heads, quorum).filter(|block| {
+            agreed_head.as_ref().is_none_or(|previous| block.number > previous.number)
+        });
+        if let Some(block) = advanced {
+            let block = Arc::new(block);
+            *agreed_head = Some(Arc::clone(&block));
+            return Ok(Some(block));
+        }
+
+        match (agreed_head.as_ref(), last_error) {
+            (Some(previous), _) => Ok(Some(Arc::clone(previous))),
+            (None, Some(e)) => Err(e),
+            (None, None) => Ok(None),
         }
     }
 
@@ -36,15 +959,24 @@ impl JsonRpcCachedProvider {
         let mut cache_guard = self.cache.lock().await;
 
         if let Some((timestamp, block)) = &*cache_guard {
-            if timestamp.elapsed() < Duration::from_millis(300) {
+            if timestamp.elapsed() < self.latest_block_cache_ttl {
                 return Ok(Some(Arc::clone(block)));
             }
         }
 
-        let latest_block = self.provider.get_block(BlockNumber::Latest).await?;
+        let latest_block = match self.head_quorum {
+            Some(quorum) => self.agreed_latest_block(quorum).await?,
+            None => self
+                .request(
+                    "eth_getBlockByNumber",
+                    &(BlockNumber::Latest, false),
+                    |provider| async move { provider.get_block(BlockNumber::Latest).await },
+                )
+                .await?
+                .map(Arc::new),
+        };
 
-        if let Some(block) = latest_block {
-            let arc_block = Arc::new(block);
+        if let Some(arc_block) = latest_block {
             *cache_guard = Some((Instant::now(), Arc::clone(&arc_block)));
             return Ok(Some(arc_block));
         } else {
@@ -55,17 +987,343 @@ impl JsonRpcCachedProvider {
     }
 
     pub async fn get_block_number(&self) -> Result<U64, ProviderError> {
-        self.provider.get_block_number().await
+        if self.head_quorum.is_some() {
+            return self.get_latest_block().await?.and_then(|block| block.number).ok_or_else(|| {
+                ProviderError::CustomError("No head block the endpoints agree on yet".to_string())
+            });
+        }
+
+        self.request("eth_blockNumber", &NO_PARAMS, |provider| async move {
+            provider.get_block_number().await
+        })
+        .await
     }
 
     pub async fn get_logs(&self, filter: &RindexerEventFilter) -> Result<Vec<Log>, ProviderError> {
-        self.provider.get_logs(filter.raw_filter()).await
+        if self.log_fetch_strategy == LogFetchStrategy::BlockReceipts {
+            return self.get_logs_from_block_receipts(filter.raw_filter()).await;
+        }
+
+        if let Some(merged_get_logs) = &self.merged_get_logs {
+            if let Some(key) = merge_key(filter.raw_filter()) {
+                if let Some(logs) =
+                    self.get_logs_merged(merged_get_logs, key, filter.raw_filter().clone()).await
+                {
+                    return Ok(logs);
+                }
+            }
+        }
+
+        self.get_logs_chunked(filter.raw_filter()).await
+    }
+
+    /// Large address sets are fetched as one filter split into as few requests as the provider
+    /// address list cap allows, halving the cap whenever the provider rejects the list size
+    async fn get_logs_chunked(&self, filter: &Filter) -> Result<Vec<Log>, ProviderError> {
+        loop {
+            let max_addresses = self.max_addresses_per_get_logs.load(Ordering::Relaxed);
+            let result = match chunk_filter_addresses(filter, max_addresses) {
+                None => {
+                    self.request_at(
+                        "eth_getLogs",
+                        &[filter],
+                        filter.get_to_block(),
+                        |provider| async move { provider.get_logs(filter).await },
+                    )
+                    .await
+                }
+                Some(chunks) => self.get_logs_for_chunks(&chunks).await,
+            };
+
+            let Err(error) = result else {
+                return result;
+            };
+
+            let address_count = match &filter.address {
+                Some(ValueOrArray::Array(addresses)) => addresses.len(),
+                _ => 1,
+            };
+            let current_cap = if max_addresses == 0 { address_count } else { max_addresses };
+            if current_cap <= 1 || !is_too_many_addresses_error(&error) {
+                return Err(error);
+            }
+
+            let lowered_cap = current_cap / 2;
+            debug!(
+                "Provider rejected {} addresses in getLogs - retrying with {}",
+                current_cap, lowered_cap
+            );
+            self.max_addresses_per_get_logs.store(lowered_cap, Ordering::Relaxed);
+        }
+    }
+
+    async fn get_logs_from_block_receipts(
+        &self,
+        filter: &Filter,
+    ) -> Result<Vec<Log>, ProviderError> {
+        let (Some(from_block), Some(to_block)) = (filter.get_from_block(), filter.get_to_block())
+        else {
+            return Err(ProviderError::CustomError(
+                "block receipts log fetching needs a numbered block range".to_string(),
+            ));
+        };
+
+        let receipts: Vec<Vec<TransactionReceipt>> =
+            stream::iter(from_block.as_u64()..=to_block.as_u64())
+                .map(|block| async move {
+                    let params = [BlockNumber::Number(block.into())];
+                    self.request("eth_getBlockReceipts", &params, move |provider| async move {
+                        provider.get_block_receipts(block).await
+                    })
+                    .await
+                })
+                .buffered(BLOCK_RECEIPTS_CONCURRENCY)
+                .try_collect()
+                .await?;
+
+        Ok(receipts
+            .into_iter()
+            .flatten()
+            .flat_map(|receipt| receipt.logs)
+            .filter(|log| log_matches_filter(filter, log))
+            .collect())
+    }
+
+    async fn get_logs_for_chunks(&self, chunks: &[Filter]) -> Result<Vec<Log>, ProviderError> {
+        let mut logs = vec![];
+        for chunk in chunks {
+            logs.extend(
+                self.request_at(
+                    "eth_getLogs",
+                    &[chunk],
+                    chunk.get_to_block(),
+                    |provider| async move { provider.get_logs(chunk).await },
+                )
+                .await?,
+            );
+        }
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+        Ok(logs)
+    }
+
+    /// The first caller for a block range waits a moment for others to join then fetches for all
+    /// of them, on any error everyone falls back to their own request so range retries still work
+    async fn get_logs_merged(
+        &self,
+        merged_get_logs: &MergedGetLogs,
+        key: MergeKey,
+        filter: Filter,
+    ) -> Option<Vec<Log>> {
+        let (reply, receiver) = oneshot::channel();
+        let is_leader = {
+            let mut pending = merged_get_logs.pending.lock().expect("merged getLogs lock poisoned");
+            let group = pending.entry(key).or_default();
+            group.push(PendingGetLogs { filter, reply });
+            group.len() == 1
+        };
+
+        if is_leader {
+            tokio::time::sleep(MERGE_GET_LOGS_WINDOW).await;
+            let group = merged_get_logs
+                .pending
+                .lock()
+                .expect("merged getLogs lock poisoned")
+                .remove(&key)
+                .unwrap_or_default();
+            self.fetch_merged_get_logs(group).await;
+        }
+
+        receiver.await.ok().flatten()
+    }
+
+    async fn fetch_merged_get_logs(&self, group: Vec<PendingGetLogs>) {
+        if group.len() < 2 {
+            for pending in group {
+                let _ = pending.reply.send(None);
+            }
+            return;
+        }
+
+        let merged =
+            merge_filters(&group.iter().map(|pending| &pending.filter).collect::<Vec<_>>());
+        match self.get_logs_chunked(&merged).await {
+            Ok(logs) => {
+                debug!(
+                    "Merged {} getLogs requests into one returning {} logs",
+                    group.len(),
+                    logs.len()
+                );
+                for pending in group {
+                    let matched = logs
+                        .iter()
+                        .filter(|log| log_matches_filter(&pending.filter, log))
+                        .cloned()
+                        .collect();
+                    let _ = pending.reply.send(Some(matched));
+                }
+            }
+            Err(e) => {
+                debug!("Merged getLogs request failed, falling back to single requests: {}", e);
+                for pending in group {
+                    let _ = pending.reply.send(None);
+                }
+            }
+        }
     }
 
     pub async fn get_chain_id(&self) -> Result<U256, ProviderError> {
-        self.provider.get_chainid().await
+        self.endpoints[0].provider_for("eth_chainId").await.get_chainid().await
+    }
+
+    pub async fn fee_history(
+        &self,
+        block_count: u64,
+        newest_block: U64,
+        reward_percentiles: &[f64],
+    ) -> Result<FeeHistory, ProviderError> {
+        let params =
+            (U256::from(block_count), BlockNumber::Number(newest_block), reward_percentiles);
+        self.request("eth_feeHistory", &params, |provider| async move {
+            provider
+                .fee_history(block_count, BlockNumber::Number(newest_block), reward_percentiles)
+                .await
+        })
+        .await
+    }
+
+    /// How many blocks behind the head a reorg can reach on this chain
+    pub async fn reorg_safe_distance(&self) -> Result<U64, ProviderError> {
+        self.reorg_safe_distance
+            .get_or_try_init(|| async {
+                Ok::<_, ProviderError>(reorg_safe_distance_for_chain(&self.get_chain_id().await?))
+            })
+            .await
+            .copied()
+    }
+
+    /// Highest block a reorg can no longer replace, None until the head is known
+    async fn reorg_safe_block(&self) -> Result<Option<U64>, ProviderError> {
+        let reorg_safe_distance = self.reorg_safe_distance().await?;
+        Ok(self
+            .get_latest_block()
+            .await?
+            .and_then(|block| block.number)
+            .and_then(|head| head.checked_sub(reorg_safe_distance)))
+    }
+
+    /// Header of the block without its transactions, blocks past the reorg safe distance are
+    /// looked up through the network block cache
+    pub async fn get_block_header(&self, block: U64) -> Result<Arc<Block<H256>>, ProviderError> {
+        let fetch = || async move {
+            let params = (BlockNumber::Number(block), false);
+            self.request("eth_getBlockByNumber", &params, move |provider| async move {
+                provider.get_block(block).await
+            })
+            .await?
+            .ok_or_else(|| ProviderError::CustomError(format!("block {} not found", block)))
+        };
+
+        if self.reorg_safe_block().await?.is_none_or(|safe_block| block > safe_block) {
+            return fetch().await.map(Arc::new);
+        }
+        self.block_cache.get_or_fetch(block, fetch).await
+    }
+
+    /// Headers of every block in the inclusive range in order, without their transactions
+    pub async fn get_block_headers(
+        &self,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<Vec<Block<H256>>, ProviderError> {
+        stream::iter(from_block.as_u64()..=to_block.as_u64())
+            .map(|block| async move {
+                self.get_block_header(block.into()).await.map(|block| (*block).clone())
+            })
+            .buffered(BLOCK_HEADERS_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Transactions mined past the reorg safe distance are cached for everything using the
+    /// network's provider, newer ones are always fetched as a reorg can still drop them
+    pub async fn get_transaction(
+        &self,
+        hash: H256,
+    ) -> Result<Option<Arc<Transaction>>, ProviderError> {
+        let cell = self
+            .transactions
+            .lock()
+            .expect("transaction cache lock poisoned")
+            .get_or_insert(hash, MAX_CACHED_TRANSACTIONS);
+        if let Some(transaction) = cell.get() {
+            return Ok(Some(Arc::clone(transaction)));
+        }
+
+        let Some(transaction) = self
+            .request("eth_getTransactionByHash", &[hash], |provider| async move {
+                provider.get_transaction(hash).await
+            })
+            .await?
+        else {
+            return Ok(None);
+        };
+        let transaction = Arc::new(transaction);
+
+        let reorg_safe_block = self.reorg_safe_block().await?;
+        if transaction.block_number.zip(reorg_safe_block).is_some_and(|(mined, safe)| mined <= safe)
+        {
+            let _ = cell.set(Arc::clone(&transaction));
+        }
+
+        Ok(Some(transaction))
+    }
+
+    pub async fn get_transaction_receipt(
+        &self,
+        hash: H256,
+    ) -> Result<Option<TransactionReceipt>, ProviderError> {
+        self.request("eth_getTransactionReceipt", &[hash], |provider| async move {
+            provider.get_transaction_receipt(hash).await
+        })
+        .await
+    }
+
+    /// getLogs for a plain filter, split by the address cap like every other getLogs
+    pub async fn get_filter_logs(&self, filter: &Filter) -> Result<Vec<Log>, ProviderError> {
+        self.get_logs_chunked(filter).await
+    }
+
+    pub async fn get_code(&self, address: Address, block: U64) -> Result<Bytes, ProviderError> {
+        self.request("eth_getCode", &(address, BlockNumber::Number(block)), |provider| async move {
+            provider.get_code(address, Some(BlockId::Number(BlockNumber::Number(block)))).await
+        })
+        .await
     }
 
+    /// eth_call against the first endpoint, spending from its compute unit budget
+    pub async fn call(
+        &self,
+        tx: &TypedTransaction,
+        block: Option<BlockId>,
+    ) -> Result<Bytes, ProviderError> {
+        self.endpoints[0].provider_for("eth_call").await.call(tx, block).await
+    }
+
+    /// Filters live on the node which created them so these always use the first endpoint
+    pub async fn new_block_filter(&self) -> Result<U256, ProviderError> {
+        self.endpoints[0]
+            .provider_for("eth_newBlockFilter")
+            .await
+            .new_filter(FilterKind::NewBlocks)
+            .await
+    }
+
+    pub async fn get_block_filter_changes(&self, id: U256) -> Result<Vec<H256>, ProviderError> {
+        self.endpoints[0].provider_for("eth_getFilterChanges").await.get_filter_changes(id).await
+    }
+
+    /// The first endpoint's provider for typed contract calls, these skip the load balancing,
+    /// circuit breakers and rpc debug logging of the methods above
     pub fn get_inner_provider(&self) -> Arc<Provider<RetryClient<Http>>> {
         Arc::clone(&self.provider)
     }
@@ -77,30 +1335,146 @@ pub enum RetryClientError {
 
     #[error("Could not build client: {0}")]
     CouldNotBuildClient(#[from] reqwest::Error),
+
+    #[error("No rpc endpoints configured")]
+    NoRpcEndpoints,
+
+    #[error("Invalid proxy {0}: {1}")]
+    InvalidProxy(String, String),
 }
 
-pub fn create_client(
+fn create_proxy(proxy: &NetworkProxy) -> Result<reqwest::Proxy, RetryClientError> {
+    // only the scheme and host get into errors, the url can carry credentials
+    let proxy_host = || {
+        Url::parse(&proxy.url)
+            .map(|url| format!("{}://{}", url.scheme(), url.host_str().unwrap_or_default()))
+            .unwrap_or_else(|_| "proxy".to_string())
+    };
+    let mut reqwest_proxy = reqwest::Proxy::all(&proxy.url)
+        .map_err(|e| RetryClientError::InvalidProxy(proxy_host(), e.to_string()))?;
+    if let Some(username) = &proxy.username {
+        reqwest_proxy =
+            reqwest_proxy.basic_auth(username, proxy.password.as_deref().unwrap_or_default());
+    }
+    Ok(reqwest_proxy)
+}
+
+fn create_retry_provider(
     rpc_url: &str,
-    compute_units_per_second: Option<u64>,
-    max_block_range: Option<U64>,
+    compute_units_per_second: u64,
+    retry_policy: &NetworkRetryPolicy,
+    proxy: Option<&NetworkProxy>,
     custom_headers: HeaderMap,
-) -> Result<Arc<JsonRpcCachedProvider>, RetryClientError> {
+) -> Result<Provider<RetryClient<Http>>, RetryClientError> {
     let url = Url::parse(rpc_url).map_err(|e| {
         RetryClientError::HttpProviderCantBeCreated(rpc_url.to_string(), e.to_string())
     })?;
-    let client = reqwest::Client::builder().default_headers(custom_headers).build()?;
+    let mut client_builder = reqwest::Client::builder().default_headers(custom_headers);
+    if let Some(request_timeout_ms) = retry_policy.request_timeout_ms {
+        client_builder = client_builder.timeout(Duration::from_millis(request_timeout_ms));
+    }
+    if let Some(proxy) = proxy {
+        client_builder = client_builder.proxy(create_proxy(proxy)?);
+    }
+    let client = client_builder.build()?;
 
     let provider = Http::new_with_client(url, client);
-    let instance = Provider::new(
+    Ok(Provider::new(
         RetryClientBuilder::default()
-            // assume minimum compute units per second if not provided as growth plan standard
-            .compute_units_per_second(compute_units_per_second.unwrap_or(660))
-            .rate_limit_retries(5000)
-            .timeout_retries(1000)
-            .initial_backoff(Duration::from_millis(500))
-            .build(provider, Box::<ethers::providers::HttpRateLimitRetryPolicy>::default()),
-    );
-    Ok(Arc::new(JsonRpcCachedProvider::new(instance, max_block_range)))
+            .compute_units_per_second(compute_units_per_second)
+            .rate_limit_retries(
+                retry_policy.max_rate_limit_retries.unwrap_or(DEFAULT_RATE_LIMIT_RETRIES),
+            )
+            .timeout_retries(retry_policy.max_timeout_retries.unwrap_or(DEFAULT_TIMEOUT_RETRIES))
+            .initial_backoff(Duration::from_millis(
+                retry_policy.initial_backoff_ms.unwrap_or(DEFAULT_INITIAL_BACKOFF_MS),
+            ))
+            .build(
+                provider,
+                Box::new(RateLimitRetryPolicy {
+                    inner: HttpRateLimitRetryPolicy,
+                    error_codes: retry_policy.rate_limit_error_codes.clone().unwrap_or_default(),
+                }),
+            ),
+    ))
+}
+
+pub fn create_client(
+    rpc_url: &str,
+    compute_units_per_second: Option<u64>,
+    max_block_range: Option<U64>,
+    custom_headers: HeaderMap,
+) -> Result<Arc<JsonRpcCachedProvider>, RetryClientError> {
+    create_load_balanced_client(
+        &[NetworkRpcEndpoint { url: rpc_url.to_string(), compute_units_per_second }],
+        &RpcClientOptions { max_block_range, ..Default::default() },
+        custom_headers,
+    )
+}
+
+/// Network settings applied to every endpoint of a client
+#[derive(Debug, Clone, Default)]
+pub struct RpcClientOptions {
+    pub max_block_range: Option<U64>,
+    pub retry_policy: NetworkRetryPolicy,
+    pub compute_unit_costs: Option<ComputeUnitCosts>,
+    pub merge_get_logs: bool,
+    pub max_addresses_per_get_logs: Option<usize>,
+    pub block_poll_interval_ms: Option<u64>,
+    pub circuit_breaker: Option<CircuitBreakerSettings>,
+    pub log_fetch_strategy: LogFetchStrategy,
+    pub proxy: Option<NetworkProxy>,
+    pub rpc_debug: Option<RpcDebugSettings>,
+    pub block_cache: Option<BlockCacheSettings>,
+    /// Endpoints which need to agree on a head block, see `HeadConsensusSettings`
+    pub head_quorum: Option<usize>,
+}
+
+impl RpcClientOptions {
+    pub fn from_network(network: &Network) -> Self {
+        Self {
+            max_block_range: network.max_block_range,
+            retry_policy: network.retry.clone().unwrap_or_default(),
+            compute_unit_costs: network.compute_unit_costs.clone(),
+            merge_get_logs: network.merge_get_logs.unwrap_or_default(),
+            max_addresses_per_get_logs: network.max_addresses_per_get_logs,
+            block_poll_interval_ms: network.block_poll_interval_ms,
+            circuit_breaker: network.circuit_breaker.clone(),
+            log_fetch_strategy: network.log_fetch_strategy.unwrap_or_default(),
+            proxy: network.proxy.clone(),
+            rpc_debug: network.rpc_debug.clone(),
+            block_cache: network.block_cache.clone(),
+            head_quorum: network.head_quorum(),
+        }
+    }
+}
+
+/// Distributes getLogs and getBlock requests round-robin across equally trusted endpoints,
+/// typed contract calls go through the first endpoint
+pub fn create_load_balanced_client(
+    endpoints: &[NetworkRpcEndpoint],
+    options: &RpcClientOptions,
+    custom_headers: HeaderMap,
+) -> Result<Arc<JsonRpcCachedProvider>, RetryClientError> {
+    if endpoints.is_empty() {
+        return Err(RetryClientError::NoRpcEndpoints);
+    }
+
+    let mut providers = vec![];
+    for endpoint in endpoints {
+        let compute_units_per_second =
+            endpoint.compute_units_per_second.unwrap_or(DEFAULT_COMPUTE_UNITS_PER_SECOND);
+        let provider = create_retry_provider(
+            &endpoint.url,
+            compute_units_per_second,
+            &options.retry_policy,
+            options.proxy.as_ref(),
+            custom_headers.clone(),
+        )?;
+        providers.push((endpoint.url.clone(), provider, compute_units_per_second));
+    }
+
+    Ok(Arc::new(JsonRpcCachedProvider::new_load_balanced(providers, options)))
 }
 
 pub async fn get_chain_id(rpc_url: &str) -> Result<U256, ProviderError> {
@@ -121,10 +1495,9 @@ impl CreateNetworkProvider {
     pub fn create(manifest: &Manifest) -> Result<Vec<CreateNetworkProvider>, RetryClientError> {
         let mut result: Vec<CreateNetworkProvider> = vec![];
         for network in &manifest.networks {
-            let provider = create_client(
-                &network.rpc,
-                network.compute_units_per_second,
-                network.max_block_range,
+            let provider = create_load_balanced_client(
+                &network.rpc_endpoints(),
+                &RpcClientOptions::from_network(network),
                 manifest.get_custom_headers(),
             )?;
             result.push(CreateNetworkProvider {
@@ -149,6 +1522,210 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_weighted_round_robin_schedule() {
+        assert_eq!(weighted_round_robin_schedule(&[660]), vec![0]);
+        assert_eq!(weighted_round_robin_schedule(&[660, 660]), vec![0, 1]);
+        assert_eq!(weighted_round_robin_schedule(&[1000, 500]), vec![0, 1, 0]);
+        assert_eq!(weighted_round_robin_schedule(&[1000, 100]).len(), 11);
+    }
+
+    #[test]
+    fn test_agreed_block_takes_quorum_head() {
+        let heads: Vec<Block<H256>> = [10u64, 12, 11]
+            .into_iter()
+            .map(|number| Block { number: Some(number.into()), ..Default::default() })
+            .collect();
+
+        assert_eq!(agreed_block(heads.clone(), 1).and_then(|block| block.number), Some(12.into()));
+        assert_eq!(agreed_block(heads.clone(), 2).and_then(|block| block.number), Some(11.into()));
+        assert_eq!(agreed_block(heads.clone(), 3).and_then(|block| block.number), Some(10.into()));
+        assert!(agreed_block(vec![], 2).is_none());
+    }
+
+    #[test]
+    fn test_block_cache_evicts_least_recently_used() {
+        let mut blocks = CachedCells::<U64, Arc<Block<H256>>>::default();
+        let first = blocks.get_or_insert(1.into(), 2);
+        blocks.get_or_insert(2.into(), 2);
+        // using block 1 again leaves block 2 as the least recently used
+        assert!(Arc::ptr_eq(&first, &blocks.get_or_insert(1.into(), 2)));
+        blocks.get_or_insert(3.into(), 2);
+
+        assert_eq!(blocks.cells.len(), 2);
+        assert!(blocks.cells.contains_key(&1.into()));
+        assert!(!blocks.cells.contains_key(&2.into()));
+        assert!(blocks.cells.contains_key(&3.into()));
+    }
+
+    #[test]
+    fn test_agreed_block_ignores_failed_endpoints() {
+        // four endpoints must all agree but two of them failed to answer
+        let heads: Vec<Block<H256>> = [12u64, 11]
+            .into_iter()
+            .map(|number| Block { number: Some(number.into()), ..Default::default() })
+            .collect();
+
+        assert_eq!(agreed_block(heads, 4).and_then(|block| block.number), Some(11.into()));
+    }
+
+    #[tokio::test]
+    async fn test_agreed_latest_block_errors_when_every_endpoint_fails() {
+        let _mocks: Vec<mockito::Mock> = ["/first", "/second"]
+            .into_iter()
+            .map(|path| {
+                mockito::mock("POST", path)
+                    .with_header("content-type", "application/json")
+                    .with_body(
+                        r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32000,"message":"down"}}"#,
+                    )
+                    .create()
+            })
+            .collect();
+        let endpoints: Vec<NetworkRpcEndpoint> = ["/first", "/second"]
+            .into_iter()
+            .map(|path| NetworkRpcEndpoint {
+                url: format!("{}{}", mockito::server_url(), path),
+                compute_units_per_second: None,
+            })
+            .collect();
+        let options = RpcClientOptions { head_quorum: Some(2), ..Default::default() };
+        let provider = create_load_balanced_client(&endpoints, &options, HeaderMap::new()).unwrap();
+        *provider.agreed_head.lock().unwrap() =
+            Some(Arc::new(Block { number: Some(100.into()), ..Default::default() }));
+
+        assert!(provider.agreed_latest_block(2).await.is_err());
+    }
+
+    #[test]
+    fn test_get_logs_endpoint_has_reached_to_block() {
+        let endpoints: Vec<NetworkRpcEndpoint> = ["http://localhost:8545", "http://localhost:8546"]
+            .into_iter()
+            .map(|url| NetworkRpcEndpoint { url: url.to_string(), compute_units_per_second: None })
+            .collect();
+        let options = RpcClientOptions { head_quorum: Some(1), ..Default::default() };
+        let provider = create_load_balanced_client(&endpoints, &options, HeaderMap::new()).unwrap();
+        provider.endpoints[0].head.store(100, Ordering::Relaxed);
+        provider.endpoints[1].head.store(90, Ordering::Relaxed);
+
+        for _ in 0..4 {
+            assert_eq!(provider.next_endpoint(Some(95.into())).0.url, "http://localhost:8545");
+        }
+        // nobody has reached the block so the furthest endpoint gets it
+        assert_eq!(provider.next_endpoint(Some(120.into())).0.url, "http://localhost:8545");
+
+        let mut urls: Vec<&str> =
+            (0..2).map(|_| provider.next_endpoint(Some(90.into())).0.url.as_str()).collect();
+        urls.sort();
+        assert_eq!(urls, vec!["http://localhost:8545", "http://localhost:8546"]);
+    }
+
+    #[test]
+    fn test_scrub_endpoint_url() {
+        let url = "https://eth-mainnet.g.alchemy.com/v2/secret-key";
+        let error = format!("error sending request for url ({}): timed out", url);
+        assert_eq!(
+            scrub_endpoint_url(&error, url),
+            "error sending request for url (eth-mainnet.g.alchemy.com): timed out"
+        );
+    }
+
+    #[test]
+    fn test_rpc_debug_log_limits_methods_and_rate() {
+        let rpc_debug = RpcDebugLog::new(RpcDebugSettings {
+            methods: Some(vec!["eth_getLogs".to_string()]),
+            max_logs_per_minute: Some(2),
+        });
+
+        assert!(!rpc_debug.try_acquire("eth_blockNumber"));
+        assert!(rpc_debug.try_acquire("eth_getLogs"));
+        assert!(rpc_debug.try_acquire("eth_getLogs"));
+        assert!(!rpc_debug.try_acquire("eth_getLogs"));
+    }
+
+    #[test]
+    fn test_merge_filters() {
+        let transfer = H256::from_low_u64_be(1);
+        let approval = H256::from_low_u64_be(2);
+        let a = Filter::new()
+            .address(Address::from_low_u64_be(10))
+            .topic0(transfer)
+            .from_block(1)
+            .to_block(5);
+        let b = Filter::new()
+            .address(Address::from_low_u64_be(20))
+            .topic0(approval)
+            .from_block(1)
+            .to_block(5);
+        assert_eq!(merge_key(&a), merge_key(&b));
+
+        let merged = merge_filters(&[&a, &b]);
+        assert_eq!(
+            merged.address,
+            Some(ValueOrArray::Array(vec![
+                Address::from_low_u64_be(10),
+                Address::from_low_u64_be(20)
+            ]))
+        );
+
+        let log = Log {
+            address: Address::from_low_u64_be(20),
+            topics: vec![approval],
+            ..Default::default()
+        };
+        assert!(log_matches_filter(&b, &log));
+        assert!(!log_matches_filter(&a, &log));
+        assert!(merge_key(&a.clone().topic1(transfer)).is_none());
+        assert!(!log_matches_filter(&b.clone().topic1(transfer), &log));
+        assert!(log_matches_filter(
+            &b.clone().topic1(vec![transfer, approval]),
+            &Log { topics: vec![approval, approval], ..log.clone() }
+        ));
+    }
+
+    #[test]
+    fn test_chunk_filter_addresses() {
+        let addresses: Vec<Address> = (0..5).map(Address::from_low_u64_be).collect();
+        let filter = Filter::new().address(addresses).from_block(1).to_block(2);
+
+        assert!(chunk_filter_addresses(&filter, 0).is_none());
+        assert!(chunk_filter_addresses(&filter, 5).is_none());
+
+        let chunks = chunk_filter_addresses(&filter, 2).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].address, Some(ValueOrArray::Array(vec![Address::from_low_u64_be(4)])));
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let circuit = CircuitBreaker::new(
+            "https://rpc.example.com/v2/key",
+            &CircuitBreakerSettings {
+                error_rate_threshold: Some(0.5),
+                window: Some(4),
+                open_duration_ms: Some(0),
+            },
+        );
+        assert_eq!(circuit.endpoint, "rpc.example.com");
+
+        for failed in [true, false, false] {
+            circuit.record(failed);
+        }
+        assert_eq!(circuit.state(), CircuitState::Closed);
+
+        circuit.record(true);
+        assert_eq!(circuit.state(), CircuitState::HalfOpen);
+
+        let probe = circuit.try_acquire().unwrap();
+        assert!(circuit.try_acquire().is_none());
+        drop(probe);
+
+        // a cancelled probe does not leave the breaker waiting for an outcome
+        let probe = circuit.try_acquire().unwrap();
+        probe.record(false);
+        assert_eq!(circuit.state(), CircuitState::Closed);
+    }
+
     #[test]
     fn test_create_retry_client_invalid_url() {
         let rpc_url = "invalid_url";
@@ -160,4 +1737,51 @@ mod tests {
             panic!("Expected HttpProviderCantBeCreated error");
         }
     }
+
+    #[tokio::test]
+    async fn test_block_cache_collapses_duplicate_fetches() {
+        let cache = BlockCache::new(None);
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(Block { number: Some(U64::from(7)), ..Default::default() })
+        };
+
+        let (a, b) = tokio::join!(
+            cache.get_or_fetch(U64::from(7), fetch),
+            cache.get_or_fetch(U64::from(7), fetch)
+        );
+        assert_eq!(a.unwrap().number, b.unwrap().number);
+        cache.get_or_fetch(U64::from(7), fetch).await.unwrap();
+
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_transaction_caches_reorg_safe_transactions() {
+        let rpc_mock = |method: &str, result: serde_json::Value| {
+            mockito::mock("POST", "/")
+                .match_body(mockito::Matcher::PartialJson(serde_json::json!({ "method": method })))
+                .with_header("content-type", "application/json")
+                .with_body(
+                    serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string(),
+                )
+        };
+        let head = Block::<H256> { number: Some(U64::from(1_000)), ..Default::default() };
+        let transaction = Transaction { block_number: Some(U64::from(10)), ..Default::default() };
+        let _chain_id = rpc_mock("eth_chainId", serde_json::json!("0x1")).create();
+        let _head = rpc_mock("eth_getBlockByNumber", serde_json::to_value(&head).unwrap()).create();
+        let get_transaction =
+            rpc_mock("eth_getTransactionByHash", serde_json::to_value(&transaction).unwrap())
+                .expect(1)
+                .create();
+
+        let provider = create_client(&mockito::server_url(), None, None, HeaderMap::new()).unwrap();
+        let first = provider.get_transaction(transaction.hash).await.unwrap().unwrap();
+        let second = provider.get_transaction(transaction.hash).await.unwrap().unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        get_transaction.assert();
+    }
 }
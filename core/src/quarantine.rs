@@ -0,0 +1,421 @@
+use std::sync::Arc;
+
+use ethers::types::Log;
+use tracing::{error, info, warn};
+
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresConnectionError, PostgresError},
+        internal_schema::{internal_schema, internal_table},
+        sql_type_wrapper::ParamDecodeError,
+    },
+    event::callback_registry::{
+        EventCallbackRegistry, EventCallbackRegistryInformation, EventResult,
+    },
+    manifest::core::Manifest,
+};
+
+fn failed_events_table_name() -> String {
+    internal_table("failed_events")
+}
+
+pub fn create_failed_events_table_sql() -> String {
+    format!(
+        r#"
+        CREATE SCHEMA IF NOT EXISTS {};
+        CREATE TABLE IF NOT EXISTS {} (
+            id BIGSERIAL PRIMARY KEY,
+            indexer_name TEXT NOT NULL,
+            contract_name TEXT NOT NULL,
+            event_name TEXT NOT NULL,
+            network TEXT NOT NULL,
+            block_number NUMERIC NOT NULL,
+            tx_hash CHAR(66) NOT NULL,
+            log_index NUMERIC NOT NULL,
+            log JSONB NOT NULL,
+            error TEXT NOT NULL,
+            attempts INT NOT NULL,
+            failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    "#,
+        internal_schema(),
+        failed_events_table_name()
+    )
+}
+
+/// Writes events whose handler keeps failing to rindexer_internal.failed_events so indexing can
+/// move on, `rindexer replay-failed` runs them again once the handler is fixed
+pub struct Quarantine {
+    postgres: Arc<PostgresClient>,
+    indexer_name: String,
+    max_attempts: u32,
+}
+
+impl Quarantine {
+    pub async fn new(
+        postgres: Arc<PostgresClient>,
+        indexer_name: &str,
+        max_attempts: u32,
+    ) -> Result<Self, PostgresError> {
+        postgres
+            .execute_ddl(&create_failed_events_table_sql(), "create the failed events table")
+            .await?;
+        Ok(Self { postgres, indexer_name: indexer_name.to_string(), max_attempts })
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns false when the events could not be written, the caller should keep retrying
+    pub async fn quarantine(
+        &self,
+        event: &EventCallbackRegistryInformation,
+        data: &[EventResult],
+        error: &str,
+        attempts: u32,
+    ) -> bool {
+        let failures: Vec<(&EventResult, String)> =
+            data.iter().map(|result| (result, error.to_string())).collect();
+        if let Err(e) = insert_failed_events(
+            &self.postgres,
+            &self.indexer_name,
+            &event.contract.name,
+            &event.event_name,
+            &failures,
+            attempts,
+        )
+        .await
+        {
+            error!("{} - Could not quarantine failed events: {}", event.info_log_name(), e);
+            return false;
+        }
+
+        warn!(
+            "{} - Quarantined {} events after {} failed attempts, replay them with `rindexer replay-failed`",
+            event.info_log_name(),
+            data.len(),
+            attempts
+        );
+        true
+    }
+}
+
+/// Inserts the events in one transaction, a batch which fails half way is retried whole rather
+/// than replayed twice
+async fn insert_failed_events(
+    postgres: &PostgresClient,
+    indexer_name: &str,
+    contract_name: &str,
+    event_name: &str,
+    failures: &[(&EventResult, String)],
+    attempts: u32,
+) -> Result<(), String> {
+    let sql = format!(
+        "INSERT INTO {} (indexer_name, contract_name, event_name, network, block_number, tx_hash, log_index, log, error, attempts) \
+        VALUES ($1, $2, $3, $4, $5::TEXT::NUMERIC, $6, $7::TEXT::NUMERIC, $8::TEXT::JSONB, $9, $10)",
+        failed_events_table_name()
+    );
+
+    let transaction = postgres.transaction().await.map_err(|e| e.to_string())?;
+    for (result, error) in failures {
+        let log = serde_json::to_string(&result.log).map_err(|e| e.to_string())?;
+        let tx_information = &result.tx_information;
+        transaction
+            .execute(
+                &sql,
+                &[
+                    &indexer_name,
+                    &contract_name,
+                    &event_name,
+                    &tx_information.network,
+                    &tx_information.block_number.to_string(),
+                    &format!("{:?}", tx_information.transaction_hash),
+                    &tx_information.log_index.to_string(),
+                    &log,
+                    error,
+                    &(attempts as i32),
+                ],
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    transaction.commit().await.map_err(|e| e.to_string())
+}
+
+/// Writes logs whose params did not fit the ABI to the failed events table so the rest of their
+/// batch is still indexed, they are replayed like any other quarantined event once the ABI is
+/// fixed. The table is created by `setup_postgres`, an error means the batch must not advance.
+pub async fn quarantine_undecodable(
+    postgres: &PostgresClient,
+    indexer_name: &str,
+    contract_name: &str,
+    event_name: &str,
+    failures: &[(&EventResult, ParamDecodeError)],
+) -> Result<(), String> {
+    let failures: Vec<(&EventResult, String)> =
+        failures.iter().map(|(result, decode_error)| (*result, decode_error.to_string())).collect();
+    insert_failed_events(postgres, indexer_name, contract_name, event_name, &failures, 1).await
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ReplayFailedEventsError {
+    #[error("Postgres storage is not enabled - failed events are quarantined in postgres")]
+    PostgresNotEnabled,
+
+    #[error("Could not connect to postgres: {0}")]
+    PostgresConnectionError(#[from] PostgresConnectionError),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayFailedEventsOptions {
+    pub contract_name: Option<String>,
+    pub event_name: Option<String>,
+    pub network: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayFailedEventsReport {
+    pub replayed: usize,
+    pub still_failing: usize,
+    /// Events of contracts or networks no longer registered, left in the table
+    pub skipped: usize,
+}
+
+struct FailedEvent {
+    id: i64,
+    contract_name: String,
+    event_name: String,
+    network: String,
+    log: Log,
+}
+
+async fn read_failed_events(
+    postgres: &PostgresClient,
+    indexer_name: &str,
+    options: &ReplayFailedEventsOptions,
+) -> Result<Vec<FailedEvent>, PostgresError> {
+    let rows = postgres
+        .query(
+            &format!(
+                "SELECT id, contract_name, event_name, network, log::TEXT FROM {} \
+                WHERE indexer_name = $1 \
+                AND ($2::TEXT IS NULL OR contract_name = $2) \
+                AND ($3::TEXT IS NULL OR event_name = $3) \
+                AND ($4::TEXT IS NULL OR network = $4) \
+                ORDER BY id",
+                failed_events_table_name()
+            ),
+            &[&indexer_name, &options.contract_name, &options.event_name, &options.network],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let log = match serde_json::from_str::<Log>(&row.get::<_, String>(4)) {
+                Ok(log) => log,
+                Err(e) => {
+                    error!("Could not parse quarantined log {}: {}", row.get::<_, i64>(0), e);
+                    return None;
+                }
+            };
+            Some(FailedEvent {
+                id: row.get(0),
+                contract_name: row.get(1),
+                event_name: row.get(2),
+                network: row.get(3),
+                log,
+            })
+        })
+        .collect())
+}
+
+/// Runs the quarantined events through the registered handlers again, events are grouped into
+/// one handler call per contract, event and network in the order they failed
+pub async fn replay_failed_events(
+    manifest: &Manifest,
+    registry: &EventCallbackRegistry,
+    options: &ReplayFailedEventsOptions,
+) -> Result<ReplayFailedEventsReport, ReplayFailedEventsError> {
+    if !manifest.storage.postgres_enabled() {
+        return Err(ReplayFailedEventsError::PostgresNotEnabled);
+    }
+
+    let postgres = PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+    postgres
+        .execute_ddl(&create_failed_events_table_sql(), "create the failed events table")
+        .await?;
+
+    let mut failed_events = read_failed_events(&postgres, &manifest.name, options).await?;
+    let mut report = ReplayFailedEventsReport::default();
+
+    while !failed_events.is_empty() {
+        let first = &failed_events[0];
+        let group_len = failed_events
+            .iter()
+            .take_while(|failed| {
+                failed.contract_name == first.contract_name &&
+                    failed.event_name == first.event_name &&
+                    failed.network == first.network
+            })
+            .count();
+        let group: Vec<FailedEvent> = failed_events.drain(..group_len).collect();
+        let ids: Vec<i64> = group.iter().map(|failed| failed.id).collect();
+
+        let registered = registry.events.iter().find_map(|event| {
+            if event.contract.name != group[0].contract_name ||
+                event.event_name != group[0].event_name
+            {
+                return None;
+            }
+            event
+                .contract
+                .details
+                .iter()
+                .find(|details| details.network == group[0].network)
+                .map(|details| (event, Arc::new(details.clone())))
+        });
+        let Some((event, network_contract)) = registered else {
+            warn!(
+                "{}::{} on {} is no longer registered, leaving {} failed events",
+                group[0].contract_name,
+                group[0].event_name,
+                group[0].network,
+                group.len()
+            );
+            report.skipped += group.len();
+            continue;
+        };
+
+        let data: Vec<EventResult> = group
+            .into_iter()
+            .filter_map(|failed| {
+                let block_number = failed.log.block_number?;
+                Some(EventResult::new(
+                    Arc::clone(&network_contract),
+                    failed.log,
+                    block_number,
+                    block_number,
+                ))
+            })
+            .collect();
+
+        match (event.callback)(data).await {
+            Ok(_) => {
+                postgres
+                    .execute(
+                        &format!("DELETE FROM {} WHERE id = ANY($1)", failed_events_table_name()),
+                        &[&ids],
+                    )
+                    .await?;
+                info!("{} - Replayed {} failed events", event.info_log_name(), ids.len());
+                report.replayed += ids.len();
+            }
+            Err(e) => {
+                postgres
+                    .execute(
+                        &format!(
+                            "UPDATE {} SET error = $2, attempts = attempts + 1, failed_at = NOW() WHERE id = ANY($1)",
+                            failed_events_table_name()
+                        ),
+                        &[&ids, &e],
+                    )
+                    .await?;
+                error!(
+                    "{} - Replaying {} failed events failed: {}",
+                    event.info_log_name(),
+                    ids.len(),
+                    e
+                );
+                report.still_failing += ids.len();
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{H256, U256, U64};
+
+    use super::*;
+    use crate::event::callback_registry::{LogFoundInRequest, TxInformation};
+
+    fn event_result(network: &str, block_number: u64) -> EventResult {
+        let log = Log {
+            block_number: Some(block_number.into()),
+            transaction_hash: Some(H256::from_low_u64_be(block_number)),
+            log_index: Some(U256::zero()),
+            ..Default::default()
+        };
+        EventResult {
+            decoded_data: Arc::new(()),
+            tx_information: TxInformation {
+                network: network.to_string(),
+                address: log.address,
+                block_hash: H256::zero(),
+                block_number: block_number.into(),
+                transaction_hash: H256::from_low_u64_be(block_number),
+                log_index: U256::zero(),
+                transaction_index: U64::zero(),
+                removed: false,
+                after_code_removed: false,
+            },
+            found_in_request: LogFoundInRequest {
+                from_block: block_number.into(),
+                to_block: block_number.into(),
+            },
+            log,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database in DATABASE_URL"]
+    async fn test_failed_events_are_inserted_in_one_transaction() {
+        let postgres = PostgresClient::new().await.expect("DATABASE_URL has to point to postgres");
+        postgres
+            .execute_ddl(&create_failed_events_table_sql(), "create the failed events table")
+            .await
+            .unwrap();
+        let indexer_name = format!("quarantine_test_{}", std::process::id());
+
+        // postgres rejects the nul byte in the second network so the whole batch is rolled back
+        let first = event_result("ethereum", 10);
+        let broken = event_result("ether\0eum", 11);
+        let failures = vec![(&first, "boom".to_string()), (&broken, "boom".to_string())];
+        assert!(insert_failed_events(&postgres, &indexer_name, "Token", "Transfer", &failures, 3)
+            .await
+            .is_err());
+        let options = ReplayFailedEventsOptions::default();
+        assert!(read_failed_events(&postgres, &indexer_name, &options).await.unwrap().is_empty());
+
+        let second = event_result("base", 12);
+        let failures = vec![(&first, "boom".to_string()), (&second, "boom".to_string())];
+        insert_failed_events(&postgres, &indexer_name, "Token", "Transfer", &failures, 3)
+            .await
+            .unwrap();
+
+        let failed = read_failed_events(&postgres, &indexer_name, &options).await.unwrap();
+        assert_eq!(failed.len(), 2);
+        assert_eq!(failed[0].log.block_number, Some(10.into()));
+
+        let base_only =
+            ReplayFailedEventsOptions { network: Some("base".to_string()), ..Default::default() };
+        let failed = read_failed_events(&postgres, &indexer_name, &base_only).await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].network, "base");
+
+        postgres
+            .execute(
+                &format!("DELETE FROM {} WHERE indexer_name = $1", failed_events_table_name()),
+                &[&indexer_name],
+            )
+            .await
+            .unwrap();
+    }
+}
@@ -1,33 +1,55 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use tokio::signal;
+use tokio::{runtime::Runtime, signal};
 use tracing::{error, info};
 
+#[cfg(feature = "graphql")]
+use crate::api::{start_graphql_server, StartGraphqlServerError};
 use crate::{
-    api::{start_graphql_server, GraphqlOverrideSettings, StartGraphqlServerError},
-    database::postgres::{
-        client::PostgresConnectionError,
-        indexes::{ApplyPostgresIndexesError, PostgresIndexResult},
-        relationship::{ApplyAllRelationships, Relationship},
-        setup::{setup_postgres, SetupPostgresError},
+    api::{start_registry_event_push, GraphqlOverrideSettings, StartEventPushServerError},
+    database::{
+        backend::{setup_registered_storage_backends, StorageBackendError},
+        postgres::{
+            client::{PostgresClient, PostgresConnectionError, PostgresError},
+            indexes::{ApplyPostgresIndexesError, PostgresIndexResult},
+            insert_strategy::log_insert_path_metrics,
+            relationship::{ApplyAllRelationships, Relationship},
+            setup::{setup_postgres, SetupPostgresError},
+            write_retry::log_write_retry_metrics,
+        },
     },
     event::callback_registry::EventCallbackRegistry,
+    helpers::set_address_format,
     indexer::{
+        flush_last_synced_checkpoints, flush_pending_indexing_stats,
         no_code::{setup_no_code, SetupNoCodeError},
         start::{start_indexing, StartIndexingError},
-        ContractEventDependencies, ContractEventDependenciesMapFromRelationshipsError,
+        start_block_anomaly_tracking, start_gas_price_sampling, start_verify_schedule,
+        BlockAnomalyError, ContractEventDependencies,
+        ContractEventDependenciesMapFromRelationshipsError, GasPriceError,
     },
     load_env_from_path,
     manifest::{
-        core::ProjectType,
-        storage::RelationshipsAndIndexersError,
-        yaml::{read_manifest, ReadManifestError},
+        core::{Manifest, ProjectType},
+        storage::{ApplyPolicy, RelationshipsAndIndexersError},
+        yaml::{read_manifest, read_manifest_raw, ReadManifestError},
     },
+    provider::{log_circuit_breaker_metrics, CreateNetworkProvider, RetryClientError},
+    quarantine::Quarantine,
     setup_info_logger,
+    streams::{
+        check_registered_stream_sinks, flush_registered_stream_sinks, stream_registry_events,
+    },
 };
 
 pub struct IndexingDetails {
     pub registry: EventCallbackRegistry,
+    pub apply_migrations: bool,
 }
 
 pub struct StartDetails<'a> {
@@ -44,6 +66,7 @@ pub enum StartRindexerError {
     #[error("Could not read manifest: {0}")]
     CouldNotReadManifest(#[from] ReadManifestError),
 
+    #[cfg(feature = "graphql")]
     #[error("Could not start graphql error {0}")]
     CouldNotStartGraphqlServer(#[from] StartGraphqlServerError),
 
@@ -72,141 +95,315 @@ pub enum StartRindexerError {
 
     #[error("{0}")]
     RelationshipsAndIndexersError(#[from] RelationshipsAndIndexersError),
+
+    #[error("Could not create the failed events table: {0}")]
+    QuarantineSetupError(#[from] PostgresError),
+
+    #[error("Could not setup storage backend: {0}")]
+    StorageBackendSetupError(#[from] StorageBackendError),
+
+    #[error("{0}")]
+    RetryClientError(#[from] RetryClientError),
+
+    #[error("Could not start block anomaly tracking: {0}")]
+    BlockAnomalyError(#[from] BlockAnomalyError),
+
+    #[error("Could not start gas price sampling: {0}")]
+    GasPriceError(#[from] GasPriceError),
+
+    #[error("Could not start event push server: {0}")]
+    StartEventPushServerError(#[from] StartEventPushServerError),
+}
+
+/// The runtime sized by the manifest `runtime` settings, tokio defaults are used when the
+/// manifest can not be read as starting up reports why
+pub fn build_runtime(manifest_path: &PathBuf) -> io::Result<Runtime> {
+    read_manifest_raw(manifest_path)
+        .map(|manifest| manifest.runtime_settings())
+        .unwrap_or_default()
+        .build()
+}
+
+const METRICS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Logs how the postgres writes and rpc endpoints have been doing since startup
+fn start_metrics_logging() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(METRICS_LOG_INTERVAL);
+        // first tick completes straight away
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            log_insert_path_metrics();
+            log_write_retry_metrics();
+            log_circuit_breaker_metrics();
+        }
+    });
 }
 
 pub async fn start_rindexer(details: StartDetails<'_>) -> Result<(), StartRindexerError> {
-    let project_path = details.manifest_path.parent();
-    match project_path {
-        Some(project_path) => {
-            load_env_from_path(project_path);
-            let manifest = Arc::new(read_manifest(details.manifest_path)?);
-
-            if manifest.project_type != ProjectType::NoCode {
-                setup_info_logger();
-                info!("Starting rindexer rust project");
+    let project_path = details
+        .manifest_path
+        .parent()
+        .ok_or(StartRindexerError::NoProjectPathFoundUsingParentOfManifestPath)?;
+    load_env_from_path(project_path);
+    let manifest = Arc::new(read_manifest(details.manifest_path)?);
+
+    // embedded indexers leave logging to the program running them
+    if manifest.project_type != ProjectType::NoCode {
+        setup_info_logger();
+    }
+
+    start_rindexer_with_manifest(
+        project_path,
+        manifest,
+        details.indexing_details,
+        details.graphql_details,
+    )
+    .await
+}
+
+/// Starts from a manifest already in memory, the project path is where ABIs and generated files
+/// are read from
+pub(crate) async fn start_rindexer_with_manifest(
+    project_path: &Path,
+    manifest: Arc<Manifest>,
+    indexing_details: Option<IndexingDetails>,
+    graphql_details: GraphqlOverrideSettings,
+) -> Result<(), StartRindexerError> {
+    set_address_format(
+        manifest.global.as_ref().and_then(|global| global.address_format).unwrap_or_default(),
+    );
+
+    if manifest.project_type != ProjectType::NoCode {
+        info!("Starting rindexer rust project");
+    }
+
+    // Spawn a separate task for the GraphQL server if specified
+    #[cfg(feature = "graphql")]
+    let graphql_server_handle = if graphql_details.enabled && manifest.storage.postgres_enabled() {
+        let manifest_clone = Arc::clone(&manifest);
+        let indexer = manifest_clone.to_indexer();
+        let storage = manifest.storage.clone();
+        let graphql_project_path = project_path.to_path_buf();
+        let mut graphql_settings = manifest.graphql.clone().unwrap_or_default();
+        if let Some(override_port) = &graphql_details.override_port {
+            graphql_settings.set_port(*override_port);
+        }
+        Some(tokio::spawn(async move {
+            if let Err(e) =
+                start_graphql_server(&graphql_project_path, &indexer, &graphql_settings, &storage)
+                    .await
+            {
+                error!("Failed to start GraphQL server: {:?}", e);
             }
+        }))
+    } else {
+        None
+    };
+    #[cfg(not(feature = "graphql"))]
+    let graphql_server_handle: Option<tokio::task::JoinHandle<()>> = None;
+
+    if graphql_server_handle.is_none() && graphql_details.enabled {
+        if cfg!(feature = "graphql") {
+            error!("GraphQL can not run without postgres storage enabled, you have tried to run GraphQL which will now be skipped.");
+        } else {
+            error!("rindexer was built without the graphql feature, you have tried to run GraphQL which will now be skipped.");
+        }
+    }
 
-            // Spawn a separate task for the GraphQL server if specified
-            let graphql_server_handle =
-                if details.graphql_details.enabled && manifest.storage.postgres_enabled() {
-                    let manifest_clone = Arc::clone(&manifest);
-                    let indexer = manifest_clone.to_indexer();
-                    let mut graphql_settings = manifest.graphql.clone().unwrap_or_default();
-                    if let Some(override_port) = &details.graphql_details.override_port {
-                        graphql_settings.set_port(*override_port);
-                    }
-                    Some(tokio::spawn(async move {
-                        if let Err(e) = start_graphql_server(&indexer, &graphql_settings).await {
-                            error!("Failed to start GraphQL server: {:?}", e);
-                        }
-                    }))
-                } else {
-                    None
-                };
-
-            if graphql_server_handle.is_none() && details.graphql_details.enabled {
-                error!("GraphQL can not run without postgres storage enabled, you have tried to run GraphQL which will now be skipped.");
+    if let Some(mut indexing_details) = indexing_details {
+        indexing_details.registry.apply_event_networks(&manifest.contracts);
+        indexing_details
+            .registry
+            .apply_handler_limits(&manifest.contracts, &manifest.runtime_settings());
+
+        let postgres_enabled = &manifest.storage.postgres_enabled();
+
+        // setup postgres is already called in no-code startup
+        if manifest.project_type != ProjectType::NoCode && *postgres_enabled {
+            setup_postgres(project_path, &manifest, indexing_details.apply_migrations).await?;
+
+            if let Some(block_anomalies) = &manifest.block_anomalies {
+                let postgres =
+                    PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+                start_block_anomaly_tracking(
+                    block_anomalies,
+                    Arc::new(postgres),
+                    &CreateNetworkProvider::create(&manifest)?,
+                )?;
             }
 
-            if let Some(mut indexing_details) = details.indexing_details {
-                let postgres_enabled = &manifest.storage.postgres_enabled();
+            if let Some(gas_prices) = &manifest.gas_prices {
+                let postgres =
+                    PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+                start_gas_price_sampling(
+                    gas_prices,
+                    Arc::new(postgres),
+                    manifest.storage.postgres_insert_strategy(),
+                    &CreateNetworkProvider::create(&manifest)?,
+                )?;
+            }
+        }
+
+        setup_registered_storage_backends(project_path, &manifest).await?;
+        check_registered_stream_sinks().await;
+        // no-code projects stream to the registered sinks as they write
+        if manifest.project_type != ProjectType::NoCode {
+            stream_registry_events(project_path, &manifest, &mut indexing_details.registry)?;
+        }
+
+        // no-code projects publish to the push hub as they write
+        if let Some(push) =
+            manifest.push.as_ref().filter(|_| manifest.project_type != ProjectType::NoCode)
+        {
+            let postgres = match *postgres_enabled {
+                true => Some(Arc::new(
+                    PostgresClient::from_storage(&manifest.storage, &manifest.name).await?,
+                )),
+                false => None,
+            };
+            start_registry_event_push(
+                project_path,
+                &manifest,
+                push,
+                postgres,
+                &mut indexing_details.registry,
+            )
+            .await?;
+        }
 
-                // setup postgres is already called in no-code startup
-                if manifest.project_type != ProjectType::NoCode && *postgres_enabled {
-                    setup_postgres(project_path, &manifest).await?;
+        match manifest.runtime_settings().handler_max_attempts {
+            Some(max_attempts) if *postgres_enabled => {
+                let postgres =
+                    PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+                indexing_details.registry.quarantine = Some(Arc::new(
+                    Quarantine::new(Arc::new(postgres), &manifest.name, max_attempts).await?,
+                ));
+            }
+            Some(_) => {
+                error!("handler_max_attempts needs postgres storage to quarantine failed events, failing handlers will be retried forever");
+            }
+            None => {}
+        }
+        start_verify_schedule(&manifest, indexing_details.registry.complete());
+
+        start_metrics_logging();
+
+        // throttled checkpoints write the blocks they held back and the indexing stats write the
+        // last partial interval before the process exits
+        if manifest.contracts.iter().any(|contract| contract.checkpoint.is_some()) ||
+            manifest.storage.postgres_indexing_stats().is_some()
+        {
+            tokio::spawn(async {
+                if signal::ctrl_c().await.is_ok() {
+                    info!("Writing held back last synced blocks and indexing stats before shutting down");
+                    flush_last_synced_checkpoints().await;
+                    flush_pending_indexing_stats().await;
+                    std::process::exit(0);
                 }
+            });
+        }
+
+        let (relationships, postgres_indexes) = manifest
+            .storage
+            .create_relationships_and_indexes(project_path, &manifest.name, &manifest.contracts)
+            .await?;
+
+        let immediate_relationships =
+            Relationship::with_apply_policy(&relationships, ApplyPolicy::Immediately);
+        PostgresIndexResult::apply_indexes(
+            &manifest.storage,
+            &manifest.name,
+            PostgresIndexResult::with_apply_policy(&postgres_indexes, ApplyPolicy::Immediately),
+        )
+        .await?;
+        Relationship::apply_all(&manifest.storage, &manifest.name, &immediate_relationships)
+            .await?;
+        let mut dependencies: Vec<ContractEventDependencies> =
+            ContractEventDependencies::parse(&manifest, &immediate_relationships)?;
+
+        let postgres_indexes = PostgresIndexResult::with_apply_policy(
+            &postgres_indexes,
+            ApplyPolicy::AfterHistoricSync,
+        );
+        let deferred_relationships =
+            Relationship::with_apply_policy(&relationships, ApplyPolicy::AfterHistoricSync);
+
+        let processed_network_contracts = start_indexing(
+            &manifest,
+            project_path,
+            &dependencies,
+            // we index all the historic data first before then applying FKs
+            !deferred_relationships.is_empty(),
+            indexing_details.registry.complete(),
+        )
+        .await?;
+
+        // TODO if graphql isn't up yet, and we apply this on graphql wont refresh we need
+        // to handle this
+        flush_registered_stream_sinks().await;
+        flush_last_synced_checkpoints().await;
+
+        info!("Applying indexes if any back to the database as historic resync is complete");
+        PostgresIndexResult::apply_indexes(&manifest.storage, &manifest.name, postgres_indexes)
+            .await?;
+
+        if !deferred_relationships.is_empty() {
+            // TODO if graphql isn't up yet, and we apply this on graphql wont refresh we
+            // need to handle this
+            info!("Applying constraints relationships back to the database as historic resync is complete");
+            Relationship::apply_all(&manifest.storage, &manifest.name, &deferred_relationships)
+                .await?;
 
-                let (relationships, postgres_indexes) = manifest
-                    .storage
-                    .create_relationships_and_indexes(
-                        project_path,
-                        &manifest.name,
-                        &manifest.contracts,
-                    )
-                    .await?;
+            if manifest.has_any_contracts_live_indexing() {
+                info!("Starting live indexing now relationship re-applied..");
 
-                let mut dependencies: Vec<ContractEventDependencies> =
-                    ContractEventDependencies::parse(&manifest);
+                let applied_relationships: Vec<Relationship> =
+                    immediate_relationships.into_iter().chain(deferred_relationships).collect();
+                dependencies = ContractEventDependencies::parse(&manifest, &applied_relationships)?;
 
-                let processed_network_contracts = start_indexing(
+                start_indexing(
                     &manifest,
                     project_path,
                     &dependencies,
-                    // we index all the historic data first before then applying FKs
-                    !relationships.is_empty(),
-                    indexing_details.registry.complete(),
+                    false,
+                    indexing_details.registry.reapply_after_historic(processed_network_contracts),
                 )
-                .await?;
-
-                // TODO if graphql isn't up yet, and we apply this on graphql wont refresh we need
-                // to handle this
-                info!(
-                    "Applying indexes if any back to the database as historic resync is complete"
-                );
-                PostgresIndexResult::apply_indexes(postgres_indexes).await?;
-
-                if !relationships.is_empty() {
-                    // TODO if graphql isn't up yet, and we apply this on graphql wont refresh we
-                    // need to handle this
-                    info!("Applying constraints relationships back to the database as historic resync is complete");
-                    Relationship::apply_all(&relationships).await?;
-
-                    if manifest.has_any_contracts_live_indexing() {
-                        info!("Starting live indexing now relationship re-applied..");
-
-                        if dependencies.is_empty() {
-                            dependencies =
-                                ContractEventDependencies::map_from_relationships(&relationships)?;
-                        } else {
-                            info!("Manual dependency_events found, skipping auto-applying the dependency_events with the relationships");
-                        }
-
-                        start_indexing(
-                            &manifest,
-                            project_path,
-                            &dependencies,
-                            false,
-                            indexing_details
-                                .registry
-                                .reapply_after_historic(processed_network_contracts),
-                        )
-                        .await
-                        .map_err(StartRindexerError::CouldNotStartIndexing)?;
-                    }
-                }
-
-                // keep graphql alive even if indexing has finished
-                if details.graphql_details.enabled {
-                    signal::ctrl_c()
-                        .await
-                        .map_err(|_| StartRindexerError::FailedToListenToGraphqlSocket)?;
-                } else {
-                    info!("rindexer resync is complete");
-                    // to avoid the thread closing before the stream is consumed
-                    // lets just sit here for 5 seconds to avoid the race
-                    // 100% a better way to handle this
-                    // TODO - handle this nicer
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                }
-            }
-
-            // Await the GraphQL server task if it was started
-            if let Some(handle) = graphql_server_handle {
-                handle.await.unwrap_or_else(|e| {
-                    error!("GraphQL server task failed: {:?}", e);
-                });
+                .await
+                .map_err(StartRindexerError::CouldNotStartIndexing)?;
             }
         }
-        None => {
-            return Err(StartRindexerError::NoProjectPathFoundUsingParentOfManifestPath);
+
+        // keep graphql alive even if indexing has finished
+        if graphql_details.enabled {
+            signal::ctrl_c()
+                .await
+                .map_err(|_| StartRindexerError::FailedToListenToGraphqlSocket)?;
+        } else {
+            info!("rindexer resync is complete");
+            flush_pending_indexing_stats().await;
+            // to avoid the thread closing before the stream is consumed
+            // lets just sit here for 5 seconds to avoid the race
+            // 100% a better way to handle this
+            // TODO - handle this nicer
+            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
         }
     }
 
+    // Await the GraphQL server task if it was started
+    if let Some(handle) = graphql_server_handle {
+        handle.await.unwrap_or_else(|e| {
+            error!("GraphQL server task failed: {:?}", e);
+        });
+    }
+
     Ok(())
 }
 
 pub struct IndexerNoCodeDetails {
     pub enabled: bool,
+    pub apply_migrations: bool,
 }
 
 pub struct StartNoCodeDetails<'a> {
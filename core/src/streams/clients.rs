@@ -1,238 +1,327 @@
-use std::sync::Arc;
+#![allow(clippy::large_enum_variant)]
 
+use std::{future::Future, path::Path, sync::Arc};
+
+use async_trait::async_trait;
+#[cfg(feature = "sns")]
 use aws_sdk_sns::{config::http::HttpResponse, error::SdkError, operation::publish::PublishError};
 use futures::future::join_all;
-use serde_json::Value;
+use serde_json::{Map, Value};
 use thiserror::Error;
 use tokio::{
+    sync::Semaphore,
     task,
     task::{JoinError, JoinHandle},
 };
-use tracing::error;
 
 use crate::{
+    abi::ABIInput,
+    chat::Template,
     event::{filter_event_data_by_conditions, EventMessage},
-    manifest::stream::{
-        KafkaStreamConfig, KafkaStreamQueueConfig, RabbitMQStreamConfig, RabbitMQStreamQueueConfig,
-        SNSStreamTopicConfig, StreamEvent, StreamsConfig, WebhookStreamConfig,
+    manifest::stream::{StreamEvent, StreamSpillConfig, StreamsConfig, WebhookStreamConfig},
+    streams::{
+        sink::{registered_stream_sinks, StreamBatch, StreamSink},
+        spill::{spill_dir, SpillingStreamSink},
+        StreamWatermark, Webhook, WebhookError,
     },
+};
+#[cfg(feature = "sns")]
+use crate::{manifest::stream::SNSStreamTopicConfig, streams::SNS};
+#[cfg(feature = "kafka")]
+use crate::{
+    manifest::stream::{KafkaStreamConfig, KafkaStreamQueueConfig, StreamSerialization},
     streams::{
         kafka::{Kafka, KafkaError},
-        RabbitMQ, RabbitMQError, Webhook, WebhookError, SNS,
+        schema::StreamSchema,
     },
 };
+#[cfg(feature = "rabbitmq")]
+use crate::{
+    manifest::stream::{RabbitMQStreamConfig, RabbitMQStreamQueueConfig},
+    streams::{RabbitMQ, RabbitMQError},
+};
 
 // we should limit the max chunk size we send over when streaming to 70KB - 100KB is most limits
 // we can add this to yaml if people need it
 const MAX_CHUNK_SIZE: usize = 75 * 1024; // 75 KB
 
-type StreamPublishes = Vec<JoinHandle<Result<usize, StreamError>>>;
+/// The publishes of a batch with the index of the chunk each one publishes
+type StreamPublishes = Vec<(usize, JoinHandle<Result<usize, StreamError>>)>;
 
-#[derive(Debug, Clone)]
-struct SNSStream {
-    config: Vec<SNSStreamTopicConfig>,
-    client: Arc<SNS>,
+fn render_payload_template(template: &Value, event_data: &Value) -> Value {
+    match template {
+        Value::String(value) => Template::new(value.clone()).parse_template_value(event_data),
+        Value::Array(values) => {
+            Value::Array(values.iter().map(|v| render_payload_template(v, event_data)).collect())
+        }
+        Value::Object(map) => Value::Object(
+            map.iter().map(|(k, v)| (k.clone(), render_payload_template(v, event_data))).collect(),
+        ),
+        other => other.clone(),
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum StreamError {
+    #[cfg(feature = "sns")]
     #[error("SNS could not publish - {0}")]
     SnsCouldNotPublish(#[from] SdkError<PublishError, HttpResponse>),
 
     #[error("Webhook could not publish: {0}")]
     WebhookCouldNotPublish(#[from] WebhookError),
 
+    #[cfg(feature = "rabbitmq")]
     #[error("RabbitMQ could not publish: {0}")]
     RabbitMQCouldNotPublish(#[from] RabbitMQError),
 
+    #[cfg(feature = "kafka")]
     #[error("Kafka could not publish: {0}")]
     KafkaCouldNotPublish(#[from] KafkaError),
 
     #[error("Task failed: {0}")]
     JoinError(JoinError),
+
+    #[error("Could not spill undelivered messages: {0}")]
+    Spill(String),
+
+    #[error("Chunks {failed_chunks:?} could not be published: {source}")]
+    ChunksNotPublished { failed_chunks: Vec<usize>, source: Box<StreamError> },
+
+    #[error("{0}")]
+    Sink(#[from] Box<dyn std::error::Error + Send + Sync>),
+}
+
+#[cfg(feature = "sns")]
+struct SNSStream {
+    config: Vec<SNSStreamTopicConfig>,
+    client: Arc<SNS>,
+    publish_permits: Option<Arc<Semaphore>>,
 }
 
-#[derive(Debug, Clone)]
 struct WebhookStream {
     config: Vec<WebhookStreamConfig>,
     client: Arc<Webhook>,
+    publish_permits: Option<Arc<Semaphore>>,
 }
 
+#[cfg(feature = "rabbitmq")]
 pub struct RabbitMQStream {
     config: RabbitMQStreamConfig,
     client: Arc<RabbitMQ>,
+    publish_permits: Option<Arc<Semaphore>>,
 }
 
+#[cfg(feature = "kafka")]
 pub struct KafkaStream {
     config: KafkaStreamConfig,
     client: Arc<Kafka>,
+    schema: Option<Arc<StreamSchema>>,
+    publish_permits: Option<Arc<Semaphore>>,
 }
 
-pub struct StreamsClients {
-    sns: Option<SNSStream>,
-    webhook: Option<WebhookStream>,
-    rabbitmq: Option<RabbitMQStream>,
-    kafka: Option<KafkaStream>,
-}
-
-impl StreamsClients {
-    pub async fn new(stream_config: StreamsConfig) -> Self {
-        let sns = if let Some(config) = &stream_config.sns {
-            Some(SNSStream {
-                config: config.topics.clone(),
-                client: Arc::new(SNS::new(&config.aws_config).await),
-            })
-        } else {
-            None
-        };
-
-        let webhook = stream_config.webhooks.as_ref().map(|config| WebhookStream {
-            config: config.clone(),
-            client: Arc::new(Webhook::new()),
-        });
-
-        let rabbitmq = if let Some(config) = stream_config.rabbitmq.as_ref() {
-            Some(RabbitMQStream {
-                config: config.clone(),
-                client: Arc::new(RabbitMQ::new(&config.url).await),
-            })
-        } else {
-            None
+fn spawn_publish(
+    permits: &Option<Arc<Semaphore>>,
+    chunk_index: usize,
+    publish: impl Future<Output = Result<usize, StreamError>> + Send + 'static,
+) -> (usize, JoinHandle<Result<usize, StreamError>>) {
+    let permits = permits.clone();
+    let task = task::spawn(async move {
+        let _permit = match permits {
+            Some(permits) => permits.acquire_owned().await.ok(),
+            None => None,
         };
+        publish.await
+    });
+    (chunk_index, task)
+}
 
-        let kafka = if let Some(config) = stream_config.kafka.as_ref() {
-            Some(KafkaStream {
-                config: config.clone(),
-                client: Arc::new(
-                    Kafka::new(config)
-                        .await
-                        .unwrap_or_else(|e| panic!("Failed to create Kafka client: {:?}", e)),
-                ),
-            })
-        } else {
-            None
+/// Awaits the publishes in the order they were spawned, every failed chunk is returned so only
+/// those have to be published again
+async fn await_publishes(tasks: StreamPublishes) -> Result<usize, StreamError> {
+    let mut streamed_total = 0;
+    let mut failed_chunks = vec![];
+    let mut first_error = None;
+    for (chunk_index, task) in tasks {
+        let error = match task.await {
+            Ok(Ok(streamed)) => {
+                streamed_total += streamed;
+                continue;
+            }
+            Ok(Err(e)) => e,
+            Err(e) => StreamError::JoinError(e),
         };
-
-        Self { sns, webhook, rabbitmq, kafka }
+        if !failed_chunks.contains(&chunk_index) {
+            failed_chunks.push(chunk_index);
+        }
+        first_error.get_or_insert(error);
     }
 
-    fn has_any_streams(&self) -> bool {
-        self.sns.is_some() ||
-            self.webhook.is_some() ||
-            self.rabbitmq.is_some() ||
-            self.kafka.is_some()
+    match first_error {
+        Some(error) => {
+            failed_chunks.sort_unstable();
+            Err(StreamError::ChunksNotPublished { failed_chunks, source: Box::new(error) })
+        }
+        None => Ok(streamed_total),
     }
+}
 
-    fn chunk_data(&self, data_array: &Vec<Value>) -> Vec<Vec<Value>> {
-        let mut current_chunk = Vec::new();
-        let mut current_size = 0;
-
-        let mut chunks = Vec::new();
-        for item in data_array {
-            let item_str = serde_json::to_string(item).unwrap();
-            let item_size = item_str.len();
+fn block_number(item: &Value) -> Option<&Value> {
+    item.get("transaction_information")?.get("block_number")
+}
 
-            if current_size + item_size > MAX_CHUNK_SIZE {
-                chunks.push(current_chunk);
-                current_chunk = Vec::new();
-                current_size = 0;
-            }
+/// Chunks are cut at every new block as well so the same logs end up in the same chunks however
+/// the blocks were batched
+fn chunk_data(data_array: &Vec<Value>) -> Vec<Vec<Value>> {
+    let mut current_chunk: Vec<Value> = Vec::new();
+    let mut current_size = 0;
 
-            current_chunk.push(item.clone());
-            current_size += item_size;
-        }
+    let mut chunks = Vec::new();
+    for item in data_array {
+        let item_str = serde_json::to_string(item).unwrap();
+        let item_size = item_str.len();
 
-        if !current_chunk.is_empty() {
+        let new_block =
+            current_chunk.last().is_some_and(|last| block_number(last) != block_number(item));
+        if !current_chunk.is_empty() && (current_size + item_size > MAX_CHUNK_SIZE || new_block) {
             chunks.push(current_chunk);
+            current_chunk = Vec::new();
+            current_size = 0;
         }
 
-        chunks
+        current_chunk.push(item.clone());
+        current_size += item_size;
     }
 
-    fn create_chunk_message_raw(&self, event_message: &EventMessage, chunk: &[Value]) -> String {
-        let chunk_message = EventMessage {
-            event_name: event_message.event_name.clone(),
-            event_data: Value::Array(chunk.to_vec()),
-            network: event_message.network.clone(),
-        };
-
-        serde_json::to_string(&chunk_message).unwrap()
+    if !current_chunk.is_empty() {
+        chunks.push(current_chunk);
     }
 
-    fn create_chunk_message_json(&self, event_message: &EventMessage, chunk: &[Value]) -> Value {
-        let chunk_message = EventMessage {
-            event_name: event_message.event_name.clone(),
-            event_data: Value::Array(chunk.to_vec()),
-            network: event_message.network.clone(),
-        };
+    chunks
+}
 
-        serde_json::to_value(&chunk_message).unwrap()
+#[cfg(feature = "sns")]
+fn create_chunk_message_raw(
+    events: &[StreamEvent],
+    event_message: &EventMessage,
+    chunk: &[Value],
+) -> String {
+    serde_json::to_string(&create_chunk_message_json(events, event_message, chunk)).unwrap()
+}
+
+/// With a template the chunk is sent as an array of the rendered events instead of the
+/// default message
+fn create_chunk_message_json(
+    events: &[StreamEvent],
+    event_message: &EventMessage,
+    chunk: &[Value],
+) -> Value {
+    if let Some(template) = &find_stream_event(events, event_message).template {
+        return Value::Array(
+            chunk
+                .iter()
+                .map(|event_data| {
+                    let mut context = event_data.clone();
+                    if let Value::Object(map) = &mut context {
+                        map.entry("event_name")
+                            .or_insert_with(|| Value::String(event_message.event_name.clone()));
+                        map.entry("network")
+                            .or_insert_with(|| Value::String(event_message.network.clone()));
+                    }
+                    render_payload_template(template, &context)
+                })
+                .collect(),
+        );
     }
 
-    fn generate_publish_message_id(
-        &self,
-        id: &str,
-        index: usize,
-        prefix: &Option<String>,
-    ) -> String {
-        format!(
-            "rindexer_stream__{}-{}-chunk-{}",
-            prefix.as_ref().unwrap_or(&"".to_string()),
-            id.to_lowercase(),
-            index
-        )
-    }
-
-    fn filter_chunk_event_data_by_conditions(
-        &self,
-        events: &[StreamEvent],
-        event_message: &EventMessage,
-        chunk: &[Value],
-    ) -> Vec<Value> {
-        let stream_event = events
-            .iter()
-            .find(|e| e.event_name == event_message.event_name)
-            .expect("Failed to find stream event - should never happen please raise an issue");
+    let chunk_message = EventMessage {
+        event_name: event_message.event_name.clone(),
+        event_data: Value::Array(chunk.to_vec()),
+        network: event_message.network.clone(),
+        removed: event_message.removed,
+    };
 
-        let filtered_chunk: Vec<Value> = chunk
-            .iter()
-            .filter(|event_data| {
-                if let Some(conditions) = &stream_event.conditions {
-                    filter_event_data_by_conditions(event_data, conditions)
-                } else {
-                    true
-                }
-            })
-            .cloned()
-            .collect();
+    serde_json::to_value(&chunk_message).unwrap()
+}
 
-        filtered_chunk
-    }
+fn generate_publish_message_id(
+    batch: &StreamBatch<'_>,
+    index: usize,
+    prefix: &Option<String>,
+) -> String {
+    format!(
+        "rindexer_stream__{}-{}",
+        prefix.as_ref().unwrap_or(&"".to_string()),
+        batch.chunk_id(index).to_lowercase()
+    )
+}
 
-    fn sns_stream_tasks(
+fn find_stream_event<'a>(
+    events: &'a [StreamEvent],
+    event_message: &EventMessage,
+) -> &'a StreamEvent {
+    events
+        .iter()
+        .find(|e| e.event_name == event_message.event_name)
+        .expect("Failed to find stream event - should never happen please raise an issue")
+}
+
+fn is_streamed(events: &[StreamEvent], networks: &[String], event_message: &EventMessage) -> bool {
+    events.iter().any(|e| e.event_name == event_message.event_name) &&
+        networks.contains(&event_message.network)
+}
+
+fn filter_chunk_event_data_by_conditions(
+    stream_conditions: &Option<Vec<Map<String, Value>>>,
+    events: &[StreamEvent],
+    event_message: &EventMessage,
+    chunk: &[Value],
+) -> Vec<Value> {
+    let stream_event = find_stream_event(events, event_message);
+
+    let filtered_chunk: Vec<Value> = chunk
+        .iter()
+        .filter(|event_data| {
+            [stream_conditions, &stream_event.conditions]
+                .into_iter()
+                .flatten()
+                .all(|conditions| filter_event_data_by_conditions(event_data, conditions))
+        })
+        .cloned()
+        .collect();
+
+    filtered_chunk
+}
+
+#[cfg(feature = "sns")]
+impl SNSStream {
+    fn stream_tasks(
         &self,
         config: &SNSStreamTopicConfig,
-        client: Arc<SNS>,
-        id: &str,
-        event_message: &EventMessage,
-        chunks: Arc<Vec<Vec<Value>>>,
+        batch: &StreamBatch<'_>,
     ) -> StreamPublishes {
-        let tasks: Vec<_> = chunks
+        let tasks: Vec<_> = batch
+            .chunks
             .iter()
             .enumerate()
             .map(|(index, chunk)| {
-                let filtered_chunk: Vec<Value> = self.filter_chunk_event_data_by_conditions(
+                let filtered_chunk: Vec<Value> = filter_chunk_event_data_by_conditions(
+                    &config.conditions,
                     &config.events,
-                    event_message,
+                    batch.event_message,
                     chunk,
                 );
 
                 let publish_message_id =
-                    self.generate_publish_message_id(id, index, &config.prefix_id);
-                let client = Arc::clone(&client);
+                    generate_publish_message_id(batch, index, &config.prefix_id);
+                let client = Arc::clone(&self.client);
                 let topic_arn = config.topic_arn.clone();
-                let publish_message = self.create_chunk_message_raw(event_message, &filtered_chunk);
-                task::spawn(async move {
+                let publish_message =
+                    create_chunk_message_raw(&config.events, batch.event_message, &filtered_chunk);
+                spawn_publish(&self.publish_permits, index, async move {
+                    if filtered_chunk.is_empty() {
+                        return Ok(0);
+                    }
+
                     let _ =
                         client.publish(&publish_message_id, &topic_arn, &publish_message).await?;
 
@@ -243,32 +332,67 @@ impl StreamsClients {
 
         tasks
     }
+}
+
+#[cfg(feature = "sns")]
+#[async_trait]
+impl StreamSink for SNSStream {
+    fn name(&self) -> &str {
+        "sns"
+    }
+
+    async fn publish_batch(&self, batch: &StreamBatch<'_>) -> Result<usize, StreamError> {
+        let tasks = self
+            .config
+            .iter()
+            .filter(|config| is_streamed(&config.events, &config.networks, batch.event_message))
+            .flat_map(|config| self.stream_tasks(config, batch))
+            .collect();
+
+        await_publishes(tasks).await
+    }
+
+    async fn publish_watermark(&self, watermark: &StreamWatermark) -> Result<(), StreamError> {
+        let message = serde_json::to_string(watermark).unwrap();
+        for config in
+            self.config.iter().filter(|config| config.networks.contains(&watermark.network))
+        {
+            self.client.publish(&watermark.id(), &config.topic_arn, &message).await?;
+        }
+
+        Ok(())
+    }
+}
 
-    fn webhook_stream_tasks(
+impl WebhookStream {
+    fn stream_tasks(
         &self,
         config: &WebhookStreamConfig,
-        client: Arc<Webhook>,
-        id: &str,
-        event_message: &EventMessage,
-        chunks: Arc<Vec<Vec<Value>>>,
+        batch: &StreamBatch<'_>,
     ) -> StreamPublishes {
-        let tasks: Vec<_> = chunks
+        let tasks: Vec<_> = batch
+            .chunks
             .iter()
             .enumerate()
             .map(|(index, chunk)| {
-                let filtered_chunk: Vec<Value> = self.filter_chunk_event_data_by_conditions(
+                let filtered_chunk: Vec<Value> = filter_chunk_event_data_by_conditions(
+                    &config.conditions,
                     &config.events,
-                    event_message,
+                    batch.event_message,
                     chunk,
                 );
 
-                let publish_message_id = self.generate_publish_message_id(id, index, &None);
+                let publish_message_id = generate_publish_message_id(batch, index, &None);
                 let endpoint = config.endpoint.clone();
                 let shared_secret = config.shared_secret.clone();
-                let client = Arc::clone(&client);
+                let client = Arc::clone(&self.client);
                 let publish_message =
-                    self.create_chunk_message_json(event_message, &filtered_chunk);
-                task::spawn(async move {
+                    create_chunk_message_json(&config.events, batch.event_message, &filtered_chunk);
+                spawn_publish(&self.publish_permits, index, async move {
+                    if filtered_chunk.is_empty() {
+                        return Ok(0);
+                    }
+
                     client
                         .publish(&publish_message_id, &endpoint, &shared_secret, &publish_message)
                         .await?;
@@ -280,34 +404,71 @@ impl StreamsClients {
 
         tasks
     }
+}
+
+#[async_trait]
+impl StreamSink for WebhookStream {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn publish_batch(&self, batch: &StreamBatch<'_>) -> Result<usize, StreamError> {
+        let tasks = self
+            .config
+            .iter()
+            .filter(|config| is_streamed(&config.events, &config.networks, batch.event_message))
+            .flat_map(|config| self.stream_tasks(config, batch))
+            .collect();
+
+        await_publishes(tasks).await
+    }
+
+    async fn publish_watermark(&self, watermark: &StreamWatermark) -> Result<(), StreamError> {
+        let message = serde_json::to_value(watermark).unwrap();
+        for config in
+            self.config.iter().filter(|config| config.networks.contains(&watermark.network))
+        {
+            self.client
+                .publish(&watermark.id(), &config.endpoint, &config.shared_secret, &message)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
 
-    fn rabbitmq_stream_tasks(
+#[cfg(feature = "rabbitmq")]
+impl RabbitMQStream {
+    fn stream_tasks(
         &self,
         config: &RabbitMQStreamQueueConfig,
-        client: Arc<RabbitMQ>,
-        id: &str,
-        event_message: &EventMessage,
-        chunks: Arc<Vec<Vec<Value>>>,
+        batch: &StreamBatch<'_>,
     ) -> StreamPublishes {
-        let tasks: Vec<_> = chunks
+        let tasks: Vec<_> = batch
+            .chunks
             .iter()
             .enumerate()
             .map(|(index, chunk)| {
-                let filtered_chunk: Vec<Value> = self.filter_chunk_event_data_by_conditions(
+                let filtered_chunk: Vec<Value> = filter_chunk_event_data_by_conditions(
+                    &config.conditions,
                     &config.events,
-                    event_message,
+                    batch.event_message,
                     chunk,
                 );
 
-                let publish_message_id = self.generate_publish_message_id(id, index, &None);
-                let client = Arc::clone(&client);
+                let publish_message_id = generate_publish_message_id(batch, index, &None);
+                let client = Arc::clone(&self.client);
                 let exchange = config.exchange.clone();
                 let exchange_type = config.exchange_type.clone();
                 let routing_key = config.routing_key.clone();
                 let publish_message =
-                    self.create_chunk_message_json(event_message, &filtered_chunk);
+                    create_chunk_message_json(&config.events, batch.event_message, &filtered_chunk);
+
+                spawn_publish(&self.publish_permits, index, async move {
+                    if filtered_chunk.is_empty() {
+                        return Ok(0);
+                    }
 
-                task::spawn(async move {
                     client
                         .publish(
                             &publish_message_id,
@@ -323,34 +484,91 @@ impl StreamsClients {
             .collect();
         tasks
     }
+}
+
+#[cfg(feature = "rabbitmq")]
+#[async_trait]
+impl StreamSink for RabbitMQStream {
+    fn name(&self) -> &str {
+        "rabbitmq"
+    }
+
+    async fn publish_batch(&self, batch: &StreamBatch<'_>) -> Result<usize, StreamError> {
+        let tasks = self
+            .config
+            .exchanges
+            .iter()
+            .filter(|config| is_streamed(&config.events, &config.networks, batch.event_message))
+            .flat_map(|config| self.stream_tasks(config, batch))
+            .collect();
+
+        await_publishes(tasks).await
+    }
 
-    fn kafka_stream_tasks(
+    async fn publish_watermark(&self, watermark: &StreamWatermark) -> Result<(), StreamError> {
+        let message = serde_json::to_value(watermark).unwrap();
+        for config in self
+            .config
+            .exchanges
+            .iter()
+            .filter(|config| config.networks.contains(&watermark.network))
+        {
+            self.client
+                .publish(
+                    &watermark.id(),
+                    &config.exchange,
+                    &config.exchange_type,
+                    &config.routing_key,
+                    &message,
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn health(&self) -> Result<(), StreamError> {
+        Ok(self.client.health().await?)
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaStream {
+    fn stream_tasks(
         &self,
         config: &KafkaStreamQueueConfig,
-        client: Arc<Kafka>,
-        id: &str,
-        event_message: &EventMessage,
-        chunks: Arc<Vec<Vec<Value>>>,
+        batch: &StreamBatch<'_>,
     ) -> StreamPublishes {
-        let tasks: Vec<_> = chunks
+        let tasks: Vec<_> = batch
+            .chunks
             .iter()
             .enumerate()
             .map(|(index, chunk)| {
-                let filtered_chunk: Vec<Value> = self.filter_chunk_event_data_by_conditions(
+                let filtered_chunk: Vec<Value> = filter_chunk_event_data_by_conditions(
+                    &config.conditions,
                     &config.events,
-                    event_message,
+                    batch.event_message,
                     chunk,
                 );
 
-                let publish_message_id = self.generate_publish_message_id(id, index, &None);
-                let client = Arc::clone(&client);
+                let publish_message_id = generate_publish_message_id(batch, index, &None);
+                let client = Arc::clone(&self.client);
                 let exchange = config.topic.clone();
                 let routing_key = config.key.clone();
+                let serialization = config.serialization.unwrap_or_default();
+                let schema = self.schema.clone();
                 let publish_message =
-                    self.create_chunk_message_json(event_message, &filtered_chunk);
-                task::spawn(async move {
+                    create_chunk_message_json(&config.events, batch.event_message, &filtered_chunk);
+                spawn_publish(&self.publish_permits, index, async move {
+                    if filtered_chunk.is_empty() {
+                        return Ok(0);
+                    }
+
+                    let message_body = client
+                        .encode(&exchange, serialization, schema.as_deref(), &publish_message)
+                        .await?;
                     client
-                        .publish(&publish_message_id, &exchange, &routing_key, &publish_message)
+                        .publish(&publish_message_id, &exchange, &routing_key, &message_body)
                         .await?;
                     Ok(filtered_chunk.len())
                 })
@@ -358,6 +576,142 @@ impl StreamsClients {
             .collect();
         tasks
     }
+}
+
+#[cfg(feature = "kafka")]
+#[async_trait]
+impl StreamSink for KafkaStream {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    async fn publish_batch(&self, batch: &StreamBatch<'_>) -> Result<usize, StreamError> {
+        let tasks = self
+            .config
+            .topics
+            .iter()
+            .filter(|config| is_streamed(&config.events, &config.networks, batch.event_message))
+            .flat_map(|config| self.stream_tasks(config, batch))
+            .collect();
+
+        await_publishes(tasks).await
+    }
+
+    /// Avro and protobuf topics only take messages matching the event schema so they get none
+    async fn publish_watermark(&self, watermark: &StreamWatermark) -> Result<(), StreamError> {
+        let message_body = serde_json::to_vec(watermark).unwrap();
+        for config in self.config.topics.iter().filter(|config| {
+            config.networks.contains(&watermark.network) &&
+                config.serialization.unwrap_or_default() == StreamSerialization::Json
+        }) {
+            self.client.publish(&watermark.id(), &config.topic, &config.key, &message_body).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), StreamError> {
+        Ok(self.client.flush().await?)
+    }
+}
+
+/// The sinks an event is streamed to, the ones configured in the manifest followed by any
+/// registered with `register_stream_sink`
+pub struct StreamsClients {
+    sinks: Vec<Arc<dyn StreamSink>>,
+}
+
+impl StreamsClients {
+    #[cfg_attr(not(feature = "kafka"), allow(unused_variables))]
+    pub async fn new(
+        stream_config: StreamsConfig,
+        event_name: &str,
+        inputs: &[ABIInput],
+        max_concurrent_publishes: Option<usize>,
+    ) -> Self {
+        let publish_permits = max_concurrent_publishes.map(|max| Arc::new(Semaphore::new(max)));
+        let mut sinks: Vec<Arc<dyn StreamSink>> = vec![];
+
+        #[cfg(feature = "sns")]
+        if let Some(config) = &stream_config.sns {
+            sinks.push(Arc::new(SNSStream {
+                config: config.topics.clone(),
+                client: Arc::new(SNS::new(&config.aws_config).await),
+                publish_permits: publish_permits.clone(),
+            }));
+        }
+
+        if let Some(config) = stream_config.webhooks.as_ref() {
+            sinks.push(Arc::new(WebhookStream {
+                config: config.clone(),
+                client: Arc::new(Webhook::new()),
+                publish_permits: publish_permits.clone(),
+            }));
+        }
+
+        #[cfg(feature = "rabbitmq")]
+        if let Some(config) = stream_config.rabbitmq.as_ref() {
+            sinks.push(Arc::new(RabbitMQStream {
+                config: config.clone(),
+                client: Arc::new(RabbitMQ::new(&config.url).await),
+                publish_permits: publish_permits.clone(),
+            }));
+        }
+
+        #[cfg(feature = "kafka")]
+        if let Some(config) = stream_config.kafka.as_ref() {
+            sinks.push(Arc::new(KafkaStream {
+                config: config.clone(),
+                client: Arc::new(
+                    Kafka::new(config)
+                        .await
+                        .unwrap_or_else(|e| panic!("Failed to create Kafka client: {:?}", e)),
+                ),
+                schema: config
+                    .topics
+                    .iter()
+                    .any(|t| t.serialization.unwrap_or_default() != StreamSerialization::Json)
+                    .then(|| Arc::new(StreamSchema::new(event_name, inputs))),
+                publish_permits: publish_permits.clone(),
+            }));
+        }
+
+        sinks.extend(registered_stream_sinks());
+
+        Self { sinks }
+    }
+
+    /// Only the sinks registered with `register_stream_sink`, for events without streams in
+    /// the manifest
+    pub fn registered() -> Option<Self> {
+        Self::from_sinks(registered_stream_sinks())
+    }
+
+    pub(crate) fn from_sinks(sinks: Vec<Arc<dyn StreamSink>>) -> Option<Self> {
+        (!sinks.is_empty()).then_some(Self { sinks })
+    }
+
+    /// Spills whatever a sink can not take to disk under the project instead of failing the event
+    pub fn with_spill(
+        self,
+        project_path: &Path,
+        spill: &StreamSpillConfig,
+        contract_name: &str,
+        event_name: &str,
+    ) -> Result<Self, std::io::Error> {
+        let root = project_path.join(spill.path());
+        let sinks = self
+            .sinks
+            .into_iter()
+            .map(|sink| {
+                let dir = spill_dir(&root, contract_name, event_name, sink.name());
+                SpillingStreamSink::start(sink, dir, spill.max_size_bytes())
+                    .map(|sink| sink as Arc<dyn StreamSink>)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { sinks })
+    }
 
     pub async fn stream(
         &self,
@@ -365,104 +719,26 @@ impl StreamsClients {
         event_message: &EventMessage,
         index_event_in_order: bool,
     ) -> Result<usize, StreamError> {
-        if !self.has_any_streams() {
+        if self.sinks.is_empty() {
             return Ok(0);
         }
 
         // will always have something even if the event has no parameters due to the tx_information
         if let Value::Array(data_array) = &event_message.event_data {
-            let chunks = Arc::new(self.chunk_data(data_array));
-            let mut streams: Vec<StreamPublishes> = Vec::new();
-
-            if let Some(sns) = &self.sns {
-                for config in &sns.config {
-                    if config.events.iter().any(|e| e.event_name == event_message.event_name) &&
-                        config.networks.contains(&event_message.network)
-                    {
-                        streams.push(self.sns_stream_tasks(
-                            config,
-                            Arc::clone(&sns.client),
-                            &id,
-                            event_message,
-                            Arc::clone(&chunks),
-                        ));
-                    }
-                }
-            };
-
-            if let Some(webhook) = &self.webhook {
-                for config in &webhook.config {
-                    if config.events.iter().any(|e| e.event_name == event_message.event_name) &&
-                        config.networks.contains(&event_message.network)
-                    {
-                        streams.push(self.webhook_stream_tasks(
-                            config,
-                            Arc::clone(&webhook.client),
-                            &id,
-                            event_message,
-                            Arc::clone(&chunks),
-                        ));
-                    }
-                }
-            }
-
-            if let Some(rabbitmq) = &self.rabbitmq {
-                for config in &rabbitmq.config.exchanges {
-                    if config.events.iter().any(|e| e.event_name == event_message.event_name) &&
-                        config.networks.contains(&event_message.network)
-                    {
-                        streams.push(self.rabbitmq_stream_tasks(
-                            config,
-                            Arc::clone(&rabbitmq.client),
-                            &id,
-                            event_message,
-                            Arc::clone(&chunks),
-                        ));
-                    }
-                }
-            }
-
-            if let Some(kafka) = &self.kafka {
-                for config in &kafka.config.topics {
-                    if config.events.iter().any(|e| e.event_name == event_message.event_name) &&
-                        config.networks.contains(&event_message.network)
-                    {
-                        streams.push(self.kafka_stream_tasks(
-                            config,
-                            Arc::clone(&kafka.client),
-                            &id,
-                            event_message,
-                            Arc::clone(&chunks),
-                        ));
-                    }
-                }
-            }
+            let batch =
+                StreamBatch { id: &id, event_message, chunks: Arc::new(chunk_data(data_array)) };
 
             let mut streamed_total = 0;
 
             if index_event_in_order {
-                for stream in streams {
-                    for task in stream {
-                        match task.await {
-                            Ok(Ok(streamed)) => {
-                                streamed_total += streamed;
-                            }
-                            Ok(Err(e)) => return Err(e),
-                            Err(e) => return Err(StreamError::JoinError(e)),
-                        }
-                    }
+                for sink in &self.sinks {
+                    streamed_total += sink.publish_batch(&batch).await?;
                 }
             } else {
-                let tasks: Vec<_> = streams.into_iter().flatten().collect();
-                let results = join_all(tasks).await;
+                let results =
+                    join_all(self.sinks.iter().map(|sink| sink.publish_batch(&batch))).await;
                 for result in results {
-                    match result {
-                        Ok(Ok(streamed)) => {
-                            streamed_total += streamed;
-                        }
-                        Ok(Err(e)) => return Err(e),
-                        Err(e) => return Err(StreamError::JoinError(e)),
-                    }
+                    streamed_total += result?;
                 }
             }
 
@@ -471,4 +747,90 @@ impl StreamsClients {
             unreachable!("Event data should be an array");
         }
     }
+
+    pub async fn publish_watermark(&self, watermark: &StreamWatermark) -> Result<(), StreamError> {
+        for sink in &self.sinks {
+            sink.publish_watermark(watermark).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn flush(&self) -> Result<(), StreamError> {
+        for sink in &self.sinks {
+            sink.flush().await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn event(block_number: u64, log_index: u64) -> Value {
+        json!({
+            "value": "1",
+            "transaction_information": {
+                "block_number": format!("{:#x}", block_number),
+                "transaction_hash": format!("0x{:064x}", block_number),
+                "log_index": format!("{:#x}", log_index),
+            }
+        })
+    }
+
+    fn message_ids(events: Vec<Value>) -> Vec<String> {
+        let event_message = EventMessage {
+            event_name: "Transfer".to_string(),
+            event_data: Value::Array(events.clone()),
+            network: "ethereum".to_string(),
+            removed: false,
+        };
+        let batch = StreamBatch {
+            id: "Erc20-Transfer-ethereum",
+            event_message: &event_message,
+            chunks: Arc::new(chunk_data(&events)),
+        };
+        (0..batch.chunks.len())
+            .map(|index| generate_publish_message_id(&batch, index, &None))
+            .collect()
+    }
+
+    #[test]
+    fn test_message_ids_do_not_depend_on_the_block_range() {
+        let live_range = message_ids(vec![event(10, 0), event(10, 1), event(11, 0)]);
+        assert_eq!(live_range.len(), 2);
+
+        let mut replayed = message_ids(vec![event(10, 0), event(10, 1)]);
+        replayed.extend(message_ids(vec![event(11, 0)]));
+        assert_eq!(live_range, replayed);
+        assert_eq!(
+            live_range[1],
+            format!("rindexer_stream__-erc20-transfer-ethereum-0x{:064x}-0x0", 11)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_await_publishes_returns_the_failed_chunks() {
+        let tasks = (0..4)
+            .map(|index| {
+                spawn_publish(&None, index % 3, async move {
+                    match index {
+                        1 => Err(StreamError::Spill("down".to_string())),
+                        _ => Ok(1),
+                    }
+                })
+            })
+            .collect();
+
+        match await_publishes(tasks).await {
+            Err(StreamError::ChunksNotPublished { failed_chunks, .. }) => {
+                assert_eq!(failed_chunks, vec![1]);
+            }
+            other => panic!("expected the failed chunks, got {:?}", other),
+        }
+    }
 }
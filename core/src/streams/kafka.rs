@@ -1,16 +1,21 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 #[cfg(not(windows))]
 use rdkafka::{
     config::ClientConfig,
     message::{Header, OwnedHeaders},
-    producer::{FutureProducer, FutureRecord},
+    producer::{FutureProducer, FutureRecord, Producer},
     util::Timeout,
 };
-use serde_json::Value;
+use reqwest::{header::CONTENT_TYPE, Client};
+use serde_json::{json, Value};
 use thiserror::Error;
+use tokio::sync::Mutex;
 
-use crate::{manifest::stream::KafkaStreamConfig, streams::STREAM_MESSAGE_ID_KEY};
+use crate::{
+    manifest::stream::{KafkaStreamConfig, SchemaRegistryConfig, StreamSerialization},
+    streams::{schema::StreamSchema, STREAM_MESSAGE_ID_KEY},
+};
 
 #[derive(Error, Debug)]
 pub enum KafkaError {
@@ -19,12 +24,75 @@ pub enum KafkaError {
 
     #[error("Could not parse message: {0}")]
     CouldNotParseMessage(#[from] serde_json::Error),
+
+    #[error("Schema registry error: {0}")]
+    SchemaRegistryError(String),
+
+    #[error("{0:?} serialization needs a schema registry and event schema")]
+    SchemaRegistryNotConfigured(StreamSerialization),
+}
+
+/// Registers schemas with a Confluent compatible schema registry, the ids are cached per subject
+pub struct SchemaRegistry {
+    config: SchemaRegistryConfig,
+    client: Client,
+    ids: Mutex<HashMap<String, u32>>,
+}
+
+impl SchemaRegistry {
+    pub fn new(config: SchemaRegistryConfig) -> Self {
+        Self { config, client: Client::new(), ids: Mutex::new(HashMap::new()) }
+    }
+
+    pub async fn register(
+        &self,
+        subject: &str,
+        serialization: StreamSerialization,
+        schema: String,
+    ) -> Result<u32, KafkaError> {
+        if let Some(id) = self.ids.lock().await.get(subject) {
+            return Ok(*id);
+        }
+
+        let mut request = self
+            .client
+            .post(format!(
+                "{}/subjects/{}/versions",
+                self.config.url.trim_end_matches('/'),
+                subject
+            ))
+            .header(CONTENT_TYPE, "application/vnd.schemaregistry.v1+json")
+            .json(&json!({ "schema": schema, "schemaType": serialization.schema_type() }));
+        if let Some(username) = &self.config.username {
+            request = request.basic_auth(username, self.config.password.as_ref());
+        }
+
+        let response =
+            request.send().await.map_err(|e| KafkaError::SchemaRegistryError(e.to_string()))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(KafkaError::SchemaRegistryError(format!(
+                "registering {} failed with {}: {}",
+                subject, status, body
+            )));
+        }
+
+        let body: Value =
+            response.json().await.map_err(|e| KafkaError::SchemaRegistryError(e.to_string()))?;
+        let id = body.get("id").and_then(Value::as_u64).ok_or_else(|| {
+            KafkaError::SchemaRegistryError(format!("no schema id returned for {}", subject))
+        })? as u32;
+
+        self.ids.lock().await.insert(subject.to_string(), id);
+        Ok(id)
+    }
 }
 
-#[derive(Clone)]
 pub struct Kafka {
     #[cfg(not(windows))]
     producer: FutureProducer,
+    schema_registry: Option<SchemaRegistry>,
 }
 
 impl Kafka {
@@ -52,7 +120,10 @@ impl Kafka {
             let producer: FutureProducer =
                 client_config.create().map_err(|e| KafkaError::RdkafkaError(e.to_string()))?;
 
-            Ok(Self { producer })
+            Ok(Self {
+                producer,
+                schema_registry: config.schema_registry.clone().map(SchemaRegistry::new),
+            })
         }
 
         #[cfg(windows)]
@@ -61,24 +132,44 @@ impl Kafka {
         }
     }
 
+    pub async fn encode(
+        &self,
+        topic: &str,
+        serialization: StreamSerialization,
+        schema: Option<&StreamSchema>,
+        message: &Value,
+    ) -> Result<Vec<u8>, KafkaError> {
+        if serialization == StreamSerialization::Json {
+            return Ok(serde_json::to_vec(message)?);
+        }
+
+        let (Some(schema_registry), Some(schema)) = (&self.schema_registry, schema) else {
+            return Err(KafkaError::SchemaRegistryNotConfigured(serialization));
+        };
+
+        let schema_id = schema_registry
+            .register(&schema.subject(topic), serialization, schema.schema(serialization))
+            .await?;
+
+        Ok(schema.encode(serialization, schema_id, message))
+    }
+
     pub async fn publish(
         &self,
         id: &str,
         topic: &str,
         key: &Option<String>,
-        message: &Value,
+        message_body: &[u8],
     ) -> Result<(), KafkaError> {
         #[cfg(not(windows))]
         {
-            let message_body = serde_json::to_vec(message)?;
-
             let record = if key.is_some() {
-                FutureRecord::to(topic).key(key.as_ref().unwrap()).payload(&message_body).headers(
+                FutureRecord::to(topic).key(key.as_ref().unwrap()).payload(message_body).headers(
                     OwnedHeaders::new()
                         .insert(Header { key: STREAM_MESSAGE_ID_KEY, value: Some(id) }),
                 )
             } else {
-                FutureRecord::to(topic).payload(&message_body).headers(
+                FutureRecord::to(topic).payload(message_body).headers(
                     OwnedHeaders::new()
                         .insert(Header { key: STREAM_MESSAGE_ID_KEY, value: Some(id) }),
                 )
@@ -97,4 +188,21 @@ impl Kafka {
             panic!("Kafka is not supported on Windows")
         }
     }
+
+    /// Waits for the messages still queued in the producer to be delivered
+    pub async fn flush(&self) -> Result<(), KafkaError> {
+        #[cfg(not(windows))]
+        {
+            let producer = self.producer.clone();
+            tokio::task::spawn_blocking(move || producer.flush(Duration::from_secs(30)))
+                .await
+                .map_err(|e| KafkaError::RdkafkaError(e.to_string()))?
+                .map_err(|e| KafkaError::RdkafkaError(e.to_string()))
+        }
+
+        #[cfg(windows)]
+        {
+            panic!("Kafka is not supported on Windows")
+        }
+    }
 }
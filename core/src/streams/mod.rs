@@ -1,15 +1,39 @@
+#[cfg(feature = "sns")]
 mod sns;
+#[cfg(feature = "sns")]
 pub use sns::SNS;
 
 mod webhook;
 pub use webhook::{Webhook, WebhookError};
 
+#[cfg(feature = "rabbitmq")]
 mod rabbitmq;
+#[cfg(feature = "rabbitmq")]
 pub use rabbitmq::{RabbitMQ, RabbitMQError};
 
+#[cfg(feature = "kafka")]
 mod kafka;
+#[cfg(feature = "kafka")]
+mod schema;
 
 mod clients;
-pub use clients::StreamsClients;
+pub use clients::{StreamError, StreamsClients};
+
+mod sink;
+pub(crate) use sink::stream_registry_events;
+pub use sink::{
+    check_registered_stream_sinks, flush_registered_stream_sinks, register_stream_sink,
+    registered_stream_sinks, StreamBatch, StreamSink,
+};
+
+mod spill;
+pub(crate) use spill::has_spilled_batches;
+
+mod watermark;
+pub use watermark::StreamWatermark;
+pub(crate) use watermark::{contract_streams_clients, published_blocks};
+
+mod replay;
+pub use replay::{replay_streams, EventRowsReader, ReplayStreamsError, ReplayStreamsOptions};
 
 pub const STREAM_MESSAGE_ID_KEY: &str = "x-rindexer-id";
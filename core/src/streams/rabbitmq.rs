@@ -69,4 +69,19 @@ impl RabbitMQ {
 
         Ok(())
     }
+
+    /// Checks a connection can be taken from the pool
+    pub async fn health(&self) -> Result<(), RabbitMQError> {
+        let _connection = self.pool.get().await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_exchange(&self, exchange: &str) -> Result<(), RabbitMQError> {
+        let conn = self.pool.get().await?;
+        let channel = conn.create_channel().await?;
+        channel.exchange_delete(exchange, ExchangeDeleteOptions::default()).await?;
+
+        Ok(())
+    }
 }
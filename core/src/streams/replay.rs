@@ -0,0 +1,389 @@
+use std::{path::Path, str::FromStr};
+
+use ethers::types::{Address, H256, U256, U64};
+use serde_json::{json, Map, Value};
+use tokio_postgres::Row;
+use tracing::info;
+
+use crate::{
+    abi::{ABIInput, ABIItem, EventInfo, ParamTypeError, ReadAbiError},
+    database::postgres::{
+        client::{PostgresConnectionError, PostgresError},
+        generate::generate_event_table_full_name_for_network,
+    },
+    event::{callback_registry::TxInformation, EventMessage},
+    helpers::camel_to_snake,
+    manifest::{
+        contract::Contract, core::Manifest, storage::PostgresLayout,
+        transform::transforms_for_event,
+    },
+    streams::{StreamError, StreamsClients},
+    PostgresClient,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ReplayStreamsError {
+    #[error("Postgres storage is not enabled - replaying reads the indexed rows from postgres")]
+    PostgresNotEnabled,
+
+    #[error("Could not connect to postgres: {0}")]
+    PostgresConnectionError(#[from] PostgresConnectionError),
+
+    #[error("Could not read ABI items: {0}")]
+    ReadAbiError(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
+
+    #[error("Could not read rows from {0}: {1}")]
+    QueryError(String, PostgresError),
+
+    #[error("Could not stream: {0}")]
+    StreamError(Box<StreamError>),
+}
+
+pub struct ReplayStreamsOptions {
+    pub from_block: u64,
+    pub to_block: u64,
+    pub contract_name: Option<String>,
+    pub network: Option<String>,
+}
+
+enum ReplayValue {
+    Column(usize, String),
+    Jsonb(usize),
+    Tuple(Vec<(String, ReplayValue)>),
+}
+
+/// Mirrors how event inputs are flattened into columns so rows can be put back into the shape
+/// the indexer streams
+fn replay_values(
+    inputs: &[ABIInput],
+    prefix: Option<&str>,
+    jsonb_inputs: &[String],
+    transformed_inputs: &[&str],
+    select_columns: &mut Vec<String>,
+) -> Vec<(String, ReplayValue)> {
+    inputs
+        .iter()
+        .map(|input| {
+            if input.is_hashed_topic() {
+                select_columns
+                    .push(format!("\"{}\"::TEXT", camel_to_snake(&input.hashed_topic_name())));
+                return (
+                    input.hashed_topic_name(),
+                    ReplayValue::Column(select_columns.len() - 1, "bytes32".to_string()),
+                );
+            }
+
+            let value = if prefix.is_none() && jsonb_inputs.contains(&input.name) {
+                select_columns.push(format!("\"{}\"::TEXT", camel_to_snake(&input.name)));
+                ReplayValue::Jsonb(select_columns.len() - 1)
+            } else if prefix.is_none() && transformed_inputs.contains(&input.name.as_str()) {
+                select_columns.push(format!("\"{}\"::TEXT", camel_to_snake(&input.name)));
+                ReplayValue::Column(select_columns.len() - 1, "string".to_string())
+            } else if let Some(components) = &input.components {
+                ReplayValue::Tuple(replay_values(
+                    components,
+                    Some(&camel_to_snake(&input.name)),
+                    jsonb_inputs,
+                    transformed_inputs,
+                    select_columns,
+                ))
+            } else {
+                let column = format!(
+                    "{}{}",
+                    prefix.map_or_else(|| "".to_string(), |p| format!("{}_", p)),
+                    camel_to_snake(&input.name)
+                );
+                if input.type_.ends_with(']') {
+                    select_columns.push(format!("to_jsonb(\"{}\"::TEXT[])::TEXT", column));
+                } else {
+                    select_columns.push(format!("\"{}\"::TEXT", column));
+                }
+                ReplayValue::Column(select_columns.len() - 1, input.type_.clone())
+            };
+
+            (input.name.clone(), value)
+        })
+        .collect()
+}
+
+/// Columns only hold text so values are converted back to how the indexer serializes them
+fn column_json(abi_type: &str, text: &str) -> Value {
+    if let Some((element, _)) = abi_type.strip_suffix(']').and_then(|t| t.rsplit_once('[')) {
+        return match serde_json::from_str::<Vec<Option<String>>>(text) {
+            Ok(values) => Value::Array(
+                values
+                    .iter()
+                    .map(|value| value.as_deref().map_or(Value::Null, |v| column_json(element, v)))
+                    .collect(),
+            ),
+            Err(_) => Value::String(text.to_string()),
+        };
+    }
+
+    match abi_type {
+        "bool" => Value::Bool(text == "true" || text == "t"),
+        t if t.starts_with("bytes") => {
+            Value::String(text.trim_start_matches("\\x").trim_start_matches("0x").to_string())
+        }
+        t if t.starts_with("int") || t.starts_with("uint") => {
+            let size: usize =
+                t.trim_start_matches('u').trim_start_matches("int").parse().unwrap_or(256);
+            match (size, text.parse::<u64>()) {
+                (size, Ok(value)) if size <= 32 => json!(value),
+                (64, Ok(value)) => json!(U64::from(value)),
+                _ => Value::String(text.to_string()),
+            }
+        }
+        _ => Value::String(text.to_string()),
+    }
+}
+
+fn row_json(row: &Row, values: &[(String, ReplayValue)]) -> Map<String, Value> {
+    values
+        .iter()
+        .map(|(key, value)| {
+            let json = match value {
+                ReplayValue::Column(index, abi_type) => row
+                    .get::<_, Option<String>>(*index)
+                    .map_or(Value::Null, |text| column_json(abi_type, &text)),
+                ReplayValue::Jsonb(index) => row
+                    .get::<_, Option<String>>(*index)
+                    .and_then(|text| serde_json::from_str(&text).ok())
+                    .unwrap_or(Value::Null),
+                ReplayValue::Tuple(values) => Value::Object(row_json(row, values)),
+            };
+            (key.clone(), json)
+        })
+        .collect()
+}
+
+fn row_tx_information(row: &Row, base_index: usize) -> TxInformation {
+    let text =
+        |offset: usize| row.get::<_, Option<String>>(base_index + offset).unwrap_or_default();
+    TxInformation {
+        address: Address::from_str(&text(0)).unwrap_or_default(),
+        transaction_hash: H256::from_str(&text(1)).unwrap_or_default(),
+        block_number: U64::from_dec_str(&text(2)).unwrap_or_default(),
+        block_hash: H256::from_str(&text(3)).unwrap_or_default(),
+        network: text(4),
+        transaction_index: U64::from_dec_str(&text(5)).unwrap_or_default(),
+        log_index: U256::from_dec_str(&text(6)).unwrap_or_default(),
+        removed: false,
+        after_code_removed: false,
+    }
+}
+
+/// Reads the indexed rows of an event back into the json the indexer streams
+pub struct EventRowsReader {
+    values: Vec<(String, ReplayValue)>,
+    select_columns: Vec<String>,
+    base_index: usize,
+    layout: PostgresLayout,
+    compact_bytea: bool,
+    indexer_name: String,
+    contract_name: String,
+    event_name: String,
+}
+
+impl EventRowsReader {
+    pub fn new(manifest: &Manifest, contract: &Contract, event_info: &EventInfo) -> Self {
+        let jsonb_inputs = contract.jsonb_inputs(&event_info.name, &event_info.inputs);
+        let transforms = transforms_for_event(&contract.transforms, &event_info.name);
+        let transformed_inputs: Vec<&str> =
+            transforms.iter().map(|t| t.input_name.as_str()).collect();
+
+        let mut select_columns = Vec::new();
+        let values = replay_values(
+            &event_info.inputs,
+            None,
+            &jsonb_inputs,
+            &transformed_inputs,
+            &mut select_columns,
+        );
+        let base_index = select_columns.len();
+        select_columns.extend(
+            [
+                "contract_address",
+                "tx_hash",
+                "block_number",
+                "block_hash",
+                "network",
+                "tx_index",
+                "log_index",
+            ]
+            .iter()
+            .map(|column| format!("\"{}\"::TEXT", column)),
+        );
+
+        Self {
+            values,
+            select_columns,
+            base_index,
+            layout: manifest.storage.postgres_layout(),
+            compact_bytea: manifest.storage.postgres_compact_bytea(),
+            indexer_name: manifest.name.clone(),
+            contract_name: contract.name.clone(),
+            event_name: event_info.name.clone(),
+        }
+    }
+
+    pub fn table_name(&self, network: &str) -> String {
+        let mut table_name = generate_event_table_full_name_for_network(
+            self.layout,
+            &self.indexer_name,
+            &self.contract_name,
+            &self.event_name,
+            network,
+        );
+        if self.compact_bytea {
+            table_name.push_str("_hex");
+        }
+        table_name
+    }
+
+    fn event_rows(&self, rows: &[Row]) -> Vec<(TxInformation, Value)> {
+        rows.iter()
+            .map(|row| {
+                let tx_information = row_tx_information(row, self.base_index);
+                let mut event_data = row_json(row, &self.values);
+                event_data.insert("transaction_information".to_string(), json!(tx_information));
+                (tx_information, Value::Object(event_data))
+            })
+            .collect()
+    }
+
+    /// Rows in the order they were emitted, `to_block` is open ended when `None`
+    pub async fn read(
+        &self,
+        postgres: &PostgresClient,
+        network: &str,
+        from_block: u64,
+        to_block: Option<u64>,
+    ) -> Result<Vec<(TxInformation, Value)>, PostgresError> {
+        let query = format!(
+            "SELECT {} FROM {} WHERE network = $1 AND block_number >= {}{} ORDER BY block_number, tx_index, log_index::NUMERIC",
+            self.select_columns.join(", "),
+            self.table_name(network),
+            from_block,
+            to_block.map_or_else(String::new, |to_block| format!(" AND block_number <= {}", to_block))
+        );
+        let rows = postgres.query(&query, &[&network]).await?;
+
+        Ok(self.event_rows(&rows))
+    }
+
+    /// Up to `limit` rows from `from_block` after the `(block_number, log_index)` position in the
+    /// order they were emitted, an empty page means there is nothing more to read
+    pub async fn read_page(
+        &self,
+        postgres: &PostgresClient,
+        network: &str,
+        from_block: u64,
+        after: Option<(U64, U256)>,
+        limit: usize,
+    ) -> Result<Vec<(TxInformation, Value)>, PostgresError> {
+        let query = format!(
+            "SELECT {} FROM {} WHERE network = $1 AND block_number >= {}{} ORDER BY block_number, log_index::NUMERIC LIMIT {}",
+            self.select_columns.join(", "),
+            self.table_name(network),
+            from_block,
+            after.map_or_else(String::new, |(block_number, log_index)| format!(
+                " AND (block_number, log_index::NUMERIC) > ({}, {})",
+                block_number, log_index
+            )),
+            limit
+        );
+        let rows = postgres.query(&query, &[&network]).await?;
+
+        Ok(self.event_rows(&rows))
+    }
+}
+
+/// Re-publishes indexed rows through the contract streams, every message is keyed on the first
+/// log in it the same way live indexing keys it
+pub async fn replay_streams(
+    project_path: &Path,
+    manifest: &Manifest,
+    options: &ReplayStreamsOptions,
+) -> Result<usize, ReplayStreamsError> {
+    if !manifest.storage.postgres_enabled() {
+        return Err(ReplayStreamsError::PostgresNotEnabled);
+    }
+
+    let postgres = PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+    let mut streamed_total = 0;
+
+    for contract in &manifest.contracts {
+        let Some(streams) = &contract.streams else {
+            continue;
+        };
+        if options.contract_name.as_ref().is_some_and(|name| name != &contract.name) {
+            continue;
+        }
+
+        let mut contract = contract.clone();
+        let is_filter = contract.identify_and_modify_filter();
+        let abi_items = ABIItem::get_abi_items(project_path, &contract, is_filter)?;
+        let event_infos = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
+
+        for event_info in event_infos.iter().filter(|e| streams.has_event(&e.name)) {
+            let reader = EventRowsReader::new(manifest, &contract, event_info);
+            let streams_clients = StreamsClients::new(
+                streams.clone(),
+                &event_info.name,
+                &event_info.inputs,
+                manifest.runtime_settings().streams_concurrency(),
+            )
+            .await;
+
+            let mut networks: Vec<&String> =
+                contract.details.iter().map(|detail| &detail.network).collect();
+            networks.sort();
+            networks.dedup();
+            for network in networks {
+                if options.network.as_ref().is_some_and(|n| n != network) {
+                    continue;
+                }
+
+                let rows = reader
+                    .read(&postgres, network, options.from_block, Some(options.to_block))
+                    .await
+                    .map_err(|e| ReplayStreamsError::QueryError(reader.table_name(network), e))?;
+
+                let stream_id = format!("{}-{}-{}", contract.name, event_info.name, network);
+                let event_message = EventMessage {
+                    event_name: event_info.name.clone(),
+                    event_data: Value::Array(
+                        rows.iter().map(|(_, event_data)| event_data.clone()).collect(),
+                    ),
+                    network: network.clone(),
+                    removed: false,
+                };
+                streamed_total += streams_clients
+                    .stream(stream_id, &event_message, true)
+                    .await
+                    .map_err(|e| ReplayStreamsError::StreamError(Box::new(e)))?;
+
+                info!(
+                    "{}::{} - replayed {} rows on {} - blocks: {} - {}",
+                    contract.name,
+                    event_info.name,
+                    rows.len(),
+                    network,
+                    options.from_block,
+                    options.to_block
+                );
+            }
+
+            streams_clients
+                .flush()
+                .await
+                .map_err(|e| ReplayStreamsError::StreamError(Box::new(e)))?;
+        }
+    }
+
+    Ok(streamed_total)
+}
@@ -0,0 +1,417 @@
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+
+use crate::{abi::ABIInput, manifest::stream::StreamSerialization};
+
+const TX_INFORMATION_FIELDS: [&str; 7] = [
+    "network",
+    "address",
+    "block_hash",
+    "block_number",
+    "transaction_hash",
+    "log_index",
+    "transaction_index",
+];
+
+#[derive(Debug, Clone)]
+enum SchemaType {
+    Boolean,
+    String,
+    Array(Box<SchemaType>),
+    Record(SchemaRecord),
+}
+
+#[derive(Debug, Clone)]
+struct SchemaRecord {
+    name: String,
+    fields: Vec<SchemaField>,
+}
+
+#[derive(Debug, Clone)]
+struct SchemaField {
+    name: String,
+    // the key of the value in the event json which may not be a valid schema name
+    key: String,
+    type_: SchemaType,
+}
+
+fn schema_name(name: &str, index: usize) -> String {
+    let mut sanitized: String =
+        name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    if sanitized.is_empty() {
+        sanitized = format!("param{}", index);
+    }
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+fn schema_type(
+    abi_type: &str,
+    components: &Option<Vec<ABIInput>>,
+    record_name: &str,
+    in_array: bool,
+) -> SchemaType {
+    if let Some((element, _)) = abi_type.strip_suffix(']').and_then(|t| t.rsplit_once('[')) {
+        // protobuf has no nested repeated fields so inner arrays are sent as json strings
+        if in_array {
+            return SchemaType::String;
+        }
+        return SchemaType::Array(Box::new(schema_type(element, components, record_name, true)));
+    }
+
+    match abi_type {
+        "bool" => SchemaType::Boolean,
+        "tuple" => SchemaType::Record(schema_record(
+            record_name,
+            components.as_deref().unwrap_or_default(),
+        )),
+        // numbers can be wider than any schema integer so they stay strings like in the json
+        _ => SchemaType::String,
+    }
+}
+
+fn schema_record(name: &str, inputs: &[ABIInput]) -> SchemaRecord {
+    let fields = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            // hashed topics are sent as the bytes32 hash under their own name, not the value
+            if input.is_hashed_topic() {
+                let key = input.hashed_topic_name();
+                return SchemaField {
+                    name: schema_name(&key, index),
+                    key,
+                    type_: SchemaType::String,
+                };
+            }
+
+            let field_name = schema_name(&input.name, index);
+            SchemaField {
+                type_: schema_type(
+                    &input.type_,
+                    &input.components,
+                    &format!("{}_{}", name, field_name),
+                    false,
+                ),
+                name: field_name,
+                key: input.name.clone(),
+            }
+        })
+        .collect();
+
+    SchemaRecord { name: name.to_string(), fields }
+}
+
+fn string_field(name: &str) -> SchemaField {
+    SchemaField { name: name.to_string(), key: name.to_string(), type_: SchemaType::String }
+}
+
+/// The ABI derived shape of the messages a stream sends for a single event
+#[derive(Debug, Clone)]
+pub struct StreamSchema {
+    root: SchemaRecord,
+}
+
+impl StreamSchema {
+    pub fn new(event_name: &str, inputs: &[ABIInput]) -> Self {
+        let event_record_name = schema_name(event_name, 0);
+        let mut event = schema_record(&event_record_name, inputs);
+        event.fields.push(SchemaField {
+            name: "transaction_information".to_string(),
+            key: "transaction_information".to_string(),
+            type_: SchemaType::Record(SchemaRecord {
+                name: "TransactionInformation".to_string(),
+                fields: TX_INFORMATION_FIELDS.iter().map(|name| string_field(name)).collect(),
+            }),
+        });
+
+        let root = SchemaRecord {
+            name: format!("{}EventMessage", event_record_name),
+            fields: vec![
+                string_field("event_name"),
+                SchemaField {
+                    name: "event_data".to_string(),
+                    key: "event_data".to_string(),
+                    type_: SchemaType::Array(Box::new(SchemaType::Record(event))),
+                },
+                string_field("network"),
+            ],
+        };
+
+        Self { root }
+    }
+
+    /// Subject under the record name strategy so every event can share a topic
+    pub fn subject(&self, topic: &str) -> String {
+        format!("{}-rindexer.{}", topic, self.root.name)
+    }
+
+    pub fn schema(&self, serialization: StreamSerialization) -> String {
+        match serialization {
+            StreamSerialization::Avro => {
+                let mut schema =
+                    avro_type(&SchemaType::Record(self.root.clone()), &mut HashSet::new());
+                schema["namespace"] = json!("rindexer");
+                schema.to_string()
+            }
+            StreamSerialization::Protobuf => {
+                let mut messages = Vec::new();
+                protobuf_messages(&self.root, &mut messages, &mut HashSet::new());
+                format!("syntax = \"proto3\";\npackage rindexer;\n\n{}\n", messages.join("\n\n"))
+            }
+            StreamSerialization::Json => String::new(),
+        }
+    }
+
+    /// Confluent wire format, a zero magic byte and the schema id ahead of the encoded message
+    pub fn encode(
+        &self,
+        serialization: StreamSerialization,
+        schema_id: u32,
+        message: &Value,
+    ) -> Vec<u8> {
+        let mut out = vec![0];
+        out.extend(schema_id.to_be_bytes());
+        match serialization {
+            StreamSerialization::Avro => {
+                avro_encode(&SchemaType::Record(self.root.clone()), Some(message), &mut out)
+            }
+            StreamSerialization::Protobuf => {
+                // message index of the root which is always the first message in the schema
+                out.push(0);
+                protobuf_encode_record(&self.root, Some(message), &mut out);
+            }
+            StreamSerialization::Json => {
+                return serde_json::to_vec(message).expect("json value always serializes")
+            }
+        }
+        out
+    }
+}
+
+fn avro_type(type_: &SchemaType, defined: &mut HashSet<String>) -> Value {
+    match type_ {
+        SchemaType::Boolean => json!("boolean"),
+        SchemaType::String => json!("string"),
+        SchemaType::Array(items) => json!({ "type": "array", "items": avro_type(items, defined) }),
+        SchemaType::Record(record) => {
+            if !defined.insert(record.name.clone()) {
+                return json!(record.name);
+            }
+            let fields: Vec<Value> = record
+                .fields
+                .iter()
+                .map(
+                    |field| json!({ "name": field.name, "type": avro_type(&field.type_, defined) }),
+                )
+                .collect();
+            json!({ "type": "record", "name": record.name, "fields": fields })
+        }
+    }
+}
+
+fn protobuf_type(type_: &SchemaType) -> &str {
+    match type_ {
+        SchemaType::Boolean => "bool",
+        SchemaType::Record(record) => &record.name,
+        SchemaType::String | SchemaType::Array(_) => "string",
+    }
+}
+
+fn protobuf_messages(record: &SchemaRecord, out: &mut Vec<String>, defined: &mut HashSet<String>) {
+    if !defined.insert(record.name.clone()) {
+        return;
+    }
+
+    let mut lines = vec![format!("message {} {{", record.name)];
+    let mut nested = Vec::new();
+    for (index, field) in record.fields.iter().enumerate() {
+        let (label, type_) = match &field.type_ {
+            SchemaType::Array(items) => ("repeated ", items.as_ref()),
+            other => ("", other),
+        };
+        if let SchemaType::Record(nested_record) = type_ {
+            nested.push(nested_record);
+        }
+        lines.push(format!("  {}{} {} = {};", label, protobuf_type(type_), field.name, index + 1));
+    }
+    lines.push("}".to_string());
+    out.push(lines.join("\n"));
+
+    for nested_record in nested {
+        protobuf_messages(nested_record, out, defined);
+    }
+}
+
+fn value_string(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(value)) => value.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(value) => value.to_string(),
+    }
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_avro_long(value: i64, out: &mut Vec<u8>) {
+    write_varint(((value << 1) ^ (value >> 63)) as u64, out);
+}
+
+fn avro_encode(type_: &SchemaType, value: Option<&Value>, out: &mut Vec<u8>) {
+    match type_ {
+        SchemaType::Boolean => out.push(value.and_then(Value::as_bool).unwrap_or(false) as u8),
+        SchemaType::String => {
+            let value = value_string(value);
+            write_avro_long(value.len() as i64, out);
+            out.extend(value.as_bytes());
+        }
+        SchemaType::Array(items) => {
+            let values = value.and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+            if !values.is_empty() {
+                write_avro_long(values.len() as i64, out);
+                for value in values {
+                    avro_encode(items, Some(value), out);
+                }
+            }
+            write_avro_long(0, out);
+        }
+        SchemaType::Record(record) => {
+            for field in &record.fields {
+                avro_encode(&field.type_, value.and_then(|v| v.get(&field.key)), out);
+            }
+        }
+    }
+}
+
+fn protobuf_encode_record(record: &SchemaRecord, value: Option<&Value>, out: &mut Vec<u8>) {
+    for (index, field) in record.fields.iter().enumerate() {
+        let number = (index + 1) as u64;
+        let field_value = value.and_then(|v| v.get(&field.key));
+        match &field.type_ {
+            SchemaType::Array(items) => {
+                for item in field_value.and_then(Value::as_array).into_iter().flatten() {
+                    protobuf_encode_field(number, items, item, out);
+                }
+            }
+            other => {
+                if let Some(field_value) = field_value {
+                    protobuf_encode_field(number, other, field_value, out);
+                }
+            }
+        }
+    }
+}
+
+fn protobuf_encode_field(number: u64, type_: &SchemaType, value: &Value, out: &mut Vec<u8>) {
+    match type_ {
+        SchemaType::Boolean => {
+            write_varint(number << 3, out);
+            write_varint(value.as_bool().unwrap_or(false) as u64, out);
+        }
+        SchemaType::Record(record) => {
+            let mut nested = Vec::new();
+            protobuf_encode_record(record, Some(value), &mut nested);
+            write_varint((number << 3) | 2, out);
+            write_varint(nested.len() as u64, out);
+            out.extend(nested);
+        }
+        SchemaType::String | SchemaType::Array(_) => {
+            let value = value_string(Some(value));
+            write_varint((number << 3) | 2, out);
+            write_varint(value.len() as u64, out);
+            out.extend(value.as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str, type_: &str) -> ABIInput {
+        ABIInput {
+            indexed: None,
+            name: name.to_string(),
+            type_: type_.to_string(),
+            components: None,
+        }
+    }
+
+    #[test]
+    fn encodes_event_messages() {
+        let schema = StreamSchema::new("Approval", &[input("approved", "bool")]);
+        let message = json!({
+            "event_name": "Approval",
+            "event_data": [{ "approved": true }],
+            "network": "base"
+        });
+
+        let avro = schema.encode(StreamSerialization::Avro, 7, &message);
+        assert_eq!(&avro[..5], &[0, 0, 0, 0, 7]);
+        // "Approval", one event of true followed by 7 empty tx strings, end of array, "base"
+        let mut expected = vec![16];
+        expected.extend(b"Approval");
+        expected.extend([2, 1]);
+        expected.extend([0; 7]);
+        expected.extend([0, 8]);
+        expected.extend(b"base");
+        assert_eq!(&avro[5..], expected.as_slice());
+
+        let protobuf = schema.encode(StreamSerialization::Protobuf, 7, &message);
+        let mut expected = vec![0, 0x0a, 8];
+        expected.extend(b"Approval");
+        expected.extend([0x12, 2, 0x08, 1, 0x1a, 4]);
+        expected.extend(b"base");
+        assert_eq!(&protobuf[5..], expected.as_slice());
+
+        assert!(schema
+            .schema(StreamSerialization::Protobuf)
+            .contains("repeated Approval event_data = 2;"));
+    }
+
+    #[test]
+    fn encodes_hashed_topics_under_their_hash_name() {
+        let mut name = input("name", "string");
+        name.indexed = Some(true);
+        let schema = StreamSchema::new("Registered", &[name]);
+        let hash = format!("0x{}", "ab".repeat(32));
+        let message = json!({
+            "event_name": "Registered",
+            "event_data": [{ "name_hash": hash }],
+            "network": "base"
+        });
+
+        assert!(schema
+            .schema(StreamSerialization::Avro)
+            .contains(r#"{"name":"name_hash","type":"string"}"#));
+        assert!(schema.schema(StreamSerialization::Protobuf).contains("string name_hash = 1;"));
+
+        let avro = schema.encode(StreamSerialization::Avro, 7, &message);
+        let mut expected = vec![20];
+        expected.extend(b"Registered");
+        expected.extend([2, 132, 1]);
+        expected.extend(hash.as_bytes());
+        expected.extend([0; 7]);
+        expected.extend([0, 8]);
+        expected.extend(b"base");
+        assert_eq!(&avro[5..], expected.as_slice());
+
+        let protobuf = schema.encode(StreamSerialization::Protobuf, 7, &message);
+        let mut expected = vec![0, 0x0a, 10];
+        expected.extend(b"Registered");
+        expected.extend([0x12, 68, 0x0a, 66]);
+        expected.extend(hash.as_bytes());
+        expected.extend([0x1a, 4]);
+        expected.extend(b"base");
+        assert_eq!(&protobuf[5..], expected.as_slice());
+    }
+}
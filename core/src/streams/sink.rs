@@ -0,0 +1,328 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde_json::Value;
+use tracing::{debug, error, info};
+
+use crate::{
+    api::{registry_event_decoders, PushDecoder, StartEventPushServerError},
+    event::{callback_registry::EventCallbackRegistry, EventMessage},
+    manifest::core::Manifest,
+    streams::{contract_streams_clients, StreamError, StreamWatermark, StreamsClients},
+};
+
+/// An event's data split into chunks small enough to publish as one message each, a chunk never
+/// holds logs of more than one block
+pub struct StreamBatch<'a> {
+    pub id: &'a str,
+    pub event_message: &'a EventMessage,
+    pub chunks: Arc<Vec<Vec<Value>>>,
+}
+
+/// The transaction hash and log index of an event
+fn log_key(event_data: &Value) -> Option<String> {
+    let tx_information = event_data.get("transaction_information")?;
+    Some(format!(
+        "{}-{}",
+        tx_information.get("transaction_hash")?.as_str()?,
+        tx_information.get("log_index")?.as_str()?
+    ))
+}
+
+impl StreamBatch<'_> {
+    /// Keys the chunk on its first log so a log is published under the same id whichever block
+    /// range it was indexed or replayed in
+    pub fn chunk_id(&self, index: usize) -> String {
+        match self.chunks.get(index).and_then(|chunk| chunk.first()).and_then(log_key) {
+            Some(log_key) => format!("{}-{}", self.id, log_key),
+            None => format!("{}-chunk-{}", self.id, index),
+        }
+    }
+}
+
+/// Where streamed events are published to, SNS, webhooks, RabbitMQ and Kafka are built in and
+/// anything else can be plugged in with `register_stream_sink`
+#[async_trait]
+pub trait StreamSink: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Returns how many events were published
+    async fn publish_batch(&self, batch: &StreamBatch<'_>) -> Result<usize, StreamError>;
+
+    /// Only called once every event up to the watermark block has been published to the sink
+    async fn publish_watermark(&self, _watermark: &StreamWatermark) -> Result<(), StreamError> {
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), StreamError> {
+        Ok(())
+    }
+
+    async fn health(&self) -> Result<(), StreamError> {
+        Ok(())
+    }
+}
+
+fn stream_sinks() -> &'static Mutex<Vec<Arc<dyn StreamSink>>> {
+    static STREAM_SINKS: OnceLock<Mutex<Vec<Arc<dyn StreamSink>>>> = OnceLock::new();
+    STREAM_SINKS.get_or_init(Default::default)
+}
+
+/// Publishes every indexed event to the sink alongside the streams in the manifest, register it
+/// before starting rindexer
+pub fn register_stream_sink(sink: Arc<dyn StreamSink>) {
+    stream_sinks().lock().unwrap().push(sink);
+}
+
+pub fn registered_stream_sinks() -> Vec<Arc<dyn StreamSink>> {
+    stream_sinks().lock().unwrap().clone()
+}
+
+/// Logs any registered sink which is not healthy, indexing still starts so it can recover
+pub async fn check_registered_stream_sinks() {
+    let sinks = registered_stream_sinks();
+    let results = join_all(sinks.iter().map(|sink| sink.health())).await;
+    for (sink, result) in sinks.iter().zip(results) {
+        match result {
+            Ok(()) => info!("{} stream sink is healthy", sink.name()),
+            Err(e) => error!("{} stream sink is not healthy: {}", sink.name(), e),
+        }
+    }
+}
+
+pub async fn flush_registered_stream_sinks() {
+    for sink in registered_stream_sinks() {
+        if let Err(e) = sink.flush().await {
+            error!("Could not flush {} stream sink: {}", sink.name(), e);
+        }
+    }
+}
+
+/// Streams every event of a rust project to the registered sinks once its handler has succeeded,
+/// no-code projects stream as they write
+pub(crate) fn stream_registry_events(
+    project_path: &Path,
+    manifest: &Manifest,
+    registry: &mut EventCallbackRegistry,
+) -> Result<(), StartEventPushServerError> {
+    let Some(streams_clients) = StreamsClients::registered() else {
+        return Ok(());
+    };
+    let decoders = registry_event_decoders(project_path, manifest)?;
+    stream_registry_events_to(registry, &decoders, Arc::new(streams_clients));
+
+    Ok(())
+}
+
+fn stream_registry_events_to(
+    registry: &mut EventCallbackRegistry,
+    decoders: &HashMap<(String, String), Arc<PushDecoder>>,
+    streams_clients: Arc<StreamsClients>,
+) {
+    for event in registry.events.iter_mut() {
+        let Some(decoder) = decoders
+            .get(&(event.contract.name.clone(), event.event_name.clone()))
+            .filter(|decoder| decoder.decodes(event))
+            .map(Arc::clone)
+        else {
+            debug!("{} is not streamed as its ABI event was not found", event.info_log_name());
+            continue;
+        };
+
+        contract_streams_clients().register(&event.contract.name, &streams_clients);
+        let callback = Arc::clone(&event.callback);
+        let streams_clients = Arc::clone(&streams_clients);
+        let contract_name = event.contract.name.clone();
+        let event_name = event.event_name.clone();
+        let index_event_in_order = event.index_event_in_order;
+        event.callback = Arc::new(move |results| {
+            // a batch is always fetched from a single network
+            let network = results.first().map(|result| result.tx_information.network.clone());
+            let event_data: Vec<Value> = decoder
+                .pushed_events(&contract_name, &event_name, &results)
+                .into_iter()
+                .map(|pushed_event| pushed_event.data)
+                .collect();
+            let callback = Arc::clone(&callback);
+            let streams_clients = Arc::clone(&streams_clients);
+            let stream_id = format!(
+                "{}-{}-{}",
+                contract_name,
+                event_name,
+                network.as_deref().unwrap_or_default()
+            );
+            let event_name = event_name.clone();
+            Box::pin(async move {
+                callback(results).await?;
+                let Some(network) = network.filter(|_| !event_data.is_empty()) else {
+                    return Ok(());
+                };
+
+                let event_message = EventMessage {
+                    event_name,
+                    event_data: Value::Array(event_data),
+                    network,
+                    removed: false,
+                };
+                streams_clients
+                    .stream(stream_id, &event_message, index_event_in_order)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                streams_clients.flush().await.map_err(|e| e.to_string())
+            })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::{
+        abi::{encode, Token},
+        types::{Address, Log, H256, U256},
+    };
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::event::{
+        callback_registry::{
+            EventCallbackRegistryInformation, EventResult, LogFoundInRequest, TxInformation,
+        },
+        contract_setup::ContractInformation,
+    };
+
+    #[derive(Default)]
+    struct CollectingSink {
+        batches: Mutex<Vec<Value>>,
+    }
+
+    #[async_trait]
+    impl StreamSink for CollectingSink {
+        fn name(&self) -> &str {
+            "collecting"
+        }
+
+        async fn publish_batch(&self, batch: &StreamBatch<'_>) -> Result<usize, StreamError> {
+            self.batches.lock().unwrap().push(batch.event_message.event_data.clone());
+            Ok(batch.chunks.iter().map(|chunk| chunk.len()).sum())
+        }
+    }
+
+    fn manifest(project_path: &Path) -> Manifest {
+        std::fs::create_dir_all(project_path.join("abis")).unwrap();
+        std::fs::write(
+            project_path.join("abis/Token.json"),
+            r#"[{"type":"event","name":"Transfer","anonymous":false,"inputs":[
+                {"name":"from","type":"address","indexed":true},
+                {"name":"to","type":"address","indexed":true},
+                {"name":"value","type":"uint256","indexed":false}]}]"#,
+        )
+        .unwrap();
+
+        serde_yaml::from_str(
+            r#"
+name: Tokens
+project_type: rust
+networks: []
+storage:
+  postgres:
+    enabled: false
+contracts:
+  - name: Token
+    abi: ./abis/Token.json
+    details:
+      - network: ethereum
+        address: "0x0000000000000000000000000000000000000001"
+"#,
+        )
+        .unwrap()
+    }
+
+    fn transfer(topic_id: H256) -> EventResult {
+        let log = Log {
+            address: Address::from_low_u64_be(1),
+            topics: vec![
+                topic_id,
+                H256::from(Address::from_low_u64_be(2)),
+                H256::from(Address::from_low_u64_be(3)),
+            ],
+            data: encode(&[Token::Uint(U256::from(42))]).into(),
+            block_number: Some(10.into()),
+            block_hash: Some(H256::zero()),
+            transaction_hash: Some(H256::zero()),
+            transaction_index: Some(0.into()),
+            log_index: Some(U256::zero()),
+            ..Default::default()
+        };
+        EventResult {
+            decoded_data: Arc::new(()),
+            tx_information: TxInformation {
+                network: "ethereum".to_string(),
+                address: log.address,
+                block_hash: H256::zero(),
+                block_number: 10.into(),
+                transaction_hash: H256::zero(),
+                log_index: U256::zero(),
+                transaction_index: 0.into(),
+                removed: false,
+                after_code_removed: false,
+            },
+            found_in_request: LogFoundInRequest { from_block: 10.into(), to_block: 10.into() },
+            log,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_events_are_streamed_after_the_handler_succeeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let decoders = registry_event_decoders(dir.path(), &manifest(dir.path())).unwrap();
+        let topic_id = H256::from(ethers::utils::keccak256("Transfer(address,address,uint256)"));
+
+        let mut registry = EventCallbackRegistry::new();
+        for (event_name, fails) in [("Transfer", false), ("Transfer", true)] {
+            registry.register_event(EventCallbackRegistryInformation {
+                id: event_name.to_string(),
+                indexer_name: "Tokens".to_string(),
+                topic_id,
+                event_name: event_name.to_string(),
+                index_event_in_order: false,
+                contract: ContractInformation {
+                    name: "Token".to_string(),
+                    details: vec![],
+                    abi: "./abis/Token.json".to_string(),
+                    reorg_safe_distance: false,
+                    checkpoint: None,
+                },
+                callback: Arc::new(move |_| {
+                    async move {
+                        if fails {
+                            Err("handler failed".to_string())
+                        } else {
+                            Ok(())
+                        }
+                    }
+                    .boxed()
+                }),
+            });
+        }
+
+        let sink = Arc::new(CollectingSink::default());
+        let streams_clients = StreamsClients::from_sinks(vec![sink.clone()]).unwrap();
+        stream_registry_events_to(&mut registry, &decoders, Arc::new(streams_clients));
+        // watermarks of the contract are published through the same clients
+        assert!(contract_streams_clients().get("Token").is_some());
+
+        (registry.events[0].callback)(vec![transfer(topic_id)]).await.unwrap();
+        let batches = sink.batches.lock().unwrap().clone();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0][0]["value"], "42");
+
+        // a failed handler is retried so nothing is streamed for it
+        assert!((registry.events[1].callback)(vec![transfer(topic_id)]).await.is_err());
+        assert_eq!(sink.batches.lock().unwrap().len(), 1);
+    }
+}
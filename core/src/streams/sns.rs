@@ -1,3 +1,5 @@
+#![allow(clippy::upper_case_acronyms)]
+
 use aws_config::{meta::region::RegionProviderChain, BehaviorVersion, Region};
 use aws_sdk_sns::{
     config::{http::HttpResponse, Credentials},
@@ -0,0 +1,475 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::{
+    event::EventMessage,
+    streams::{
+        sink::{StreamBatch, StreamSink},
+        StreamError, StreamWatermark,
+    },
+};
+
+const SPILL_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+struct SpilledBatch {
+    id: String,
+    event_message: EventMessage,
+    chunks: Vec<Vec<Value>>,
+}
+
+#[derive(Default)]
+struct SpillState {
+    next_sequence: u64,
+    size_bytes: u64,
+    // sequence and size of every file on disk, oldest first
+    files: VecDeque<(u64, u64)>,
+}
+
+/// Batches waiting on disk for a single sink, one JSON file each named by its sequence so they
+/// are delivered in the order they were spilled, also after a restart
+struct SpillQueue {
+    dir: PathBuf,
+    max_size_bytes: u64,
+    state: Mutex<SpillState>,
+}
+
+impl SpillQueue {
+    fn open(dir: PathBuf, max_size_bytes: u64) -> Result<Self, std::io::Error> {
+        fs::create_dir_all(&dir)?;
+
+        let mut files = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            // left behind by a crash while spilling, the publish it belonged to failed
+            if entry.path().extension().is_some_and(|extension| extension == "tmp") {
+                fs::remove_file(entry.path())?;
+                continue;
+            }
+            let Some(sequence) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|sequence| sequence.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            files.push((sequence, entry.metadata()?.len()));
+        }
+        files.sort_unstable();
+
+        let state = SpillState {
+            next_sequence: files.last().map_or(0, |(sequence, _)| sequence + 1),
+            size_bytes: files.iter().map(|(_, size)| size).sum(),
+            files: files.into(),
+        };
+        Ok(Self { dir, max_size_bytes, state: Mutex::new(state) })
+    }
+
+    fn file_path(&self, sequence: u64) -> PathBuf {
+        self.dir.join(format!("{:020}.json", sequence))
+    }
+
+    async fn len(&self) -> usize {
+        self.state.lock().await.files.len()
+    }
+
+    /// Spills the chunks of the batch which are yet to be published, all of them when `None`
+    async fn push(
+        &self,
+        batch: &StreamBatch<'_>,
+        failed_chunks: Option<&[usize]>,
+    ) -> Result<(), StreamError> {
+        let spilled = SpilledBatch {
+            id: batch.id.to_string(),
+            event_message: batch.event_message.clone(),
+            chunks: unpublished_chunks(&batch.chunks, failed_chunks),
+        };
+        let bytes = serde_json::to_vec(&spilled).map_err(|e| StreamError::Spill(e.to_string()))?;
+
+        let mut state = self.state.lock().await;
+        if state.size_bytes + bytes.len() as u64 > self.max_size_bytes {
+            return Err(StreamError::Spill(format!(
+                "{} is full with {} undelivered batches",
+                self.dir.display(),
+                state.files.len()
+            )));
+        }
+
+        // written to a temporary name first so a crash never leaves half a batch to deliver
+        let sequence = state.next_sequence;
+        let temporary_path = self.dir.join(format!("{:020}.tmp", sequence));
+        tokio::fs::write(&temporary_path, &bytes)
+            .await
+            .map_err(|e| StreamError::Spill(e.to_string()))?;
+        tokio::fs::rename(&temporary_path, self.file_path(sequence))
+            .await
+            .map_err(|e| StreamError::Spill(e.to_string()))?;
+
+        state.next_sequence += 1;
+        state.size_bytes += bytes.len() as u64;
+        state.files.push_back((sequence, bytes.len() as u64));
+        Ok(())
+    }
+
+    async fn peek(&self) -> Result<Option<(u64, SpilledBatch)>, StreamError> {
+        let Some((sequence, _)) = self.state.lock().await.files.front().copied() else {
+            return Ok(None);
+        };
+
+        let bytes = match tokio::fs::read(self.file_path(sequence)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                warn!("Spilled stream batch {} was removed from disk, skipping it", sequence);
+                self.forget(sequence).await;
+                return Ok(None);
+            }
+            Err(e) => return Err(StreamError::Spill(e.to_string())),
+        };
+        let spilled =
+            serde_json::from_slice(&bytes).map_err(|e| StreamError::Spill(e.to_string()))?;
+        Ok(Some((sequence, spilled)))
+    }
+
+    /// Keeps only the chunks of a spilled batch which failed to deliver again
+    async fn retain(
+        &self,
+        sequence: u64,
+        spilled: &SpilledBatch,
+        failed_chunks: &[usize],
+    ) -> Result<(), StreamError> {
+        let retained = SpilledBatch {
+            id: spilled.id.clone(),
+            event_message: spilled.event_message.clone(),
+            chunks: unpublished_chunks(&spilled.chunks, Some(failed_chunks)),
+        };
+        let bytes = serde_json::to_vec(&retained).map_err(|e| StreamError::Spill(e.to_string()))?;
+
+        let mut state = self.state.lock().await;
+        let temporary_path = self.dir.join(format!("{:020}.tmp", sequence));
+        tokio::fs::write(&temporary_path, &bytes)
+            .await
+            .map_err(|e| StreamError::Spill(e.to_string()))?;
+        tokio::fs::rename(&temporary_path, self.file_path(sequence))
+            .await
+            .map_err(|e| StreamError::Spill(e.to_string()))?;
+
+        if let Some((_, size)) = state.files.iter_mut().find(|(s, _)| *s == sequence) {
+            let previous_size = std::mem::replace(size, bytes.len() as u64);
+            state.size_bytes = state.size_bytes - previous_size + bytes.len() as u64;
+        }
+        Ok(())
+    }
+
+    async fn pop(&self, sequence: u64) -> Result<(), StreamError> {
+        tokio::fs::remove_file(self.file_path(sequence))
+            .await
+            .map_err(|e| StreamError::Spill(e.to_string()))?;
+        self.forget(sequence).await;
+        Ok(())
+    }
+
+    async fn forget(&self, sequence: u64) {
+        let mut state = self.state.lock().await;
+        if let Some(position) = state.files.iter().position(|(s, _)| *s == sequence) {
+            if let Some((_, size)) = state.files.remove(position) {
+                state.size_bytes -= size;
+            }
+        }
+    }
+}
+
+/// The chunks a sink failed to publish, every chunk when it did not say which
+fn unpublished_chunks(chunks: &[Vec<Value>], failed_chunks: Option<&[usize]>) -> Vec<Vec<Value>> {
+    match failed_chunks {
+        Some(failed_chunks) => {
+            failed_chunks.iter().filter_map(|index| chunks.get(*index)).cloned().collect()
+        }
+        None => chunks.to_vec(),
+    }
+}
+
+/// Which chunks of a failed publish are yet to be published, `None` when all of them are
+fn failed_chunks(error: &StreamError) -> Option<&[usize]> {
+    match error {
+        StreamError::ChunksNotPublished { failed_chunks, .. } => Some(failed_chunks),
+        _ => None,
+    }
+}
+
+/// Wraps a sink so failed publishes are spilled to disk instead of failing the event, anything
+/// published while older batches are still spilled joins the queue to keep the order
+pub struct SpillingStreamSink {
+    inner: Arc<dyn StreamSink>,
+    queue: SpillQueue,
+    /// Held while publishing so a publish can not overtake a spilled batch being delivered or a
+    /// concurrent publish deciding whether to spill
+    publishing: Mutex<()>,
+}
+
+impl SpillingStreamSink {
+    /// Opens the queue in `dir` and starts delivering whatever is already spilled there
+    pub fn start(
+        inner: Arc<dyn StreamSink>,
+        dir: PathBuf,
+        max_size_bytes: u64,
+    ) -> Result<Arc<Self>, std::io::Error> {
+        let sink = Arc::new(Self {
+            inner,
+            queue: SpillQueue::open(dir, max_size_bytes)?,
+            publishing: Mutex::new(()),
+        });
+
+        let delivering = Arc::clone(&sink);
+        tokio::spawn(async move { delivering.deliver_spilled().await });
+
+        Ok(sink)
+    }
+
+    async fn deliver_spilled(&self) {
+        loop {
+            let publishing = self.publishing.lock().await;
+            let (sequence, spilled) = match self.queue.peek().await {
+                Ok(Some(next)) => next,
+                Ok(None) => {
+                    drop(publishing);
+                    tokio::time::sleep(SPILL_RETRY_INTERVAL).await;
+                    continue;
+                }
+                Err(e) => {
+                    drop(publishing);
+                    error!("Could not read spilled {} stream batch: {}", self.inner.name(), e);
+                    tokio::time::sleep(SPILL_RETRY_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            let batch = StreamBatch {
+                id: &spilled.id,
+                event_message: &spilled.event_message,
+                chunks: Arc::new(spilled.chunks.clone()),
+            };
+            let delivered = match self.inner.publish_batch(&batch).await {
+                Ok(_) => match self.queue.pop(sequence).await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        error!(
+                            "Could not remove delivered {} stream batch: {}",
+                            self.inner.name(),
+                            e
+                        );
+                        false
+                    }
+                },
+                Err(e) => {
+                    // the chunks which made it are not delivered twice
+                    if let Some(failed_chunks) = failed_chunks(&e) {
+                        if let Err(e) = self.queue.retain(sequence, &spilled, failed_chunks).await {
+                            error!(
+                                "Could not update spilled {} stream batch: {}",
+                                self.inner.name(),
+                                e
+                            );
+                        }
+                    }
+                    warn!(
+                        "{} stream still failing with {} batches spilled: {}",
+                        self.inner.name(),
+                        self.queue.len().await,
+                        e
+                    );
+                    false
+                }
+            };
+            let recovered = delivered && self.queue.len().await == 0;
+            drop(publishing);
+
+            if recovered {
+                info!("{} stream recovered - delivered every spilled batch", self.inner.name());
+            }
+            if !delivered {
+                tokio::time::sleep(SPILL_RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StreamSink for SpillingStreamSink {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn publish_batch(&self, batch: &StreamBatch<'_>) -> Result<usize, StreamError> {
+        let _publishing = self.publishing.lock().await;
+        if self.queue.len().await > 0 {
+            self.queue.push(batch, None).await?;
+            return Ok(0);
+        }
+
+        match self.inner.publish_batch(batch).await {
+            Ok(published) => Ok(published),
+            Err(e) => {
+                warn!("{} stream failed, spilling to disk: {}", self.inner.name(), e);
+                self.queue.push(batch, failed_chunks(&e)).await?;
+                Ok(0)
+            }
+        }
+    }
+
+    async fn publish_watermark(&self, watermark: &StreamWatermark) -> Result<(), StreamError> {
+        self.inner.publish_watermark(watermark).await
+    }
+
+    async fn flush(&self) -> Result<(), StreamError> {
+        self.inner.flush().await
+    }
+
+    async fn health(&self) -> Result<(), StreamError> {
+        self.inner.health().await
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// One directory per sink of an event so each is delivered on its own
+pub fn spill_dir(root: &Path, contract_name: &str, event_name: &str, sink_name: &str) -> PathBuf {
+    root.join(sanitize(contract_name)).join(sanitize(event_name)).join(sanitize(sink_name))
+}
+
+/// Whether any sink of the contract still has batches spilled under `root` which are yet to be
+/// delivered
+pub fn has_spilled_batches(root: &Path, contract_name: &str) -> bool {
+    let Ok(events) = fs::read_dir(root.join(sanitize(contract_name))) else {
+        return false;
+    };
+
+    events
+        .flatten()
+        .flat_map(|event| fs::read_dir(event.path()).into_iter().flatten().flatten())
+        .any(|sink| {
+            fs::read_dir(sink.path())
+                .into_iter()
+                .flatten()
+                .flatten()
+                .any(|file| file.path().extension().is_some_and(|extension| extension == "json"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn batch_message() -> EventMessage {
+        EventMessage {
+            event_name: "Transfer".to_string(),
+            event_data: json!([{ "value": "1" }]),
+            network: "ethereum".to_string(),
+            removed: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spill_queue_keeps_order_across_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let event_message = batch_message();
+        let queue = SpillQueue::open(dir.path().to_path_buf(), 1024 * 1024).unwrap();
+        for id in ["first", "second"] {
+            let batch = StreamBatch {
+                id,
+                event_message: &event_message,
+                chunks: Arc::new(vec![vec![json!({ "value": "1" })]]),
+            };
+            queue.push(&batch, None).await.unwrap();
+        }
+
+        let reopened = SpillQueue::open(dir.path().to_path_buf(), 1024 * 1024).unwrap();
+        assert_eq!(reopened.len().await, 2);
+        let (sequence, spilled) = reopened.peek().await.unwrap().unwrap();
+        assert_eq!(spilled.id, "first");
+        reopened.pop(sequence).await.unwrap();
+        assert_eq!(reopened.peek().await.unwrap().unwrap().1.id, "second");
+    }
+
+    #[tokio::test]
+    async fn test_spill_queue_rejects_batches_once_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let event_message = batch_message();
+        let queue = SpillQueue::open(dir.path().to_path_buf(), 10).unwrap();
+        let batch = StreamBatch {
+            id: "first",
+            event_message: &event_message,
+            chunks: Arc::new(vec![vec![json!({ "value": "1" })]]),
+        };
+
+        assert!(matches!(queue.push(&batch, None).await, Err(StreamError::Spill(_))));
+        assert_eq!(queue.len().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_spill_queue_keeps_only_failed_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let event_message = batch_message();
+        let queue = SpillQueue::open(dir.path().to_path_buf(), 1024 * 1024).unwrap();
+        let batch = StreamBatch {
+            id: "first",
+            event_message: &event_message,
+            chunks: Arc::new(vec![
+                vec![json!({ "value": "1" })],
+                vec![json!({ "value": "2" })],
+                vec![json!({ "value": "3" })],
+            ]),
+        };
+        queue.push(&batch, Some(&[0, 2])).await.unwrap();
+
+        let (sequence, spilled) = queue.peek().await.unwrap().unwrap();
+        assert_eq!(
+            spilled.chunks,
+            vec![vec![json!({ "value": "1" })], vec![json!({ "value": "3" })]]
+        );
+
+        queue.retain(sequence, &spilled, &[1]).await.unwrap();
+        let (_, spilled) = queue.peek().await.unwrap().unwrap();
+        assert_eq!(spilled.chunks, vec![vec![json!({ "value": "3" })]]);
+        assert_eq!(
+            queue.state.lock().await.size_bytes,
+            fs::metadata(queue.file_path(sequence)).unwrap().len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_has_spilled_batches() {
+        let root = tempfile::tempdir().unwrap();
+        let event_message = batch_message();
+        let queue =
+            SpillQueue::open(spill_dir(root.path(), "Erc20", "Transfer", "kafka"), 1024 * 1024)
+                .unwrap();
+        assert!(!has_spilled_batches(root.path(), "Erc20"));
+
+        let batch = StreamBatch {
+            id: "first",
+            event_message: &event_message,
+            chunks: Arc::new(vec![vec![json!({ "value": "1" })]]),
+        };
+        queue.push(&batch, None).await.unwrap();
+        assert!(has_spilled_batches(root.path(), "Erc20"));
+        assert!(!has_spilled_batches(root.path(), "Other"));
+    }
+}
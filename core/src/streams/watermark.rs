@@ -0,0 +1,177 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use ethers::types::U64;
+use serde::Serialize;
+
+use crate::streams::StreamsClients;
+
+/// Tells consumers every event of the contract on the network up to and including the block has
+/// been published, published as `{"type": "watermark", ...}` next to the event messages
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreamWatermark {
+    #[serde(rename = "type")]
+    pub message_type: &'static str,
+    pub contract_name: String,
+    pub network: String,
+    pub block_number: u64,
+    /// Unix seconds of the block, `None` when the provider could not return its header
+    pub block_timestamp: Option<u64>,
+}
+
+impl StreamWatermark {
+    pub fn new(
+        contract_name: &str,
+        network: &str,
+        block_number: u64,
+        block_timestamp: Option<u64>,
+    ) -> Self {
+        Self {
+            message_type: "watermark",
+            contract_name: contract_name.to_string(),
+            network: network.to_string(),
+            block_number,
+            block_timestamp,
+        }
+    }
+
+    pub fn id(&self) -> String {
+        format!(
+            "rindexer_watermark__{}-{}-{}",
+            self.contract_name.to_lowercase(),
+            self.network,
+            self.block_number
+        )
+    }
+}
+
+#[derive(Default)]
+struct PublishedRanges {
+    // from block of every range still being handled, with how many times it is in flight
+    in_flight: BTreeMap<U64, usize>,
+    highest: Option<U64>,
+}
+
+impl PublishedRanges {
+    fn published_up_to(&self) -> Option<U64> {
+        let highest = self.highest?;
+        match self.in_flight.keys().next() {
+            Some(from_block) => Some(highest.min(from_block.checked_sub(U64::one())?)),
+            None => Some(highest),
+        }
+    }
+}
+
+/// Block ranges of every network contract event which are handed to the event callbacks, events
+/// not indexed in order finish out of order so only what is below every range still in flight
+/// counts as published
+#[derive(Default)]
+pub struct PublishedBlocks {
+    ranges: Mutex<HashMap<String, PublishedRanges>>,
+}
+
+impl PublishedBlocks {
+    pub fn start(&self, id: &str, from_block: U64) {
+        let mut ranges = self.ranges.lock().unwrap();
+        *ranges.entry(id.to_string()).or_default().in_flight.entry(from_block).or_default() += 1;
+    }
+
+    pub fn finish(&self, id: &str, from_block: U64, to_block: U64) {
+        let mut ranges = self.ranges.lock().unwrap();
+        let range = ranges.entry(id.to_string()).or_default();
+        if let Some(count) = range.in_flight.get_mut(&from_block) {
+            *count -= 1;
+            if *count == 0 {
+                range.in_flight.remove(&from_block);
+            }
+        }
+        range.highest = range.highest.max(Some(to_block));
+    }
+
+    /// A range without any logs is published as soon as it is fetched
+    pub fn skip(&self, id: &str, to_block: U64) {
+        let mut ranges = self.ranges.lock().unwrap();
+        let range = ranges.entry(id.to_string()).or_default();
+        range.highest = range.highest.max(Some(to_block));
+    }
+
+    pub fn published_up_to(&self, id: &str) -> Option<U64> {
+        self.ranges.lock().unwrap().get(id).and_then(|range| range.published_up_to())
+    }
+}
+
+pub fn published_blocks() -> &'static PublishedBlocks {
+    static PUBLISHED_BLOCKS: OnceLock<PublishedBlocks> = OnceLock::new();
+    PUBLISHED_BLOCKS.get_or_init(Default::default)
+}
+
+/// The stream clients each contract's events are published through, watermarks go out through the
+/// same clients rather than opening a second set of connections
+#[derive(Default)]
+pub struct ContractStreamsClients {
+    clients: Mutex<HashMap<String, Arc<StreamsClients>>>,
+}
+
+impl ContractStreamsClients {
+    /// Keeps the first clients registered for the contract, watermarks only go to topics taking
+    /// any message so the clients of any of its events will do
+    pub fn register(&self, contract_name: &str, clients: &Arc<StreamsClients>) {
+        self.clients
+            .lock()
+            .unwrap()
+            .entry(contract_name.to_string())
+            .or_insert_with(|| Arc::clone(clients));
+    }
+
+    pub fn get(&self, contract_name: &str) -> Option<Arc<StreamsClients>> {
+        self.clients.lock().unwrap().get(contract_name).map(Arc::clone)
+    }
+}
+
+pub fn contract_streams_clients() -> &'static ContractStreamsClients {
+    static CONTRACT_STREAMS_CLIENTS: OnceLock<ContractStreamsClients> = OnceLock::new();
+    CONTRACT_STREAMS_CLIENTS.get_or_init(Default::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_published_up_to_waits_for_ranges_in_flight() {
+        let published = PublishedBlocks::default();
+        assert_eq!(published.published_up_to("a"), None);
+
+        published.start("a", U64::from(100));
+        published.start("a", U64::from(111));
+        published.finish("a", U64::from(111), U64::from(120));
+        published.skip("a", U64::from(130));
+        assert_eq!(published.published_up_to("a"), Some(U64::from(99)));
+
+        published.finish("a", U64::from(100), U64::from(110));
+        assert_eq!(published.published_up_to("a"), Some(U64::from(130)));
+
+        published.start("a", U64::from(131));
+        published.skip("a", U64::from(140));
+        assert_eq!(published.published_up_to("a"), Some(U64::from(130)));
+    }
+
+    #[test]
+    fn test_watermark_message() {
+        let watermark = StreamWatermark::new("Erc20", "ethereum", 100, Some(1700000000));
+        assert_eq!(
+            serde_json::to_value(&watermark).unwrap(),
+            json!({
+                "type": "watermark",
+                "contract_name": "Erc20",
+                "network": "ethereum",
+                "block_number": 100,
+                "block_timestamp": 1700000000
+            })
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::{fmt, path::Path};
+
+#[cfg(feature = "rabbitmq")]
+use crate::streams::{RabbitMQ, RabbitMQError};
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresConnectionError, PostgresError},
+        internal_schema::{internal_table, SHARED_INTERNAL_TABLES},
+    },
+    drop_tables_for_indexer_sql,
+    helpers::camel_to_snake,
+    manifest::core::Manifest,
+};
+
+/// Internal tables holding the drop statements of what the indexer created on top of the event
+/// tables, views go first as they depend on the columns the others touch
+const LAST_KNOWN_DROPPING_SQL_TABLES: [&str; 3] = [
+    "last_known_relationship_views_dropping_sql",
+    "last_known_relationship_dropping_sql",
+    "last_known_indexes_dropping_sql",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum TeardownError {
+    #[error("Could not connect to Postgres: {0}")]
+    PostgresConnection(#[from] PostgresConnectionError),
+
+    #[error("{0}")]
+    Postgres(#[from] PostgresError),
+
+    #[error("Could not parse the last known dropping sql: {0}")]
+    CouldNotParseDroppingSql(#[from] serde_json::Error),
+
+    #[cfg(feature = "rabbitmq")]
+    #[error("Could not delete RabbitMQ exchange {0}: {1}")]
+    CouldNotDeleteExchange(String, RabbitMQError),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TeardownOptions {
+    /// Also delete the RabbitMQ exchanges rindexer declared for the contract streams
+    pub delete_streams: bool,
+}
+
+/// Everything `rindexer down` removes, built without changing anything so it can be previewed
+#[derive(Debug, Default)]
+pub struct TeardownPlan {
+    pub postgres_sql: Vec<String>,
+    /// RabbitMQ url and exchange name
+    pub rabbitmq_exchanges: Vec<(String, String)>,
+}
+
+impl TeardownPlan {
+    pub fn is_empty(&self) -> bool {
+        self.postgres_sql.is_empty() && self.rabbitmq_exchanges.is_empty()
+    }
+}
+
+impl fmt::Display for TeardownPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.postgres_sql.is_empty() {
+            writeln!(f, "Postgres:")?;
+            for sql in &self.postgres_sql {
+                writeln!(f, "  {}", sql)?;
+            }
+        }
+
+        if !self.rabbitmq_exchanges.is_empty() {
+            writeln!(f, "RabbitMQ exchanges:")?;
+            for (_, exchange) in &self.rabbitmq_exchanges {
+                writeln!(f, "  {}", exchange)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn last_known_dropping_sql(
+    client: &PostgresClient,
+    table_name: &str,
+) -> Result<Vec<String>, TeardownError> {
+    let exists = client
+        .query_one_or_none("SELECT 1 WHERE to_regclass($1) IS NOT NULL", &[&table_name])
+        .await?
+        .is_some();
+    if !exists {
+        return Ok(vec![]);
+    }
+
+    let Some(row) = client
+        .query_one_or_none(&format!("SELECT value FROM {} WHERE key = 1", table_name), &[])
+        .await?
+    else {
+        return Ok(vec![]);
+    };
+
+    let value: &str = row.get(0);
+    Ok(serde_json::from_str(value)?)
+}
+
+async fn postgres_teardown_sql(
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<Vec<String>, TeardownError> {
+    let client = PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+    let indexer_name = camel_to_snake(&manifest.name);
+
+    let mut sql = vec![];
+    for table in LAST_KNOWN_DROPPING_SQL_TABLES {
+        sql.extend(
+            last_known_dropping_sql(
+                &client,
+                &internal_table(&format!("{}_{}", indexer_name, table)),
+            )
+            .await?,
+        );
+    }
+
+    sql.extend(
+        drop_tables_for_indexer_sql(
+            project_path,
+            &manifest.to_indexer(),
+            manifest.storage.postgres_layout(),
+        )
+        .as_str()
+        .split_inclusive(';')
+        .map(|statement| statement.trim().to_string())
+        .filter(|statement| !statement.is_empty()),
+    );
+
+    // these tables are shared by every indexer on the database
+    for table in SHARED_INTERNAL_TABLES {
+        let table = internal_table(table);
+        sql.push(format!(
+            "DO $$ BEGIN IF to_regclass('{table}') IS NOT NULL THEN DELETE FROM {table} WHERE indexer_name = '{}'; END IF; END $$;",
+            manifest.name.replace('\'', "''")
+        ));
+    }
+
+    Ok(sql)
+}
+
+/// Reads what the project created, nothing is dropped until `run_teardown`
+pub async fn plan_teardown(
+    project_path: &Path,
+    manifest: &Manifest,
+    options: &TeardownOptions,
+) -> Result<TeardownPlan, TeardownError> {
+    let mut plan = TeardownPlan::default();
+
+    if manifest.storage.postgres_enabled() {
+        plan.postgres_sql = postgres_teardown_sql(project_path, manifest).await?;
+    }
+
+    // kafka and sns topics are created outside rindexer so they are always left alone
+    if options.delete_streams {
+        for contract in &manifest.contracts {
+            let Some(rabbitmq) = contract.streams.as_ref().and_then(|s| s.rabbitmq.as_ref()) else {
+                continue;
+            };
+            for exchange in &rabbitmq.exchanges {
+                let entry = (rabbitmq.url.clone(), exchange.exchange.clone());
+                if !plan.rabbitmq_exchanges.contains(&entry) {
+                    plan.rabbitmq_exchanges.push(entry);
+                }
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+pub async fn run_teardown(manifest: &Manifest, plan: &TeardownPlan) -> Result<(), TeardownError> {
+    if !plan.postgres_sql.is_empty() {
+        let client = PostgresClient::from_storage(&manifest.storage, &manifest.name).await?;
+        for sql in &plan.postgres_sql {
+            client.execute_confirmed_ddl(sql, "tear down with rindexer down").await?;
+        }
+    }
+
+    #[cfg(feature = "rabbitmq")]
+    for (url, exchange) in &plan.rabbitmq_exchanges {
+        RabbitMQ::new(url)
+            .await
+            .delete_exchange(exchange)
+            .await
+            .map_err(|e| TeardownError::CouldNotDeleteExchange(exchange.clone(), e))?;
+    }
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::unnecessary_map_or)]
+
 use std::{
     any::Any,
     error::Error,
@@ -131,7 +133,9 @@ where
             callback: componentvalueset_handler(closure),
             context: Arc::new(EventContext {
                 database: Arc::new(
-                    PostgresClient::new().await.expect("Failed to connect to Postgres"),
+                    PostgresClient::new_from_env("DATABASE_URL")
+                        .await
+                        .expect("Failed to connect to Postgres"),
                 ),
                 csv: Arc::new(csv),
                 extensions: Arc::new(extensions),
@@ -293,6 +297,7 @@ where
                 .collect(),
             abi: contract_details.abi,
             reorg_safe_distance: contract_details.reorg_safe_distance.unwrap_or_default(),
+            checkpoint: contract_details.checkpoint,
         };
 
         let callback: Arc<